@@ -3,26 +3,34 @@
 use anyhow::{Context, Result};
 use clap::Args;
 use nitrogen_core::{
+    capture::{grid_layout, LayoutLayer},
     config::{
-        discord, AudioCodec, AudioSource, Av1Config, Av1Tier, Av1Tune, CaptureConfig,
-        ChromaFormat, Codec, ConfigFile, EncoderPreset, MultipassMode, Preset,
+        discord, parse_bitrate, parse_duration, AudioSource, Av1Tune, ChromaFormat, Codec,
+        ColorRange, ConfigFile, CursorMode, OutputAudioSettings, Preset, ReloadPlan, Tune,
     },
     daemon_running,
+    encode::{BarColor, FitMode, RateControl},
     gpu::detect_rtx50_features,
     ipc::IpcServer,
-    overlay::OverlayPosition,
+    output::{resolve_record_path, PathTemplateVars},
     pipeline::Pipeline,
     socket_path,
     types::CaptureSource,
-    TonemapAlgorithm, TonemapMode,
+    TonemapMode,
 };
+#[cfg(feature = "streaming")]
+use nitrogen_core::output::{RistOptions, SrtOptions, StreamOutput};
 use std::sync::Arc;
-use tokio::signal;
+use std::time::Duration;
+use tokio::signal::{self, unix::SignalKind};
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
+/// How long a graceful stop may take before the daemon force-exits
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Arguments for the cast command
-#[derive(Args)]
+#[derive(Args, Clone)]
 pub struct CastArgs {
     /// Monitor ID to capture (e.g., "DP-2")
     /// If not specified, the portal picker will be shown
@@ -33,8 +41,18 @@ pub struct CastArgs {
     #[arg(short, long)]
     window: Option<String>,
 
+    /// Composite several sources side by side in a grid (repeatable)
+    /// e.g. --source window --source monitor:DP-2
+    #[arg(long = "source", value_name = "SOURCE", conflicts_with = "layers")]
+    sources: Vec<String>,
+
+    /// Composite a source at an explicit position (repeatable)
+    /// Format: SOURCE@X,Y,WxH[,zN], later layers draw on top at equal z
+    #[arg(long = "layer", value_name = "LAYER")]
+    layers: Vec<String>,
+
     /// Output preset (720p30, 720p60, 1080p30, 1080p60, 1440p60, 1440p120, 4k30, 4k60)
-    /// Use --resolution and --fps for custom settings instead
+    /// or a custom WxH@FPS such as 2560x1080@60
     #[arg(short, long, default_value = "1080p60")]
     preset: String,
 
@@ -46,12 +64,20 @@ pub struct CastArgs {
     #[arg(long, value_name = "FPS")]
     fps: Option<u32>,
 
+    /// Fit a source with another aspect ratio: letterbox (bars), crop, stretch
+    #[arg(long, value_name = "MODE")]
+    fit: Option<String>,
+
+    /// Letterbox bar color (#rrggbb)
+    #[arg(long, value_name = "COLOR")]
+    fit_color: Option<String>,
+
     /// Video codec (h264, hevc, av1)
     #[arg(short, long, default_value = "h264")]
     codec: String,
 
-    /// Bitrate in kbps (0 = auto)
-    #[arg(short, long, default_value = "0")]
+    /// Bitrate in kbps, or with a unit like 6M or 6000k (0 = auto)
+    #[arg(short, long, default_value = "0", value_parser = parse_bitrate)]
     bitrate: u32,
 
     /// Encoder quality preset (fast, medium, slow, quality)
@@ -75,22 +101,69 @@ pub struct CastArgs {
     gpu: u32,
 
     /// Record to file (e.g., recording.mp4 or recording.mkv)
-    /// Can be used alongside virtual camera output
+    /// Can be used alongside virtual camera output. Templates such as
+    /// '~/Videos/nitrogen-{date}-{n}.mp4' are filled in at start: {date},
+    /// {time}, {source}, {preset}, and {n} (counts up past existing files)
     #[arg(short = 'r', long, value_name = "FILE")]
     record: Option<String>,
 
-    /// Audio source (none, desktop, mic, both)
+    /// Replace an existing recording file instead of refusing to start
+    #[arg(long)]
+    overwrite: bool,
+
+    /// Encode the recording at a different resolution than --preset
+    /// (same framerate), e.g. --record-preset 1440p60 --stream-preset 1080p60
+    #[arg(long, value_name = "PRESET")]
+    record_preset: Option<String>,
+
+    /// Title tag written to the recording
+    #[arg(long, value_name = "TITLE")]
+    title: Option<String>,
+
+    /// Recording container regardless of the file extension
+    /// (mp4, mkv, fmp4, webm)
+    #[arg(long, value_name = "FORMAT")]
+    container: Option<String>,
+
+    /// Keep the last SECS seconds in memory for `nitrogen save-replay`
+    #[arg(long, value_name = "SECS")]
+    replay_buffer: Option<u32>,
+
+    /// Start a new numbered recording file after this long (e.g. 10m, 1h)
+    #[arg(long, value_name = "DURATION")]
+    segment_duration: Option<String>,
+
+    /// Start a new numbered recording file after this many MB
+    #[arg(long, value_name = "MB")]
+    segment_size: Option<u64>,
+
+    /// Audio source (none, desktop, mic, both, app:<name>)
     #[arg(short = 'a', long, default_value = "none")]
     audio: String,
 
+    /// Record audio only, without capturing video (needs --record with an
+    /// .m4a, .mka, .opus or .ogg file; --audio defaults to both)
+    #[arg(long)]
+    audio_only: bool,
+
     /// Audio codec (aac, opus)
     #[arg(long, default_value = "aac")]
     audio_codec: String,
 
-    /// Audio bitrate in kbps (0 = auto)
-    #[arg(long, default_value = "0")]
+    /// Audio bitrate in kbps, or with a unit like 160k (0 = auto)
+    #[arg(long, default_value = "0", value_parser = parse_bitrate)]
     audio_bitrate: u32,
 
+    /// Audio codec for the recording only (aac, opus)
+    /// Defaults to --audio-codec
+    #[arg(long, value_name = "CODEC")]
+    record_audio_codec: Option<String>,
+
+    /// Audio codec for the RTMP/SRT stream only (aac, opus)
+    /// Defaults to --audio-codec
+    #[arg(long, value_name = "CODEC")]
+    stream_audio_codec: Option<String>,
+
     /// Frame generation mode for Smooth Motion (off, 2x, 3x, 4x, adaptive)
     /// Interpolates frames to increase output framerate
     /// WARNING: Experimental feature, may cause visual artifacts
@@ -105,10 +178,11 @@ pub struct CastArgs {
     #[arg(long, default_value = "auto")]
     hdr_tonemap: String,
 
-    /// HDR tonemapping algorithm (reinhard, aces, hable)
+    /// HDR tonemapping algorithm (reinhard, aces, hable, bt2390)
     /// reinhard: Simple, preserves colors
     /// aces: Filmic, cinematic look
     /// hable: Uncharted 2 filmic curve
+    /// bt2390: Broadcast reference, keeps mid-tones and rolls off highlights
     #[arg(long, default_value = "reinhard")]
     hdr_algorithm: String,
 
@@ -116,12 +190,110 @@ pub struct CastArgs {
     #[arg(long, default_value = "1000")]
     hdr_peak_luminance: u32,
 
+    /// Adapt HDR tonemapping to each frame's luminance instead of the peak
+    #[arg(long)]
+    hdr_dynamic: bool,
+
+    /// Treat the capture as this HDR signal when the compositor doesn't
+    /// signal it: pq, hlg or sdr, optionally with the peak in nits (pq:1000)
+    #[arg(long, value_name = "TRANSFER[:NITS]")]
+    hdr_input: Option<String>,
+
+    /// Output color range (limited, full). Defaults to full for desktop capture
+    #[arg(long, value_name = "RANGE")]
+    color_range: Option<String>,
+
     // ========== Output options ==========
     /// Disable virtual camera output (useful for file recording only)
     /// At least one output (camera or recording) must be enabled
     #[arg(long)]
     no_camera: bool,
 
+    /// Keep the alpha channel on the virtual camera (for OBS keying)
+    /// Recording and streaming output stay opaque
+    #[arg(long)]
+    camera_alpha: bool,
+
+    /// Pixel format of the virtual camera (bgra, nv12)
+    /// Use nv12 for applications that only accept YUV cameras
+    #[arg(long, value_name = "FORMAT")]
+    camera_format: Option<String>,
+
+    // ========== Encoder quality options (all codecs) ==========
+    /// Enable encoder lookahead (improves quality, ignored in low-latency mode)
+    #[arg(long, alias = "av1-lookahead")]
+    lookahead: bool,
+
+    /// Lookahead depth in frames (default 20, RTX 50: up to 250)
+    #[arg(long, alias = "av1-lookahead-depth", default_value = "20")]
+    lookahead_depth: u32,
+
+    /// Frames NVENC may have in flight (raises throughput, adds latency;
+    /// forced to 1 in low-latency mode)
+    #[arg(long, value_name = "FRAMES")]
+    async_depth: Option<u32>,
+
+    /// Enable spatial adaptive quantization
+    #[arg(long, alias = "av1-spatial-aq")]
+    spatial_aq: bool,
+
+    /// Enable temporal adaptive quantization (~4-5% efficiency)
+    #[arg(long, alias = "av1-temporal-aq")]
+    temporal_aq: bool,
+
+    /// Two-pass encoding (quarter, full; default full when no value is given)
+    /// Improves quality at a given bitrate, ignored in low-latency mode
+    #[arg(long, value_name = "MODE", num_args = 0..=1, default_missing_value = "full")]
+    two_pass: Option<String>,
+
+    /// Content mode (motion, static)
+    /// static: skip near-identical frames for slides/docs on slow links
+    #[arg(long, value_name = "MODE")]
+    content_mode: Option<String>,
+
+    /// NVENC tuning for all codecs (auto, hq, ll, ull)
+    /// ull: lowest latency, lower quality at the same bitrate
+    #[arg(long, value_name = "TUNE")]
+    tune: Option<String>,
+
+    /// Rate control (cbr, vbr, cqp:<qp>, cq:<level>; default: from the tune)
+    /// cqp ignores --bitrate, cq uses it as the ceiling
+    #[arg(long, value_name = "MODE")]
+    rate_control: Option<String>,
+
+    /// Peak bitrate in kbps, or with a unit like 6M (default: 1.5x --bitrate)
+    #[arg(long, value_name = "KBPS", value_parser = parse_bitrate)]
+    max_bitrate: Option<u32>,
+
+    /// VBV buffer size in kbits, or with a unit like 3M (default: one second
+    /// at --bitrate). Smaller buffers hold the rate closer to the cap
+    #[arg(long, value_name = "KBITS", value_parser = parse_bitrate)]
+    vbv_buffer: Option<u32>,
+
+    /// Always copy DMA-BUF frames through system memory before encoding
+    /// (disables the CUDA zero-copy path)
+    #[arg(long)]
+    no_zero_copy: bool,
+
+    /// Temporal SVC layers for H.264 (2-4; viewers on lossy links can drop
+    /// the upper layers). Best for WebRTC and SRT
+    #[arg(long, value_name = "N")]
+    temporal_layers: Option<u8>,
+
+    /// Refresh the picture gradually instead of with periodic keyframes
+    /// (smoother recovery on lossy SRT/WebRTC links; not for recordings)
+    #[arg(long)]
+    intra_refresh: bool,
+
+    /// Cursor mode (hidden, embedded, metadata)
+    /// metadata: Nitrogen draws the cursor from portal metadata
+    #[arg(long, value_name = "MODE")]
+    cursor: Option<String>,
+
+    /// Cursor size in metadata mode, relative to the compositor's cursor
+    #[arg(long, value_name = "SCALE")]
+    cursor_scale: Option<f32>,
+
     // ========== AV1-specific options ==========
     /// AV1: Enable 10-bit color (main10 profile)
     #[arg(long)]
@@ -136,22 +308,6 @@ pub struct CastArgs {
     #[arg(long, default_value = "hq")]
     av1_tune: String,
 
-    /// AV1: Enable lookahead (improves quality)
-    #[arg(long)]
-    av1_lookahead: bool,
-
-    /// AV1: Lookahead depth in frames (default 20, RTX 50: up to 250)
-    #[arg(long, default_value = "20")]
-    av1_lookahead_depth: u32,
-
-    /// AV1: Enable spatial adaptive quantization
-    #[arg(long)]
-    av1_spatial_aq: bool,
-
-    /// AV1: Enable temporal adaptive quantization (RTX 50+, ~4-5% efficiency)
-    #[arg(long)]
-    av1_temporal_aq: bool,
-
     /// AV1: Chroma format (420, 422, 444)
     /// 422 and 444 require RTX 50 series
     #[arg(long, default_value = "420")]
@@ -170,6 +326,10 @@ pub struct CastArgs {
     av1_auto: bool,
 
     // ========== Performance overlay options ==========
+    /// Log performance stats every N seconds (0 = off)
+    #[arg(long, value_name = "SECS")]
+    stats_interval: Option<u32>,
+
     /// Enable latency overlay (shows capture/encode latency, FPS, drops)
     #[arg(long)]
     overlay: bool,
@@ -178,21 +338,113 @@ pub struct CastArgs {
     #[arg(long, default_value = "top-left")]
     overlay_position: String,
 
+    /// Custom overlay text, e.g. "{title} | {time} | {fps}fps"
+    /// Placeholders: fps, bitrate, drops, frames, capture, encode, output,
+    /// latency, time, title. Use \n for a new line
+    #[arg(long, value_name = "TEMPLATE")]
+    overlay_template: Option<String>,
+
+    /// Text for the overlay {title} placeholder
+    #[arg(long, value_name = "TEXT")]
+    overlay_title: Option<String>,
+
+    /// Burn a SMPTE timecode into the video, counting from START (HH:MM:SS:FF)
+    #[arg(long, value_name = "START", num_args = 0..=1, default_missing_value = "00:00:00:00")]
+    timecode: Option<String>,
+
+    /// Also write the timecode as HEVC SEI / AV1 metadata (implies --timecode)
+    #[arg(long)]
+    timecode_metadata: bool,
+
     // ========== Streaming options ==========
-    /// Stream to RTMP/SRT URL (e.g., rtmp://live.twitch.tv/app/stream_key)
-    /// Supports rtmp://, rtmps://, and srt:// protocols
-    #[arg(long, value_name = "URL")]
-    stream: Option<String>,
+    /// Stream to RTMP/SRT/RIST URL (e.g., rtmp://live.twitch.tv/app/stream_key)
+    /// Supports rtmp://, rtmps://, srt://, and rist:// protocols. Repeat to
+    /// stream to several destinations at once
+    #[cfg(feature = "streaming")]
+    #[arg(long, value_name = "URL", visible_alias = "stream-url")]
+    stream: Vec<String>,
+
+    /// RIST retransmission buffer in milliseconds
+    #[cfg(feature = "streaming")]
+    #[arg(long, value_name = "MS")]
+    rist_buffer: Option<u32>,
+
+    /// RIST pre-shared secret (enables encryption)
+    #[cfg(feature = "streaming")]
+    #[arg(long, value_name = "SECRET")]
+    rist_secret: Option<String>,
+
+    /// RIST AES key size in bits (128 or 256, default 128)
+    #[cfg(feature = "streaming")]
+    #[arg(long, value_name = "BITS")]
+    rist_encryption: Option<u32>,
+
+    /// SRT receiver latency in milliseconds (default 120)
+    #[cfg(feature = "streaming")]
+    #[arg(long, value_name = "MS")]
+    srt_latency: Option<u32>,
+
+    /// SRT passphrase, 10 to 79 characters (enables encryption)
+    #[cfg(feature = "streaming")]
+    #[arg(long, value_name = "PASSPHRASE")]
+    srt_passphrase: Option<String>,
+
+    /// SRT AES key length in bytes (16, 24 or 32, default 16)
+    #[cfg(feature = "streaming")]
+    #[arg(long, value_name = "BYTES")]
+    srt_pbkeylen: Option<u32>,
+
+    /// Encode the stream at a different resolution than --preset (same framerate)
+    #[arg(long, value_name = "PRESET")]
+    stream_preset: Option<String>,
 
     /// Enable WebRTC output for browser-based viewing
     /// Starts a local HTTP signaling server for WebRTC connections
+    #[cfg(feature = "webrtc")]
     #[arg(long)]
     webrtc: bool,
 
     /// WebRTC local signaling server port
+    #[cfg(feature = "webrtc")]
     #[arg(long, default_value = "9000")]
     webrtc_port: u16,
 
+    /// Publish WebRTC video as simulcast layers (1080p/720p/360p by default)
+    /// Layers are configured with [webrtc] layers in the config file
+    #[cfg(feature = "webrtc")]
+    #[arg(long)]
+    webrtc_simulcast: bool,
+
+    /// Enable HLS output (rolling playlist served over HTTP)
+    /// Works on networks where WebRTC can't connect
+    #[arg(long)]
+    hls: bool,
+
+    /// HLS output directory (default: system temp dir)
+    #[arg(long, value_name = "DIR")]
+    hls_dir: Option<String>,
+
+    /// HLS HTTP server port (0 = only write files)
+    #[arg(long)]
+    hls_port: Option<u16>,
+
+    /// Enable DASH output (manifest.mpd plus CMAF segments)
+    #[arg(long)]
+    dash: bool,
+
+    /// DASH output directory (default: system temp dir)
+    #[arg(long, value_name = "DIR")]
+    dash_dir: Option<String>,
+
+    /// Write chunked fMP4 segments for LL-DASH players
+    #[arg(long)]
+    dash_low_latency: bool,
+
+    /// Stop automatically after this long (e.g. 30s, 5m, 1h30m, 00:05:00)
+    /// The recording is finalized and outputs are closed cleanly
+    #[arg(long, value_name = "DURATION")]
+    duration: Option<String>,
+
     // ========== Preset shortcuts ==========
     /// Discord-optimized preset (1080p60, H.264, 6Mbps, low-latency)
     /// Overrides preset, codec, and bitrate settings for optimal Discord compatibility
@@ -211,11 +463,49 @@ pub struct CastArgs {
     /// Enable audio ducking (reduce desktop volume when mic is active)
     #[arg(long)]
     audio_ducking: bool,
+
+    /// What mic activity ducks the desktop: rms (any sound), vad (speech only).
+    /// Implies --audio-ducking
+    #[arg(long, value_name = "MODE")]
+    ducking_mode: Option<String>,
+
+    /// Microphone channel handling (left, right, mono, stereo)
+    /// Use left/right for interfaces that put the mic on one channel
+    #[arg(long, value_name = "CHANNEL")]
+    mic_channel: Option<String>,
+}
+
+/// Recording defaults applied on top of the cast options by `nitrogen record`
+pub(super) struct RecordProfile {
+    /// Recording file or path template, as for --record
+    pub path: String,
+    /// Constant-quality level (None = the recording default)
+    pub constant_quality: Option<u8>,
+    /// Seconds between keyframes (None = the recording default)
+    pub keyframe_seconds: Option<u32>,
 }
 
 /// Start a capture session
 pub async fn cast(args: CastArgs) -> Result<()> {
-    println!("Nitrogen - Starting Capture\n");
+    run_session(args, None).await
+}
+
+/// Run a capture session until it is stopped
+///
+/// With a [`RecordProfile`] the session records to its path with the
+/// recording defaults instead of feeding the virtual camera.
+pub(super) async fn run_session(mut args: CastArgs, profile: Option<RecordProfile>) -> Result<()> {
+    if let Some(ref profile) = profile {
+        if args.record.is_some() {
+            return Err(anyhow::anyhow!(
+                "--record can't be used with 'nitrogen record'; pass the file as its path"
+            ));
+        }
+        args.record = Some(profile.path.clone());
+        println!("Nitrogen - Starting Recording\n");
+    } else {
+        println!("Nitrogen - Starting Capture\n");
+    }
 
     // Check if another instance is already running
     if daemon_running().await {
@@ -225,55 +515,10 @@ pub async fn cast(args: CastArgs) -> Result<()> {
     }
 
     // Load config file for defaults
-    let file_config = ConfigFile::load_or_default();
+    let mut file_config = ConfigFile::load_or_default();
     debug!("Loaded config, using file defaults where CLI args are default");
 
-    // Use config file values when CLI args are at their default values
-    // This allows config file to set new defaults while CLI can still override
-    let preset_str = if args.preset == "1080p60" {
-        &file_config.defaults.preset
-    } else {
-        &args.preset
-    };
-
-    let codec_str = if args.codec == "h264" {
-        &file_config.defaults.codec
-    } else {
-        &args.codec
-    };
-
-    let quality_str = if args.quality == "medium" {
-        &file_config.encoder.quality
-    } else {
-        &args.quality
-    };
-
-    let bitrate = if args.bitrate == 0 {
-        file_config.defaults.bitrate
-    } else {
-        args.bitrate
-    };
-
-    let camera_name = if args.camera_name == "Nitrogen Camera" {
-        &file_config.camera.name
-    } else {
-        &args.camera_name
-    };
-
-    let low_latency = if args.no_low_latency {
-        false
-    } else {
-        file_config.defaults.low_latency
-    };
-
-    let gpu = if args.gpu == 0 {
-        file_config.encoder.gpu
-    } else {
-        args.gpu
-    };
-
-    // Discord preset overrides - apply before other parsing if --discord is specified
-    let (preset_str, codec_str, bitrate) = if args.discord {
+    if args.discord {
         info!("Using Discord-optimized preset");
         println!("Using Discord-optimized preset (1080p60, H.264, {} kbps)", discord::DEFAULT_BITRATE);
 
@@ -287,295 +532,157 @@ pub async fn cast(args: CastArgs) -> Result<()> {
         if args.bitrate != 0 {
             warn!("--discord overrides custom bitrate to {} kbps", discord::DEFAULT_BITRATE);
         }
+    }
+    apply_session_overrides(&mut file_config, &args)?;
 
-        ("1080p60".to_string(), "h264".to_string(), discord::DEFAULT_BITRATE)
+    // Determine capture source
+    let source = if let Some(ref monitor) = args.monitor {
+        CaptureSource::monitor(monitor)
+    } else if let Some(ref window) = args.window {
+        CaptureSource::window(window)
     } else {
-        (preset_str.to_string(), codec_str.to_string(), bitrate)
+        // Default to portal selection (monitor)
+        CaptureSource::monitor("portal")
     };
-    let preset_str = &preset_str;
-    let codec_str = &codec_str;
 
-    // Parse preset - check for custom resolution/fps first
-    let preset = if args.resolution.is_some() || args.fps.is_some() {
-        // Custom resolution/fps specified
-        let base_preset = Preset::from_preset_str(preset_str).unwrap_or(Preset::P1080_60);
-        let (base_width, base_height) = base_preset.resolution();
-        let base_fps = base_preset.fps();
+    // Parse and validate every file/CLI setting in one place
+    let mut config = file_config
+        .to_capture_config(source)
+        .map_err(|e| anyhow::anyhow!("Invalid configuration: {}", e))?;
 
-        // Parse custom resolution if provided
-        let (width, height) = if let Some(ref res) = args.resolution {
-            parse_resolution(res)?
-        } else {
-            (base_width, base_height)
+    // Options that only exist on the command line
+    #[cfg(feature = "streaming")]
+    {
+        let mut urls = args.stream.iter().cloned();
+        config.stream_url = urls.next();
+        config.stream_mirrors = urls.collect();
+        config.stream_rist = RistOptions {
+            buffer_ms: args.rist_buffer,
+            secret: args.rist_secret.clone(),
+            encryption: args.rist_encryption,
         };
-
-        // Use custom fps if provided
-        let fps = args.fps.unwrap_or(base_fps);
-
-        Preset::Custom { width, height, fps }
-    } else {
-        Preset::from_preset_str(preset_str).ok_or_else(|| {
-            anyhow::anyhow!(
-                "Invalid preset '{}'. Valid options: 720p30, 720p60, 1080p30, 1080p60, 1440p30, 1440p60, 1440p120, 4k30, 4k60, 4k120",
-                preset_str
-            )
-        })?
-    };
-
-    // Parse codec
-    let codec: Codec = codec_str.parse().map_err(|_| {
-        anyhow::anyhow!(
-            "Invalid codec '{}'. Valid options: h264, hevc, av1",
-            codec_str
-        )
-    })?;
-
-    // Parse encoder preset
-    let encoder_preset = match quality_str.to_lowercase().as_str() {
-        "fast" => EncoderPreset::Fast,
-        "medium" => EncoderPreset::Medium,
-        "slow" => EncoderPreset::Slow,
-        "quality" => EncoderPreset::Quality,
-        _ => {
-            return Err(anyhow::anyhow!(
-                "Invalid quality '{}'. Valid options: fast, medium, slow, quality",
-                quality_str
-            ))
+        config.stream_srt = SrtOptions {
+            latency_ms: args.srt_latency,
+            passphrase: args.srt_passphrase.clone(),
+            pbkeylen: args.srt_pbkeylen,
+        };
+    }
+    config.audio_only = args.audio_only;
+    config.camera_enabled = !args.no_camera && !args.audio_only;
+    config.desktop_volume = args.desktop_volume;
+    config.mic_volume = args.mic_volume;
+    if let Some(ref profile) = profile {
+        if let Some(cq) = profile.constant_quality {
+            config = config.with_constant_quality(cq);
         }
-    };
-
-    // Parse audio source - use config file default if CLI is default
-    let audio_source_str = if args.audio == "none" {
-        &file_config.audio.source
-    } else {
-        &args.audio
-    };
-    let audio_source = match audio_source_str.to_lowercase().as_str() {
-        "none" => AudioSource::None,
-        "desktop" => AudioSource::Desktop,
-        "mic" | "microphone" => AudioSource::Microphone,
-        "both" => AudioSource::Both,
-        _ => {
-            return Err(anyhow::anyhow!(
-                "Invalid audio source '{}'. Valid options: none, desktop, mic, both",
-                audio_source_str
-            ))
+        if let Some(secs) = profile.keyframe_seconds {
+            let interval = config.fps() * secs;
+            config = config.with_keyframe_interval(interval);
         }
-    };
+        config = config.with_recording_profile();
+    }
+    if let Some(ref preset) = args.record_preset {
+        config.record_preset = Some(
+            preset
+                .parse::<Preset>()
+                .map_err(|e| anyhow::anyhow!("Invalid --record-preset: {}", e))?,
+        );
+    }
+    if let Some(ref preset) = args.stream_preset {
+        config.stream_preset = Some(
+            preset
+                .parse::<Preset>()
+                .map_err(|e| anyhow::anyhow!("Invalid --stream-preset: {}", e))?,
+        );
+    }
+    if let Some(ref template) = args.record {
+        let preset = config.record_preset.unwrap_or(config.preset);
+        let vars = PathTemplateVars::now(&config.source, &preset);
+        config.record_path = Some(
+            resolve_record_path(template, &vars, args.overwrite)
+                .map_err(|e| anyhow::anyhow!("Invalid --record: {}", e))?,
+        );
+    }
 
-    // Parse audio codec
-    let audio_codec_str = if args.audio_codec == "aac" {
-        &file_config.audio.codec
-    } else {
-        &args.audio_codec
-    };
-    let audio_codec = match audio_codec_str.to_lowercase().as_str() {
-        "aac" => AudioCodec::Aac,
-        "opus" => AudioCodec::Opus,
-        _ => {
-            return Err(anyhow::anyhow!(
-                "Invalid audio codec '{}'. Valid options: aac, opus",
-                audio_codec_str
-            ))
-        }
-    };
+    // A layout on the command line replaces the [layout] section
+    if !args.sources.is_empty() {
+        let sources = args
+            .sources
+            .iter()
+            .map(|s| s.parse::<CaptureSource>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("Invalid --source: {}", e))?;
+        config.layout = grid_layout(&sources, config.width(), config.height());
+    } else if !args.layers.is_empty() {
+        config.layout = args
+            .layers
+            .iter()
+            .map(|l| l.parse::<LayoutLayer>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("Invalid --layer: {}", e))?;
+    }
+    if let Some(ref duration) = args.duration {
+        config.max_duration = Some(parse_duration(duration).map_err(|e| {
+            anyhow::anyhow!(
+                "Invalid duration '{}': {}. Use e.g. 30s, 5m, 1h30m or HH:MM:SS",
+                duration,
+                e
+            )
+        })?);
+    }
 
-    // Audio bitrate (0 = use codec default)
-    let audio_bitrate = if args.audio_bitrate == 0 {
-        file_config.audio.bitrate
-    } else {
-        args.audio_bitrate
-    };
+    // Parse color range (default depends on the capture source)
+    if let Some(ref range) = args.color_range {
+        config.color_range = range.parse::<ColorRange>().map_err(|e| {
+            anyhow::anyhow!("Invalid color range '{}'. Valid options: limited, full. {}", range, e)
+        })?;
+    }
 
     // Parse frame generation mode for Smooth Motion
-    let frame_gen = nitrogen_core::encode::FrameGenMode::from_str(&args.frame_gen);
-    if frame_gen != nitrogen_core::encode::FrameGenMode::Off {
+    config.frame_gen = nitrogen_core::encode::FrameGenMode::from_str(&args.frame_gen);
+    if config.frame_gen != nitrogen_core::encode::FrameGenMode::Off {
         if !nitrogen_core::encode::supports_smooth_motion() {
             warn!("Smooth Motion requested but GPU may not support optical flow optimally");
         }
-        info!("Smooth Motion enabled: {} interpolation", frame_gen);
+        info!("Smooth Motion enabled: {} interpolation", config.frame_gen);
     }
 
-    // Parse HDR tonemapping settings
-    let hdr_tonemap_str = if args.hdr_tonemap == "auto" {
-        &file_config.hdr.tonemap
-    } else {
-        &args.hdr_tonemap
-    };
-    let hdr_tonemap: TonemapMode = hdr_tonemap_str.parse().map_err(|e: String| {
-        anyhow::anyhow!(
-            "Invalid HDR tonemap mode '{}'. Valid options: auto, on, off. {}",
-            hdr_tonemap_str,
-            e
-        )
-    })?;
-
-    let hdr_algorithm_str = if args.hdr_algorithm == "reinhard" {
-        &file_config.hdr.algorithm
-    } else {
-        &args.hdr_algorithm
-    };
-    let hdr_algorithm: TonemapAlgorithm = hdr_algorithm_str.parse().map_err(|e: String| {
-        anyhow::anyhow!(
-            "Invalid HDR algorithm '{}'. Valid options: reinhard, aces, hable. {}",
-            hdr_algorithm_str,
-            e
-        )
-    })?;
-
-    let hdr_peak_luminance = if args.hdr_peak_luminance == 1000 {
-        file_config.hdr.peak_luminance
-    } else {
-        args.hdr_peak_luminance
-    };
-
-    if hdr_tonemap != TonemapMode::Off {
-        info!("HDR tonemapping: {} with {} algorithm", hdr_tonemap_str, hdr_algorithm_str);
+    // Re-check now that the CLI-only outputs are set
+    if let Err(e) = config.validate_strict() {
+        return Err(anyhow::anyhow!("Invalid configuration: {}", e));
     }
 
-    // Determine capture source
-    let source = if let Some(ref monitor) = args.monitor {
-        CaptureSource::monitor(monitor)
-    } else if let Some(ref window) = args.window {
-        CaptureSource::window(window)
-    } else {
-        // Default to portal selection (monitor)
-        CaptureSource::monitor("portal")
-    };
-
-    // Build AV1 configuration
-    let av1_config = if codec == Codec::Av1 {
-        // Parse AV1 tier
-        let av1_tier = match args.av1_tier.to_lowercase().as_str() {
-            "main" => Av1Tier::Main,
-            "high" => Av1Tier::High,
-            _ => {
-                return Err(anyhow::anyhow!(
-                    "Invalid AV1 tier '{}'. Valid options: main, high",
-                    args.av1_tier
-                ))
-            }
-        };
+    if config.hdr_tonemap != TonemapMode::Off {
+        info!(
+            "HDR tonemapping: {} with {} algorithm{}",
+            config.hdr_tonemap,
+            config.hdr_algorithm,
+            if config.hdr_dynamic { " (dynamic)" } else { "" }
+        );
+    }
 
-        // Parse AV1 tune
-        let av1_tune = match args.av1_tune.to_lowercase().as_str() {
-            "hq" => Av1Tune::Hq,
-            "uhq" => Av1Tune::Uhq,
-            "ll" => Av1Tune::Ll,
-            "ull" => Av1Tune::Ull,
-            "lossless" => Av1Tune::Lossless,
-            _ => {
-                return Err(anyhow::anyhow!(
-                    "Invalid AV1 tune '{}'. Valid options: hq, uhq, ll, ull, lossless",
-                    args.av1_tune
-                ))
+    // Auto-detect RTX 50 features if requested
+    if config.codec == Codec::Av1 && args.av1_auto {
+        match detect_rtx50_features(config.gpu) {
+            Ok(features) => {
+                info!("Detected RTX 50 series: {:?}", features);
+                println!("  RTX 50 detected: Enabling UHQ features...");
+                config.quality.temporal_aq = features.temporal_aq_supported;
+                if features.extended_lookahead {
+                    config.quality.lookahead_depth = config.quality.lookahead_depth.max(100);
+                }
+                if features.uhq_supported {
+                    config.av1.tune = Av1Tune::Uhq;
+                }
+                if !features.yuv422_supported {
+                    config.av1.chroma = ChromaFormat::Yuv420;
+                }
+                config.av1.b_ref_mode = features.b_ref_supported;
             }
-        };
-
-        // Parse chroma format
-        let av1_chroma = match args.av1_chroma.as_str() {
-            "420" => ChromaFormat::Yuv420,
-            "422" => ChromaFormat::Yuv422,
-            "444" => ChromaFormat::Yuv444,
-            _ => {
-                return Err(anyhow::anyhow!(
-                    "Invalid AV1 chroma format '{}'. Valid options: 420, 422, 444",
-                    args.av1_chroma
-                ))
+            Err(e) => {
+                debug!("RTX 50 detection failed: {}, using defaults", e);
             }
-        };
-
-        // Auto-detect RTX 50 features if requested
-        let (final_tune, final_temporal_aq, final_chroma, final_b_ref, final_lookahead_depth) =
-            if args.av1_auto {
-                match detect_rtx50_features(gpu) {
-                    Ok(features) => {
-                        info!("Detected RTX 50 series: {:?}", features);
-                        println!("  RTX 50 detected: Enabling UHQ features...");
-                        (
-                            if features.uhq_supported {
-                                Av1Tune::Uhq
-                            } else {
-                                av1_tune
-                            },
-                            features.temporal_aq_supported,
-                            if features.yuv422_supported {
-                                av1_chroma
-                            } else {
-                                ChromaFormat::Yuv420
-                            },
-                            features.b_ref_supported,
-                            if features.extended_lookahead {
-                                args.av1_lookahead_depth.max(100)
-                            } else {
-                                args.av1_lookahead_depth
-                            },
-                        )
-                    }
-                    Err(e) => {
-                        debug!("RTX 50 detection failed: {}, using defaults", e);
-                        (av1_tune, args.av1_temporal_aq, av1_chroma, args.av1_b_ref, args.av1_lookahead_depth)
-                    }
-                }
-            } else {
-                (av1_tune, args.av1_temporal_aq, av1_chroma, args.av1_b_ref, args.av1_lookahead_depth)
-            };
-
-        Av1Config {
-            ten_bit: args.av1_10bit,
-            tier: av1_tier,
-            gop_length: args.av1_gop,
-            lookahead: args.av1_lookahead,
-            lookahead_depth: final_lookahead_depth,
-            spatial_aq: args.av1_spatial_aq,
-            temporal_aq: final_temporal_aq,
-            tune: final_tune,
-            chroma: final_chroma,
-            multipass: MultipassMode::Disabled,
-            b_ref_mode: final_b_ref,
         }
-    } else {
-        Av1Config::default()
-    };
-
-    // Build configuration
-    let config = CaptureConfig {
-        source,
-        preset,
-        codec,
-        bitrate,
-        encoder_preset,
-        camera_name: camera_name.to_string(),
-        low_latency,
-        gpu,
-        record_path: args.record.as_ref().map(std::path::PathBuf::from),
-        cursor_mode: nitrogen_core::config::CursorMode::Embedded,
-        audio_source,
-        av1: av1_config,
-        audio_codec,
-        audio_bitrate,
-        frame_gen,
-        hdr_tonemap,
-        hdr_algorithm,
-        hdr_peak_luminance,
-        camera_enabled: !args.no_camera,
-        overlay_enabled: args.overlay || file_config.overlay.enabled,
-        overlay_position: OverlayPosition::from_str(&if args.overlay_position == "top-left" {
-            file_config.overlay.position.clone()
-        } else {
-            args.overlay_position.clone()
-        }),
-        stream_url: args.stream.clone(),
-        webrtc_enabled: args.webrtc,
-        webrtc_port: args.webrtc_port,
-        desktop_volume: args.desktop_volume,
-        mic_volume: args.mic_volume,
-        audio_ducking: args.audio_ducking,
-    };
-
-    // Validate configuration
-    if let Err(e) = config.validate_strict() {
-        return Err(anyhow::anyhow!("Invalid configuration: {}", e));
     }
 
     // Check for warnings
@@ -589,18 +696,61 @@ pub async fn cast(args: CastArgs) -> Result<()> {
     }
 
     println!("Configuration:");
+    if config.audio_only {
+        println!("  Mode:        audio only");
+    }
     println!("  Preset:      {}", config.preset);
     println!("  Resolution:  {}x{}", config.width(), config.height());
     println!("  Framerate:   {} fps", config.fps());
+    if config.fit == FitMode::Letterbox && config.fit_color != BarColor::BLACK {
+        println!("  Fit:         letterbox ({} bars)", config.fit_color);
+    } else if config.fit != FitMode::default() {
+        println!("  Fit:         {}", config.fit);
+    }
     println!("  Codec:       {}", config.codec);
-    println!("  Bitrate:     {} kbps", config.effective_bitrate());
+    match config.effective_rate_control() {
+        RateControl::ConstQuality { cq } => {
+            println!("  Quality:     CQ {} (up to {} kbps)", cq, config.effective_bitrate());
+        }
+        RateControl::Cqp { qp } => println!("  Quality:     QP {}", qp),
+        _ => println!("  Bitrate:     {} kbps", config.effective_bitrate()),
+    }
+    if let Some(interval) = config.keyframe_interval {
+        println!("  Keyframes:   every {} frames", interval);
+    }
+    if config.is_composited() {
+        println!("  Layout:      {} sources composited", config.layout.len());
+        for layer in &config.layout {
+            println!(
+                "               {} at {},{} ({}x{})",
+                layer.source, layer.x, layer.y, layer.width, layer.height
+            );
+        }
+    }
     if config.camera_enabled {
         println!("  Camera:      {}", config.camera_name);
     } else {
         println!("  Camera:      disabled");
     }
-    println!("  Low Latency: {}", config.low_latency);
+    match config.effective_tune().ffmpeg_value() {
+        Some(tune) if config.tune != Tune::Auto => println!("  Tune:        {}", tune),
+        _ => println!("  Low Latency: {}", config.low_latency),
+    }
     println!("  GPU:         {}", config.gpu);
+    if config.cursor_mode == CursorMode::Metadata {
+        println!("  Cursor:      metadata ({}x)", config.cursor_scale);
+    } else {
+        println!("  Cursor:      {}", config.cursor_mode);
+    }
+    if config.temporal_layers > 1 {
+        println!(
+            "  SVC:         {} temporal layers",
+            config.temporal_layers.min(config.codec.max_temporal_layers())
+        );
+    }
+    if let Some(period) = config.resolved_intra_refresh_period() {
+        println!("  Refresh:     intra-refresh every {} frames", period);
+    }
     if config.audio_source != AudioSource::None {
         let effective_audio_bitrate = if config.audio_bitrate == 0 {
             config.audio_codec.default_bitrate()
@@ -614,6 +764,24 @@ pub async fn cast(args: CastArgs) -> Result<()> {
     }
     if let Some(ref path) = config.record_path {
         println!("  Recording:   {:?}", path);
+        if let Some(record) = config.record_encoder_config() {
+            println!(
+                "               video {}x{}, {} kbps (separate encoder)",
+                record.width(),
+                record.height(),
+                record.effective_bitrate()
+            );
+        }
+        if config.audio_source != AudioSource::None
+            && (config.record_audio.is_some() || config.audio_only)
+        {
+            let audio = config.effective_record_audio();
+            println!(
+                "               audio {}, {} kbps",
+                audio.codec,
+                audio.effective_bitrate()
+            );
+        }
     }
     if config.frame_gen != nitrogen_core::encode::FrameGenMode::Off {
         println!(
@@ -623,35 +791,72 @@ pub async fn cast(args: CastArgs) -> Result<()> {
     }
     if config.hdr_tonemap != TonemapMode::Off {
         println!(
-            "  HDR Tonemap: {} ({})",
-            config.hdr_tonemap, config.hdr_algorithm
+            "  HDR Tonemap: {} ({}{})",
+            config.hdr_tonemap,
+            config.hdr_algorithm,
+            if config.hdr_dynamic { ", dynamic" } else { "" }
         );
     }
     if config.overlay_enabled {
         println!("  Overlay:     enabled ({:?})", config.overlay_position);
     }
+    #[cfg(feature = "streaming")]
     if let Some(ref url) = config.stream_url {
-        // Mask stream key for display
-        let safe_url = if let Some(idx) = url.rfind('/') {
-            let (base, key) = url.split_at(idx + 1);
-            if !key.is_empty() && !key.contains(':') {
-                format!("{}****", base)
-            } else {
-                url.clone()
-            }
-        } else {
-            url.clone()
-        };
-        println!("  Stream:      {}", safe_url);
+        // Mask stream keys and secrets for display
+        println!("  Stream:      {}", StreamOutput::safe_url(url));
+        for mirror in &config.stream_mirrors {
+            println!("               + {}", StreamOutput::safe_url(mirror));
+        }
+        if let Some(stream) = config.stream_encoder_config() {
+            println!(
+                "               video {}x{}, {} kbps (separate encoder)",
+                stream.width(),
+                stream.height(),
+                stream.effective_bitrate()
+            );
+        }
+        if config.audio_source != AudioSource::None && config.stream_audio.is_some() {
+            let audio = config.effective_stream_audio();
+            println!(
+                "               audio {}, {} kbps",
+                audio.codec,
+                audio.effective_bitrate()
+            );
+        }
     }
     if config.webrtc_enabled {
         println!("  WebRTC:      http://localhost:{}", config.webrtc_port);
+        if !config.webrtc_simulcast.is_empty() {
+            let layers: Vec<String> = config
+                .webrtc_simulcast
+                .iter()
+                .map(|l| format!("{}p@{}k", l.height, l.bitrate))
+                .collect();
+            println!("  Simulcast:   {}", layers.join(", "));
+        }
+    }
+    if let Some(ref hls) = config.hls {
+        if hls.port != 0 {
+            println!("  HLS:         http://localhost:{}/index.m3u8", hls.port);
+        } else {
+            println!("  HLS:         {}", hls.playlist_path().display());
+        }
+    }
+    if let Some(ref dash) = config.dash {
+        let mode = if dash.low_latency { " (low latency)" } else { "" };
+        println!("  DASH:        {}{}", dash.manifest_path().display(), mode);
+    }
+    if let Some(duration) = config.max_duration {
+        println!("  Auto-stop:   after {}s", duration.as_secs());
     }
     println!();
 
     // Save values we need after pipeline creation (since config is moved)
     let camera_enabled = config.camera_enabled;
+    let camera_name = config.camera_name.clone();
+    let audio_only = config.audio_only;
     let record_path_display = config.record_path.clone();
+    let layout_sources = config.layout.len();
 
     // Create pipeline
     let pipeline = Pipeline::new(config)
@@ -672,8 +877,19 @@ pub async fn cast(args: CastArgs) -> Result<()> {
         None
     };
 
-    println!("Waiting for source selection...");
-    println!("(A dialog should appear to select your screen or window)\n");
+    if audio_only {
+        println!("Starting audio capture...\n");
+    } else {
+        println!("Waiting for source selection...");
+        if layout_sources > 0 {
+            println!(
+                "(A dialog will appear for each of the {} layout sources, in order)\n",
+                layout_sources
+            );
+        } else {
+            println!("(A dialog should appear to select your screen or window)\n");
+        }
+    }
 
     // Start pipeline (will prompt user via portal)
     let session = {
@@ -684,10 +900,14 @@ pub async fn cast(args: CastArgs) -> Result<()> {
         p.start().await.context("Failed to start pipeline")?
     };
 
-    println!("Capture started!");
-    println!("  Source:     {:?}", session.source_type);
-    println!("  Resolution: {}x{}", session.width, session.height);
-    println!("  Node ID:    {}", session.node_id);
+    if audio_only {
+        println!("Audio capture started!");
+    } else {
+        println!("Capture started!");
+        println!("  Source:     {:?}", session.source_type);
+        println!("  Resolution: {}x{}", session.width, session.height);
+        println!("  Node ID:    {}", session.node_id);
+    }
     println!();
 
     if camera_enabled {
@@ -704,13 +924,21 @@ pub async fn cast(args: CastArgs) -> Result<()> {
     }
     println!("Press Ctrl+C to stop...\n");
 
-    // Wait for Ctrl+C or IPC shutdown
-    let ctrl_c = async {
-        if let Err(e) = signal::ctrl_c().await {
-            error!("Failed to listen for Ctrl+C: {}", e);
+    // Auto-stop timer for --duration, runs the same stop path as IPC Stop
+    let deadline = pipeline.read().await.as_ref().and_then(|p| p.deadline());
+    let duration_elapsed = async move {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+            None => std::future::pending().await,
         }
     };
 
+    // Wait for SIGINT/SIGTERM or IPC shutdown
+    let stop_signal = shutdown_signal();
+
+    // SIGHUP reloads the config file into the running session
+    let reload_task = tokio::spawn(reload_on_sighup(pipeline.clone(), args.clone()));
+
     // Main processing loop
     let pipeline_clone = pipeline.clone();
     let process_loop = async move {
@@ -757,8 +985,8 @@ pub async fn cast(args: CastArgs) -> Result<()> {
         };
 
         tokio::select! {
-            _ = ctrl_c => {
-                println!("\nReceived interrupt signal...");
+            name = stop_signal => {
+                println!("\nReceived {}...", name);
             }
             _ = process_loop => {
                 info!("Processing loop ended");
@@ -766,27 +994,58 @@ pub async fn cast(args: CastArgs) -> Result<()> {
             _ = ipc_loop => {
                 info!("IPC loop ended (shutdown requested)");
             }
+            _ = duration_elapsed => {
+                println!("\nDuration reached...");
+            }
         }
     } else {
         tokio::select! {
-            _ = ctrl_c => {
-                println!("\nReceived interrupt signal...");
+            name = stop_signal => {
+                println!("\nReceived {}...", name);
             }
             _ = process_loop => {
                 info!("Processing loop ended");
             }
+            _ = duration_elapsed => {
+                println!("\nDuration reached...");
+            }
         }
     }
 
-    // Stop pipeline
+    // Stop pipeline: same path as IPC Stop, so the recording gets its trailer.
+    // A second signal or a hung output skips straight to exit.
+    reload_task.abort();
     println!("Stopping capture...");
-    {
+    let stop = async {
         let mut guard = pipeline.write().await;
-        if let Some(ref mut p) = *guard {
-            p.stop().await?;
-        }
+        let result = match guard.as_mut() {
+            Some(p) => p.stop().await,
+            None => Ok(()),
+        };
         *guard = None;
-    }
+        result
+    };
+    let stopped = tokio::select! {
+        result = tokio::time::timeout(STOP_TIMEOUT, stop) => match result {
+            Ok(result) => Some(result),
+            Err(_) => {
+                error!("Graceful stop timed out after {}s", STOP_TIMEOUT.as_secs());
+                None
+            }
+        },
+        name = shutdown_signal() => {
+            error!("Received {} while stopping, exiting without finalizing", name);
+            None
+        }
+    };
+
+    let Some(result) = stopped else {
+        if let Some(server) = ipc_server {
+            server.cleanup();
+        }
+        std::process::exit(1);
+    };
+    result?;
 
     // Clean up IPC server
     if let Some(server) = ipc_server {
@@ -798,6 +1057,320 @@ pub async fn cast(args: CastArgs) -> Result<()> {
     Ok(())
 }
 
+/// Apply the CLI args on top of the config file values
+///
+/// Shared by startup and config reload, so a reload keeps whatever the
+/// command line overrode.
+fn apply_session_overrides(file_config: &mut ConfigFile, args: &CastArgs) -> Result<()> {
+    // CLI args override the config file; args left at their defaults keep the file values
+    apply_cli_overrides(file_config, args);
+
+    // Discord preset overrides - apply before other parsing if --discord is specified
+    if args.discord {
+        file_config.defaults.preset = "1080p60".to_string();
+        file_config.defaults.codec = "h264".to_string();
+        file_config.defaults.bitrate = discord::DEFAULT_BITRATE;
+    }
+
+    // Custom resolution/fps override the preset's values
+    if args.resolution.is_some() || args.fps.is_some() {
+        let base_preset =
+            Preset::from_preset_str(&file_config.defaults.preset).unwrap_or(Preset::P1080_60);
+
+        // Parse custom resolution if provided
+        let (width, height) = if let Some(ref res) = args.resolution {
+            parse_resolution(res)?
+        } else {
+            base_preset.resolution()
+        };
+
+        // Use custom fps if provided
+        let fps = args.fps.unwrap_or(base_preset.fps());
+
+        file_config.defaults.preset = Preset::Custom { width, height, fps }.to_string();
+    }
+    Ok(())
+}
+
+/// Reload the config file on every SIGHUP and apply what can change live
+///
+/// Settings that need a new session are logged and left as they are.
+async fn reload_on_sighup(pipeline: Arc<RwLock<Option<Pipeline>>>, args: CastArgs) {
+    let mut sighup = match signal::unix::signal(SignalKind::hangup()) {
+        Ok(sighup) => sighup,
+        Err(e) => {
+            error!("Failed to listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+
+    while sighup.recv().await.is_some() {
+        info!("Received SIGHUP, reloading {:?}", ConfigFile::default_path());
+        let mut guard = pipeline.write().await;
+        let Some(p) = guard.as_mut() else {
+            break;
+        };
+        let reloaded = ConfigFile::load().map_err(anyhow::Error::from).and_then(|mut file| {
+            apply_session_overrides(&mut file, &args)?;
+            Ok(file.to_capture_config(p.config().source.clone())?)
+        });
+        let reloaded = match reloaded {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("Config reload failed, keeping the running settings: {}", e);
+                continue;
+            }
+        };
+
+        let plan = ReloadPlan::new(p.config(), &reloaded);
+        if plan.is_empty() {
+            info!("Config reloaded, nothing changed");
+            continue;
+        }
+        if let Err(e) = p.apply_reload(&plan) {
+            warn!("Config reload partly failed: {}", e);
+        }
+        if !plan.live.is_empty() {
+            info!("Config reloaded: applied {} change(s)", plan.live.len());
+        }
+        if !plan.restart.is_empty() {
+            warn!(
+                "Config changes that need a restart were skipped: {}",
+                plan.restart.join(", ")
+            );
+        }
+    }
+}
+
+/// Wait for SIGINT (Ctrl+C) or SIGTERM, returning which one arrived
+async fn shutdown_signal() -> &'static str {
+    let sigterm = async {
+        match signal::unix::signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    tokio::select! {
+        result = signal::ctrl_c() => {
+            if let Err(e) = result {
+                error!("Failed to listen for Ctrl+C: {}", e);
+                std::future::pending::<()>().await;
+            }
+            "interrupt signal"
+        }
+        _ = sigterm => "SIGTERM",
+    }
+}
+
+/// Copy CLI args onto the config file values
+///
+/// Args still at their clap defaults keep whatever the config file set, so
+/// the config file can change defaults while the CLI still overrides them.
+fn apply_cli_overrides(file: &mut ConfigFile, args: &CastArgs) {
+    if args.preset != "1080p60" {
+        file.defaults.preset = args.preset.clone();
+    }
+    if args.codec != "h264" {
+        file.defaults.codec = args.codec.clone();
+    }
+    if args.bitrate != 0 {
+        file.defaults.bitrate = args.bitrate;
+    }
+    if args.no_low_latency {
+        file.defaults.low_latency = false;
+    }
+    if let Some(ref fit) = args.fit {
+        file.defaults.fit = fit.clone();
+    }
+    if let Some(ref color) = args.fit_color {
+        file.defaults.fit_color = color.clone();
+    }
+    if let Some(ref title) = args.title {
+        file.recording.metadata.title = Some(title.clone());
+    }
+    if let Some(ref container) = args.container {
+        file.recording.container = Some(container.clone());
+    }
+    if let Some(secs) = args.replay_buffer {
+        file.recording.replay_buffer = secs;
+    }
+    if let Some(ref duration) = args.segment_duration {
+        file.recording.segment_duration = Some(duration.clone());
+    }
+    if let Some(megabytes) = args.segment_size {
+        file.recording.segment_size = megabytes;
+    }
+
+    if args.quality != "medium" {
+        file.encoder.quality = args.quality.clone();
+    }
+    if args.gpu != 0 {
+        file.encoder.gpu = args.gpu;
+    }
+    file.encoder.lookahead |= args.lookahead;
+    if args.lookahead_depth != 20 {
+        file.encoder.lookahead_depth = args.lookahead_depth;
+    }
+    if let Some(depth) = args.async_depth {
+        file.encoder.async_depth = depth;
+    }
+    file.encoder.spatial_aq |= args.spatial_aq;
+    file.encoder.temporal_aq |= args.temporal_aq;
+    if let Some(ref mode) = args.two_pass {
+        file.encoder.multipass = mode.clone();
+    }
+    if args.no_zero_copy {
+        file.encoder.zero_copy = false;
+    }
+    if let Some(ref mode) = args.content_mode {
+        file.encoder.content_mode = mode.clone();
+    }
+    if let Some(ref tune) = args.tune {
+        file.encoder.tune = tune.clone();
+    }
+    if let Some(ref mode) = args.rate_control {
+        file.encoder.rate_control = mode.clone();
+    }
+    if let Some(kbps) = args.max_bitrate {
+        file.encoder.max_bitrate = kbps;
+    }
+    if let Some(kbits) = args.vbv_buffer {
+        file.encoder.vbv_buffer_size = kbits;
+    }
+    if let Some(layers) = args.temporal_layers {
+        file.encoder.temporal_layers = layers;
+    }
+    file.encoder.intra_refresh |= args.intra_refresh;
+    if let Some(secs) = args.stats_interval {
+        file.performance.stats_interval_secs = secs;
+    }
+    if let Some(ref mode) = args.cursor {
+        file.capture.cursor = mode.clone();
+    }
+    if let Some(scale) = args.cursor_scale {
+        file.capture.cursor_scale = scale;
+    }
+
+    file.av1.ten_bit |= args.av1_10bit;
+    if args.av1_tier != "main" {
+        file.av1.tier = args.av1_tier.clone();
+    }
+    if args.av1_tune != "hq" {
+        file.av1.tune = args.av1_tune.clone();
+    }
+    if args.av1_chroma != "420" {
+        file.av1.chroma = args.av1_chroma.clone();
+    }
+    file.av1.b_ref_mode |= args.av1_b_ref;
+    if let Some(gop) = args.av1_gop {
+        file.av1.gop_length = gop;
+    }
+
+    if args.camera_name != "Nitrogen Camera" {
+        file.camera.name = args.camera_name.clone();
+    }
+    file.camera.alpha |= args.camera_alpha;
+    if let Some(ref format) = args.camera_format {
+        file.camera.format = format.clone();
+    }
+
+    if args.audio != "none" {
+        file.audio.source = args.audio.clone();
+    } else if args.audio_only && file.audio.source == "none" {
+        file.audio.source = "both".to_string();
+    }
+    if args.audio_codec != "aac" {
+        file.audio.codec = args.audio_codec.clone();
+    }
+    if args.audio_bitrate != 0 {
+        file.audio.bitrate = args.audio_bitrate;
+    }
+    if let Some(ref channel) = args.mic_channel {
+        file.audio.mic.channel = channel.clone();
+    }
+    file.audio.ducking.enabled |= args.audio_ducking;
+    if let Some(ref mode) = args.ducking_mode {
+        file.audio.ducking.enabled = true;
+        file.audio.ducking.mode = mode.clone();
+    }
+    for (codec, output) in [
+        (&args.record_audio_codec, &mut file.audio.record),
+        (&args.stream_audio_codec, &mut file.audio.stream),
+    ] {
+        if let Some(codec) = codec {
+            output
+                .get_or_insert_with(|| OutputAudioSettings {
+                    codec: String::new(),
+                    bitrate: 0,
+                })
+                .codec = codec.clone();
+        }
+    }
+
+    if args.hdr_tonemap != "auto" {
+        file.hdr.tonemap = args.hdr_tonemap.clone();
+    }
+    if args.hdr_algorithm != "reinhard" {
+        file.hdr.algorithm = args.hdr_algorithm.clone();
+    }
+    if args.hdr_peak_luminance != 1000 {
+        file.hdr.peak_luminance = args.hdr_peak_luminance;
+    }
+    file.hdr.dynamic |= args.hdr_dynamic;
+    if let Some(ref input) = args.hdr_input {
+        file.hdr.input = input.clone();
+    }
+
+    file.overlay.enabled |= args.overlay;
+    if args.overlay_position != "top-left" {
+        file.overlay.position = args.overlay_position.clone();
+    }
+    if let Some(ref template) = args.overlay_template {
+        // Shells pass "\n" through literally
+        file.overlay.template = template.replace("\\n", "\n");
+    }
+    if let Some(ref title) = args.overlay_title {
+        file.overlay.title = title.clone();
+    }
+    if let Some(ref start) = args.timecode {
+        file.overlay.timecode = true;
+        file.overlay.timecode_start = start.clone();
+    }
+    if args.timecode_metadata {
+        file.overlay.timecode = true;
+        file.overlay.timecode_metadata = true;
+    }
+
+    #[cfg(feature = "webrtc")]
+    {
+        file.webrtc.enabled |= args.webrtc;
+        file.webrtc.simulcast |= args.webrtc_simulcast;
+        if args.webrtc_port != 9000 {
+            file.webrtc.port = args.webrtc_port;
+        }
+    }
+
+    file.hls.enabled |= args.hls || args.hls_dir.is_some();
+    if let Some(ref dir) = args.hls_dir {
+        file.hls.dir = dir.clone();
+    }
+    if let Some(port) = args.hls_port {
+        file.hls.port = port;
+    }
+
+    file.dash.enabled |= args.dash || args.dash_dir.is_some() || args.dash_low_latency;
+    if let Some(ref dir) = args.dash_dir {
+        file.dash.dir = dir.clone();
+    }
+    file.dash.low_latency |= args.dash_low_latency;
+}
+
 /// Parse a resolution string like "1920x1080" or "2560x1440"
 fn parse_resolution(s: &str) -> Result<(u32, u32)> {
     let parts: Vec<&str> = s.split('x').collect();
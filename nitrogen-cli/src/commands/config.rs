@@ -1,8 +1,11 @@
 //! Config command - manage configuration files
 
+use std::path::{Path, PathBuf};
+
 use anyhow::{Context, Result};
 use clap::{Args, Subcommand};
-use nitrogen_core::config::{sample_config, ConfigFile};
+use nitrogen_core::config::{sample_config, setting_line, ConfigFile};
+use nitrogen_core::CaptureSource;
 
 /// Arguments for the config command
 #[derive(Args)]
@@ -19,8 +22,23 @@ pub enum ConfigCommand {
     /// Show the current configuration
     Show,
 
+    /// Check a config file for errors without starting a capture
+    Validate {
+        /// Config file to check (default: the standard path)
+        path: Option<PathBuf>,
+    },
+
+    /// Print the effective configuration, with defaults filled in
+    Print {
+        /// Config file to read (default: the standard path)
+        path: Option<PathBuf>,
+    },
+
     /// Generate a default config file
     Init {
+        /// Where to write the file (default: the standard path)
+        path: Option<PathBuf>,
+
         /// Force overwrite if file exists
         #[arg(short, long)]
         force: bool,
@@ -57,8 +75,24 @@ pub async fn config(args: ConfigArgs) -> Result<()> {
             println!("Configuration file: {}\n", path.display());
             println!("{}", content);
         }
-        ConfigCommand::Init { force } => {
-            let path = ConfigFile::default_path();
+        ConfigCommand::Validate { path } => {
+            validate(&path.unwrap_or_else(ConfigFile::default_path))?;
+        }
+        ConfigCommand::Print { path } => {
+            let path = path.unwrap_or_else(ConfigFile::default_path);
+            let file = if path.exists() {
+                let content = std::fs::read_to_string(&path).context("Failed to read config file")?;
+                ConfigFile::parse(&content)?
+            } else {
+                ConfigFile::default()
+            };
+
+            let content =
+                toml::to_string_pretty(&file).context("Failed to serialize configuration")?;
+            print!("{}", content);
+        }
+        ConfigCommand::Init { path, force } => {
+            let path = path.unwrap_or_else(ConfigFile::default_path);
 
             if path.exists() && !force {
                 println!("Configuration file already exists: {}", path.display());
@@ -88,3 +122,58 @@ pub async fn config(args: ConfigArgs) -> Result<()> {
 
     Ok(())
 }
+
+/// Parse and validate a config file, printing every problem found
+///
+/// Warnings are printed but don't fail; parse and validation errors do.
+fn validate(path: &Path) -> Result<()> {
+    if !path.exists() {
+        anyhow::bail!("No configuration file found at: {}", path.display());
+    }
+    let content = std::fs::read_to_string(path).context("Failed to read config file")?;
+
+    // Syntax and type errors already quote the offending line
+    let file = ConfigFile::parse(&content)?;
+
+    // The source is only a placeholder; layouts in the file are still checked
+    let config = match file.to_capture_config(CaptureSource::monitor("portal")) {
+        Ok(config) => config,
+        Err(e) => {
+            let message = e.to_string();
+            eprintln!("Error: {}", message);
+            print_line_context(path, &content, &message);
+            anyhow::bail!("{} is not valid", path.display());
+        }
+    };
+
+    let warnings = config.validate();
+    for warning in &warnings {
+        println!("Warning: {}", warning);
+    }
+
+    if warnings.is_empty() {
+        println!("{} is valid", path.display());
+    } else {
+        println!();
+        println!("{} is valid ({} warnings)", path.display(), warnings.len());
+    }
+    Ok(())
+}
+
+/// Show the line an "Invalid <key> '<value>'" error refers to
+fn print_line_context(path: &Path, content: &str, message: &str) {
+    let key = message
+        .split_once("Invalid ")
+        .and_then(|(_, rest)| rest.split_whitespace().next())
+        .map(|key| key.trim_end_matches(':'));
+    let Some(line) = key.and_then(|key| setting_line(content, key)) else {
+        return;
+    };
+
+    if let Some(text) = content.lines().nth(line - 1) {
+        eprintln!("  --> {}:{}", path.display(), line);
+        eprintln!("   |");
+        eprintln!("{:>3} | {}", line, text);
+        eprintln!("   |");
+    }
+}
@@ -0,0 +1,245 @@
+//! Doctor command - check the system for common setup problems
+
+use std::path::Path;
+
+use anyhow::Result;
+use clap::Args;
+use nitrogen_core::config::Codec;
+use nitrogen_core::env::{detect_environment, is_pipewire_available, RuntimeEnvironment};
+use nitrogen_core::{capture, encode, query_gpu_stats};
+use serde::Serialize;
+
+/// Where distributions install the portal daemon
+const PORTAL_BINARIES: &[&str] = &[
+    "/usr/libexec/xdg-desktop-portal",
+    "/usr/lib/xdg-desktop-portal",
+];
+
+/// Arguments for the doctor command
+#[derive(Args, Debug)]
+pub struct DoctorArgs {
+    /// Print the report as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// GPU index to query stats for
+    #[arg(long, default_value = "0")]
+    pub gpu: u32,
+}
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    /// Everything is fine
+    Pass,
+    /// Works, but some feature is unavailable or degraded
+    Warn,
+    /// A hard requirement is missing; casting will fail
+    Fail,
+}
+
+impl CheckStatus {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Pass => "[OK]",
+            Self::Warn => "[!!]",
+            Self::Fail => "[FAIL]",
+        }
+    }
+}
+
+/// One line of the report
+#[derive(Debug, Serialize)]
+struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// Run all checks and report what needs fixing
+pub async fn doctor(args: DoctorArgs) -> Result<()> {
+    let mut checks = vec![check_nvenc(), check_pipewire(), check_portal()];
+    #[cfg(feature = "hotkeys")]
+    checks.push(check_input_devices());
+    checks.extend([check_environment(), check_gpu(args.gpu)]);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+    } else {
+        print_report(&checks);
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{} required check(s) failed", failed);
+    }
+    Ok(())
+}
+
+fn print_report(checks: &[Check]) {
+    println!("Nitrogen - Doctor\n");
+
+    for check in checks {
+        println!("  {:<6} {:<14} {}", check.status.label(), check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            println!("         {:<14} {}", "", hint);
+        }
+    }
+
+    let warnings = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Warn)
+        .count();
+    let failures = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Fail)
+        .count();
+
+    println!();
+    if failures == 0 && warnings == 0 {
+        println!("All checks passed.");
+    } else {
+        println!("{} failed, {} warning(s).", failures, warnings);
+    }
+}
+
+fn check_nvenc() -> Check {
+    let encoders = encode::available_encoders();
+    if !encode::nvenc_available() || encoders.is_empty() {
+        return Check::fail(
+            "NVENC",
+            "No NVENC encoders found",
+            "Install the NVIDIA driver (515.43.04+) and an FFmpeg build with NVENC support",
+        );
+    }
+
+    let codecs: Vec<&str> = [Codec::H264, Codec::Hevc, Codec::Av1]
+        .into_iter()
+        .filter(|&codec| encode::get_encoder_capabilities(codec).is_some())
+        .map(|codec| match codec {
+            Codec::H264 => "H.264",
+            Codec::Hevc => "HEVC",
+            Codec::Av1 => "AV1",
+        })
+        .collect();
+    Check::pass("NVENC", format!("Available ({})", codecs.join(", ")))
+}
+
+fn check_pipewire() -> Check {
+    let (_, status) = capture::check_pipewire_status();
+    if is_pipewire_available() {
+        Check::pass("PipeWire", "Running")
+    } else {
+        Check::fail(
+            "PipeWire",
+            format!("Not running ({})", status),
+            "PipeWire is required for screen capture. Try: systemctl --user start pipewire",
+        )
+    }
+}
+
+fn check_portal() -> Check {
+    let (running, status) = capture::check_portal_status();
+    if running {
+        return Check::pass("Portal", "xdg-desktop-portal running");
+    }
+
+    // The portal is D-Bus activated, so an installed but idle one is fine
+    if PORTAL_BINARIES.iter().any(|path| Path::new(path).exists()) {
+        Check::warn(
+            "Portal",
+            format!("xdg-desktop-portal installed but not running ({})", status),
+            "It usually starts on demand. If capture fails, try: systemctl --user start xdg-desktop-portal",
+        )
+    } else {
+        Check::fail(
+            "Portal",
+            "xdg-desktop-portal not found",
+            "Install xdg-desktop-portal and the backend for your compositor (e.g. xdg-desktop-portal-gnome, -kde, -hyprland)",
+        )
+    }
+}
+
+#[cfg(feature = "hotkeys")]
+fn check_input_devices() -> Check {
+    let keyboards = nitrogen_core::readable_keyboard_count();
+    if keyboards > 0 {
+        Check::pass("Hotkeys", format!("{} keyboard device(s) readable", keyboards))
+    } else {
+        Check::warn(
+            "Hotkeys",
+            "Cannot read any keyboard in /dev/input",
+            "Global hotkeys need it. Add yourself to the input group: sudo usermod -aG input $USER (then log out and back in)",
+        )
+    }
+}
+
+fn check_environment() -> Check {
+    let env = detect_environment();
+    match &env {
+        RuntimeEnvironment::NativeWayland(_) | RuntimeEnvironment::Gamescope(_) => {
+            Check::pass("Environment", env.description())
+        }
+        RuntimeEnvironment::X11 => Check::warn(
+            "Environment",
+            env.description(),
+            "Nitrogen is designed for Wayland. X11 support is limited.",
+        ),
+        RuntimeEnvironment::Unknown => Check::warn(
+            "Environment",
+            "Could not detect display server",
+            "Run nitrogen from inside a Wayland session (WAYLAND_DISPLAY must be set)",
+        ),
+    }
+}
+
+fn check_gpu(gpu: u32) -> Check {
+    match query_gpu_stats(gpu) {
+        Some(stats) => {
+            let name = encode::get_gpu_info()
+                .map(|info| format!("{} ({}) ", info.name, info.driver_version))
+                .unwrap_or_default();
+            Check::pass("GPU", format!("{}{}", name, stats.format_line()))
+        }
+        None => Check::warn(
+            "GPU",
+            format!("No stats for GPU {}", gpu),
+            "nvidia-smi was not found or failed. Check that the NVIDIA driver is loaded.",
+        ),
+    }
+}
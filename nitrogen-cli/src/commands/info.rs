@@ -1,13 +1,26 @@
 //! Info command - show system information and capabilities
 
 use anyhow::Result;
+use clap::Args;
 use nitrogen_core::capture;
 use nitrogen_core::config::Codec;
 use nitrogen_core::encode;
 use nitrogen_core::env::{detect_environment, RuntimeEnvironment};
 
+/// Arguments for the info command
+#[derive(Args, Debug)]
+pub struct InfoArgs {
+    /// Show a live dashboard of the running session instead (same as `status --watch`)
+    #[arg(short, long)]
+    pub watch: bool,
+}
+
 /// Show system information and NVENC capabilities
-pub async fn info() -> Result<()> {
+pub async fn info(args: InfoArgs) -> Result<()> {
+    if args.watch {
+        return super::status::watch().await;
+    }
+
     println!("Nitrogen - System Information\n");
 
     // GPU Information
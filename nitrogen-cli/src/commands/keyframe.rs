@@ -0,0 +1,32 @@
+//! Keyframe command - force the running encoder to emit a keyframe
+
+use anyhow::Result;
+use nitrogen_core::{daemon_running, IpcClient};
+
+/// Request a keyframe from the running capture session
+pub async fn keyframe() -> Result<()> {
+    println!("Nitrogen - Force Keyframe\n");
+
+    // Check if daemon is running
+    if !daemon_running().await {
+        println!("No nitrogen instance is currently running.");
+        println!();
+        println!("Start a capture session with: nitrogen cast");
+        return Ok(());
+    }
+
+    let mut client = IpcClient::connect().await?;
+
+    match client.force_keyframe().await {
+        Ok(()) => {
+            println!("Keyframe requested. The next encoded frame will be an IDR.");
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Failed to request keyframe: {}", e);
+            eprintln!();
+            eprintln!("Keyframes are only available when recording, streaming, or using WebRTC.");
+            Err(e.into())
+        }
+    }
+}
@@ -11,6 +11,7 @@ pub async fn list_sources() -> Result<()> {
 
     if sources.is_empty() {
         println!("No sources found.");
+        print_audio_applications();
         println!("\nNote: On Wayland, source selection happens through the desktop portal.");
         println!("Use 'nitrogen cast' to start a capture session and select a source.");
         return Ok(());
@@ -44,12 +45,60 @@ pub async fn list_sources() -> Result<()> {
         );
     }
 
+    let outputs: Vec<_> = capture::list_outputs()
+        .into_iter()
+        .filter(|o| o.connected)
+        .collect();
+    if !outputs.is_empty() {
+        println!("\nConnected outputs (use with --monitor):");
+        for output in outputs {
+            let dims = output
+                .resolution
+                .map(|(w, h)| format!("{}x{}", w, h))
+                .unwrap_or_else(|| "Unknown".to_string());
+            let state = if output.enabled { "" } else { " (disabled)" };
+            println!("  {:<18} {}{}", output.name, dims, state);
+        }
+    }
+
+    print_audio_applications();
+
     println!("\nNote: On Wayland, use 'nitrogen cast' to start capturing.");
     println!("The desktop portal will prompt you to select a screen or window.");
+    println!("With --monitor <connector>, the matching stream is picked from your selection.");
 
     Ok(())
 }
 
+/// Print the applications playing audio (for --audio app:<name>)
+fn print_audio_applications() {
+    let apps = match capture::list_audio_applications() {
+        Ok(apps) => apps,
+        Err(e) => {
+            println!("\nAudio applications: unavailable ({})", e);
+            return;
+        }
+    };
+    if apps.is_empty() {
+        return;
+    }
+
+    println!("\nApplications playing audio (use with --audio app:<name>):");
+    for app in apps {
+        let binary = app
+            .binary
+            .as_deref()
+            .filter(|binary| !binary.eq_ignore_ascii_case(&app.name))
+            .map(|binary| format!(" ({})", binary))
+            .unwrap_or_default();
+        let media = app
+            .media_name
+            .map(|media| format!(" - {}", media))
+            .unwrap_or_default();
+        println!("  {:<6} {}{}{}", app.node_id, app.name, binary, media);
+    }
+}
+
 fn truncate(s: &str, max: usize) -> String {
     if s.len() <= max {
         s.to_string()
@@ -2,14 +2,34 @@
 
 mod cast;
 mod config;
+mod doctor;
 mod info;
+mod keyframe;
 mod list;
+mod pause;
+mod presets;
+mod record;
+mod save_replay;
+mod screenshot;
+mod set_bitrate;
+mod set_source;
 mod status;
 mod stop;
+mod sync_offset;
 
 pub use cast::{cast, CastArgs};
 pub use config::{config, ConfigArgs};
-pub use info::info;
+pub use doctor::{doctor, DoctorArgs};
+pub use info::{info, InfoArgs};
+pub use keyframe::keyframe;
 pub use list::list_sources;
-pub use status::status;
+pub use pause::{pause, resume};
+pub use presets::list_presets;
+pub use record::{record, RecordArgs};
+pub use save_replay::save_replay;
+pub use screenshot::screenshot;
+pub use set_bitrate::set_bitrate;
+pub use set_source::set_source;
+pub use status::{status, StatusArgs};
 pub use stop::{stop, StopArgs};
+pub use sync_offset::sync_offset;
@@ -0,0 +1,45 @@
+//! Pause/resume commands - hide the virtual camera behind a placeholder
+
+use anyhow::Result;
+use nitrogen_core::{daemon_running, IpcClient};
+
+/// Fade the virtual camera of the running session to its placeholder
+pub async fn pause() -> Result<()> {
+    set_camera_paused(true).await
+}
+
+/// Fade the virtual camera of the running session back to the capture
+pub async fn resume() -> Result<()> {
+    set_camera_paused(false).await
+}
+
+async fn set_camera_paused(paused: bool) -> Result<()> {
+    let title = if paused { "Pause" } else { "Resume" };
+    println!("Nitrogen - {} Camera\n", title);
+
+    // Check if daemon is running
+    if !daemon_running().await {
+        println!("No nitrogen instance is currently running.");
+        println!();
+        println!("Start a capture session with: nitrogen cast");
+        return Ok(());
+    }
+
+    let mut client = IpcClient::connect().await?;
+
+    match client.set_camera_paused(paused).await {
+        Ok(()) => {
+            if paused {
+                println!("Camera paused; viewers see the placeholder.");
+                println!("Recording and streams keep the capture.");
+            } else {
+                println!("Camera resumed.");
+            }
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Failed to {} the camera: {}", title.to_lowercase(), e);
+            Err(e.into())
+        }
+    }
+}
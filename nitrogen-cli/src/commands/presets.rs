@@ -0,0 +1,38 @@
+//! List presets command
+
+use anyhow::Result;
+use nitrogen_core::config::Preset;
+
+/// List the named capture presets
+pub async fn list_presets() -> Result<()> {
+    println!("Nitrogen - Capture Presets\n");
+
+    println!(
+        "{:<12} {:<12} {:<6} {:<10}",
+        "Preset", "Resolution", "FPS", "Bitrate"
+    );
+    println!("{}", "-".repeat(44));
+
+    for preset in Preset::all_named() {
+        let marker = if *preset == Preset::default() {
+            " (default)"
+        } else {
+            ""
+        };
+        let name = preset.to_string();
+        let resolution = format!("{}x{}", preset.width(), preset.height());
+
+        println!(
+            "{:<12} {:<12} {:<6} {} kbps{}",
+            name,
+            resolution,
+            preset.fps(),
+            preset.suggested_bitrate(),
+            marker
+        );
+    }
+
+    println!("\nCustom presets use WxH@FPS, e.g. --preset 2560x1080@144");
+
+    Ok(())
+}
@@ -0,0 +1,42 @@
+//! Record command - capture straight to a file
+//!
+//! Runs the same session as `cast`, but records instead of feeding the
+//! virtual camera and encodes with recording defaults: constant quality,
+//! a keyframe every couple of seconds and no low-latency tuning.
+
+use anyhow::Result;
+use clap::Args;
+
+use super::cast::{run_session, CastArgs, RecordProfile};
+
+/// Arguments for the record command
+#[derive(Args)]
+pub struct RecordArgs {
+    /// Recording file (e.g. recording.mkv), or a template like
+    /// '~/Videos/nitrogen-{date}-{n}.mp4' (see cast --record)
+    #[arg(value_name = "FILE")]
+    path: String,
+
+    /// Constant-quality level, 1-51 (lower is better, default 23)
+    /// --bitrate becomes the ceiling
+    #[arg(long, value_name = "CQ")]
+    cq: Option<u8>,
+
+    /// Seconds between keyframes (default 2)
+    #[arg(long, value_name = "SECS")]
+    keyframe_interval: Option<u32>,
+
+    /// Capture, encoder and audio options, as for cast
+    #[command(flatten)]
+    cast: CastArgs,
+}
+
+/// Record a capture session to a file
+pub async fn record(args: RecordArgs) -> Result<()> {
+    let profile = RecordProfile {
+        path: args.path,
+        constant_quality: args.cq,
+        keyframe_seconds: args.keyframe_interval,
+    };
+    run_session(args.cast, Some(profile)).await
+}
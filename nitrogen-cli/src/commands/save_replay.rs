@@ -0,0 +1,44 @@
+//! Save-replay command - write out the last seconds of the running capture
+
+use anyhow::Result;
+use nitrogen_core::output::DEFAULT_REPLAY_PATH;
+use nitrogen_core::{daemon_running, IpcClient};
+
+/// Save the replay buffer of the running session to a video file
+pub async fn save_replay(path: Option<&str>) -> Result<()> {
+    println!("Nitrogen - Save Replay\n");
+
+    // Check if daemon is running
+    if !daemon_running().await {
+        println!("No nitrogen instance is currently running.");
+        println!();
+        println!("Start a capture session with: nitrogen cast --replay-buffer 30");
+        return Ok(());
+    }
+
+    // The daemon resolves the path, so relative paths must be made absolute here
+    let path = match path {
+        Some(path) if !path.starts_with('/') && !path.starts_with("~/") => {
+            std::env::current_dir()?.join(path).to_string_lossy().into_owned()
+        }
+        Some(path) => path.to_string(),
+        None => DEFAULT_REPLAY_PATH.to_string(),
+    };
+
+    let mut client = IpcClient::connect().await?;
+
+    match client.save_replay(&path).await {
+        Ok(info) => {
+            println!(
+                "Saved {:.1}s replay to {}",
+                info.duration_ms as f64 / 1000.0,
+                info.path.display()
+            );
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Failed to save replay: {}", e);
+            Err(e.into())
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Screenshot command - save a still of the running capture
+
+use anyhow::Result;
+use nitrogen_core::snapshot::DEFAULT_SNAPSHOT_PATH;
+use nitrogen_core::{daemon_running, IpcClient};
+
+/// Save the latest captured frame of the running session to an image file
+pub async fn screenshot(path: Option<&str>, raw: bool) -> Result<()> {
+    println!("Nitrogen - Screenshot\n");
+
+    // Check if daemon is running
+    if !daemon_running().await {
+        println!("No nitrogen instance is currently running.");
+        println!();
+        println!("Start a capture session with: nitrogen cast");
+        return Ok(());
+    }
+
+    // The daemon resolves the path, so relative paths must be made absolute here
+    let path = match path {
+        Some(path) if !path.starts_with('/') && !path.starts_with("~/") => {
+            std::env::current_dir()?.join(path).to_string_lossy().into_owned()
+        }
+        Some(path) => path.to_string(),
+        None => DEFAULT_SNAPSHOT_PATH.to_string(),
+    };
+
+    let mut client = IpcClient::connect().await?;
+
+    match client.snapshot(&path, raw).await {
+        Ok(info) => {
+            println!(
+                "Saved {}x{} {} to {}",
+                info.width,
+                info.height,
+                info.format.to_string().to_uppercase(),
+                info.path.display()
+            );
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Failed to save screenshot: {}", e);
+            Err(e.into())
+        }
+    }
+}
@@ -0,0 +1,31 @@
+//! Set-bitrate command - change the video bitrate of the running session
+
+use anyhow::Result;
+use nitrogen_core::{daemon_running, IpcClient};
+
+/// Change the video bitrate in kbps of the running capture session
+pub async fn set_bitrate(kbps: u32) -> Result<()> {
+    println!("Nitrogen - Set Bitrate\n");
+
+    // Check if daemon is running
+    if !daemon_running().await {
+        println!("No nitrogen instance is currently running.");
+        println!();
+        println!("Start a capture session with: nitrogen cast");
+        return Ok(());
+    }
+
+    let mut client = IpcClient::connect().await?;
+
+    match client.set_bitrate(kbps).await {
+        Ok((old_kbps, new_kbps)) => {
+            println!("Bitrate changed from {} to {} kbps.", old_kbps, new_kbps);
+            println!("The new rate starts with the next keyframe.");
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Failed to change bitrate: {}", e);
+            Err(e.into())
+        }
+    }
+}
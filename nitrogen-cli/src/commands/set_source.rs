@@ -0,0 +1,42 @@
+//! Set-source command - switch the running capture to another source
+
+use anyhow::{anyhow, Result};
+use nitrogen_core::ipc::SOURCE_SELECTION_TIMEOUT;
+use nitrogen_core::{daemon_running, CaptureSource, IpcClient};
+
+/// Switch the running capture session to `source` (`monitor[:ID]` or `window[:ID]`)
+pub async fn set_source(source: &str) -> Result<()> {
+    println!("Nitrogen - Switch Source\n");
+
+    let source: CaptureSource = source.parse().map_err(|e: String| anyhow!(e))?;
+
+    // Check if daemon is running
+    if !daemon_running().await {
+        println!("No nitrogen instance is currently running.");
+        println!();
+        println!("Start a capture session with: nitrogen cast");
+        return Ok(());
+    }
+
+    let mut client = IpcClient::connect().await?;
+
+    println!("Switching to {}...", source);
+    println!("Select the new source in the portal dialog if prompted.");
+
+    match client.set_source(source).await {
+        Ok((width, height)) => {
+            println!();
+            println!("Capture switched. New source is {}x{}.", width, height);
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Failed to switch source: {}", e);
+            eprintln!();
+            eprintln!(
+                "The previous source keeps running. The dialog times out after {}s.",
+                SOURCE_SELECTION_TIMEOUT.as_secs()
+            );
+            Err(e.into())
+        }
+    }
+}
@@ -1,18 +1,47 @@
 //! Status command - show status of running capture
 
+use std::io::Write;
+use std::time::Duration;
+
 use anyhow::Result;
-use nitrogen_core::{daemon_running, socket_path, IpcClient};
+use clap::Args;
+use nitrogen_core::ipc::{PipelineStatistics, PipelineStatus};
+use nitrogen_core::{probe_daemon, query_gpu_stats, socket_path, GpuStats, IpcClient, NitrogenError};
+use tokio::signal;
+
+/// How often the watch dashboard refreshes
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Arguments for the status command
+#[derive(Args, Debug)]
+pub struct StatusArgs {
+    /// Keep refreshing a live dashboard until Ctrl+C
+    #[arg(short, long)]
+    pub watch: bool,
+}
 
 /// Show status of running capture
-pub async fn status() -> Result<()> {
+pub async fn status(args: StatusArgs) -> Result<()> {
+    if args.watch {
+        return watch().await;
+    }
+
     println!("Nitrogen - Status\n");
 
     // Check if daemon is running
-    if !daemon_running().await {
-        println!("Status: Not running");
-        println!();
-        println!("Start a capture session with: nitrogen cast");
-        return Ok(());
+    match probe_daemon().await {
+        Ok(()) => {}
+        Err(NitrogenError::NoActiveSession | NitrogenError::DaemonUnreachable(_)) => {
+            println!("Status: Not running");
+            println!();
+            println!("Start a capture session with: nitrogen cast");
+            return Ok(());
+        }
+        Err(e) => {
+            println!("Status: Not responding");
+            eprintln!("Socket: {:?}", socket_path());
+            return Err(e.into());
+        }
     }
 
     // Connect to daemon
@@ -35,6 +64,15 @@ pub async fn status() -> Result<()> {
             println!("State:  {}", status.state);
             println!("PID:    {}", status.pid);
             println!("Uptime: {:.1}s", status.uptime_seconds);
+            if let Some(remaining) = status.remaining_seconds {
+                let secs = remaining.ceil() as u64;
+                println!(
+                    "Remaining: {:02}:{:02}:{:02} (auto-stop)",
+                    secs / 3600,
+                    (secs / 60) % 60,
+                    secs % 60
+                );
+            }
 
             if let Some((w, h)) = status.resolution {
                 println!();
@@ -53,6 +91,37 @@ pub async fn status() -> Result<()> {
                 println!("Source:     {}", source);
             }
 
+            if let Some(ref stream) = status.stream {
+                println!();
+                println!("Stream ({}):", stream.protocol);
+                println!(
+                    "  Sent:      {:.2} MB ({:.0} kbps)",
+                    stream.bytes_sent as f64 / 1_000_000.0,
+                    stream.send_rate_kbps
+                );
+                if let Some(ref link) = stream.link {
+                    println!("  RTT:       {:.0} ms", link.rtt_ms);
+                    println!("  Bandwidth: {:.0} kbps (estimated)", link.bandwidth_kbps);
+                    println!(
+                        "  Loss:      {:.1}% ({} lost, {} retransmitted total)",
+                        link.loss_percent(),
+                        link.packets_lost_total,
+                        link.packets_retransmitted_total
+                    );
+                    println!(
+                        "  Send buf:  {} ms ({} KB)",
+                        link.send_buffer_ms,
+                        link.send_buffer_bytes / 1024
+                    );
+                    if link.congested() {
+                        println!("  Link is CONGESTED. Consider lowering the bitrate.");
+                    }
+                }
+                if stream.timestamps_corrected > 0 {
+                    println!("  Fixed TS:  {} packets", stream.timestamps_corrected);
+                }
+            }
+
             // Try to get stats too
             if let Ok(stats) = client.stats().await {
                 println!();
@@ -136,3 +205,142 @@ pub async fn status() -> Result<()> {
 
     Ok(())
 }
+
+/// Redraw a live dashboard every second until Ctrl+C or the daemon exits
+pub(super) async fn watch() -> Result<()> {
+    match probe_daemon().await {
+        Ok(()) => {}
+        Err(NitrogenError::NoActiveSession | NitrogenError::DaemonUnreachable(_)) => {
+            println!("No nitrogen instance is currently running.");
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    // Hide the cursor and start from a clear screen
+    print!("\x1b[?25l\x1b[2J");
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    let result = loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = signal::ctrl_c() => break Ok(()),
+        }
+
+        // The daemon serves one connection at a time, so don't hold one open
+        // between refreshes or `nitrogen stop` would wait on us
+        let (status, stats) = match poll_daemon().await {
+            Some(polled) => polled,
+            None => {
+                print!("\x1b[?25h");
+                println!("\nDaemon stopped.");
+                return Ok(());
+            }
+        };
+        let gpu = match stats.as_ref().and_then(|s| s.gpu) {
+            Some(index) => tokio::task::spawn_blocking(move || query_gpu_stats(index))
+                .await
+                .ok()
+                .flatten(),
+            None => None,
+        };
+
+        let mut out = std::io::stdout().lock();
+        // Home, then overwrite line by line so the screen doesn't flicker
+        let mut frame = String::from("\x1b[H");
+        for line in dashboard_lines(&status, stats.as_ref(), gpu.as_ref()) {
+            frame.push_str(&line);
+            frame.push_str("\x1b[K\n");
+        }
+        frame.push_str("\x1b[J");
+        if let Err(e) = out.write_all(frame.as_bytes()).and_then(|_| out.flush()) {
+            break Err(e.into());
+        }
+    };
+
+    print!("\x1b[?25h");
+    println!();
+    result
+}
+
+/// Fetch status and stats over a fresh connection (None = daemon gone)
+async fn poll_daemon() -> Option<(PipelineStatus, Option<PipelineStatistics>)> {
+    let mut client = IpcClient::connect().await.ok()?;
+    let status = client.status().await.ok()?;
+    let stats = client.stats().await.ok();
+    Some((status, stats))
+}
+
+/// Lines of one dashboard refresh
+fn dashboard_lines(
+    status: &PipelineStatus,
+    stats: Option<&PipelineStatistics>,
+    gpu: Option<&GpuStats>,
+) -> Vec<String> {
+    let uptime = status.uptime_seconds as u64;
+    let mut lines = vec![
+        format!(
+            "Nitrogen - {} (PID {})    Ctrl+C to exit",
+            status.state, status.pid
+        ),
+        String::new(),
+        format!(
+            "Uptime:  {:02}:{:02}:{:02}",
+            uptime / 3600,
+            (uptime / 60) % 60,
+            uptime % 60
+        ),
+    ];
+
+    if let Some(stats) = stats {
+        let total = stats.frames_processed + stats.frames_dropped + stats.frames_failed;
+        let drop_pct = if total > 0 {
+            stats.frames_dropped as f64 / total as f64 * 100.0
+        } else {
+            0.0
+        };
+        lines.push(format!(
+            "Video:   {}x{} {} @ {} kbps",
+            stats.resolution.0, stats.resolution.1, stats.codec, stats.bitrate
+        ));
+        lines.push(format!(
+            "FPS:     {:.1} / {} target",
+            stats.actual_fps, stats.target_fps
+        ));
+        lines.push(format!(
+            "Latency: capture {:.1} ms | encode {:.1} ms | total {:.1} ms",
+            stats.capture_latency_ms, stats.encode_latency_ms, stats.total_latency_ms
+        ));
+        lines.push(format!(
+            "Frames:  {} processed | {} dropped ({:.1}%) | {} failed",
+            stats.frames_processed, stats.frames_dropped, drop_pct, stats.frames_failed
+        ));
+    }
+
+    if let Some(gpu) = gpu {
+        lines.push(gpu.format_line());
+    }
+
+    if let Some(ref stream) = status.stream {
+        let mut line = format!(
+            "Stream:  {} {:.0} kbps",
+            stream.protocol, stream.send_rate_kbps
+        );
+        if let Some(ref link) = stream.link {
+            line.push_str(&format!(
+                " | RTT {:.0} ms | loss {:.1}%",
+                link.rtt_ms,
+                link.loss_percent()
+            ));
+        }
+        lines.push(line);
+    }
+
+    lines.push(String::new());
+    if status.outputs.is_empty() {
+        lines.push("Outputs: none".to_string());
+    } else {
+        lines.push("Outputs:".to_string());
+        lines.extend(status.outputs.iter().map(|output| format!("  {}", output)));
+    }
+    lines
+}
@@ -0,0 +1,34 @@
+//! Sync-offset command - adjust A/V sync of the running session
+
+use anyhow::Result;
+use nitrogen_core::{daemon_running, IpcClient};
+
+/// Set the A/V sync offset in ms of the running capture session
+pub async fn sync_offset(offset_ms: i32) -> Result<()> {
+    println!("Nitrogen - A/V Sync Offset\n");
+
+    // Check if daemon is running
+    if !daemon_running().await {
+        println!("No nitrogen instance is currently running.");
+        println!();
+        println!("Start a capture session with: nitrogen cast");
+        return Ok(());
+    }
+
+    let mut client = IpcClient::connect().await?;
+
+    match client.set_sync_offset(offset_ms).await {
+        Ok(()) => {
+            match offset_ms {
+                0 => println!("A/V sync offset cleared."),
+                ms if ms > 0 => println!("Audio is now delayed by {} ms.", ms),
+                ms => println!("Video is now delayed by {} ms.", -ms),
+            }
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("Failed to set A/V sync offset: {}", e);
+            Err(e.into())
+        }
+    }
+}
@@ -11,6 +11,9 @@
 //! # Start casting a monitor
 //! nitrogen cast --preset 1080p60
 //!
+//! # Record to a file instead
+//! nitrogen record ~/Videos/clip.mkv
+//!
 //! # Stop casting
 //! nitrogen stop
 //! ```
@@ -18,6 +21,7 @@
 mod commands;
 
 use clap::{Parser, Subcommand};
+use nitrogen_core::config::parse_bitrate;
 use tracing::Level;
 use tracing_subscriber::EnvFilter;
 
@@ -44,17 +48,73 @@ enum Commands {
     #[command(alias = "ls")]
     ListSources,
 
+    /// List named capture presets with resolution, fps, and bitrate
+    ListPresets,
+
     /// Start capturing and streaming to virtual camera
     Cast(commands::CastArgs),
 
+    /// Record to a file with recording defaults (no virtual camera)
+    Record(commands::RecordArgs),
+
     /// Stop the current capture session
     Stop(commands::StopArgs),
 
     /// Show status of running capture
-    Status,
+    Status(commands::StatusArgs),
+
+    /// Force the running encoder to emit a keyframe
+    Keyframe,
+
+    /// Switch the running capture to another source
+    SetSource {
+        /// Source to capture: monitor[:ID] or window[:ID]
+        source: String,
+    },
+
+    /// Save a full-resolution still of the running capture
+    Screenshot {
+        /// Image file (.png, .jpg); placeholders like {date} and {n} are filled in
+        /// [default: ~/Pictures/nitrogen-{date}_{time}-{n}.png]
+        path: Option<String>,
+
+        /// Skip HDR tonemapping, watermark and overlay
+        #[arg(long)]
+        raw: bool,
+    },
+
+    /// Save the replay buffer of the running capture to a file
+    SaveReplay {
+        /// Video file; placeholders like {date} and {n} are filled in
+        /// [default: ~/Videos/nitrogen-replay-{date}_{time}-{n}.mp4]
+        path: Option<String>,
+    },
+
+    /// Adjust the A/V sync offset of the running recording/stream
+    SyncOffset {
+        /// Offset in ms: positive delays audio, negative delays video
+        #[arg(allow_hyphen_values = true)]
+        offset_ms: i32,
+    },
+
+    /// Fade the virtual camera to its privacy placeholder
+    Pause,
+
+    /// Fade the virtual camera back from its placeholder
+    Resume,
+
+    /// Change the video bitrate of the running capture
+    SetBitrate {
+        /// Bitrate in kbps; units like 6M or 6000k also work
+        #[arg(value_parser = parse_bitrate)]
+        bitrate: u32,
+    },
 
     /// Show system information and NVENC capabilities
-    Info,
+    Info(commands::InfoArgs),
+
+    /// Check the system for common setup problems
+    Doctor(commands::DoctorArgs),
 
     /// Manage configuration file
     Config(commands::ConfigArgs),
@@ -87,10 +147,21 @@ async fn main() {
     // Run the appropriate command
     let result = match cli.command {
         Commands::ListSources => commands::list_sources().await,
+        Commands::ListPresets => commands::list_presets().await,
         Commands::Cast(args) => commands::cast(args).await,
+        Commands::Record(args) => commands::record(args).await,
         Commands::Stop(args) => commands::stop(args).await,
-        Commands::Status => commands::status().await,
-        Commands::Info => commands::info().await,
+        Commands::Status(args) => commands::status(args).await,
+        Commands::Keyframe => commands::keyframe().await,
+        Commands::SetSource { source } => commands::set_source(&source).await,
+        Commands::Screenshot { path, raw } => commands::screenshot(path.as_deref(), raw).await,
+        Commands::SaveReplay { path } => commands::save_replay(path.as_deref()).await,
+        Commands::SyncOffset { offset_ms } => commands::sync_offset(offset_ms).await,
+        Commands::Pause => commands::pause().await,
+        Commands::Resume => commands::resume().await,
+        Commands::SetBitrate { bitrate } => commands::set_bitrate(bitrate).await,
+        Commands::Info(args) => commands::info(args).await,
+        Commands::Doctor(args) => commands::doctor(args).await,
         Commands::Config(args) => commands::config(args).await,
     };
 
@@ -102,6 +173,17 @@ async fn main() {
 
 /// Print an error with helpful hints when available
 fn print_error(error: &anyhow::Error) {
+    // Dismissing the portal dialog is a choice, not a failure worth a hint
+    let cancelled = error.chain().any(|cause| {
+        cause
+            .downcast_ref::<nitrogen_core::error::NitrogenError>()
+            .is_some_and(|e| e.is_cancelled())
+    });
+    if cancelled {
+        eprintln!("Capture cancelled.");
+        return;
+    }
+
     eprintln!("Error: {}", error);
 
     // Check if we can provide a helpful hint
@@ -0,0 +1,387 @@
+//! CPU affinity for Nitrogen's threads
+//!
+//! Capture, audio, hotkey and encode work can be kept on chosen cores, away
+//! from the cores a game renders on. The core lists are set once with
+//! [`configure`] (the pipeline does this from [`CaptureConfig`]); each thread
+//! then pins itself with [`pin_current_thread`] right after it is spawned.
+//! Roles without a core list are left to the scheduler, and on platforms
+//! without `sched_setaffinity` pinning does nothing.
+//!
+//! [`CaptureConfig`]: crate::config::CaptureConfig
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+use crate::error::{NitrogenError, Result};
+
+/// Number of cores a [`CpuSet`] can hold (glibc's `CPU_SETSIZE`)
+pub const MAX_CPUS: usize = 1024;
+
+/// A set of CPU cores, written as a list like "2" or "0-3,8"
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CpuSet {
+    bits: [u64; MAX_CPUS / 64],
+}
+
+impl CpuSet {
+    /// An empty set
+    pub const fn empty() -> Self {
+        Self {
+            bits: [0; MAX_CPUS / 64],
+        }
+    }
+
+    /// Set holding the given cores (cores at or above [`MAX_CPUS`] are dropped)
+    pub fn from_cores(cores: impl IntoIterator<Item = usize>) -> Self {
+        let mut set = Self::empty();
+        for core in cores {
+            set.insert(core);
+        }
+        set
+    }
+
+    /// Add a core, returning false if it is at or above [`MAX_CPUS`]
+    pub fn insert(&mut self, core: usize) -> bool {
+        if core >= MAX_CPUS {
+            return false;
+        }
+        self.bits[core / 64] |= 1 << (core % 64);
+        true
+    }
+
+    /// Whether the set holds `core`
+    pub fn contains(&self, core: usize) -> bool {
+        core < MAX_CPUS && self.bits[core / 64] & (1 << (core % 64)) != 0
+    }
+
+    /// Whether the set holds no cores
+    pub fn is_empty(&self) -> bool {
+        self.bits.iter().all(|&word| word == 0)
+    }
+
+    /// Cores in the set, lowest first
+    pub fn cores(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..MAX_CPUS).filter(|&core| self.contains(core))
+    }
+
+    /// Highest core in the set
+    pub fn max_core(&self) -> Option<usize> {
+        (0..MAX_CPUS).rev().find(|&core| self.contains(core))
+    }
+}
+
+impl Default for CpuSet {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl FromStr for CpuSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parse_core = |value: &str| {
+            let core: usize = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("'{}' is not a core number", value.trim()))?;
+            if core >= MAX_CPUS {
+                return Err(format!("core {} is above the limit of {}", core, MAX_CPUS - 1));
+            }
+            Ok(core)
+        };
+
+        let mut set = Self::empty();
+        for part in s.split(',') {
+            match part.split_once('-') {
+                Some((first, last)) => {
+                    let (first, last) = (parse_core(first)?, parse_core(last)?);
+                    if first > last {
+                        return Err(format!("range {}-{} is backwards", first, last));
+                    }
+                    for core in first..=last {
+                        set.insert(core);
+                    }
+                }
+                None => {
+                    set.insert(parse_core(part)?);
+                }
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl fmt::Display for CpuSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut cores = self.cores().peekable();
+        let mut first = true;
+        while let Some(start) = cores.next() {
+            let mut end = start;
+            while cores.peek() == Some(&(end + 1)) {
+                end = cores.next().unwrap_or(end);
+            }
+            if !first {
+                write!(f, ",")?;
+            }
+            first = false;
+            if end == start {
+                write!(f, "{}", start)?;
+            } else {
+                write!(f, "{}-{}", start, end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for CpuSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CpuSet({})", self)
+    }
+}
+
+impl TryFrom<String> for CpuSet {
+    type Error = String;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<CpuSet> for String {
+    fn from(set: CpuSet) -> Self {
+        set.to_string()
+    }
+}
+
+/// Threads that can be pinned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThreadRole {
+    /// Video capture (`nitrogen-pipewire`)
+    Capture,
+    /// The thread that drives [`Pipeline::process`](crate::Pipeline::process)
+    Encode,
+    /// Audio capture (`nitrogen-audio`)
+    Audio,
+    /// Global hotkey listener (`nitrogen-hotkeys`)
+    Hotkeys,
+}
+
+impl fmt::Display for ThreadRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThreadRole::Capture => write!(f, "capture"),
+            ThreadRole::Encode => write!(f, "encode"),
+            ThreadRole::Audio => write!(f, "audio"),
+            ThreadRole::Hotkeys => write!(f, "hotkeys"),
+        }
+    }
+}
+
+/// Cores each thread role is pinned to (None = left to the scheduler)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CpuAffinity {
+    #[serde(default)]
+    pub capture: Option<CpuSet>,
+    #[serde(default)]
+    pub encode: Option<CpuSet>,
+    #[serde(default)]
+    pub audio: Option<CpuSet>,
+    #[serde(default)]
+    pub hotkeys: Option<CpuSet>,
+}
+
+impl CpuAffinity {
+    /// No pinning for any thread
+    pub const NONE: Self = Self {
+        capture: None,
+        encode: None,
+        audio: None,
+        hotkeys: None,
+    };
+
+    /// Cores for a role
+    pub fn get(&self, role: ThreadRole) -> Option<CpuSet> {
+        match role {
+            ThreadRole::Capture => self.capture,
+            ThreadRole::Encode => self.encode,
+            ThreadRole::Audio => self.audio,
+            ThreadRole::Hotkeys => self.hotkeys,
+        }
+    }
+
+    /// Whether no role is pinned
+    pub fn is_empty(&self) -> bool {
+        *self == Self::NONE
+    }
+
+    /// Roles that have cores, with their cores
+    pub fn iter(&self) -> impl Iterator<Item = (ThreadRole, CpuSet)> + '_ {
+        [
+            ThreadRole::Capture,
+            ThreadRole::Encode,
+            ThreadRole::Audio,
+            ThreadRole::Hotkeys,
+        ]
+        .into_iter()
+        .filter_map(|role| self.get(role).map(|cores| (role, cores)))
+    }
+}
+
+/// Affinity applied by [`pin_current_thread`]
+static CONFIGURED: RwLock<CpuAffinity> = RwLock::new(CpuAffinity::NONE);
+
+/// Set the cores threads pin themselves to from now on
+///
+/// Threads that are already running keep their current affinity.
+pub fn configure(affinity: CpuAffinity) {
+    match CONFIGURED.write() {
+        Ok(mut configured) => *configured = affinity,
+        Err(poisoned) => *poisoned.into_inner() = affinity,
+    }
+}
+
+/// The affinity set with [`configure`]
+pub fn configured() -> CpuAffinity {
+    match CONFIGURED.read() {
+        Ok(configured) => *configured,
+        Err(poisoned) => *poisoned.into_inner(),
+    }
+}
+
+/// Pin the calling thread to the cores configured for `role`
+///
+/// Does nothing when the role has no cores. A failure (a core that is
+/// offline, or outside the process's cgroup) is logged and the thread keeps
+/// running unpinned.
+pub fn pin_current_thread(role: ThreadRole) {
+    let Some(cores) = configured().get(role) else {
+        return;
+    };
+    match set_current_thread_affinity(&cores) {
+        Ok(()) => debug!("Pinned {} thread to cores {}", role, cores),
+        Err(e) => warn!("Could not pin {} thread to cores {}: {}", role, cores, e),
+    }
+}
+
+/// Restrict the calling thread to `cores`
+#[cfg(target_os = "linux")]
+pub fn set_current_thread_affinity(cores: &CpuSet) -> Result<()> {
+    if cores.is_empty() {
+        return Err(NitrogenError::config("CPU affinity needs at least one core"));
+    }
+
+    // SAFETY: cpu_set_t is a plain bit array, so all zeroes is an empty set,
+    // and every core is below CPU_SETSIZE
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for core in cores.cores() {
+        unsafe { libc::CPU_SET(core, &mut set) };
+    }
+
+    // SAFETY: pid 0 is the calling thread and `set` outlives the call
+    let ret = unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+/// Restrict the calling thread to `cores`
+#[cfg(not(target_os = "linux"))]
+pub fn set_current_thread_affinity(_cores: &CpuSet) -> Result<()> {
+    Err(NitrogenError::Unsupported(
+        "CPU affinity is only supported on Linux".to_string(),
+    ))
+}
+
+/// Cores the calling thread may run on
+#[cfg(target_os = "linux")]
+pub fn current_thread_affinity() -> Result<CpuSet> {
+    // SAFETY: as in set_current_thread_affinity; the kernel fills `set`
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    let ret =
+        unsafe { libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(CpuSet::from_cores(
+        (0..MAX_CPUS).filter(|&core| unsafe { libc::CPU_ISSET(core, &set) }),
+    ))
+}
+
+/// Cores the calling thread may run on
+#[cfg(not(target_os = "linux"))]
+pub fn current_thread_affinity() -> Result<CpuSet> {
+    Err(NitrogenError::Unsupported(
+        "CPU affinity is only supported on Linux".to_string(),
+    ))
+}
+
+/// Number of cores the system has configured, online or not
+pub fn cpu_count() -> Option<usize> {
+    #[cfg(target_os = "linux")]
+    {
+        // SAFETY: sysconf has no preconditions
+        let count = unsafe { libc::sysconf(libc::_SC_NPROCESSORS_CONF) };
+        if count > 0 {
+            return Some(count as usize);
+        }
+    }
+    std::thread::available_parallelism().ok().map(|n| n.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_set_parse_and_display() {
+        let set: CpuSet = "0-3, 8,10-11".parse().unwrap();
+        assert_eq!(set.cores().collect::<Vec<_>>(), vec![0, 1, 2, 3, 8, 10, 11]);
+        assert_eq!(set.to_string(), "0-3,8,10-11");
+        assert_eq!(set.max_core(), Some(11));
+        assert_eq!("5".parse::<CpuSet>().unwrap(), CpuSet::from_cores([5]));
+
+        assert!("".parse::<CpuSet>().is_err());
+        assert!("3-1".parse::<CpuSet>().is_err());
+        assert!("1,x".parse::<CpuSet>().is_err());
+        assert!("1024".parse::<CpuSet>().is_err());
+    }
+
+    #[test]
+    fn test_affinity_roles() {
+        let affinity = CpuAffinity {
+            encode: Some(CpuSet::from_cores([2, 3])),
+            ..Default::default()
+        };
+        assert_eq!(affinity.get(ThreadRole::Encode), Some(CpuSet::from_cores([2, 3])));
+        assert_eq!(affinity.get(ThreadRole::Capture), None);
+        assert!(!affinity.is_empty());
+        assert!(CpuAffinity::default().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_pin_thread_to_single_core() {
+        // Use a core this process is allowed on, whatever the test runner's mask
+        let allowed = current_thread_affinity().unwrap();
+        let core = allowed.cores().next().unwrap();
+
+        let reported = std::thread::spawn(move || {
+            set_current_thread_affinity(&CpuSet::from_cores([core])).unwrap();
+            current_thread_affinity().unwrap()
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(reported, CpuSet::from_cores([core]));
+        // Only the spawned thread was pinned
+        assert_eq!(current_thread_affinity().unwrap(), allowed);
+    }
+}
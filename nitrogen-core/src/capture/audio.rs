@@ -1,6 +1,7 @@
 //! PipeWire audio capture
 //!
-//! Captures desktop audio (monitor) or microphone input via PipeWire.
+//! Captures desktop audio (monitor), microphone input or a single
+//! application's output via PipeWire.
 
 use pipewire as pw;
 use pw::spa::param::audio::AudioFormat as SpaAudioFormat;
@@ -11,13 +12,18 @@ use pw::spa::pod::Pod;
 use pw::spa::utils::Direction;
 use pw::stream::{Stream, StreamFlags, StreamState};
 
+use std::cell::RefCell;
+use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::broadcast;
 use tracing::{debug, error, info, trace, warn};
 
-use crate::config::AudioSource;
+use super::stream::request_node_latency;
+use crate::affinity::{self, ThreadRole};
+use crate::config::{AudioSource, MicChannel};
 use crate::error::{NitrogenError, Result};
 use crate::types::{AudioFormat, AudioFrame, AudioSampleFormat};
 
@@ -39,6 +45,159 @@ struct AudioInfo {
     channels: u32,
 }
 
+/// How long to wait for PipeWire to list its nodes
+const REGISTRY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// An application currently playing audio
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioApplication {
+    /// PipeWire node id of the application's output stream
+    pub node_id: u32,
+    /// Object serial (stable target for `target.object`)
+    pub serial: Option<String>,
+    /// Application name (falls back to the node name)
+    pub name: String,
+    /// Process binary, e.g. `firefox`
+    pub binary: Option<String>,
+    /// PipeWire node name
+    pub node_name: Option<String>,
+    /// What the stream is playing, e.g. a tab or track title
+    pub media_name: Option<String>,
+}
+
+impl AudioApplication {
+    /// Check whether `query` names this application
+    ///
+    /// A numeric query matches the node id; anything else is compared
+    /// case-insensitively with the application name, binary and node name.
+    pub fn matches(&self, query: &str) -> bool {
+        if let Ok(id) = query.parse::<u32>() {
+            return id == self.node_id;
+        }
+        [Some(&self.name), self.binary.as_ref(), self.node_name.as_ref()]
+            .into_iter()
+            .flatten()
+            .any(|name| name.eq_ignore_ascii_case(query))
+    }
+
+    /// Value for the capture stream's `target.object` property
+    fn target_object(&self) -> String {
+        self.serial
+            .clone()
+            .unwrap_or_else(|| self.node_id.to_string())
+    }
+}
+
+/// Find the first application matching `query` (name, binary or node id)
+pub fn find_audio_application<'a>(
+    apps: &'a [AudioApplication],
+    query: &str,
+) -> Option<&'a AudioApplication> {
+    apps.iter().find(|app| app.matches(query))
+}
+
+/// List applications currently playing audio
+///
+/// Enumerates the PipeWire audio output streams (`Stream/Output/Audio`
+/// nodes). An application with several streams, like a browser with
+/// several tabs playing, is listed once per stream.
+pub fn list_audio_applications() -> Result<Vec<AudioApplication>> {
+    pw::init();
+
+    let mainloop = pw::main_loop::MainLoop::new(None)
+        .map_err(|e| NitrogenError::pipewire(format!("Failed to create main loop: {}", e)))?;
+    let context = pw::context::Context::new(&mainloop)
+        .map_err(|e| NitrogenError::pipewire(format!("Failed to create context: {}", e)))?;
+    let core = context
+        .connect(None)
+        .map_err(|e| NitrogenError::pipewire(format!("Failed to connect to PipeWire: {}", e)))?;
+    let registry = core
+        .get_registry()
+        .map_err(|e| NitrogenError::pipewire(format!("Failed to get registry: {}", e)))?;
+
+    let apps = Rc::new(RefCell::new(Vec::new()));
+    let apps_clone = apps.clone();
+    let _registry_listener = registry
+        .add_listener_local()
+        .global(move |global| {
+            if global.type_ != pw::types::ObjectType::Node {
+                return;
+            }
+            let Some(props) = global.props else { return };
+            if props.get("media.class") != Some("Stream/Output/Audio") {
+                return;
+            }
+
+            let node_name = props.get("node.name").map(str::to_string);
+            let name = props
+                .get("application.name")
+                .map(str::to_string)
+                .or_else(|| node_name.clone())
+                .unwrap_or_else(|| format!("node {}", global.id));
+            apps_clone.borrow_mut().push(AudioApplication {
+                node_id: global.id,
+                serial: props.get("object.serial").map(str::to_string),
+                name,
+                binary: props.get("application.process.binary").map(str::to_string),
+                node_name,
+                media_name: props.get("media.name").map(str::to_string),
+            });
+        })
+        .register();
+
+    // The registry has announced every node once the sync round trip is done
+    let pending = core
+        .sync(0)
+        .map_err(|e| NitrogenError::pipewire(format!("Failed to sync with PipeWire: {}", e)))?;
+    let mainloop_weak = mainloop.downgrade();
+    let _core_listener = core
+        .add_listener_local()
+        .done(move |id, seq| {
+            if id == pw::core::PW_ID_CORE && seq == pending {
+                if let Some(mainloop) = mainloop_weak.upgrade() {
+                    mainloop.quit();
+                }
+            }
+        })
+        .register();
+
+    let mainloop_weak = mainloop.downgrade();
+    let timer = mainloop.loop_().add_timer(move |_| {
+        warn!("Timed out listing PipeWire audio applications");
+        if let Some(mainloop) = mainloop_weak.upgrade() {
+            mainloop.quit();
+        }
+    });
+    let _ = timer.update_timer(Some(REGISTRY_TIMEOUT), None);
+
+    mainloop.run();
+
+    let apps = apps.borrow().clone();
+    debug!("Found {} audio application stream(s)", apps.len());
+    Ok(apps)
+}
+
+/// Look up the application an `AudioSource::Application` names
+fn resolve_application(query: &str) -> Result<AudioApplication> {
+    let apps = list_audio_applications()?;
+    if let Some(app) = find_audio_application(&apps, query) {
+        return Ok(app.clone());
+    }
+
+    let playing = if apps.is_empty() {
+        "none".to_string()
+    } else {
+        apps.iter()
+            .map(|app| format!("{} (node {})", app.name, app.node_id))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    Err(NitrogenError::SourceNotFound(format!(
+        "audio application '{}' is not playing (playing now: {})",
+        query, playing
+    )))
+}
+
 /// PipeWire audio capture stream
 pub struct AudioCaptureStream {
     /// Sender for audio frames
@@ -55,14 +214,59 @@ impl AudioCaptureStream {
     /// Create a new audio capture stream
     ///
     /// # Arguments
-    /// * `source` - What audio to capture (desktop, microphone, or both)
-    pub fn new(source: AudioSource) -> Result<Self> {
+    /// * `source` - What audio to capture (desktop, microphone, both, or one application)
+    /// * `preferred_format` - Sample format to ask PipeWire for first
+    ///
+    /// The preference only reorders the formats offered during negotiation.
+    /// If the device can't deliver it, whatever it does deliver is converted
+    /// to f32 as before.
+    pub fn new(source: AudioSource, preferred_format: AudioSampleFormat) -> Result<Self> {
+        Self::with_mic_channel(source, MicChannel::default(), preferred_format)
+    }
+
+    /// Create a new audio capture stream with microphone channel handling
+    ///
+    /// The channel mode is applied to microphone captures only, in the f32
+    /// sample path before any volume or mixing.
+    ///
+    /// An `AudioSource::Application` is resolved to its PipeWire node here,
+    /// so an application that isn't playing fails with `SourceNotFound`.
+    pub fn with_mic_channel(
+        source: AudioSource,
+        mic_channel: MicChannel,
+        preferred_format: AudioSampleFormat,
+    ) -> Result<Self> {
+        Self::with_latency_quantum(source, mic_channel, preferred_format, None)
+    }
+
+    /// Create a new audio capture stream that requests a PipeWire quantum
+    ///
+    /// `latency_quantum` is asked for as `node.latency` (see
+    /// [`request_node_latency`]); None keeps the server's default.
+    pub fn with_latency_quantum(
+        source: AudioSource,
+        mic_channel: MicChannel,
+        preferred_format: AudioSampleFormat,
+        latency_quantum: Option<u32>,
+    ) -> Result<Self> {
         if source == AudioSource::None {
             return Err(NitrogenError::config(
                 "Cannot create audio stream with AudioSource::None",
             ));
         }
 
+        let target = match &source {
+            AudioSource::Application(query) => {
+                let app = resolve_application(query)?;
+                info!(
+                    "Capturing audio from application '{}' (node {})",
+                    app.name, app.node_id
+                );
+                Some(app)
+            }
+            _ => None,
+        };
+
         let (frame_tx, _) = broadcast::channel(16);
         let (shutdown_tx, shutdown_rx) = mpsc::channel();
 
@@ -74,14 +278,28 @@ impl AudioCaptureStream {
 
         let frame_tx_clone = frame_tx.clone();
         let shared_clone = shared.clone();
-        let is_desktop = matches!(source, AudioSource::Desktop | AudioSource::Both);
+        let is_desktop = target.is_some()
+            || matches!(source, AudioSource::Desktop | AudioSource::Both);
+        let channel_mode = if is_desktop {
+            MicChannel::Stereo
+        } else {
+            mic_channel
+        };
 
         let pw_thread = std::thread::Builder::new()
             .name("nitrogen-audio".to_string())
             .spawn(move || {
-                if let Err(e) =
-                    run_audio_loop(is_desktop, frame_tx_clone, shutdown_rx, shared_clone)
-                {
+                affinity::pin_current_thread(ThreadRole::Audio);
+                if let Err(e) = run_audio_loop(
+                    is_desktop,
+                    target,
+                    channel_mode,
+                    preferred_format,
+                    latency_quantum,
+                    frame_tx_clone,
+                    shutdown_rx,
+                    shared_clone,
+                ) {
                     error!("Audio capture loop error: {}", e);
                 }
             })
@@ -163,9 +381,113 @@ fn spa_to_sample_format(spa: SpaAudioFormat) -> AudioSampleFormat {
     }
 }
 
+/// Convert our sample format to the little-endian SPA format
+fn sample_format_to_spa(format: AudioSampleFormat) -> SpaAudioFormat {
+    match format {
+        AudioSampleFormat::F32LE => SpaAudioFormat::F32LE,
+        AudioSampleFormat::S16LE => SpaAudioFormat::S16LE,
+        AudioSampleFormat::S32LE => SpaAudioFormat::S32LE,
+    }
+}
+
+/// Formats offered during negotiation, most preferred first
+///
+/// Every format the process callback can convert is listed, so a device that
+/// can't honor the preference still negotiates.
+fn format_preference(preferred: AudioSampleFormat) -> Vec<SpaAudioFormat> {
+    let preferred = sample_format_to_spa(preferred);
+    let mut formats = vec![preferred];
+    formats.extend(
+        [
+            SpaAudioFormat::F32LE,
+            SpaAudioFormat::S32LE,
+            SpaAudioFormat::S16LE,
+            SpaAudioFormat::F32BE,
+            SpaAudioFormat::S32BE,
+            SpaAudioFormat::S16BE,
+        ]
+        .into_iter()
+        .filter(|&format| format != preferred),
+    );
+    formats
+}
+
+/// Serialize the EnumFormat POD for the audio stream
+fn build_format_pod(preferred: AudioSampleFormat) -> Result<Vec<u8>> {
+    use pw::spa::pod::{ChoiceValue, Property, PropertyFlags, Value};
+    use pw::spa::utils::{Choice, ChoiceEnum, ChoiceFlags, Id};
+
+    let formats: Vec<Id> = format_preference(preferred)
+        .into_iter()
+        .map(|format| Id(format.as_raw()))
+        .collect();
+
+    let mut obj = pw::spa::pod::object!(
+        pw::spa::utils::SpaTypes::ObjectParamFormat,
+        pw::spa::param::ParamType::EnumFormat,
+        pw::spa::pod::property!(
+            pw::spa::param::format::FormatProperties::MediaType,
+            Id,
+            MediaType::Audio
+        ),
+        pw::spa::pod::property!(
+            pw::spa::param::format::FormatProperties::MediaSubtype,
+            Id,
+            MediaSubtype::Raw
+        ),
+    );
+    obj.properties.push(Property {
+        key: pw::spa::param::format::FormatProperties::AudioFormat.as_raw(),
+        flags: PropertyFlags::empty(),
+        value: Value::Choice(ChoiceValue::Id(Choice(
+            ChoiceFlags::empty(),
+            ChoiceEnum::Enum {
+                default: formats[0],
+                alternatives: formats,
+            },
+        ))),
+    });
+
+    let values: Vec<u8> = pw::spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &Value::Object(obj),
+    )
+    .map_err(|e| NitrogenError::pipewire(format!("Failed to serialize audio format: {:?}", e)))?
+    .0
+    .into_inner();
+
+    Ok(values)
+}
+
+/// Apply microphone channel handling to interleaved f32 samples
+///
+/// The channel count is preserved: `Left`/`Right` copy the selected channel
+/// to every output channel and `Mono` writes the average of all channels.
+pub fn apply_mic_channel(samples: &mut [f32], channels: u32, mode: MicChannel) {
+    let channels = channels as usize;
+    if channels < 2 || mode == MicChannel::Stereo {
+        return;
+    }
+
+    for frame in samples.chunks_exact_mut(channels) {
+        let value = match mode {
+            MicChannel::Left => frame[0],
+            MicChannel::Right => frame[1],
+            // Mono (stereo returned above)
+            _ => frame.iter().sum::<f32>() / channels as f32,
+        };
+        frame.fill(value);
+    }
+}
+
 /// Run the PipeWire audio main loop
+#[allow(clippy::too_many_arguments)]
 fn run_audio_loop(
     is_desktop: bool,
+    target: Option<AudioApplication>,
+    channel_mode: MicChannel,
+    preferred_format: AudioSampleFormat,
+    latency_quantum: Option<u32>,
     frame_tx: broadcast::Sender<Arc<AudioFrame>>,
     shutdown_rx: mpsc::Receiver<()>,
     shared: Arc<SharedState>,
@@ -173,8 +495,11 @@ fn run_audio_loop(
     pw::init();
 
     info!(
-        "Initializing PipeWire audio capture (desktop={})",
-        is_desktop
+        "Initializing PipeWire audio capture (desktop={}, app={:?}, channel={}, prefer={:?})",
+        is_desktop,
+        target.as_ref().map(|app| app.name.as_str()),
+        channel_mode,
+        preferred_format
     );
 
     let mainloop = pw::main_loop::MainLoop::new(None)
@@ -190,7 +515,17 @@ fn run_audio_loop(
         .map_err(|e| NitrogenError::pipewire(format!("Failed to connect to PipeWire: {}", e)))?;
 
     // Build stream properties
-    let props = if is_desktop {
+    let mut props = if let Some(app) = &target {
+        // Capture one application's output stream, and stop rather than
+        // falling back to another source when it goes away
+        pw::properties::properties! {
+            *pw::keys::MEDIA_TYPE => "Audio",
+            *pw::keys::MEDIA_CATEGORY => "Capture",
+            *pw::keys::MEDIA_ROLE => "Screen",
+            "target.object" => app.target_object(),
+            "node.dont-reconnect" => "true",
+        }
+    } else if is_desktop {
         // Capture desktop audio (what you hear)
         pw::properties::properties! {
             *pw::keys::MEDIA_TYPE => "Audio",
@@ -206,6 +541,7 @@ fn run_audio_loop(
             *pw::keys::MEDIA_ROLE => "Communication",
         }
     };
+    request_node_latency(&mut props, latency_quantum);
 
     let stream = Stream::new(&core, "nitrogen-audio", props)
         .map_err(|e| NitrogenError::pipewire(format!("Failed to create audio stream: {}", e)))?;
@@ -213,12 +549,14 @@ fn run_audio_loop(
     // User data for callbacks
     struct UserData {
         format: Option<AudioInfo>,
+        channel_mode: MicChannel,
         frame_tx: broadcast::Sender<Arc<AudioFrame>>,
         shared: Arc<SharedState>,
     }
 
     let user_data = UserData {
         format: None,
+        channel_mode,
         frame_tx,
         shared: shared.clone(),
     };
@@ -308,7 +646,7 @@ fn run_audio_loop(
                 let samples_raw = &samples_raw[..chunk_size];
 
                 // Convert to f32 samples
-                let samples: Vec<f32> = match format.format {
+                let mut samples: Vec<f32> = match format.format {
                     SpaAudioFormat::F32LE | SpaAudioFormat::F32P => samples_raw
                         .chunks_exact(4)
                         .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
@@ -357,6 +695,8 @@ fn run_audio_loop(
                     }
                 };
 
+                apply_mic_channel(&mut samples, format.channels, user_data.channel_mode);
+
                 let sample_count = samples.len() / format.channels as usize;
 
                 let frame = AudioFrame {
@@ -391,43 +731,8 @@ fn run_audio_loop(
             NitrogenError::pipewire(format!("Failed to register audio listener: {}", e))
         })?;
 
-    // Build format parameters - prefer F32LE stereo at 48kHz
-    // Use simple format specification (PipeWire will negotiate compatible format)
-    let obj = pw::spa::pod::object!(
-        pw::spa::utils::SpaTypes::ObjectParamFormat,
-        pw::spa::param::ParamType::EnumFormat,
-        pw::spa::pod::property!(
-            pw::spa::param::format::FormatProperties::MediaType,
-            Id,
-            MediaType::Audio
-        ),
-        pw::spa::pod::property!(
-            pw::spa::param::format::FormatProperties::MediaSubtype,
-            Id,
-            MediaSubtype::Raw
-        ),
-        pw::spa::pod::property!(
-            pw::spa::param::format::FormatProperties::AudioFormat,
-            Choice,
-            Enum,
-            Id,
-            SpaAudioFormat::F32LE,
-            SpaAudioFormat::F32LE,
-            SpaAudioFormat::S32LE,
-            SpaAudioFormat::S16LE,
-            SpaAudioFormat::F32BE,
-            SpaAudioFormat::S32BE,
-            SpaAudioFormat::S16BE
-        ),
-    );
-
-    let values: Vec<u8> = pw::spa::pod::serialize::PodSerializer::serialize(
-        std::io::Cursor::new(Vec::new()),
-        &pw::spa::pod::Value::Object(obj),
-    )
-    .map_err(|e| NitrogenError::pipewire(format!("Failed to serialize audio format: {:?}", e)))?
-    .0
-    .into_inner();
+    // Build format parameters - PipeWire will negotiate a compatible format
+    let values = build_format_pod(preferred_format)?;
 
     let pod = Pod::from_bytes(&values)
         .ok_or_else(|| NitrogenError::pipewire("Failed to create audio Pod"))?;
@@ -436,7 +741,7 @@ fn run_audio_loop(
     stream
         .connect(
             Direction::Input,
-            None, // Auto-connect to default source
+            None, // Auto-connect to the default source or `target.object`
             StreamFlags::AUTOCONNECT | StreamFlags::MAP_BUFFERS | StreamFlags::RT_PROCESS,
             &mut params,
         )
@@ -482,4 +787,116 @@ mod tests {
             AudioSampleFormat::S32LE
         );
     }
+
+    #[test]
+    fn test_format_preference_order() {
+        assert_eq!(
+            format_preference(AudioSampleFormat::F32LE),
+            vec![
+                SpaAudioFormat::F32LE,
+                SpaAudioFormat::S32LE,
+                SpaAudioFormat::S16LE,
+                SpaAudioFormat::F32BE,
+                SpaAudioFormat::S32BE,
+                SpaAudioFormat::S16BE,
+            ]
+        );
+
+        let s16 = format_preference(AudioSampleFormat::S16LE);
+        assert_eq!(s16[0], SpaAudioFormat::S16LE);
+        assert_eq!(s16.len(), 6);
+        assert_eq!(
+            s16.iter().filter(|&&f| f == SpaAudioFormat::S16LE).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_format_pod_lists_preferred_first() {
+        use pw::spa::pod::deserialize::PodDeserializer;
+        use pw::spa::pod::{ChoiceValue, Value};
+        use pw::spa::utils::{Choice, ChoiceEnum, Id};
+
+        let bytes = build_format_pod(AudioSampleFormat::S32LE).unwrap();
+        let (_, value) = PodDeserializer::deserialize_any_from(&bytes).unwrap();
+        let Value::Object(obj) = value else {
+            panic!("expected an object POD");
+        };
+
+        let prop = obj
+            .properties
+            .iter()
+            .find(|p| p.key == pw::spa::param::format::FormatProperties::AudioFormat.as_raw())
+            .expect("audio format property");
+        let Value::Choice(ChoiceValue::Id(Choice(_, ChoiceEnum::Enum { default, alternatives }))) =
+            &prop.value
+        else {
+            panic!("expected an enum choice of ids");
+        };
+
+        assert_eq!(*default, Id(SpaAudioFormat::S32LE.as_raw()));
+        assert_eq!(alternatives[0], Id(SpaAudioFormat::S32LE.as_raw()));
+    }
+
+    fn app(node_id: u32, name: &str, binary: Option<&str>) -> AudioApplication {
+        AudioApplication {
+            node_id,
+            serial: None,
+            name: name.to_string(),
+            binary: binary.map(str::to_string),
+            node_name: Some(format!("{}-output", name.to_lowercase())),
+            media_name: None,
+        }
+    }
+
+    #[test]
+    fn test_find_audio_application() {
+        let apps = vec![
+            app(87, "Firefox", Some("firefox")),
+            app(92, "WEBRTC VoiceEngine", Some("Discord")),
+        ];
+
+        assert_eq!(find_audio_application(&apps, "firefox").unwrap().node_id, 87);
+        assert_eq!(find_audio_application(&apps, "FIREFOX").unwrap().node_id, 87);
+        assert_eq!(find_audio_application(&apps, "discord").unwrap().node_id, 92);
+        assert_eq!(find_audio_application(&apps, "92").unwrap().node_id, 92);
+        assert_eq!(
+            find_audio_application(&apps, "firefox-output").unwrap().node_id,
+            87
+        );
+        assert!(find_audio_application(&apps, "fire").is_none());
+        assert!(find_audio_application(&apps, "12").is_none());
+    }
+
+    #[test]
+    fn test_application_target_prefers_serial() {
+        let mut firefox = app(87, "Firefox", Some("firefox"));
+        assert_eq!(firefox.target_object(), "87");
+        firefox.serial = Some("1204".to_string());
+        assert_eq!(firefox.target_object(), "1204");
+    }
+
+    #[test]
+    fn test_mic_channel_mono_downmix() {
+        let mut samples = vec![1.0, 0.0, 1.0, 0.0];
+        apply_mic_channel(&mut samples, 2, MicChannel::Mono);
+        for s in samples {
+            assert!((s - 0.5).abs() < 1e-6, "downmixed sample {} should be ~0.5", s);
+        }
+    }
+
+    #[test]
+    fn test_mic_channel_select() {
+        let mut left = vec![1.0, 0.0, 1.0, 0.0];
+        apply_mic_channel(&mut left, 2, MicChannel::Left);
+        assert_eq!(left, vec![1.0; 4]);
+
+        let mut right = vec![1.0, 0.0, 1.0, 0.0];
+        apply_mic_channel(&mut right, 2, MicChannel::Right);
+        assert_eq!(right, vec![0.0; 4]);
+
+        let mut stereo = vec![1.0, 0.0];
+        apply_mic_channel(&mut stereo, 2, MicChannel::Stereo);
+        assert_eq!(stereo, vec![1.0, 0.0]);
+    }
 }
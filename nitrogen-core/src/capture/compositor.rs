@@ -0,0 +1,667 @@
+//! Composite several capture sources into one output frame
+//!
+//! Each layer places a source in a rectangle of the output. Layers keep the
+//! latest frame their source delivered, and the composite is redrawn at the
+//! output frame rate, so sources running at different rates (or stalling)
+//! simply hold their last picture. Layers are drawn in ascending z-order on
+//! a black background and stretched to their rectangle.
+//!
+//! Monitor layers share one portal session, so a single picker dialog asks
+//! for all of them; windows, and monitors the session didn't grant, get a
+//! session of their own.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, trace, warn};
+
+use super::outputs::{self, is_picker_id, OutputInfo};
+use super::portal::{match_stream, CaptureType, PortalCapture, SessionInfo, SessionStream};
+use super::stream::{CaptureStream, StreamOptions};
+use crate::config::CursorMode;
+use crate::error::{NitrogenError, Result};
+use crate::types::{CaptureSource, Frame, FrameData, FrameFormat};
+
+/// `DRM_FORMAT_XRGB8888`, the format of composited frames (B, G, R, X in memory)
+const COMPOSITE_FOURCC: u32 = 0x34325258;
+
+/// A source placed in the composite output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayoutLayer {
+    /// What to capture for this layer
+    pub source: CaptureSource,
+    /// Left edge in output pixels
+    pub x: u32,
+    /// Top edge in output pixels
+    pub y: u32,
+    /// Width in output pixels
+    pub width: u32,
+    /// Height in output pixels
+    pub height: u32,
+    /// Stacking order; higher is drawn on top, ties keep list order
+    #[serde(default)]
+    pub z: i32,
+}
+
+impl LayoutLayer {
+    /// Create a layer at the given rectangle
+    pub fn new(source: CaptureSource, x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            source,
+            x,
+            y,
+            width,
+            height,
+            z: 0,
+        }
+    }
+
+    /// Set the stacking order
+    pub fn with_z(mut self, z: i32) -> Self {
+        self.z = z;
+        self
+    }
+}
+
+impl std::str::FromStr for LayoutLayer {
+    type Err = String;
+
+    /// Parse `SOURCE@X,Y,WxH` with an optional `,zN` suffix
+    ///
+    /// e.g. `window:firefox@960,0,960x1080` or `monitor:DP-2@0,0,640x360,z1`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || format!("Invalid layer '{}' (expected SOURCE@X,Y,WxH[,zN])", s);
+
+        let (source, rect) = s.rsplit_once('@').ok_or_else(invalid)?;
+        let source: CaptureSource = source.parse()?;
+
+        let mut parts = rect.split(',').map(str::trim);
+        let x = parts.next().and_then(|p| p.parse().ok()).ok_or_else(invalid)?;
+        let y = parts.next().and_then(|p| p.parse().ok()).ok_or_else(invalid)?;
+        let (width, height) = parts
+            .next()
+            .and_then(|p| p.split_once('x'))
+            .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+            .ok_or_else(invalid)?;
+        let z = match parts.next() {
+            Some(z) => z
+                .strip_prefix('z')
+                .and_then(|z| z.parse().ok())
+                .ok_or_else(invalid)?,
+            None => 0,
+        };
+        if parts.next().is_some() || width == 0 || height == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            source,
+            x,
+            y,
+            width,
+            height,
+            z,
+        })
+    }
+}
+
+/// Tile sources in an N-up grid covering the output
+///
+/// Columns come first (2 sources side by side, 3-4 in a 2x2 grid, ...).
+pub fn grid_layout(sources: &[CaptureSource], width: u32, height: u32) -> Vec<LayoutLayer> {
+    if sources.is_empty() {
+        return Vec::new();
+    }
+
+    let count = sources.len() as u32;
+    let cols = (1..=count).find(|c| c * c >= count).unwrap_or(count);
+    let rows = count.div_ceil(cols);
+    let cell_w = width / cols;
+    let cell_h = height / rows;
+
+    sources
+        .iter()
+        .enumerate()
+        .map(|(i, source)| {
+            let (col, row) = (i as u32 % cols, i as u32 / cols);
+            LayoutLayer::new(source.clone(), col * cell_w, row * cell_h, cell_w, cell_h)
+        })
+        .collect()
+}
+
+/// Latest picture of a layer, converted to packed BGRA
+struct LayerImage {
+    width: u32,
+    height: u32,
+    bgra: Vec<u8>,
+}
+
+/// Draws layers into a single output frame
+pub struct Compositor {
+    width: u32,
+    height: u32,
+    /// Layers in list order
+    layers: Vec<LayoutLayer>,
+    /// Latest image per layer
+    images: Vec<Option<LayerImage>>,
+    /// Layer indices in drawing order
+    order: Vec<usize>,
+}
+
+impl Compositor {
+    /// Create a compositor for an output of `width`x`height`
+    pub fn new(width: u32, height: u32, layers: Vec<LayoutLayer>) -> Self {
+        let mut order: Vec<usize> = (0..layers.len()).collect();
+        // Stable, so equal z keeps list order
+        order.sort_by_key(|&i| layers[i].z);
+
+        Self {
+            width,
+            height,
+            images: layers.iter().map(|_| None).collect(),
+            layers,
+            order,
+        }
+    }
+
+    /// Number of layers
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Store the latest frame of a layer
+    ///
+    /// Returns false if the frame can't be read on the CPU (DMA-BUF) or has
+    /// an unsupported format; the layer then keeps its previous picture.
+    pub fn update(&mut self, layer: usize, frame: &Frame) -> bool {
+        let Some(slot) = self.images.get_mut(layer) else {
+            return false;
+        };
+        let Some(bgra) = frame.to_bgra8() else {
+            trace!("Compositor: layer {} frame not readable on the CPU", layer);
+            return false;
+        };
+
+        *slot = Some(LayerImage {
+            width: frame.format.width,
+            height: frame.format.height,
+            bgra: bgra.into_owned(),
+        });
+        true
+    }
+
+    /// Whether any layer has produced a picture yet
+    pub fn has_content(&self) -> bool {
+        self.images.iter().any(Option::is_some)
+    }
+
+    /// Draw all layers into a new frame
+    ///
+    /// Returns `None` until at least one layer has a picture. Layers that
+    /// haven't delivered a frame yet stay black.
+    pub fn compose(&self, pts: u64) -> Option<Frame> {
+        if !self.has_content() {
+            return None;
+        }
+
+        let stride = self.width as usize * 4;
+        let mut canvas = vec![0u8; stride * self.height as usize];
+        for &index in &self.order {
+            if let Some(image) = &self.images[index] {
+                self.draw(&mut canvas, &self.layers[index], image);
+            }
+        }
+
+        Some(Frame {
+            format: FrameFormat {
+                width: self.width,
+                height: self.height,
+                fourcc: COMPOSITE_FOURCC,
+                stride: stride as u32,
+            },
+            data: FrameData::Memory(canvas),
+            pts,
+            hdr_metadata: None,
+        })
+    }
+
+    /// Stretch one layer image into its rectangle (nearest neighbour)
+    fn draw(&self, canvas: &mut [u8], layer: &LayoutLayer, image: &LayerImage) {
+        let x_start = layer.x.min(self.width);
+        let x_end = layer.x.saturating_add(layer.width).min(self.width);
+        let y_start = layer.y.min(self.height);
+        let y_end = layer.y.saturating_add(layer.height).min(self.height);
+        if x_start >= x_end || y_start >= y_end || image.width == 0 || image.height == 0 {
+            return;
+        }
+
+        let src_stride = image.width as usize * 4;
+        let src_x: Vec<usize> = (x_start..x_end)
+            .map(|x| ((x - layer.x) as u64 * image.width as u64 / layer.width as u64) as usize * 4)
+            .collect();
+
+        for y in y_start..y_end {
+            let sy = ((y - layer.y) as u64 * image.height as u64 / layer.height as u64) as usize;
+            let src_row = &image.bgra[sy * src_stride..(sy + 1) * src_stride];
+            let row_start = (y as usize * self.width as usize + x_start as usize) * 4;
+            let row_end = row_start + (x_end - x_start) as usize * 4;
+
+            for (dst, &sx) in canvas[row_start..row_end].chunks_exact_mut(4).zip(&src_x) {
+                dst[..3].copy_from_slice(&src_row[sx..sx + 3]);
+                dst[3] = 0xff;
+            }
+        }
+    }
+}
+
+/// Match monitor layers to the streams of a shared portal session
+///
+/// `wanted` holds, per layer, the output a named monitor layer asks for
+/// (None for picker layers). Named layers take their matching stream first,
+/// then picker layers take the remaining streams in order. Layers left
+/// without a stream get None and need a session of their own.
+fn assign_streams(
+    wanted: &[Option<&OutputInfo>],
+    streams: &[(Option<&str>, Option<(i32, i32)>)],
+) -> Vec<Option<usize>> {
+    let mut used = vec![false; streams.len()];
+    let mut assigned = vec![None; wanted.len()];
+
+    for (layer, output) in wanted.iter().enumerate() {
+        let Some(output) = output else { continue };
+        // Taken streams can't match again
+        let candidates: Vec<_> = streams
+            .iter()
+            .zip(&used)
+            .map(|(&stream, &taken)| if taken { (None, None) } else { stream })
+            .collect();
+        if let Some(i) = match_stream(&candidates, output) {
+            used[i] = true;
+            assigned[layer] = Some(i);
+        }
+    }
+
+    let mut free = (0..streams.len()).filter(|&i| !used[i]);
+    for (layer, output) in wanted.iter().enumerate() {
+        if output.is_none() {
+            assigned[layer] = free.next();
+        }
+    }
+    assigned
+}
+
+/// Captures every layer of a layout and emits composited frames
+///
+/// Monitor layers come from one shared portal session when there are
+/// several; every other layer gets its own session (and picker dialog).
+/// Each layer has its own PipeWire stream. Composited frames are published
+/// at the output frame rate on the same kind of channel as
+/// [`CaptureStream::subscribe`].
+pub struct CompositeCapture {
+    /// Portal sessions (a shared one for monitors may serve several layers)
+    portals: Vec<PortalCapture>,
+    /// Capture streams, one per layer
+    streams: Vec<CaptureStream>,
+    /// Session info per layer
+    sessions: Vec<SessionInfo>,
+    /// Sender for composited frames
+    frame_tx: broadcast::Sender<Arc<Frame>>,
+    /// Layer forwarding and output ticker tasks
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl CompositeCapture {
+    /// Start capturing all layers and compositing them at `fps`
+    pub async fn start(
+        layers: Vec<LayoutLayer>,
+        width: u32,
+        height: u32,
+        fps: u32,
+        cursor_mode: CursorMode,
+        cursor_scale: f32,
+    ) -> Result<Self> {
+        if layers.is_empty() {
+            return Err(NitrogenError::config("Layout has no layers"));
+        }
+
+        let mut portals = Vec::with_capacity(layers.len());
+        let mut captured: Vec<Option<(CaptureStream, SessionInfo)>> =
+            layers.iter().map(|_| None).collect();
+        let stream_options = |session: &SessionInfo| StreamOptions {
+            cursor_scale: (session.cursor_mode == CursorMode::Metadata).then_some(cursor_scale),
+            // The compositor reads pixels on the CPU, so no DMA-BUF
+            ..Default::default()
+        };
+
+        let monitor_layers: Vec<usize> = layers
+            .iter()
+            .enumerate()
+            .filter(|(_, layer)| matches!(layer.source, CaptureSource::Monitor { .. }))
+            .map(|(i, _)| i)
+            .collect();
+        if monitor_layers.len() > 1 {
+            info!("Select {} monitors in the portal dialog", monitor_layers.len());
+            let portal = PortalCapture::new().await?;
+            let granted = portal.start_multi_session(CaptureType::Monitor, cursor_mode).await?;
+
+            let known_outputs = outputs::list_outputs();
+            let wanted: Vec<Option<&OutputInfo>> = monitor_layers
+                .iter()
+                .map(|&i| match &layers[i].source {
+                    CaptureSource::Monitor { id } if !is_picker_id(id) => {
+                        outputs::find_output_in(&known_outputs, id)
+                    }
+                    _ => None,
+                })
+                .collect();
+            let candidates: Vec<_> = granted
+                .iter()
+                .map(|s| (Some(s.source.id.as_str()), Some((s.info.width as i32, s.info.height as i32))))
+                .collect();
+
+            let assigned = assign_streams(&wanted, &candidates);
+            let mut first = true;
+            for (&layer, stream) in monitor_layers.iter().zip(assigned) {
+                let Some(SessionStream { info, source }) = stream.map(|i| granted[i].clone()) else {
+                    continue;
+                };
+                // The session's own fd serves the first stream, the others
+                // open another remote on the same session
+                let fd = if first {
+                    portal.take_pipewire_fd().await?
+                } else {
+                    portal.reopen_pipewire_fd().await?
+                };
+                first = false;
+                debug!(
+                    "Layer {} uses {} from the shared session ({}x{}, node {})",
+                    layer, source.name, info.width, info.height, info.node_id
+                );
+                let stream = CaptureStream::with_options(fd, info.node_id, stream_options(&info))?;
+                captured[layer] = Some((stream, info));
+            }
+
+            let shared = captured.iter().filter(|c| c.is_some()).count();
+            if shared < monitor_layers.len() {
+                warn!(
+                    "The portal granted {} of {} monitors; the rest get their own session",
+                    shared,
+                    monitor_layers.len()
+                );
+            }
+            portals.push(portal);
+        }
+
+        for (index, layer) in layers.iter().enumerate() {
+            if captured[index].is_some() {
+                continue;
+            }
+            info!(
+                "Layout layer {}/{}: select {} for {}x{} at {},{}",
+                index + 1,
+                layers.len(),
+                layer.source,
+                layer.width,
+                layer.height,
+                layer.x,
+                layer.y
+            );
+
+            let portal = PortalCapture::new().await?;
+            let session = match &layer.source {
+                CaptureSource::Monitor { id } if !is_picker_id(id) => {
+                    portal.start_output_session(cursor_mode, id).await?
+                }
+                CaptureSource::Monitor { .. } => {
+                    portal
+                        .start_session(CaptureType::Monitor, cursor_mode, false)
+                        .await?
+                }
+                CaptureSource::Window { .. } => {
+                    portal
+                        .start_session(CaptureType::Window, cursor_mode, false)
+                        .await?
+                }
+            };
+
+            let fd = portal.take_pipewire_fd().await?;
+            let stream = CaptureStream::with_options(fd, session.node_id, stream_options(&session))?;
+
+            debug!(
+                "Layer {} source is {}x{} (node {})",
+                index, session.width, session.height, session.node_id
+            );
+            portals.push(portal);
+            captured[index] = Some((stream, session));
+        }
+
+        let (streams, sessions): (Vec<_>, Vec<_>) = captured.into_iter().flatten().unzip();
+
+        let (frame_tx, _) = broadcast::channel(4);
+        let compositor = Arc::new(parking_lot::Mutex::new(Compositor::new(width, height, layers)));
+        let mut tasks = Vec::with_capacity(streams.len() + 1);
+
+        for (index, stream) in streams.iter().enumerate() {
+            let mut rx = stream.subscribe();
+            let compositor = compositor.clone();
+            tasks.push(tokio::spawn(async move {
+                loop {
+                    match rx.recv().await {
+                        Ok(frame) => {
+                            compositor.lock().update(index, &frame);
+                        }
+                        // Only the latest frame matters
+                        Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            }));
+        }
+
+        let tx = frame_tx.clone();
+        let period = Duration::from_secs_f64(1.0 / fps.max(1) as f64);
+        tasks.push(tokio::spawn(async move {
+            let start = Instant::now();
+            let mut ticker = tokio::time::interval(period);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                ticker.tick().await;
+                let pts = start.elapsed().as_nanos() as u64;
+                let frame = compositor.lock().compose(pts);
+                if let Some(frame) = frame {
+                    // No receivers just means the pipeline isn't reading yet
+                    let _ = tx.send(Arc::new(frame));
+                }
+            }
+        }));
+
+        info!(
+            "Compositing {} sources into {}x{} @ {}fps",
+            streams.len(),
+            width,
+            height,
+            fps
+        );
+
+        Ok(Self {
+            portals,
+            streams,
+            sessions,
+            frame_tx,
+            tasks,
+        })
+    }
+
+    /// Subscribe to composited frames
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Frame>> {
+        self.frame_tx.subscribe()
+    }
+
+    /// Session info per layer, in layout order
+    pub fn sessions(&self) -> &[SessionInfo] {
+        &self.sessions
+    }
+
+    /// Check if any layer is still capturing
+    ///
+    /// A layer whose source went away keeps showing its last picture.
+    pub fn is_running(&self) -> bool {
+        self.streams.iter().any(CaptureStream::is_running)
+    }
+
+    /// Stop all layer streams and close their portal sessions
+    pub async fn stop(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.abort();
+        }
+        for stream in &mut self.streams {
+            stream.stop();
+        }
+        for portal in &self.portals {
+            if let Err(e) = portal.stop_session().await {
+                warn!("Failed to close layer portal session: {}", e);
+            }
+        }
+        self.streams.clear();
+        self.portals.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid_frame(width: u32, height: u32, bgr: [u8; 3]) -> Frame {
+        let pixel = [bgr[0], bgr[1], bgr[2], 0xff];
+        Frame {
+            format: FrameFormat {
+                width,
+                height,
+                fourcc: COMPOSITE_FOURCC,
+                stride: width * 4,
+            },
+            data: FrameData::Memory(pixel.repeat((width * height) as usize)),
+            pts: 0,
+            hdr_metadata: None,
+        }
+    }
+
+    fn pixel(frame: &Frame, x: u32, y: u32) -> [u8; 3] {
+        let FrameData::Memory(data) = &frame.data else {
+            panic!("composite frames live in memory");
+        };
+        let i = (y * frame.format.stride + x * 4) as usize;
+        [data[i], data[i + 1], data[i + 2]]
+    }
+
+    #[test]
+    fn test_grid_layout() {
+        let two = grid_layout(&[CaptureSource::monitor("a"), CaptureSource::window("b")], 1920, 1080);
+        assert_eq!((two[0].x, two[0].width, two[0].height), (0, 960, 1080));
+        assert_eq!((two[1].x, two[1].y), (960, 0));
+
+        let sources: Vec<_> = (0..3).map(|i| CaptureSource::monitor(i.to_string())).collect();
+        let three = grid_layout(&sources, 1920, 1080);
+        assert_eq!((three[2].x, three[2].y, three[2].width, three[2].height), (0, 540, 960, 540));
+
+        assert!(grid_layout(&[], 1920, 1080).is_empty());
+    }
+
+    #[test]
+    fn test_assign_streams() {
+        let output = |name: &str| OutputInfo {
+            name: name.to_string(),
+            card: "card1".to_string(),
+            connected: true,
+            enabled: true,
+            resolution: Some((1920, 1080)),
+        };
+        let (dp1, dp2) = (output("DP-1"), output("DP-2"));
+        let streams = [(Some("DP-2"), Some((1920, 1080))), (Some("DP-1"), Some((1920, 1080)))];
+
+        // Named layers take their own stream, the picker layer gets nothing
+        assert_eq!(
+            assign_streams(&[Some(&dp1), None, Some(&dp2)], &streams),
+            vec![Some(1), None, Some(0)]
+        );
+
+        // Picker layers share out what named layers left over
+        assert_eq!(assign_streams(&[None, Some(&dp2)], &streams), vec![Some(1), Some(0)]);
+
+        // Only one stream granted: the remaining layer needs its own session
+        assert_eq!(assign_streams(&[None, None], &streams[..1]), vec![Some(0), None]);
+    }
+
+    #[test]
+    fn test_parse_layer() {
+        let layer: LayoutLayer = "window:firefox@960,0,960x1080".parse().unwrap();
+        assert_eq!(layer.source, CaptureSource::window("firefox"));
+        assert_eq!((layer.x, layer.y, layer.width, layer.height, layer.z), (960, 0, 960, 1080, 0));
+
+        let layer: LayoutLayer = "monitor:DP-2@0,0,640x360,z2".parse().unwrap();
+        assert_eq!(layer.source, CaptureSource::monitor("DP-2"));
+        assert_eq!(layer.z, 2);
+
+        assert!("monitor:DP-2".parse::<LayoutLayer>().is_err());
+        assert!("monitor@0,0,0x360".parse::<LayoutLayer>().is_err());
+        assert!("screen@0,0,640x360".parse::<LayoutLayer>().is_err());
+    }
+
+    #[test]
+    fn test_compose_side_by_side() {
+        let layers = grid_layout(&[CaptureSource::monitor("a"), CaptureSource::monitor("b")], 8, 4);
+        let mut compositor = Compositor::new(8, 4, layers);
+        assert!(compositor.compose(0).is_none());
+
+        // Sources at a different size than their cells are stretched
+        assert!(compositor.update(0, &solid_frame(16, 16, [255, 0, 0])));
+        let frame = compositor.compose(0).unwrap();
+        assert_eq!((frame.format.width, frame.format.height), (8, 4));
+        assert_eq!(pixel(&frame, 0, 0), [255, 0, 0]);
+        // The second layer has no frame yet and stays black
+        assert_eq!(pixel(&frame, 7, 3), [0, 0, 0]);
+
+        compositor.update(1, &solid_frame(2, 2, [0, 0, 255]));
+        let frame = compositor.compose(0).unwrap();
+        assert_eq!(pixel(&frame, 3, 3), [255, 0, 0]);
+        assert_eq!(pixel(&frame, 4, 0), [0, 0, 255]);
+    }
+
+    #[test]
+    fn test_compose_z_order_and_clipping() {
+        let layers = vec![
+            LayoutLayer::new(CaptureSource::monitor("top"), 2, 2, 20, 20).with_z(1),
+            LayoutLayer::new(CaptureSource::monitor("bottom"), 0, 0, 8, 8),
+        ];
+        let mut compositor = Compositor::new(8, 8, layers);
+        compositor.update(0, &solid_frame(4, 4, [0, 255, 0]));
+        compositor.update(1, &solid_frame(4, 4, [0, 0, 255]));
+
+        let frame = compositor.compose(0).unwrap();
+        assert_eq!(pixel(&frame, 0, 0), [0, 0, 255]);
+        // Higher z wins, and the layer is clipped to the output
+        assert_eq!(pixel(&frame, 7, 7), [0, 255, 0]);
+    }
+
+    #[test]
+    fn test_layer_holds_last_frame() {
+        let layers = vec![LayoutLayer::new(CaptureSource::monitor("a"), 0, 0, 4, 4)];
+        let mut compositor = Compositor::new(4, 4, layers);
+        compositor.update(0, &solid_frame(4, 4, [10, 20, 30]));
+
+        let dmabuf = Frame {
+            data: FrameData::DmaBuf {
+                fd: -1,
+                offset: 0,
+                modifier: 0,
+            },
+            ..solid_frame(4, 4, [0, 0, 0])
+        };
+        assert!(!compositor.update(0, &dmabuf));
+        assert_eq!(pixel(&compositor.compose(0).unwrap(), 1, 1), [10, 20, 30]);
+    }
+}
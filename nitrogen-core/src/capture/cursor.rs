@@ -0,0 +1,479 @@
+//! Cursor compositing from PipeWire cursor metadata
+//!
+//! With [`CursorMode::Metadata`](crate::config::CursorMode::Metadata) the
+//! compositor leaves the pointer out of the frames and attaches its
+//! position and bitmap to each buffer as `SPA_META_Cursor` metadata. The
+//! capture stream keeps the latest state here and draws the cursor onto
+//! shared-memory frames itself, so its size no longer depends on how the
+//! compositor scales the hardware cursor.
+
+use pipewire::spa::param::video::VideoFormat;
+use tracing::debug;
+
+/// Default cursor scale (bitmap size as sent by the compositor)
+pub const DEFAULT_CURSOR_SCALE: f32 = 1.0;
+
+/// Largest supported cursor scale
+pub const MAX_CURSOR_SCALE: f32 = 4.0;
+
+/// Largest cursor bitmap requested from the compositor, per side
+pub const MAX_CURSOR_SIZE: u32 = 256;
+
+/// Cursor bitmap size the metadata buffer is sized for by default
+pub const DEFAULT_CURSOR_SIZE: u32 = 64;
+
+/// `struct spa_point`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SpaPoint {
+    x: i32,
+    y: i32,
+}
+
+/// `struct spa_meta_cursor` from spa/buffer/meta.h
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SpaMetaCursor {
+    /// Cursor id (0 = no valid cursor information)
+    id: u32,
+    flags: u32,
+    /// Position of the hotspot in the frame
+    position: SpaPoint,
+    /// Hotspot inside the bitmap
+    hotspot: SpaPoint,
+    /// Offset of a `spa_meta_bitmap` from the start of this struct (0 = unchanged)
+    bitmap_offset: u32,
+}
+
+/// `struct spa_meta_bitmap` from spa/buffer/meta.h
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct SpaMetaBitmap {
+    /// `spa_video_format` of the pixels (0 = no bitmap)
+    format: u32,
+    width: u32,
+    height: u32,
+    stride: i32,
+    /// Offset of the pixels from the start of this struct
+    offset: u32,
+}
+
+/// Metadata size needed for a cursor bitmap of `width`x`height` (4 bytes per pixel)
+pub fn cursor_meta_size(width: u32, height: u32) -> usize {
+    std::mem::size_of::<SpaMetaCursor>()
+        + std::mem::size_of::<SpaMetaBitmap>()
+        + width as usize * height as usize * 4
+}
+
+/// Byte positions of the color channels in a packed pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelLayout {
+    /// Bytes per pixel
+    pub bpp: usize,
+    /// Red byte
+    pub r: usize,
+    /// Green byte
+    pub g: usize,
+    /// Blue byte
+    pub b: usize,
+    /// Alpha byte, if the format has one
+    pub a: Option<usize>,
+}
+
+impl PixelLayout {
+    /// Layout of a packed RGB format (None for planar and YUV formats)
+    pub fn for_format(format: VideoFormat) -> Option<Self> {
+        let (bpp, r, g, b, a) = match format {
+            VideoFormat::BGRx => (4, 2, 1, 0, None),
+            VideoFormat::BGRA => (4, 2, 1, 0, Some(3)),
+            VideoFormat::RGBx => (4, 0, 1, 2, None),
+            VideoFormat::RGBA => (4, 0, 1, 2, Some(3)),
+            VideoFormat::xRGB => (4, 1, 2, 3, None),
+            VideoFormat::ARGB => (4, 1, 2, 3, Some(0)),
+            VideoFormat::xBGR => (4, 3, 2, 1, None),
+            VideoFormat::ABGR => (4, 3, 2, 1, Some(0)),
+            VideoFormat::RGB => (3, 0, 1, 2, None),
+            VideoFormat::BGR => (3, 2, 1, 0, None),
+            _ => return None,
+        };
+        Some(Self { bpp, r, g, b, a })
+    }
+}
+
+/// Cursor image with its hotspot, stored as straight-alpha RGBA
+#[derive(Debug, Clone, PartialEq)]
+pub struct CursorBitmap {
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// Hotspot column
+    pub hotspot_x: i32,
+    /// Hotspot row
+    pub hotspot_y: i32,
+    /// RGBA pixels, `width * height * 4` bytes
+    pub pixels: Vec<u8>,
+}
+
+impl CursorBitmap {
+    /// Convert packed pixels in `format` to an RGBA bitmap
+    ///
+    /// Returns None for formats without a packed RGB layout or when `data`
+    /// is too short for the given size and stride.
+    pub fn from_pixels(
+        format: VideoFormat,
+        width: u32,
+        height: u32,
+        stride: usize,
+        data: &[u8],
+    ) -> Option<Self> {
+        let layout = PixelLayout::for_format(format)?;
+        let row_bytes = width as usize * layout.bpp;
+        let stride = if stride == 0 { row_bytes } else { stride };
+        if stride < row_bytes || height == 0 {
+            return None;
+        }
+        if data.len() < stride * (height as usize - 1) + row_bytes {
+            return None;
+        }
+
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for row in data.chunks(stride).take(height as usize) {
+            for px in row[..row_bytes].chunks_exact(layout.bpp) {
+                pixels.extend_from_slice(&[
+                    px[layout.r],
+                    px[layout.g],
+                    px[layout.b],
+                    layout.a.map_or(255, |a| px[a]),
+                ]);
+            }
+        }
+
+        Some(Self {
+            width,
+            height,
+            hotspot_x: 0,
+            hotspot_y: 0,
+            pixels,
+        })
+    }
+
+    /// Resize with nearest-neighbour sampling, scaling the hotspot along
+    pub fn scaled(&self, scale: f32) -> Self {
+        if (scale - 1.0).abs() < f32::EPSILON {
+            return self.clone();
+        }
+
+        let width = ((self.width as f32 * scale).round() as u32).max(1);
+        let height = ((self.height as f32 * scale).round() as u32).max(1);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            let src_y = ((y as f32 / scale) as u32).min(self.height - 1);
+            for x in 0..width {
+                let src_x = ((x as f32 / scale) as u32).min(self.width - 1);
+                let i = (src_y * self.width + src_x) as usize * 4;
+                pixels.extend_from_slice(&self.pixels[i..i + 4]);
+            }
+        }
+
+        Self {
+            width,
+            height,
+            hotspot_x: (self.hotspot_x as f32 * scale).round() as i32,
+            hotspot_y: (self.hotspot_y as f32 * scale).round() as i32,
+            pixels,
+        }
+    }
+}
+
+/// Latest cursor state for a stream, drawn onto each frame
+#[derive(Debug)]
+pub struct CursorOverlay {
+    /// Scale applied to the compositor's bitmap
+    scale: f32,
+    /// Hotspot position in frame coordinates (None = no cursor over the source)
+    position: Option<(i32, i32)>,
+    /// Current bitmap, already scaled (None = no bitmap received yet)
+    bitmap: Option<CursorBitmap>,
+    /// Whether any buffer carried cursor metadata
+    seen_metadata: bool,
+}
+
+impl CursorOverlay {
+    /// Create an overlay drawing the cursor at `scale`
+    pub fn new(scale: f32) -> Self {
+        Self {
+            scale: scale.clamp(f32::EPSILON, MAX_CURSOR_SCALE),
+            position: None,
+            bitmap: None,
+            seen_metadata: false,
+        }
+    }
+
+    /// Whether any buffer so far carried cursor metadata
+    pub fn has_metadata(&self) -> bool {
+        self.seen_metadata
+    }
+
+    /// Move the cursor (None hides it)
+    pub fn set_position(&mut self, position: Option<(i32, i32)>) {
+        self.position = position;
+    }
+
+    /// Replace the cursor image (None hides it until the next bitmap)
+    pub fn set_bitmap(&mut self, bitmap: Option<CursorBitmap>) {
+        self.bitmap = bitmap.map(|b| b.scaled(self.scale));
+    }
+
+    /// Update from a `SPA_META_Cursor` block
+    ///
+    /// # Safety
+    /// `data` must point to `size` readable bytes of cursor metadata.
+    pub unsafe fn update_from_meta(&mut self, data: *const u8, size: usize) {
+        if data.is_null() || size < std::mem::size_of::<SpaMetaCursor>() {
+            return;
+        }
+        self.seen_metadata = true;
+
+        // SAFETY: the caller guarantees `size` readable bytes at `data`; every
+        // read is bounds-checked against it and done unaligned
+        let bytes = unsafe { std::slice::from_raw_parts(data, size) };
+        let meta = unsafe { std::ptr::read_unaligned(data as *const SpaMetaCursor) };
+
+        if meta.id == 0 {
+            self.position = None;
+            return;
+        }
+        self.position = Some((meta.position.x, meta.position.y));
+
+        let offset = meta.bitmap_offset as usize;
+        if offset == 0 || offset + std::mem::size_of::<SpaMetaBitmap>() > size {
+            // Bitmap unchanged since the last update
+            return;
+        }
+
+        // SAFETY: offset + size_of::<SpaMetaBitmap>() is within the block
+        let header =
+            unsafe { std::ptr::read_unaligned(bytes[offset..].as_ptr() as *const SpaMetaBitmap) };
+        if header.format == 0 || header.width == 0 || header.height == 0 {
+            // The compositor hides the pointer with an empty bitmap
+            self.bitmap = None;
+            return;
+        }
+
+        let pixels_start = offset + header.offset as usize;
+        let bitmap = bytes.get(pixels_start..).and_then(|pixels| {
+            CursorBitmap::from_pixels(
+                VideoFormat::from_raw(header.format),
+                header.width,
+                header.height,
+                header.stride.max(0) as usize,
+                pixels,
+            )
+        });
+        match bitmap {
+            Some(mut bitmap) => {
+                bitmap.hotspot_x = meta.hotspot.x;
+                bitmap.hotspot_y = meta.hotspot.y;
+                self.set_bitmap(Some(bitmap));
+            }
+            None => debug!(
+                "Ignoring cursor bitmap {}x{} in format {}",
+                header.width, header.height, header.format
+            ),
+        }
+    }
+
+    /// Alpha-blend the cursor onto a packed RGB frame
+    ///
+    /// Frames in formats without a packed layout are left unchanged.
+    pub fn composite(
+        &self,
+        data: &mut [u8],
+        width: u32,
+        height: u32,
+        stride: usize,
+        format: VideoFormat,
+    ) {
+        let (Some((x, y)), Some(bitmap)) = (self.position, self.bitmap.as_ref()) else {
+            return;
+        };
+        let Some(layout) = PixelLayout::for_format(format) else {
+            return;
+        };
+        let stride = if stride == 0 {
+            width as usize * layout.bpp
+        } else {
+            stride
+        };
+
+        let left = x - bitmap.hotspot_x;
+        let top = y - bitmap.hotspot_y;
+        for row in 0..bitmap.height as i32 {
+            let dst_y = top + row;
+            if dst_y < 0 || dst_y >= height as i32 {
+                continue;
+            }
+            for col in 0..bitmap.width as i32 {
+                let dst_x = left + col;
+                if dst_x < 0 || dst_x >= width as i32 {
+                    continue;
+                }
+
+                let src = (row as usize * bitmap.width as usize + col as usize) * 4;
+                let alpha = u32::from(bitmap.pixels[src + 3]);
+                if alpha == 0 {
+                    continue;
+                }
+
+                let dst = dst_y as usize * stride + dst_x as usize * layout.bpp;
+                let Some(px) = data.get_mut(dst..dst + layout.bpp) else {
+                    continue;
+                };
+                for (channel, value) in [
+                    (layout.r, bitmap.pixels[src]),
+                    (layout.g, bitmap.pixels[src + 1]),
+                    (layout.b, bitmap.pixels[src + 2]),
+                ] {
+                    px[channel] = blend(px[channel], value, alpha);
+                }
+                if let Some(a) = layout.a {
+                    px[a] = blend(px[a], 255, alpha);
+                }
+            }
+        }
+    }
+}
+
+/// Blend `src` over `dst` with 8-bit `alpha`
+fn blend(dst: u8, src: u8, alpha: u32) -> u8 {
+    ((u32::from(src) * alpha + u32::from(dst) * (255 - alpha) + 127) / 255) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2x2 cursor: opaque red, transparent, half-transparent white, opaque blue
+    fn test_bitmap() -> CursorBitmap {
+        CursorBitmap {
+            width: 2,
+            height: 2,
+            hotspot_x: 1,
+            hotspot_y: 1,
+            pixels: vec![
+                255, 0, 0, 255, //
+                0, 0, 0, 0, //
+                255, 255, 255, 128, //
+                0, 0, 255, 255,
+            ],
+        }
+    }
+
+    #[test]
+    fn test_composite_at_hotspot() {
+        let mut overlay = CursorOverlay::new(1.0);
+        overlay.set_bitmap(Some(test_bitmap()));
+        overlay.set_position(Some((2, 2)));
+
+        // 4x4 black BGRx frame
+        let mut frame = vec![0u8; 4 * 4 * 4];
+        overlay.composite(&mut frame, 4, 4, 16, VideoFormat::BGRx);
+
+        let pixel = |x: usize, y: usize| &frame[y * 16 + x * 4..y * 16 + x * 4 + 3];
+        // Hotspot (1,1) lands on (2,2), so the bitmap covers (1,1)..(2,2)
+        assert_eq!(pixel(1, 1), &[0, 0, 255]);
+        assert_eq!(pixel(2, 1), &[0, 0, 0]);
+        assert_eq!(pixel(1, 2), &[128, 128, 128]);
+        assert_eq!(pixel(2, 2), &[255, 0, 0]);
+        assert_eq!(pixel(0, 0), &[0, 0, 0]);
+    }
+
+    #[test]
+    fn test_composite_clips_at_edges() {
+        let mut overlay = CursorOverlay::new(1.0);
+        overlay.set_bitmap(Some(test_bitmap()));
+        overlay.set_position(Some((0, 0)));
+
+        let mut frame = vec![0u8; 2 * 2 * 4];
+        overlay.composite(&mut frame, 2, 2, 8, VideoFormat::RGBA);
+        // Only the bottom-right bitmap pixel (blue) is inside the frame
+        assert_eq!(&frame[..4], &[0, 0, 255, 255]);
+        assert_eq!(&frame[4..], &[0; 12]);
+    }
+
+    #[test]
+    fn test_hidden_cursor_is_not_drawn() {
+        let mut overlay = CursorOverlay::new(1.0);
+        overlay.set_bitmap(Some(test_bitmap()));
+        overlay.set_position(None);
+
+        let mut frame = vec![0u8; 4 * 4 * 4];
+        overlay.composite(&mut frame, 4, 4, 16, VideoFormat::BGRx);
+        assert!(frame.iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_scaled_bitmap() {
+        let scaled = test_bitmap().scaled(2.0);
+        assert_eq!((scaled.width, scaled.height), (4, 4));
+        assert_eq!((scaled.hotspot_x, scaled.hotspot_y), (2, 2));
+        // Top-left 2x2 block is the red source pixel
+        assert_eq!(&scaled.pixels[..4], &[255, 0, 0, 255]);
+        assert_eq!(&scaled.pixels[4..8], &[255, 0, 0, 255]);
+        assert_eq!(&scaled.pixels[16..20], &[255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_update_from_meta() {
+        let pixels: [u8; 8] = [10, 20, 30, 255, 40, 50, 60, 0]; // 2x1 BGRA
+        let cursor_size = std::mem::size_of::<SpaMetaCursor>();
+        let bitmap_size = std::mem::size_of::<SpaMetaBitmap>();
+
+        let cursor = SpaMetaCursor {
+            id: 1,
+            flags: 0,
+            position: SpaPoint { x: 5, y: 6 },
+            hotspot: SpaPoint { x: 1, y: 0 },
+            bitmap_offset: cursor_size as u32,
+        };
+        let bitmap = SpaMetaBitmap {
+            format: VideoFormat::BGRA.as_raw(),
+            width: 2,
+            height: 1,
+            stride: 8,
+            offset: bitmap_size as u32,
+        };
+
+        let mut block = vec![0u8; cursor_meta_size(2, 1)];
+        // SAFETY: block is sized for both headers plus the pixels
+        unsafe {
+            std::ptr::write_unaligned(block.as_mut_ptr() as *mut SpaMetaCursor, cursor);
+            std::ptr::write_unaligned(
+                block[cursor_size..].as_mut_ptr() as *mut SpaMetaBitmap,
+                bitmap,
+            );
+        }
+        block[cursor_size + bitmap_size..].copy_from_slice(&pixels);
+
+        let mut overlay = CursorOverlay::new(1.0);
+        // SAFETY: block is a valid, fully initialized metadata block
+        unsafe { overlay.update_from_meta(block.as_ptr(), block.len()) };
+
+        assert!(overlay.has_metadata());
+        assert_eq!(overlay.position, Some((5, 6)));
+        let bitmap = overlay.bitmap.as_ref().expect("bitmap should be parsed");
+        assert_eq!((bitmap.hotspot_x, bitmap.hotspot_y), (1, 0));
+        assert_eq!(bitmap.pixels, vec![30, 20, 10, 255, 60, 50, 40, 0]);
+
+        // id 0 hides the cursor but keeps the bitmap for later
+        let hidden = SpaMetaCursor { id: 0, ..cursor };
+        // SAFETY: as above
+        unsafe {
+            std::ptr::write_unaligned(block.as_mut_ptr() as *mut SpaMetaCursor, hidden);
+            overlay.update_from_meta(block.as_ptr(), block.len());
+        }
+        assert_eq!(overlay.position, None);
+        assert!(overlay.bitmap.is_some());
+    }
+}
@@ -0,0 +1,428 @@
+//! Output clipping stage for the audio mixer
+//!
+//! Summing sources and raising volume can push samples past full scale. The
+//! mixer runs its output through one of three stages:
+//! - `none`: hard clamp to ±1.0
+//! - `tanh`: soft clip with a tanh curve above -6 dBFS (the default)
+//! - `truepeak`: a look-ahead limiter that keeps inter-sample peaks under a
+//!   threshold and leaves quieter audio untouched
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// Default limiter threshold in dBFS
+pub const DEFAULT_LIMITER_THRESHOLD_DB: f32 = -1.0;
+
+/// Default limiter release time in milliseconds
+pub const DEFAULT_LIMITER_RELEASE_MS: u32 = 50;
+
+/// How far ahead the true-peak limiter looks, in milliseconds
+///
+/// Also the time gain reduction takes to fade in before a peak.
+const LOOKAHEAD_MS: f32 = 1.5;
+
+/// Clipping stage applied to mixed audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LimiterMode {
+    /// Hard clamp to full scale
+    None,
+    /// Soft clip with a tanh curve
+    #[default]
+    Tanh,
+    /// Look-ahead true-peak limiter
+    TruePeak,
+}
+
+impl std::fmt::Display for LimiterMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Tanh => write!(f, "tanh"),
+            Self::TruePeak => write!(f, "truepeak"),
+        }
+    }
+}
+
+impl std::str::FromStr for LimiterMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" | "off" | "clamp" => Ok(Self::None),
+            "tanh" | "soft" => Ok(Self::Tanh),
+            "truepeak" | "true-peak" | "limiter" => Ok(Self::TruePeak),
+            _ => Err(format!("Unknown limiter mode: {}", s)),
+        }
+    }
+}
+
+/// Limiter configuration
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimiterConfig {
+    /// Clipping stage
+    pub mode: LimiterMode,
+    /// Ceiling for the true-peak limiter in dBFS
+    pub threshold_db: f32,
+    /// Time the true-peak limiter takes to recover after a peak, in milliseconds
+    pub release_ms: u32,
+}
+
+impl Default for LimiterConfig {
+    fn default() -> Self {
+        Self {
+            mode: LimiterMode::default(),
+            threshold_db: DEFAULT_LIMITER_THRESHOLD_DB,
+            release_ms: DEFAULT_LIMITER_RELEASE_MS,
+        }
+    }
+}
+
+impl LimiterConfig {
+    /// Ceiling as a linear amplitude (never above full scale)
+    pub fn ceiling(&self) -> f32 {
+        10f32.powf(self.threshold_db / 20.0).min(1.0)
+    }
+}
+
+/// Clipping stage for one audio stream
+///
+/// The true-peak mode keeps state across calls and delays the audio by the
+/// look-ahead time, so every frame of a stream has to pass through the same
+/// instance.
+#[derive(Debug)]
+pub struct Limiter {
+    /// Selected mode
+    mode: LimiterMode,
+    /// Look-ahead limiter (true-peak mode only)
+    true_peak: Option<TruePeakLimiter>,
+}
+
+impl Limiter {
+    /// Create a limiter for a stream at `sample_rate`
+    pub fn new(config: LimiterConfig, sample_rate: u32) -> Self {
+        let true_peak = (config.mode == LimiterMode::TruePeak)
+            .then(|| TruePeakLimiter::new(config.ceiling(), config.release_ms, sample_rate));
+        Self {
+            mode: config.mode,
+            true_peak,
+        }
+    }
+
+    /// Selected mode
+    pub fn mode(&self) -> LimiterMode {
+        self.mode
+    }
+
+    /// Whether the stage has to see every frame, even at unity gain
+    pub fn is_stateful(&self) -> bool {
+        self.true_peak.is_some()
+    }
+
+    /// Process interleaved samples in place
+    pub fn process(&mut self, samples: &mut [f32], channels: usize) {
+        match self.true_peak {
+            Some(ref mut limiter) => limiter.process(samples, channels),
+            None if self.mode == LimiterMode::Tanh => {
+                for sample in samples.iter_mut() {
+                    *sample = soft_clip(*sample);
+                }
+            }
+            None => {
+                for sample in samples.iter_mut() {
+                    *sample = sample.clamp(-1.0, 1.0);
+                }
+            }
+        }
+    }
+}
+
+/// Look-ahead true-peak limiter
+///
+/// Peaks are estimated between samples by interpolation, so the ceiling also
+/// holds after reconstruction. The required gain is held for the look-ahead
+/// window and smoothed with a moving average of the same length; with the
+/// audio delayed to match, the gain reaches its minimum exactly when the
+/// peak is output. Below the threshold the gain stays at 1.0 and samples pass
+/// through unchanged (only delayed).
+#[derive(Debug)]
+pub struct TruePeakLimiter {
+    /// Ceiling as a linear amplitude
+    ceiling: f32,
+    /// Per-sample release coefficient for the gain envelope
+    release_coeff: f32,
+    /// Look-ahead length in sample frames
+    lookahead: usize,
+    /// Number of interleaved channels the state is sized for
+    channels: usize,
+    /// Last three input samples per channel, for peak interpolation
+    history: Vec<[f32; 3]>,
+    /// Delayed interleaved audio
+    delay: VecDeque<f32>,
+    /// Required gain for the frames in the hold window
+    hold: VecDeque<f32>,
+    /// Gain envelope after release smoothing
+    envelope: f32,
+    /// Envelope values in the moving-average window
+    smooth: VecDeque<f32>,
+    /// Sum of `smooth`
+    smooth_sum: f64,
+}
+
+impl TruePeakLimiter {
+    /// Create a limiter with a linear `ceiling`
+    pub fn new(ceiling: f32, release_ms: u32, sample_rate: u32) -> Self {
+        let sample_rate = sample_rate.max(1) as f32;
+        let lookahead = ((sample_rate * LOOKAHEAD_MS / 1000.0) as usize).max(1);
+        let release_samples = sample_rate * release_ms as f32 / 1000.0;
+        let release_coeff = if release_samples >= 1.0 {
+            1.0 - (-1.0 / release_samples).exp()
+        } else {
+            1.0
+        };
+
+        let mut limiter = Self {
+            ceiling: ceiling.clamp(f32::EPSILON, 1.0),
+            release_coeff,
+            lookahead,
+            channels: 0,
+            history: Vec::new(),
+            delay: VecDeque::new(),
+            hold: VecDeque::new(),
+            envelope: 1.0,
+            smooth: VecDeque::new(),
+            smooth_sum: 0.0,
+        };
+        limiter.reset(1);
+        limiter
+    }
+
+    /// Latency added by the limiter, in sample frames
+    pub fn latency(&self) -> usize {
+        self.lookahead + 1
+    }
+
+    /// Clear the state for a stream with `channels` channels
+    fn reset(&mut self, channels: usize) {
+        self.channels = channels;
+        self.history = vec![[0.0; 3]; channels];
+        self.delay = std::iter::repeat_n(0.0, self.latency() * channels).collect();
+        // Hold two extra frames: an interpolated peak is only seen once the
+        // sample after it arrives
+        self.hold = std::iter::repeat_n(1.0, self.lookahead + 2).collect();
+        self.envelope = 1.0;
+        self.smooth = std::iter::repeat_n(1.0, self.lookahead).collect();
+        self.smooth_sum = self.lookahead as f64;
+    }
+
+    /// Limit interleaved samples in place (output is delayed by `latency()`)
+    pub fn process(&mut self, samples: &mut [f32], channels: usize) {
+        let channels = channels.max(1);
+        if channels != self.channels {
+            self.reset(channels);
+        }
+
+        for frame in samples.chunks_mut(channels) {
+            // True peak of the newest sample and the gap before the previous one
+            let mut peak = 0.0f32;
+            for (sample, history) in frame.iter().zip(self.history.iter_mut()) {
+                peak = peak.max(inter_sample_peak(history, *sample));
+                *history = [history[1], history[2], *sample];
+            }
+
+            let required = if peak > self.ceiling {
+                self.ceiling / peak
+            } else {
+                1.0
+            };
+            self.hold.pop_front();
+            self.hold.push_back(required);
+            let held = self.hold.iter().copied().fold(1.0, f32::min);
+
+            // Attack immediately, release gradually
+            self.envelope = if held < self.envelope {
+                held
+            } else {
+                self.envelope + (held - self.envelope) * self.release_coeff
+            };
+
+            if let Some(oldest) = self.smooth.pop_front() {
+                self.smooth_sum -= f64::from(oldest);
+            }
+            self.smooth.push_back(self.envelope);
+            self.smooth_sum += f64::from(self.envelope);
+            let gain = (self.smooth_sum / self.lookahead as f64).min(1.0) as f32;
+
+            for sample in frame.iter_mut() {
+                let delayed = self.delay.pop_front().unwrap_or(0.0);
+                self.delay.push_back(*sample);
+                *sample = if gain >= 1.0 {
+                    delayed
+                } else {
+                    (delayed * gain).clamp(-self.ceiling, self.ceiling)
+                };
+            }
+        }
+    }
+}
+
+/// Largest absolute value of `next` and of the curve between the two samples
+/// before it
+///
+/// Uses Catmull-Rom interpolation at quarter-sample steps on
+/// `history[0..3], next`, which approximates 4x oversampled peak detection.
+fn inter_sample_peak(history: &[f32; 3], next: f32) -> f32 {
+    let [p0, p1, p2] = *history;
+    let p3 = next;
+
+    let mut peak = p3.abs().max(p2.abs());
+    for t in [0.25f32, 0.5, 0.75] {
+        let t2 = t * t;
+        let t3 = t2 * t;
+        let value = 0.5
+            * ((2.0 * p1)
+                + (p2 - p0) * t
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3);
+        peak = peak.max(value.abs());
+    }
+    peak
+}
+
+/// Soft clip function to prevent harsh clipping using tanh
+pub(crate) fn soft_clip(x: f32) -> f32 {
+    // Use tanh for smooth soft clipping
+    // This maps any input to (-1, 1) range smoothly
+    if x.abs() <= 0.5 {
+        x // Linear region for low levels
+    } else {
+        x.tanh()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn true_peak_config() -> LimiterConfig {
+        LimiterConfig {
+            mode: LimiterMode::TruePeak,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_limiter_mode_parse() {
+        assert_eq!("tanh".parse::<LimiterMode>().unwrap(), LimiterMode::Tanh);
+        assert_eq!("off".parse::<LimiterMode>().unwrap(), LimiterMode::None);
+        assert_eq!(
+            "true-peak".parse::<LimiterMode>().unwrap(),
+            LimiterMode::TruePeak
+        );
+        assert!("brickwall".parse::<LimiterMode>().is_err());
+        assert_eq!(LimiterMode::default(), LimiterMode::Tanh);
+    }
+
+    #[test]
+    fn test_under_threshold_is_untouched() {
+        let mut limiter = Limiter::new(true_peak_config(), 48000);
+        let latency = TruePeakLimiter::new(1.0, 50, 48000).latency();
+
+        // 1 kHz sine at -6 dBFS, stereo
+        let input: Vec<f32> = (0..4800)
+            .flat_map(|i| {
+                let s = 0.5 * (i as f32 * 2.0 * std::f32::consts::PI / 48.0).sin();
+                [s, s]
+            })
+            .collect();
+        let mut output = input.clone();
+        for chunk in output.chunks_mut(960) {
+            limiter.process(chunk, 2);
+        }
+
+        // Identical, just delayed by the look-ahead
+        assert_eq!(&output[latency * 2..], &input[..input.len() - latency * 2]);
+    }
+
+    #[test]
+    fn test_transient_is_limited_to_ceiling() {
+        let config = true_peak_config();
+        let ceiling = config.ceiling();
+        let mut limiter = Limiter::new(config, 48000);
+
+        // Quiet tone with a 2x full-scale burst in the middle
+        let mut samples: Vec<f32> = (0..9600)
+            .map(|i| 0.2 * (i as f32 * 2.0 * std::f32::consts::PI / 48.0).sin())
+            .collect();
+        for (i, sample) in samples[4800..4900].iter_mut().enumerate() {
+            *sample = 2.0 * (i as f32 * 2.0 * std::f32::consts::PI / 20.0).sin();
+        }
+        limiter.process(&mut samples, 1);
+
+        let peak = samples.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!(
+            peak <= ceiling + 1e-6,
+            "peak {} exceeds ceiling {}",
+            peak,
+            ceiling
+        );
+        assert!(peak > ceiling * 0.9, "burst over-attenuated to {}", peak);
+        // Well before the burst the tone passes unchanged
+        let latency = TruePeakLimiter::new(ceiling, 50, 48000).latency();
+        let expected = 0.2 * ((1000 - latency) as f32 * 2.0 * std::f32::consts::PI / 48.0).sin();
+        assert_eq!(samples[1000], expected);
+    }
+
+    #[test]
+    fn test_clamp_and_tanh_modes() {
+        let mut clamp = Limiter::new(
+            LimiterConfig {
+                mode: LimiterMode::None,
+                ..Default::default()
+            },
+            48000,
+        );
+        let mut samples = vec![0.8, 1.5, -2.0];
+        clamp.process(&mut samples, 1);
+        assert_eq!(samples, vec![0.8, 1.0, -1.0]);
+        assert!(!clamp.is_stateful());
+
+        let mut tanh = Limiter::new(LimiterConfig::default(), 48000);
+        let mut samples = vec![0.3, 2.0];
+        tanh.process(&mut samples, 1);
+        assert_eq!(samples[0], 0.3);
+        assert_eq!(samples[1], soft_clip(2.0));
+    }
+
+    #[test]
+    fn test_soft_clip() {
+        // Linear region
+        assert_eq!(soft_clip(0.3), 0.3);
+        assert_eq!(soft_clip(-0.3), -0.3);
+        // Soft clipping region (uses tanh)
+        let clipped = soft_clip(2.0);
+        assert!(
+            clipped < 1.0,
+            "soft_clip(2.0) = {} should be < 1.0",
+            clipped
+        );
+        assert!(
+            clipped > 0.9,
+            "soft_clip(2.0) = {} should be > 0.9",
+            clipped
+        );
+        let neg_clipped = soft_clip(-2.0);
+        assert!(
+            neg_clipped > -1.0,
+            "soft_clip(-2.0) = {} should be > -1.0",
+            neg_clipped
+        );
+        assert!(
+            neg_clipped < -0.9,
+            "soft_clip(-2.0) = {} should be < -0.9",
+            neg_clipped
+        );
+    }
+}
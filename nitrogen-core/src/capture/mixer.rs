@@ -1,19 +1,26 @@
 //! Audio mixing for multiple sources
 //!
 //! Combines multiple audio streams (desktop + microphone) into a single output
-//! with configurable volume levels for each source.
+//! with configurable volume levels for each source. Volume, mute and ducking
+//! changes ramp linearly over a few milliseconds so they don't click.
 
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use parking_lot::Mutex;
 use tokio::sync::broadcast;
 use tracing::{debug, info, warn};
 
-use crate::config::AudioSource;
+use crate::config::{AudioSource, CaptureConfig, MicChannel};
 use crate::error::{NitrogenError, Result};
 use crate::types::{AudioFormat, AudioFrame, AudioSampleFormat};
 
+use super::limiter::{Limiter, LimiterConfig};
+use super::vad::{DuckingMode, VoiceDetector};
 use super::AudioCaptureStream;
 
+/// Default time a gain change takes to ramp from 0 to 1
+pub const DEFAULT_GAIN_RAMP_MS: u32 = 10;
+
 /// Volume control for an audio source
 #[derive(Debug, Clone, Copy)]
 pub struct VolumeControl {
@@ -51,6 +58,82 @@ impl VolumeControl {
     }
 }
 
+/// Gain that moves linearly toward its target instead of jumping
+///
+/// The slope is fixed, so a change from 1.0 to 0.0 takes the configured ramp
+/// time and smaller changes finish sooner. The ramp carries over between
+/// frames.
+#[derive(Debug, Clone, Copy)]
+pub struct GainRamp {
+    /// Gain applied to the last sample
+    current: f32,
+    /// Gain being ramped toward
+    target: f32,
+    /// Maximum change per sample frame
+    step: f32,
+}
+
+impl GainRamp {
+    /// Create a ramp settled at `gain`
+    pub fn new(gain: f32, sample_rate: u32, ramp_ms: u32) -> Self {
+        let ramp_frames = u64::from(sample_rate) * u64::from(ramp_ms) / 1000;
+        let step = if ramp_frames == 0 {
+            f32::INFINITY
+        } else {
+            1.0 / ramp_frames as f32
+        };
+        let gain = gain.max(0.0);
+
+        Self {
+            current: gain,
+            target: gain,
+            step,
+        }
+    }
+
+    /// Set the gain to ramp toward
+    pub fn set_target(&mut self, target: f32) {
+        self.target = target.max(0.0);
+    }
+
+    /// Gain applied to the most recent sample
+    pub fn current(&self) -> f32 {
+        self.current
+    }
+
+    /// Gain being ramped toward
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    /// Whether the ramp has reached its target
+    pub fn is_settled(&self) -> bool {
+        self.current == self.target
+    }
+
+    /// Scale interleaved samples, stepping toward the target once per sample frame
+    pub fn apply(&mut self, samples: &mut [f32], channels: usize) {
+        if self.is_settled() {
+            for sample in samples.iter_mut() {
+                *sample *= self.current;
+            }
+            return;
+        }
+
+        for frame in samples.chunks_mut(channels.max(1)) {
+            let remaining = self.target - self.current;
+            self.current = if remaining.abs() <= self.step {
+                self.target
+            } else {
+                self.current + self.step.copysign(remaining)
+            };
+            for sample in frame {
+                *sample *= self.current;
+            }
+        }
+    }
+}
+
 /// Audio mixer configuration
 #[derive(Debug, Clone)]
 pub struct MixerConfig {
@@ -58,6 +141,8 @@ pub struct MixerConfig {
     pub desktop_volume: VolumeControl,
     /// Microphone volume
     pub mic_volume: VolumeControl,
+    /// Microphone channel handling (applied before volume)
+    pub mic_channel: MicChannel,
     /// Output sample rate
     pub output_sample_rate: u32,
     /// Output channels
@@ -68,6 +153,12 @@ pub struct MixerConfig {
     pub ducking_amount: f32,
     /// Ducking threshold (mic amplitude to trigger ducking)
     pub ducking_threshold: f32,
+    /// What counts as mic activity for ducking
+    pub ducking_mode: DuckingMode,
+    /// Time a full-scale gain change takes, in milliseconds (0 = instant)
+    pub gain_ramp_ms: u32,
+    /// Clipping stage applied to the mixed output
+    pub limiter: LimiterConfig,
 }
 
 impl Default for MixerConfig {
@@ -75,11 +166,35 @@ impl Default for MixerConfig {
         Self {
             desktop_volume: VolumeControl::default(),
             mic_volume: VolumeControl::default(),
+            mic_channel: MicChannel::default(),
             output_sample_rate: 48000,
             output_channels: 2,
             ducking_enabled: false,
             ducking_amount: 0.5,
             ducking_threshold: 0.05,
+            ducking_mode: DuckingMode::default(),
+            gain_ramp_ms: DEFAULT_GAIN_RAMP_MS,
+            limiter: LimiterConfig::default(),
+        }
+    }
+}
+
+impl From<&CaptureConfig> for MixerConfig {
+    fn from(config: &CaptureConfig) -> Self {
+        Self {
+            desktop_volume: VolumeControl {
+                volume: config.desktop_volume,
+                muted: false,
+            },
+            mic_volume: VolumeControl {
+                volume: config.mic_volume,
+                muted: false,
+            },
+            mic_channel: config.mic_channel,
+            ducking_enabled: config.audio_ducking,
+            ducking_mode: config.audio_ducking_mode,
+            limiter: config.audio_limiter,
+            ..Default::default()
         }
     }
 }
@@ -92,6 +207,14 @@ pub struct AudioMixer {
     desktop_stream: Option<AudioCaptureStream>,
     /// Microphone stream
     mic_stream: Option<AudioCaptureStream>,
+    /// Desktop gain ramp (target follows volume, mute and ducking)
+    desktop_gain: Mutex<GainRamp>,
+    /// Microphone gain ramp (target follows volume and mute)
+    mic_gain: Mutex<GainRamp>,
+    /// Output clipping stage (shared by mixed and single-source frames)
+    limiter: Mutex<Limiter>,
+    /// Speech detector for VAD ducking
+    voice: Mutex<VoiceDetector>,
     /// Output sender
     output_tx: broadcast::Sender<Arc<AudioFrame>>,
     /// Running flag
@@ -112,16 +235,32 @@ impl AudioMixer {
         let (output_tx, _) = broadcast::channel(32);
 
         // Create streams based on source
-        let desktop_stream = if matches!(source, AudioSource::Desktop | AudioSource::Both) {
-            info!("Creating desktop audio capture for mixer");
-            Some(AudioCaptureStream::new(AudioSource::Desktop)?)
-        } else {
-            None
+        // An application capture takes the desktop slot
+        let desktop_stream = match &source {
+            AudioSource::Desktop | AudioSource::Both => {
+                info!("Creating desktop audio capture for mixer");
+                Some(AudioCaptureStream::new(
+                    AudioSource::Desktop,
+                    AudioSampleFormat::default(),
+                )?)
+            }
+            AudioSource::Application(app) => {
+                info!("Creating application audio capture for mixer: {}", app);
+                Some(AudioCaptureStream::new(
+                    source.clone(),
+                    AudioSampleFormat::default(),
+                )?)
+            }
+            _ => None,
         };
 
         let mic_stream = if matches!(source, AudioSource::Microphone | AudioSource::Both) {
             info!("Creating microphone capture for mixer");
-            Some(AudioCaptureStream::new(AudioSource::Microphone)?)
+            Some(AudioCaptureStream::with_mic_channel(
+                AudioSource::Microphone,
+                config.mic_channel,
+                AudioSampleFormat::default(),
+            )?)
         } else {
             None
         };
@@ -132,10 +271,28 @@ impl AudioMixer {
             mic_stream.is_some()
         );
 
+        let desktop_gain = GainRamp::new(
+            config.desktop_volume.effective_volume(),
+            config.output_sample_rate,
+            config.gain_ramp_ms,
+        );
+        let mic_gain = GainRamp::new(
+            config.mic_volume.effective_volume(),
+            config.output_sample_rate,
+            config.gain_ramp_ms,
+        );
+
+        let limiter = Limiter::new(config.limiter, config.output_sample_rate);
+        let voice = VoiceDetector::new(config.ducking_threshold);
+
         Ok(Self {
             config,
             desktop_stream,
             mic_stream,
+            desktop_gain: Mutex::new(desktop_gain),
+            mic_gain: Mutex::new(mic_gain),
+            limiter: Mutex::new(limiter),
+            voice: Mutex::new(voice),
             output_tx,
             running: AtomicBool::new(false),
             frame_count: std::sync::atomic::AtomicU64::new(0),
@@ -167,12 +324,10 @@ impl AudioMixer {
         // Route based on available sources using pattern matching (no unwrap panic risk)
         match (desktop_rx, mic_rx) {
             (Some(desktop), None) => {
-                self.forward_single_source(desktop, self.config.desktop_volume)
-                    .await?;
+                self.forward_single_source(desktop, false).await?;
             }
             (None, Some(mic)) => {
-                self.forward_single_source(mic, self.config.mic_volume)
-                    .await?;
+                self.forward_single_source(mic, true).await?;
             }
             (Some(desktop), Some(mic)) => {
                 self.mix_sources(desktop, mic).await?;
@@ -189,12 +344,16 @@ impl AudioMixer {
     async fn forward_single_source(
         &self,
         mut rx: broadcast::Receiver<Arc<AudioFrame>>,
-        volume: VolumeControl,
+        is_mic: bool,
     ) -> Result<()> {
         while self.running.load(Ordering::SeqCst) {
             match rx.recv().await {
                 Ok(frame) => {
-                    let adjusted = self.apply_volume(&frame, volume.effective_volume());
+                    let adjusted = if is_mic {
+                        self.apply_mic_gain(&frame)
+                    } else {
+                        self.apply_desktop_gain(&frame)
+                    };
                     if self.output_tx.send(Arc::new(adjusted)).is_err() {
                         // No receivers
                     }
@@ -281,14 +440,13 @@ impl AudioMixer {
             // If one source is done, drain the other
             if desktop_done.load(Ordering::SeqCst) {
                 while let Some(mic) = mic_buffer.pop_front() {
-                    let adjusted = self.apply_volume(&mic, self.config.mic_volume.effective_volume());
+                    let adjusted = self.apply_mic_gain(&mic);
                     let _ = self.output_tx.send(Arc::new(adjusted));
                 }
             }
             if mic_done.load(Ordering::SeqCst) {
                 while let Some(desktop) = desktop_buffer.pop_front() {
-                    let adjusted =
-                        self.apply_volume(&desktop, self.config.desktop_volume.effective_volume());
+                    let adjusted = self.apply_desktop_gain(&desktop);
                     let _ = self.output_tx.send(Arc::new(adjusted));
                 }
             }
@@ -302,36 +460,50 @@ impl AudioMixer {
         Ok(())
     }
 
+    /// Whether the mic should duck the desktop for this frame
+    fn mic_active(&self, mic: &AudioFrame) -> bool {
+        match self.config.ducking_mode {
+            DuckingMode::Rms => !mic.is_silent(self.config.ducking_threshold),
+            DuckingMode::Vad => self.voice.lock().process(mic),
+        }
+    }
+
     /// Mix two audio frames together
     fn mix_frames(&self, desktop: &AudioFrame, mic: &AudioFrame) -> AudioFrame {
-        let desktop_vol = self.config.desktop_volume.effective_volume();
-        let mic_vol = self.config.mic_volume.effective_volume();
-
-        // Apply ducking if enabled
-        let effective_desktop_vol = if self.config.ducking_enabled {
-            let mic_amplitude = self.calculate_amplitude(&mic.samples);
-            if mic_amplitude > self.config.ducking_threshold {
-                desktop_vol * (1.0 - self.config.ducking_amount)
-            } else {
-                desktop_vol
-            }
+        // Apply ducking if enabled (ramped like any other gain change)
+        let duck = if self.config.ducking_enabled && self.mic_active(mic) {
+            1.0 - self.config.ducking_amount
         } else {
-            desktop_vol
+            1.0
         };
 
+        let mut desktop_samples = desktop.samples.clone();
+        let mut mic_samples = mic.samples.clone();
+        {
+            let mut gain = self.desktop_gain.lock();
+            gain.set_target(self.config.desktop_volume.effective_volume() * duck);
+            gain.apply(&mut desktop_samples, desktop.format.channels as usize);
+        }
+        {
+            let mut gain = self.mic_gain.lock();
+            gain.set_target(self.config.mic_volume.effective_volume());
+            gain.apply(&mut mic_samples, mic.format.channels as usize);
+        }
+
         // Mix samples - use the longer frame's length
-        let max_len = desktop.samples.len().max(mic.samples.len());
+        let max_len = desktop_samples.len().max(mic_samples.len());
         let mut mixed = Vec::with_capacity(max_len);
 
         for i in 0..max_len {
-            let d = desktop.samples.get(i).copied().unwrap_or(0.0) * effective_desktop_vol;
-            let m = mic.samples.get(i).copied().unwrap_or(0.0) * mic_vol;
+            let d = desktop_samples.get(i).copied().unwrap_or(0.0);
+            let m = mic_samples.get(i).copied().unwrap_or(0.0);
 
-            // Simple additive mixing with soft clipping
-            let sum = d + m;
-            let clipped = soft_clip(sum);
-            mixed.push(clipped);
+            // Simple additive mixing, clipped below
+            mixed.push(d + m);
         }
+        self.limiter
+            .lock()
+            .process(&mut mixed, self.config.output_channels as usize);
 
         AudioFrame {
             format: AudioFormat {
@@ -345,30 +517,16 @@ impl AudioMixer {
         }
     }
 
-    /// Apply volume to audio frame
-    fn apply_volume(&self, frame: &AudioFrame, volume: f32) -> AudioFrame {
-        let samples = if (volume - 1.0).abs() < 0.001 {
-            // Volume is ~1.0, no need to modify
-            frame.samples.clone()
-        } else {
-            frame.samples.iter().map(|s| soft_clip(s * volume)).collect()
-        };
-
-        AudioFrame {
-            format: frame.format.clone(),
-            samples,
-            pts: frame.pts,
-            sample_count: frame.sample_count,
-        }
+    /// Apply the desktop gain to a frame
+    fn apply_desktop_gain(&self, frame: &AudioFrame) -> AudioFrame {
+        let target = self.config.desktop_volume.effective_volume();
+        apply_gain(frame, &mut self.desktop_gain.lock(), &mut self.limiter.lock(), target)
     }
 
-    /// Calculate RMS amplitude of samples
-    fn calculate_amplitude(&self, samples: &[f32]) -> f32 {
-        if samples.is_empty() {
-            return 0.0;
-        }
-        let sum: f32 = samples.iter().map(|s| s * s).sum();
-        (sum / samples.len() as f32).sqrt()
+    /// Apply the microphone gain to a frame
+    fn apply_mic_gain(&self, frame: &AudioFrame) -> AudioFrame {
+        let target = self.config.mic_volume.effective_volume();
+        apply_gain(frame, &mut self.mic_gain.lock(), &mut self.limiter.lock(), target)
     }
 
     /// Stop the mixer
@@ -401,13 +559,13 @@ impl AudioMixer {
         debug!("Mic volume set to {}", volume);
     }
 
-    /// Mute/unmute desktop
+    /// Mute/unmute desktop (fades rather than cuts)
     pub fn set_desktop_muted(&mut self, muted: bool) {
         self.config.desktop_volume.muted = muted;
         debug!("Desktop muted: {}", muted);
     }
 
-    /// Mute/unmute microphone
+    /// Mute/unmute microphone (fades rather than cuts)
     pub fn set_mic_muted(&mut self, muted: bool) {
         self.config.mic_volume.muted = muted;
         debug!("Mic muted: {}", muted);
@@ -430,20 +588,37 @@ impl Drop for AudioMixer {
     }
 }
 
-/// Soft clip function to prevent harsh clipping using tanh
-fn soft_clip(x: f32) -> f32 {
-    // Use tanh for smooth soft clipping
-    // This maps any input to (-1, 1) range smoothly
-    if x.abs() <= 0.5 {
-        x // Linear region for low levels
-    } else {
-        x.tanh()
+/// Ramp `gain` toward `target` across the frame and clip the result
+fn apply_gain(
+    frame: &AudioFrame,
+    gain: &mut GainRamp,
+    limiter: &mut Limiter,
+    target: f32,
+) -> AudioFrame {
+    gain.set_target(target);
+
+    let channels = frame.format.channels as usize;
+    let mut samples = frame.samples.clone();
+    if !gain.is_settled() || (gain.current() - 1.0).abs() >= 0.001 {
+        gain.apply(&mut samples, channels);
+        limiter.process(&mut samples, channels);
+    } else if limiter.is_stateful() {
+        // The look-ahead limiter delays audio, so it has to see every frame
+        limiter.process(&mut samples, channels);
+    }
+
+    AudioFrame {
+        format: frame.format.clone(),
+        samples,
+        pts: frame.pts,
+        sample_count: frame.sample_count,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::capture::limiter::LimiterMode;
 
     #[test]
     fn test_volume_control_default() {
@@ -467,20 +642,63 @@ mod tests {
         let config = MixerConfig::default();
         assert_eq!(config.output_sample_rate, 48000);
         assert_eq!(config.output_channels, 2);
+        assert_eq!(config.mic_channel, MicChannel::Stereo);
         assert!(!config.ducking_enabled);
+        assert_eq!(config.ducking_mode, DuckingMode::Rms);
+        assert_eq!(config.limiter.mode, LimiterMode::Tanh);
+    }
+
+    #[test]
+    fn test_mute_fades_over_successive_frames() {
+        // 10 ms ramp at 48 kHz, fed 64-sample stereo frames
+        let mut gain = GainRamp::new(1.0, 48000, 10);
+        let muted = VolumeControl {
+            volume: 1.0,
+            muted: true,
+        };
+        gain.set_target(muted.effective_volume());
+
+        let mut applied = Vec::new();
+        for _ in 0..5 {
+            let mut samples = vec![1.0f32; 64 * 2];
+            gain.apply(&mut samples, 2);
+            applied.push(samples[samples.len() - 1]);
+        }
+
+        assert!(applied[0] > 0.8, "first frame gain {} dropped too fast", applied[0]);
+        for pair in applied.windows(2) {
+            assert!(pair[1] < pair[0], "gain should keep falling: {:?}", applied);
+        }
+        assert!(applied[4] > 0.0);
+        assert!(!gain.is_settled());
+
+        // Finishes within the ramp time and then stays muted
+        let mut samples = vec![1.0f32; 480 * 2];
+        gain.apply(&mut samples, 2);
+        assert!(gain.is_settled());
+        assert_eq!(samples[samples.len() - 1], 0.0);
     }
 
     #[test]
-    fn test_soft_clip() {
-        // Linear region
-        assert_eq!(soft_clip(0.3), 0.3);
-        assert_eq!(soft_clip(-0.3), -0.3);
-        // Soft clipping region (uses tanh)
-        let clipped = soft_clip(2.0);
-        assert!(clipped < 1.0, "soft_clip(2.0) = {} should be < 1.0", clipped);
-        assert!(clipped > 0.9, "soft_clip(2.0) = {} should be > 0.9", clipped);
-        let neg_clipped = soft_clip(-2.0);
-        assert!(neg_clipped > -1.0, "soft_clip(-2.0) = {} should be > -1.0", neg_clipped);
-        assert!(neg_clipped < -0.9, "soft_clip(-2.0) = {} should be < -0.9", neg_clipped);
+    fn test_gain_ramp_within_frame_is_linear() {
+        let mut gain = GainRamp::new(0.0, 1000, 10); // 0.1 per sample frame
+        gain.set_target(0.5);
+
+        let mut samples = vec![1.0f32; 8];
+        gain.apply(&mut samples, 1);
+        let expected = [0.1, 0.2, 0.3, 0.4, 0.5, 0.5, 0.5, 0.5];
+        for (got, want) in samples.iter().zip(expected) {
+            assert!((got - want).abs() < 1e-6, "{:?}", samples);
+        }
+    }
+
+    #[test]
+    fn test_zero_ramp_is_instant() {
+        let mut gain = GainRamp::new(1.0, 48000, 0);
+        gain.set_target(0.25);
+
+        let mut samples = vec![1.0f32; 4];
+        gain.apply(&mut samples, 2);
+        assert_eq!(samples, vec![0.25; 4]);
     }
 }
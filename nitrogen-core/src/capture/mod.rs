@@ -1,24 +1,45 @@
 //! Screen and audio capture via xdg-desktop-portal and PipeWire
 //!
 //! This module handles:
-//! - Source enumeration via portals
+//! - Source enumeration via portals, optionally cached
+//! - Output (connector name) discovery via sysfs
 //! - Screencast session setup
 //! - PipeWire stream connection for video frames
-//! - PipeWire audio capture (desktop and microphone)
-//! - Audio mixing (combining multiple sources)
+//! - PipeWire audio capture (desktop, microphone or a single application)
+//! - Audio mixing (combining multiple sources), ducked by voice activity
+//! - Compositing several video sources into one output
+//! - Drawing the cursor from portal cursor metadata
 //! - (Future) Direct DRM capture for lower latency
 
 pub mod audio;
+pub mod compositor;
+pub mod cursor;
 pub mod drm;
+pub mod limiter;
 pub mod mixer;
+pub mod outputs;
 pub mod portal;
+pub mod sources;
 pub mod stream;
+pub mod vad;
 
-pub use audio::AudioCaptureStream;
+pub use audio::{
+    apply_mic_channel, find_audio_application, list_audio_applications, AudioApplication,
+    AudioCaptureStream,
+};
+pub use compositor::{grid_layout, CompositeCapture, Compositor, LayoutLayer};
+pub use cursor::{CursorBitmap, CursorOverlay, DEFAULT_CURSOR_SCALE, MAX_CURSOR_SCALE};
 pub use drm::DrmCapture;
-pub use mixer::{AudioMixer, MixerConfig, VolumeControl};
-pub use portal::PortalCapture;
-pub use stream::CaptureStream;
+pub use limiter::{Limiter, LimiterConfig, LimiterMode, TruePeakLimiter};
+pub use mixer::{AudioMixer, GainRamp, MixerConfig, VolumeControl, DEFAULT_GAIN_RAMP_MS};
+pub use outputs::{find_output, list_outputs, OutputInfo};
+pub use portal::{PortalCapture, SessionStream, DEFAULT_PORTAL_ATTEMPTS};
+pub use sources::{diff_sources, PortalSources, SourceChange, SourceEnumerator, SourceProvider};
+pub use stream::{
+    request_node_latency, CaptureStream, FrameDecimator, FrameWatchdog, StreamOptions,
+    WatchdogStatus, LATENCY_RATE, MAX_LATENCY_QUANTUM,
+};
+pub use vad::{zero_crossing_rate, DuckingMode, VoiceDetector};
 
 use crate::error::Result;
 use crate::types::SourceInfo;
@@ -28,6 +49,7 @@ use crate::types::SourceInfo;
 /// This queries the xdg-desktop-portal for available screens and windows.
 /// Note: On Wayland, the portal may return limited information until a
 /// capture session is started (at which point the user picks the source).
+/// Callers listing repeatedly should keep a [`SourceEnumerator`] instead.
 pub async fn list_sources() -> Result<Vec<SourceInfo>> {
     portal::list_sources().await
 }
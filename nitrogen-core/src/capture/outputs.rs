@@ -0,0 +1,167 @@
+//! Display output (connector) discovery
+//!
+//! Screencast portals don't enumerate outputs, so connector names such as
+//! `DP-2` or `HDMI-A-1` are read from sysfs (`/sys/class/drm/card*-*`).
+//! This works regardless of compositor and lets `--monitor DP-2` be matched
+//! against the stream the portal hands back.
+
+use std::path::Path;
+
+use tracing::debug;
+
+/// sysfs directory with one entry per DRM connector
+const DRM_SYSFS_ROOT: &str = "/sys/class/drm";
+
+/// A display output known to the kernel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputInfo {
+    /// Connector name (e.g. "DP-2", "HDMI-A-1", "eDP-1")
+    pub name: String,
+    /// DRM card the connector belongs to (e.g. "card1")
+    pub card: String,
+    /// Whether a display is plugged in
+    pub connected: bool,
+    /// Whether the compositor has the output enabled
+    pub enabled: bool,
+    /// Preferred mode of the attached display, if known
+    pub resolution: Option<(u32, u32)>,
+}
+
+/// List display outputs by connector name
+///
+/// Returns an empty list when sysfs isn't available (containers, non-DRM
+/// systems). Connected outputs are listed first.
+pub fn list_outputs() -> Vec<OutputInfo> {
+    list_outputs_in(Path::new(DRM_SYSFS_ROOT))
+}
+
+/// List display outputs from a sysfs-style DRM directory
+pub fn list_outputs_in(root: &Path) -> Vec<OutputInfo> {
+    let entries = match std::fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) => {
+            debug!("Cannot read {}: {}", root.display(), e);
+            return Vec::new();
+        }
+    };
+
+    let mut outputs: Vec<OutputInfo> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let dir_name = entry.file_name().to_string_lossy().into_owned();
+            let (card, name) = split_connector_dir(&dir_name)?;
+            let path = entry.path();
+            let read = |file: &str| std::fs::read_to_string(path.join(file)).unwrap_or_default();
+
+            Some(OutputInfo {
+                name: name.to_string(),
+                card: card.to_string(),
+                connected: read("status").trim() == "connected",
+                enabled: read("enabled").trim() == "enabled",
+                resolution: read("modes").lines().next().and_then(parse_mode),
+            })
+        })
+        .collect();
+
+    outputs.sort_by(|a, b| b.connected.cmp(&a.connected).then_with(|| a.name.cmp(&b.name)));
+    outputs
+}
+
+/// Find a connected output by connector name (case-insensitive)
+///
+/// Accepts a `monitor:` prefix, so `monitor:DP-2` and `DP-2` are equivalent.
+pub fn find_output(name: &str) -> Option<OutputInfo> {
+    find_output_in(&list_outputs(), name).cloned()
+}
+
+/// Find a connected output by connector name in a list of outputs
+pub fn find_output_in<'a>(outputs: &'a [OutputInfo], name: &str) -> Option<&'a OutputInfo> {
+    let name = output_name(name);
+    outputs
+        .iter()
+        .find(|o| o.connected && o.name.eq_ignore_ascii_case(name))
+}
+
+/// Strip an optional `monitor:` prefix from a source ID
+pub fn output_name(id: &str) -> &str {
+    id.strip_prefix("monitor:").unwrap_or(id)
+}
+
+/// Check whether a monitor source ID asks for the portal picker
+pub fn is_picker_id(id: &str) -> bool {
+    let id = output_name(id);
+    id.is_empty() || id == "portal" || id.starts_with("portal:")
+}
+
+/// Split a sysfs connector directory ("card1-DP-2") into card and connector
+fn split_connector_dir(dir_name: &str) -> Option<(&str, &str)> {
+    let (card, name) = dir_name.split_once('-')?;
+    let is_card = card
+        .strip_prefix("card")
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+    (is_card && !name.is_empty()).then_some((card, name))
+}
+
+/// Parse a sysfs mode line such as "2560x1440" or "1920x1080i"
+fn parse_mode(line: &str) -> Option<(u32, u32)> {
+    let (width, height) = line.trim().split_once('x')?;
+    let height: String = height.chars().take_while(|c| c.is_ascii_digit()).collect();
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_connector(root: &Path, dir: &str, status: &str, modes: &str) {
+        let path = root.join(dir);
+        std::fs::create_dir_all(&path).unwrap();
+        std::fs::write(path.join("status"), format!("{}\n", status)).unwrap();
+        std::fs::write(path.join("enabled"), "enabled\n").unwrap();
+        std::fs::write(path.join("modes"), modes).unwrap();
+    }
+
+    #[test]
+    fn test_list_outputs_from_sysfs() {
+        let root = tempfile::tempdir().unwrap();
+        write_connector(root.path(), "card1-DP-2", "connected", "2560x1440\n1920x1080\n");
+        write_connector(root.path(), "card1-HDMI-A-1", "disconnected", "");
+        // Non-connector entries are ignored
+        std::fs::create_dir_all(root.path().join("card1")).unwrap();
+        std::fs::create_dir_all(root.path().join("renderD128")).unwrap();
+
+        let outputs = list_outputs_in(root.path());
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(outputs[0].name, "DP-2");
+        assert_eq!(outputs[0].card, "card1");
+        assert!(outputs[0].connected);
+        assert_eq!(outputs[0].resolution, Some((2560, 1440)));
+        assert!(!outputs[1].connected);
+        assert_eq!(outputs[1].resolution, None);
+
+        assert_eq!(find_output_in(&outputs, "monitor:dp-2").map(|o| o.name.as_str()), Some("DP-2"));
+        // Disconnected outputs can't be captured
+        assert!(find_output_in(&outputs, "HDMI-A-1").is_none());
+    }
+
+    #[test]
+    fn test_list_outputs_missing_root() {
+        assert!(list_outputs_in(Path::new("/nonexistent/drm")).is_empty());
+    }
+
+    #[test]
+    fn test_picker_ids() {
+        assert!(is_picker_id("portal"));
+        assert!(is_picker_id("portal:screen"));
+        assert!(is_picker_id(""));
+        assert!(!is_picker_id("DP-2"));
+        assert!(!is_picker_id("monitor:DP-2"));
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("3840x2160"), Some((3840, 2160)));
+        assert_eq!(parse_mode("1920x1080i"), Some((1920, 1080)));
+        assert_eq!(parse_mode("garbage"), None);
+    }
+}
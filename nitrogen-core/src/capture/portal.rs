@@ -4,16 +4,31 @@
 //! - Listing available monitors/windows
 //! - Starting capture sessions
 //! - Getting PipeWire node IDs for stream connection
+//! - Matching the selected stream to a connector name (e.g. `DP-2`)
+//! - Granting several monitors in one session, one PipeWire stream each
+//! - Retrying session creation when the portal fails transiently
 
 use ashpd::desktop::screencast::{CursorMode as AshpdCursorMode, Screencast, SourceType};
+use ashpd::desktop::Session;
 use ashpd::{enumflags2::BitFlags, WindowIdentifier};
+use std::future::Future;
+use std::os::fd::OwnedFd;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 use tracing::{debug, info, warn};
 
+use super::outputs::{self, OutputInfo};
+use super::stream::{CaptureStream, StreamOptions};
 use crate::config::CursorMode;
 use crate::error::{NitrogenError, Result};
-use crate::types::{SourceInfo, SourceKind};
+use crate::types::{CaptureSource, SourceInfo, SourceKind};
+
+/// Attempts at starting a session before a transient portal error is reported
+pub const DEFAULT_PORTAL_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry; doubled for each further one
+const PORTAL_RETRY_BACKOFF: Duration = Duration::from_millis(500);
 
 /// Convert our CursorMode to ashpd's CursorMode
 fn to_ashpd_cursor_mode(mode: CursorMode) -> AshpdCursorMode {
@@ -30,14 +45,20 @@ pub struct PortalCapture {
     screencast: Screencast<'static>,
     /// Active session (if any)
     session: Arc<Mutex<Option<ActiveSession>>>,
+    /// Attempts at starting a session on transient portal errors
+    attempts: u32,
 }
 
 /// Active screencast session state
 struct ActiveSession {
     /// PipeWire node ID for the stream
     pub node_id: u32,
-    /// PipeWire file descriptor
-    pub fd: std::os::fd::OwnedFd,
+    /// Every node granted by the portal, primary first
+    pub node_ids: Vec<u32>,
+    /// PipeWire file descriptor (taken by the capture stream)
+    pub fd: Option<std::os::fd::OwnedFd>,
+    /// Portal session handle, kept so the PipeWire remote can be reopened
+    pub session: Session<'static, Screencast<'static>>,
 }
 
 impl PortalCapture {
@@ -47,9 +68,18 @@ impl PortalCapture {
         Ok(Self {
             screencast,
             session: Arc::new(Mutex::new(None)),
+            attempts: DEFAULT_PORTAL_ATTEMPTS,
         })
     }
 
+    /// Try starting a session up to `attempts` times on transient errors
+    ///
+    /// Cancelling the selection dialog is never retried.
+    pub fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts.max(1);
+        self
+    }
+
     /// Start a screencast session
     ///
     /// This will prompt the user to select a screen/window via the portal.
@@ -60,17 +90,154 @@ impl PortalCapture {
         cursor_mode: CursorMode,
         multiple: bool,
     ) -> Result<SessionInfo> {
+        self.start_session_inner(capture_type, cursor_mode, multiple, None).await
+    }
+
+    /// Start a screencast session for an output given by connector name
+    ///
+    /// Best effort, for compositors like wlroots and KDE where the output is
+    /// known up front. The connector is resolved via sysfs and matched
+    /// against the streams the portal returns: by stream ID where the
+    /// compositor exposes connector metadata, otherwise by size. Selecting
+    /// several outputs in the picker is allowed; only the matching one is
+    /// used. Unknown connectors fall back to the plain picker.
+    pub async fn start_output_session(
+        &self,
+        cursor_mode: CursorMode,
+        output: &str,
+    ) -> Result<SessionInfo> {
+        let Some(info) = outputs::find_output(output) else {
+            let known: Vec<String> = outputs::list_outputs()
+                .into_iter()
+                .filter(|o| o.connected)
+                .map(|o| o.name)
+                .collect();
+            warn!(
+                "Output '{}' not found (connected outputs: {}), falling back to the portal picker",
+                outputs::output_name(output),
+                if known.is_empty() { "unknown".to_string() } else { known.join(", ") }
+            );
+            return self
+                .start_session_inner(CaptureType::Monitor, cursor_mode, false, None)
+                .await;
+        };
+
+        info!("Requesting output {} ({})", info.name, info.card);
+        self.start_session_inner(CaptureType::Monitor, cursor_mode, true, Some(&info))
+            .await
+    }
+
+    /// Start one session for several sources (one portal dialog)
+    ///
+    /// The user can select multiple monitors or windows and each comes
+    /// back as its own PipeWire node, in the order the portal lists them;
+    /// see [`capture_streams`](Self::capture_streams) to connect them all.
+    /// Compositors that only grant a single stream per session still work,
+    /// with a warning, and return one entry.
+    pub async fn start_multi_session(
+        &self,
+        capture_type: CaptureType,
+        cursor_mode: CursorMode,
+    ) -> Result<Vec<SessionStream>> {
+        let streams = self.start_streams(capture_type, cursor_mode, true, None).await?;
+        if streams.len() == 1 {
+            warn!("The portal granted a single stream; capturing only {}", streams[0].source.name);
+        } else {
+            info!("Portal granted {} streams in one session", streams.len());
+        }
+        Ok(streams)
+    }
+
+    async fn start_session_inner(
+        &self,
+        capture_type: CaptureType,
+        cursor_mode: CursorMode,
+        multiple: bool,
+        output: Option<&OutputInfo>,
+    ) -> Result<SessionInfo> {
+        let streams = self.start_streams(capture_type, cursor_mode, multiple, output).await?;
+        // Only the primary stream is used; other selections stay granted
+        Ok(streams[0].info.clone())
+    }
+
+    /// Run the picker and start the session, returning every granted stream
+    ///
+    /// With an `output`, the stream matching it is moved to the front.
+    /// Transient portal failures are retried, see
+    /// [`with_attempts`](Self::with_attempts).
+    async fn start_streams(
+        &self,
+        capture_type: CaptureType,
+        cursor_mode: CursorMode,
+        multiple: bool,
+        output: Option<&OutputInfo>,
+    ) -> Result<Vec<SessionStream>> {
         let mut session_guard = self.session.lock().await;
 
         if session_guard.is_some() {
             return Err(NitrogenError::SessionAlreadyRunning);
         }
 
+        let cursor_mode = self.supported_cursor_mode(cursor_mode).await;
+
+        let (granted, active) = start_with_retry(self.attempts, PORTAL_RETRY_BACKOFF, move || {
+            self.try_start_streams(capture_type, cursor_mode, multiple, output)
+        })
+        .await?;
+
+        *session_guard = Some(active);
+
+        Ok(granted)
+    }
+
+    /// One attempt at [`start_streams`](Self::start_streams)
+    ///
+    /// A portal session that fails halfway is closed again, so the next
+    /// attempt starts from scratch.
+    async fn try_start_streams(
+        &self,
+        capture_type: CaptureType,
+        cursor_mode: CursorMode,
+        multiple: bool,
+        output: Option<&OutputInfo>,
+    ) -> Result<(Vec<SessionStream>, ActiveSession)> {
         info!("Creating screencast session via portal");
 
         // Create a new session
         let session = self.screencast.create_session().await?;
 
+        let result = self
+            .select_and_start(&session, capture_type, cursor_mode, multiple, output)
+            .await;
+        let (granted, fd) = match result {
+            Ok(started) => started,
+            Err(e) => {
+                if let Err(close) = session.close().await {
+                    debug!("Failed to close portal session: {}", close);
+                }
+                return Err(e);
+            }
+        };
+
+        let active = ActiveSession {
+            node_id: granted[0].info.node_id,
+            node_ids: granted.iter().map(|s| s.info.node_id).collect(),
+            fd: Some(fd),
+            session,
+        };
+
+        Ok((granted, active))
+    }
+
+    /// Show the picker and start the screencast in `session`
+    async fn select_and_start(
+        &self,
+        session: &Session<'static, Screencast<'static>>,
+        capture_type: CaptureType,
+        cursor_mode: CursorMode,
+        multiple: bool,
+        output: Option<&OutputInfo>,
+    ) -> Result<(Vec<SessionStream>, OwnedFd)> {
         // Select sources based on capture type
         let source_type: BitFlags<SourceType> = match capture_type {
             CaptureType::Monitor => SourceType::Monitor.into(),
@@ -83,7 +250,7 @@ impl PortalCapture {
         // Select sources (this triggers the portal dialog)
         self.screencast
             .select_sources(
-                &session,
+                session,
                 to_ashpd_cursor_mode(cursor_mode),
                 source_type,
                 multiple,
@@ -97,7 +264,7 @@ impl PortalCapture {
         // Start the screencast (use None for CLI apps without a window)
         let response = self
             .screencast
-            .start(&session, None::<&WindowIdentifier>)
+            .start(session, None::<&WindowIdentifier>)
             .await?
             .response()?;
 
@@ -106,52 +273,113 @@ impl PortalCapture {
             return Err(NitrogenError::portal("No streams returned from portal"));
         }
 
-        let stream = &streams[0];
-        let node_id = stream.pipe_wire_node_id();
-
-        debug!("Got PipeWire node ID: {}", node_id);
+        let index = match output {
+            Some(output) => {
+                let candidates: Vec<_> = streams.iter().map(|s| (s.id(), s.size())).collect();
+                match match_stream(&candidates, output) {
+                    Some(i) => {
+                        info!("Matched portal stream {} to output {}", i, output.name);
+                        i
+                    }
+                    None => {
+                        warn!(
+                            "Could not match a portal stream to output {}, using the first selected stream",
+                            output.name
+                        );
+                        0
+                    }
+                }
+            }
+            None => 0,
+        };
 
         // Get the PipeWire file descriptor
-        let fd = self.screencast.open_pipe_wire_remote(&session).await?;
-
-        info!(
-            "Screencast session started: node_id={}, fd={:?}",
-            node_id, fd
-        );
-
-        // Determine source kind from stream properties
-        let source_kind = stream
-            .source_type()
-            .map(|st| {
-                if st == SourceType::Monitor {
-                    SourceKind::Monitor
-                } else {
-                    SourceKind::Window
+        let fd = self.screencast.open_pipe_wire_remote(session).await?;
+
+        let mut granted: Vec<SessionStream> = streams
+            .iter()
+            .enumerate()
+            .map(|(i, stream)| {
+                let node_id = stream.pipe_wire_node_id();
+                // Determine source kind from stream properties
+                let kind = match stream.source_type() {
+                    Some(SourceType::Monitor) | None => SourceKind::Monitor,
+                    Some(_) => SourceKind::Window,
+                };
+                // Get stream dimensions if available
+                let (width, height) = match stream.size() {
+                    Some((w, h)) => (w.max(0) as u32, h.max(0) as u32),
+                    None => {
+                        warn!(
+                            "Portal did not report dimensions for node {}, using default 1920x1080",
+                            node_id
+                        );
+                        (1920, 1080)
+                    }
+                };
+
+                SessionStream {
+                    info: SessionInfo {
+                        node_id,
+                        width,
+                        height,
+                        source_type: kind,
+                        cursor_mode,
+                    },
+                    source: stream_source_info(i, node_id, stream.id(), kind, (width, height)),
                 }
             })
-            .unwrap_or(SourceKind::Monitor);
-
-        let active = ActiveSession { node_id, fd };
-
-        // Get stream dimensions if available
-        let (width, height) = match stream.size() {
-            Some((w, h)) => (w, h),
-            None => {
-                warn!("Portal did not report stream dimensions, using default 1920x1080");
-                (1920, 1080)
+            .collect();
+        if index > 0 {
+            let primary = granted.remove(index);
+            granted.insert(0, primary);
+        }
+        // A size match can't be confirmed; a stream ID naming another output can
+        if let Some(output) = output {
+            let requested = CaptureSource::monitor(&output.name);
+            let primary = &granted[0].source;
+            if requested.matches(primary) {
+                debug!("Portal stream confirmed as output {}", output.name);
+            } else if !primary.id.starts_with("node:") {
+                warn!("Requested output {} but the portal granted {}", output.name, primary.id);
             }
-        };
+        }
 
-        let info = SessionInfo {
+        let node_id = granted[0].info.node_id;
+        info!(
+            "Screencast session started: node_id={}, fd={:?}{}",
             node_id,
-            width: width.max(0) as u32,
-            height: height.max(0) as u32,
-            source_type: source_kind,
-        };
+            fd,
+            if granted.len() > 1 {
+                format!(" ({} streams)", granted.len())
+            } else {
+                String::new()
+            }
+        );
 
-        *session_guard = Some(active);
+        Ok((granted, fd))
+    }
 
-        Ok(info)
+    /// Fall back to an embedded cursor if the portal can't send cursor metadata
+    async fn supported_cursor_mode(&self, requested: CursorMode) -> CursorMode {
+        if requested != CursorMode::Metadata {
+            return requested;
+        }
+
+        match self.screencast.available_cursor_modes().await {
+            Ok(modes) if modes.contains(AshpdCursorMode::Metadata) => requested,
+            Ok(_) => {
+                warn!("Compositor does not provide cursor metadata, falling back to an embedded cursor");
+                CursorMode::Embedded
+            }
+            Err(e) => {
+                warn!(
+                    "Could not query supported cursor modes ({}), falling back to an embedded cursor",
+                    e
+                );
+                CursorMode::Embedded
+            }
+        }
     }
 
     /// Get the PipeWire file descriptor for the active session
@@ -170,19 +398,62 @@ impl PortalCapture {
             .ok_or(NitrogenError::NoActiveSession)
     }
 
+    /// PipeWire node IDs of every stream in the session, primary first
+    pub async fn pipewire_node_ids(&self) -> Result<Vec<u32>> {
+        let session = self.session.lock().await;
+        session
+            .as_ref()
+            .map(|s| s.node_ids.clone())
+            .ok_or(NitrogenError::NoActiveSession)
+    }
+
+    /// Connect a capture stream to every node in the session
+    ///
+    /// The first stream uses the session's fd (like
+    /// [`take_pipewire_fd`](Self::take_pipewire_fd)); the others get their
+    /// own PipeWire remote from the same session, so no dialog is shown.
+    pub async fn capture_streams(&self, options: StreamOptions) -> Result<Vec<CaptureStream>> {
+        let node_ids = self.pipewire_node_ids().await?;
+        let mut streams = Vec::with_capacity(node_ids.len());
+        for (i, node_id) in node_ids.into_iter().enumerate() {
+            let fd = if i == 0 {
+                self.take_pipewire_fd().await?
+            } else {
+                self.reopen_pipewire_fd().await?
+            };
+            streams.push(CaptureStream::with_options(fd, node_id, options)?);
+        }
+        Ok(streams)
+    }
+
     /// Take ownership of the session's PipeWire fd
     pub async fn take_pipewire_fd(&self) -> Result<std::os::fd::OwnedFd> {
         let mut session = self.session.lock().await;
         session
-            .take()
-            .map(|s| s.fd)
+            .as_mut()
+            .and_then(|s| s.fd.take())
             .ok_or(NitrogenError::NoActiveSession)
     }
 
+    /// Open a new PipeWire remote for the active session
+    ///
+    /// Used to reconnect a stalled capture stream without prompting the
+    /// user to pick the source again.
+    pub async fn reopen_pipewire_fd(&self) -> Result<std::os::fd::OwnedFd> {
+        let session = self.session.lock().await;
+        let active = session.as_ref().ok_or(NitrogenError::NoActiveSession)?;
+        let fd = self.screencast.open_pipe_wire_remote(&active.session).await?;
+        debug!("Reopened PipeWire remote for node {}: fd={:?}", active.node_id, fd);
+        Ok(fd)
+    }
+
     /// Stop the active session
     pub async fn stop_session(&self) -> Result<()> {
         let mut session = self.session.lock().await;
-        if session.take().is_some() {
+        if let Some(active) = session.take() {
+            if let Err(e) = active.session.close().await {
+                debug!("Failed to close portal session: {}", e);
+            }
             info!("Screencast session stopped");
             Ok(())
         } else {
@@ -196,6 +467,37 @@ impl PortalCapture {
     }
 }
 
+/// Run `start` up to `attempts` times while it fails transiently
+///
+/// The wait between attempts starts at `backoff` and doubles each time.
+/// Cancellation and other errors are returned straight away.
+async fn start_with_retry<T, F, Fut>(attempts: u32, backoff: Duration, mut start: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let attempts = attempts.max(1);
+    let mut delay = backoff;
+    let mut attempt = 1;
+    loop {
+        match start().await {
+            Ok(started) => {
+                if attempt > 1 {
+                    info!("Portal session started on attempt {}/{}", attempt, attempts);
+                }
+                return Ok(started);
+            }
+            Err(e) if e.is_transient() && attempt < attempts => {
+                warn!("{}, retrying in {:?} (attempt {}/{})", e, delay, attempt, attempts);
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 /// Type of sources to capture
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CaptureType {
@@ -218,6 +520,77 @@ pub struct SessionInfo {
     pub height: u32,
     /// Type of source that was selected
     pub source_type: SourceKind,
+    /// Cursor mode in effect (Metadata falls back to Embedded when unsupported)
+    pub cursor_mode: CursorMode,
+}
+
+/// One stream of a portal session
+#[derive(Debug, Clone)]
+pub struct SessionStream {
+    /// Node, size and cursor mode of the stream
+    pub info: SessionInfo,
+    /// What the stream shows, as far as the portal tells
+    pub source: SourceInfo,
+}
+
+/// Describe a granted portal stream
+///
+/// The portal only names streams on compositors that set a stream ID
+/// (often the connector, e.g. `DP-2`); others get a numbered name.
+fn stream_source_info(
+    index: usize,
+    node_id: u32,
+    id: Option<&str>,
+    kind: SourceKind,
+    dimensions: (u32, u32),
+) -> SourceInfo {
+    let (id, name) = match id.filter(|id| !id.is_empty()) {
+        Some(id) => (id.to_string(), id.to_string()),
+        None => {
+            let label = match kind {
+                SourceKind::Window => "Window",
+                _ => "Monitor",
+            };
+            (format!("node:{}", node_id), format!("{} {}", label, index + 1))
+        }
+    };
+
+    SourceInfo {
+        id,
+        name,
+        kind,
+        dimensions,
+        refresh_rate: None,
+        hw_accelerated: true,
+    }
+}
+
+/// Pick the portal stream that corresponds to an output
+///
+/// `streams` holds each stream's (ID, size). A stream ID equal to the
+/// connector name wins; otherwise a size match is accepted only if it is
+/// unique, since several outputs may share a resolution.
+pub(crate) fn match_stream(
+    streams: &[(Option<&str>, Option<(i32, i32)>)],
+    output: &OutputInfo,
+) -> Option<usize> {
+    if let Some(i) = streams
+        .iter()
+        .position(|(id, _)| id.is_some_and(|id| id.eq_ignore_ascii_case(&output.name)))
+    {
+        return Some(i);
+    }
+
+    let (width, height) = output.resolution?;
+    let size = Some((width as i32, height as i32));
+    let mut matches = streams
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, stream_size))| *stream_size == size);
+    match (matches.next(), matches.next()) {
+        (Some((i, _)), None) => Some(i),
+        _ => None,
+    }
 }
 
 /// List available capture sources
@@ -258,10 +631,119 @@ pub async fn list_sources() -> Result<Vec<SourceInfo>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ashpd::desktop::ResponseError;
+    use std::collections::VecDeque;
+
+    /// Portal answering session requests from a script
+    struct MockPortal {
+        responses: parking_lot::Mutex<VecDeque<Result<u32>>>,
+        requests: std::sync::atomic::AtomicU32,
+    }
+
+    impl MockPortal {
+        fn new(responses: impl IntoIterator<Item = Result<u32>>) -> Self {
+            Self {
+                responses: parking_lot::Mutex::new(responses.into_iter().collect()),
+                requests: Default::default(),
+            }
+        }
+
+        /// Start a session, returning its node ID
+        async fn start(&self) -> Result<u32> {
+            self.requests.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.responses.lock().pop_front().expect("unexpected portal request")
+        }
+
+        fn requests(&self) -> u32 {
+            self.requests.load(std::sync::atomic::Ordering::SeqCst)
+        }
+    }
+
+    const NO_WAIT: Duration = Duration::from_millis(1);
+
+    #[tokio::test]
+    async fn test_transient_failure_is_retried() {
+        // What GNOME returns for the first ScreenCast request after login
+        let portal = MockPortal::new([
+            Err(ashpd::Error::Response(ResponseError::Other).into()),
+            Ok(57),
+        ]);
+
+        let node_id = start_with_retry(3, NO_WAIT, || portal.start()).await.unwrap();
+        assert_eq!(node_id, 57);
+        assert_eq!(portal.requests(), 2);
+
+        // Gives up once the attempts are used up
+        let busy = || {
+            let io = std::io::Error::from(std::io::ErrorKind::BrokenPipe);
+            NitrogenError::from(zbus::Error::InputOutput(Arc::new(io)))
+        };
+        let portal = MockPortal::new([Err(busy()), Err(busy()), Ok(57)]);
+        let err = start_with_retry(2, NO_WAIT, || portal.start()).await.unwrap_err();
+        assert!(err.is_transient());
+        assert_eq!(portal.requests(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_is_not_retried() {
+        let portal = MockPortal::new([
+            Err(ashpd::Error::Response(ResponseError::Cancelled).into()),
+            Ok(57),
+        ]);
+
+        let err = start_with_retry(3, NO_WAIT, || portal.start()).await.unwrap_err();
+        assert!(err.is_cancelled());
+        assert!(matches!(err, NitrogenError::CaptureCancelled));
+        assert_eq!(portal.requests(), 1);
+
+        // Neither are permanent failures
+        let unsupported = NitrogenError::portal("Screencast not supported");
+        let portal = MockPortal::new([Err(unsupported), Ok(57)]);
+        assert!(start_with_retry(3, NO_WAIT, || portal.start()).await.is_err());
+        assert_eq!(portal.requests(), 1);
+    }
 
     #[tokio::test]
     async fn test_capture_type() {
         assert_eq!(CaptureType::Monitor, CaptureType::Monitor);
         assert_ne!(CaptureType::Monitor, CaptureType::Window);
     }
+
+    #[test]
+    fn test_stream_source_info() {
+        let named = stream_source_info(0, 57, Some("DP-2"), SourceKind::Monitor, (2560, 1440));
+        assert_eq!(named.id, "DP-2");
+        assert_eq!(named.name, "DP-2");
+        assert_eq!(named.dimensions, (2560, 1440));
+
+        // Streams without an ID are numbered by their position in the session
+        let unnamed = stream_source_info(1, 63, None, SourceKind::Monitor, (1920, 1080));
+        assert_eq!(unnamed.id, "node:63");
+        assert_eq!(unnamed.name, "Monitor 2");
+        let window = stream_source_info(0, 70, Some(""), SourceKind::Window, (800, 600));
+        assert_eq!(window.name, "Window 1");
+    }
+
+    #[test]
+    fn test_match_stream() {
+        let output = OutputInfo {
+            name: "DP-2".to_string(),
+            card: "card1".to_string(),
+            connected: true,
+            enabled: true,
+            resolution: Some((2560, 1440)),
+        };
+
+        // Stream ID carrying the connector name wins
+        let streams = [(Some("HDMI-A-1"), Some((2560, 1440))), (Some("dp-2"), None)];
+        assert_eq!(match_stream(&streams, &output), Some(1));
+
+        // Otherwise a unique size match
+        let streams = [(None, Some((1920, 1080))), (None, Some((2560, 1440)))];
+        assert_eq!(match_stream(&streams, &output), Some(1));
+
+        // Ambiguous sizes can't be resolved
+        let streams = [(None, Some((2560, 1440))), (None, Some((2560, 1440)))];
+        assert_eq!(match_stream(&streams, &output), None);
+    }
 }
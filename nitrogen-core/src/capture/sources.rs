@@ -0,0 +1,206 @@
+//! Cached source enumeration
+//!
+//! [`list_sources`](super::list_sources) asks the portal every time, which
+//! is a D-Bus round-trip a GUI redrawing its source picker doesn't want to
+//! pay on every frame. [`SourceEnumerator`] keeps the last result until it
+//! is refreshed, and tells subscribers which sources appeared or went away.
+//! The portal has no change signal of its own, so changes are detected by
+//! comparing each refresh with the cached list.
+
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use tokio::sync::broadcast;
+use tracing::debug;
+
+use crate::error::Result;
+use crate::types::SourceInfo;
+
+/// Capacity of the change notification channel
+const CHANGE_CHANNEL_CAPACITY: usize = 32;
+
+/// Where a [`SourceEnumerator`] gets its sources from
+#[async_trait]
+pub trait SourceProvider: Send + Sync {
+    /// Enumerate the sources available right now
+    async fn list_sources(&self) -> Result<Vec<SourceInfo>>;
+}
+
+/// Sources reported by xdg-desktop-portal
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PortalSources;
+
+#[async_trait]
+impl SourceProvider for PortalSources {
+    async fn list_sources(&self) -> Result<Vec<SourceInfo>> {
+        super::portal::list_sources().await
+    }
+}
+
+/// A source appearing or disappearing between two refreshes
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceChange {
+    /// A source that wasn't listed before
+    Added(SourceInfo),
+    /// A previously listed source that is gone
+    Removed(SourceInfo),
+}
+
+/// Source list that is only re-enumerated on demand
+pub struct SourceEnumerator {
+    provider: Box<dyn SourceProvider>,
+    cache: RwLock<Option<Vec<SourceInfo>>>,
+    changes: broadcast::Sender<SourceChange>,
+}
+
+impl Default for SourceEnumerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SourceEnumerator {
+    /// Enumerate portal sources
+    pub fn new() -> Self {
+        Self::with_provider(PortalSources)
+    }
+
+    /// Enumerate sources from a custom provider
+    pub fn with_provider(provider: impl SourceProvider + 'static) -> Self {
+        let (changes, _) = broadcast::channel(CHANGE_CHANNEL_CAPACITY);
+        Self {
+            provider: Box::new(provider),
+            cache: RwLock::new(None),
+            changes,
+        }
+    }
+
+    /// The cached sources, enumerating them first if there are none yet
+    pub async fn list(&self) -> Result<Vec<SourceInfo>> {
+        if let Some(sources) = self.cached() {
+            return Ok(sources);
+        }
+        self.refresh().await
+    }
+
+    /// The cached sources without enumerating (None before the first refresh)
+    pub fn cached(&self) -> Option<Vec<SourceInfo>> {
+        self.cache.read().clone()
+    }
+
+    /// Enumerate again, replacing the cache and notifying subscribers
+    ///
+    /// On error the cache is left as it was.
+    pub async fn refresh(&self) -> Result<Vec<SourceInfo>> {
+        let sources = self.provider.list_sources().await?;
+        let previous = self.cache.write().replace(sources.clone());
+
+        // The first enumeration is not a change
+        if let Some(previous) = previous {
+            let changes = diff_sources(&previous, &sources);
+            if !changes.is_empty() {
+                debug!("Capture sources changed: {:?}", changes);
+            }
+            for change in changes {
+                // Nobody listening is fine
+                let _ = self.changes.send(change);
+            }
+        }
+        Ok(sources)
+    }
+
+    /// Drop the cache so the next [`list`](Self::list) enumerates again
+    pub fn invalidate(&self) {
+        *self.cache.write() = None;
+    }
+
+    /// Get notified of sources added or removed by later refreshes
+    pub fn subscribe(&self) -> broadcast::Receiver<SourceChange> {
+        self.changes.subscribe()
+    }
+}
+
+/// Changes from `old` to `new`
+///
+/// A source whose details changed (e.g. a new resolution) is reported as
+/// removed and added again.
+pub fn diff_sources(old: &[SourceInfo], new: &[SourceInfo]) -> Vec<SourceChange> {
+    let removed = old
+        .iter()
+        .filter(|source| !new.contains(source))
+        .cloned()
+        .map(SourceChange::Removed);
+    let added = new
+        .iter()
+        .filter(|source| !old.contains(source))
+        .cloned()
+        .map(SourceChange::Added);
+    removed.chain(added).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SourceKind;
+    use std::sync::Arc;
+
+    /// Provider returning whatever the test put in
+    #[derive(Clone, Default)]
+    struct MockSources(Arc<parking_lot::Mutex<Vec<SourceInfo>>>);
+
+    #[async_trait]
+    impl SourceProvider for MockSources {
+        async fn list_sources(&self) -> Result<Vec<SourceInfo>> {
+            Ok(self.0.lock().clone())
+        }
+    }
+
+    fn monitor(id: &str, width: u32) -> SourceInfo {
+        SourceInfo::new(id, id, SourceKind::Monitor, (width, width * 9 / 16))
+    }
+
+    #[tokio::test]
+    async fn test_refresh_updates_cache() {
+        let mock = MockSources::default();
+        *mock.0.lock() = vec![monitor("DP-1", 2560), monitor("HDMI-A-1", 1920)];
+        let sources = SourceEnumerator::with_provider(mock.clone());
+        let mut changes = sources.subscribe();
+
+        assert!(sources.cached().is_none());
+        assert_eq!(sources.list().await.unwrap().len(), 2);
+        // The first listing is not reported as a change
+        assert!(changes.try_recv().is_err());
+
+        // Unplugged: list() keeps serving the cache until refreshed
+        *mock.0.lock() = vec![monitor("DP-1", 2560), monitor("DP-2", 3840)];
+        assert_eq!(sources.list().await.unwrap()[1].id, "HDMI-A-1");
+
+        let refreshed = sources.refresh().await.unwrap();
+        let ids: Vec<_> = refreshed.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ["DP-1", "DP-2"]);
+        assert_eq!(sources.list().await.unwrap(), refreshed);
+        // The stale entry is dropped and reported
+        let removed = SourceChange::Removed(monitor("HDMI-A-1", 1920));
+        assert_eq!(changes.try_recv().unwrap(), removed);
+        assert_eq!(changes.try_recv().unwrap(), SourceChange::Added(monitor("DP-2", 3840)));
+        assert!(changes.try_recv().is_err());
+
+        sources.invalidate();
+        assert!(sources.cached().is_none());
+    }
+
+    #[test]
+    fn test_diff_sources() {
+        let old = [monitor("DP-1", 2560)];
+        assert!(diff_sources(&old, &old).is_empty());
+
+        // Same ID at a new resolution
+        let new = [monitor("DP-1", 1920)];
+        assert_eq!(
+            diff_sources(&old, &new),
+            vec![
+                SourceChange::Removed(monitor("DP-1", 2560)),
+                SourceChange::Added(monitor("DP-1", 1920)),
+            ]
+        );
+    }
+}
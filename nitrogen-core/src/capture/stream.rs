@@ -4,23 +4,29 @@
 //! the screencast portal session.
 
 use pipewire as pw;
-use pw::spa::param::format::{MediaSubtype, MediaType};
+use pw::spa::buffer::DataType;
+use pw::spa::param::format::{FormatProperties, MediaSubtype, MediaType};
 use pw::spa::param::format_utils;
-use pw::spa::param::video::VideoFormat;
-use pw::spa::pod::Pod;
-use pw::spa::utils::{Direction, Fraction, Rectangle};
+use pw::spa::param::video::{VideoFlags, VideoFormat};
+use pw::spa::pod::{ChoiceValue, Object, Pod, Property, PropertyFlags, Value};
+use pw::spa::utils::{Choice, ChoiceEnum, ChoiceFlags, Direction, Fraction, Rectangle};
 use pw::stream::{Stream, StreamFlags, StreamState};
 
-use std::os::fd::OwnedFd;
-use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::fd::{OwnedFd, RawFd};
+use std::ptr::NonNull;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::mpsc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::broadcast;
 use tracing::{debug, error, info, trace, warn};
 
+use super::cursor::{cursor_meta_size, CursorOverlay, DEFAULT_CURSOR_SIZE, MAX_CURSOR_SIZE};
+use crate::affinity::{self, ThreadRole};
 use crate::error::{NitrogenError, Result};
-use crate::types::{Frame, FrameData, FrameFormat, HdrMetadata};
+use crate::types::{
+    ColorPrimaries, Frame, FrameData, FrameFormat, HdrMetadata, TransferFunction,
+};
 
 /// Shared state between PipeWire thread and main thread
 struct SharedState {
@@ -40,9 +46,33 @@ struct VideoInfo {
     height: u32,
     framerate_num: u32,
     framerate_denom: u32,
+    /// DRM modifier when the format was negotiated for DMA-BUF
+    modifier: Option<u64>,
+    /// HDR signaling from the negotiated colorimetry (None = SDR or unknown)
+    hdr: Option<HdrMetadata>,
 }
 
 impl VideoInfo {
+    /// Whether `next` renegotiates away from this format
+    ///
+    /// Only size and pixel format count; a new framerate or modifier doesn't
+    /// change how frames are scaled or converted.
+    fn is_changed_by(&self, next: &VideoInfo) -> bool {
+        (self.width, self.height, self.format) != (next.width, next.height, next.format)
+    }
+
+    /// Format as announced to [`CaptureStream::subscribe_format_changes`]
+    ///
+    /// The stride is only known per buffer, so it is left at 0.
+    fn frame_format(&self) -> FrameFormat {
+        FrameFormat {
+            width: self.width,
+            height: self.height,
+            fourcc: self.to_fourcc(),
+            stride: 0,
+        }
+    }
+
     /// Convert SPA VideoFormat to DRM fourcc
     fn to_fourcc(&self) -> u32 {
         match self.format {
@@ -58,34 +88,262 @@ impl VideoInfo {
             VideoFormat::BGR => 0x20524742,  // BGR
             VideoFormat::YUY2 => 0x56595559, // YUYV
             VideoFormat::NV12 => 0x3231564E, // NV12
+            VideoFormat::I420 => 0x32315559, // YU12
             _ => 0x34325258,                 // Default to XR24
         }
     }
+}
 
-    /// Check if the video format indicates HDR content (10-bit or higher)
+/// HDR metadata for a negotiated SPA transfer function and primaries
+///
+/// Only PQ (SMPTE ST 2084) and HLG (ARIB STD-B67) count as HDR; SDR and
+/// unset colorimetry give None. HDR with unset primaries is assumed to be
+/// BT.2020. SPA formats carry no mastering display or light level info, so
+/// the tonemapper falls back to its default peak.
+fn hdr_from_colorimetry(transfer: u32, primaries: u32) -> Option<HdrMetadata> {
+    let transfer = match transfer {
+        pw::spa::sys::SPA_VIDEO_TRANSFER_SMPTE2084 => TransferFunction::Pq,
+        pw::spa::sys::SPA_VIDEO_TRANSFER_ARIB_STD_B67 => TransferFunction::Hlg,
+        _ => return None,
+    };
+    let primaries = match primaries {
+        pw::spa::sys::SPA_VIDEO_COLOR_PRIMARIES_BT709 => ColorPrimaries::Bt709,
+        pw::spa::sys::SPA_VIDEO_COLOR_PRIMARIES_SMPTERP431
+        | pw::spa::sys::SPA_VIDEO_COLOR_PRIMARIES_SMPTEEG432 => ColorPrimaries::DciP3,
+        _ => ColorPrimaries::Bt2020,
+    };
+
+    Some(HdrMetadata {
+        transfer,
+        primaries,
+        ..Default::default()
+    })
+}
+
+/// `DRM_FORMAT_MOD_LINEAR`, the only layout offered for DMA-BUF capture
+const DRM_FORMAT_MOD_LINEAR: u64 = 0;
+
+/// Frames without cursor metadata before warning that none is coming
+const CURSOR_METADATA_GRACE_FRAMES: u64 = 120;
+
+/// Graph rate a latency quantum is expressed in
+pub const LATENCY_RATE: u32 = 48000;
+
+/// Largest quantum PipeWire can run a graph at
+pub const MAX_LATENCY_QUANTUM: u32 = 8192;
+
+/// Ask PipeWire to run a stream's node at `quantum` samples per cycle
+///
+/// Sets `node.latency` to `quantum/48000`. It is only a request: the server
+/// clamps it to its `clock.min-quantum`/`clock.max-quantum`, and the graph
+/// runs at the lowest latency any of its nodes asks for.
+pub fn request_node_latency(props: &mut pw::properties::Properties, quantum: Option<u32>) {
+    if let Some(quantum) = quantum {
+        props.insert("node.latency", format!("{}/{}", quantum, LATENCY_RATE));
+    }
+}
+
+/// Options for negotiating a capture stream
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StreamOptions {
+    /// Prefer formats with an alpha channel (BGRA over BGRx)
+    pub prefer_alpha: bool,
+    /// Offer linear DMA-BUF buffers before shared memory
     ///
-    /// Common HDR formats include P010 (10-bit NV12) and 10-bit RGB variants.
-    /// Currently most Wayland compositors send 8-bit SDR formats, but this
-    /// detection allows proper handling when HDR passthrough is supported.
-    fn detect_hdr_metadata(&self) -> Option<HdrMetadata> {
-        // Check for 10-bit formats that typically indicate HDR content
-        // Note: PipeWire VideoFormat enum values - checking for common 10-bit formats
-        match self.format {
-            // 10-bit YUV formats (common for HDR video)
-            // VideoFormat::P010 and similar 10-bit formats indicate HDR
-            // When matched, return HDR10 metadata as a reasonable default
-            // Current accepted formats are all 8-bit SDR
-            VideoFormat::NV12 => {
-                // NV12 is typically 8-bit SDR, but could be HDR in some contexts
-                // For now, treat as SDR unless we have additional metadata
-                None
-            }
-            _ => {
-                // All currently accepted formats (BGRx, BGRA, RGBx, RGBA, etc.)
-                // are 8-bit SDR formats
-                None
-            }
+    /// Frames then arrive as [`FrameData::DmaBuf`] when the compositor
+    /// agrees, so the encoder can import them without a CPU copy. The fd is
+    /// borrowed from the stream's buffer pool and stays valid while the
+    /// stream runs.
+    pub dmabuf: bool,
+    /// Draw the cursor from buffer metadata at this scale
+    ///
+    /// Only for sessions started with
+    /// [`CursorMode::Metadata`](crate::config::CursorMode::Metadata). The
+    /// cursor is drawn onto shared-memory frames; DMA-BUF frames pass
+    /// through without it. None leaves the cursor to the compositor.
+    pub cursor_scale: Option<f32>,
+    /// Tag every frame with this HDR metadata instead of what the stream
+    /// negotiates, for compositors that don't signal HDR
+    pub hdr_input: Option<HdrMetadata>,
+    /// PipeWire quantum to request (None = the server's default)
+    pub latency_quantum: Option<u32>,
+}
+
+/// Default time without frames before the capture watchdog fires
+pub const DEFAULT_FRAME_TIMEOUT: Duration = Duration::from_millis(2000);
+
+/// Default number of reconnect attempts before a stall becomes an error
+pub const DEFAULT_RECOVERY_ATTEMPTS: u32 = 3;
+
+/// Result of a [`FrameWatchdog::check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogStatus {
+    /// Frames are arriving (or the watchdog is not armed)
+    Healthy,
+    /// No frame within the timeout; the caller should try to reconnect
+    Stalled {
+        /// Time since the last frame (or last recovery attempt)
+        elapsed: Duration,
+        /// Recovery attempt number, starting at 1
+        attempt: u32,
+    },
+    /// Every recovery attempt failed to bring frames back
+    Exhausted {
+        /// Number of attempts made
+        attempts: u32,
+    },
+}
+
+/// Detects capture stalls (compositor glitches that stop frame delivery)
+///
+/// The watchdog is armed once the stream is active and fed on every frame.
+/// Each stall grants the caller one recovery attempt and a fresh timeout;
+/// once `max_attempts` stalls happen without a frame in between, it reports
+/// [`WatchdogStatus::Exhausted`].
+#[derive(Debug, Clone)]
+pub struct FrameWatchdog {
+    /// Time without frames before a stall is reported (zero disables)
+    timeout: Duration,
+    /// Recovery attempts before giving up
+    max_attempts: u32,
+    /// Last frame or recovery attempt (None = not armed)
+    last_activity: Option<Instant>,
+    /// Recovery attempts since the last frame
+    attempts: u32,
+}
+
+impl FrameWatchdog {
+    /// Create a watchdog (disarmed until [`arm`](Self::arm) is called)
+    pub fn new(timeout: Duration, max_attempts: u32) -> Self {
+        Self {
+            timeout,
+            max_attempts,
+            last_activity: None,
+            attempts: 0,
+        }
+    }
+
+    /// Create a watchdog from a millisecond timeout (0 = disabled)
+    pub fn from_millis(timeout_ms: u64) -> Self {
+        Self::new(Duration::from_millis(timeout_ms), DEFAULT_RECOVERY_ATTEMPTS)
+    }
+
+    /// Check if the watchdog can fire at all
+    pub fn is_enabled(&self) -> bool {
+        !self.timeout.is_zero()
+    }
+
+    /// Check if the watchdog is armed
+    pub fn is_armed(&self) -> bool {
+        self.last_activity.is_some()
+    }
+
+    /// Start (or restart) the timeout without resetting recovery attempts
+    pub fn arm(&mut self) {
+        self.last_activity = Some(Instant::now());
+    }
+
+    /// Stop watching (e.g. while paused)
+    pub fn disarm(&mut self) {
+        self.last_activity = None;
+    }
+
+    /// Record a received frame
+    ///
+    /// Returns true if this frame ended a stall.
+    pub fn frame_received(&mut self) -> bool {
+        let recovered = self.attempts > 0;
+        self.last_activity = Some(Instant::now());
+        self.attempts = 0;
+        recovered
+    }
+
+    /// Number of recovery attempts since the last frame
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Check for a stall
+    pub fn check(&mut self) -> WatchdogStatus {
+        if !self.is_enabled() {
+            return WatchdogStatus::Healthy;
+        }
+        let Some(last) = self.last_activity else {
+            return WatchdogStatus::Healthy;
+        };
+
+        let elapsed = last.elapsed();
+        if elapsed < self.timeout {
+            return WatchdogStatus::Healthy;
+        }
+
+        if self.attempts >= self.max_attempts {
+            return WatchdogStatus::Exhausted {
+                attempts: self.attempts,
+            };
+        }
+
+        // Give the recovery attempt a full timeout before firing again
+        self.attempts += 1;
+        self.last_activity = Some(Instant::now());
+        WatchdogStatus::Stalled {
+            elapsed,
+            attempt: self.attempts,
+        }
+    }
+}
+
+/// Drops frames evenly when the source runs faster than the target rate
+///
+/// A 144Hz source captured for 60fps otherwise falls behind until the frame
+/// channel lags and drops a burst, which judders. The decimator instead
+/// keeps, for every tick of the target cadence, the frame whose PTS is
+/// closest to it. Sources at or below the target rate pass unchanged.
+#[derive(Debug, Clone)]
+pub struct FrameDecimator {
+    /// Target frame interval in nanoseconds
+    interval: u64,
+    /// PTS of the previous frame seen, kept or not
+    last_pts: Option<u64>,
+    /// Ideal PTS of the next frame to keep
+    next_pts: u64,
+}
+
+impl FrameDecimator {
+    /// Decimate to `fps` frames per second
+    pub fn new(fps: u32) -> Self {
+        Self {
+            interval: 1_000_000_000 / u64::from(fps.max(1)),
+            last_pts: None,
+            next_pts: 0,
+        }
+    }
+
+    /// Forget the cadence, e.g. after switching sources
+    pub fn reset(&mut self) {
+        self.last_pts = None;
+    }
+
+    /// Check whether the frame with this PTS (in nanoseconds) should be kept
+    pub fn keep(&mut self, pts: u64) -> bool {
+        let previous = self.last_pts.replace(pts);
+        let Some(source_interval) = previous.and_then(|last| pts.checked_sub(last)) else {
+            // First frame, or the timestamps went backwards: start over
+            self.next_pts = pts + self.interval;
+            return true;
+        };
+
+        // Keep this frame unless the next one (one source interval later)
+        // will land closer to the tick
+        if pts + source_interval / 2 < self.next_pts {
+            return false;
+        }
+        self.next_pts += self.interval;
+        if self.next_pts <= pts {
+            // The source paused or runs slower than the target; realign
+            self.next_pts = pts + self.interval;
         }
+        true
     }
 }
 
@@ -95,12 +353,18 @@ impl VideoInfo {
 pub struct CaptureStream {
     /// Sender for frames to the processing pipeline
     frame_tx: broadcast::Sender<Arc<Frame>>,
+    /// Sender for mid-stream format renegotiations
+    format_tx: broadcast::Sender<FrameFormat>,
     /// Thread handle for the PipeWire main loop
     pw_thread: Option<std::thread::JoinHandle<()>>,
     /// Channel to signal shutdown
     shutdown_tx: Option<mpsc::Sender<()>>,
     /// Shared state
     shared: Arc<SharedState>,
+    /// PipeWire node ID the stream is connected to
+    node_id: u32,
+    /// Format negotiation options, reused on reconnect
+    options: StreamOptions,
 }
 
 impl CaptureStream {
@@ -110,7 +374,25 @@ impl CaptureStream {
     /// * `fd` - PipeWire file descriptor from the portal
     /// * `node_id` - PipeWire node ID to connect to
     pub fn new(fd: OwnedFd, node_id: u32) -> Result<Self> {
+        Self::with_alpha(fd, node_id, false)
+    }
+
+    /// Create a capture stream that asks the compositor for BGRA over BGRx
+    ///
+    /// Compositors that only offer padded formats still work; those frames
+    /// are treated as opaque downstream.
+    pub fn with_alpha(fd: OwnedFd, node_id: u32, prefer_alpha: bool) -> Result<Self> {
+        let options = StreamOptions {
+            prefer_alpha,
+            ..Default::default()
+        };
+        Self::with_options(fd, node_id, options)
+    }
+
+    /// Create a capture stream with explicit negotiation options
+    pub fn with_options(fd: OwnedFd, node_id: u32, options: StreamOptions) -> Result<Self> {
         let (frame_tx, _) = broadcast::channel(4); // Small buffer for low latency
+        let (format_tx, _) = broadcast::channel(4);
         let (shutdown_tx, shutdown_rx) = mpsc::channel();
 
         let shared = Arc::new(SharedState {
@@ -119,46 +401,68 @@ impl CaptureStream {
             running: AtomicBool::new(false),
         });
 
-        // Clone what we need for the thread
-        let frame_tx_clone = frame_tx.clone();
-        let shared_clone = shared.clone();
-        let raw_fd = fd.as_raw_fd();
-
-        // Spawn PipeWire thread
-        let pw_thread = std::thread::Builder::new()
-            .name("nitrogen-pipewire".to_string())
-            .spawn(move || {
-                // SAFETY: We transfer ownership of the file descriptor from the main thread
-                // to this spawned thread. The original fd is forgotten with std::mem::forget()
-                // after the spawn succeeds, ensuring exactly one owner. raw_fd is a valid
-                // file descriptor obtained from the portal's PipeWire connection.
-                let fd = unsafe { OwnedFd::from_raw_fd(raw_fd) };
-                if let Err(e) =
-                    run_pipewire_loop(fd, node_id, frame_tx_clone, shutdown_rx, shared_clone)
-                {
-                    error!("PipeWire loop error: {}", e);
-                }
-            })
-            .map_err(|e| {
-                NitrogenError::pipewire(format!("Failed to spawn PipeWire thread: {}", e))
-            })?;
-
-        // Forget the original fd since the thread now owns it
-        std::mem::forget(fd);
+        let pw_thread = spawn_pipewire_thread(
+            fd,
+            node_id,
+            options,
+            frame_tx.clone(),
+            format_tx.clone(),
+            shutdown_rx,
+            shared.clone(),
+        )?;
 
         Ok(Self {
             frame_tx,
+            format_tx,
             pw_thread: Some(pw_thread),
             shutdown_tx: Some(shutdown_tx),
             shared,
+            node_id,
+            options,
         })
     }
 
+    /// Tear down and re-create the PipeWire stream on the same node
+    ///
+    /// `fd` must be a fresh PipeWire remote for the same portal session
+    /// (see [`PortalCapture::reopen_pipewire_fd`](super::PortalCapture::reopen_pipewire_fd)).
+    /// Existing subscribers keep receiving frames and format changes once the
+    /// new stream starts, since both channels are reused.
+    pub fn reconnect(&mut self, fd: OwnedFd) -> Result<()> {
+        info!("Reconnecting PipeWire stream to node {}", self.node_id);
+        self.shutdown_loop();
+
+        let (shutdown_tx, shutdown_rx) = mpsc::channel();
+        let pw_thread = spawn_pipewire_thread(
+            fd,
+            self.node_id,
+            self.options,
+            self.frame_tx.clone(),
+            self.format_tx.clone(),
+            shutdown_rx,
+            self.shared.clone(),
+        )?;
+
+        self.pw_thread = Some(pw_thread);
+        self.shutdown_tx = Some(shutdown_tx);
+        Ok(())
+    }
+
     /// Subscribe to frames from this stream
     pub fn subscribe(&self) -> broadcast::Receiver<Arc<Frame>> {
         self.frame_tx.subscribe()
     }
 
+    /// Subscribe to format renegotiations after the initial one
+    ///
+    /// The producer renegotiates when the source changes size mid-capture,
+    /// e.g. a game toggling fullscreen. Each change is sent once, before the
+    /// first frame in the new format; the stride is left at 0 since it is
+    /// only known per buffer.
+    pub fn subscribe_format_changes(&self) -> broadcast::Receiver<FrameFormat> {
+        self.format_tx.subscribe()
+    }
+
     /// Check if the stream is still running
     pub fn is_running(&self) -> bool {
         self.shared.running.load(Ordering::SeqCst)
@@ -174,6 +478,14 @@ impl CaptureStream {
         self.shared.frame_count.load(Ordering::Relaxed)
     }
 
+    /// Whether frames are being delivered as DMA-BUFs
+    pub fn is_dmabuf(&self) -> bool {
+        self.shared
+            .format
+            .lock()
+            .is_some_and(|f| f.modifier.is_some())
+    }
+
     /// Get the current video format if known
     pub fn format(&self) -> Option<(u32, u32, u32)> {
         self.shared
@@ -185,7 +497,12 @@ impl CaptureStream {
     /// Stop the capture stream
     pub fn stop(&mut self) {
         info!("Stopping capture stream");
+        self.shutdown_loop();
+        info!("Capture stream stopped");
+    }
 
+    /// Signal the PipeWire thread to quit and wait for it
+    fn shutdown_loop(&mut self) {
         // Signal shutdown
         if let Some(tx) = self.shutdown_tx.take() {
             let _ = tx.send(());
@@ -198,10 +515,32 @@ impl CaptureStream {
         }
 
         self.shared.running.store(false, Ordering::SeqCst);
-        info!("Capture stream stopped");
     }
 }
 
+/// Spawn the dedicated PipeWire thread for a stream
+fn spawn_pipewire_thread(
+    fd: OwnedFd,
+    node_id: u32,
+    options: StreamOptions,
+    frame_tx: broadcast::Sender<Arc<Frame>>,
+    format_tx: broadcast::Sender<FrameFormat>,
+    shutdown_rx: mpsc::Receiver<()>,
+    shared: Arc<SharedState>,
+) -> Result<std::thread::JoinHandle<()>> {
+    std::thread::Builder::new()
+        .name("nitrogen-pipewire".to_string())
+        .spawn(move || {
+            affinity::pin_current_thread(ThreadRole::Capture);
+            if let Err(e) = run_pipewire_loop(
+                fd, node_id, options, frame_tx, format_tx, shutdown_rx, shared,
+            ) {
+                error!("PipeWire loop error: {}", e);
+            }
+        })
+        .map_err(|e| NitrogenError::pipewire(format!("Failed to spawn PipeWire thread: {}", e)))
+}
+
 impl Drop for CaptureStream {
     fn drop(&mut self) {
         self.stop();
@@ -212,7 +551,9 @@ impl Drop for CaptureStream {
 fn run_pipewire_loop(
     fd: OwnedFd,
     node_id: u32,
+    options: StreamOptions,
     frame_tx: broadcast::Sender<Arc<Frame>>,
+    format_tx: broadcast::Sender<FrameFormat>,
     shutdown_rx: mpsc::Receiver<()>,
     shared: Arc<SharedState>,
 ) -> Result<()> {
@@ -240,26 +581,32 @@ fn run_pipewire_loop(
     struct UserData {
         format: Option<VideoInfo>,
         frame_tx: broadcast::Sender<Arc<Frame>>,
+        format_tx: broadcast::Sender<FrameFormat>,
         shared: Arc<SharedState>,
+        cursor: Option<CursorOverlay>,
+        cursor_warned: bool,
+        hdr_input: Option<HdrMetadata>,
     }
 
     let user_data = UserData {
         format: None,
         frame_tx,
+        format_tx,
         shared: shared.clone(),
+        cursor: options.cursor_scale.map(CursorOverlay::new),
+        cursor_warned: false,
+        hdr_input: options.hdr_input,
     };
 
     // Create stream
-    let stream = Stream::new(
-        &core,
-        "nitrogen-capture",
-        pw::properties::properties! {
-            *pw::keys::MEDIA_TYPE => "Video",
-            *pw::keys::MEDIA_CATEGORY => "Capture",
-            *pw::keys::MEDIA_ROLE => "Screen",
-        },
-    )
-    .map_err(|e| NitrogenError::pipewire(format!("Failed to create stream: {}", e)))?;
+    let mut props = pw::properties::properties! {
+        *pw::keys::MEDIA_TYPE => "Video",
+        *pw::keys::MEDIA_CATEGORY => "Capture",
+        *pw::keys::MEDIA_ROLE => "Screen",
+    };
+    request_node_latency(&mut props, options.latency_quantum);
+    let stream = Stream::new(&core, "nitrogen-capture", props)
+        .map_err(|e| NitrogenError::pipewire(format!("Failed to create stream: {}", e)))?;
 
     // Set up stream listener
     let _listener = stream
@@ -296,7 +643,7 @@ fn run_pipewire_loop(
                 _ => {}
             }
         })
-        .param_changed(|_, user_data, id, param| {
+        .param_changed(|stream, user_data, id, param| {
             // Only handle format changes
             let Some(param) = param else { return };
             if id != pw::spa::param::ParamType::Format.as_raw() {
@@ -326,19 +673,84 @@ fn run_pipewire_loop(
                 height: video_info.size().height,
                 framerate_num: video_info.framerate().num,
                 framerate_denom: video_info.framerate().denom,
+                modifier: video_info
+                    .flags()
+                    .contains(VideoFlags::MODIFIER)
+                    .then_some(video_info.modifier()),
+                hdr: hdr_from_colorimetry(
+                    video_info.transfer_function(),
+                    video_info.color_primaries(),
+                ),
             };
 
             info!(
-                "Video format negotiated: {:?} {}x{} @ {}/{}fps",
-                info.format, info.width, info.height, info.framerate_num, info.framerate_denom
+                "Video format negotiated: {:?} {}x{} @ {}/{}fps{}",
+                info.format,
+                info.width,
+                info.height,
+                info.framerate_num,
+                info.framerate_denom,
+                if info.modifier.is_some() { " (DMA-BUF)" } else { "" }
             );
+            match (user_data.hdr_input, info.hdr) {
+                (Some(input), _) => info!(
+                    "Treating capture as {} ({}) from the HDR input override",
+                    input.transfer, input.primaries
+                ),
+                (None, Some(hdr)) => {
+                    info!("Stream signals HDR: {} ({})", hdr.transfer, hdr.primaries)
+                }
+                (None, None) => debug!(
+                    "Stream signals no HDR (transfer {}, primaries {})",
+                    video_info.transfer_function(),
+                    video_info.color_primaries()
+                ),
+            }
+
+            // Tell the producer which buffer types we accept for this format,
+            // and ask for cursor metadata when drawing the cursor ourselves
+            let mut values = Vec::new();
+            match buffers_param(info.modifier.is_some()) {
+                Ok(buffers) => values.push(buffers),
+                Err(e) => warn!("Failed to build buffer params: {}", e),
+            }
+            if user_data.cursor.is_some() {
+                match cursor_meta_param() {
+                    Ok(meta) => values.push(meta),
+                    Err(e) => warn!("Failed to build cursor meta params: {}", e),
+                }
+            }
+            let mut pods: Vec<&Pod> = values.iter().filter_map(|v| Pod::from_bytes(v)).collect();
+            if !pods.is_empty() {
+                if let Err(e) = stream.update_params(&mut pods) {
+                    warn!("Failed to update buffer params: {}", e);
+                }
+            }
+
+            // A renegotiation mid-capture (fullscreen toggle, window resize)
+            // changes the input of every scaler downstream
+            if let Some(previous) = user_data.format {
+                if previous.is_changed_by(&info) {
+                    info!(
+                        "Capture format changed: {:?} {}x{} -> {:?} {}x{}",
+                        previous.format,
+                        previous.width,
+                        previous.height,
+                        info.format,
+                        info.width,
+                        info.height
+                    );
+                    let _ = user_data.format_tx.send(info.frame_format());
+                }
+            }
 
             user_data.format = Some(info);
             *user_data.shared.format.lock() = Some(info);
         })
         .process(|stream, user_data| {
             // Dequeue buffer
-            let Some(mut buffer) = stream.dequeue_buffer() else {
+            // SAFETY: called from the stream's process callback on its loop
+            let Some(mut buffer) = (unsafe { RawBuffer::dequeue(stream) }) else {
                 trace!("No buffer available");
                 return;
             };
@@ -348,6 +760,20 @@ fn run_pipewire_loop(
                 return;
             };
 
+            if let Some(cursor) = user_data.cursor.as_mut() {
+                if let Some((meta, size)) = buffer.meta(pw::spa::sys::SPA_META_Cursor) {
+                    // SAFETY: the meta block belongs to the dequeued buffer
+                    unsafe { cursor.update_from_meta(meta, size) };
+                } else if !cursor.has_metadata()
+                    && !user_data.cursor_warned
+                    && user_data.shared.frame_count.load(Ordering::Relaxed)
+                        >= CURSOR_METADATA_GRACE_FRAMES
+                {
+                    warn!("Compositor sends no cursor metadata; the cursor will be missing from the capture");
+                    user_data.cursor_warned = true;
+                }
+            }
+
             let datas = buffer.datas_mut();
             if datas.is_empty() {
                 return;
@@ -358,6 +784,42 @@ fn run_pipewire_loop(
             // Get chunk info first (immutable borrow)
             let chunk_size = data.chunk().size() as usize;
             let chunk_stride = data.chunk().stride() as u32;
+            let chunk_offset = data.chunk().offset();
+
+            let frame_format = FrameFormat {
+                width: format.width,
+                height: format.height,
+                fourcc: format.to_fourcc(),
+                stride: chunk_stride,
+            };
+            let pts = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or(0);
+
+            // DMA-BUF: pass the fd through without touching the pixels.
+            // Compositors may report a zero chunk size for these.
+            if data.type_() == DataType::DmaBuf {
+                if let Some(modifier) = format.modifier {
+                    let frame = Frame {
+                        format: frame_format,
+                        data: FrameData::DmaBuf {
+                            fd: data.as_raw().fd as RawFd,
+                            offset: chunk_offset,
+                            modifier,
+                        },
+                        pts,
+                        hdr_metadata: user_data.hdr_input.or(format.hdr),
+                    };
+
+                    let count = user_data.shared.frame_count.fetch_add(1, Ordering::Relaxed);
+                    let _ = user_data.frame_tx.send(Arc::new(frame));
+                    if count % 60 == 0 {
+                        trace!("Captured {} DMA-BUF frames", count + 1);
+                    }
+                    return;
+                }
+            }
 
             if chunk_size == 0 {
                 return;
@@ -366,21 +828,22 @@ fn run_pipewire_loop(
             // Now get frame data (mutable borrow)
             if let Some(slice) = data.data() {
                 // Copy frame data (we need to copy since the buffer is returned)
-                let frame_data = slice[..chunk_size.min(slice.len())].to_vec();
+                let mut frame_data = slice[..chunk_size.min(slice.len())].to_vec();
+                if let Some(cursor) = user_data.cursor.as_ref() {
+                    cursor.composite(
+                        &mut frame_data,
+                        format.width,
+                        format.height,
+                        chunk_stride as usize,
+                        format.format,
+                    );
+                }
 
                 let frame = Frame {
-                    format: FrameFormat {
-                        width: format.width,
-                        height: format.height,
-                        fourcc: format.to_fourcc(),
-                        stride: chunk_stride,
-                    },
+                    format: frame_format,
                     data: FrameData::Memory(frame_data),
-                    pts: std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .map(|d| d.as_nanos() as u64)
-                        .unwrap_or(0),
-                    hdr_metadata: format.detect_hdr_metadata(),
+                    pts,
+                    hdr_metadata: user_data.hdr_input.or(format.hdr),
                 };
 
                 // Send frame
@@ -397,26 +860,80 @@ fn run_pipewire_loop(
         .register()
         .map_err(|e| NitrogenError::pipewire(format!("Failed to register listener: {}", e)))?;
 
-    // Build format parameters - accept common video formats
-    let obj = pw::spa::pod::object!(
+    // Build format parameters - accept common video formats. The first
+    // entry is the preferred default; BGRA keeps transparency for the camera.
+    let preferred_format = if options.prefer_alpha {
+        VideoFormat::BGRA
+    } else {
+        VideoFormat::BGRx
+    };
+
+    // With DMA-BUF enabled, a linear-modifier variant goes first so the
+    // compositor picks it if it can; the plain variant falls back to SHM
+    let mut formats = Vec::new();
+    if options.dmabuf {
+        formats.push(serialize_pod(format_object(
+            preferred_format,
+            Some(DRM_FORMAT_MOD_LINEAR),
+        ))?);
+    }
+    formats.push(serialize_pod(format_object(preferred_format, None))?);
+
+    let mut params = formats
+        .iter()
+        .map(|values| {
+            Pod::from_bytes(values)
+                .ok_or_else(|| NitrogenError::pipewire("Failed to create Pod from serialized format"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // Connect stream to the portal's node
+    stream
+        .connect(
+            Direction::Input,
+            Some(node_id),
+            StreamFlags::AUTOCONNECT | StreamFlags::MAP_BUFFERS,
+            &mut params,
+        )
+        .map_err(|e| NitrogenError::pipewire(format!("Failed to connect stream: {}", e)))?;
+
+    info!("PipeWire stream connected to node {}", node_id);
+
+    // Add a source to check for shutdown
+    let mainloop_weak = mainloop.downgrade();
+    let _source = loop_.add_idle(true, move || {
+        // Check for shutdown signal (non-blocking)
+        if shutdown_rx.try_recv().is_ok() {
+            info!("Shutdown signal received");
+            if let Some(mainloop) = mainloop_weak.upgrade() {
+                mainloop.quit();
+            }
+            return;
+        }
+    });
+
+    // Run the main loop
+    mainloop.run();
+
+    info!("PipeWire main loop ended");
+    shared.running.store(false, Ordering::SeqCst);
+
+    Ok(())
+}
+
+/// Build an EnumFormat object, optionally restricted to a DMA-BUF modifier
+fn format_object(preferred_format: VideoFormat, modifier: Option<u64>) -> Object {
+    let mut obj = pw::spa::pod::object!(
         pw::spa::utils::SpaTypes::ObjectParamFormat,
         pw::spa::param::ParamType::EnumFormat,
+        pw::spa::pod::property!(FormatProperties::MediaType, Id, MediaType::Video),
+        pw::spa::pod::property!(FormatProperties::MediaSubtype, Id, MediaSubtype::Raw),
         pw::spa::pod::property!(
-            pw::spa::param::format::FormatProperties::MediaType,
-            Id,
-            MediaType::Video
-        ),
-        pw::spa::pod::property!(
-            pw::spa::param::format::FormatProperties::MediaSubtype,
-            Id,
-            MediaSubtype::Raw
-        ),
-        pw::spa::pod::property!(
-            pw::spa::param::format::FormatProperties::VideoFormat,
+            FormatProperties::VideoFormat,
             Choice,
             Enum,
             Id,
-            VideoFormat::BGRx,
+            preferred_format,
             VideoFormat::BGRx,
             VideoFormat::BGRA,
             VideoFormat::RGBx,
@@ -429,7 +946,7 @@ fn run_pipewire_loop(
             VideoFormat::BGR
         ),
         pw::spa::pod::property!(
-            pw::spa::param::format::FormatProperties::VideoSize,
+            FormatProperties::VideoSize,
             Choice,
             Range,
             Rectangle,
@@ -447,7 +964,7 @@ fn run_pipewire_loop(
             }
         ),
         pw::spa::pod::property!(
-            pw::spa::param::format::FormatProperties::VideoFramerate,
+            FormatProperties::VideoFramerate,
             Choice,
             Range,
             Fraction,
@@ -457,56 +974,206 @@ fn run_pipewire_loop(
         ),
     );
 
-    let values: Vec<u8> = pw::spa::pod::serialize::PodSerializer::serialize(
-        std::io::Cursor::new(Vec::new()),
-        &pw::spa::pod::Value::Object(obj),
-    )
-    .map_err(|e| NitrogenError::pipewire(format!("Failed to serialize format: {:?}", e)))?
-    .0
-    .into_inner();
+    if let Some(modifier) = modifier {
+        // Mandatory so producers without DMA-BUF support skip this variant
+        obj.properties.push(Property {
+            key: FormatProperties::VideoModifier.as_raw(),
+            flags: PropertyFlags::MANDATORY | PropertyFlags::DONT_FIXATE,
+            value: Value::Choice(ChoiceValue::Long(Choice(
+                ChoiceFlags::empty(),
+                ChoiceEnum::Enum {
+                    default: modifier as i64,
+                    alternatives: vec![modifier as i64],
+                },
+            ))),
+        });
+    }
 
-    let pod = Pod::from_bytes(&values)
-        .ok_or_else(|| NitrogenError::pipewire("Failed to create Pod from serialized format"))?;
-    let mut params = [pod];
+    obj
+}
 
-    // Connect stream to the portal's node
-    stream
-        .connect(
-            Direction::Input,
-            Some(node_id),
-            StreamFlags::AUTOCONNECT | StreamFlags::MAP_BUFFERS,
-            &mut params,
-        )
-        .map_err(|e| NitrogenError::pipewire(format!("Failed to connect stream: {}", e)))?;
+/// Build the Buffers param for the negotiated format
+///
+/// DMA-BUF formats only accept DMA-BUF buffers; everything else uses the
+/// mappable memory types the copy path reads.
+fn buffers_param(dmabuf: bool) -> Result<Vec<u8>> {
+    let data_types = if dmabuf {
+        1 << DataType::DmaBuf.as_raw()
+    } else {
+        (1 << DataType::MemFd.as_raw()) | (1 << DataType::MemPtr.as_raw())
+    };
 
-    info!("PipeWire stream connected to node {}", node_id);
+    let obj = Object {
+        type_: pw::spa::utils::SpaTypes::ObjectParamBuffers.as_raw(),
+        id: pw::spa::param::ParamType::Buffers.as_raw(),
+        properties: vec![Property::new(
+            pw::spa::sys::SPA_PARAM_BUFFERS_dataType,
+            Value::Choice(ChoiceValue::Int(Choice(
+                ChoiceFlags::empty(),
+                ChoiceEnum::Flags {
+                    default: data_types as i32,
+                    flags: vec![data_types as i32],
+                },
+            ))),
+        )],
+    };
+    serialize_pod(obj)
+}
 
-    // Add a source to check for shutdown
-    let mainloop_weak = mainloop.downgrade();
-    let _source = loop_.add_idle(true, move || {
-        // Check for shutdown signal (non-blocking)
-        if shutdown_rx.try_recv().is_ok() {
-            info!("Shutdown signal received");
-            if let Some(mainloop) = mainloop_weak.upgrade() {
-                mainloop.quit();
-            }
-            return;
+/// Build the Meta param requesting cursor metadata with room for a bitmap
+fn cursor_meta_param() -> Result<Vec<u8>> {
+    let obj = Object {
+        type_: pw::spa::utils::SpaTypes::ObjectParamMeta.as_raw(),
+        id: pw::spa::param::ParamType::Meta.as_raw(),
+        properties: vec![
+            Property::new(
+                pw::spa::sys::SPA_PARAM_META_type,
+                Value::Id(pw::spa::utils::Id(pw::spa::sys::SPA_META_Cursor)),
+            ),
+            Property::new(
+                pw::spa::sys::SPA_PARAM_META_size,
+                Value::Choice(ChoiceValue::Int(Choice(
+                    ChoiceFlags::empty(),
+                    ChoiceEnum::Range {
+                        default: cursor_meta_size(DEFAULT_CURSOR_SIZE, DEFAULT_CURSOR_SIZE) as i32,
+                        min: cursor_meta_size(1, 1) as i32,
+                        max: cursor_meta_size(MAX_CURSOR_SIZE, MAX_CURSOR_SIZE) as i32,
+                    },
+                ))),
+            ),
+        ],
+    };
+    serialize_pod(obj)
+}
+
+/// Buffer dequeued from a stream, queued back when dropped
+///
+/// Used instead of `pw::buffer::Buffer` because buffer metadata (the cursor)
+/// is only reachable through the raw `spa_buffer`.
+struct RawBuffer<'s> {
+    stream: &'s pw::stream::StreamRef,
+    buffer: NonNull<pw::sys::pw_buffer>,
+}
+
+impl<'s> RawBuffer<'s> {
+    /// Dequeue the next buffer, if one is ready
+    ///
+    /// # Safety
+    /// Must be called from the stream's process callback.
+    unsafe fn dequeue(stream: &'s pw::stream::StreamRef) -> Option<Self> {
+        // SAFETY: the caller runs on the stream's loop thread
+        let buffer = NonNull::new(unsafe { stream.dequeue_raw_buffer() })?;
+        Some(Self { stream, buffer })
+    }
+
+    /// The underlying `spa_buffer`
+    fn spa_buffer(&self) -> Option<&pw::spa::sys::spa_buffer> {
+        // SAFETY: the pw_buffer stays valid until it is queued back in drop
+        unsafe { self.buffer.as_ref().buffer.as_ref() }
+    }
+
+    /// Data planes of the buffer
+    fn datas_mut(&mut self) -> &mut [pw::spa::buffer::Data] {
+        let Some(buffer) = self.spa_buffer() else {
+            return &mut [];
+        };
+        if buffer.datas.is_null() || buffer.n_datas == 0 {
+            return &mut [];
         }
-    });
+        // SAFETY: Data is a transparent wrapper around spa_data, and the
+        // array of n_datas entries lives as long as the dequeued buffer
+        unsafe {
+            std::slice::from_raw_parts_mut(
+                buffer.datas as *mut pw::spa::buffer::Data,
+                buffer.n_datas as usize,
+            )
+        }
+    }
 
-    // Run the main loop
-    mainloop.run();
+    /// Pointer and size of the metadata block of `type_`, if present
+    fn meta(&self, type_: u32) -> Option<(*const u8, usize)> {
+        let buffer = self.spa_buffer()?;
+        if buffer.metas.is_null() {
+            return None;
+        }
+        // SAFETY: metas holds n_metas entries for the buffer's lifetime
+        let metas = unsafe { std::slice::from_raw_parts(buffer.metas, buffer.n_metas as usize) };
+        metas
+            .iter()
+            .find(|m| m.type_ == type_ && !m.data.is_null())
+            .map(|m| (m.data as *const u8, m.size as usize))
+    }
+}
 
-    info!("PipeWire main loop ended");
-    shared.running.store(false, Ordering::SeqCst);
+impl Drop for RawBuffer<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the buffer was dequeued from this stream and not queued yet
+        unsafe { self.stream.queue_raw_buffer(self.buffer.as_ptr()) };
+    }
+}
 
-    Ok(())
+/// Serialize a pod object for stream params
+fn serialize_pod(obj: Object) -> Result<Vec<u8>> {
+    Ok(pw::spa::pod::serialize::PodSerializer::serialize(
+        std::io::Cursor::new(Vec::new()),
+        &Value::Object(obj),
+    )
+    .map_err(|e| NitrogenError::pipewire(format!("Failed to serialize format: {:?}", e)))?
+    .0
+    .into_inner())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_request_node_latency() {
+        let mut props = pw::properties::properties! {
+            *pw::keys::MEDIA_TYPE => "Video",
+        };
+        request_node_latency(&mut props, None);
+        assert_eq!(props.get("node.latency"), None);
+
+        request_node_latency(&mut props, Some(512));
+        assert_eq!(props.get("node.latency"), Some("512/48000"));
+        assert_eq!(props.get(*pw::keys::MEDIA_TYPE), Some("Video"));
+    }
+
+    #[test]
+    fn test_decimator_keeps_even_cadence() {
+        // 120 frames of a 120fps source, one second
+        let interval = 1_000_000_000 / 120;
+        let mut decimator = FrameDecimator::new(60);
+        let kept: Vec<u64> = (0..120u64)
+            .map(|i| i * interval)
+            .filter(|&pts| decimator.keep(pts))
+            .collect();
+
+        assert_eq!(kept.len(), 60);
+        assert!(kept.windows(2).all(|pair| pair[1] - pair[0] == 2 * interval));
+
+        // A source at or below the target passes through untouched
+        let mut decimator = FrameDecimator::new(60);
+        assert!((0..30u64).all(|i| decimator.keep(i * 1_000_000_000 / 30)));
+    }
+
+    #[test]
+    fn test_decimator_from_144hz() {
+        let mut decimator = FrameDecimator::new(60);
+        let kept: Vec<u64> = (0..144u64)
+            .filter(|i| decimator.keep(i * 1_000_000_000 / 144))
+            .collect();
+        assert_eq!(kept.len(), 60);
+
+        // 144/60 = 2.4 source frames per output frame: gaps of 2 or 3
+        assert!(kept.windows(2).all(|pair| (2..=3).contains(&(pair[1] - pair[0]))));
+
+        // Timestamps starting over (new source) restart the cadence
+        decimator.reset();
+        assert!(decimator.keep(0));
+    }
+
     #[test]
     fn test_video_info_fourcc() {
         let info = VideoInfo {
@@ -515,7 +1182,109 @@ mod tests {
             height: 1080,
             framerate_num: 60,
             framerate_denom: 1,
+            modifier: None,
+            hdr: None,
         };
         assert_eq!(info.to_fourcc(), 0x34325258);
     }
+
+    #[test]
+    fn test_hdr_from_colorimetry() {
+        use pw::spa::sys::*;
+
+        let pq =
+            hdr_from_colorimetry(SPA_VIDEO_TRANSFER_SMPTE2084, SPA_VIDEO_COLOR_PRIMARIES_BT2020)
+                .expect("PQ is HDR");
+        assert_eq!(pq.transfer, TransferFunction::Pq);
+        assert_eq!(pq.primaries, ColorPrimaries::Bt2020);
+
+        // HDR without primaries is taken to be BT.2020
+        let hlg = hdr_from_colorimetry(
+            SPA_VIDEO_TRANSFER_ARIB_STD_B67,
+            SPA_VIDEO_COLOR_PRIMARIES_UNKNOWN,
+        )
+        .expect("HLG is HDR");
+        assert_eq!(hlg.transfer, TransferFunction::Hlg);
+        assert_eq!(hlg.primaries, ColorPrimaries::Bt2020);
+
+        let srgb = hdr_from_colorimetry(SPA_VIDEO_TRANSFER_SRGB, SPA_VIDEO_COLOR_PRIMARIES_BT709);
+        assert!(srgb.is_none());
+        assert!(hdr_from_colorimetry(SPA_VIDEO_TRANSFER_UNKNOWN, 0).is_none());
+    }
+
+    #[test]
+    fn test_pq_tagged_frame_is_tonemapped_as_pq() {
+        use crate::encode::{TonemapConfig, TonemapMode, Tonemapper};
+        use pw::spa::sys::*;
+
+        let tagged =
+            hdr_from_colorimetry(SPA_VIDEO_TRANSFER_SMPTE2084, SPA_VIDEO_COLOR_PRIMARIES_BT2020);
+        let tonemapper = Tonemapper::new(TonemapConfig {
+            mode: TonemapMode::Auto,
+            ..Default::default()
+        });
+        assert!(tonemapper.should_tonemap(tagged.as_ref()));
+
+        let pixel = [200u8, 150, 100, 255];
+        let tonemapped = |metadata: Option<&HdrMetadata>| {
+            let mut frame = pixel;
+            tonemapper.tonemap(&mut frame, 1, 1, metadata);
+            frame
+        };
+        let result = tonemapped(tagged.as_ref());
+        assert_ne!(result, pixel);
+        assert_eq!(result, tonemapped(Some(&HdrMetadata::hdr10())));
+        assert_ne!(result, tonemapped(Some(&HdrMetadata::hlg())));
+
+        // Untagged frames stay as captured in auto mode
+        assert_eq!(tonemapped(None), pixel);
+    }
+
+    #[test]
+    fn test_video_info_change_detection() {
+        let fullscreen = VideoInfo {
+            format: VideoFormat::BGRx,
+            width: 1920,
+            height: 1080,
+            framerate_num: 60,
+            framerate_denom: 1,
+            modifier: None,
+            hdr: None,
+        };
+        let windowed = VideoInfo {
+            width: 1280,
+            height: 720,
+            ..fullscreen
+        };
+        let slower = VideoInfo {
+            framerate_num: 30,
+            modifier: Some(DRM_FORMAT_MOD_LINEAR),
+            ..fullscreen
+        };
+
+        assert!(fullscreen.is_changed_by(&windowed));
+        assert!(!fullscreen.is_changed_by(&slower));
+        assert_eq!(
+            windowed.frame_format(),
+            FrameFormat {
+                width: 1280,
+                height: 720,
+                fourcc: 0x34325258,
+                stride: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_watchdog_disarmed_and_disabled() {
+        let mut watchdog = FrameWatchdog::new(Duration::ZERO, 3);
+        watchdog.arm();
+        assert!(!watchdog.is_enabled());
+        assert_eq!(watchdog.check(), WatchdogStatus::Healthy);
+
+        let mut watchdog = FrameWatchdog::new(Duration::from_millis(1), 3);
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(!watchdog.is_armed());
+        assert_eq!(watchdog.check(), WatchdogStatus::Healthy);
+    }
 }
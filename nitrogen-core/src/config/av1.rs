@@ -1,11 +1,16 @@
 //! AV1-specific configuration and helpers
 //!
 //! Supports RTX 40 series (Ada) and RTX 50 series (Blackwell) features:
-//! - RTX 40+: AV1 encoding, tier selection, spatial AQ
-//! - RTX 50+: Ultra High Quality mode, temporal filtering, 4:2:2 chroma, extended lookahead
+//! - RTX 40+: AV1 encoding, tier selection
+//! - RTX 50+: Ultra High Quality mode, 4:2:2 chroma, B-frame reference mode
+//!
+//! Lookahead, AQ and multipass apply to every codec and live in
+//! [`EncoderQuality`](super::EncoderQuality).
 
 use serde::{Deserialize, Serialize};
 
+use super::quality::MultipassMode;
+
 /// NVENC AV1 tier selection
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -27,6 +32,18 @@ impl Av1Tier {
     }
 }
 
+impl std::str::FromStr for Av1Tier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "main" => Ok(Self::Main),
+            "high" => Ok(Self::High),
+            _ => Err(format!("Unknown AV1 tier: {}", s)),
+        }
+    }
+}
+
 /// NVENC tuning mode for quality optimization
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -62,6 +79,21 @@ impl Av1Tune {
     }
 }
 
+impl std::str::FromStr for Av1Tune {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hq" => Ok(Self::Hq),
+            "uhq" => Ok(Self::Uhq),
+            "ll" => Ok(Self::Ll),
+            "ull" => Ok(Self::Ull),
+            "lossless" => Ok(Self::Lossless),
+            _ => Err(format!("Unknown AV1 tune: {}", s)),
+        }
+    }
+}
+
 /// Chroma subsampling format
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -85,26 +117,15 @@ impl ChromaFormat {
     }
 }
 
-/// Multipass encoding mode
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
-#[serde(rename_all = "lowercase")]
-pub enum MultipassMode {
-    /// Disabled (fastest)
-    #[default]
-    Disabled,
-    /// Quarter resolution first pass
-    Quarter,
-    /// Full resolution first pass (best quality)
-    Full,
-}
+impl std::str::FromStr for ChromaFormat {
+    type Err = String;
 
-impl MultipassMode {
-    /// Returns ffmpeg multipass option string
-    pub fn ffmpeg_value(&self) -> Option<&'static str> {
-        match self {
-            Self::Disabled => None,
-            Self::Quarter => Some("qres"),
-            Self::Full => Some("fullres"),
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "420" | "yuv420" => Ok(Self::Yuv420),
+            "422" | "yuv422" => Ok(Self::Yuv422),
+            "444" | "yuv444" => Ok(Self::Yuv444),
+            _ => Err(format!("Unknown chroma format: {}", s)),
         }
     }
 }
@@ -119,19 +140,12 @@ pub struct Av1Config {
     pub tier: Av1Tier,
     /// GOP length override (frames)
     pub gop_length: Option<u32>,
-    /// Enable lookahead (if supported)
-    pub lookahead: bool,
-    /// Lookahead depth (frames, RTX 50: up to 250, default 20)
-    pub lookahead_depth: u32,
-    /// Enable spatial AQ (adaptive quantization)
-    pub spatial_aq: bool,
-    /// Enable temporal AQ (RTX 50 series, ~4-5% efficiency gain)
-    pub temporal_aq: bool,
     /// Tuning mode (hq, uhq for RTX 50, ll, ull)
     pub tune: Av1Tune,
     /// Chroma subsampling format (420, 422 for RTX 50, 444)
     pub chroma: ChromaFormat,
-    /// Multipass encoding mode
+    /// Multipass encoding mode, used when the shared
+    /// [`EncoderQuality::multipass`](super::EncoderQuality::multipass) is disabled
     pub multipass: MultipassMode,
     /// B-frame reference mode for better compression (RTX 50)
     pub b_ref_mode: bool,
@@ -143,10 +157,6 @@ impl Default for Av1Config {
             ten_bit: false,
             tier: Av1Tier::Main,
             gop_length: None,
-            lookahead: false,
-            lookahead_depth: 20,
-            spatial_aq: true,
-            temporal_aq: false,
             tune: Av1Tune::Hq,
             chroma: ChromaFormat::Yuv420,
             multipass: MultipassMode::Disabled,
@@ -167,10 +177,6 @@ impl Av1Config {
             ten_bit: true,
             tier: Av1Tier::High,
             gop_length: None,
-            lookahead: true,
-            lookahead_depth: 250, // Extended lookahead on Blackwell
-            spatial_aq: true,
-            temporal_aq: true, // New on Blackwell
             tune: Av1Tune::Uhq, // Ultra High Quality mode
             chroma: ChromaFormat::Yuv420, // 422 available but less compatible
             multipass: MultipassMode::Full,
@@ -184,10 +190,6 @@ impl Av1Config {
             ten_bit: false,
             tier: Av1Tier::Main,
             gop_length: Some(60), // 1 second at 60fps
-            lookahead: false,
-            lookahead_depth: 0,
-            spatial_aq: true,
-            temporal_aq: false,
             tune: Av1Tune::Ll,
             chroma: ChromaFormat::Yuv420,
             multipass: MultipassMode::Disabled,
@@ -197,9 +199,6 @@ impl Av1Config {
 
     /// Check if this config uses RTX 50 series features
     pub fn uses_blackwell_features(&self) -> bool {
-        self.tune.requires_blackwell()
-            || self.chroma.requires_blackwell()
-            || self.temporal_aq
-            || self.lookahead_depth > 32
+        self.tune.requires_blackwell() || self.chroma.requires_blackwell()
     }
 }
@@ -6,7 +6,27 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use tracing::{debug, info, warn};
 
+use super::{
+    parse_bitrate, parse_duration, AudioCodec, AudioSource, Av1Config, CameraFormat,
+    CaptureConfig, CursorMode, EncoderQuality, MicChannel, OutputAudio,
+};
+use crate::affinity::{CpuAffinity, CpuSet};
+use crate::capture::limiter::{DEFAULT_LIMITER_RELEASE_MS, DEFAULT_LIMITER_THRESHOLD_DB};
+use crate::capture::{
+    grid_layout, DuckingMode, LayoutLayer, LimiterConfig, LimiterMode, DEFAULT_CURSOR_SCALE,
+    DEFAULT_PORTAL_ATTEMPTS,
+};
+use crate::encode::{RateControl, Timecode, TonemapAlgorithm, TonemapMode};
 use crate::error::{NitrogenError, Result};
+use crate::output::{
+    default_ice_servers, default_simulcast_layers, ContainerFormat, DashConfig, HlsConfig,
+    IceServer, RecordingMetadata, SegmentConfig, SimulcastLayer, DEFAULT_CAMERA_TRANSITION_MS,
+    WEBRTC_VIDEO_CODECS,
+};
+use crate::overlay::OverlayPosition;
+use crate::performance::{MetricsMode, DEFAULT_EMA_ALPHA};
+use crate::types::{CaptureSource, HdrMetadata};
+use crate::watermark::WatermarkConfig;
 
 /// Configuration file structure
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -31,6 +51,10 @@ pub struct ConfigFile {
     #[serde(default)]
     pub audio: AudioSettings,
 
+    /// Video capture settings
+    #[serde(default)]
+    pub capture: CaptureSettings,
+
     /// Environment detection settings
     #[serde(default)]
     pub detection: DetectionSettings,
@@ -47,6 +71,10 @@ pub struct ConfigFile {
     #[serde(default)]
     pub overlay: OverlaySettings,
 
+    /// Logo watermark settings
+    #[serde(default)]
+    pub watermark: WatermarkSettings,
+
     /// Hotkey bindings
     #[serde(default)]
     pub hotkeys: HotkeySettings,
@@ -54,6 +82,46 @@ pub struct ConfigFile {
     /// WebRTC streaming settings
     #[serde(default)]
     pub webrtc: WebRTCSettings,
+
+    /// HLS output settings
+    #[serde(default)]
+    pub hls: HlsSettings,
+
+    /// DASH output settings
+    #[serde(default)]
+    pub dash: DashSettings,
+
+    /// Multi-source layout settings
+    #[serde(default)]
+    pub layout: LayoutSettings,
+
+    /// File recording settings
+    #[serde(default)]
+    pub recording: RecordingSettings,
+}
+
+/// File recording settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RecordingSettings {
+    /// Container format (mp4, mkv, fmp4, webm); unset = from the file extension
+    #[serde(default)]
+    pub container: Option<String>,
+
+    /// Seconds of output kept in memory for `nitrogen save-replay` (0 = off)
+    #[serde(default)]
+    pub replay_buffer: u32,
+
+    /// Start a new numbered file after this long (e.g. "10m"); unset = never
+    #[serde(default)]
+    pub segment_duration: Option<String>,
+
+    /// Start a new numbered file after this many MB (0 = no size limit)
+    #[serde(default)]
+    pub segment_size: u64,
+
+    /// Container tags (title, creation time, source, codec)
+    #[serde(default)]
+    pub metadata: RecordingMetadata,
 }
 
 /// Default capture settings
@@ -67,13 +135,21 @@ pub struct DefaultSettings {
     #[serde(default = "default_codec")]
     pub codec: String,
 
-    /// Default bitrate in kbps (0 = auto)
-    #[serde(default)]
+    /// Default bitrate in kbps (0 = auto); also accepts "6M" or "6000k"
+    #[serde(default, deserialize_with = "deserialize_bitrate")]
     pub bitrate: u32,
 
     /// Enable low latency mode by default
     #[serde(default = "default_true")]
     pub low_latency: bool,
+
+    /// Aspect ratio mismatch handling (stretch, letterbox, crop)
+    #[serde(default = "default_fit")]
+    pub fit: String,
+
+    /// Letterbox bar color ("#rrggbb")
+    #[serde(default = "default_fit_color")]
+    pub fit_color: String,
 }
 
 /// Encoder-specific settings
@@ -86,6 +162,71 @@ pub struct EncoderSettings {
     /// GPU index to use (0 = first GPU)
     #[serde(default)]
     pub gpu: u32,
+
+    /// Enable lookahead for better quality (disabled in low-latency mode)
+    #[serde(default)]
+    pub lookahead: bool,
+
+    /// Lookahead depth in frames (RTX 50: up to 250, default 20)
+    #[serde(default = "default_lookahead_depth")]
+    pub lookahead_depth: u32,
+
+    /// Enable spatial adaptive quantization
+    #[serde(default)]
+    pub spatial_aq: bool,
+
+    /// Enable temporal AQ (~4-5% efficiency gain)
+    #[serde(default)]
+    pub temporal_aq: bool,
+
+    /// Multipass encoding: disabled (default), quarter, full
+    /// (disabled in low-latency mode)
+    #[serde(default = "default_multipass")]
+    pub multipass: String,
+
+    /// Frames NVENC may have in flight (0 = FFmpeg default, 1 in low-latency mode)
+    #[serde(default)]
+    pub async_depth: u32,
+
+    /// Encode DMA-BUF frames straight from GPU memory when supported
+    #[serde(default = "default_true")]
+    pub zero_copy: bool,
+
+    /// Concurrent encoder sessions allowed (0 = detect from the GPU and driver)
+    #[serde(default)]
+    pub max_sessions: u32,
+
+    /// Content mode (motion, static)
+    #[serde(default = "default_content_mode")]
+    pub content_mode: String,
+
+    /// Temporal SVC layers for H.264 (0 = disabled, up to 4)
+    #[serde(default)]
+    pub temporal_layers: u8,
+
+    /// Gradual intra-refresh instead of periodic IDR frames
+    #[serde(default)]
+    pub intra_refresh: bool,
+
+    /// Frames per intra-refresh cycle (0 = one second)
+    #[serde(default)]
+    pub intra_refresh_period: u32,
+
+    /// NVENC tuning (auto, hq, ll, ull); auto follows low_latency
+    #[serde(default = "default_tune")]
+    pub tune: String,
+
+    /// Rate control (auto, cbr, vbr, cqp:<qp>, cq:<level>); auto follows the tune
+    #[serde(default = "default_rate_control")]
+    pub rate_control: String,
+
+    /// Peak bitrate in kbps (0 = 1.5x the bitrate); also accepts "6M"
+    #[serde(default, deserialize_with = "deserialize_bitrate")]
+    pub max_bitrate: u32,
+
+    /// VBV buffer size in kbits (0 = one second at the bitrate); also accepts "3M"
+    #[serde(default, deserialize_with = "deserialize_bitrate")]
+    pub vbv_buffer_size: u32,
 }
 
 /// Virtual camera settings
@@ -94,12 +235,32 @@ pub struct CameraSettings {
     /// Virtual camera name
     #[serde(default = "default_camera_name")]
     pub name: String,
+
+    /// Keep the alpha channel on the camera (BGRA with transparency)
+    #[serde(default)]
+    pub alpha: bool,
+
+    /// Pixel format offered to applications (bgra, nv12)
+    #[serde(default = "default_camera_format")]
+    pub format: String,
+
+    /// Send a full frame right away when an application opens the camera
+    #[serde(default = "default_true")]
+    pub refresh_on_connect: bool,
+
+    /// Crossfade in ms to and from the placeholder on pause (0 = hard cut)
+    #[serde(default = "default_camera_transition_ms")]
+    pub transition_ms: u32,
+
+    /// PNG shown while the camera is paused (empty = black)
+    #[serde(default)]
+    pub placeholder: String,
 }
 
 /// Audio capture and encoding settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioSettings {
-    /// Audio source (none, desktop, mic, both)
+    /// Audio source (none, desktop, mic, both, app:<name>)
     #[serde(default = "default_audio_source")]
     pub source: String,
 
@@ -107,11 +268,152 @@ pub struct AudioSettings {
     #[serde(default = "default_audio_codec")]
     pub codec: String,
 
-    /// Audio bitrate in kbps (0 = auto based on codec)
+    /// Audio bitrate in kbps (0 = auto based on codec); also accepts "160k"
+    #[serde(default, deserialize_with = "deserialize_bitrate")]
+    pub bitrate: u32,
+
+    /// Clipping stage for mixed audio (none, tanh, truepeak)
+    #[serde(default = "default_limiter")]
+    pub limiter: String,
+
+    /// True-peak limiter ceiling in dBFS
+    #[serde(default = "default_limiter_threshold_db")]
+    pub limiter_threshold_db: f32,
+
+    /// True-peak limiter release time in milliseconds
+    #[serde(default = "default_limiter_release_ms")]
+    pub limiter_release_ms: u32,
+
+    /// A/V sync offset in ms (positive delays audio, negative delays video)
+    #[serde(default)]
+    pub sync_offset_ms: i32,
+
+    /// PipeWire quantum to request for audio capture, in samples at 48 kHz
     #[serde(default)]
+    pub latency_quantum: Option<u32>,
+
+    /// Microphone settings
+    #[serde(default)]
+    pub mic: MicSettings,
+
+    /// Desktop ducking while the mic is active
+    #[serde(default)]
+    pub ducking: DuckingSettings,
+
+    /// Audio encoding for the recording (unset = codec/bitrate above)
+    #[serde(default)]
+    pub record: Option<OutputAudioSettings>,
+
+    /// Audio encoding for the RTMP/SRT stream (unset = codec/bitrate above)
+    #[serde(default)]
+    pub stream: Option<OutputAudioSettings>,
+}
+
+/// Audio encoding override for a single output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputAudioSettings {
+    /// Audio codec (aac, opus)
+    #[serde(default = "default_audio_codec")]
+    pub codec: String,
+
+    /// Audio bitrate in kbps (0 = auto based on codec); also accepts "160k"
+    #[serde(default, deserialize_with = "deserialize_bitrate")]
     pub bitrate: u32,
 }
 
+/// Microphone input settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MicSettings {
+    /// Channel handling (left, right, mono, stereo)
+    #[serde(default = "default_mic_channel")]
+    pub channel: String,
+}
+
+impl Default for MicSettings {
+    fn default() -> Self {
+        Self {
+            channel: default_mic_channel(),
+        }
+    }
+}
+
+/// Desktop audio ducking settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuckingSettings {
+    /// Lower the desktop while the mic is active
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// What counts as mic activity: rms (any sound), vad (speech)
+    #[serde(default = "default_ducking_mode")]
+    pub mode: String,
+}
+
+impl Default for DuckingSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            mode: default_ducking_mode(),
+        }
+    }
+}
+
+fn default_ducking_mode() -> String {
+    "rms".to_string()
+}
+
+/// Video capture settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureSettings {
+    /// Reconnect the PipeWire stream if no frame arrives for this long (0 = disabled)
+    #[serde(default = "default_frame_timeout_ms")]
+    pub frame_timeout_ms: u64,
+
+    /// Cursor mode (hidden, embedded, metadata)
+    #[serde(default = "default_cursor")]
+    pub cursor: String,
+
+    /// Size of the cursor drawn in metadata mode (1.0 = compositor size)
+    #[serde(default = "default_cursor_scale")]
+    pub cursor_scale: f32,
+
+    /// PipeWire quantum to request for the video stream, in samples at 48 kHz
+    #[serde(default)]
+    pub latency_quantum: Option<u32>,
+
+    /// Attempts at starting the portal session when it fails transiently
+    #[serde(default = "default_portal_attempts")]
+    pub portal_attempts: u32,
+}
+
+impl Default for CaptureSettings {
+    fn default() -> Self {
+        Self {
+            frame_timeout_ms: default_frame_timeout_ms(),
+            cursor: default_cursor(),
+            cursor_scale: default_cursor_scale(),
+            latency_quantum: None,
+            portal_attempts: default_portal_attempts(),
+        }
+    }
+}
+
+fn default_frame_timeout_ms() -> u64 {
+    2000
+}
+
+fn default_cursor() -> String {
+    "embedded".to_string()
+}
+
+fn default_cursor_scale() -> f32 {
+    DEFAULT_CURSOR_SCALE
+}
+
+fn default_portal_attempts() -> u32 {
+    DEFAULT_PORTAL_ATTEMPTS
+}
+
 /// Environment detection settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DetectionSettings {
@@ -145,7 +447,7 @@ pub struct HdrSettings {
     #[serde(default = "default_hdr_tonemap")]
     pub tonemap: String,
 
-    /// Tonemapping algorithm: reinhard, aces, hable
+    /// Tonemapping algorithm: reinhard, aces, hable, bt2390
     #[serde(default = "default_hdr_algorithm")]
     pub algorithm: String,
 
@@ -153,9 +455,17 @@ pub struct HdrSettings {
     #[serde(default = "default_peak_luminance")]
     pub peak_luminance: u32,
 
+    /// Adapt tonemapping to each frame's luminance instead of the static peak
+    #[serde(default)]
+    pub dynamic: bool,
+
     /// Preserve HDR for file recording (only tonemap virtual camera)
     #[serde(default)]
     pub preserve_hdr_recording: bool,
+
+    /// Override the captured signal: pq[:NITS], hlg[:NITS], sdr (empty = detect)
+    #[serde(default)]
+    pub input: String,
 }
 
 impl Default for HdrSettings {
@@ -164,7 +474,9 @@ impl Default for HdrSettings {
             tonemap: default_hdr_tonemap(),
             algorithm: default_hdr_algorithm(),
             peak_luminance: default_peak_luminance(),
+            dynamic: false,
             preserve_hdr_recording: false,
+            input: String::new(),
         }
     }
 }
@@ -196,9 +508,44 @@ pub struct PerformanceSettings {
     #[serde(default = "default_sample_interval")]
     pub sample_interval_ms: u32,
 
-    /// Number of samples to keep for rolling averages (default: 120)
+    /// Number of samples to keep for rolling averages (default: 120, 0 = EMA)
     #[serde(default = "default_metrics_samples")]
     pub metrics_sample_count: usize,
+
+    /// EMA smoothing factor when `metrics_sample_count` is 0 (default: 0.2)
+    #[serde(default = "default_ema_alpha")]
+    pub ema_alpha: f64,
+
+    /// Trace-log a sampled hash of each frame at capture and output
+    #[serde(default)]
+    pub frame_hashing: bool,
+
+    /// Log performance stats at info level every N seconds (0 = off)
+    #[serde(default)]
+    pub stats_interval_secs: u32,
+
+    /// Cores to pin each thread to
+    #[serde(default)]
+    pub cpu_affinity: CpuAffinitySettings,
+}
+
+/// `[performance.cpu_affinity]`: core lists such as "2" or "0-1,4"
+///
+/// Empty leaves that thread to the scheduler.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CpuAffinitySettings {
+    /// PipeWire video capture thread
+    #[serde(default)]
+    pub capture: String,
+    /// Thread that converts and encodes frames
+    #[serde(default)]
+    pub encode: String,
+    /// PipeWire audio capture thread
+    #[serde(default)]
+    pub audio: String,
+    /// Global hotkey listener
+    #[serde(default)]
+    pub hotkeys: String,
 }
 
 impl Default for PerformanceSettings {
@@ -208,6 +555,10 @@ impl Default for PerformanceSettings {
             gpu_monitoring: true,
             sample_interval_ms: default_sample_interval(),
             metrics_sample_count: default_metrics_samples(),
+            ema_alpha: default_ema_alpha(),
+            frame_hashing: false,
+            stats_interval_secs: 0,
+            cpu_affinity: CpuAffinitySettings::default(),
         }
     }
 }
@@ -216,6 +567,10 @@ fn default_metrics_samples() -> usize {
     120
 }
 
+fn default_ema_alpha() -> f64 {
+    DEFAULT_EMA_ALPHA
+}
+
 fn default_sample_interval() -> u32 {
     100
 }
@@ -254,6 +609,26 @@ pub struct OverlaySettings {
     /// Overlay font scale (1.0 = normal)
     #[serde(default = "default_font_scale")]
     pub font_scale: f32,
+
+    /// Custom text replacing the stats line, e.g. "{title}\n{time} | {fps}fps"
+    #[serde(default)]
+    pub template: String,
+
+    /// Value of the `{title}` placeholder
+    #[serde(default)]
+    pub title: String,
+
+    /// Burn a SMPTE timecode into every output (independent of `enabled`)
+    #[serde(default)]
+    pub timecode: bool,
+
+    /// Timecode of the first frame, HH:MM:SS:FF
+    #[serde(default = "default_timecode_start")]
+    pub timecode_start: String,
+
+    /// Also write the timecode as HEVC SEI / AV1 metadata OBUs
+    #[serde(default)]
+    pub timecode_metadata: bool,
 }
 
 impl Default for OverlaySettings {
@@ -267,6 +642,11 @@ impl Default for OverlaySettings {
             show_bitrate: true,
             show_drops: true,
             font_scale: default_font_scale(),
+            template: String::new(),
+            title: String::new(),
+            timecode: false,
+            timecode_start: default_timecode_start(),
+            timecode_metadata: false,
         }
     }
 }
@@ -275,6 +655,62 @@ fn default_overlay_position() -> String {
     "top-left".to_string()
 }
 
+/// Logo watermark settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatermarkSettings {
+    /// PNG file to blend into every frame (empty = no watermark)
+    #[serde(default)]
+    pub path: String,
+
+    /// Corner: top-left, top-right, bottom-left, bottom-right
+    #[serde(default = "default_watermark_position")]
+    pub position: String,
+
+    /// Distance from the frame edges in pixels
+    #[serde(default = "default_watermark_margin")]
+    pub margin: u32,
+
+    /// Opacity on top of the PNG's own alpha (0.0 - 1.0)
+    #[serde(default = "default_watermark_opacity")]
+    pub opacity: f32,
+
+    /// Size relative to the PNG (1.0 = as is)
+    #[serde(default = "default_watermark_scale")]
+    pub scale: f32,
+}
+
+impl Default for WatermarkSettings {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            position: default_watermark_position(),
+            margin: default_watermark_margin(),
+            opacity: default_watermark_opacity(),
+            scale: default_watermark_scale(),
+        }
+    }
+}
+
+fn default_watermark_position() -> String {
+    "bottom-right".to_string()
+}
+
+fn default_watermark_margin() -> u32 {
+    16
+}
+
+fn default_watermark_opacity() -> f32 {
+    1.0
+}
+
+fn default_watermark_scale() -> f32 {
+    1.0
+}
+
+fn default_timecode_start() -> String {
+    Timecode::default().to_string()
+}
+
 fn default_font_scale() -> f32 {
     1.0
 }
@@ -297,6 +733,22 @@ pub struct HotkeySettings {
     /// Toggle latency overlay
     #[serde(default = "default_hotkey_overlay")]
     pub overlay_toggle: String,
+
+    /// Save a screenshot of the capture
+    #[serde(default = "default_hotkey_screenshot")]
+    pub screenshot: String,
+
+    /// Where screenshots are saved (path template, extension picks PNG/JPEG)
+    #[serde(default = "default_screenshot_path")]
+    pub screenshot_path: String,
+
+    /// Save the replay buffer to a file
+    #[serde(default = "default_hotkey_save_replay")]
+    pub save_replay: String,
+
+    /// Where replays are saved (path template)
+    #[serde(default = "default_replay_path")]
+    pub replay_path: String,
 }
 
 impl Default for HotkeySettings {
@@ -306,6 +758,10 @@ impl Default for HotkeySettings {
             pause: default_hotkey_pause(),
             record: default_hotkey_record(),
             overlay_toggle: default_hotkey_overlay(),
+            screenshot: default_hotkey_screenshot(),
+            screenshot_path: default_screenshot_path(),
+            save_replay: default_hotkey_save_replay(),
+            replay_path: default_replay_path(),
         }
     }
 }
@@ -326,6 +782,22 @@ fn default_hotkey_overlay() -> String {
     "ctrl+shift+f12".to_string()
 }
 
+fn default_hotkey_screenshot() -> String {
+    "ctrl+shift+print".to_string()
+}
+
+fn default_screenshot_path() -> String {
+    crate::snapshot::DEFAULT_SNAPSHOT_PATH.to_string()
+}
+
+fn default_hotkey_save_replay() -> String {
+    "ctrl+shift+f8".to_string()
+}
+
+fn default_replay_path() -> String {
+    crate::output::DEFAULT_REPLAY_PATH.to_string()
+}
+
 /// WebRTC streaming settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WebRTCSettings {
@@ -337,17 +809,26 @@ pub struct WebRTCSettings {
     #[serde(default)]
     pub signaling_url: String,
 
-    /// ICE/STUN servers for NAT traversal
+    /// STUN/TURN servers for NAT traversal (URL strings or tables with
+    /// `urls`, `username` and `credential`)
     #[serde(default = "default_ice_servers")]
-    pub ice_servers: Vec<String>,
+    pub ice_servers: Vec<IceServer>,
 
-    /// Preferred video codec for WebRTC: h264, vp8, vp9, av1
+    /// Video codec for WebRTC: h264, vp8, vp9, av1 (must match the encoder codec)
     #[serde(default = "default_webrtc_codec")]
     pub video_codec: String,
 
     /// WebRTC listen port (0 = random)
     #[serde(default)]
     pub port: u16,
+
+    /// Publish several resolution/bitrate layers instead of one
+    #[serde(default)]
+    pub simulcast: bool,
+
+    /// Simulcast layers, highest first
+    #[serde(default = "default_simulcast_layers")]
+    pub layers: Vec<SimulcastLayer>,
 }
 
 impl Default for WebRTCSettings {
@@ -358,14 +839,148 @@ impl Default for WebRTCSettings {
             ice_servers: default_ice_servers(),
             video_codec: default_webrtc_codec(),
             port: 0,
+            simulcast: false,
+            layers: default_simulcast_layers(),
         }
     }
 }
 
-fn default_ice_servers() -> Vec<String> {
-    vec!["stun:stun.l.google.com:19302".to_string()]
+/// HLS playlist output settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HlsSettings {
+    /// Enable HLS output
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Output directory for the playlist and segments (empty = temp dir)
+    #[serde(default)]
+    pub dir: String,
+
+    /// Target segment duration in seconds
+    #[serde(default = "default_hls_segment_duration")]
+    pub segment_duration: u32,
+
+    /// Segments kept in the sliding-window playlist
+    #[serde(default = "default_hls_playlist_length")]
+    pub playlist_length: u32,
+
+    /// Segment container: mpegts, fmp4
+    #[serde(default = "default_hls_segment_format")]
+    pub segment_format: String,
+
+    /// Built-in HTTP server port (0 = don't serve)
+    #[serde(default = "default_hls_port")]
+    pub port: u16,
 }
 
+impl Default for HlsSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: String::new(),
+            segment_duration: default_hls_segment_duration(),
+            playlist_length: default_hls_playlist_length(),
+            segment_format: default_hls_segment_format(),
+            port: default_hls_port(),
+        }
+    }
+}
+
+/// DASH manifest output settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DashSettings {
+    /// Enable DASH output
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Output directory for the manifest and segments (empty = temp dir)
+    #[serde(default)]
+    pub dir: String,
+
+    /// Target segment duration in seconds
+    #[serde(default = "default_dash_segment_duration")]
+    pub segment_duration: u32,
+
+    /// Segments kept in the manifest window
+    #[serde(default = "default_dash_window_size")]
+    pub window_size: u32,
+
+    /// Write chunked fMP4 for LL-DASH players
+    #[serde(default)]
+    pub low_latency: bool,
+}
+
+impl Default for DashSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: String::new(),
+            segment_duration: default_dash_segment_duration(),
+            window_size: default_dash_window_size(),
+            low_latency: false,
+        }
+    }
+}
+
+/// Multi-source layout settings
+///
+/// Leave both lists empty to capture a single source as usual.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LayoutSettings {
+    /// Sources tiled in an N-up grid ("monitor:DP-2", "window:ID", "window")
+    #[serde(default)]
+    pub sources: Vec<String>,
+
+    /// Explicitly placed layers
+    #[serde(default)]
+    pub layers: Vec<LayerSettings>,
+}
+
+/// One explicitly placed layout layer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerSettings {
+    /// Source ("monitor:DP-2", "window:ID", "window")
+    pub source: String,
+    /// Left edge in output pixels
+    #[serde(default)]
+    pub x: u32,
+    /// Top edge in output pixels
+    #[serde(default)]
+    pub y: u32,
+    /// Width in output pixels
+    pub width: u32,
+    /// Height in output pixels
+    pub height: u32,
+    /// Stacking order (higher is on top)
+    #[serde(default)]
+    pub z: i32,
+}
+
+fn default_hls_segment_duration() -> u32 {
+    2
+}
+
+fn default_hls_playlist_length() -> u32 {
+    6
+}
+
+fn default_hls_segment_format() -> String {
+    "mpegts".to_string()
+}
+
+fn default_hls_port() -> u16 {
+    8088
+}
+
+fn default_dash_segment_duration() -> u32 {
+    2
+}
+
+fn default_dash_window_size() -> u32 {
+    6
+}
+
+
 fn default_webrtc_codec() -> String {
     "h264".to_string()
 }
@@ -385,21 +1000,9 @@ pub struct Av1Settings {
     #[serde(default = "default_av1_tune")]
     pub tune: String,
 
-    /// Enable lookahead for better quality (disabled in low-latency mode)
-    #[serde(default)]
-    pub lookahead: bool,
-
-    /// Lookahead depth in frames (RTX 50: up to 250, default 20)
-    #[serde(default = "default_lookahead_depth")]
-    pub lookahead_depth: u32,
-
-    /// Enable spatial adaptive quantization
-    #[serde(default = "default_true")]
-    pub spatial_aq: bool,
-
-    /// Enable temporal AQ (RTX 50 series, ~4-5% efficiency gain)
-    #[serde(default)]
-    pub temporal_aq: bool,
+    /// Chroma format: 420 (default), 422 (RTX 50 only), 444
+    #[serde(default = "default_av1_chroma")]
+    pub chroma: String,
 
     /// Multipass encoding: disabled (default), quarter, full
     #[serde(default = "default_multipass")]
@@ -412,6 +1015,22 @@ pub struct Av1Settings {
     /// B-frame reference mode (RTX 50 series)
     #[serde(default)]
     pub b_ref_mode: bool,
+
+    /// Deprecated: use `[encoder] lookahead`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookahead: Option<bool>,
+
+    /// Deprecated: use `[encoder] lookahead_depth`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lookahead_depth: Option<u32>,
+
+    /// Deprecated: use `[encoder] spatial_aq`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spatial_aq: Option<bool>,
+
+    /// Deprecated: use `[encoder] temporal_aq`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temporal_aq: Option<bool>,
 }
 
 impl Default for Av1Settings {
@@ -420,13 +1039,42 @@ impl Default for Av1Settings {
             ten_bit: false,
             tier: default_av1_tier(),
             tune: default_av1_tune(),
-            lookahead: false,
-            lookahead_depth: 20,
-            spatial_aq: true,
-            temporal_aq: false,
+            chroma: default_av1_chroma(),
             multipass: default_multipass(),
             gop_length: 0,
             b_ref_mode: false,
+            lookahead: None,
+            lookahead_depth: None,
+            spatial_aq: None,
+            temporal_aq: None,
+        }
+    }
+}
+
+impl Av1Settings {
+    /// Carry the lookahead and AQ keys `[av1]` used to have over to `quality`
+    ///
+    /// They apply to every codec now and live in `[encoder]`; when an old
+    /// config still sets them here, they win over `[encoder]` with a warning.
+    fn apply_deprecated_quality(&self, quality: &mut EncoderQuality) {
+        let deprecated = |key: &str| {
+            warn!("[av1] {} is deprecated and applies to every codec, set [encoder] {}", key, key)
+        };
+        if let Some(lookahead) = self.lookahead {
+            deprecated("lookahead");
+            quality.lookahead = lookahead;
+        }
+        if let Some(depth) = self.lookahead_depth {
+            deprecated("lookahead_depth");
+            quality.lookahead_depth = depth;
+        }
+        if let Some(spatial_aq) = self.spatial_aq {
+            deprecated("spatial_aq");
+            quality.spatial_aq = spatial_aq;
+        }
+        if let Some(temporal_aq) = self.temporal_aq {
+            deprecated("temporal_aq");
+            quality.temporal_aq = temporal_aq;
         }
     }
 }
@@ -439,10 +1087,26 @@ fn default_av1_tune() -> String {
     "hq".to_string()
 }
 
+fn default_tune() -> String {
+    "auto".to_string()
+}
+
+fn default_rate_control() -> String {
+    "auto".to_string()
+}
+
+fn default_content_mode() -> String {
+    "motion".to_string()
+}
+
 fn default_lookahead_depth() -> u32 {
     20
 }
 
+fn default_av1_chroma() -> String {
+    "420".to_string()
+}
+
 fn default_multipass() -> String {
     "disabled".to_string()
 }
@@ -456,6 +1120,14 @@ fn default_codec() -> String {
     "h264".to_string()
 }
 
+fn default_fit() -> String {
+    "letterbox".to_string()
+}
+
+fn default_fit_color() -> String {
+    "#000000".to_string()
+}
+
 fn default_quality() -> String {
     "medium".to_string()
 }
@@ -464,6 +1136,14 @@ fn default_camera_name() -> String {
     "Nitrogen Camera".to_string()
 }
 
+fn default_camera_format() -> String {
+    "bgra".to_string()
+}
+
+fn default_camera_transition_ms() -> u32 {
+    DEFAULT_CAMERA_TRANSITION_MS
+}
+
 fn default_true() -> bool {
     true
 }
@@ -472,10 +1152,26 @@ fn default_audio_source() -> String {
     "none".to_string()
 }
 
+fn default_mic_channel() -> String {
+    "stereo".to_string()
+}
+
 fn default_audio_codec() -> String {
     "aac".to_string()
 }
 
+fn default_limiter() -> String {
+    "tanh".to_string()
+}
+
+fn default_limiter_threshold_db() -> f32 {
+    DEFAULT_LIMITER_THRESHOLD_DB
+}
+
+fn default_limiter_release_ms() -> u32 {
+    DEFAULT_LIMITER_RELEASE_MS
+}
+
 impl Default for DefaultSettings {
     fn default() -> Self {
         Self {
@@ -483,6 +1179,8 @@ impl Default for DefaultSettings {
             codec: default_codec(),
             bitrate: 0,
             low_latency: true,
+            fit: default_fit(),
+            fit_color: default_fit_color(),
         }
     }
 }
@@ -492,6 +1190,22 @@ impl Default for EncoderSettings {
         Self {
             quality: default_quality(),
             gpu: 0,
+            lookahead: false,
+            lookahead_depth: default_lookahead_depth(),
+            spatial_aq: false,
+            temporal_aq: false,
+            multipass: default_multipass(),
+            async_depth: 0,
+            zero_copy: true,
+            max_sessions: 0,
+            content_mode: default_content_mode(),
+            temporal_layers: 0,
+            intra_refresh: false,
+            intra_refresh_period: 0,
+            tune: default_tune(),
+            rate_control: default_rate_control(),
+            max_bitrate: 0,
+            vbv_buffer_size: 0,
         }
     }
 }
@@ -500,6 +1214,11 @@ impl Default for CameraSettings {
     fn default() -> Self {
         Self {
             name: default_camera_name(),
+            alpha: false,
+            format: default_camera_format(),
+            refresh_on_connect: true,
+            transition_ms: default_camera_transition_ms(),
+            placeholder: String::new(),
         }
     }
 }
@@ -510,6 +1229,15 @@ impl Default for AudioSettings {
             source: default_audio_source(),
             codec: default_audio_codec(),
             bitrate: 0,
+            limiter: default_limiter(),
+            limiter_threshold_db: default_limiter_threshold_db(),
+            limiter_release_ms: default_limiter_release_ms(),
+            sync_offset_ms: 0,
+            latency_quantum: None,
+            mic: MicSettings::default(),
+            ducking: DuckingSettings::default(),
+            record: None,
+            stream: None,
         }
     }
 }
@@ -544,13 +1272,21 @@ impl ConfigFile {
         let content = std::fs::read_to_string(&path)
             .map_err(|e| NitrogenError::Config(format!("Failed to read config file: {}", e)))?;
 
-        let config: ConfigFile = toml::from_str(&content)
-            .map_err(|e| NitrogenError::Config(format!("Failed to parse config file: {}", e)))?;
+        let config = Self::parse(&content)?;
 
         info!("Loaded configuration from {:?}", path);
         Ok(config)
     }
 
+    /// Parse configuration from TOML text
+    ///
+    /// Missing sections and keys take their defaults. Syntax and type errors
+    /// quote the offending line.
+    pub fn parse(content: &str) -> Result<Self> {
+        toml::from_str(content)
+            .map_err(|e| NitrogenError::Config(format!("Failed to parse config file: {}", e)))
+    }
+
     /// Load configuration, logging warnings but returning defaults on error
     pub fn load_or_default() -> Self {
         match Self::load() {
@@ -588,6 +1324,342 @@ impl ConfigFile {
         Ok(())
     }
 
+    /// Build a validated capture config from these settings
+    ///
+    /// Parses every string setting into its typed value, applies the
+    /// encoder, AV1, audio, HDR, overlay, watermark, WebRTC, HLS, DASH, and
+    /// capture sections, and runs [`CaptureConfig::validate_strict`]. Apply CLI
+    /// overrides to the `ConfigFile` first so they go through the same parsing.
+    pub fn to_capture_config(&self, source: CaptureSource) -> Result<CaptureConfig> {
+        let mut config = match source {
+            CaptureSource::Monitor { id } => CaptureConfig::monitor(id),
+            CaptureSource::Window { id } => CaptureConfig::window(id),
+        };
+
+        // [defaults]
+        config.preset = parse_setting(
+            "defaults.preset",
+            &self.defaults.preset,
+            "720p30, 720p60, 1080p30, 1080p60, 1440p30, 1440p60, 1440p120, 4k30, 4k60, 4k120, or WxH@FPS",
+        )?;
+        config.codec = parse_setting("defaults.codec", &self.defaults.codec, "h264, hevc, av1")?;
+        config.bitrate = self.defaults.bitrate;
+        config.low_latency = self.defaults.low_latency;
+        config.fit = parse_setting("defaults.fit", &self.defaults.fit, "stretch, letterbox, crop")?;
+        config.fit_color =
+            parse_setting("defaults.fit_color", &self.defaults.fit_color, "#rrggbb or black")?;
+
+        // [encoder]
+        config.encoder_preset = parse_setting(
+            "encoder.quality",
+            &self.encoder.quality,
+            "fast, medium, slow, quality",
+        )?;
+        config.gpu = self.encoder.gpu;
+        config.quality = EncoderQuality {
+            lookahead: self.encoder.lookahead,
+            lookahead_depth: self.encoder.lookahead_depth,
+            spatial_aq: self.encoder.spatial_aq,
+            temporal_aq: self.encoder.temporal_aq,
+            multipass: parse_setting(
+                "encoder.multipass",
+                &self.encoder.multipass,
+                "disabled, quarter, full",
+            )?,
+            async_depth: self.encoder.async_depth,
+        };
+        self.av1.apply_deprecated_quality(&mut config.quality);
+        config.zero_copy = self.encoder.zero_copy;
+        config.max_encoder_sessions =
+            (self.encoder.max_sessions > 0).then_some(self.encoder.max_sessions);
+        config.content_mode = parse_setting(
+            "encoder.content_mode",
+            &self.encoder.content_mode,
+            "motion, static",
+        )?;
+        config.temporal_layers = self.encoder.temporal_layers;
+        config.intra_refresh = self.encoder.intra_refresh;
+        config.intra_refresh_period =
+            (self.encoder.intra_refresh_period > 0).then_some(self.encoder.intra_refresh_period);
+        config.tune = parse_setting("encoder.tune", &self.encoder.tune, "auto, hq, ll, ull")?;
+        config.rate_control = match self.encoder.rate_control.trim() {
+            "" | "auto" => None,
+            mode => Some(parse_setting::<RateControl>(
+                "encoder.rate_control",
+                mode,
+                "auto, cbr, vbr, cqp:<qp>, cq:<level>",
+            )?),
+        };
+        config.max_bitrate = (self.encoder.max_bitrate > 0).then_some(self.encoder.max_bitrate);
+        config.vbv_buffer_size =
+            (self.encoder.vbv_buffer_size > 0).then_some(self.encoder.vbv_buffer_size);
+
+        // [av1]
+        config.av1 = Av1Config {
+            ten_bit: self.av1.ten_bit,
+            tier: parse_setting("av1.tier", &self.av1.tier, "main, high")?,
+            gop_length: (self.av1.gop_length > 0).then_some(self.av1.gop_length),
+            tune: parse_setting("av1.tune", &self.av1.tune, "hq, uhq, ll, ull, lossless")?,
+            chroma: parse_setting("av1.chroma", &self.av1.chroma, "420, 422, 444")?,
+            multipass: parse_setting(
+                "av1.multipass",
+                &self.av1.multipass,
+                "disabled, quarter, full",
+            )?,
+            b_ref_mode: self.av1.b_ref_mode,
+        };
+
+        // [camera]
+        config.camera_name = self.camera.name.clone();
+        config.camera_alpha = self.camera.alpha;
+        config.camera_format =
+            parse_setting::<CameraFormat>("camera.format", &self.camera.format, "bgra, nv12")?;
+        config.camera_refresh_on_connect = self.camera.refresh_on_connect;
+        config.camera_transition_ms = self.camera.transition_ms;
+        if !self.camera.placeholder.is_empty() {
+            config.camera_placeholder = Some(PathBuf::from(&self.camera.placeholder));
+        }
+
+        // [audio]
+        config.audio_source = parse_setting::<AudioSource>(
+            "audio.source",
+            &self.audio.source,
+            "none, desktop, mic, both, app:<name>",
+        )?;
+        config.audio_codec = parse_setting("audio.codec", &self.audio.codec, "aac, opus")?;
+        if config.audio_codec == AudioCodec::Copy {
+            return Err(NitrogenError::config(
+                "Invalid audio.codec 'copy': captured audio must be encoded. Valid options: aac, opus",
+            ));
+        }
+        config.audio_bitrate = self.audio.bitrate;
+        if let Some(ref record) = self.audio.record {
+            config.record_audio = Some(output_audio("audio.record", record)?);
+        }
+        if let Some(ref stream) = self.audio.stream {
+            config.stream_audio = Some(output_audio("audio.stream", stream)?);
+        }
+        config.mic_channel = parse_setting::<MicChannel>(
+            "audio.mic.channel",
+            &self.audio.mic.channel,
+            "left, right, mono, stereo",
+        )?;
+        config.audio_limiter = LimiterConfig {
+            mode: parse_setting::<LimiterMode>(
+                "audio.limiter",
+                &self.audio.limiter,
+                "none, tanh, truepeak",
+            )?,
+            threshold_db: self.audio.limiter_threshold_db,
+            release_ms: self.audio.limiter_release_ms,
+        };
+        config.audio_ducking = self.audio.ducking.enabled;
+        config.audio_ducking_mode = parse_setting::<DuckingMode>(
+            "audio.ducking.mode",
+            &self.audio.ducking.mode,
+            "rms, vad",
+        )?;
+        config.av_sync_offset_ms = self.audio.sync_offset_ms;
+        config.audio_latency_quantum = self.audio.latency_quantum;
+
+        // [hdr]
+        config.hdr_tonemap =
+            parse_setting::<TonemapMode>("hdr.tonemap", &self.hdr.tonemap, "auto, on, off")?;
+        config.hdr_algorithm = parse_setting::<TonemapAlgorithm>(
+            "hdr.algorithm",
+            &self.hdr.algorithm,
+            "reinhard, aces, hable, bt2390",
+        )?;
+        config.hdr_peak_luminance = self.hdr.peak_luminance;
+        config.hdr_dynamic = self.hdr.dynamic;
+        if !self.hdr.input.is_empty() {
+            config.hdr_input = Some(parse_setting::<HdrMetadata>(
+                "hdr.input",
+                &self.hdr.input,
+                "pq[:NITS], hlg[:NITS], sdr",
+            )?);
+        }
+
+        // [overlay]
+        config.overlay_enabled = self.overlay.enabled;
+        config.overlay_position = parse_setting::<OverlayPosition>(
+            "overlay.position",
+            &self.overlay.position,
+            "top-left, top-right, bottom-left, bottom-right",
+        )?;
+        if !self.overlay.template.is_empty() {
+            config.overlay_template = Some(self.overlay.template.clone());
+        }
+        config.overlay_title = self.overlay.title.clone();
+        if self.overlay.timecode {
+            config.overlay_timecode = Some(parse_setting::<Timecode>(
+                "overlay.timecode_start",
+                &self.overlay.timecode_start,
+                "HH:MM:SS:FF",
+            )?);
+        }
+        config.timecode_metadata = self.overlay.timecode_metadata;
+
+        // [watermark]
+        if !self.watermark.path.is_empty() {
+            config.watermark = Some(WatermarkConfig {
+                path: PathBuf::from(&self.watermark.path),
+                position: parse_setting::<OverlayPosition>(
+                    "watermark.position",
+                    &self.watermark.position,
+                    "top-left, top-right, bottom-left, bottom-right",
+                )?,
+                margin: self.watermark.margin,
+                opacity: self.watermark.opacity,
+                scale: self.watermark.scale,
+            });
+        }
+
+        // [webrtc] (port 0 keeps the default signaling port)
+        config.webrtc_enabled = self.webrtc.enabled;
+        if self.webrtc.port != 0 {
+            config.webrtc_port = self.webrtc.port;
+        }
+        let video_codec = self.webrtc.video_codec.to_lowercase();
+        if !WEBRTC_VIDEO_CODECS.contains(&video_codec.as_str()) {
+            return Err(NitrogenError::config(format!(
+                "Invalid webrtc.video_codec '{}'. Valid options: {}",
+                self.webrtc.video_codec,
+                WEBRTC_VIDEO_CODECS.join(", ")
+            )));
+        }
+        config.webrtc_video_codec = video_codec;
+        if self.webrtc.simulcast {
+            config.webrtc_simulcast = validate_simulcast_layers(&self.webrtc.layers)?;
+        }
+        for server in &self.webrtc.ice_servers {
+            server
+                .validate()
+                .map_err(|e| NitrogenError::config(format!("Invalid webrtc.ice_servers: {}", e)))?;
+        }
+        config.webrtc_ice_servers = self.webrtc.ice_servers.clone();
+
+        // [hls]
+        if self.hls.enabled {
+            let mut hls = HlsConfig {
+                segment_duration: self.hls.segment_duration,
+                playlist_length: self.hls.playlist_length,
+                segment_format: parse_setting(
+                    "hls.segment_format",
+                    &self.hls.segment_format,
+                    "mpegts, fmp4",
+                )?,
+                port: self.hls.port,
+                ..Default::default()
+            };
+            if !self.hls.dir.is_empty() {
+                hls.output_dir = PathBuf::from(&self.hls.dir);
+            }
+            config.hls = Some(hls);
+        }
+
+        // [dash]
+        if self.dash.enabled {
+            let mut dash = DashConfig {
+                segment_duration: self.dash.segment_duration,
+                window_size: self.dash.window_size,
+                low_latency: self.dash.low_latency,
+                ..Default::default()
+            };
+            if !self.dash.dir.is_empty() {
+                dash.output_dir = PathBuf::from(&self.dash.dir);
+            }
+            config.dash = Some(dash);
+        }
+
+        // [layout]
+        config.layout = layout_layers(&self.layout, config.width(), config.height())?;
+
+        // [recording]
+        config.record_container = self
+            .recording
+            .container
+            .as_deref()
+            .map(|c| {
+                parse_setting::<ContainerFormat>("recording.container", c, "mp4, mkv, fmp4, webm")
+            })
+            .transpose()?;
+        config.replay_buffer_secs = (self.recording.replay_buffer > 0)
+            .then_some(self.recording.replay_buffer);
+        let segment_duration = self
+            .recording
+            .segment_duration
+            .as_deref()
+            .map(|d| {
+                parse_duration(d).map_err(|e| {
+                    NitrogenError::config(format!(
+                        "Invalid recording.segment_duration '{}': {}. Use e.g. 10m, 1h or HH:MM:SS",
+                        d, e
+                    ))
+                })
+            })
+            .transpose()?;
+        config.record_segments = SegmentConfig {
+            duration: segment_duration.filter(|d| !d.is_zero()),
+            max_bytes: (self.recording.segment_size > 0)
+                .then(|| self.recording.segment_size * 1_000_000),
+        };
+        config.record_metadata = self.recording.metadata.clone();
+
+        // [capture]
+        config.frame_timeout_ms = self.capture.frame_timeout_ms;
+        config.cursor_mode = parse_setting::<CursorMode>(
+            "capture.cursor",
+            &self.capture.cursor,
+            "hidden, embedded, metadata",
+        )?;
+        config.cursor_scale = self.capture.cursor_scale;
+        config.capture_latency_quantum = self.capture.latency_quantum;
+        if self.capture.portal_attempts == 0 {
+            return Err(NitrogenError::config(
+                "Invalid capture.portal_attempts: must be at least 1",
+            ));
+        }
+        config.portal_attempts = self.capture.portal_attempts;
+
+        // [performance]
+        let alpha = self.performance.ema_alpha;
+        let samples = self.performance.metrics_sample_count;
+        config.metrics_mode = match MetricsMode::from_sample_count(samples) {
+            MetricsMode::Ema { .. } => {
+                if !(f64::EPSILON..=1.0).contains(&alpha) {
+                    return Err(NitrogenError::config(format!(
+                        "Invalid performance.ema_alpha '{}': must be greater than 0.0 and at most 1.0",
+                        alpha
+                    )));
+                }
+                MetricsMode::Ema { alpha }
+            }
+            rolling => rolling,
+        };
+        config.frame_hashing = self.performance.frame_hashing;
+        config.stats_interval_secs = self.performance.stats_interval_secs;
+        config.gpu_monitoring = self.performance.gpu_monitoring;
+        let affinity = &self.performance.cpu_affinity;
+        let cores = |key: &str, value: &str| -> Result<Option<CpuSet>> {
+            if value.is_empty() {
+                return Ok(None);
+            }
+            parse_setting::<CpuSet>(key, value, "core lists such as \"2\" or \"0-1,4\"")
+                .map(Some)
+        };
+        config.cpu_affinity = CpuAffinity {
+            capture: cores("performance.cpu_affinity.capture", &affinity.capture)?,
+            encode: cores("performance.cpu_affinity.encode", &affinity.encode)?,
+            audio: cores("performance.cpu_affinity.audio", &affinity.audio)?,
+            hotkeys: cores("performance.cpu_affinity.hotkeys", &affinity.hotkeys)?,
+        };
+
+        config.validate_strict().map_err(NitrogenError::config)?;
+
+        Ok(config)
+    }
+
     /// Create a default config file if it doesn't exist
     pub fn create_default_if_missing() -> Result<bool> {
         let path = Self::default_path();
@@ -601,9 +1673,151 @@ impl ConfigFile {
     }
 }
 
+/// Deserialize a bitrate given as kbps (`6000`) or with a unit (`"6M"`)
+fn deserialize_bitrate<'de, D>(deserializer: D) -> std::result::Result<u32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Bitrate {
+        Kbps(u32),
+        Text(String),
+    }
+
+    match Bitrate::deserialize(deserializer)? {
+        Bitrate::Kbps(kbps) => Ok(kbps),
+        Bitrate::Text(text) => parse_bitrate(&text).map_err(serde::de::Error::custom),
+    }
+}
+
+/// Parse a string setting, naming the key and valid options on failure
+fn parse_setting<T>(key: &str, value: &str, valid: &str) -> Result<T>
+where
+    T: std::str::FromStr<Err = String>,
+{
+    value.parse().map_err(|e: String| {
+        NitrogenError::config(format!(
+            "Invalid {} '{}': {}. Valid options: {}",
+            key, value, e, valid
+        ))
+    })
+}
+
+/// Find the line (1-based) that sets a dotted key such as `encoder.tune`
+///
+/// Only handles the layout the sample config uses: one `[table]` header per
+/// section and `key = value` lines. Returns None for settings left at their
+/// default (or written in another form).
+pub fn setting_line(content: &str, key: &str) -> Option<usize> {
+    let (table, name) = key.rsplit_once('.').unwrap_or(("", key));
+    let mut current = "";
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = header.trim();
+            continue;
+        }
+        if current != table {
+            continue;
+        }
+        if let Some((setting, _)) = line.split_once('=') {
+            if setting.trim().trim_matches('"') == name {
+                return Some(i + 1);
+            }
+        }
+    }
+    None
+}
+
+/// Parse an `[audio.record]`/`[audio.stream]` table
+fn output_audio(section: &str, settings: &OutputAudioSettings) -> Result<OutputAudio> {
+    let key = format!("{}.codec", section);
+    let codec: AudioCodec = parse_setting(&key, &settings.codec, "aac, opus")?;
+    if codec == AudioCodec::Copy {
+        return Err(NitrogenError::config(format!(
+            "Invalid {} 'copy': captured audio must be encoded. Valid options: aac, opus",
+            key
+        )));
+    }
+    Ok(OutputAudio::new(codec, settings.bitrate))
+}
+
+/// Resolve the `[layout]` section into layers for a `width`x`height` output
+fn layout_layers(layout: &LayoutSettings, width: u32, height: u32) -> Result<Vec<LayoutLayer>> {
+    if !layout.sources.is_empty() && !layout.layers.is_empty() {
+        return Err(NitrogenError::config(
+            "Invalid layout: use either layout.sources (grid) or layout.layers, not both",
+        ));
+    }
+
+    if !layout.layers.is_empty() {
+        return layout
+            .layers
+            .iter()
+            .map(|layer| {
+                if layer.width == 0 || layer.height == 0 {
+                    return Err(NitrogenError::config(format!(
+                        "Invalid layout.layers '{}': width and height must be non-zero",
+                        layer.source
+                    )));
+                }
+                let source = parse_setting(
+                    "layout.layers.source",
+                    &layer.source,
+                    "monitor[:ID], window[:ID]",
+                )?;
+                Ok(LayoutLayer::new(source, layer.x, layer.y, layer.width, layer.height)
+                    .with_z(layer.z))
+            })
+            .collect();
+    }
+
+    let sources = layout
+        .sources
+        .iter()
+        .map(|s| parse_setting("layout.sources", s, "monitor[:ID], window[:ID]"))
+        .collect::<Result<Vec<CaptureSource>>>()?;
+    Ok(grid_layout(&sources, width, height))
+}
+
+/// Check a simulcast ladder and sort it highest layer first
+fn validate_simulcast_layers(layers: &[SimulcastLayer]) -> Result<Vec<SimulcastLayer>> {
+    if layers.len() < 2 {
+        return Err(NitrogenError::config(
+            "Invalid webrtc.layers: simulcast needs at least two layers",
+        ));
+    }
+    for (i, layer) in layers.iter().enumerate() {
+        if layer.rid.is_empty() || !layer.rid.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(NitrogenError::config(format!(
+                "Invalid webrtc.layers rid '{}': must be non-empty and alphanumeric",
+                layer.rid
+            )));
+        }
+        if layer.height < 144 || layer.bitrate == 0 {
+            return Err(NitrogenError::config(format!(
+                "Invalid webrtc.layers '{}': height must be at least 144 and bitrate non-zero",
+                layer.rid
+            )));
+        }
+        if layers[..i].iter().any(|other| other.rid == layer.rid) {
+            return Err(NitrogenError::config(format!(
+                "Invalid webrtc.layers: duplicate rid '{}'",
+                layer.rid
+            )));
+        }
+    }
+
+    let mut sorted = layers.to_vec();
+    sorted.sort_by(|a, b| b.height.cmp(&a.height));
+    Ok(sorted)
+}
+
 /// Generate a sample configuration file
 pub fn sample_config() -> String {
-    r#"# Nitrogen Configuration
+    r##"# Nitrogen Configuration
 # https://github.com/ghostkellz/nitrogen
 
 [defaults]
@@ -613,12 +1827,18 @@ preset = "1080p60"
 # Video codec: h264, hevc, av1
 codec = "h264"
 
-# Bitrate in kbps (0 = automatic based on preset)
+# Bitrate in kbps (0 = automatic based on preset); "6M" or "6000k" also work
 bitrate = 0
 
 # Enable low-latency mode (recommended for Discord)
 low_latency = true
 
+# Sources with a different aspect ratio: "letterbox" (bars), "crop" or "stretch"
+fit = "letterbox"
+
+# Bar color for letterbox/pillarbox
+fit_color = "#000000"
+
 [encoder]
 # Quality preset: fast, medium, slow, quality
 quality = "medium"
@@ -626,6 +1846,74 @@ quality = "medium"
 # GPU index (0 = first NVIDIA GPU)
 gpu = 0
 
+# Lookahead, adaptive quantization and multipass apply to H.264, HEVC and AV1
+# Enable lookahead for better quality (ignored in low-latency mode)
+lookahead = false
+
+# Lookahead depth in frames (RTX 40: up to 32, RTX 50: up to 250)
+lookahead_depth = 20
+
+# Enable spatial adaptive quantization (better quality at same bitrate)
+spatial_aq = false
+
+# Enable temporal adaptive quantization (~4-5% efficiency improvement)
+temporal_aq = false
+
+# Two-pass encoding: "disabled" (default), "quarter", "full" (best quality)
+# Adds latency, so it is ignored in low-latency mode. Best for recordings.
+multipass = "disabled"
+
+# Frames NVENC may have in flight (0 = FFmpeg default). Deeper queues keep
+# the GPU busy at 4K120 or with frame generation, but each frame in flight is
+# a frame of latency, so low-latency mode always uses 1.
+async_depth = 0
+
+# NVENC tuning: auto, hq, ll, ull
+# auto uses ll when low_latency is on, hq otherwise. ull is the lowest latency
+# (no B-frames, lookahead or multipass) at a visible quality cost.
+tune = "auto"
+
+# Rate control: auto, cbr, vbr, cqp:<qp>, cq:<level>
+# auto = CBR when low_latency is on, VBR otherwise. cq:23 aims for a quality
+# level with the bitrate as the ceiling; cqp:20 uses one quantizer and ignores
+# the bitrate. Quality modes are for recordings: low latency stays CBR.
+# H.264/HEVC take 0-51; AV1 takes cq up to 63 and a 0-255 quantizer index.
+rate_control = "auto"
+
+# Capped VBR: peak bitrate and VBV buffer, for ingest servers that throttle
+# streams going over a limit (e.g. max_bitrate = "6M" for Twitch)
+# 0 = 1.5x the bitrate, and a buffer of one second at the bitrate
+max_bitrate = 0
+vbv_buffer_size = 0
+
+# Import DMA-BUF capture frames into CUDA and encode them without a CPU copy.
+# Used when the driver supports it, for linear buffers with 8-bit limited
+# range output; everything else falls back to the copy path automatically.
+zero_copy = true
+
+# Concurrent encoder sessions (0 = detect: 3/5/8 on GeForce depending on the
+# driver, unlimited on professional cards). Each recording preset and
+# simulcast layer opens its own session.
+max_sessions = 0
+
+# Content mode: motion, static
+# static = for slides/docs on slow links: skips frames that barely changed and
+# refreshes with a keyframe every few seconds so late joiners can sync
+content_mode = "motion"
+
+# Temporal SVC layers (H.264 only, 0 = disabled, max 4). Viewers on lossy
+# links (WebRTC, SRT) can drop the upper layers and keep decoding at a lower
+# framerate. No benefit for recordings.
+temporal_layers = 0
+
+# Refresh the picture with a moving band of intra blocks instead of periodic
+# keyframes: no bitrate spikes, and lost packets heal within one cycle instead
+# of freezing until the next keyframe. For SRT/WebRTC streams, not recordings
+# (there are no clean keyframes to seek to).
+intra_refresh = false
+# Frames per refresh cycle (0 = one second)
+intra_refresh_period = 0
+
 [av1]
 # AV1-specific settings (only used when codec = "av1")
 # Supports RTX 40 series (Ada) and RTX 50 series (Blackwell) features
@@ -643,19 +1931,10 @@ tier = "main"
 #   "ull" - Ultra low latency
 tune = "hq"
 
-# Enable lookahead for better quality (ignored in low-latency mode)
-lookahead = false
-
-# Lookahead depth in frames (RTX 40: up to 32, RTX 50: up to 250)
-lookahead_depth = 20
-
-# Enable spatial adaptive quantization (better quality at same bitrate)
-spatial_aq = true
-
-# Enable temporal AQ (RTX 50 series only, ~4-5% efficiency improvement)
-temporal_aq = false
+# Chroma format: "420" (default), "422" (RTX 50 only), "444"
+chroma = "420"
 
-# Multipass encoding: "disabled" (default), "quarter", "full" (best quality)
+# AV1-only multipass, used when [encoder] multipass is "disabled"
 multipass = "disabled"
 
 # GOP (keyframe interval) override in frames (0 = auto, defaults to 2x FPS)
@@ -667,9 +1946,21 @@ b_ref_mode = false
 [camera]
 # Virtual camera name shown in applications
 name = "Nitrogen Camera"
+# Keep transparency on the camera feed (recording/streams stay opaque)
+alpha = false
+# Pixel format: bgra, or nv12 for apps that only accept YUV cameras
+format = "bgra"
+# Send a full frame as soon as an app opens the camera (avoids a garbled
+# first second when Discord toggles it)
+refresh_on_connect = true
+# Crossfade in ms to the placeholder on `nitrogen pause` and back on
+# `nitrogen resume` (0 = hard cut)
+transition_ms = 250
+# PNG shown while paused, scaled to the camera size (empty = black)
+placeholder = ""
 
 [audio]
-# Audio source: none, desktop, mic, both
+# Audio source: none, desktop, mic, both, or app:<name> for one application
 source = "none"
 
 # Audio codec: aac, opus
@@ -678,6 +1969,65 @@ codec = "aac"
 # Audio bitrate in kbps (0 = automatic based on codec)
 bitrate = 0
 
+# Clipping stage for mixed audio: none (hard clamp), tanh (soft clip), truepeak
+# truepeak is a look-ahead limiter that leaves audio below the threshold untouched
+limiter = "tanh"
+limiter_threshold_db = -1.0
+limiter_release_ms = 50
+
+# A/V sync offset in ms for recordings and streams
+# Positive delays audio, negative delays video (-80 when audio lags by 80 ms)
+sync_offset_ms = 0
+
+# PipeWire quantum to request for audio capture, in samples at 48 kHz
+# (unset = the server's default; PipeWire may clamp the request)
+# latency_quantum = 256
+
+[audio.mic]
+# Microphone channel handling: left, right, mono, stereo
+# Use "left" or "right" for interfaces that put a mono mic on one channel
+channel = "stereo"
+
+[audio.ducking]
+# Lower desktop audio while the mic is active
+enabled = false
+
+# What counts as mic activity: rms (anything above the threshold) or vad
+# (speech only, so typing and fan noise don't duck the desktop)
+mode = "rms"
+
+# Per-output audio encoding (defaults to codec/bitrate above)
+# [audio.record]
+# codec = "opus"
+# bitrate = 160
+#
+# [audio.stream]
+# codec = "aac"
+# bitrate = 160
+
+[capture]
+# Reconnect the PipeWire stream when no frame arrives for this many
+# milliseconds (compositor stalls). 0 disables the watchdog.
+frame_timeout_ms = 2000
+
+# Cursor: hidden, embedded (drawn by the compositor), metadata (drawn by
+# Nitrogen from portal cursor metadata; falls back to embedded if unsupported)
+cursor = "embedded"
+
+# Cursor size in metadata mode, relative to the compositor's cursor
+cursor_scale = 1.0
+
+# PipeWire quantum to request for the video stream, in samples at 48 kHz
+# Lower (256) trims buffering on low-latency setups, higher (1024) can steady
+# capture on weaker hardware. Unset = the server's default; PipeWire clamps
+# the request to its clock.min-quantum/max-quantum.
+# latency_quantum = 512
+
+# How often to try starting the portal session when it fails with a
+# transient D-Bus error (GNOME sometimes does on the first request).
+# Cancelling the selection dialog is never retried.
+portal_attempts = 3
+
 [detection]
 # Automatically detect and optimize for Gamescope
 auto_gamescope = true
@@ -692,15 +2042,23 @@ compositor_optimizations = true
 # HDR tonemapping mode: auto (detect), on (always), off (never)
 tonemap = "auto"
 
-# Tonemapping algorithm: reinhard, aces, hable
+# Tonemapping algorithm: reinhard, aces, hable, bt2390
 algorithm = "reinhard"
 
 # Peak luminance in nits (fallback when metadata unavailable)
 peak_luminance = 1000
 
+# Measure each frame's luminance and tonemap for it instead of the static
+# peak, smoothed over time so scene changes don't flicker
+dynamic = false
+
 # Preserve HDR for file recording (only tonemap virtual camera output)
 preserve_hdr_recording = false
 
+# What the captured signal is, when the compositor doesn't say: pq, hlg or sdr,
+# optionally with the mastering peak in nits ("pq:1000"). Empty = detect.
+input = ""
+
 [performance]
 # Log frame times to console for debugging
 log_frame_times = false
@@ -712,8 +2070,29 @@ gpu_monitoring = true
 sample_interval_ms = 100
 
 # Number of samples for rolling average calculations (higher = smoother stats)
+# 0 = exponential moving average instead (reacts faster to spikes)
 metrics_sample_count = 120
 
+# EMA weight of the newest sample when metrics_sample_count = 0 (0.0 - 1.0)
+ema_alpha = 0.2
+
+# Log a cheap hash of each frame with its PTS at capture and output (trace
+# level), to match frames through the pipeline and spot drops or duplicates
+frame_hashing = false
+
+# Log latency/FPS stats (and GPU stats with gpu_monitoring) every N seconds,
+# for long unattended streams without an overlay. 0 = off
+stats_interval_secs = 0
+
+# Pin threads to CPU cores, e.g. to keep them off the cores a game uses.
+# Core lists like "2" or "0-1,4"; empty leaves the thread to the scheduler.
+# Linux only.
+[performance.cpu_affinity]
+capture = ""
+encode = ""
+audio = ""
+hotkeys = ""
+
 [overlay]
 # Enable on-screen latency overlay
 enabled = false
@@ -731,6 +2110,39 @@ show_drops = true
 # Font scale (1.0 = normal)
 font_scale = 1.0
 
+# Custom text instead of the stats above (empty = stats line). Placeholders:
+# {fps} {bitrate} {drops} {frames} {capture} {encode} {output} {latency}
+# {time} (elapsed HH:MM:SS) {title}. Use \n for multiple lines.
+template = ""
+
+# Text for the {title} placeholder
+title = ""
+
+# Burn a SMPTE timecode (HH:MM:SS:FF, non-drop) into every output, counting
+# frames from timecode_start. Works with or without the stats overlay.
+timecode = false
+timecode_start = "00:00:00:00"
+
+# Also write the timecode into the bitstream (HEVC time_code SEI, AV1
+# timecode metadata OBU). H.264 outputs only get the burn-in.
+timecode_metadata = false
+
+[watermark]
+# PNG logo blended into every frame before encoding (empty = none)
+path = ""
+
+# Corner: top-left, top-right, bottom-left, bottom-right
+position = "bottom-right"
+
+# Distance from the frame edges in pixels
+margin = 16
+
+# Opacity on top of the PNG's own alpha (0.0 - 1.0)
+opacity = 1.0
+
+# Size relative to the PNG (1.0 = as is)
+scale = 1.0
+
 [hotkeys]
 # Toggle capture on/off
 toggle = "ctrl+shift+f9"
@@ -744,6 +2156,19 @@ record = "ctrl+shift+f11"
 # Toggle latency overlay
 overlay_toggle = "ctrl+shift+f12"
 
+# Save a screenshot of the capture
+screenshot = "ctrl+shift+print"
+
+# Where screenshots go; {date}, {time} and {n} as in recording paths, and
+# the extension picks PNG or JPEG
+screenshot_path = "~/Pictures/nitrogen-{date}_{time}-{n}.png"
+
+# Save the replay buffer (needs [recording] replay_buffer)
+save_replay = "ctrl+shift+f8"
+
+# Where replays go
+replay_path = "~/Videos/nitrogen-replay-{date}_{time}-{n}.mp4"
+
 [webrtc]
 # Enable WebRTC output for browser-based viewing
 enabled = false
@@ -751,15 +2176,114 @@ enabled = false
 # Signaling server URL (leave empty for local-only)
 signaling_url = ""
 
-# ICE/STUN servers for NAT traversal
+# STUN/TURN servers for NAT traversal. TURN relays need credentials:
+# ice_servers = [
+#     "stun:stun.l.google.com:19302",
+#     { urls = ["turn:turn.example.com:3478"], username = "user", credential = "secret" },
+# ]
 ice_servers = ["stun:stun.l.google.com:19302"]
 
-# Video codec for WebRTC: h264, vp8, vp9, av1
+# Video codec for WebRTC, must match the encoder codec: h264, vp8, vp9, av1
 video_codec = "h264"
 
 # Listen port (0 = random available port)
 port = 0
-"#
+
+# Encode several resolution/bitrate layers so SFUs can pick one per viewer.
+# Direct peers (the built-in viewer) get the best layer whose max_rtt_ms
+# fits the measured round-trip time. Layers taller than the output are skipped.
+simulcast = false
+layers = [
+    { rid = "f", height = 1080, bitrate = 6000, max_rtt_ms = 60 },
+    { rid = "h", height = 720, bitrate = 2500, max_rtt_ms = 150 },
+    { rid = "q", height = 360, bitrate = 600 },
+]
+
+[hls]
+# Write a rolling HLS playlist (open it on a phone or any HLS player)
+enabled = false
+
+# Output directory for index.m3u8 and segments (empty = system temp dir)
+dir = ""
+
+# Target segment duration in seconds (segments always start on a keyframe)
+segment_duration = 2
+
+# Number of segments kept in the playlist; older segments are deleted
+playlist_length = 6
+
+# Segment container: mpegts, fmp4 (use fmp4 for AV1)
+segment_format = "mpegts"
+
+# Built-in HTTP server port (0 = only write files)
+port = 8088
+
+[dash]
+# Write a live MPEG-DASH manifest with CMAF segments (serve the directory
+# with any HTTP server)
+enabled = false
+
+# Output directory for manifest.mpd and segments (empty = system temp dir)
+dir = ""
+
+# Target segment duration in seconds (segments always start on a keyframe)
+segment_duration = 2
+
+# Number of segments kept in the manifest window
+window_size = 6
+
+# Chunked fMP4 for LL-DASH players
+low_latency = false
+
+[recording]
+# Container format, regardless of the file extension
+# Options: mp4, mkv, fmp4 (fragmented MP4: playable while recording and
+# after a crash), webm (AV1 video with Opus audio only)
+# Unset = picked from the file extension
+# container = "mkv"
+
+# Keep the last N seconds in memory so `nitrogen save-replay` can write them
+# out after something happens (0 = off, or pass --replay-buffer)
+replay_buffer = 0
+
+# Split the recording into numbered files (clip-0001.mp4, clip-0002.mp4, ...)
+# after this long or this many MB, whichever comes first. Each file starts on
+# a keyframe and plays on its own (or pass --segment-duration/--segment-size)
+# segment_duration = "10m"
+segment_size = 0
+
+[recording.metadata]
+# Tag recordings with their creation time, capture source, codec/preset and
+# Nitrogen version (MP4 udta atoms, Matroska tags)
+enabled = true
+
+# Title tag (or pass --title)
+# title = "Friday raid"
+
+[layout]
+# Composite several sources into one output (monitors share one portal dialog,
+# windows open one each).
+# Leave empty to capture a single source.
+# Tile sources in a grid, e.g. two windows side by side:
+#   sources = ["window", "window"]
+sources = []
+
+# Or place each layer explicitly (higher z is drawn on top):
+# [[layout.layers]]
+# source = "monitor:DP-2"
+# x = 0
+# y = 0
+# width = 1920
+# height = 1080
+#
+# [[layout.layers]]
+# source = "window"
+# x = 1440
+# y = 810
+# width = 480
+# height = 270
+# z = 1
+"##
     .to_string()
 }
 
@@ -781,4 +2305,15 @@ mod tests {
         let config: ConfigFile = toml::from_str(&sample).unwrap();
         assert_eq!(config.defaults.preset, "1080p60");
     }
+
+    #[test]
+    fn test_setting_line() {
+        let content = "[defaults]\ncodec = \"h264\"\n\n[encoder]\n# tune = \"hq\"\ntune = \"fast\"\n\n[audio.record]\ncodec = \"mp3\"\n";
+
+        assert_eq!(setting_line(content, "defaults.codec"), Some(2));
+        // Commented-out settings are skipped
+        assert_eq!(setting_line(content, "encoder.tune"), Some(6));
+        assert_eq!(setting_line(content, "audio.record.codec"), Some(9));
+        assert_eq!(setting_line(content, "encoder.preset"), None);
+    }
 }
@@ -4,18 +4,39 @@
 
 mod av1;
 mod file;
+mod quality;
+mod reload;
 
-pub use av1::{Av1Config, Av1Tier, Av1Tune, ChromaFormat, MultipassMode};
+pub use av1::{Av1Config, Av1Tier, Av1Tune, ChromaFormat};
+pub use quality::{EncoderQuality, MultipassMode, Tune, MAX_ASYNC_DEPTH, MAX_LOOKAHEAD_DEPTH};
+pub use reload::{LiveChange, ReloadPlan};
 pub use file::{
-    sample_config, ConfigFile, DetectionSettings, HdrSettings, HotkeySettings,
-    OverlaySettings, PerformanceSettings, WebRTCSettings,
+    sample_config, setting_line, CaptureSettings, ConfigFile, CpuAffinitySettings, DashSettings,
+    DetectionSettings, DuckingSettings, HdrSettings, HlsSettings, HotkeySettings, LayerSettings,
+    LayoutSettings, MicSettings, OutputAudioSettings, OverlaySettings, PerformanceSettings,
+    WatermarkSettings, WebRTCSettings,
 };
 
-use crate::encode::{FrameGenMode, TonemapAlgorithm, TonemapMode};
+use crate::affinity::{self, CpuAffinity};
+use crate::capture::{
+    DuckingMode, LayoutLayer, LimiterConfig, LimiterMode, DEFAULT_CURSOR_SCALE, MAX_CURSOR_SCALE,
+    DEFAULT_PORTAL_ATTEMPTS, MAX_LATENCY_QUANTUM,
+};
+use crate::encode::{
+    BarColor, FitMode, FrameGenMode, RateControl, Timecode, TonemapAlgorithm, TonemapMode,
+};
+use crate::output::{
+    default_ice_servers, ContainerFormat, DashConfig, HlsConfig, HlsSegmentFormat, IceServer,
+    RecordingMetadata, RistOptions, SegmentConfig, SimulcastLayer, SrtOptions, StreamProtocol,
+    DEFAULT_CAMERA_TRANSITION_MS, MAX_AV_SYNC_OFFSET_MS,
+};
 use crate::overlay::OverlayPosition;
-use crate::types::CaptureSource;
+use crate::performance::MetricsMode;
+use crate::types::{CaptureSource, HdrMetadata};
+use crate::watermark::WatermarkConfig;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Discord streaming constraints and recommended settings
 pub mod discord {
@@ -35,6 +56,17 @@ pub mod discord {
     pub const PREFERRED_CODEC: &str = "h264";
 }
 
+/// Defaults for file recordings (see [`CaptureConfig::with_recording_profile`])
+pub mod recording {
+    /// NVENC constant-quality level (1-51, lower is better)
+    pub const CONSTANT_QUALITY: u8 = 23;
+    /// Keyframe spacing in seconds, so recordings seek quickly
+    pub const KEYFRAME_SECONDS: u32 = 2;
+}
+
+/// Highest H.264/HEVC constant-quality level (AV1 goes to 63)
+pub const MAX_CONSTANT_QUALITY: u8 = 51;
+
 /// Video codec for encoding
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -66,6 +98,16 @@ impl Codec {
             Self::Av1 => "AV1",
         }
     }
+
+    /// Maximum number of temporal (SVC) layers NVENC supports for the codec
+    ///
+    /// 0 means temporal scalability isn't available.
+    pub fn max_temporal_layers(&self) -> u8 {
+        match self {
+            Self::H264 => 4,
+            Self::Hevc | Self::Av1 => 0,
+        }
+    }
 }
 
 impl std::fmt::Display for Codec {
@@ -96,13 +138,155 @@ pub enum CursorMode {
     /// Show cursor embedded in capture frames
     #[default]
     Embedded,
-    /// Cursor metadata only (compositor-dependent)
+    /// Draw the cursor from portal metadata (falls back to embedded if unsupported)
     Metadata,
 }
 
-/// Audio capture source
+impl std::fmt::Display for CursorMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hidden => write!(f, "hidden"),
+            Self::Embedded => write!(f, "embedded"),
+            Self::Metadata => write!(f, "metadata"),
+        }
+    }
+}
+
+impl std::str::FromStr for CursorMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hidden" | "none" | "off" => Ok(Self::Hidden),
+            "embedded" | "on" => Ok(Self::Embedded),
+            "metadata" | "overlay" => Ok(Self::Metadata),
+            _ => Err(format!("Unknown cursor mode: {}", s)),
+        }
+    }
+}
+
+/// Pixel format the virtual camera is offered in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CameraFormat {
+    /// Packed BGRA, which most applications accept (and the only one with alpha)
+    #[default]
+    Bgra,
+    /// NV12 (YUV 4:2:0), for applications that only take YUV from a camera
+    Nv12,
+}
+
+impl std::fmt::Display for CameraFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bgra => write!(f, "bgra"),
+            Self::Nv12 => write!(f, "nv12"),
+        }
+    }
+}
+
+impl std::str::FromStr for CameraFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bgra" | "rgb" => Ok(Self::Bgra),
+            "nv12" | "yuv" | "yuv420" => Ok(Self::Nv12),
+            _ => Err(format!("Unknown camera format: {}", s)),
+        }
+    }
+}
+
+/// Video color range signalled in the encoded stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorRange {
+    /// Limited/TV range (16-235), the broadcast and NVENC default
+    Limited,
+    /// Full/PC range (0-255), matches desktop RGB capture
+    #[default]
+    Full,
+}
+
+impl ColorRange {
+    /// Returns ffmpeg `color_range` option string
+    pub fn ffmpeg_value(&self) -> &'static str {
+        match self {
+            Self::Limited => "tv",
+            Self::Full => "pc",
+        }
+    }
+
+    /// Pick the range that preserves the source's levels
+    ///
+    /// Desktop capture (monitors and windows) delivers full-range RGB.
+    pub fn for_source(source: &CaptureSource) -> Self {
+        match source {
+            CaptureSource::Monitor { .. } | CaptureSource::Window { .. } => Self::Full,
+        }
+    }
+}
+
+impl std::fmt::Display for ColorRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Limited => write!(f, "limited"),
+            Self::Full => write!(f, "full"),
+        }
+    }
+}
+
+impl std::str::FromStr for ColorRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "limited" | "tv" | "mpeg" => Ok(Self::Limited),
+            "full" | "pc" | "jpeg" => Ok(Self::Full),
+            _ => Err(format!("Unknown color range: {}", s)),
+        }
+    }
+}
+
+/// What kind of content is being captured
+///
+/// `Static` targets slides and documents over slow links: frames that
+/// barely differ from the last encoded one are skipped, and a periodic
+/// refresh with a forced IDR lets late joiners sync.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
+pub enum ContentMode {
+    /// Encode every frame (games, video)
+    #[default]
+    Motion,
+    /// Encode only on change or periodic refresh (slides, docs)
+    Static,
+}
+
+impl std::fmt::Display for ContentMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Motion => write!(f, "motion"),
+            Self::Static => write!(f, "static"),
+        }
+    }
+}
+
+impl std::str::FromStr for ContentMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "motion" => Ok(Self::Motion),
+            "static" | "slideshow" | "slides" => Ok(Self::Static),
+            _ => Err(format!("Unknown content mode: {}", s)),
+        }
+    }
+}
+
+/// Audio capture source
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
 pub enum AudioSource {
     /// No audio capture
     #[default]
@@ -113,6 +297,8 @@ pub enum AudioSource {
     Microphone,
     /// Capture both desktop and microphone
     Both,
+    /// Capture one application's output (application name, binary or node id)
+    Application(String),
 }
 
 impl std::fmt::Display for AudioSource {
@@ -122,6 +308,71 @@ impl std::fmt::Display for AudioSource {
             Self::Desktop => write!(f, "desktop"),
             Self::Microphone => write!(f, "microphone"),
             Self::Both => write!(f, "both"),
+            Self::Application(app) => write!(f, "app:{}", app),
+        }
+    }
+}
+
+impl std::str::FromStr for AudioSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((prefix, app)) = s.split_once(':') {
+            if matches!(prefix.to_lowercase().as_str(), "app" | "application") {
+                let app = app.trim();
+                if app.is_empty() {
+                    return Err(format!("Missing application name in audio source: {}", s));
+                }
+                return Ok(Self::Application(app.to_string()));
+            }
+        }
+
+        match s.to_lowercase().as_str() {
+            "none" | "off" => Ok(Self::None),
+            "desktop" => Ok(Self::Desktop),
+            "mic" | "microphone" => Ok(Self::Microphone),
+            "both" => Ok(Self::Both),
+            _ => Err(format!("Unknown audio source: {}", s)),
+        }
+    }
+}
+
+/// Microphone channel handling applied before volume and mixing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MicChannel {
+    /// Use only the left input channel
+    Left,
+    /// Use only the right input channel
+    Right,
+    /// Average all input channels
+    Mono,
+    /// Pass channels through unchanged
+    #[default]
+    Stereo,
+}
+
+impl std::fmt::Display for MicChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Left => write!(f, "left"),
+            Self::Right => write!(f, "right"),
+            Self::Mono => write!(f, "mono"),
+            Self::Stereo => write!(f, "stereo"),
+        }
+    }
+}
+
+impl std::str::FromStr for MicChannel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "left" | "l" => Ok(Self::Left),
+            "right" | "r" => Ok(Self::Right),
+            "mono" | "downmix" => Ok(Self::Mono),
+            "stereo" => Ok(Self::Stereo),
+            _ => Err(format!("Unknown mic channel: {}", s)),
         }
     }
 }
@@ -169,6 +420,48 @@ impl std::fmt::Display for AudioCodec {
     }
 }
 
+impl std::str::FromStr for AudioCodec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "aac" => Ok(Self::Aac),
+            "opus" => Ok(Self::Opus),
+            "copy" => Ok(Self::Copy),
+            _ => Err(format!("Unknown audio codec: {}", s)),
+        }
+    }
+}
+
+/// Audio encoding for a single output
+///
+/// Lets the recording and the stream use different codecs (e.g. Opus for the
+/// archive, AAC for Twitch) from the same captured audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OutputAudio {
+    /// Audio codec
+    pub codec: AudioCodec,
+    /// Bitrate in kbps (0 = codec default)
+    #[serde(default)]
+    pub bitrate: u32,
+}
+
+impl OutputAudio {
+    /// Create an output audio setting
+    pub fn new(codec: AudioCodec, bitrate: u32) -> Self {
+        Self { codec, bitrate }
+    }
+
+    /// Bitrate in kbps, falling back to the codec default
+    pub fn effective_bitrate(&self) -> u32 {
+        if self.bitrate > 0 {
+            self.bitrate
+        } else {
+            self.codec.default_bitrate()
+        }
+    }
+}
+
 /// Encoder quality preset
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -196,6 +489,20 @@ impl EncoderPreset {
     }
 }
 
+impl std::str::FromStr for EncoderPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fast" => Ok(Self::Fast),
+            "medium" => Ok(Self::Medium),
+            "slow" => Ok(Self::Slow),
+            "quality" => Ok(Self::Quality),
+            _ => Err(format!("Unknown encoder preset: {}", s)),
+        }
+    }
+}
+
 /// Output resolution/framerate preset
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -240,6 +547,24 @@ pub enum Preset {
 }
 
 impl Preset {
+    /// Every named preset, in ascending resolution and framerate order
+    ///
+    /// Excludes [`Preset::Custom`]; useful for populating preset pickers.
+    pub fn all_named() -> &'static [Preset] {
+        &[
+            Self::P720_30,
+            Self::P720_60,
+            Self::P1080_30,
+            Self::P1080_60,
+            Self::P1440_30,
+            Self::P1440_60,
+            Self::P1440_120,
+            Self::P4k30,
+            Self::P4k60,
+            Self::P4k120,
+        ]
+    }
+
     /// Get width in pixels
     pub fn width(&self) -> u32 {
         match self {
@@ -338,11 +663,165 @@ impl std::str::FromStr for Preset {
             "4k30" | "2160p30" => Ok(Self::P4k30),
             "4k60" | "2160p60" => Ok(Self::P4k60),
             "4k120" | "2160p120" => Ok(Self::P4k120),
+            other if other.contains('x') || other.contains('@') => parse_custom_preset(other)
+                .map_err(|e| format!("Invalid custom preset '{}': {}", s, e)),
             _ => Err(format!("Unknown preset: {}", s)),
         }
     }
 }
 
+/// Maximum supported output width
+pub const MAX_WIDTH: u32 = 7680;
+
+/// Maximum supported output height
+pub const MAX_HEIGHT: u32 = 4320;
+
+/// Maximum supported output framerate
+pub const MAX_FPS: u32 = 240;
+
+/// Recording extensions accepted in audio-only mode
+pub const AUDIO_ONLY_EXTENSIONS: &[&str] = &["m4a", "mka", "opus", "ogg"];
+
+/// Replay buffer size past which validation warns about memory use
+const REPLAY_BUFFER_WARN_MB: u64 = 1024;
+
+/// Parse a lowercased `WxH@FPS` (optionally `WxH@FPSfps`) preset string
+fn parse_custom_preset(s: &str) -> Result<Preset, String> {
+    let (resolution, fps) = s
+        .split_once('@')
+        .ok_or_else(|| "expected WxH@FPS".to_string())?;
+    let (width, height) = resolution
+        .split_once('x')
+        .ok_or_else(|| "expected WxH@FPS".to_string())?;
+    let fps = fps.strip_suffix("fps").unwrap_or(fps);
+
+    let parse = |value: &str, what: &str| {
+        value
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("{} '{}' is not a number", what, value.trim()))
+    };
+    let width = parse(width, "width")?;
+    let height = parse(height, "height")?;
+    let fps = parse(fps, "framerate")?;
+
+    if width == 0 || height == 0 {
+        return Err("resolution cannot be zero".to_string());
+    }
+    if fps == 0 {
+        return Err("framerate cannot be zero".to_string());
+    }
+    if width > MAX_WIDTH || height > MAX_HEIGHT {
+        return Err(format!(
+            "resolution {}x{} exceeds maximum supported ({}x{})",
+            width, height, MAX_WIDTH, MAX_HEIGHT
+        ));
+    }
+    if fps > MAX_FPS {
+        return Err(format!(
+            "framerate {} exceeds maximum supported ({})",
+            fps, MAX_FPS
+        ));
+    }
+
+    Ok(Preset::Custom { width, height, fps })
+}
+
+/// Parse a human duration such as `30s`, `5m`, `1h30m`, `90` (seconds),
+/// `05:00` (MM:SS) or `00:05:00` (HH:MM:SS)
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err("empty duration".to_string());
+    }
+
+    let seconds = if s.contains(':') {
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() > 3 {
+            return Err(format!("'{}' is not HH:MM:SS", s));
+        }
+        let mut total = 0u64;
+        for (i, part) in parts.iter().enumerate() {
+            let value: u64 = part
+                .parse()
+                .map_err(|_| format!("invalid number '{}' in '{}'", part, s))?;
+            // Minutes and seconds fields after the first must be below 60
+            if i > 0 && value >= 60 {
+                return Err(format!("'{}' has a field of 60 or more", s));
+            }
+            total = total * 60 + value;
+        }
+        total
+    } else {
+        let mut total = 0u64;
+        let mut digits = String::new();
+        for c in s.chars() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                continue;
+            }
+            let unit = match c.to_ascii_lowercase() {
+                's' => 1,
+                'm' => 60,
+                'h' => 3600,
+                _ => return Err(format!("unknown unit '{}' in '{}' (use s, m or h)", c, s)),
+            };
+            let value: u64 = digits
+                .parse()
+                .map_err(|_| format!("missing number before '{}' in '{}'", c, s))?;
+            total += value * unit;
+            digits.clear();
+        }
+        // Trailing bare number counts as seconds
+        if !digits.is_empty() {
+            total += digits.parse::<u64>().map_err(|e| e.to_string())?;
+        }
+        total
+    };
+
+    if seconds == 0 {
+        return Err("duration must be greater than zero".to_string());
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Parse a bitrate such as `6M`, `6mbps`, `6000k` or `6000` into kbps
+///
+/// Bare numbers are kbps. Units are case-insensitive and may follow a
+/// space; `M` values may be fractional (`2.5M`).
+pub fn parse_bitrate(s: &str) -> crate::error::Result<u32> {
+    let invalid = |reason: &str| {
+        crate::error::NitrogenError::config(format!("Invalid bitrate '{}': {}", s, reason))
+    };
+
+    let trimmed = s.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    if number.is_empty() {
+        return Err(invalid("expected a number"));
+    }
+
+    let scale = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "k" | "kb" | "kbps" | "kbit" => 1.0,
+        "m" | "mb" | "mbps" | "mbit" => 1000.0,
+        other => {
+            return Err(invalid(&format!("unknown unit '{}' (use k or M)", other)));
+        }
+    };
+    let value: f64 = number.parse().map_err(|_| invalid("expected a number"))?;
+    let kbps = value * scale;
+    if kbps > u32::MAX as f64 {
+        return Err(invalid("too large"));
+    }
+    // Allow for float error in products like 1.1 * 1000
+    if (kbps - kbps.round()).abs() > 1e-6 {
+        return Err(invalid("must be a whole number of kbps"));
+    }
+    Ok(kbps.round() as u32)
+}
+
 /// Complete capture configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CaptureConfig {
@@ -354,20 +833,56 @@ pub struct CaptureConfig {
     pub codec: Codec,
     /// Bitrate in kbps (0 = auto)
     pub bitrate: u32,
+    /// Peak bitrate in kbps for VBR (None = 1.5x the bitrate)
+    #[serde(default)]
+    pub max_bitrate: Option<u32>,
+    /// VBV (decoder buffer) size in kbits (None = one second at the bitrate)
+    #[serde(default)]
+    pub vbv_buffer_size: Option<u32>,
     /// Encoder quality preset
     pub encoder_preset: EncoderPreset,
     /// Virtual camera name
     pub camera_name: String,
     /// Enable low-latency mode
     pub low_latency: bool,
+    /// NVENC tuning; an explicit tune overrides `low_latency`
+    #[serde(default)]
+    pub tune: Tune,
     /// GPU index for encoding
     pub gpu: u32,
+    /// Concurrent encoder sessions allowed (None = detect from the GPU and driver)
+    #[serde(default)]
+    pub max_encoder_sessions: Option<u32>,
+    /// Temporal SVC layers (hierarchical P-frames); 0 or 1 = single layer
+    #[serde(default)]
+    pub temporal_layers: u8,
+    /// Refresh the picture gradually with intra rows instead of periodic IDRs
+    #[serde(default)]
+    pub intra_refresh: bool,
+    /// Frames per intra-refresh cycle (None = one second)
+    #[serde(default)]
+    pub intra_refresh_period: Option<u32>,
+    /// Rate control mode (None = CBR in low-latency mode, VBR otherwise)
+    #[serde(default)]
+    pub rate_control: Option<RateControl>,
+    /// Frames between keyframes (None = the encoder's default, or `av1.gop`)
+    #[serde(default)]
+    pub keyframe_interval: Option<u32>,
     /// Optional recording file path
     pub record_path: Option<std::path::PathBuf>,
+    /// Record audio only, skipping video capture and the encoder
+    #[serde(default)]
+    pub audio_only: bool,
     /// Cursor capture mode
     pub cursor_mode: CursorMode,
+    /// Size of the cursor drawn in metadata mode, relative to the compositor's bitmap
+    #[serde(default = "default_cursor_scale")]
+    pub cursor_scale: f32,
     /// Audio capture source
     pub audio_source: AudioSource,
+    /// Lookahead and adaptive quantization (all codecs)
+    #[serde(default)]
+    pub quality: EncoderQuality,
     /// AV1-specific configuration
     pub av1: Av1Config,
     /// Audio codec
@@ -380,12 +895,19 @@ pub struct CaptureConfig {
     /// HDR tonemapping mode (auto, on, off)
     #[serde(default)]
     pub hdr_tonemap: TonemapMode,
-    /// HDR tonemapping algorithm (reinhard, aces, hable)
+    /// HDR tonemapping algorithm (reinhard, aces, hable, bt2390)
     #[serde(default)]
     pub hdr_algorithm: TonemapAlgorithm,
     /// HDR peak luminance override (nits), used when metadata unavailable
     #[serde(default = "default_hdr_peak_luminance")]
     pub hdr_peak_luminance: u32,
+    /// Scale tonemapping for each frame's measured luminance
+    #[serde(default)]
+    pub hdr_dynamic: bool,
+    /// Treat captured frames as this HDR signal instead of what the
+    /// compositor negotiates (None = trust the stream)
+    #[serde(default)]
+    pub hdr_input: Option<HdrMetadata>,
     /// Enable virtual camera output
     #[serde(default = "default_camera_enabled")]
     pub camera_enabled: bool,
@@ -395,15 +917,54 @@ pub struct CaptureConfig {
     /// Overlay position
     #[serde(default)]
     pub overlay_position: OverlayPosition,
-    /// RTMP/SRT stream URL (optional)
+    /// Custom overlay text with placeholders (None = stats line)
+    #[serde(default)]
+    pub overlay_template: Option<String>,
+    /// Value of the overlay `{title}` placeholder
+    #[serde(default)]
+    pub overlay_title: String,
+    /// Burn a SMPTE timecode counting from this start (None = off)
+    #[serde(default)]
+    pub overlay_timecode: Option<Timecode>,
+    /// Also write the timecode into the bitstream (HEVC SEI / AV1 OBU)
+    #[serde(default)]
+    pub timecode_metadata: bool,
+    /// Logo blended into every frame before encoding (None = off)
+    #[serde(default)]
+    pub watermark: Option<WatermarkConfig>,
+    /// RTMP/SRT/RIST stream URL (optional)
     #[serde(default)]
     pub stream_url: Option<String>,
+    /// More destinations sent the same stream as `stream_url`
+    #[serde(default)]
+    pub stream_mirrors: Vec<String>,
+    /// Buffer and encryption for rist:// streams
+    #[serde(default)]
+    pub stream_rist: RistOptions,
+    /// Latency and encryption for srt:// streams
+    #[serde(default)]
+    pub stream_srt: SrtOptions,
     /// Enable WebRTC output for browser-based viewing
     #[serde(default)]
     pub webrtc_enabled: bool,
     /// WebRTC local signaling server port
     #[serde(default = "default_webrtc_port")]
     pub webrtc_port: u16,
+    /// WebRTC video codec, which must be what the encoder produces
+    #[serde(default = "default_webrtc_video_codec")]
+    pub webrtc_video_codec: String,
+    /// WebRTC simulcast layers, highest first (empty = single bitrate)
+    #[serde(default)]
+    pub webrtc_simulcast: Vec<SimulcastLayer>,
+    /// STUN/TURN servers for WebRTC peers
+    #[serde(default = "default_ice_servers")]
+    pub webrtc_ice_servers: Vec<IceServer>,
+    /// HLS playlist output (None = disabled)
+    #[serde(default)]
+    pub hls: Option<HlsConfig>,
+    /// DASH manifest output (None = disabled)
+    #[serde(default)]
+    pub dash: Option<DashConfig>,
     /// Desktop audio volume (0.0 - 2.0, 1.0 = normal)
     #[serde(default = "default_volume")]
     pub desktop_volume: f32,
@@ -413,6 +974,109 @@ pub struct CaptureConfig {
     /// Enable audio ducking (reduce desktop when mic active)
     #[serde(default)]
     pub audio_ducking: bool,
+    /// What mic activity triggers ducking (rms, vad)
+    #[serde(default)]
+    pub audio_ducking_mode: DuckingMode,
+    /// Reconnect capture if no frame arrives within this many ms (0 = disabled)
+    #[serde(default = "default_frame_timeout_ms")]
+    pub frame_timeout_ms: u64,
+    /// Attempts at starting the portal session on transient D-Bus errors
+    #[serde(default = "default_portal_attempts")]
+    pub portal_attempts: u32,
+    /// PipeWire quantum requested for the video stream (None = server default)
+    #[serde(default)]
+    pub capture_latency_quantum: Option<u32>,
+    /// PipeWire quantum requested for audio capture (None = server default)
+    #[serde(default)]
+    pub audio_latency_quantum: Option<u32>,
+    /// Color range of the encoded output
+    #[serde(default)]
+    pub color_range: ColorRange,
+    /// Microphone channel handling (left, right, mono, stereo)
+    #[serde(default)]
+    pub mic_channel: MicChannel,
+    /// Clipping stage for mixed audio (hard clamp, tanh or true-peak limiter)
+    #[serde(default)]
+    pub audio_limiter: LimiterConfig,
+    /// A/V sync offset in ms for recordings and streams (positive delays audio)
+    #[serde(default)]
+    pub av_sync_offset_ms: i32,
+    /// Keep the alpha channel on the virtual camera (recording/streams stay opaque)
+    #[serde(default)]
+    pub camera_alpha: bool,
+    /// Pixel format of the virtual camera
+    #[serde(default)]
+    pub camera_format: CameraFormat,
+    /// Push a full frame as soon as an application opens the camera
+    #[serde(default = "default_camera_refresh_on_connect")]
+    pub camera_refresh_on_connect: bool,
+    /// Crossfade in ms when the camera is paused or resumed (0 = hard cut)
+    #[serde(default = "default_camera_transition_ms")]
+    pub camera_transition_ms: u32,
+    /// Image the paused camera shows, scaled to the output (None = black)
+    #[serde(default)]
+    pub camera_placeholder: Option<PathBuf>,
+    /// How a source with a different aspect ratio is fitted into the preset
+    #[serde(default)]
+    pub fit: FitMode,
+    /// Letterbox/pillarbox bar color
+    #[serde(default)]
+    pub fit_color: BarColor,
+    /// Averaging strategy for latency/FPS metrics (rolling window or EMA)
+    #[serde(default)]
+    pub metrics_mode: MetricsMode,
+    /// Stop the session automatically after this much wall-clock time
+    #[serde(default)]
+    pub max_duration: Option<Duration>,
+    /// Feed DMA-BUF frames to NVENC through CUDA when the system supports it
+    #[serde(default = "default_zero_copy")]
+    pub zero_copy: bool,
+    /// Encode every frame, or only changes for mostly static content
+    #[serde(default)]
+    pub content_mode: ContentMode,
+    /// Sources composited into the output (empty = capture `source` alone)
+    #[serde(default)]
+    pub layout: Vec<LayoutLayer>,
+    /// Audio encoding for the recording (None = `audio_codec`/`audio_bitrate`)
+    #[serde(default)]
+    pub record_audio: Option<OutputAudio>,
+    /// Audio encoding for the RTMP/SRT stream (None = `audio_codec`/`audio_bitrate`)
+    #[serde(default)]
+    pub stream_audio: Option<OutputAudio>,
+    /// Resolution the recording is encoded at (None = `preset`)
+    #[serde(default)]
+    pub record_preset: Option<Preset>,
+    /// Container tags written to the recording
+    #[serde(default)]
+    pub record_metadata: RecordingMetadata,
+    /// Container of the recording (None = from the file extension)
+    #[serde(default)]
+    pub record_container: Option<ContainerFormat>,
+    /// Seconds of output kept in memory for saving replays (None = off)
+    #[serde(default)]
+    pub replay_buffer_secs: Option<u32>,
+    /// Split the recording into numbered files (default = a single file)
+    #[serde(default)]
+    pub record_segments: SegmentConfig,
+    /// Resolution the RTMP/SRT stream is encoded at (None = `preset`)
+    #[serde(default)]
+    pub stream_preset: Option<Preset>,
+    /// Trace-log a sampled hash of each frame at capture and output
+    #[serde(default)]
+    pub frame_hashing: bool,
+    /// Cores the capture, encode, audio and hotkey threads are pinned to
+    #[serde(default)]
+    pub cpu_affinity: CpuAffinity,
+    /// Log performance stats every this many seconds (0 = off)
+    #[serde(default)]
+    pub stats_interval_secs: u32,
+    /// Include GPU temperature, power and utilization in the stats log
+    #[serde(default = "default_gpu_monitoring")]
+    pub gpu_monitoring: bool,
+}
+
+fn default_gpu_monitoring() -> bool {
+    true
 }
 
 fn default_volume() -> f32 {
@@ -423,6 +1087,14 @@ fn default_camera_enabled() -> bool {
     true
 }
 
+fn default_zero_copy() -> bool {
+    true
+}
+
+fn default_cursor_scale() -> f32 {
+    DEFAULT_CURSOR_SCALE
+}
+
 fn default_camera_name() -> String {
     "Nitrogen Camera".to_string()
 }
@@ -431,25 +1103,59 @@ fn default_hdr_peak_luminance() -> u32 {
     1000
 }
 
+fn default_camera_refresh_on_connect() -> bool {
+    true
+}
+
+fn default_camera_transition_ms() -> u32 {
+    DEFAULT_CAMERA_TRANSITION_MS
+}
+
 fn default_webrtc_port() -> u16 {
     9000
 }
 
+fn default_webrtc_video_codec() -> String {
+    "h264".to_string()
+}
+
+fn default_frame_timeout_ms() -> u64 {
+    2000
+}
+
+fn default_portal_attempts() -> u32 {
+    DEFAULT_PORTAL_ATTEMPTS
+}
+
 impl CaptureConfig {
     /// Create a new config for monitor capture
     pub fn monitor(id: impl Into<String>) -> Self {
+        let source = CaptureSource::monitor(id);
+        let color_range = ColorRange::for_source(&source);
         Self {
-            source: CaptureSource::monitor(id),
+            source,
             preset: Preset::default(),
             codec: Codec::default(),
             bitrate: 0,
+            max_bitrate: None,
+            vbv_buffer_size: None,
             encoder_preset: EncoderPreset::default(),
             camera_name: default_camera_name(),
             low_latency: true,
+            tune: Tune::default(),
             gpu: 0,
+            max_encoder_sessions: None,
+            temporal_layers: 0,
+            intra_refresh: false,
+            intra_refresh_period: None,
+            rate_control: None,
+            keyframe_interval: None,
             record_path: None,
+            audio_only: false,
             cursor_mode: CursorMode::default(),
+            cursor_scale: default_cursor_scale(),
             audio_source: AudioSource::default(),
+            quality: EncoderQuality::default(),
             av1: Av1Config::default(),
             audio_codec: AudioCodec::default(),
             audio_bitrate: 0,
@@ -457,32 +1163,94 @@ impl CaptureConfig {
             hdr_tonemap: TonemapMode::default(),
             hdr_algorithm: TonemapAlgorithm::default(),
             hdr_peak_luminance: default_hdr_peak_luminance(),
+            hdr_dynamic: false,
+            hdr_input: None,
             camera_enabled: default_camera_enabled(),
             overlay_enabled: false,
             overlay_position: OverlayPosition::default(),
+            overlay_template: None,
+            overlay_title: String::new(),
+            overlay_timecode: None,
+            timecode_metadata: false,
+            watermark: None,
             stream_url: None,
+            stream_mirrors: Vec::new(),
+            stream_rist: RistOptions::default(),
+            stream_srt: SrtOptions::default(),
             webrtc_enabled: false,
             webrtc_port: default_webrtc_port(),
+            webrtc_video_codec: default_webrtc_video_codec(),
+            webrtc_simulcast: Vec::new(),
+            webrtc_ice_servers: default_ice_servers(),
+            hls: None,
+            dash: None,
             desktop_volume: default_volume(),
             mic_volume: default_volume(),
             audio_ducking: false,
+            audio_ducking_mode: DuckingMode::default(),
+            frame_timeout_ms: default_frame_timeout_ms(),
+            portal_attempts: default_portal_attempts(),
+            capture_latency_quantum: None,
+            audio_latency_quantum: None,
+            color_range,
+            mic_channel: MicChannel::default(),
+            audio_limiter: LimiterConfig::default(),
+            av_sync_offset_ms: 0,
+            camera_alpha: false,
+            camera_format: CameraFormat::Bgra,
+            camera_refresh_on_connect: true,
+            camera_transition_ms: default_camera_transition_ms(),
+            camera_placeholder: None,
+            fit: FitMode::default(),
+            fit_color: BarColor::BLACK,
+            metrics_mode: MetricsMode::default(),
+            max_duration: None,
+            zero_copy: default_zero_copy(),
+            content_mode: ContentMode::default(),
+            layout: Vec::new(),
+            record_audio: None,
+            stream_audio: None,
+            record_preset: None,
+            record_metadata: RecordingMetadata::default(),
+            record_container: None,
+            replay_buffer_secs: None,
+            record_segments: SegmentConfig::default(),
+            stream_preset: None,
+            frame_hashing: false,
+            cpu_affinity: CpuAffinity::default(),
+            stats_interval_secs: 0,
+            gpu_monitoring: default_gpu_monitoring(),
         }
     }
 
     /// Create a new config for window capture
     pub fn window(id: impl Into<String>) -> Self {
+        let source = CaptureSource::window(id);
+        let color_range = ColorRange::for_source(&source);
         Self {
-            source: CaptureSource::window(id),
+            source,
             preset: Preset::default(),
             codec: Codec::default(),
             bitrate: 0,
+            max_bitrate: None,
+            vbv_buffer_size: None,
             encoder_preset: EncoderPreset::default(),
             camera_name: default_camera_name(),
             low_latency: true,
+            tune: Tune::default(),
             gpu: 0,
+            max_encoder_sessions: None,
+            temporal_layers: 0,
+            intra_refresh: false,
+            intra_refresh_period: None,
+            rate_control: None,
+            keyframe_interval: None,
             record_path: None,
+            audio_only: false,
             cursor_mode: CursorMode::default(),
+            cursor_scale: default_cursor_scale(),
             audio_source: AudioSource::default(),
+            quality: EncoderQuality::default(),
             av1: Av1Config::default(),
             audio_codec: AudioCodec::default(),
             audio_bitrate: 0,
@@ -490,15 +1258,63 @@ impl CaptureConfig {
             hdr_tonemap: TonemapMode::default(),
             hdr_algorithm: TonemapAlgorithm::default(),
             hdr_peak_luminance: default_hdr_peak_luminance(),
+            hdr_dynamic: false,
+            hdr_input: None,
             camera_enabled: default_camera_enabled(),
             overlay_enabled: false,
             overlay_position: OverlayPosition::default(),
+            overlay_template: None,
+            overlay_title: String::new(),
+            overlay_timecode: None,
+            timecode_metadata: false,
+            watermark: None,
             stream_url: None,
+            stream_mirrors: Vec::new(),
+            stream_rist: RistOptions::default(),
+            stream_srt: SrtOptions::default(),
             webrtc_enabled: false,
             webrtc_port: default_webrtc_port(),
+            webrtc_video_codec: default_webrtc_video_codec(),
+            webrtc_simulcast: Vec::new(),
+            webrtc_ice_servers: default_ice_servers(),
+            hls: None,
+            dash: None,
             desktop_volume: default_volume(),
             mic_volume: default_volume(),
             audio_ducking: false,
+            audio_ducking_mode: DuckingMode::default(),
+            frame_timeout_ms: default_frame_timeout_ms(),
+            portal_attempts: default_portal_attempts(),
+            capture_latency_quantum: None,
+            audio_latency_quantum: None,
+            color_range,
+            mic_channel: MicChannel::default(),
+            audio_limiter: LimiterConfig::default(),
+            av_sync_offset_ms: 0,
+            camera_alpha: false,
+            camera_format: CameraFormat::Bgra,
+            camera_refresh_on_connect: true,
+            camera_transition_ms: default_camera_transition_ms(),
+            camera_placeholder: None,
+            fit: FitMode::default(),
+            fit_color: BarColor::BLACK,
+            metrics_mode: MetricsMode::default(),
+            max_duration: None,
+            zero_copy: default_zero_copy(),
+            content_mode: ContentMode::default(),
+            layout: Vec::new(),
+            record_audio: None,
+            stream_audio: None,
+            record_preset: None,
+            record_metadata: RecordingMetadata::default(),
+            record_container: None,
+            replay_buffer_secs: None,
+            record_segments: SegmentConfig::default(),
+            stream_preset: None,
+            frame_hashing: false,
+            cpu_affinity: CpuAffinity::default(),
+            stats_interval_secs: 0,
+            gpu_monitoring: default_gpu_monitoring(),
         }
     }
 
@@ -508,13 +1324,82 @@ impl CaptureConfig {
         self
     }
 
-    /// Set the output preset
-    pub fn with_preset(mut self, preset: Preset) -> Self {
-        self.preset = preset;
+    /// Override the detected concurrent encoder session limit
+    pub fn with_max_encoder_sessions(mut self, limit: u32) -> Self {
+        self.max_encoder_sessions = Some(limit);
         self
     }
 
-    /// Set the codec
+    /// Set the number of temporal SVC layers (H.264 only)
+    pub fn with_temporal_layers(mut self, layers: u8) -> Self {
+        self.temporal_layers = layers;
+        self
+    }
+
+    /// Use intra-refresh instead of periodic IDRs (None = one-second cycle)
+    pub fn with_intra_refresh(mut self, period: Option<u32>) -> Self {
+        self.intra_refresh = true;
+        self.intra_refresh_period = period;
+        self
+    }
+
+    /// Pick the rate control mode instead of leaving it to the tune
+    pub fn with_rate_control(mut self, rate_control: RateControl) -> Self {
+        self.rate_control = Some(rate_control);
+        self
+    }
+
+    /// Encode towards a constant quality level instead of the bitrate
+    ///
+    /// Shorthand for [`RateControl::ConstQuality`]; the bitrate is the ceiling.
+    pub fn with_constant_quality(self, cq: u8) -> Self {
+        self.with_rate_control(RateControl::ConstQuality { cq })
+    }
+
+    /// Constant-quality level, if that is the rate control mode
+    pub fn constant_quality(&self) -> Option<u8> {
+        match self.rate_control {
+            Some(RateControl::ConstQuality { cq }) => Some(cq),
+            _ => None,
+        }
+    }
+
+    /// Set the number of frames between keyframes
+    pub fn with_keyframe_interval(mut self, frames: u32) -> Self {
+        self.keyframe_interval = Some(frames);
+        self
+    }
+
+    /// Switch to the defaults for a file recording
+    ///
+    /// Turns off low latency and the virtual camera, encodes at
+    /// [`recording::CONSTANT_QUALITY`] and places a keyframe every
+    /// [`recording::KEYFRAME_SECONDS`]. Quality and keyframe settings made
+    /// before are kept.
+    pub fn with_recording_profile(mut self) -> Self {
+        self.low_latency = false;
+        self.camera_enabled = false;
+        self.rate_control.get_or_insert(RateControl::ConstQuality {
+            cq: recording::CONSTANT_QUALITY,
+        });
+        let interval = self.fps() * recording::KEYFRAME_SECONDS;
+        self.keyframe_interval.get_or_insert(interval);
+        self
+    }
+
+    /// Frames per intra-refresh cycle, if intra-refresh is on
+    pub fn resolved_intra_refresh_period(&self) -> Option<u32> {
+        self.intra_refresh
+            .then(|| self.intra_refresh_period.unwrap_or_else(|| self.fps().max(1)))
+    }
+
+    /// Set the output preset
+    pub fn with_preset(mut self, preset: Preset) -> Self {
+        self.preset = preset;
+        self
+    }
+
+    /// Set the codec
     pub fn with_codec(mut self, codec: Codec) -> Self {
         self.codec = codec;
         self
@@ -526,6 +1411,23 @@ impl CaptureConfig {
         self
     }
 
+    /// Cap VBR peaks at `kbps` (e.g. a platform's ingest limit)
+    ///
+    /// 0 goes back to the default of 1.5x the bitrate.
+    pub fn with_max_bitrate(mut self, kbps: u32) -> Self {
+        self.max_bitrate = (kbps > 0).then_some(kbps);
+        self
+    }
+
+    /// Set the VBV buffer size in kbits
+    ///
+    /// Smaller buffers hold the rate closer to the cap over short windows.
+    /// 0 goes back to the default of one second at the bitrate.
+    pub fn with_vbv_buffer_size(mut self, kbits: u32) -> Self {
+        self.vbv_buffer_size = (kbits > 0).then_some(kbits);
+        self
+    }
+
     /// Set the encoder quality preset
     pub fn with_encoder_preset(mut self, preset: EncoderPreset) -> Self {
         self.encoder_preset = preset;
@@ -544,18 +1446,73 @@ impl CaptureConfig {
         self
     }
 
+    /// Write the recording as `container` whatever its file extension
+    pub fn with_record_container(mut self, container: ContainerFormat) -> Self {
+        self.record_container = Some(container);
+        self
+    }
+
+    /// Keep the last `secs` seconds of output in memory for saving replays
+    ///
+    /// 0 turns the replay buffer off.
+    pub fn with_replay_buffer(mut self, secs: u32) -> Self {
+        self.replay_buffer_secs = (secs > 0).then_some(secs);
+        self
+    }
+
+    /// Start a new recording file every `duration`
+    ///
+    /// Files are numbered after the recording path (`clip-0001.mp4`, ...).
+    pub fn with_segment_duration(mut self, duration: Duration) -> Self {
+        self.record_segments.duration = (!duration.is_zero()).then_some(duration);
+        self
+    }
+
+    /// Start a new recording file every `megabytes` MB
+    pub fn with_segment_size(mut self, megabytes: u64) -> Self {
+        self.record_segments.max_bytes = (megabytes > 0).then_some(megabytes * 1_000_000);
+        self
+    }
+
+    /// Container the recording is written as, if known
+    ///
+    /// The explicit container, otherwise the one the extension implies.
+    pub fn effective_record_container(&self) -> Option<ContainerFormat> {
+        self.record_container
+            .or_else(|| ContainerFormat::from_extension(&self.record_extension()?))
+    }
+
+    /// Set the title tag of the recording
+    pub fn with_record_title(mut self, title: impl Into<String>) -> Self {
+        self.record_metadata.title = Some(title.into());
+        self
+    }
+
     /// Set the cursor capture mode
     pub fn with_cursor_mode(mut self, mode: CursorMode) -> Self {
         self.cursor_mode = mode;
         self
     }
 
+    /// Set the cursor size for metadata mode (1.0 = as sent by the compositor)
+    pub fn with_cursor_scale(mut self, scale: f32) -> Self {
+        self.cursor_scale = scale;
+        self
+    }
+
     /// Set the audio capture source
     pub fn with_audio_source(mut self, source: AudioSource) -> Self {
         self.audio_source = source;
         self
     }
 
+    /// Record audio only (needs an audio source and an audio recording path)
+    pub fn with_audio_only(mut self) -> Self {
+        self.audio_only = true;
+        self.camera_enabled = false;
+        self
+    }
+
     /// Set the audio codec
     pub fn with_audio_codec(mut self, codec: AudioCodec) -> Self {
         self.audio_codec = codec;
@@ -568,6 +1525,114 @@ impl CaptureConfig {
         self
     }
 
+    /// Render custom overlay text; see [`crate::overlay`] for placeholders
+    pub fn with_overlay_template(mut self, template: impl Into<String>) -> Self {
+        self.overlay_template = Some(template.into());
+        self
+    }
+
+    /// Burn a timecode counting from `start` into every output
+    pub fn with_timecode(mut self, start: Timecode) -> Self {
+        self.overlay_timecode = Some(start);
+        self
+    }
+
+    /// Treat captured frames as `input` (e.g. PQ) regardless of the stream
+    pub fn with_hdr_input(mut self, input: HdrMetadata) -> Self {
+        self.hdr_input = Some(input);
+        self
+    }
+
+    /// Blend a logo into every frame
+    pub fn with_watermark(mut self, watermark: WatermarkConfig) -> Self {
+        self.watermark = Some(watermark);
+        self
+    }
+
+    /// Trace-log a sampled hash of each frame at capture and output
+    pub fn with_frame_hashing(mut self, enabled: bool) -> Self {
+        self.frame_hashing = enabled;
+        self
+    }
+
+    /// Pin Nitrogen's threads to CPU cores
+    pub fn with_cpu_affinity(mut self, affinity: CpuAffinity) -> Self {
+        self.cpu_affinity = affinity;
+        self
+    }
+
+    /// Log performance stats every `secs` seconds (0 = off)
+    pub fn with_stats_interval(mut self, secs: u32) -> Self {
+        self.stats_interval_secs = secs;
+        self
+    }
+
+    /// Use a different audio codec and bitrate for the recording
+    pub fn with_record_audio(mut self, codec: AudioCodec, bitrate: u32) -> Self {
+        self.record_audio = Some(OutputAudio::new(codec, bitrate));
+        self
+    }
+
+    /// Use a different audio codec and bitrate for the RTMP/SRT stream
+    pub fn with_stream_audio(mut self, codec: AudioCodec, bitrate: u32) -> Self {
+        self.stream_audio = Some(OutputAudio::new(codec, bitrate));
+        self
+    }
+
+    /// Encode the recording at a different resolution than `preset`
+    pub fn with_record_preset(mut self, preset: Preset) -> Self {
+        self.record_preset = Some(preset);
+        self
+    }
+
+    /// Encode the RTMP/SRT stream at a different resolution than `preset`
+    pub fn with_stream_preset(mut self, preset: Preset) -> Self {
+        self.stream_preset = Some(preset);
+        self
+    }
+
+    /// Set the buffer and encryption used for a rist:// stream
+    pub fn with_stream_rist(mut self, rist: RistOptions) -> Self {
+        self.stream_rist = rist;
+        self
+    }
+
+    /// Set the latency and encryption used for an srt:// stream
+    pub fn with_stream_srt(mut self, srt: SrtOptions) -> Self {
+        self.stream_srt = srt;
+        self
+    }
+
+    /// Also send the stream to `url`, with its own connection
+    pub fn with_stream_mirror(mut self, url: impl Into<String>) -> Self {
+        self.stream_mirrors.push(url.into());
+        self
+    }
+
+    /// Every stream destination, `stream_url` first
+    ///
+    /// Empty without a `stream_url`: mirrors only copy a main stream.
+    pub fn stream_urls(&self) -> Vec<&str> {
+        match self.stream_url.as_deref() {
+            Some(url) => std::iter::once(url)
+                .chain(self.stream_mirrors.iter().map(String::as_str))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Whether any stream destination uses `protocol`
+    pub fn streams_to(&self, protocol: StreamProtocol) -> bool {
+        self.stream_urls()
+            .into_iter()
+            .any(|url| StreamProtocol::from_url(url) == Some(protocol))
+    }
+
+    /// Protocol of the stream URL, if streaming to a supported URL
+    pub fn stream_protocol(&self) -> Option<StreamProtocol> {
+        self.stream_url.as_deref().and_then(StreamProtocol::from_url)
+    }
+
     /// Set AV1-specific configuration
     pub fn with_av1(mut self, av1: Av1Config) -> Self {
         self.av1 = av1;
@@ -586,15 +1651,217 @@ impl CaptureConfig {
         self
     }
 
-    /// Enable AV1 lookahead
-    pub fn with_av1_lookahead(mut self, enabled: bool) -> Self {
-        self.av1.lookahead = enabled;
+    /// Set lookahead and adaptive quantization options
+    pub fn with_quality(mut self, quality: EncoderQuality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Enable encoder lookahead (ignored in low-latency mode)
+    pub fn with_lookahead(mut self, enabled: bool) -> Self {
+        self.quality.lookahead = enabled;
+        self
+    }
+
+    /// Set the lookahead depth in frames
+    pub fn with_lookahead_depth(mut self, depth: u32) -> Self {
+        self.quality.lookahead_depth = depth;
+        self
+    }
+
+    /// Enable spatial adaptive quantization
+    pub fn with_spatial_aq(mut self, enabled: bool) -> Self {
+        self.quality.spatial_aq = enabled;
+        self
+    }
+
+    /// Enable temporal adaptive quantization
+    pub fn with_temporal_aq(mut self, enabled: bool) -> Self {
+        self.quality.temporal_aq = enabled;
+        self
+    }
+
+    /// Set multipass encoding for all codecs (ignored in low-latency mode)
+    pub fn with_multipass(mut self, multipass: MultipassMode) -> Self {
+        self.quality.multipass = multipass;
+        self
+    }
+
+    /// Let NVENC keep up to `depth` frames in flight (1 in low-latency mode)
+    pub fn with_async_depth(mut self, depth: u32) -> Self {
+        self.quality.async_depth = depth;
+        self
+    }
+
+    /// Set the NVENC tuning for all codecs
+    pub fn with_tune(mut self, tune: Tune) -> Self {
+        self.tune = tune;
+        self
+    }
+
+    /// NVENC tuning in effect (`Auto` resolved against `low_latency`)
+    pub fn effective_tune(&self) -> Tune {
+        self.tune.resolve(self.low_latency)
+    }
+
+    /// Rate control in effect
+    ///
+    /// Low-latency tunes are always CBR; otherwise the explicit mode, or VBR.
+    pub fn effective_rate_control(&self) -> RateControl {
+        if self.effective_tune().is_low_latency() {
+            RateControl::Cbr
+        } else {
+            self.rate_control.unwrap_or(RateControl::Vbr)
+        }
+    }
+
+    /// Multipass mode for the selected codec
+    ///
+    /// The shared setting wins; AV1 falls back to its own `av1.multipass`.
+    pub fn multipass(&self) -> MultipassMode {
+        match self.codec {
+            Codec::Av1 if self.quality.multipass == MultipassMode::Disabled => self.av1.multipass,
+            _ => self.quality.multipass,
+        }
+    }
+
+    /// Composite several sources into the output instead of capturing `source`
+    pub fn with_layout(mut self, layers: Vec<LayoutLayer>) -> Self {
+        self.layout = layers;
+        self
+    }
+
+    /// Whether the output is composited from several sources
+    pub fn is_composited(&self) -> bool {
+        !self.layout.is_empty()
+    }
+
+    /// Publish WebRTC video as simulcast layers (highest first)
+    pub fn with_webrtc_simulcast(mut self, layers: Vec<SimulcastLayer>) -> Self {
+        self.webrtc_simulcast = layers;
+        self
+    }
+
+    /// Use these STUN/TURN servers for WebRTC instead of the default STUN server
+    pub fn with_webrtc_ice_servers(mut self, servers: Vec<IceServer>) -> Self {
+        self.webrtc_ice_servers = servers;
+        self
+    }
+
+    /// Enable HLS output with the given settings
+    pub fn with_hls(mut self, hls: HlsConfig) -> Self {
+        self.hls = Some(hls);
+        self
+    }
+
+    /// Enable DASH output with the given settings
+    pub fn with_dash(mut self, dash: DashConfig) -> Self {
+        self.dash = Some(dash);
+        self
+    }
+
+    /// Set microphone channel handling
+    pub fn with_mic_channel(mut self, channel: MicChannel) -> Self {
+        self.mic_channel = channel;
+        self
+    }
+
+    /// Duck desktop audio while the mic is active, detected by `mode`
+    pub fn with_audio_ducking(mut self, mode: DuckingMode) -> Self {
+        self.audio_ducking = true;
+        self.audio_ducking_mode = mode;
+        self
+    }
+
+    /// Set the clipping stage for mixed audio
+    pub fn with_audio_limiter(mut self, limiter: LimiterConfig) -> Self {
+        self.audio_limiter = limiter;
+        self
+    }
+
+    /// Set the A/V sync offset in ms (positive delays audio, negative delays video)
+    pub fn with_av_sync_offset(mut self, offset_ms: i32) -> Self {
+        self.av_sync_offset_ms = offset_ms;
+        self
+    }
+
+    /// Keep the alpha channel on the virtual camera output
+    pub fn with_camera_alpha(mut self, enabled: bool) -> Self {
+        self.camera_alpha = enabled;
+        self
+    }
+
+    /// Set the pixel format of the virtual camera
+    pub fn with_camera_format(mut self, format: CameraFormat) -> Self {
+        self.camera_format = format;
+        self
+    }
+
+    /// Set the crossfade in ms when pausing or resuming the camera (0 = hard cut)
+    pub fn with_camera_transition_ms(mut self, ms: u32) -> Self {
+        self.camera_transition_ms = ms;
+        self
+    }
+
+    /// Show an image instead of black while the camera is paused
+    pub fn with_camera_placeholder(mut self, path: impl Into<PathBuf>) -> Self {
+        self.camera_placeholder = Some(path.into());
+        self
+    }
+
+    /// Set how aspect ratio mismatches are handled, with the bar color for letterboxing
+    pub fn with_fit(mut self, fit: FitMode, color: BarColor) -> Self {
+        self.fit = fit;
+        self.fit_color = color;
+        self
+    }
+
+    /// Set the averaging strategy for performance metrics
+    pub fn with_metrics_mode(mut self, mode: MetricsMode) -> Self {
+        self.metrics_mode = mode;
+        self
+    }
+
+    /// Stop the session automatically after `duration`
+    pub fn with_max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = Some(duration);
+        self
+    }
+
+    /// Set the content mode (static skips near-identical frames)
+    pub fn with_content_mode(mut self, mode: ContentMode) -> Self {
+        self.content_mode = mode;
+        self
+    }
+
+    /// Enable or disable the zero-copy DMA-BUF path to NVENC
+    pub fn with_zero_copy(mut self, enabled: bool) -> Self {
+        self.zero_copy = enabled;
+        self
+    }
+
+    /// Set the output color range
+    pub fn with_color_range(mut self, range: ColorRange) -> Self {
+        self.color_range = range;
+        self
+    }
+
+    /// Set the capture frame timeout in milliseconds (0 disables the watchdog)
+    pub fn with_frame_timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.frame_timeout_ms = timeout_ms;
         self
     }
 
-    /// Enable AV1 spatial adaptive quantization
-    pub fn with_av1_spatial_aq(mut self, enabled: bool) -> Self {
-        self.av1.spatial_aq = enabled;
+    /// Set how often the portal session start is tried (at least once)
+    pub fn with_portal_attempts(mut self, attempts: u32) -> Self {
+        self.portal_attempts = attempts.max(1);
+        self
+    }
+
+    /// Request a PipeWire quantum for the video and audio capture streams
+    pub fn with_latency_quantum(mut self, capture: Option<u32>, audio: Option<u32>) -> Self {
+        self.capture_latency_quantum = capture;
+        self.audio_latency_quantum = audio;
         self
     }
 
@@ -603,6 +1870,15 @@ impl CaptureConfig {
         self.audio_source != AudioSource::None
     }
 
+    /// Check if any output needs the NVENC encoder (recording, streaming, WebRTC, HLS, DASH)
+    pub fn has_encoded_output(&self) -> bool {
+        self.record_path.is_some()
+            || self.stream_url.is_some()
+            || self.webrtc_enabled
+            || self.hls.is_some()
+            || self.dash.is_some()
+    }
+
     /// Get the effective audio bitrate (uses default if 0)
     pub fn effective_audio_bitrate(&self) -> u32 {
         if self.audio_bitrate > 0 {
@@ -616,6 +1892,144 @@ impl CaptureConfig {
         }
     }
 
+    /// Shared audio encoding used by outputs without their own setting
+    pub fn shared_audio(&self) -> OutputAudio {
+        OutputAudio::new(self.audio_codec, self.audio_bitrate)
+    }
+
+    /// Audio encoding for the recording
+    ///
+    /// In audio-only mode an `.opus`/`.ogg` file always gets Opus and an
+    /// `.m4a` file AAC, since those containers can't hold anything else.
+    pub fn effective_record_audio(&self) -> OutputAudio {
+        let audio = self.record_audio.unwrap_or_else(|| self.shared_audio());
+        if !self.audio_only {
+            return audio;
+        }
+        let codec = match self.record_extension().as_deref() {
+            Some("opus" | "ogg") => AudioCodec::Opus,
+            Some("m4a") => AudioCodec::Aac,
+            _ => audio.codec,
+        };
+        if codec == audio.codec {
+            audio
+        } else {
+            OutputAudio::new(codec, 0)
+        }
+    }
+
+    /// Lowercased extension of the recording path
+    fn record_extension(&self) -> Option<String> {
+        let path = self.record_path.as_ref()?;
+        Some(path.extension()?.to_str()?.to_lowercase())
+    }
+
+    /// Check that an audio-only session has somewhere to go
+    ///
+    /// Audio-only mode records to a file (`.m4a`, `.mka`, `.opus` or `.ogg`);
+    /// there's no video for the virtual camera, streams or WebRTC.
+    pub fn validate_audio_only(&self) -> Result<(), String> {
+        if self.audio_source == AudioSource::None {
+            return Err("Audio-only mode needs an audio source".to_string());
+        }
+        if self.record_path.is_none() {
+            return Err("Audio-only mode needs a recording file (--record)".to_string());
+        }
+        match self.record_extension() {
+            Some(ext) if AUDIO_ONLY_EXTENSIONS.contains(&ext.as_str()) => {}
+            _ => {
+                return Err(format!(
+                    "Audio-only recordings must be one of: {}",
+                    AUDIO_ONLY_EXTENSIONS.join(", ")
+                ))
+            }
+        }
+        if self.effective_record_audio().codec == AudioCodec::Copy {
+            return Err("Audio-only recordings need an encoded audio codec".to_string());
+        }
+        if self.camera_enabled {
+            return Err("Audio-only mode has no video for the virtual camera".to_string());
+        }
+        if self.stream_url.is_some()
+            || self.webrtc_enabled
+            || self.hls.is_some()
+            || self.dash.is_some()
+        {
+            return Err("Audio-only mode can only record to a file".to_string());
+        }
+        Ok(())
+    }
+
+    /// Audio encoding for the RTMP/SRT stream
+    pub fn effective_stream_audio(&self) -> OutputAudio {
+        self.stream_audio.unwrap_or_else(|| self.shared_audio())
+    }
+
+    /// Audio encoding for WebRTC viewers
+    ///
+    /// Browsers only take Opus, so this is the shared audio when that is
+    /// Opus and a default bitrate Opus encoder otherwise.
+    pub fn effective_webrtc_audio(&self) -> OutputAudio {
+        let audio = self.shared_audio();
+        if audio.codec == AudioCodec::Opus {
+            audio
+        } else {
+            OutputAudio::new(AudioCodec::Opus, 0)
+        }
+    }
+
+    /// Encoder config for the recording, if it needs its own encoder
+    ///
+    /// None when the recording shares the main encoder (no `record_preset`,
+    /// or one at the main resolution).
+    pub fn record_encoder_config(&self) -> Option<CaptureConfig> {
+        self.output_encoder_config(self.record_preset)
+    }
+
+    /// Encoder config for the RTMP/SRT stream, if it needs its own encoder
+    pub fn stream_encoder_config(&self) -> Option<CaptureConfig> {
+        self.output_encoder_config(self.stream_preset)
+    }
+
+    /// Copy of this config encoding at `preset` instead, unless that's the
+    /// main resolution anyway
+    fn output_encoder_config(&self, preset: Option<Preset>) -> Option<CaptureConfig> {
+        let preset = preset.filter(|p| p.resolution() != self.preset.resolution())?;
+        let mut config = self.clone();
+        config.preset = preset;
+        // An explicit bitrate was chosen for the main resolution
+        config.bitrate = 0;
+        // Zero-copy input only helps at the capture size
+        config.zero_copy = false;
+        config.record_preset = None;
+        config.stream_preset = None;
+        Some(config)
+    }
+
+    /// Pixels per second across every NVENC session this config opens
+    ///
+    /// Counts the main encoder plus the per-output ones for
+    /// `record_preset`/`stream_preset`.
+    pub fn encode_pixel_rate(&self) -> u64 {
+        let rate = |config: &CaptureConfig| {
+            u64::from(config.width()) * u64::from(config.height()) * u64::from(config.fps())
+        };
+        let outputs = [
+            self.record_path.as_ref().and(self.record_encoder_config()),
+            self.stream_url.as_ref().and(self.stream_encoder_config()),
+        ];
+
+        // The main encoder only runs if some output still uses it
+        let main_needed = self.webrtc_enabled
+            || self.hls.is_some()
+            || self.dash.is_some()
+            || (self.record_path.is_some() && outputs[0].is_none())
+            || (self.stream_url.is_some() && outputs[1].is_none());
+
+        let main = if main_needed { rate(self) } else { 0 };
+        main + outputs.iter().flatten().map(rate).sum::<u64>()
+    }
+
     /// Get the effective bitrate (uses suggested if 0)
     pub fn effective_bitrate(&self) -> u32 {
         if self.bitrate > 0 {
@@ -625,6 +2039,17 @@ impl CaptureConfig {
         }
     }
 
+    /// Peak bitrate in kbps, falling back to 1.5x the bitrate
+    pub fn effective_max_bitrate(&self) -> u32 {
+        let bitrate = self.effective_bitrate();
+        self.max_bitrate.unwrap_or(bitrate + bitrate / 2)
+    }
+
+    /// VBV buffer size in kbits, falling back to one second at the bitrate
+    pub fn effective_vbv_buffer_size(&self) -> u32 {
+        self.vbv_buffer_size.unwrap_or_else(|| self.effective_bitrate())
+    }
+
     /// Get output width
     pub fn width(&self) -> u32 {
         self.preset.width()
@@ -676,6 +2101,15 @@ impl CaptureConfig {
             }
         }
 
+        // NVENC can't average above its own ceiling
+        let max_bitrate = self.effective_max_bitrate();
+        if self.effective_rate_control().uses_bitrate() && max_bitrate < effective {
+            warnings.push(format!(
+                "Max bitrate {} kbps is below the {} kbps target; the encoder is held to the cap.",
+                max_bitrate, effective
+            ));
+        }
+
         // Check for high-bandwidth configurations that might stress the system
         let pixels_per_second =
             (self.width() as u64) * (self.height() as u64) * (self.fps() as u64);
@@ -687,11 +2121,286 @@ impl CaptureConfig {
             ));
         }
 
+        // Separate recording/stream encoders add up on the same NVENC engine
+        if self.record_encoder_config().is_some() || self.stream_encoder_config().is_some() {
+            let total = self.encode_pixel_rate();
+            if total > 500_000_000 {
+                warnings.push(format!(
+                    "Encoding the outputs at separate resolutions needs {} Mpx/s, more than NVENC handles comfortably (~500 Mpx/s, 4K60). Frames may drop; lower --record-preset or --stream-preset.",
+                    total / 1_000_000
+                ));
+            }
+        }
+
+        // Temporal layers only help when a receiver can drop the upper ones
+        if self.temporal_layers > 1 {
+            let max = self.codec.max_temporal_layers();
+            if max > 0 && self.temporal_layers > max {
+                warnings.push(format!(
+                    "{} supports at most {} temporal layers; {} will be clamped.",
+                    self.codec, max, self.temporal_layers
+                ));
+            }
+            if self.record_path.is_some() {
+                warnings.push(
+                    "Temporal layers add overhead to the recording without any benefit. They help WebRTC and SRT viewers on lossy links.".to_string(),
+                );
+            }
+        }
+
+        // Intra-refresh trades clean keyframes for smooth recovery from loss
+        if self.intra_refresh {
+            if self.record_path.is_some() {
+                warnings.push(
+                    "Intra-refresh is meant for streaming: the recording has no clean keyframes, \
+                     so seeking in it is slow or lands on a partly drawn picture."
+                        .to_string(),
+                );
+            }
+            if self.content_mode == ContentMode::Static {
+                warnings.push(
+                    "Intra-refresh is ignored in static content mode, which refreshes with full \
+                     keyframes."
+                        .to_string(),
+                );
+            }
+        }
+
         // AV1 encoding is more demanding
         if self.codec == Codec::Av1 && self.fps() > 60 {
             warnings.push("AV1 encoding at high framerates may cause performance issues. Consider HEVC or H.264.".to_string());
         }
 
+        // Full-range output is tagged correctly, but not every player honors the tag
+        if self.color_range == ColorRange::Full
+            && (self.record_path.is_some()
+                || self.stream_url.is_some()
+                || self.hls.is_some()
+                || self.dash.is_some())
+        {
+            warnings.push(
+                "Full color range is tagged in the output, but some players ignore the flag and show washed-out or crushed colors. Use limited range for maximum compatibility.".to_string(),
+            );
+        }
+
+        // HLS players only accept AV1 in fMP4 segments
+        if let Some(ref hls) = self.hls {
+            if self.codec == Codec::Av1 && hls.segment_format == HlsSegmentFormat::Mpegts {
+                warnings.push(
+                    "AV1 in MPEG-TS HLS segments is not widely playable. Use fmp4 segments instead."
+                        .to_string(),
+                );
+            }
+        }
+
+        let (width, height) = (self.width(), self.height());
+        for layer in &self.layout {
+            if layer.x >= width || layer.y >= height {
+                warnings.push(format!(
+                    "Layout layer {} at {},{} is outside the {}x{} output and won't be visible.",
+                    layer.source, layer.x, layer.y, width, height
+                ));
+            }
+        }
+
+        if !self.webrtc_simulcast.is_empty() && !self.webrtc_enabled {
+            warnings.push("Simulcast layers have no effect with WebRTC disabled.".to_string());
+        }
+
+        // NVENC has no alpha support, so only the raw camera path keeps it
+        if self.camera_alpha {
+            if !self.camera_enabled {
+                warnings.push(
+                    "Camera alpha has no effect with the virtual camera disabled.".to_string(),
+                );
+            } else if self.camera_format == CameraFormat::Nv12 {
+                warnings.push(
+                    "Camera alpha has no effect with the NV12 camera format.".to_string(),
+                );
+            } else if self.has_encoded_output() {
+                warnings.push(
+                    "Alpha is only kept on the virtual camera. Recording and streaming output will be opaque.".to_string(),
+                );
+            }
+        }
+
+        let low_latency = self.effective_tune().is_low_latency();
+
+        // Lookahead adds latency and is dropped by the encoder in low-latency mode
+        if self.quality.lookahead && low_latency {
+            warnings.push(
+                "Lookahead is ignored in low-latency mode. Disable low latency to use it."
+                    .to_string(),
+            );
+        }
+
+        // Low-latency tunes are CBR, which has no quality target
+        if let Some(rate_control) = self.rate_control.filter(|rc| *rc != RateControl::Cbr) {
+            if low_latency {
+                warnings.push(format!(
+                    "{} rate control is ignored in low-latency mode (CBR). Disable low latency \
+                     to use it.",
+                    rate_control.describe()
+                ));
+            } else if !rate_control.uses_bitrate() && self.bitrate > 0 {
+                warnings.push(format!(
+                    "Constant QP ignores the {} kbps bitrate. Drop the bitrate, or use cq:<level> \
+                     to keep it as a ceiling.",
+                    self.bitrate
+                ));
+            }
+        }
+
+        // Multipass likewise adds latency and is dropped in low-latency mode
+        if self.multipass() != MultipassMode::Disabled && low_latency {
+            warnings.push(
+                "Multipass is ignored in low-latency mode. Disable low latency to use it."
+                    .to_string(),
+            );
+        }
+
+        if self.quality.async_depth > 1 && low_latency {
+            warnings.push(format!(
+                "Async depth {} is clamped to 1 in low-latency mode; each frame in flight adds latency.",
+                self.quality.async_depth
+            ));
+        } else if self.quality.async_depth > MAX_ASYNC_DEPTH {
+            warnings.push(format!(
+                "Async depth {} is clamped to {}.",
+                self.quality.async_depth, MAX_ASYNC_DEPTH
+            ));
+        }
+
+        if self.tune == Tune::Ull {
+            warnings.push(
+                "Ultra-low-latency tuning lowers quality at the same bitrate; raise the bitrate or use tune \"ll\" if motion looks blocky.".to_string(),
+            );
+        } else if self.low_latency && self.tune == Tune::Hq {
+            warnings.push(
+                "Tune \"hq\" overrides low-latency mode; B-frames, lookahead and multipass are allowed.".to_string(),
+            );
+        }
+
+        if !self.stream_rist.is_empty() && !self.streams_to(StreamProtocol::Rist) {
+            warnings.push(
+                "RIST options are set but no stream URL is rist://; they are ignored.".to_string(),
+            );
+        }
+
+        if !self.stream_srt.is_empty() && !self.streams_to(StreamProtocol::Srt) {
+            warnings.push(
+                "SRT options are set but no stream URL is srt://; they are ignored.".to_string(),
+            );
+        }
+
+        if self.timecode_metadata {
+            if self.overlay_timecode.is_none() {
+                warnings.push(
+                    "Timecode metadata needs overlay timecode enabled; nothing will be written.".to_string(),
+                );
+            } else if !crate::encode::timecode_metadata_supported(self.codec) {
+                warnings.push(format!(
+                    "{} has no timecode metadata syntax; the timecode is only burned in.",
+                    self.codec
+                ));
+            }
+        }
+
+        if self.hdr_input.is_some() && self.hdr_tonemap == TonemapMode::Off {
+            warnings.push("HDR input override has no effect with tonemapping off.".to_string());
+        }
+        if self.hdr_dynamic && self.hdr_tonemap == TonemapMode::Off {
+            warnings
+                .push("Dynamic HDR tonemapping has no effect with tonemapping off.".to_string());
+        }
+
+        if let Some(count) = affinity::cpu_count() {
+            for (role, cores) in self.cpu_affinity.iter() {
+                if cores.max_core().is_some_and(|core| core >= count) {
+                    warnings.push(format!(
+                        "CPU affinity for the {} thread ({}) includes cores this system doesn't \
+                         have ({} cores); those are ignored.",
+                        role, cores, count
+                    ));
+                }
+            }
+        }
+
+        // Codecs the recording's container can't hold
+        if self.record_path.is_some() && !self.audio_only {
+            if let Some(container) = self.effective_record_container() {
+                let audio = self.has_audio().then(|| self.effective_record_audio().codec);
+                if let Some(warning) = container.codec_warning(self.codec, audio) {
+                    warnings.push(format!("Recording: {}.", warning));
+                }
+            }
+        }
+
+        if self.record_segments.is_enabled() {
+            if self.record_path.is_none() {
+                warnings.push(
+                    "Segment limits are set but there is no recording to split.".to_string(),
+                );
+            } else if self.audio_only {
+                warnings.push(
+                    "Audio-only recordings are not split into segments; one file is written."
+                        .to_string(),
+                );
+            } else if self.resolved_intra_refresh_period().is_some() {
+                warnings.push(
+                    "Intra-refresh leaves no keyframes to start a segment on, so the recording \
+                     stays in one file."
+                        .to_string(),
+                );
+            }
+        }
+
+        // The whole window is held in memory at the output bitrate
+        if let Some(secs) = self.replay_buffer_secs {
+            let megabytes = self.effective_bitrate() as u64 * secs as u64 / 8 / 1000;
+            if megabytes > REPLAY_BUFFER_WARN_MB {
+                warnings.push(format!(
+                    "A {}s replay buffer keeps about {} MB of video in memory.",
+                    secs, megabytes
+                ));
+            }
+        }
+
+        if let Some(watermark) = &self.watermark {
+            if !watermark.path.exists() {
+                warnings.push(format!(
+                    "Watermark image {} does not exist; capture will fail to start.",
+                    watermark.path.display()
+                ));
+            }
+        }
+
+        if let Some(placeholder) = &self.camera_placeholder {
+            if !placeholder.exists() {
+                warnings.push(format!(
+                    "Camera placeholder {} does not exist; capture will fail to start.",
+                    placeholder.display()
+                ));
+            }
+        }
+
+        // The scale only applies when Nitrogen draws the cursor itself
+        if self.cursor_mode != CursorMode::Metadata
+            && (self.cursor_scale - DEFAULT_CURSOR_SCALE).abs() > f32::EPSILON
+        {
+            warnings.push(
+                "Cursor scale has no effect unless the cursor mode is metadata.".to_string(),
+            );
+        }
+
+        if self.audio_limiter.mode == LimiterMode::TruePeak && self.audio_limiter.threshold_db > 0.0
+        {
+            warnings.push(format!(
+                "Limiter threshold {} dBFS is above full scale and will be capped at 0 dBFS.",
+                self.audio_limiter.threshold_db
+            ));
+        }
+
         // Check if 120fps is selected (Discord doesn't support >60fps)
         if self.fps() > 60 {
             warnings.push(format!(
@@ -719,22 +2428,136 @@ impl CaptureConfig {
         }
 
         // Check reasonable resolution limits
-        if self.width() > 7680 || self.height() > 4320 {
+        if self.width() > MAX_WIDTH || self.height() > MAX_HEIGHT {
             return Err(format!(
-                "Resolution {}x{} exceeds maximum supported (7680x4320)",
+                "Resolution {}x{} exceeds maximum supported ({}x{})",
                 self.width(),
-                self.height()
+                self.height(),
+                MAX_WIDTH,
+                MAX_HEIGHT
             ));
         }
 
         // Check reasonable framerate limits
-        if self.fps() > 240 {
+        if self.fps() > MAX_FPS {
             return Err(format!(
-                "Framerate {} exceeds maximum supported (240)",
-                self.fps()
+                "Framerate {} exceeds maximum supported ({})",
+                self.fps(),
+                MAX_FPS
+            ));
+        }
+
+        if self.temporal_layers > 1 && self.codec.max_temporal_layers() == 0 {
+            return Err(format!(
+                "Temporal layers are not supported for {}; use H.264 or set temporal layers to 0",
+                self.codec
             ));
         }
 
+        if self.intra_refresh && self.intra_refresh_period == Some(0) {
+            return Err("Intra-refresh period must be at least 1 frame".to_string());
+        }
+
+        if let Some(rate_control) = self.rate_control {
+            rate_control.validate(self.codec)?;
+        }
+
+        if self.keyframe_interval == Some(0) {
+            return Err("Keyframe interval must be at least 1 frame".to_string());
+        }
+
+        let quanta = [
+            ("Capture", self.capture_latency_quantum),
+            ("Audio", self.audio_latency_quantum),
+        ];
+        for (stream, quantum) in quanta {
+            if let Some(quantum) = quantum {
+                if !(1..=MAX_LATENCY_QUANTUM).contains(&quantum) {
+                    return Err(format!(
+                        "{} latency quantum {} is out of range (1-{} samples)",
+                        stream, quantum, MAX_LATENCY_QUANTUM
+                    ));
+                }
+            }
+        }
+
+        if !(self.cursor_scale > 0.0 && self.cursor_scale <= MAX_CURSOR_SCALE) {
+            return Err(format!(
+                "Cursor scale {} is out of range (0 < scale <= {})",
+                self.cursor_scale, MAX_CURSOR_SCALE
+            ));
+        }
+
+        if !self.audio_limiter.threshold_db.is_finite() {
+            return Err("Limiter threshold must be a finite dBFS value".to_string());
+        }
+
+        if self.av_sync_offset_ms.unsigned_abs() > MAX_AV_SYNC_OFFSET_MS as u32 {
+            return Err(format!(
+                "A/V sync offset {} ms is out of range (at most {} ms either way)",
+                self.av_sync_offset_ms, MAX_AV_SYNC_OFFSET_MS
+            ));
+        }
+
+        if self.streams_to(StreamProtocol::Rist) {
+            self.stream_rist.validate()?;
+        }
+
+        if self.streams_to(StreamProtocol::Srt) {
+            self.stream_srt.validate()?;
+        }
+
+        if let Some(start) = &self.overlay_timecode {
+            start
+                .validate(self.fps())
+                .map_err(|e| format!("Invalid timecode start: {}", e))?;
+        }
+
+        if let Some(watermark) = &self.watermark {
+            watermark.validate()?;
+        }
+
+        if let Some(layer) = self.layout.iter().find(|l| l.width == 0 || l.height == 0) {
+            return Err(format!("Layout layer {} has zero size", layer.source));
+        }
+
+        // Every encoder is fed the same captured frames at the main framerate
+        let output_presets = [("Recording", self.record_preset), ("Stream", self.stream_preset)];
+        for (output, preset) in output_presets {
+            if let Some(preset) = preset {
+                if preset.fps() != self.fps() {
+                    return Err(format!(
+                        "{} preset {} must keep the {} fps framerate; only the resolution can differ",
+                        output,
+                        preset,
+                        self.fps()
+                    ));
+                }
+                let (width, height) = preset.resolution();
+                if width == 0 || height == 0 || width > MAX_WIDTH || height > MAX_HEIGHT {
+                    return Err(format!(
+                        "{} preset {} has an unsupported resolution",
+                        output, preset
+                    ));
+                }
+            }
+        }
+
+        // Captured audio is raw PCM, so there is nothing to pass through
+        if self.audio_source != AudioSource::None {
+            for (output, audio) in [
+                ("Recording", self.record_audio),
+                ("Stream", self.stream_audio),
+            ] {
+                if audio.is_some_and(|a| a.codec == AudioCodec::Copy) {
+                    return Err(format!(
+                        "{} audio codec 'copy' needs an encoded audio source; captured audio must be encoded (aac or opus)",
+                        output
+                    ));
+                }
+            }
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,190 @@
+//! Codec-independent NVENC quality settings
+//!
+//! Lookahead, adaptive quantization, multipass, latency tuning and the
+//! number of frames in flight work the same way for H.264, HEVC and AV1, so
+//! they live here instead of in the per-codec configs.
+
+use serde::{Deserialize, Serialize};
+
+/// Maximum lookahead depth accepted by NVENC (RTX 50 series)
+pub const MAX_LOOKAHEAD_DEPTH: u32 = 250;
+
+/// Most frames Nitrogen lets NVENC have in flight at once
+pub const MAX_ASYNC_DEPTH: u32 = 32;
+
+/// Multipass encoding mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MultipassMode {
+    /// Disabled (fastest)
+    #[default]
+    Disabled,
+    /// Quarter resolution first pass
+    Quarter,
+    /// Full resolution first pass (best quality)
+    Full,
+}
+
+impl MultipassMode {
+    /// Returns ffmpeg multipass option string
+    pub fn ffmpeg_value(&self) -> Option<&'static str> {
+        match self {
+            Self::Disabled => None,
+            Self::Quarter => Some("qres"),
+            Self::Full => Some("fullres"),
+        }
+    }
+}
+
+impl std::str::FromStr for MultipassMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "disabled" | "off" | "none" => Ok(Self::Disabled),
+            "quarter" | "qres" => Ok(Self::Quarter),
+            "full" | "fullres" => Ok(Self::Full),
+            _ => Err(format!("Unknown multipass mode: {}", s)),
+        }
+    }
+}
+
+/// NVENC latency/quality tuning (`-tune`) applied to every codec
+///
+/// `ll` and `ull` trade quality for latency: both encode at constant bitrate
+/// with zero frame delay and no B-frames, lookahead or multipass. `ull` also
+/// tunes NVENC's rate control for the smallest per-frame encode time, which
+/// looks noticeably worse on fast motion at the same bitrate; it is best kept
+/// for local game streaming.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Tune {
+    /// `ll` in low-latency mode, otherwise `hq` (AV1 uses its own `av1.tune`)
+    #[default]
+    Auto,
+    /// High quality
+    Hq,
+    /// Low latency
+    Ll,
+    /// Ultra-low latency (lowest latency, lowest quality)
+    Ull,
+}
+
+impl Tune {
+    /// Resolve `Auto` against the low-latency flag
+    pub fn resolve(self, low_latency: bool) -> Self {
+        match self {
+            Self::Auto if low_latency => Self::Ll,
+            Self::Auto => Self::Hq,
+            tune => tune,
+        }
+    }
+
+    /// Check if this tune disables B-frames, lookahead and multipass
+    pub fn is_low_latency(&self) -> bool {
+        matches!(self, Self::Ll | Self::Ull)
+    }
+
+    /// Returns ffmpeg tune option string (None for `Auto`)
+    pub fn ffmpeg_value(&self) -> Option<&'static str> {
+        match self {
+            Self::Auto => None,
+            Self::Hq => Some("hq"),
+            Self::Ll => Some("ll"),
+            Self::Ull => Some("ull"),
+        }
+    }
+}
+
+impl std::str::FromStr for Tune {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "hq" => Ok(Self::Hq),
+            "ll" | "low-latency" => Ok(Self::Ll),
+            "ull" | "ultra-low-latency" => Ok(Self::Ull),
+            _ => Err(format!("Unknown tune: {}", s)),
+        }
+    }
+}
+
+/// Lookahead, adaptive quantization and multipass options applied to every codec
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EncoderQuality {
+    /// Enable lookahead (ignored in low-latency mode)
+    pub lookahead: bool,
+    /// Lookahead depth (frames, RTX 50: up to 250, default 20)
+    pub lookahead_depth: u32,
+    /// Enable spatial AQ (adaptive quantization)
+    pub spatial_aq: bool,
+    /// Enable temporal AQ (~4-5% efficiency gain)
+    pub temporal_aq: bool,
+    /// Multipass encoding (ignored in low-latency mode)
+    pub multipass: MultipassMode,
+    /// Frames NVENC may have in flight (0 = FFmpeg's default; 1 in low-latency mode)
+    pub async_depth: u32,
+}
+
+impl Default for EncoderQuality {
+    fn default() -> Self {
+        Self {
+            lookahead: false,
+            lookahead_depth: 20,
+            spatial_aq: false,
+            temporal_aq: false,
+            multipass: MultipassMode::Disabled,
+            async_depth: 0,
+        }
+    }
+}
+
+impl EncoderQuality {
+    /// Settings for offline-quality encodes on RTX 50 series (Blackwell)
+    pub fn blackwell_optimized() -> Self {
+        Self {
+            lookahead: true,
+            lookahead_depth: MAX_LOOKAHEAD_DEPTH, // Extended lookahead on Blackwell
+            spatial_aq: true,
+            temporal_aq: true,
+            multipass: MultipassMode::Full,
+            async_depth: 0,
+        }
+    }
+
+    /// Settings for low-latency streaming (no lookahead)
+    pub fn streaming() -> Self {
+        Self {
+            lookahead: false,
+            lookahead_depth: 0,
+            spatial_aq: true,
+            temporal_aq: false,
+            multipass: MultipassMode::Disabled,
+            async_depth: 0,
+        }
+    }
+
+    /// Lookahead depth clamped to the range NVENC accepts
+    pub fn effective_lookahead_depth(&self) -> u32 {
+        self.lookahead_depth.clamp(1, MAX_LOOKAHEAD_DEPTH)
+    }
+
+    /// Frames in flight for the latency mode (None = leave it to FFmpeg)
+    ///
+    /// Each extra frame in flight adds a frame of latency, so low-latency
+    /// encodes keep a single one.
+    pub fn effective_async_depth(&self, low_latency: bool) -> Option<u32> {
+        match self.async_depth {
+            0 => None,
+            _ if low_latency => Some(1),
+            depth => Some(depth.min(MAX_ASYNC_DEPTH)),
+        }
+    }
+
+    /// Check if these settings rely on RTX 50 series features
+    pub fn uses_blackwell_features(&self) -> bool {
+        self.temporal_aq || (self.lookahead && self.lookahead_depth > 32)
+    }
+}
@@ -0,0 +1,140 @@
+//! Config reload for a running session
+//!
+//! When the config file changes, the daemon builds a fresh
+//! [`CaptureConfig`] from it and compares that with the config it is
+//! running. Settings the pipeline can change on the fly come back as
+//! [`LiveChange`]s for [`Pipeline::apply_reload`](crate::Pipeline::apply_reload);
+//! everything else that differs is listed by its config file key so the
+//! user knows it waits for the next start.
+
+use super::CaptureConfig;
+use crate::overlay::OverlayPosition;
+
+/// A setting the running pipeline can pick up without restarting
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiveChange {
+    /// Latency overlay settings
+    Overlay {
+        enabled: bool,
+        position: OverlayPosition,
+        template: Option<String>,
+        title: String,
+    },
+    /// A/V sync offset in ms (positive delays audio)
+    SyncOffset(i32),
+    /// Seconds between performance stats log lines (0 = off)
+    StatsInterval(u32),
+}
+
+/// What a reload changes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReloadPlan {
+    /// Changes applied to the running pipeline
+    pub live: Vec<LiveChange>,
+    /// Config file keys that changed but only apply on the next start
+    pub restart: Vec<&'static str>,
+}
+
+impl ReloadPlan {
+    /// Compare the running config with a freshly loaded one
+    pub fn new(running: &CaptureConfig, reloaded: &CaptureConfig) -> Self {
+        let mut live = Vec::new();
+        if running.overlay_enabled != reloaded.overlay_enabled
+            || running.overlay_position != reloaded.overlay_position
+            || running.overlay_template != reloaded.overlay_template
+            || running.overlay_title != reloaded.overlay_title
+        {
+            live.push(LiveChange::Overlay {
+                enabled: reloaded.overlay_enabled,
+                position: reloaded.overlay_position,
+                template: reloaded.overlay_template.clone(),
+                title: reloaded.overlay_title.clone(),
+            });
+        }
+        if running.av_sync_offset_ms != reloaded.av_sync_offset_ms {
+            live.push(LiveChange::SyncOffset(reloaded.av_sync_offset_ms));
+        }
+        if running.stats_interval_secs != reloaded.stats_interval_secs {
+            live.push(LiveChange::StatsInterval(reloaded.stats_interval_secs));
+        }
+
+        let restart = [
+            ("defaults.preset", running.preset != reloaded.preset),
+            ("defaults.codec", running.codec != reloaded.codec),
+            ("defaults.bitrate", running.bitrate != reloaded.bitrate),
+            ("defaults.low_latency", running.low_latency != reloaded.low_latency),
+            ("encoder.quality", running.encoder_preset != reloaded.encoder_preset),
+            ("encoder.gpu", running.gpu != reloaded.gpu),
+            ("encoder.rate_control", running.rate_control != reloaded.rate_control),
+            ("encoder.max_bitrate", running.max_bitrate != reloaded.max_bitrate),
+            ("encoder.vbv_buffer_size", running.vbv_buffer_size != reloaded.vbv_buffer_size),
+            ("camera.name", running.camera_name != reloaded.camera_name),
+            ("audio.source", running.audio_source != reloaded.audio_source),
+            ("audio.codec", running.audio_codec != reloaded.audio_codec),
+            ("audio.bitrate", running.audio_bitrate != reloaded.audio_bitrate),
+            ("audio.ducking.enabled", running.audio_ducking != reloaded.audio_ducking),
+            ("audio.ducking.mode", running.audio_ducking_mode != reloaded.audio_ducking_mode),
+            ("hdr.tonemap", running.hdr_tonemap != reloaded.hdr_tonemap),
+            ("capture.cursor", running.cursor_mode != reloaded.cursor_mode),
+            ("recording.replay_buffer", running.replay_buffer_secs != reloaded.replay_buffer_secs),
+            (
+                "recording.segment_duration",
+                running.record_segments.duration != reloaded.record_segments.duration,
+            ),
+            (
+                "recording.segment_size",
+                running.record_segments.max_bytes != reloaded.record_segments.max_bytes,
+            ),
+        ];
+
+        Self {
+            live,
+            restart: restart
+                .into_iter()
+                .filter(|&(_, changed)| changed)
+                .map(|(key, _)| key)
+                .collect(),
+        }
+    }
+
+    /// Whether the reload changed nothing this plan tracks
+    pub fn is_empty(&self) -> bool {
+        self.live.is_empty() && self.restart.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Codec, Preset};
+
+    #[test]
+    fn test_reload_plan() {
+        let running = CaptureConfig::monitor("DP-1").with_preset(Preset::P1080_60);
+        assert!(ReloadPlan::new(&running, &running.clone()).is_empty());
+
+        let mut reloaded = running.clone();
+        reloaded.overlay_enabled = true;
+        reloaded.overlay_title = "Ranked".to_string();
+        reloaded.av_sync_offset_ms = -40;
+        reloaded.stats_interval_secs = 10;
+        reloaded.codec = Codec::Hevc;
+        reloaded.preset = Preset::P1440_60;
+
+        let plan = ReloadPlan::new(&running, &reloaded);
+        assert_eq!(
+            plan.live,
+            [
+                LiveChange::Overlay {
+                    enabled: true,
+                    position: running.overlay_position,
+                    template: None,
+                    title: "Ranked".to_string(),
+                },
+                LiveChange::SyncOffset(-40),
+                LiveChange::StatsInterval(10),
+            ]
+        );
+        assert_eq!(plan.restart, ["defaults.preset", "defaults.codec"]);
+    }
+}
@@ -0,0 +1,392 @@
+//! DMA-BUF import into CUDA for zero-copy NVENC input
+//!
+//! Captured DMA-BUF frames are imported with `cuImportExternalMemory` and
+//! mapped as device memory with `cuExternalMemoryGetMappedBuffer`, then
+//! copied GPU-side into an NVENC input surface. The CPU never touches the
+//! pixels. Only linear buffers can be mapped this way; tiled modifiers (and
+//! any import failure) fall back to the mmap copy path in the encoder.
+//!
+//! Smooth Motion maps DMA-BUFs the same way to hand them to NvOFFRUC, and
+//! reads the interpolated frame back with [`CudaImporter::copy_to_host`].
+
+use std::collections::HashMap;
+use std::os::fd::RawFd;
+use std::sync::OnceLock;
+
+use tracing::{debug, info};
+
+use super::cuda_sys::{
+    CUcontext, CUdevice, CUdeviceptr, CUexternalMemory, CUresult, CudaLib,
+    CUDA_EXTERNAL_MEMORY_BUFFER_DESC, CUDA_EXTERNAL_MEMORY_HANDLE,
+    CUDA_EXTERNAL_MEMORY_HANDLE_DESC, CUDA_MEMCPY2D, CUDA_SUCCESS,
+    CU_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD,
+};
+use crate::error::{NitrogenError, Result};
+
+/// `DRM_FORMAT_MOD_LINEAR`
+pub const DRM_FORMAT_MOD_LINEAR: u64 = 0;
+
+/// Imports kept alive at once; PipeWire pools are much smaller than this,
+/// so hitting the limit means the pool was renegotiated
+const MAX_CACHED_IMPORTS: usize = 16;
+
+/// Check whether DMA-BUF frames can be imported into CUDA on this system
+///
+/// Loads libcuda and initializes the driver once; the result is cached.
+pub fn cuda_import_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| match CudaLib::load() {
+        // SAFETY: cuInit only initializes driver state and may be called repeatedly
+        Ok(lib) => (unsafe { (lib.init)(0) }) == CUDA_SUCCESS,
+        Err(e) => {
+            debug!("CUDA DMA-BUF import unavailable: {}", e);
+            false
+        }
+    })
+}
+
+/// Check whether a DMA-BUF with this modifier can be mapped as a CUDA buffer
+pub fn modifier_supported(modifier: u64) -> bool {
+    modifier == DRM_FORMAT_MOD_LINEAR
+}
+
+/// An imported DMA-BUF and its device mapping
+struct ImportedBuffer {
+    ext_mem: CUexternalMemory,
+    dev_ptr: CUdeviceptr,
+    size: u64,
+}
+
+/// Imports DMA-BUFs into the primary CUDA context of a GPU
+///
+/// Imports are cached per buffer (by inode), since PipeWire cycles through
+/// a small fixed pool and re-importing every frame would cost more than the
+/// copy it replaces.
+pub struct CudaImporter {
+    lib: CudaLib,
+    device: CUdevice,
+    ctx: CUcontext,
+    imports: HashMap<u64, ImportedBuffer>,
+    /// Device buffers from [`alloc`](Self::alloc), freed on drop
+    allocations: Vec<CUdeviceptr>,
+}
+
+// SAFETY: The context and external memory handles are only used with the
+// context pushed on the calling thread, and the importer is owned by a
+// single encoder at a time.
+unsafe impl Send for CudaImporter {}
+
+impl CudaImporter {
+    /// Retain the primary context of the given GPU
+    ///
+    /// FFmpeg's CUDA device is created on the same primary context, so
+    /// device pointers from here are valid in NVENC input surfaces.
+    pub fn new(gpu: u32) -> Result<Self> {
+        let lib = CudaLib::load().map_err(NitrogenError::encoder)?;
+        let mut device: CUdevice = 0;
+        let mut ctx: CUcontext = std::ptr::null_mut();
+
+        // SAFETY: Out-pointers are valid locals; the context is released in Drop.
+        unsafe {
+            check(&lib, (lib.init)(0), "cuInit")?;
+            check(&lib, (lib.device_get)(&mut device, gpu as i32), "cuDeviceGet")?;
+            check(
+                &lib,
+                (lib.primary_ctx_retain)(&mut ctx, device),
+                "cuDevicePrimaryCtxRetain",
+            )?;
+        }
+
+        info!("CUDA DMA-BUF import enabled on GPU {}", gpu);
+        Ok(Self {
+            lib,
+            device,
+            ctx,
+            imports: HashMap::new(),
+            allocations: Vec::new(),
+        })
+    }
+
+    /// Copy a linear DMA-BUF image into device memory
+    ///
+    /// `src_offset`/`src_pitch` describe the image inside the DMA-BUF and
+    /// `dst`/`dst_pitch` the destination surface. The copy runs on the GPU.
+    #[allow(clippy::too_many_arguments)]
+    pub fn copy_to_device(
+        &mut self,
+        fd: RawFd,
+        src_offset: u32,
+        src_pitch: u32,
+        dst: CUdeviceptr,
+        dst_pitch: usize,
+        width_bytes: usize,
+        height: usize,
+    ) -> Result<()> {
+        self.with_context(|importer| {
+            let src = importer.mapped(fd, src_offset, src_pitch, height)?;
+            let copy = CUDA_MEMCPY2D::device_to_device(
+                src,
+                src_pitch as usize,
+                dst,
+                dst_pitch,
+                width_bytes,
+                height,
+            );
+            // SAFETY: Both ranges were checked against their allocation sizes
+            // and the context owning them is current.
+            let result = unsafe { (importer.lib.memcpy_2d)(&copy) };
+            check(&importer.lib, result, "cuMemcpy2D")
+        })
+    }
+
+    /// Map a linear DMA-BUF image as device memory
+    ///
+    /// Returns the address of the image at `offset`. It stays valid until
+    /// the import cache is flushed, which only happens when the capture
+    /// pool changes.
+    pub fn map(
+        &mut self,
+        fd: RawFd,
+        offset: u32,
+        pitch: u32,
+        height: usize,
+    ) -> Result<CUdeviceptr> {
+        self.with_context(|importer| importer.mapped(fd, offset, pitch, height))
+    }
+
+    /// Allocate device memory in this importer's context
+    ///
+    /// The buffer lives as long as the importer.
+    pub fn alloc(&mut self, size: usize) -> Result<CUdeviceptr> {
+        self.with_context(|importer| {
+            let mut dev_ptr: CUdeviceptr = 0;
+            // SAFETY: dev_ptr is a valid out-pointer and the context is current
+            let result = unsafe { (importer.lib.mem_alloc)(&mut dev_ptr, size) };
+            check(&importer.lib, result, "cuMemAlloc")?;
+            importer.allocations.push(dev_ptr);
+            Ok(dev_ptr)
+        })
+    }
+
+    /// Copy a pitched device image into host memory
+    pub fn copy_to_host(
+        &mut self,
+        src: CUdeviceptr,
+        src_pitch: usize,
+        dst: &mut [u8],
+        dst_pitch: usize,
+        width_bytes: usize,
+        height: usize,
+    ) -> Result<()> {
+        let needed = dst_pitch * height.saturating_sub(1) + width_bytes;
+        if height > 0 && dst.len() < needed {
+            return Err(NitrogenError::encoder(format!(
+                "Host buffer too small: {} bytes, frame needs {}",
+                dst.len(),
+                needed
+            )));
+        }
+        self.with_context(|importer| {
+            let copy = CUDA_MEMCPY2D::device_to_host(
+                src,
+                src_pitch,
+                dst.as_mut_ptr().cast(),
+                dst_pitch,
+                width_bytes,
+                height,
+            );
+            // SAFETY: The host range was checked above, and the device range
+            // belongs to the current context
+            let result = unsafe { (importer.lib.memcpy_2d)(&copy) };
+            check(&importer.lib, result, "cuMemcpy2D")
+        })
+    }
+
+    /// Number of DMA-BUFs currently imported
+    pub fn cached_imports(&self) -> usize {
+        self.imports.len()
+    }
+
+    /// Device address of an image in a DMA-BUF (context must be current)
+    fn mapped(
+        &mut self,
+        fd: RawFd,
+        offset: u32,
+        pitch: u32,
+        height: usize,
+    ) -> Result<CUdeviceptr> {
+        let needed = offset as u64 + pitch as u64 * height as u64;
+        let buffer = self.import(fd)?;
+        if buffer.size < needed {
+            return Err(NitrogenError::encoder(format!(
+                "DMA-BUF too small: {} bytes, frame needs {}",
+                buffer.size, needed
+            )));
+        }
+        Ok(buffer.dev_ptr + offset as u64)
+    }
+
+    /// Import a DMA-BUF, reusing an earlier import of the same buffer
+    fn import(&mut self, fd: RawFd) -> Result<&ImportedBuffer> {
+        let key = buffer_inode(fd)?;
+        if !self.imports.contains_key(&key) {
+            if self.imports.len() >= MAX_CACHED_IMPORTS {
+                debug!("DMA-BUF pool changed, dropping {} imports", self.imports.len());
+                self.release_imports();
+            }
+            let buffer = self.import_new(fd)?;
+            self.imports.insert(key, buffer);
+        }
+        Ok(&self.imports[&key])
+    }
+
+    fn import_new(&self, fd: RawFd) -> Result<ImportedBuffer> {
+        // SAFETY: lseek on a borrowed fd only queries the DMA-BUF size; the
+        // offset of a DMA-BUF fd isn't used by anyone else.
+        let size = unsafe { libc::lseek(fd, 0, libc::SEEK_END) };
+        if size <= 0 {
+            return Err(NitrogenError::encoder(format!(
+                "Cannot determine DMA-BUF size: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+
+        // CUDA takes ownership of the fd on success, and the capture stream
+        // still owns the original, so import a duplicate
+        // SAFETY: F_DUPFD_CLOEXEC on a valid fd returns a new fd or -1.
+        let dup = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+        if dup < 0 {
+            return Err(NitrogenError::encoder(format!(
+                "Failed to duplicate DMA-BUF fd: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+
+        let desc = CUDA_EXTERNAL_MEMORY_HANDLE_DESC {
+            type_: CU_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD,
+            handle: CUDA_EXTERNAL_MEMORY_HANDLE { fd: dup },
+            size: size as u64,
+            flags: 0,
+            reserved: [0; 16],
+        };
+        let mut ext_mem: CUexternalMemory = std::ptr::null_mut();
+        // SAFETY: desc is fully initialized and ext_mem is a valid out-pointer.
+        let result = unsafe { (self.lib.import_external_memory)(&mut ext_mem, &desc) };
+        if result != CUDA_SUCCESS {
+            // SAFETY: On failure CUDA did not take ownership, so the dup is ours
+            unsafe { libc::close(dup) };
+            return Err(cuda_error(&self.lib, result, "cuImportExternalMemory"));
+        }
+
+        let buffer_desc = CUDA_EXTERNAL_MEMORY_BUFFER_DESC {
+            offset: 0,
+            size: size as u64,
+            ..Default::default()
+        };
+        let mut dev_ptr: CUdeviceptr = 0;
+        // SAFETY: ext_mem was just imported and the mapping covers its full size.
+        let result = unsafe {
+            (self.lib.external_memory_get_mapped_buffer)(&mut dev_ptr, ext_mem, &buffer_desc)
+        };
+        if result != CUDA_SUCCESS {
+            // SAFETY: ext_mem is valid and not referenced anywhere else
+            unsafe { (self.lib.destroy_external_memory)(ext_mem) };
+            return Err(cuda_error(&self.lib, result, "cuExternalMemoryGetMappedBuffer"));
+        }
+
+        debug!("Imported DMA-BUF ({} bytes) into CUDA", size);
+        Ok(ImportedBuffer {
+            ext_mem,
+            dev_ptr,
+            size: size as u64,
+        })
+    }
+
+    /// Free all mappings and imports (context must be current)
+    fn release_imports(&mut self) {
+        for (_, buffer) in self.imports.drain() {
+            // SAFETY: Each mapping and import is freed exactly once here
+            unsafe {
+                (self.lib.mem_free)(buffer.dev_ptr);
+                (self.lib.destroy_external_memory)(buffer.ext_mem);
+            }
+        }
+    }
+
+    /// Run `f` with this importer's context current on the calling thread
+    pub(super) fn with_context<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        // SAFETY: ctx is a retained primary context; it is popped again below
+        // so the caller's current context is left untouched.
+        unsafe {
+            check(&self.lib, (self.lib.ctx_push_current)(self.ctx), "cuCtxPushCurrent")?;
+        }
+        let result = f(self);
+        let mut popped: CUcontext = std::ptr::null_mut();
+        // SAFETY: Balances the push above
+        unsafe { (self.lib.ctx_pop_current)(&mut popped) };
+        result
+    }
+}
+
+impl Drop for CudaImporter {
+    fn drop(&mut self) {
+        if !self.imports.is_empty() || !self.allocations.is_empty() {
+            let _ = self.with_context(|importer| {
+                importer.release_imports();
+                for dev_ptr in importer.allocations.drain(..) {
+                    // SAFETY: Each allocation is freed exactly once here
+                    unsafe { (importer.lib.mem_free)(dev_ptr) };
+                }
+                Ok(())
+            });
+        }
+        // SAFETY: Balances the retain in new()
+        unsafe { (self.lib.primary_ctx_release)(self.device) };
+    }
+}
+
+/// Identify a DMA-BUF by inode, which stays stable across fd numbers
+fn buffer_inode(fd: RawFd) -> Result<u64> {
+    // SAFETY: stat is plain old data, and fstat only writes into it
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::fstat(fd, &mut stat) };
+    if result != 0 {
+        return Err(NitrogenError::encoder(format!(
+            "fstat on DMA-BUF fd {} failed: {}",
+            fd,
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(stat.st_ino)
+}
+
+fn check(lib: &CudaLib, result: CUresult, call: &str) -> Result<()> {
+    if result == CUDA_SUCCESS {
+        Ok(())
+    } else {
+        Err(cuda_error(lib, result, call))
+    }
+}
+
+fn cuda_error(lib: &CudaLib, result: CUresult, call: &str) -> NitrogenError {
+    NitrogenError::encoder(format!("{} failed: {}", call, lib.error_string(result)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_linear_modifier_supported() {
+        assert!(modifier_supported(DRM_FORMAT_MOD_LINEAR));
+        // DRM_FORMAT_MOD_INVALID and an NVIDIA block-linear modifier
+        assert!(!modifier_supported(0x00ff_ffff_ffff_ffff));
+        assert!(!modifier_supported(0x0300_0000_0060_6015));
+    }
+
+    #[test]
+    fn test_cuda_import_detection() {
+        // Just checks the capability probe doesn't panic without a GPU
+        let _ = cuda_import_available();
+    }
+}
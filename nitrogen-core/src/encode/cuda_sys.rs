@@ -0,0 +1,336 @@
+//! Raw FFI bindings for the CUDA driver API (external memory subset)
+//!
+//! These bindings are loaded dynamically at runtime from libcuda.so, which
+//! ships with the NVIDIA driver. Only the calls needed to import a DMA-BUF
+//! as CUDA device memory, copy it into an NVENC input surface and read
+//! interpolated frames back to the CPU are bound.
+
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(dead_code)]
+
+use std::ffi::{c_int, c_uint, c_void};
+
+/// CUDA driver result code (`CUresult`)
+pub type CUresult = c_int;
+
+/// Device ordinal handle (`CUdevice`)
+pub type CUdevice = c_int;
+
+/// Opaque context handle (`CUcontext`)
+pub type CUcontext = *mut c_void;
+
+/// Opaque external memory handle (`CUexternalMemory`)
+pub type CUexternalMemory = *mut c_void;
+
+/// Device pointer (`CUdeviceptr`)
+pub type CUdeviceptr = u64;
+
+/// Call succeeded
+pub const CUDA_SUCCESS: CUresult = 0;
+
+/// `CU_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD`
+///
+/// The driver accepts exported DMA-BUF fds through this handle type for
+/// linear buffers allocated by the NVIDIA driver itself.
+pub const CU_EXTERNAL_MEMORY_HANDLE_TYPE_OPAQUE_FD: c_uint = 1;
+
+/// `CU_MEMORYTYPE_HOST`
+pub const CU_MEMORYTYPE_HOST: c_uint = 1;
+
+/// `CU_MEMORYTYPE_DEVICE`
+pub const CU_MEMORYTYPE_DEVICE: c_uint = 2;
+
+/// Handle part of `CUDA_EXTERNAL_MEMORY_HANDLE_DESC`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union CUDA_EXTERNAL_MEMORY_HANDLE {
+    /// POSIX file descriptor (ownership passes to CUDA on success)
+    pub fd: c_int,
+    /// Win32 handle and name (unused on Linux, sizes the union)
+    pub win32: [*mut c_void; 2],
+}
+
+/// `CUDA_EXTERNAL_MEMORY_HANDLE_DESC`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct CUDA_EXTERNAL_MEMORY_HANDLE_DESC {
+    pub type_: c_uint,
+    pub handle: CUDA_EXTERNAL_MEMORY_HANDLE,
+    pub size: u64,
+    pub flags: c_uint,
+    pub reserved: [c_uint; 16],
+}
+
+/// `CUDA_EXTERNAL_MEMORY_BUFFER_DESC`
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CUDA_EXTERNAL_MEMORY_BUFFER_DESC {
+    pub offset: u64,
+    pub size: u64,
+    pub flags: c_uint,
+    pub reserved: [c_uint; 16],
+}
+
+/// `CUDA_MEMCPY2D`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CUDA_MEMCPY2D {
+    pub srcXInBytes: usize,
+    pub srcY: usize,
+    pub srcMemoryType: c_uint,
+    pub srcHost: *const c_void,
+    pub srcDevice: CUdeviceptr,
+    pub srcArray: *mut c_void,
+    pub srcPitch: usize,
+    pub dstXInBytes: usize,
+    pub dstY: usize,
+    pub dstMemoryType: c_uint,
+    pub dstHost: *mut c_void,
+    pub dstDevice: CUdeviceptr,
+    pub dstArray: *mut c_void,
+    pub dstPitch: usize,
+    pub WidthInBytes: usize,
+    pub Height: usize,
+}
+
+impl CUDA_MEMCPY2D {
+    /// Pitched device-to-device copy of `height` rows of `width_bytes` each
+    pub fn device_to_device(
+        src: CUdeviceptr,
+        src_pitch: usize,
+        dst: CUdeviceptr,
+        dst_pitch: usize,
+        width_bytes: usize,
+        height: usize,
+    ) -> Self {
+        Self {
+            srcXInBytes: 0,
+            srcY: 0,
+            srcMemoryType: CU_MEMORYTYPE_DEVICE,
+            srcHost: std::ptr::null(),
+            srcDevice: src,
+            srcArray: std::ptr::null_mut(),
+            srcPitch: src_pitch,
+            dstXInBytes: 0,
+            dstY: 0,
+            dstMemoryType: CU_MEMORYTYPE_DEVICE,
+            dstHost: std::ptr::null_mut(),
+            dstDevice: dst,
+            dstArray: std::ptr::null_mut(),
+            dstPitch: dst_pitch,
+            WidthInBytes: width_bytes,
+            Height: height,
+        }
+    }
+
+    /// Pitched device-to-host copy of `height` rows of `width_bytes` each
+    pub fn device_to_host(
+        src: CUdeviceptr,
+        src_pitch: usize,
+        dst: *mut c_void,
+        dst_pitch: usize,
+        width_bytes: usize,
+        height: usize,
+    ) -> Self {
+        Self {
+            dstMemoryType: CU_MEMORYTYPE_HOST,
+            dstHost: dst,
+            dstDevice: 0,
+            ..Self::device_to_device(src, src_pitch, 0, dst_pitch, width_bytes, height)
+        }
+    }
+}
+
+// Function pointer types
+
+pub type FnCuInit = unsafe extern "C" fn(flags: c_uint) -> CUresult;
+
+pub type FnCuDeviceGet = unsafe extern "C" fn(device: *mut CUdevice, ordinal: c_int) -> CUresult;
+
+pub type FnCuDevicePrimaryCtxRetain =
+    unsafe extern "C" fn(ctx: *mut CUcontext, device: CUdevice) -> CUresult;
+
+pub type FnCuDevicePrimaryCtxRelease = unsafe extern "C" fn(device: CUdevice) -> CUresult;
+
+pub type FnCuCtxPushCurrent = unsafe extern "C" fn(ctx: CUcontext) -> CUresult;
+
+pub type FnCuCtxPopCurrent = unsafe extern "C" fn(ctx: *mut CUcontext) -> CUresult;
+
+pub type FnCuImportExternalMemory = unsafe extern "C" fn(
+    ext_mem: *mut CUexternalMemory,
+    desc: *const CUDA_EXTERNAL_MEMORY_HANDLE_DESC,
+) -> CUresult;
+
+pub type FnCuExternalMemoryGetMappedBuffer = unsafe extern "C" fn(
+    dev_ptr: *mut CUdeviceptr,
+    ext_mem: CUexternalMemory,
+    desc: *const CUDA_EXTERNAL_MEMORY_BUFFER_DESC,
+) -> CUresult;
+
+pub type FnCuDestroyExternalMemory = unsafe extern "C" fn(ext_mem: CUexternalMemory) -> CUresult;
+
+pub type FnCuMemAlloc = unsafe extern "C" fn(dev_ptr: *mut CUdeviceptr, size: usize) -> CUresult;
+
+pub type FnCuMemFree = unsafe extern "C" fn(dev_ptr: CUdeviceptr) -> CUresult;
+
+pub type FnCuMemcpy2D = unsafe extern "C" fn(copy: *const CUDA_MEMCPY2D) -> CUresult;
+
+pub type FnCuGetErrorString =
+    unsafe extern "C" fn(error: CUresult, out: *mut *const std::ffi::c_char) -> CUresult;
+
+/// Library paths to search for libcuda.so (installed by the NVIDIA driver)
+pub const CUDA_LIB_PATHS: &[&str] = &[
+    "libcuda.so.1",
+    "libcuda.so",
+    "/usr/lib/libcuda.so.1",
+    "/usr/lib/x86_64-linux-gnu/libcuda.so.1",
+    "/usr/lib64/libcuda.so.1",
+];
+
+/// Dynamically loaded CUDA driver library
+pub struct CudaLib {
+    _lib: libloading::Library,
+    pub init: FnCuInit,
+    pub device_get: FnCuDeviceGet,
+    pub primary_ctx_retain: FnCuDevicePrimaryCtxRetain,
+    pub primary_ctx_release: FnCuDevicePrimaryCtxRelease,
+    pub ctx_push_current: FnCuCtxPushCurrent,
+    pub ctx_pop_current: FnCuCtxPopCurrent,
+    pub import_external_memory: FnCuImportExternalMemory,
+    pub external_memory_get_mapped_buffer: FnCuExternalMemoryGetMappedBuffer,
+    pub destroy_external_memory: FnCuDestroyExternalMemory,
+    pub mem_alloc: FnCuMemAlloc,
+    pub mem_free: FnCuMemFree,
+    pub memcpy_2d: FnCuMemcpy2D,
+    pub get_error_string: FnCuGetErrorString,
+}
+
+impl CudaLib {
+    /// Try to load the CUDA driver library from standard paths
+    pub fn load() -> Result<Self, String> {
+        for path in CUDA_LIB_PATHS {
+            if let Ok(lib) = Self::load_from_path(path) {
+                tracing::debug!("Loaded CUDA driver library from: {}", path);
+                return Ok(lib);
+            }
+        }
+        Err("Failed to load libcuda.so from any known path".to_string())
+    }
+
+    /// Load the library from a specific path
+    ///
+    /// # Safety
+    /// This function uses unsafe to:
+    /// 1. Load a dynamic library which could execute arbitrary code in its init
+    /// 2. Look up function symbols and cast them to Rust function pointers
+    ///
+    /// We mitigate risks by:
+    /// - Only loading from known driver paths (CUDA_LIB_PATHS)
+    /// - Verifying all required symbols exist before returning success
+    /// - Binding the versioned `_v2` entry points documented in cuda.h
+    pub fn load_from_path(path: &str) -> Result<Self, String> {
+        // SAFETY: libcuda is the NVIDIA driver's own library. The function
+        // signatures match the CUDA driver API headers, and the pointers are
+        // copied out while the library handle is kept alive in the struct.
+        unsafe {
+            let lib = libloading::Library::new(path)
+                .map_err(|e| format!("Failed to load {}: {}", path, e))?;
+
+            macro_rules! symbol {
+                ($ty:ty, $name:literal) => {
+                    *lib.get::<$ty>($name).map_err(|e| {
+                        format!(
+                            "Failed to get {}: {}",
+                            String::from_utf8_lossy(&$name[..$name.len() - 1]),
+                            e
+                        )
+                    })?
+                };
+            }
+
+            let init = symbol!(FnCuInit, b"cuInit\0");
+            let device_get = symbol!(FnCuDeviceGet, b"cuDeviceGet\0");
+            let primary_ctx_retain =
+                symbol!(FnCuDevicePrimaryCtxRetain, b"cuDevicePrimaryCtxRetain\0");
+            let primary_ctx_release =
+                symbol!(FnCuDevicePrimaryCtxRelease, b"cuDevicePrimaryCtxRelease_v2\0");
+            let ctx_push_current = symbol!(FnCuCtxPushCurrent, b"cuCtxPushCurrent_v2\0");
+            let ctx_pop_current = symbol!(FnCuCtxPopCurrent, b"cuCtxPopCurrent_v2\0");
+            let import_external_memory =
+                symbol!(FnCuImportExternalMemory, b"cuImportExternalMemory\0");
+            let external_memory_get_mapped_buffer = symbol!(
+                FnCuExternalMemoryGetMappedBuffer,
+                b"cuExternalMemoryGetMappedBuffer\0"
+            );
+            let destroy_external_memory =
+                symbol!(FnCuDestroyExternalMemory, b"cuDestroyExternalMemory\0");
+            let mem_alloc = symbol!(FnCuMemAlloc, b"cuMemAlloc_v2\0");
+            let mem_free = symbol!(FnCuMemFree, b"cuMemFree_v2\0");
+            let memcpy_2d = symbol!(FnCuMemcpy2D, b"cuMemcpy2D_v2\0");
+            let get_error_string = symbol!(FnCuGetErrorString, b"cuGetErrorString\0");
+
+            Ok(Self {
+                _lib: lib,
+                init,
+                device_get,
+                primary_ctx_retain,
+                primary_ctx_release,
+                ctx_push_current,
+                ctx_pop_current,
+                import_external_memory,
+                external_memory_get_mapped_buffer,
+                destroy_external_memory,
+                mem_alloc,
+                mem_free,
+                memcpy_2d,
+                get_error_string,
+            })
+        }
+    }
+
+    /// Convert a result code to a readable message
+    pub fn error_string(&self, result: CUresult) -> String {
+        let mut msg: *const std::ffi::c_char = std::ptr::null();
+        // SAFETY: cuGetErrorString writes a pointer to a static string owned
+        // by the driver, or leaves it null for unknown codes.
+        let status = unsafe { (self.get_error_string)(result, &mut msg) };
+        if status != CUDA_SUCCESS || msg.is_null() {
+            return format!("CUDA error {}", result);
+        }
+        // SAFETY: non-null pointers from cuGetErrorString are NUL-terminated statics
+        unsafe { std::ffi::CStr::from_ptr(msg) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+// SAFETY: CudaLib only holds function pointers into libcuda, whose driver API
+// is thread-safe. The library handle (_lib) is kept alive for the lifetime of
+// this struct, ensuring the function pointers remain valid.
+unsafe impl Send for CudaLib {}
+unsafe impl Sync for CudaLib {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_struct_layout_matches_cuda_h() {
+        // Sizes from cuda.h on x86_64
+        assert_eq!(std::mem::size_of::<CUDA_EXTERNAL_MEMORY_HANDLE_DESC>(), 104);
+        assert_eq!(std::mem::size_of::<CUDA_EXTERNAL_MEMORY_BUFFER_DESC>(), 88);
+        assert_eq!(std::mem::size_of::<CUDA_MEMCPY2D>(), 128);
+    }
+
+    #[test]
+    fn test_device_to_host_copy() {
+        let mut host = [0u8; 16];
+        let copy = CUDA_MEMCPY2D::device_to_host(0x1000, 8, host.as_mut_ptr().cast(), 4, 4, 4);
+        assert_eq!(copy.srcMemoryType, CU_MEMORYTYPE_DEVICE);
+        assert_eq!(copy.srcDevice, 0x1000);
+        assert_eq!(copy.dstMemoryType, CU_MEMORYTYPE_HOST);
+        assert_eq!(copy.dstHost, host.as_mut_ptr().cast());
+        assert_eq!((copy.srcPitch, copy.dstPitch), (8, 4));
+    }
+}
@@ -1,8 +1,11 @@
 //! Smooth Motion Frame Generation for streaming
 //!
 //! Provides frame interpolation to increase output framerate without
-//! requiring higher capture rates. Uses NVIDIA Optical Flow when available.
+//! requiring higher capture rates. Uses NVIDIA Optical Flow when available,
+//! for DMA-BUF frames too (see [`NvFruc`]); without it, GPU-captured frames
+//! are duplicated instead.
 
+use std::collections::VecDeque;
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use tracing::{debug, info, warn};
@@ -10,8 +13,10 @@ use tracing::{debug, info, warn};
 use serde::{Deserialize, Serialize};
 
 use crate::error::Result;
-use crate::types::Frame;
+use crate::formats::{bytes_per_pixel_or_planar, frame_planes, to_bgra8, PixelLayout, YuvMatrix};
+use crate::types::{Frame, FrameFormat};
 
+use super::motion::motion_compensated_blend;
 use super::nvfruc::{nvfruc_available, NvFruc};
 
 /// Frame generation mode
@@ -28,6 +33,9 @@ pub enum FrameGenMode {
     /// 4x interpolation (30fps -> 120fps)
     Quadruple,
     /// Adaptive - adjusts based on scene complexity
+    ///
+    /// Starts at 2x, goes up to 4x while motion is low and down to
+    /// passthrough while it is high; see [`AdaptiveMultiplier`].
     Adaptive,
 }
 
@@ -73,6 +81,186 @@ impl std::fmt::Display for FrameGenMode {
     }
 }
 
+/// Scene change threshold used before auto-calibration finishes
+pub const DEFAULT_SCENE_THRESHOLD: f32 = 0.4;
+
+/// Quality above which the CPU fallback follows block motion instead of
+/// blending linearly (see `encode::motion`)
+pub const MOTION_COMPENSATION_QUALITY: u8 = 60;
+
+/// Frames sampled to calibrate an automatic scene threshold (~3s at 60fps)
+pub const SCENE_CALIBRATION_FRAMES: usize = 180;
+
+/// Percentile of calibration differences that normal motion stays under
+const SCENE_THRESHOLD_PERCENTILE: f32 = 0.95;
+
+/// Headroom above the percentile so the busiest normal frames don't trip it
+const SCENE_THRESHOLD_HEADROOM: f32 = 1.5;
+
+/// Range an automatic threshold is clamped to
+const AUTO_SCENE_THRESHOLD_RANGE: (f32, f32) = (0.05, 0.9);
+
+/// Frames the adaptive motion average covers (~0.5s at 60fps)
+pub const ADAPTIVE_WINDOW: usize = 30;
+
+/// Average frame difference under which adaptive mode interpolates 4x
+pub const ADAPTIVE_CALM_MOTION: f32 = 0.01;
+
+/// Average frame difference above which adaptive mode stops interpolating
+pub const ADAPTIVE_BUSY_MOTION: f32 = 0.1;
+
+/// Picks the [`FrameGenMode::Adaptive`] multiplier from recent motion
+///
+/// Calm content interpolates cleanly, so it gets more in-between frames;
+/// busy content is where interpolation artifacts show, so it passes
+/// through. The decision follows the rolling average of the last
+/// [`ADAPTIVE_WINDOW`] frame differences; until the window is full the
+/// multiplier stays at 2x.
+#[derive(Debug, Clone)]
+pub struct AdaptiveMultiplier {
+    /// Recent frame differences, oldest first
+    window: VecDeque<f32>,
+    /// Current multiplier
+    multiplier: u32,
+}
+
+impl AdaptiveMultiplier {
+    /// Start at 2x with no history
+    pub fn new() -> Self {
+        Self {
+            window: VecDeque::with_capacity(ADAPTIVE_WINDOW),
+            multiplier: FrameGenMode::Adaptive.multiplier(),
+        }
+    }
+
+    /// Record the difference between two consecutive frames
+    ///
+    /// Scene cuts are not motion and shouldn't be fed here.
+    pub fn observe(&mut self, diff: f32) {
+        if !diff.is_finite() {
+            return;
+        }
+        if self.window.len() == ADAPTIVE_WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(diff);
+        if self.window.len() < ADAPTIVE_WINDOW {
+            return;
+        }
+
+        let multiplier = match self.average() {
+            average if average < ADAPTIVE_CALM_MOTION => 4,
+            average if average > ADAPTIVE_BUSY_MOTION => 1,
+            _ => 2,
+        };
+        if multiplier != self.multiplier {
+            debug!(
+                "Adaptive frame generation: {}x -> {}x (motion {:.3})",
+                self.multiplier,
+                multiplier,
+                self.average()
+            );
+            self.multiplier = multiplier;
+        }
+    }
+
+    /// Average difference over the window (0.0 before any frame)
+    pub fn average(&self) -> f32 {
+        if self.window.is_empty() {
+            0.0
+        } else {
+            self.window.iter().sum::<f32>() / self.window.len() as f32
+        }
+    }
+
+    /// Multiplier to interpolate with
+    pub fn multiplier(&self) -> u32 {
+        self.multiplier
+    }
+}
+
+impl Default for AdaptiveMultiplier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Scene change threshold for frame generation
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SceneThreshold {
+    /// Calibrate from the histogram differences of the first frames
+    ///
+    /// After [`SCENE_CALIBRATION_FRAMES`] frames the threshold is set just
+    /// above the 95th percentile, so games with constant motion don't trip it
+    /// and slides still register every cut.
+    #[default]
+    Auto,
+    /// Fixed histogram difference (0.0-1.0, lower = more sensitive)
+    Fixed(f32),
+}
+
+/// Calibrates an automatic scene threshold from observed frame differences
+#[derive(Debug, Clone)]
+pub struct SceneThresholdTuner {
+    /// Differences seen so far (freed once calibrated)
+    samples: Vec<f32>,
+    /// Calibrated threshold
+    threshold: Option<f32>,
+}
+
+impl SceneThresholdTuner {
+    /// Start calibrating
+    pub fn new() -> Self {
+        Self {
+            samples: Vec::with_capacity(SCENE_CALIBRATION_FRAMES),
+            threshold: None,
+        }
+    }
+
+    /// Record the difference between two consecutive frames
+    pub fn observe(&mut self, diff: f32) {
+        if self.threshold.is_some() || !diff.is_finite() {
+            return;
+        }
+
+        self.samples.push(diff);
+        if self.samples.len() >= SCENE_CALIBRATION_FRAMES {
+            let threshold = calibrated_threshold(&mut self.samples);
+            info!(
+                "Scene change threshold calibrated to {:.3} over {} frames",
+                threshold,
+                self.samples.len()
+            );
+            self.threshold = Some(threshold);
+            self.samples = Vec::new();
+        }
+    }
+
+    /// Calibrated threshold, or [`DEFAULT_SCENE_THRESHOLD`] while sampling
+    pub fn threshold(&self) -> f32 {
+        self.threshold.unwrap_or(DEFAULT_SCENE_THRESHOLD)
+    }
+
+    /// Check if calibration has finished
+    pub fn is_calibrated(&self) -> bool {
+        self.threshold.is_some()
+    }
+}
+
+impl Default for SceneThresholdTuner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Threshold just above the normal-motion percentile of `samples`
+fn calibrated_threshold(samples: &mut [f32]) -> f32 {
+    samples.sort_by(f32::total_cmp);
+    let index = ((samples.len() - 1) as f32 * SCENE_THRESHOLD_PERCENTILE).round() as usize;
+    let (min, max) = AUTO_SCENE_THRESHOLD_RANGE;
+    (samples[index] * SCENE_THRESHOLD_HEADROOM).clamp(min, max)
+}
+
 /// Smooth Motion configuration
 #[derive(Debug, Clone)]
 pub struct SmoothMotionConfig {
@@ -81,11 +269,14 @@ pub struct SmoothMotionConfig {
     /// Use GPU-accelerated optical flow (requires CUDA)
     pub gpu_accelerated: bool,
     /// Quality preset (0-100, higher = better quality but more latency)
+    ///
+    /// Above [`MOTION_COMPENSATION_QUALITY`] the CPU fallback estimates
+    /// motion instead of cross-fading.
     pub quality: u8,
     /// Maximum latency in milliseconds (0 = no limit)
     pub max_latency_ms: u32,
-    /// Scene change threshold (0.0-1.0, lower = more sensitive)
-    pub scene_threshold: f32,
+    /// Scene change threshold (auto-calibrated, or fixed 0.0-1.0)
+    pub scene_threshold: SceneThreshold,
     /// Enable temporal stability (reduces flickering)
     pub temporal_stability: bool,
 }
@@ -97,7 +288,7 @@ impl Default for SmoothMotionConfig {
             gpu_accelerated: true,
             quality: 75,
             max_latency_ms: 50,
-            scene_threshold: 0.4,
+            scene_threshold: SceneThreshold::Auto,
             temporal_stability: true,
         }
     }
@@ -111,7 +302,7 @@ impl SmoothMotionConfig {
             gpu_accelerated: true,
             quality: 50,
             max_latency_ms: 16, // ~1 frame at 60fps
-            scene_threshold: 0.5,
+            scene_threshold: SceneThreshold::Fixed(0.5),
             temporal_stability: false,
         }
     }
@@ -123,7 +314,7 @@ impl SmoothMotionConfig {
             gpu_accelerated: true,
             quality: 90,
             max_latency_ms: 100,
-            scene_threshold: 0.3,
+            scene_threshold: SceneThreshold::Fixed(0.3),
             temporal_stability: true,
         }
     }
@@ -135,7 +326,7 @@ impl SmoothMotionConfig {
             gpu_accelerated: true,
             quality: 80,
             max_latency_ms: 50,
-            scene_threshold: 0.4,
+            scene_threshold: SceneThreshold::Fixed(DEFAULT_SCENE_THRESHOLD),
             temporal_stability: true,
         }
     }
@@ -154,6 +345,10 @@ pub struct SmoothMotion {
     optical_flow_available: bool,
     /// NVIDIA FRUC instance for GPU interpolation
     nvfruc: Option<NvFruc>,
+    /// Calibration state for [`SceneThreshold::Auto`]
+    scene_tuner: Option<SceneThresholdTuner>,
+    /// Motion tracking for [`FrameGenMode::Adaptive`]
+    adaptive: Option<AdaptiveMultiplier>,
 }
 
 impl SmoothMotion {
@@ -183,6 +378,10 @@ impl SmoothMotion {
             }
         }
 
+        let scene_tuner =
+            (config.scene_threshold == SceneThreshold::Auto).then(SceneThresholdTuner::new);
+        let adaptive = (config.mode == FrameGenMode::Adaptive).then(AdaptiveMultiplier::new);
+
         Self {
             config,
             prev_frame: None,
@@ -190,6 +389,8 @@ impl SmoothMotion {
             output_tx,
             optical_flow_available,
             nvfruc: None, // Initialized lazily on first frame
+            scene_tuner,
+            adaptive,
         }
     }
 
@@ -233,14 +434,15 @@ impl SmoothMotion {
         }
 
         let mut output_frames = Vec::new();
-        let multiplier = self.config.mode.multiplier();
 
         // Clone previous frame to avoid borrow conflict with interpolate_frame
         let prev_frame = self.prev_frame.clone();
 
         if let Some(ref prev) = prev_frame {
-            // Check for scene change once (it's the same for all interpolated frames)
+            // Check for scene change once (it's the same for all interpolated frames);
+            // this also updates the adaptive multiplier
             let is_scene_change = self.detect_scene_change(prev, &frame);
+            let multiplier = self.current_multiplier();
 
             // Generate interpolated frames
             for i in 1..multiplier {
@@ -274,40 +476,44 @@ impl SmoothMotion {
     }
 
     /// Detect scene change between two frames using histogram comparison
-    fn detect_scene_change(&self, prev: &Frame, curr: &Frame) -> bool {
-        use crate::types::FrameData;
-
+    ///
+    /// In auto mode the difference also feeds the threshold calibration, and
+    /// in adaptive mode the motion average (unless it is a cut).
+    fn detect_scene_change(&mut self, prev: &Frame, curr: &Frame) -> bool {
         // Different dimensions always trigger scene change
         if prev.format.width != curr.format.width || prev.format.height != curr.format.height {
             return true;
         }
 
-        // Get frame data - only works for Memory frames
-        let prev_data = match &prev.data {
-            FrameData::Memory(data) => data,
-            FrameData::DmaBuf { .. } => return false, // Can't analyze DMA-BUF without mapping
+        // DMA-BUF frames can't be analyzed without mapping
+        let Some(diff) = frame_difference(prev, curr) else {
+            return false;
         };
-        let curr_data = match &curr.data {
-            FrameData::Memory(data) => data,
-            FrameData::DmaBuf { .. } => return false,
-        };
-
-        // Compute histogram difference
-        let diff = compute_histogram_difference(
-            prev_data,
-            curr_data,
-            prev.format.width,
-            prev.format.height,
-            prev.format.stride,
-            prev.format.fourcc,
-        );
+        if let Some(ref mut tuner) = self.scene_tuner {
+            tuner.observe(diff);
+        }
+        let is_scene_change = diff > self.scene_threshold();
+        if let Some(ref mut adaptive) = self.adaptive {
+            if !is_scene_change {
+                adaptive.observe(diff);
+            }
+        }
+        is_scene_change
+    }
 
-        diff > self.config.scene_threshold
+    /// Scene change threshold currently in use
+    pub fn scene_threshold(&self) -> f32 {
+        match (self.config.scene_threshold, &self.scene_tuner) {
+            (SceneThreshold::Fixed(threshold), _) => threshold,
+            (SceneThreshold::Auto, Some(tuner)) => tuner.threshold(),
+            (SceneThreshold::Auto, None) => DEFAULT_SCENE_THRESHOLD,
+        }
     }
 
     /// Interpolate between two frames at time t (0.0 to 1.0)
     fn interpolate_frame(&mut self, prev: &Frame, curr: &Frame, t: f32) -> Result<Frame> {
-        // Try GPU interpolation first if available
+        // Try GPU interpolation first if available; it is the only way to
+        // interpolate DMA-BUF frames
         if self.config.gpu_accelerated {
             if let Some(ref mut fruc) = self.nvfruc {
                 match fruc.interpolate(prev, curr, t) {
@@ -323,7 +529,10 @@ impl SmoothMotion {
         self.cpu_interpolate(prev, curr, t)
     }
 
-    /// CPU fallback interpolation using linear pixel blending
+    /// CPU fallback interpolation
+    ///
+    /// Follows block motion for RGB frames when the quality is above
+    /// [`MOTION_COMPENSATION_QUALITY`], and blends linearly otherwise.
     fn cpu_interpolate(&self, prev: &Frame, curr: &Frame, t: f32) -> Result<Frame> {
         use crate::types::FrameData;
 
@@ -331,7 +540,7 @@ impl SmoothMotion {
         let prev_data = match &prev.data {
             FrameData::Memory(data) => data,
             FrameData::DmaBuf { .. } => {
-                // Fall back to frame duplication for DMA-BUF
+                // NvOFFRUC couldn't import it, so duplicate
                 return self.duplicate_frame(curr, t);
             }
         };
@@ -351,19 +560,25 @@ impl SmoothMotion {
             return self.duplicate_frame(curr, t);
         }
 
-        // Linear blend: result = prev * (1-t) + curr * t
-        // Use fixed-point math for performance: multiply by 256, then >> 8
-        let t_fixed = (t * 256.0).round() as u16;
-        let inv_t_fixed = 256 - t_fixed;
-
-        let blended: Vec<u8> = prev_data
-            .iter()
-            .zip(curr_data.iter())
-            .map(|(&p, &c)| {
-                // Fixed-point blend: (p * inv_t + c * t) >> 8
-                (((p as u16 * inv_t_fixed) + (c as u16 * t_fixed)) >> 8) as u8
-            })
-            .collect();
+        let motion_compensated = (self.config.quality > MOTION_COMPENSATION_QUALITY)
+            .then(|| motion_compensated_blend(prev_data, curr_data, &curr.format, t))
+            .flatten();
+
+        let blended = motion_compensated.unwrap_or_else(|| {
+            // Linear blend: result = prev * (1-t) + curr * t
+            // Use fixed-point math for performance: multiply by 256, then >> 8
+            let t_fixed = (t * 256.0).round() as u16;
+            let inv_t_fixed = 256 - t_fixed;
+
+            prev_data
+                .iter()
+                .zip(curr_data.iter())
+                .map(|(&p, &c)| {
+                    // Fixed-point blend: (p * inv_t + c * t) >> 8
+                    (((p as u16 * inv_t_fixed) + (c as u16 * t_fixed)) >> 8) as u8
+                })
+                .collect()
+        });
 
         // Interpolate presentation timestamp
         let interpolated_pts = interpolate_pts(prev.pts, curr.pts, t);
@@ -381,28 +596,16 @@ impl SmoothMotion {
     /// For Memory frames, this clones the data.
     /// For DMA-BUF frames, this references the same fd (no interpolation).
     ///
-    /// # DMA-BUF Interpolation (Future Work)
-    ///
-    /// True DMA-BUF interpolation would require:
-    /// 1. Import both DMA-BUF frames to GPU memory (CUDA, Vulkan, or OpenGL)
-    /// 2. Run interpolation shader/kernel on GPU
-    /// 3. Export result as new DMA-BUF or copy to CPU memory
-    ///
-    /// This is blocked on:
-    /// - CUDA interop for DMA-BUF import (`cuExternalMemoryGetMappedBuffer`)
-    /// - Or Vulkan compute shader implementation
-    ///
-    /// For now, DMA-BUF frames fall back to frame duplication (no interpolation),
-    /// which avoids artifacts but provides no smoothing benefit.
+    /// DMA-BUF frames only get here when NvOFFRUC can't take them: no CUDA
+    /// import, a tiled modifier, or a failed import. That avoids artifacts
+    /// but provides no smoothing benefit.
     fn duplicate_frame(&self, frame: &Frame, _t: f32) -> Result<Frame> {
         use crate::types::FrameData;
 
         let data = match &frame.data {
             FrameData::Memory(bytes) => FrameData::Memory(bytes.clone()),
             FrameData::DmaBuf { fd, offset, modifier } => {
-                // DMA-BUF interpolation requires GPU compute - not yet implemented.
-                // For now, just reference the same fd (caller must handle lifetime).
-                // This means no smoothing for DMA-BUF frames, but also no artifacts.
+                // Just reference the same fd (caller must handle lifetime)
                 FrameData::DmaBuf {
                     fd: *fd,
                     offset: *offset,
@@ -425,16 +628,99 @@ impl SmoothMotion {
     }
 
     /// Get effective output multiplier
+    ///
+    /// This is the configured mode's; in adaptive mode see
+    /// [`current_multiplier`](Self::current_multiplier).
     pub fn multiplier(&self) -> u32 {
         self.config.mode.multiplier()
     }
 
+    /// Multiplier the next frame pair is interpolated with
+    ///
+    /// Follows recent motion in adaptive mode, and is the mode's fixed
+    /// multiplier otherwise.
+    pub fn current_multiplier(&self) -> u32 {
+        match self.adaptive {
+            Some(ref adaptive) => adaptive.multiplier(),
+            None => self.config.mode.multiplier(),
+        }
+    }
+
     /// Check if optical flow is being used
     pub fn using_optical_flow(&self) -> bool {
         self.optical_flow_available && self.config.gpu_accelerated
     }
 }
 
+/// Luma histogram difference between two frames
+///
+/// Returns a value between 0.0 (identical) and 1.0 (completely different),
+/// or `None` when the frames can't be compared on the CPU (DMA-BUF data or
+/// mismatched dimensions).
+pub fn frame_difference(prev: &Frame, curr: &Frame) -> Option<f32> {
+    use crate::types::FrameData;
+
+    if prev.format.width != curr.format.width || prev.format.height != curr.format.height {
+        return None;
+    }
+
+    match (&prev.data, &curr.data) {
+        (FrameData::Memory(prev_data), FrameData::Memory(curr_data)) => {
+            Some(compute_histogram_difference(
+                prev_data,
+                curr_data,
+                prev.format.width,
+                prev.format.height,
+                prev.format.stride,
+                prev.format.fourcc,
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Sample every Nth pixel in each dimension (1/16 of pixels at 4)
+const SAMPLE_STEP: usize = 4;
+
+/// Cheap hash of sampled pixels for tracing frames through the pipeline
+///
+/// Uses the same 1/16 sampling as scene detection and FNV-1a over the raw
+/// bytes, so it costs far less than a full-frame hash. Identical frames hash
+/// equal; it is not meant for anything stronger than spotting drops and
+/// duplicates. Returns `None` for DMA-BUF frames.
+pub fn frame_hash(frame: &Frame) -> Option<u32> {
+    use crate::formats::bytes_per_pixel;
+    use crate::types::FrameData;
+
+    const FNV_OFFSET: u32 = 0x811c_9dc5;
+    const FNV_PRIME: u32 = 0x0100_0193;
+
+    let FrameData::Memory(ref data) = frame.data else {
+        return None;
+    };
+
+    let format = &frame.format;
+    let bpp = bytes_per_pixel(format.fourcc) as usize;
+    let stride = format.stride as usize;
+
+    let mut hash = FNV_OFFSET;
+    for y in (0..format.height as usize).step_by(SAMPLE_STEP) {
+        let Some(row) = data.get(y * stride..(y + 1) * stride) else {
+            break;
+        };
+        for x in (0..format.width as usize).step_by(SAMPLE_STEP) {
+            let Some(pixel) = row.get(x * bpp..(x + 1) * bpp) else {
+                break;
+            };
+            for &byte in pixel {
+                hash = (hash ^ byte as u32).wrapping_mul(FNV_PRIME);
+            }
+        }
+    }
+
+    Some(hash)
+}
+
 /// Compute histogram difference between two frames for scene change detection
 ///
 /// Returns a value between 0.0 (identical) and 1.0+ (completely different)
@@ -450,39 +736,23 @@ fn compute_histogram_difference(
     let mut prev_hist = [0u32; BINS];
     let mut curr_hist = [0u32; BINS];
 
-    // Determine bytes per pixel based on fourcc format
-    // Common formats: XRGB8888 (0x34325258), ARGB8888 (0x34325241), BGRA (0x41524742)
-    let bytes_per_pixel: u32 = match fourcc {
-        0x34325258 | 0x34325241 | 0x41524742 | 0x34324142 => 4, // XRGB, ARGB, BGRA, ABGR
-        _ => return 0.0, // Unknown format, assume no scene change
+    let format = FrameFormat {
+        width,
+        height,
+        fourcc,
+        stride,
+    };
+    let (Some(prev), Some(curr)) = (sampled_luma(prev, &format), sampled_luma(curr, &format))
+    else {
+        return 0.0; // Unknown format, assume no scene change
     };
 
-    // Sample every 4th pixel in each dimension for performance (1/16 of pixels)
     let mut sample_count = 0u32;
-    for y in (0..height).step_by(4) {
-        for x in (0..width).step_by(4) {
-            let offset = (y * stride + x * bytes_per_pixel) as usize;
-
-            // Ensure we have enough bytes to read RGB
-            if offset + 3 <= prev.len() && offset + 3 <= curr.len() {
-                // For BGRA/XRGB formats: B=0, G=1, R=2 (or R=0, G=1, B=2 for RGB)
-                // Use standard luminance formula: Y = 0.299*R + 0.587*G + 0.114*B
-                // Fixed-point: Y = (77*R + 150*G + 29*B) >> 8
-                let prev_luma = ((77 * prev[offset + 2] as u32
-                    + 150 * prev[offset + 1] as u32
-                    + 29 * prev[offset] as u32)
-                    >> 8) as usize;
-                let curr_luma = ((77 * curr[offset + 2] as u32
-                    + 150 * curr[offset + 1] as u32
-                    + 29 * curr[offset] as u32)
-                    >> 8) as usize;
-
-                // Map 0-255 to 0-63 bins
-                prev_hist[prev_luma.min(255) >> 2] += 1;
-                curr_hist[curr_luma.min(255) >> 2] += 1;
-                sample_count += 1;
-            }
-        }
+    for (&prev_luma, &curr_luma) in prev.iter().zip(&curr) {
+        // Map 0-255 to 0-63 bins
+        prev_hist[prev_luma as usize >> 2] += 1;
+        curr_hist[curr_luma as usize >> 2] += 1;
+        sample_count += 1;
     }
 
     if sample_count == 0 {
@@ -503,6 +773,39 @@ fn compute_histogram_difference(
     chi_sq / (sample_count as f32 * 2.0)
 }
 
+/// Luma of every [`SAMPLE_STEP`]th pixel in each dimension (1/16 of pixels)
+///
+/// NV12 and I420 are read straight from the Y plane; every other format
+/// to_bgra8 understands is compared on the luma of its BGRA pixels.
+fn sampled_luma(data: &[u8], format: &FrameFormat) -> Option<Vec<u8>> {
+    let width = format.width as usize;
+    let height = format.height as usize;
+    let positions = (0..height)
+        .step_by(SAMPLE_STEP)
+        .flat_map(|y| (0..width).step_by(SAMPLE_STEP).map(move |x| (x, y)));
+
+    if let PixelLayout::Yuv420 { .. } = bytes_per_pixel_or_planar(format.fourcc) {
+        let y_plane = frame_planes(format)[0];
+        if data.len() < y_plane.end() {
+            return None;
+        }
+        return Some(positions.map(|(x, y)| data[y_plane.at(x, y)]).collect());
+    }
+
+    let bgra = to_bgra8(data, format, YuvMatrix::Bt709)?;
+    Some(
+        positions
+            .map(|(x, y)| {
+                // BGRA: B=0, G=1, R=2
+                // Use standard luminance formula: Y = 0.299*R + 0.587*G + 0.114*B
+                // Fixed-point: Y = (77*R + 150*G + 29*B) >> 8
+                let pixel = &bgra[(y * width + x) * 4..];
+                ((77 * pixel[2] as u32 + 150 * pixel[1] as u32 + 29 * pixel[0] as u32) >> 8) as u8
+            })
+            .collect(),
+    )
+}
+
 /// Interpolate presentation timestamp between two frames
 fn interpolate_pts(prev_pts: u64, curr_pts: u64, t: f32) -> u64 {
     let duration = curr_pts.saturating_sub(prev_pts);
@@ -614,7 +917,7 @@ mod tests {
     #[test]
     fn test_scene_change_same_frame() {
         let config = SmoothMotionConfig::default();
-        let smooth = SmoothMotion::new(config);
+        let mut smooth = SmoothMotion::new(config);
         let frame = create_test_frame(1920, 1080, 128);
 
         // Same frame should NOT trigger scene change
@@ -624,7 +927,7 @@ mod tests {
     #[test]
     fn test_scene_change_similar_frames() {
         let config = SmoothMotionConfig::default();
-        let smooth = SmoothMotion::new(config);
+        let mut smooth = SmoothMotion::new(config);
         let frame1 = create_test_frame(1920, 1080, 128);
         let frame2 = create_test_frame(1920, 1080, 130); // Slightly different
 
@@ -635,7 +938,7 @@ mod tests {
     #[test]
     fn test_scene_change_different_frames() {
         let config = SmoothMotionConfig::default();
-        let smooth = SmoothMotion::new(config);
+        let mut smooth = SmoothMotion::new(config);
         let frame1 = create_test_frame(1920, 1080, 0); // Black
         let frame2 = create_test_frame(1920, 1080, 255); // White
 
@@ -646,7 +949,7 @@ mod tests {
     #[test]
     fn test_scene_change_different_dimensions() {
         let config = SmoothMotionConfig::default();
-        let smooth = SmoothMotion::new(config);
+        let mut smooth = SmoothMotion::new(config);
         let frame1 = create_test_frame(1920, 1080, 128);
         let frame2 = create_test_frame(1280, 720, 128);
 
@@ -654,6 +957,111 @@ mod tests {
         assert!(smooth.detect_scene_change(&frame1, &frame2));
     }
 
+    #[test]
+    fn test_scene_threshold_tuner_separates_clusters() {
+        let mut tuner = SceneThresholdTuner::new();
+        assert_eq!(tuner.threshold(), DEFAULT_SCENE_THRESHOLD);
+
+        // Mostly similar frames (0.02-0.08) with a cut every 60 frames (~0.7)
+        for i in 0..SCENE_CALIBRATION_FRAMES {
+            let diff = if i % 60 == 59 {
+                0.7
+            } else {
+                0.02 + (i % 7) as f32 * 0.01
+            };
+            tuner.observe(diff);
+        }
+
+        assert!(tuner.is_calibrated());
+        let threshold = tuner.threshold();
+        assert!(threshold > 0.08 && threshold < 0.7, "threshold {}", threshold);
+
+        // Later samples don't move it
+        tuner.observe(0.9);
+        assert_eq!(tuner.threshold(), threshold);
+    }
+
+    #[test]
+    fn test_auto_scene_threshold_from_frames() {
+        let mut smooth = SmoothMotion::new(SmoothMotionConfig::default());
+        let gray = create_test_frame(64, 64, 128);
+        let black = create_test_frame(64, 64, 0);
+
+        // Static content with occasional hard cuts
+        for i in 0..SCENE_CALIBRATION_FRAMES {
+            let next = if i % 45 == 44 { &black } else { &gray };
+            smooth.detect_scene_change(&gray, next);
+        }
+
+        let threshold = smooth.scene_threshold();
+        let cut = frame_difference(&gray, &black).unwrap();
+        assert!(threshold > 0.0 && threshold < cut, "threshold {}", threshold);
+        assert!(smooth.detect_scene_change(&gray, &black));
+        assert!(!smooth.detect_scene_change(&gray, &gray));
+    }
+
+    #[test]
+    fn test_fixed_scene_threshold() {
+        let config = SmoothMotionConfig {
+            scene_threshold: SceneThreshold::Fixed(0.25),
+            ..Default::default()
+        };
+        let smooth = SmoothMotion::new(config);
+        assert_eq!(smooth.scene_threshold(), 0.25);
+        assert!(smooth.scene_tuner.is_none());
+    }
+
+    #[test]
+    fn test_adaptive_multiplier_follows_motion() {
+        let config = SmoothMotionConfig {
+            mode: FrameGenMode::Adaptive,
+            scene_threshold: SceneThreshold::Fixed(DEFAULT_SCENE_THRESHOLD),
+            ..Default::default()
+        };
+        let mut smooth = SmoothMotion::new(config);
+        assert_eq!(smooth.current_multiplier(), 2);
+
+        let mut pts = 0;
+        let mut feed = |smooth: &mut SmoothMotion, frame: Frame| {
+            pts += 1000;
+            smooth.process(Arc::new(Frame { pts, ..frame })).unwrap().len()
+        };
+        // Busy frames change a quarter of the picture: plenty of motion,
+        // but well under the scene change threshold
+        let busy_frame = |i: usize| {
+            let mut frame = create_test_frame(64, 64, 100);
+            if i % 2 == 1 {
+                if let FrameData::Memory(ref mut data) = frame.data {
+                    data[..64 * 16 * 4].fill(200);
+                }
+            }
+            frame
+        };
+
+        // Calm: the same picture over and over
+        for _ in 0..ADAPTIVE_WINDOW * 2 {
+            feed(&mut smooth, create_test_frame(64, 64, 100));
+        }
+        assert_eq!(smooth.current_multiplier(), 4);
+        assert_eq!(feed(&mut smooth, create_test_frame(64, 64, 100)), 4);
+
+        for i in 0..ADAPTIVE_WINDOW * 2 {
+            feed(&mut smooth, busy_frame(i));
+        }
+        assert_eq!(smooth.current_multiplier(), 1);
+        // Passthrough: only the captured frame comes out
+        assert_eq!(feed(&mut smooth, busy_frame(1)), 1);
+
+        for _ in 0..ADAPTIVE_WINDOW * 2 {
+            feed(&mut smooth, create_test_frame(64, 64, 100));
+        }
+        assert_eq!(smooth.current_multiplier(), 4);
+
+        // Fixed modes never change
+        let smooth = SmoothMotion::new(SmoothMotionConfig::low_latency());
+        assert_eq!(smooth.current_multiplier(), 2);
+    }
+
     #[test]
     fn test_cpu_interpolation_midpoint() {
         let config = SmoothMotionConfig::default();
@@ -691,6 +1099,91 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cpu_interpolation_follows_motion_above_quality_threshold() {
+        // A white bar 8 px wide moves from x=16 to x=24
+        let bar_frame = |bar_x: usize| {
+            let mut frame = create_test_frame(64, 32, 0);
+            if let FrameData::Memory(ref mut data) = frame.data {
+                for y in 0..32 {
+                    data[(y * 64 + bar_x) * 4..(y * 64 + bar_x + 8) * 4].fill(255);
+                }
+            }
+            frame
+        };
+        let (frame1, frame2) = (bar_frame(16), bar_frame(24));
+        let left_edge = |frame: &Frame| frame.data.as_memory().unwrap()[16 * 4];
+        let middle = |frame: &Frame| frame.data.as_memory().unwrap()[22 * 4];
+
+        let smooth = SmoothMotion::new(SmoothMotionConfig::high_quality());
+        let interp = smooth.cpu_interpolate(&frame1, &frame2, 0.5).unwrap();
+        assert_eq!(middle(&interp), 255);
+        assert_eq!(left_edge(&interp), 0);
+
+        // Low quality keeps the fast cross-fade
+        let smooth = SmoothMotion::new(SmoothMotionConfig::low_latency());
+        let interp = smooth.cpu_interpolate(&frame1, &frame2, 0.5).unwrap();
+        assert_eq!(middle(&interp), 127);
+        assert_eq!(left_edge(&interp), 127);
+    }
+
+    #[test]
+    fn test_cpu_interpolation_nv12_gradient() {
+        // 64x32 NV12: the same luma gradient under changing flat chroma
+        let luma = |x: usize, y: usize| 16 + x as u8 * 3 + y as u8;
+        let nv12_frame = |uv: [u8; 2]| {
+            let mut data: Vec<u8> =
+                (0..32).flat_map(|y| (0..64).map(move |x| luma(x, y))).collect();
+            for _ in 0..16 * 32 {
+                data.extend_from_slice(&uv);
+            }
+            Frame {
+                format: FrameFormat {
+                    width: 64,
+                    height: 32,
+                    fourcc: crate::formats::fourcc::NV12,
+                    stride: 64,
+                },
+                data: FrameData::Memory(data),
+                pts: 0,
+                hdr_metadata: None,
+            }
+        };
+        let frame1 = nv12_frame([90, 200]);
+        let frame2 = nv12_frame([110, 180]);
+
+        for config in [SmoothMotionConfig::high_quality(), SmoothMotionConfig::low_latency()] {
+            let smooth = SmoothMotion::new(config);
+            let interp = smooth.cpu_interpolate(&frame1, &frame2, 0.5).unwrap();
+            let data = interp.data.as_memory().unwrap();
+            assert_eq!(data.len(), 64 * 32 * 3 / 2);
+
+            // The gradient is untouched
+            for (i, &y_sample) in data[..64 * 32].iter().enumerate() {
+                assert_eq!(y_sample, luma(i % 64, i / 64), "luma at {}", i);
+            }
+            // U blends with U and V with V
+            for uv in data[64 * 32..].chunks_exact(2) {
+                assert_eq!(uv, [100, 190]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_histogram_difference_nv12_reads_y_plane() {
+        let frame = |luma: u8, chroma: u8| {
+            let mut data = vec![luma; 64 * 64];
+            data.resize(64 * 64 * 3 / 2, chroma);
+            data
+        };
+        let diff = |a: &[u8], b: &[u8]| {
+            compute_histogram_difference(a, b, 64, 64, 64, crate::formats::fourcc::NV12)
+        };
+        // Chroma alone doesn't register; luma does
+        assert_eq!(diff(&frame(100, 90), &frame(100, 160)), 0.0);
+        assert!(diff(&frame(40, 128), &frame(200, 128)) > 0.9);
+    }
+
     #[test]
     fn test_pts_interpolation() {
         // Test PTS interpolation at various points
@@ -700,6 +1193,21 @@ mod tests {
         assert_eq!(interpolate_pts(1000, 2000, 0.25), 1250);
     }
 
+    #[test]
+    fn test_frame_hash_identical_frames() {
+        let a = create_test_frame(64, 64, 100);
+        let b = create_test_frame(64, 64, 100);
+        assert_eq!(frame_hash(&a), frame_hash(&b));
+        assert!(frame_hash(&a).is_some());
+    }
+
+    #[test]
+    fn test_frame_hash_different_frames() {
+        let a = create_test_frame(64, 64, 100);
+        let b = create_test_frame(64, 64, 101);
+        assert_ne!(frame_hash(&a), frame_hash(&b));
+    }
+
     #[test]
     fn test_histogram_difference_identical() {
         let data = vec![128u8; 1920 * 1080 * 4];
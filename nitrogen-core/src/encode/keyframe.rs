@@ -0,0 +1,92 @@
+//! Rate limit for forced keyframes
+//!
+//! Keyframes are requested by `nitrogen keyframe`, WebRTC viewers reporting
+//! picture loss, source switches and static content refreshes. Each forced
+//! IDR costs several times a normal frame, so a burst of requests would
+//! starve the frames around it. Requests closer together than
+//! [`MIN_KEYFRAME_INTERVAL`] stay pending and are served once it has
+//! passed: a burst becomes one keyframe now and at most one more later.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Least time between two forced keyframes
+pub const MIN_KEYFRAME_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Decides which frames honour keyframe requests
+///
+/// Time is counted in frames, the same clock the encoders stamp timestamps
+/// with, so skipped frames count too.
+#[derive(Debug, Clone)]
+pub struct KeyframeDebounce {
+    /// Frames between two forced keyframes
+    interval: u64,
+    /// Frame of the last forced keyframe
+    last: Option<u64>,
+}
+
+impl KeyframeDebounce {
+    /// Rate limit at `fps` frames per second
+    pub fn new(fps: u32) -> Self {
+        Self::with_interval(fps, MIN_KEYFRAME_INTERVAL)
+    }
+
+    /// Rate limit with another minimum interval (zero disables it)
+    pub fn with_interval(fps: u32, interval: Duration) -> Self {
+        Self {
+            interval: (f64::from(fps) * interval.as_secs_f64()).round() as u64,
+            last: None,
+        }
+    }
+
+    /// Whether `frame` should be forced to a keyframe
+    ///
+    /// Clears `requested` when it does; inside the interval the request is
+    /// left pending for a later frame.
+    pub fn take(&mut self, requested: &AtomicBool, frame: u64) -> bool {
+        if self.last.is_some_and(|last| frame < last + self.interval) {
+            return false;
+        }
+        if !requested.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+        self.last = Some(frame);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_are_debounced() {
+        let requested = AtomicBool::new(false);
+        let mut debounce = KeyframeDebounce::new(60);
+
+        // One request per frame for two seconds
+        let forced: Vec<u64> = (0..120)
+            .filter(|&frame| {
+                requested.store(true, Ordering::SeqCst);
+                debounce.take(&requested, frame)
+            })
+            .collect();
+        assert_eq!(forced, vec![0, 60]);
+
+        // The last request is still pending and served after the interval
+        assert!(!debounce.take(&requested, 119));
+        assert!(debounce.take(&requested, 120));
+        assert!(!debounce.take(&requested, 200));
+    }
+
+    #[test]
+    fn test_zero_interval_serves_every_request() {
+        let requested = AtomicBool::new(false);
+        let mut debounce = KeyframeDebounce::with_interval(30, Duration::ZERO);
+        for frame in 0..3 {
+            requested.store(true, Ordering::SeqCst);
+            assert!(debounce.take(&requested, frame));
+        }
+        assert!(!debounce.take(&requested, 3));
+    }
+}
@@ -0,0 +1,360 @@
+//! Mock video encoder for headless tests
+//!
+//! Produces deterministic fake packets in place of NVENC so the pipeline,
+//! muxers and channel handling can run in CI without an NVIDIA GPU. Packets
+//! follow the configured bitrate and GOP, but carry no decodable picture.
+//! A delay can hold packets back the way NVENC lookahead does, so flushing
+//! on stop can be exercised too.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+use tracing::{debug, info};
+
+use super::{EncodedPacket, Encoder, KeyframeDebounce, SessionSlot};
+use crate::config::CaptureConfig;
+use crate::error::Result;
+use crate::types::Frame;
+
+/// Keyframe interval in seconds when no GOP is set
+const DEFAULT_GOP_SECONDS: u32 = 2;
+
+/// How much larger than an average frame a keyframe is
+const KEYFRAME_SIZE_FACTOR: usize = 4;
+
+/// Smallest packet produced (start code, NAL header and a little payload)
+const MIN_PACKET_SIZE: usize = 16;
+
+/// Average packet size at `kbps` and `fps`
+fn frame_size(kbps: u32, fps: u32) -> usize {
+    let bytes_per_second = kbps as usize * 1000 / 8;
+    (bytes_per_second / fps as usize).max(MIN_PACKET_SIZE)
+}
+
+/// Encoder that emits fake packets instead of encoding
+pub struct MockEncoder {
+    /// Encoded data sender
+    output_tx: broadcast::Sender<Arc<EncodedPacket>>,
+    /// Frame counter (also the timestamp, in 1/fps units like NVENC)
+    frame_count: u64,
+    /// Output width
+    output_width: u32,
+    /// Output height
+    output_height: u32,
+    /// Frames per second, for packet sizes
+    fps: u32,
+    /// Frames per keyframe
+    gop: u32,
+    /// Average packet size for the configured bitrate
+    frame_size: usize,
+    /// Set when the next frame should be a keyframe
+    keyframe_requested: Arc<AtomicBool>,
+    /// Spaces forced keyframes apart like NVENC's
+    keyframe_debounce: KeyframeDebounce,
+    /// Frames since the last keyframe (None = no keyframe yet)
+    since_keyframe: Option<u32>,
+    /// Packets held back before they are sent
+    delay: usize,
+    /// Encoded packets not sent yet
+    held: VecDeque<EncodedPacket>,
+    /// Session slot held while the encoder exists
+    _session: Option<SessionSlot>,
+}
+
+impl MockEncoder {
+    /// Create a mock encoder for a config
+    ///
+    /// The GOP defaults to two seconds of frames; packet sizes average out to
+    /// the config's effective bitrate.
+    pub fn new(config: &CaptureConfig) -> Self {
+        let fps = config.fps().max(1);
+        let (output_tx, _) = broadcast::channel(64);
+
+        info!(
+            "Using mock encoder: {}x{} @ {}fps, {} kbps",
+            config.width(),
+            config.height(),
+            fps,
+            config.effective_bitrate()
+        );
+
+        Self {
+            output_tx,
+            frame_count: 0,
+            output_width: config.width(),
+            output_height: config.height(),
+            fps,
+            gop: config.keyframe_interval.unwrap_or(fps * DEFAULT_GOP_SECONDS),
+            frame_size: frame_size(config.effective_bitrate(), fps),
+            keyframe_requested: Arc::new(AtomicBool::new(false)),
+            keyframe_debounce: KeyframeDebounce::new(fps),
+            since_keyframe: None,
+            delay: 0,
+            held: VecDeque::new(),
+            _session: None,
+        }
+    }
+
+    /// Set the keyframe interval in frames (0 = first frame only)
+    pub fn with_gop(mut self, gop: u32) -> Self {
+        self.gop = gop;
+        self
+    }
+
+    /// Hold back `frames` packets until later frames or a flush push them out
+    pub fn with_delay(mut self, frames: usize) -> Self {
+        self.delay = frames;
+        self
+    }
+
+    /// Send up to `max` held packets, oldest first
+    fn release(&mut self, max: usize) -> usize {
+        let count = max.min(self.held.len());
+        for packet in self.held.drain(..count) {
+            // Ignore error if no receivers
+            let _ = self.output_tx.send(Arc::new(packet));
+        }
+        count
+    }
+
+    /// Hold a session slot until the encoder is dropped
+    pub fn with_session(mut self, slot: SessionSlot) -> Self {
+        self._session = Some(slot);
+        self
+    }
+
+    /// Build the packet for the current frame
+    ///
+    /// Keyframes are larger so the average over a GOP stays close to the
+    /// bitrate. The payload is an H.264-style NAL whose bytes depend only on
+    /// the frame number.
+    fn packet(&self, keyframe: bool) -> EncodedPacket {
+        let gop = self.gop.max(1) as usize;
+        let delta_size = (self.frame_size * gop / (gop + KEYFRAME_SIZE_FACTOR - 1))
+            .max(MIN_PACKET_SIZE);
+        let size = if keyframe {
+            delta_size * KEYFRAME_SIZE_FACTOR
+        } else {
+            delta_size
+        };
+
+        let mut data = Vec::with_capacity(size);
+        data.extend_from_slice(&[0, 0, 0, 1, if keyframe { 0x65 } else { 0x41 }]);
+        let seed = self.frame_count as u8;
+        data.extend((data.len()..size).map(|i| seed.wrapping_add(i as u8)));
+
+        EncodedPacket {
+            data,
+            pts: self.frame_count as i64,
+            dts: self.frame_count as i64,
+            keyframe,
+        }
+    }
+}
+
+impl Encoder for MockEncoder {
+    fn encode(&mut self, _frame: &Frame) -> Result<()> {
+        let requested = self
+            .keyframe_debounce
+            .take(&self.keyframe_requested, self.frame_count);
+        let keyframe = match self.since_keyframe {
+            None => true,
+            Some(since) => requested || (self.gop > 0 && since + 1 >= self.gop),
+        };
+
+        let packet = self.packet(keyframe);
+        self.since_keyframe = Some(if keyframe {
+            0
+        } else {
+            self.since_keyframe.unwrap_or(0) + 1
+        });
+        self.frame_count += 1;
+
+        self.held.push_back(packet);
+        let ready = self.held.len().saturating_sub(self.delay);
+        self.release(ready);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        debug!("Flushing mock encoder ({} frames encoded)", self.frame_count);
+        self.release(usize::MAX);
+        Ok(())
+    }
+
+    fn drain(&mut self, max: usize) -> Result<usize> {
+        Ok(self.release(max))
+    }
+
+    fn queued_packets(&self) -> usize {
+        self.output_tx.len()
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Arc<EncodedPacket>> {
+        self.output_tx.subscribe()
+    }
+
+    fn output_size(&self) -> (u32, u32) {
+        (self.output_width, self.output_height)
+    }
+
+    fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    fn request_keyframe(&self) {
+        self.keyframe_requested.store(true, Ordering::SeqCst);
+    }
+
+    fn keyframe_requester(&self) -> Arc<AtomicBool> {
+        self.keyframe_requested.clone()
+    }
+
+    fn skip_frame(&mut self) {
+        self.frame_count += 1;
+        if let Some(since) = self.since_keyframe.as_mut() {
+            *since += 1;
+        }
+    }
+
+    fn set_bitrate(&mut self, kbps: u32) -> Result<()> {
+        // Resized from the next frame on, which is a keyframe like NVENC's
+        self.frame_size = frame_size(kbps, self.fps);
+        self.request_keyframe();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Preset;
+    use crate::types::{FrameData, FrameFormat};
+
+    fn test_frame() -> Frame {
+        Frame {
+            format: FrameFormat {
+                width: 2,
+                height: 2,
+                fourcc: 0x34325258,
+                stride: 8,
+            },
+            data: FrameData::Memory(vec![0; 16]),
+            pts: 0,
+            hdr_metadata: None,
+        }
+    }
+
+    fn encode_frames(encoder: &mut MockEncoder, count: usize) -> Vec<Arc<EncodedPacket>> {
+        let mut rx = encoder.subscribe();
+        let frame = test_frame();
+        (0..count)
+            .map(|_| {
+                encoder.encode(&frame).unwrap();
+                rx.try_recv().unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_keyframes_follow_gop() {
+        let config = CaptureConfig::monitor("test");
+        let mut encoder = MockEncoder::new(&config).with_gop(5);
+        let packets = encode_frames(&mut encoder, 11);
+
+        let keyframes: Vec<i64> = packets.iter().filter(|p| p.keyframe).map(|p| p.pts).collect();
+        assert_eq!(keyframes, vec![0, 5, 10]);
+        assert_eq!(encoder.frame_count(), 11);
+    }
+
+    #[test]
+    fn test_requested_keyframe() {
+        let config = CaptureConfig::monitor("test");
+        let mut encoder = MockEncoder::new(&config).with_gop(0);
+        encode_frames(&mut encoder, 3);
+
+        encoder.keyframe_requester().store(true, Ordering::SeqCst);
+        let packets = encode_frames(&mut encoder, 2);
+        assert!(packets[0].keyframe);
+        assert!(!packets[1].keyframe);
+    }
+
+    #[test]
+    fn test_packet_sizes_follow_bitrate() {
+        let low = CaptureConfig::monitor("test")
+            .with_preset(Preset::P1080_60)
+            .with_bitrate(2_000);
+        let high = low.clone().with_bitrate(8_000);
+
+        let low_size: usize = encode_frames(&mut MockEncoder::new(&low), 120)
+            .iter()
+            .map(|p| p.data.len())
+            .sum();
+        let high_size: usize = encode_frames(&mut MockEncoder::new(&high), 120)
+            .iter()
+            .map(|p| p.data.len())
+            .sum();
+
+        // Two seconds at 2 Mbps is about 500 kB
+        assert!((450_000..550_000).contains(&low_size), "{}", low_size);
+        assert!(high_size > low_size * 3);
+    }
+
+    #[test]
+    fn test_set_bitrate() {
+        let config = CaptureConfig::monitor("test")
+            .with_preset(Preset::P1080_60)
+            .with_bitrate(8_000);
+        let mut encoder = MockEncoder::new(&config).with_gop(0);
+        let before = encode_frames(&mut encoder, 2);
+
+        encoder.set_bitrate(2_000).unwrap();
+        let after = encode_frames(&mut encoder, 2);
+        assert!(after[0].keyframe);
+        assert!(after[1].data.len() * 3 < before[1].data.len());
+    }
+
+    #[test]
+    fn test_output_is_deterministic() {
+        let config = CaptureConfig::monitor("test");
+        let first = encode_frames(&mut MockEncoder::new(&config), 4);
+        let second = encode_frames(&mut MockEncoder::new(&config), 4);
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a.data, b.data);
+            assert_eq!(a.pts, b.pts);
+        }
+    }
+
+    #[test]
+    fn test_skip_frame_advances_clock() {
+        let config = CaptureConfig::monitor("test");
+        let mut encoder = MockEncoder::new(&config);
+        encode_frames(&mut encoder, 1);
+        encoder.skip_frame();
+        let packets = encode_frames(&mut encoder, 1);
+        assert_eq!(packets[0].pts, 2);
+    }
+
+    #[test]
+    fn test_delay_holds_packets() {
+        let config = CaptureConfig::monitor("test");
+        let mut encoder = MockEncoder::new(&config).with_delay(3);
+        let mut rx = encoder.subscribe();
+        let frame = test_frame();
+        for _ in 0..5 {
+            encoder.encode(&frame).unwrap();
+        }
+        assert_eq!(rx.try_recv().unwrap().pts, 0);
+        assert_eq!(rx.try_recv().unwrap().pts, 1);
+        assert!(rx.try_recv().is_err());
+
+        assert_eq!(encoder.drain(2).unwrap(), 2);
+        assert_eq!(encoder.queued_packets(), 2);
+        encoder.flush().unwrap();
+        let rest: Vec<i64> = std::iter::from_fn(|| rx.try_recv().ok()).map(|p| p.pts).collect();
+        assert_eq!(rest, vec![2, 3, 4]);
+        assert_eq!(encoder.drain(8).unwrap(), 0);
+    }
+}
@@ -2,28 +2,194 @@
 //!
 //! This module provides:
 //! - Hardware-accelerated video encoding using NVIDIA's NVENC
+//! - Bitrate and constant-quality rate control
+//! - Forced keyframes on request, rate limited
+//! - Zero-copy DMA-BUF input to NVENC via CUDA external memory
 //! - Audio encoding (AAC, Opus) for recording
 //! - Smooth Motion frame interpolation for streaming
+//! - Frame skipping for static content (slides, documents)
+//! - SMPTE timecode for the overlay and HEVC/AV1 bitstreams
+//! - A mock encoder for headless tests (`mock-encoder` feature)
+//! - A JSON-lines packet dump for debugging (`NITROGEN_DUMP_PACKETS`)
 
 mod audio;
+mod cuda;
+mod cuda_sys;
 mod frame_gen;
+mod keyframe;
+#[cfg(feature = "mock-encoder")]
+mod mock;
+mod motion;
 mod nvenc;
 mod nvfruc;
 mod nvfruc_sys;
+mod packet_dump;
+mod rate_control;
 mod scaler;
+mod sessions;
+mod static_content;
+mod timecode;
 mod tonemap;
 
 pub use audio::{
     audio_codec_available, list_available_audio_encoders, AudioEncoder, AudioEncoderInfo,
     EncodedAudioPacket,
 };
-pub use frame_gen::{FrameGenMode, SmoothMotion, SmoothMotionConfig, supports_smooth_motion};
-pub use nvenc::{EncodedPacket, NvencEncoder};
+pub use cuda::cuda_import_available;
+pub use frame_gen::{
+    frame_difference, frame_hash, supports_smooth_motion, AdaptiveMultiplier, FrameGenMode,
+    SceneThreshold, SceneThresholdTuner, SmoothMotion, SmoothMotionConfig, ADAPTIVE_BUSY_MOTION,
+    ADAPTIVE_CALM_MOTION, ADAPTIVE_WINDOW, DEFAULT_SCENE_THRESHOLD, MOTION_COMPENSATION_QUALITY,
+    SCENE_CALIBRATION_FRAMES,
+};
+pub use keyframe::{KeyframeDebounce, MIN_KEYFRAME_INTERVAL};
+#[cfg(feature = "mock-encoder")]
+pub use mock::MockEncoder;
+pub use nvenc::{zero_copy_supported, EncodedPacket, NvencEncoder};
 pub use nvfruc::{nvfruc_available, NvFruc};
-pub use scaler::FrameScaler;
-pub use tonemap::{TonemapAlgorithm, TonemapConfig, TonemapMode, Tonemapper};
+pub use packet_dump::{
+    nal_unit_types, tap_packets, PacketDump, PacketRecord, DUMP_PACKETS_ENV,
+};
+pub use rate_control::{RateControl, MAX_AV1_CONSTANT_QUALITY, MAX_QP};
+pub use scaler::{BarColor, FitMode, FrameScaler};
+pub use sessions::{active_sessions, detect_session_limit, sessions, SessionPool, SessionSlot};
+pub use static_content::{
+    FrameDecision, StaticFrameFilter, DEFAULT_STATIC_REFRESH, DEFAULT_STATIC_THRESHOLD,
+};
+pub use timecode::{insert_timecode, timecode_metadata_supported, Timecode};
+pub use tonemap::{FrameLuminance, TonemapAlgorithm, TonemapConfig, TonemapMode, Tonemapper};
+
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+
+use crate::config::{CaptureConfig, Codec};
+use crate::error::{NitrogenError, Result};
+use crate::types::Frame;
+
+/// Environment variable that selects the mock encoder at runtime
+pub const MOCK_ENCODER_ENV: &str = "NITROGEN_MOCK_ENCODER";
+
+/// A video encoder the pipeline can drive
+///
+/// Implemented by [`NvencEncoder`] and, with the `mock-encoder` feature, by
+/// `MockEncoder`. Encoded packets are delivered to every subscriber.
+pub trait Encoder: Send {
+    /// Encode a frame
+    fn encode(&mut self, frame: &Frame) -> Result<()>;
+
+    /// Flush remaining packets from the encoder
+    fn flush(&mut self) -> Result<()>;
+
+    /// Release at most `max` of the packets still held by the encoder
+    ///
+    /// The first call ends the input, as [`flush`](Self::flush) does. Returns
+    /// how many packets were sent; 0 once the encoder is empty. Draining in
+    /// batches lets subscribers keep up with a deep lookahead instead of
+    /// lagging behind one burst. The default flushes everything at once.
+    fn drain(&mut self, max: usize) -> Result<usize> {
+        let _ = max;
+        self.flush()?;
+        Ok(0)
+    }
+
+    /// Packets sent that some subscriber has not received yet
+    fn queued_packets(&self) -> usize {
+        0
+    }
+
+    /// Subscribe to encoded packets
+    fn subscribe(&self) -> broadcast::Receiver<Arc<EncodedPacket>>;
+
+    /// Get the output resolution
+    fn output_size(&self) -> (u32, u32);
 
-use crate::config::Codec;
+    /// Get the frame count
+    fn frame_count(&self) -> u64;
+
+    /// Force the next encoded frame to be a keyframe
+    fn request_keyframe(&self);
+
+    /// Get a shareable handle for requesting keyframes
+    fn keyframe_requester(&self) -> Arc<AtomicBool>;
+
+    /// Account for a captured frame that is deliberately not encoded
+    fn skip_frame(&mut self);
+
+    /// Change the target bitrate in kbps while encoding
+    fn set_bitrate(&mut self, kbps: u32) -> Result<()> {
+        let _ = kbps;
+        Err(NitrogenError::Unsupported(
+            "This encoder can't change its bitrate while running".into(),
+        ))
+    }
+
+    /// Number of frames encoded straight from DMA-BUF
+    fn zero_copy_frames(&self) -> u64 {
+        0
+    }
+
+    /// Whether the most recent `encode` call was zero-copy
+    fn last_frame_zero_copy(&self) -> bool {
+        false
+    }
+
+    /// Number of times the session was re-created after a transient failure
+    fn reinit_count(&self) -> u64 {
+        0
+    }
+}
+
+/// Whether the mock encoder should replace NVENC
+///
+/// True in builds with the `mock-encoder` feature, or when
+/// `NITROGEN_MOCK_ENCODER=1` is set. The variable alone can't provide the
+/// mock; without the feature it is only reported.
+pub fn mock_encoder_requested() -> bool {
+    cfg!(feature = "mock-encoder") || std::env::var(MOCK_ENCODER_ENV).is_ok_and(|v| v == "1")
+}
+
+/// Create the video encoder for a config
+///
+/// Returns NVENC unless the mock encoder is requested (see
+/// [`mock_encoder_requested`]). Each encoder holds a slot in the process-wide
+/// [`sessions`] pool, so going past the GPU's session limit fails with
+/// [`NitrogenError::SessionLimit`](crate::error::NitrogenError::SessionLimit).
+pub fn create_encoder(config: &CaptureConfig) -> Result<Box<dyn Encoder>> {
+    let pool = sessions();
+    if let Some(limit) = config.max_encoder_sessions {
+        pool.set_limit(Some(limit as usize));
+    }
+    create_encoder_in(pool, config)
+}
+
+/// Create the video encoder for a config, taking its slot from `pool`
+pub fn create_encoder_in(
+    pool: &Arc<SessionPool>,
+    config: &CaptureConfig,
+) -> Result<Box<dyn Encoder>> {
+    let slot = pool.acquire()?;
+
+    #[cfg(feature = "mock-encoder")]
+    if mock_encoder_requested() {
+        let encoder = Box::new(MockEncoder::new(config).with_session(slot));
+        tap_packets(&*encoder, config);
+        return Ok(encoder);
+    }
+
+    #[cfg(not(feature = "mock-encoder"))]
+    if mock_encoder_requested() {
+        tracing::warn!(
+            "{}=1 is set, but this build lacks the mock-encoder feature; using NVENC",
+            MOCK_ENCODER_ENV
+        );
+    }
+
+    let encoder = Box::new(NvencEncoder::new(config)?.with_session(slot));
+    tap_packets(&*encoder, config);
+    Ok(encoder)
+}
 
 /// Check if NVENC is available on this system
 pub fn nvenc_available() -> bool {
@@ -7,21 +7,42 @@ use ffmpeg_next::codec::{self, encoder};
 use ffmpeg_next::format::Pixel;
 use ffmpeg_next::software::scaling::{self, Flags};
 use ffmpeg_next::util::frame::video::Video;
+use ffmpeg_next::util::picture;
 use ffmpeg_next::{Dictionary, Rational};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use tracing::{debug, info, trace, warn};
 
-use crate::config::{CaptureConfig, Codec};
+use super::cuda::{cuda_import_available, modifier_supported, CudaImporter};
+use super::keyframe::KeyframeDebounce;
+use super::rate_control::RateControl;
+use super::scaler::{FitMode, FrameScaler};
+use super::sessions::SessionSlot;
+use super::timecode::{insert_timecode, timecode_metadata_supported, Timecode};
+use super::Encoder;
+use crate::config::{
+    Av1Tune, CaptureConfig, Codec, ColorRange, ContentMode, EncoderQuality, MultipassMode, Tune,
+    MAX_ASYNC_DEPTH,
+};
 use crate::error::{NitrogenError, Result};
+use crate::formats::ffmpeg_color_range;
 use crate::types::{Frame, FrameData, FrameFormat};
 
+/// `AV_CUDA_USE_PRIMARY_CONTEXT` from libavutil/hwcontext_cuda.h
+const AV_CUDA_USE_PRIMARY_CONTEXT: i32 = 1;
+
+/// Fresh sessions to try on one failing frame before the error is fatal
+const MAX_ENCODER_REINITS: u32 = 3;
+
 /// NVENC hardware encoder
 pub struct NvencEncoder {
     /// FFmpeg encoder context
     encoder: encoder::Video,
     /// Scaler for format conversion if needed
     scaler: Option<scaling::Context>,
+    /// Letterbox/crop pass for inputs with a different aspect ratio
+    fitter: Option<FrameScaler>,
     /// Intermediate frame for input (before scaling)
     src_frame: Option<Video>,
     /// Input frame buffer (after scaling, for encoder)
@@ -38,8 +59,30 @@ pub struct NvencEncoder {
     output_height: u32,
     /// Output pixel format (NV12 or P010LE for 10-bit)
     output_format: Pixel,
+    /// YUV range written by the scaler and signalled by the encoder
+    color_range: ColorRange,
     /// Last input format (for scaler cache)
     last_input_format: Option<(u32, u32, Pixel)>,
+    /// Set when the next frame should be encoded as an IDR
+    keyframe_requested: Arc<AtomicBool>,
+    /// Spaces forced IDRs apart (kept across re-inits)
+    keyframe_debounce: KeyframeDebounce,
+    /// CUDA input surfaces for zero-copy DMA-BUF frames (None = system memory input)
+    cuda: Option<CudaInput>,
+    /// Frames whose pixels never left the GPU
+    zero_copy_frames: u64,
+    /// Whether the last encoded frame took the zero-copy path
+    last_zero_copy: bool,
+    /// Configuration the session was opened with (for re-creating it)
+    config: CaptureConfig,
+    /// Times the session was re-created after a transient failure
+    reinit_count: u64,
+    /// Timecode of frame 0 when writing timecode metadata
+    timecode: Option<Timecode>,
+    /// Whether the end of input was signalled to the current session
+    eof_sent: bool,
+    /// Session slot held while the encoder exists (kept across re-inits)
+    _session: Option<SessionSlot>,
 }
 
 /// Encoded video packet
@@ -84,7 +127,23 @@ impl NvencEncoder {
 
         // Select pixel format: P010LE for 10-bit AV1, NV12 for everything else
         let use_10bit = config.codec == Codec::Av1 && config.av1.ten_bit;
-        let pixel_format = if use_10bit {
+
+        // Zero-copy input: NVENC reads BGRA CUDA surfaces and converts to YUV itself
+        let cuda = if zero_copy_supported(config) {
+            match CudaInput::new(config.gpu, width, height) {
+                Ok(cuda) => Some(cuda),
+                Err(e) => {
+                    warn!("Zero-copy DMA-BUF input unavailable, using copy path: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        let pixel_format = if cuda.is_some() {
+            Pixel::BGRA // Uploaded to the CUDA surface as-is
+        } else if use_10bit {
             Pixel::P010LE // 10-bit 4:2:0 planar
         } else {
             Pixel::NV12 // 8-bit 4:2:0 semi-planar
@@ -92,15 +151,31 @@ impl NvencEncoder {
 
         encoder.set_width(width);
         encoder.set_height(height);
-        encoder.set_format(pixel_format);
+        if let Some(ref cuda) = cuda {
+            encoder.set_format(Pixel::CUDA);
+            // SAFETY: The encoder context is not opened yet, and it takes its
+            // own reference to the frames context (freed with the context).
+            unsafe {
+                (*encoder.as_mut_ptr()).hw_frames_ctx = ffmpeg::ffi::av_buffer_ref(cuda.frames_ref);
+            }
+        } else {
+            encoder.set_format(pixel_format);
+        }
         encoder.set_time_base(Rational::new(1, fps as i32));
         encoder.set_frame_rate(Some(Rational::new(fps as i32, 1)));
-        encoder.set_bit_rate(bitrate);
-        encoder.set_max_bit_rate(bitrate + bitrate / 2); // 1.5x headroom
+        // Constant QP leaves the bitrate unset; the peak rate is in vbv_options
+        if config.effective_rate_control().uses_bitrate() {
+            encoder.set_bit_rate(bitrate);
+        }
+        // Equivalent of -color_range; NVENC signals it in the VUI/sequence header
+        encoder.set_color_range(ffmpeg_color_range(config.color_range));
 
         if use_10bit {
             info!("Using 10-bit encoding (P010LE) for AV1 main10 profile");
         }
+        if cuda.is_some() {
+            info!("NVENC input from CUDA surfaces (zero-copy DMA-BUF enabled)");
+        }
 
         // Set up encoder options
         let mut opts = Dictionary::new();
@@ -108,35 +183,52 @@ impl NvencEncoder {
         // NVENC preset
         opts.set("preset", config.encoder_preset.nvenc_preset());
 
-        // Low latency options
-        if config.low_latency {
-            opts.set("tune", "ll"); // Low latency tune
-            opts.set("zerolatency", "1");
-            opts.set("delay", "0");
-            opts.set("rc", "cbr"); // Constant bitrate for consistent latency
-        } else {
-            opts.set("rc", "vbr"); // Variable bitrate for quality
+        // Tuning and the latency options that go with it
+        let tune = config.effective_tune();
+        let low_latency = tune.is_low_latency();
+        for (key, value) in tune_options(config.codec, tune, config.av1.tune) {
+            opts.set(key, &value);
         }
 
         // NVENC-specific options
         opts.set("gpu", &config.gpu.to_string());
-        opts.set("surfaces", "8"); // Number of surfaces for async encode
+        for (key, value) in async_depth_options(&config.quality, low_latency) {
+            opts.set(key, &value);
+        }
+        // Turn forced I-frames (see request_keyframe) into full IDRs so decoders
+        // joining mid-stream can start from them
+        opts.set("forced-idr", "1");
+
+        // Lookahead, adaptive quantization and multipass (shared by all codecs)
+        let quality = EncoderQuality {
+            multipass: config.multipass(),
+            ..config.quality.clone()
+        };
+        for (key, value) in quality_options(&quality, low_latency) {
+            opts.set(key, &value);
+        }
+        for (key, value) in rate_control_options(config.rate_control, config.codec, low_latency) {
+            opts.set(key, &value);
+        }
+        for (key, value) in vbv_options(config) {
+            opts.set(key, &value);
+        }
 
         // Codec-specific options
         match config.codec {
             Codec::H264 => {
                 opts.set("profile", "high");
                 opts.set("level", "auto");
-                // B-frames can add latency, disable for low-latency
-                if config.low_latency {
-                    opts.set("bf", "0");
+                let (layers, svc) = temporal_layer_options(config.codec, config.temporal_layers);
+                if layers > 1 {
+                    info!("Encoding {} temporal layers (hierarchical P-frames)", layers);
+                }
+                for (key, value) in svc {
+                    opts.set(key, &value);
                 }
             }
             Codec::Hevc => {
                 opts.set("profile", "main");
-                if config.low_latency {
-                    opts.set("bf", "0");
-                }
             }
             Codec::Av1 => {
                 // AV1 specific options from Av1Config
@@ -153,40 +245,6 @@ impl NvencEncoder {
                     opts.set("profile", "main");
                 }
 
-                // GOP length (keyframe interval)
-                let gop = av1.resolved_gop(fps);
-                opts.set("g", &gop.to_string());
-
-                // Tuning mode (hq, uhq for RTX 50, ll, ull)
-                // UHQ provides ~5% better compression on Blackwell
-                if !config.low_latency {
-                    opts.set("tune", av1.tune.ffmpeg_value());
-                } else {
-                    opts.set("tune", "ll"); // Force low-latency tune
-                }
-
-                // Lookahead for better quality (if not low-latency)
-                if av1.lookahead && !config.low_latency {
-                    // RTX 50 supports up to 250 frames lookahead
-                    let depth = av1.lookahead_depth.min(250).max(1);
-                    opts.set("rc-lookahead", &depth.to_string());
-                }
-
-                // Spatial AQ (adaptive quantization for better quality at same bitrate)
-                if av1.spatial_aq {
-                    opts.set("spatial_aq", "1");
-                }
-
-                // Temporal AQ - RTX 50 series feature (~4-5% efficiency gain)
-                if av1.temporal_aq {
-                    opts.set("temporal_aq", "1");
-                }
-
-                // Multipass encoding for better quality
-                if let Some(multipass) = av1.multipass.ffmpeg_value() {
-                    opts.set("multipass", multipass);
-                }
-
                 // B-frame reference mode (RTX 50 series)
                 if av1.b_ref_mode {
                     opts.set("b_ref_mode", "middle");
@@ -196,12 +254,22 @@ impl NvencEncoder {
                 opts.set("bf", "0");
 
                 // Log if using Blackwell features
-                if av1.uses_blackwell_features() {
+                if av1.uses_blackwell_features() || config.quality.uses_blackwell_features() {
                     info!("Using RTX 50 series (Blackwell) AV1 features");
                 }
             }
         }
 
+        // Keyframe interval, replaced by intra-refresh or static refreshes
+        for (key, value) in gop_options(config) {
+            opts.set(key, &value);
+        }
+        if config.content_mode == ContentMode::Static {
+            info!("Static content mode: encoding changes and periodic refreshes only");
+        } else if let Some(period) = config.resolved_intra_refresh_period() {
+            info!("Intra-refresh every {} frames instead of periodic IDRs", period);
+        }
+
         // Open encoder
         let encoder = encoder
             .open_with(opts)
@@ -220,11 +288,13 @@ impl NvencEncoder {
         let (output_tx, _) = broadcast::channel(16);
 
         // Create destination frame (matching encoder pixel format)
-        let dst_frame = Video::new(pixel_format, width, height);
+        let mut dst_frame = Video::new(pixel_format, width, height);
+        dst_frame.set_color_range(ffmpeg_color_range(config.color_range));
 
         Ok(Self {
             encoder,
             scaler: None,
+            fitter: None,
             src_frame: None,
             dst_frame,
             packet: ffmpeg::Packet::empty(),
@@ -233,10 +303,29 @@ impl NvencEncoder {
             output_width: width,
             output_height: height,
             output_format: pixel_format,
+            color_range: config.color_range,
             last_input_format: None,
+            keyframe_requested: Arc::new(AtomicBool::new(false)),
+            keyframe_debounce: KeyframeDebounce::new(fps),
+            cuda,
+            zero_copy_frames: 0,
+            last_zero_copy: false,
+            config: config.clone(),
+            reinit_count: 0,
+            eof_sent: false,
+            timecode: config
+                .overlay_timecode
+                .filter(|_| config.timecode_metadata && timecode_metadata_supported(config.codec)),
+            _session: None,
         })
     }
 
+    /// Hold a session slot until the encoder is dropped
+    pub fn with_session(mut self, slot: SessionSlot) -> Self {
+        self._session = Some(slot);
+        self
+    }
+
     /// Subscribe to encoded packets
     pub fn subscribe(&self) -> broadcast::Receiver<Arc<EncodedPacket>> {
         self.output_tx.subscribe()
@@ -252,33 +341,177 @@ impl NvencEncoder {
         self.frame_count
     }
 
+    /// Force the next encoded frame to be an IDR
+    ///
+    /// Useful when a viewer joins mid-stream and needs a keyframe to start
+    /// decoding. Multiple requests before the next frame collapse into one,
+    /// and IDRs are forced at most once per
+    /// [`MIN_KEYFRAME_INTERVAL`](super::MIN_KEYFRAME_INTERVAL); later requests
+    /// wait for it.
+    pub fn request_keyframe(&self) {
+        self.keyframe_requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Get a shareable handle for requesting keyframes
+    ///
+    /// Storing `true` into the returned flag has the same effect as calling
+    /// [`request_keyframe`](Self::request_keyframe), which lets outputs such as
+    /// WebRTC request an IDR without holding a reference to the encoder.
+    pub fn keyframe_requester(&self) -> Arc<AtomicBool> {
+        self.keyframe_requested.clone()
+    }
+
+    /// Account for a captured frame that is deliberately not encoded
+    ///
+    /// Advances the timestamp clock so the next encoded frame lands at its
+    /// real time and the previous picture is held in between.
+    pub fn skip_frame(&mut self) {
+        self.frame_count += 1;
+        self.last_zero_copy = false;
+    }
+
+    /// Change the target bitrate in kbps without re-opening the session
+    ///
+    /// FFmpeg's NVENC wrapper compares the rate fields of the codec context
+    /// with the session before every frame and reconfigures NVENC when they
+    /// differ. The next frame is also forced to an IDR so the new rate starts
+    /// on a fresh GOP. The peak rate and VBV buffer follow the bitrate unless
+    /// they were set explicitly; the peak never drops below the target.
+    pub fn set_bitrate(&mut self, kbps: u32) -> Result<()> {
+        let rate_control = self.config.effective_rate_control();
+        if !rate_control.uses_bitrate() {
+            return Err(NitrogenError::config(format!(
+                "{} rate control has no bitrate to change",
+                rate_control.describe()
+            )));
+        }
+
+        // A re-init reads the bitrate from `config`, so it keeps the new rate
+        self.config.bitrate = kbps;
+        let bits = |kbps: u32| i64::from(kbps) * 1000;
+        let max_rate = bits(self.config.effective_max_bitrate().max(kbps));
+        let buffer_size = bits(self.config.effective_vbv_buffer_size());
+        // SAFETY: The context is only used from this encoder, and NVENC picks
+        // up the plain rate fields on the next send_frame.
+        unsafe {
+            let context = self.encoder.as_mut_ptr();
+            (*context).bit_rate = bits(kbps);
+            (*context).rc_max_rate = max_rate;
+            (*context).rc_buffer_size = buffer_size.min(i32::MAX as i64) as i32;
+        }
+        self.request_keyframe();
+        info!("NVENC bitrate set to {} kbps", kbps);
+        Ok(())
+    }
+
+    /// Whether DMA-BUF frames can be encoded without a CPU copy
+    pub fn zero_copy_active(&self) -> bool {
+        self.cuda.is_some()
+    }
+
+    /// Number of frames encoded straight from DMA-BUF via CUDA
+    pub fn zero_copy_frames(&self) -> u64 {
+        self.zero_copy_frames
+    }
+
+    /// Whether the most recent [`encode`](Self::encode) call was zero-copy
+    pub fn last_frame_zero_copy(&self) -> bool {
+        self.last_zero_copy
+    }
+
+    /// Number of times the session was re-created after a transient failure
+    pub fn reinit_count(&self) -> u64 {
+        self.reinit_count
+    }
+
     /// Encode a frame
     ///
-    /// Takes a raw frame and produces encoded packets.
+    /// Takes a raw frame and produces encoded packets. If NVENC reports a
+    /// transient session error, the session is re-created (starting on an
+    /// IDR) and the frame retried, up to a few times before the error is
+    /// returned as fatal. Subscribers keep receiving packets across re-inits.
     pub fn encode(&mut self, input: &Frame) -> Result<()> {
+        encode_with_recovery(self, input, MAX_ENCODER_REINITS).map(|_| ())
+    }
+
+    /// Encode a frame on the current session, without recovery
+    fn encode_once(&mut self, input: &Frame) -> Result<()> {
+        self.last_zero_copy = false;
+
         // Convert frame data to FFmpeg format
         match &input.data {
             FrameData::Memory(data) => {
                 self.encode_memory_frame(data, &input.format)?;
             }
             FrameData::DmaBuf {
-                fd: _,
-                offset: _,
-                modifier: _,
+                fd,
+                offset,
+                modifier,
             } => {
-                // For DMA-BUF, we would need to use CUDA/NVDEC for zero-copy
-                // This requires nvenc CUDA device setup which is more complex
-                return Err(NitrogenError::Unsupported(
-                    "DMA-BUF encoding not yet implemented - use memory frames".into(),
-                ));
+                if self.try_encode_zero_copy(*fd, *offset, *modifier, &input.format)? {
+                    return Ok(());
+                }
+
+                // Copy path: map the buffer and encode it like a memory frame
+                let size = (input.format.stride * input.format.height) as usize;
+                let data = input
+                    .data
+                    .try_map_dmabuf(size)
+                    .map_err(|e| NitrogenError::encoder(format!("DMA-BUF mapping failed: {}", e)))?;
+                self.encode_memory_frame(&data, &input.format)?;
             }
         }
 
         Ok(())
     }
 
+    /// Encode a DMA-BUF frame through CUDA if it qualifies
+    ///
+    /// Returns `Ok(false)` when the frame has to take the copy path: no CUDA
+    /// input, a tiled modifier, a size or format that needs swscale, or an
+    /// import failure. Only errors from the encoder itself are returned.
+    fn try_encode_zero_copy(
+        &mut self,
+        fd: std::os::fd::RawFd,
+        offset: u32,
+        modifier: u64,
+        format: &FrameFormat,
+    ) -> Result<bool> {
+        let Some(ref mut cuda) = self.cuda else {
+            return Ok(false);
+        };
+        // NVENC can't scale, and the surfaces are fixed to BGRA
+        if !modifier_supported(modifier)
+            || pixel_format_from_fourcc(format.fourcc) != Pixel::BGRA
+            || (format.width, format.height) != (self.output_width, self.output_height)
+        {
+            trace!("DMA-BUF frame not eligible for zero-copy (modifier 0x{:x})", modifier);
+            return Ok(false);
+        }
+
+        if let Err(e) = cuda.upload_dmabuf(fd, offset, format) {
+            if !cuda.import_failed {
+                warn!("DMA-BUF import failed, falling back to copy path: {}", e);
+                cuda.import_failed = true;
+            } else {
+                trace!("DMA-BUF import failed: {}", e);
+            }
+            return Ok(false);
+        }
+
+        self.zero_copy_frames += 1;
+        self.last_zero_copy = true;
+        self.send_frame(true)?;
+        Ok(true)
+    }
+
     /// Encode a frame from memory
     fn encode_memory_frame(&mut self, data: &[u8], format: &FrameFormat) -> Result<()> {
+        let fitted = self.fit_frame(data, format)?;
+        let (data, format) = match &fitted {
+            Some((fitted_data, fitted_format)) => (fitted_data.as_slice(), fitted_format),
+            None => (data, format),
+        };
         let src_pixel_format = pixel_format_from_fourcc(format.fourcc);
 
         // Ensure scaler is set up for format conversion
@@ -309,17 +542,102 @@ impl NvencEncoder {
                 .map_err(|e| NitrogenError::encoder(format!("Scaling failed: {}", e)))?;
         }
 
+        // With CUDA input the converted frame still has to reach the GPU
+        if let Some(ref mut cuda) = self.cuda {
+            cuda.upload_frame(&self.dst_frame)?;
+        }
+
+        self.send_frame(self.cuda.is_some())
+    }
+
+    /// Letterbox or crop an input whose aspect ratio differs from the output
+    ///
+    /// Runs in the capture format, so the result is at the output size and
+    /// the conversion to NV12 only changes the pixel format. Returns `None`
+    /// when the frame can go to the scaler as is.
+    fn fit_frame(
+        &mut self,
+        data: &[u8],
+        format: &FrameFormat,
+    ) -> Result<Option<(Vec<u8>, FrameFormat)>> {
+        let (width, height) = (self.output_width, self.output_height);
+        let same_aspect =
+            format.width as u64 * height as u64 == format.height as u64 * width as u64;
+        let pixel_format = pixel_format_from_fourcc(format.fourcc);
+        let packed = matches!(
+            pixel_format,
+            Pixel::BGRA | Pixel::RGBA | Pixel::RGB24 | Pixel::BGR24
+        );
+        if self.config.fit == FitMode::Stretch || same_aspect || !packed {
+            self.fitter = None;
+            return Ok(None);
+        }
+
+        // The fitted frame keeps the capture format, so the last scaler input names it
+        let reuse = self.fitter.as_ref().is_some_and(|fitter| {
+            fitter.input_size() == (format.width, format.height)
+                && self.last_input_format.map(|(_, _, pixel)| pixel) == Some(pixel_format)
+        });
+        if !reuse {
+            self.fitter = Some(
+                FrameScaler::new(
+                    format.width,
+                    format.height,
+                    pixel_format,
+                    width,
+                    height,
+                    pixel_format,
+                )?
+                .with_fit(self.config.fit, self.config.fit_color)?,
+            );
+        }
+
+        let bytes_per_pixel = match pixel_format {
+            Pixel::RGB24 | Pixel::BGR24 => 3,
+            _ => 4,
+        };
+        let fitter = self.fitter.as_mut().expect("fitter was just created");
+        let fitted = fitter.scale_strided(data, format.stride as usize)?;
+        Ok(Some((
+            fitted,
+            FrameFormat {
+                width,
+                height,
+                fourcc: format.fourcc,
+                stride: width * bytes_per_pixel,
+            },
+        )))
+    }
+
+    /// Stamp and send the prepared frame, then collect packets
+    ///
+    /// `hw` selects the CUDA surface instead of the system memory frame.
+    fn send_frame(&mut self, hw: bool) -> Result<()> {
+        let frame = match self.cuda {
+            Some(ref mut cuda) if hw => &mut cuda.hw_frame,
+            _ => &mut self.dst_frame,
+        };
+
         // Set frame PTS
-        self.dst_frame.set_pts(Some(self.frame_count as i64));
+        let frame_number = self.frame_count;
+        frame.set_pts(Some(frame_number as i64));
         self.frame_count += 1;
 
+        // Force an IDR if one was requested, otherwise let the encoder decide
+        if self.keyframe_debounce.take(&self.keyframe_requested, frame_number) {
+            debug!("Forcing keyframe at frame {}", frame_number);
+            frame.set_kind(picture::Type::I);
+        } else {
+            frame.set_kind(picture::Type::None);
+        }
+
         // Send to encoder
         self.encoder
-            .send_frame(&self.dst_frame)
-            .map_err(|e| NitrogenError::nvenc(format!("Failed to send frame: {}", e)))?;
+            .send_frame(frame)
+            .map_err(|e| session_error("Failed to send frame", e))?;
 
         // Receive encoded packets
-        self.receive_packets()?;
+        self.receive_packets(usize::MAX)?;
 
         Ok(())
     }
@@ -356,7 +674,7 @@ impl NvencEncoder {
                 src_format, width, height, dst_format, self.output_width, self.output_height
             );
 
-            let scaler = scaling::Context::get(
+            let mut scaler = scaling::Context::get(
                 src_format,
                 width,
                 height,
@@ -366,6 +684,10 @@ impl NvencEncoder {
                 Flags::BILINEAR,
             )
             .map_err(|e| NitrogenError::encoder(format!("Failed to create scaler: {}", e)))?;
+            // CUDA input stays BGRA; NVENC applies the range when converting
+            if self.cuda.is_none() {
+                set_scaler_output_range(&mut scaler, self.color_range);
+            }
 
             self.scaler = Some(scaler);
             self.last_input_format = Some(current_input);
@@ -374,18 +696,31 @@ impl NvencEncoder {
         Ok(())
     }
 
-    /// Receive encoded packets from the encoder
-    fn receive_packets(&mut self) -> Result<()> {
-        loop {
+    /// Receive up to `max` encoded packets from the encoder
+    ///
+    /// Returns how many were sent.
+    fn receive_packets(&mut self, max: usize) -> Result<usize> {
+        let mut received = 0;
+        while received < max {
             match self.encoder.receive_packet(&mut self.packet) {
                 Ok(()) => {
-                    let packet = EncodedPacket {
+                    let mut packet = EncodedPacket {
                         data: self.packet.data().map(|d| d.to_vec()).unwrap_or_default(),
                         pts: self.packet.pts().unwrap_or(0),
                         dts: self.packet.dts().unwrap_or(0),
                         keyframe: self.packet.is_key(),
                     };
 
+                    // PTS counts frames (time base 1/fps), the same count the
+                    // burned-in timecode advances by
+                    if let Some(start) = self.timecode {
+                        let fps = self.config.fps();
+                        let timecode = start.advanced(packet.pts.max(0) as u64, fps);
+                        if let Some(data) = insert_timecode(self.config.codec, &packet.data, timecode, fps) {
+                            packet.data = data;
+                        }
+                    }
+
                     trace!(
                         "Encoded packet: pts={}, size={}, keyframe={}",
                         packet.pts,
@@ -395,30 +730,358 @@ impl NvencEncoder {
 
                     // Send packet (ignore error if no receivers)
                     let _ = self.output_tx.send(Arc::new(packet));
+                    received += 1;
                 }
                 Err(ffmpeg::Error::Other { errno }) if errno == ffmpeg::error::EAGAIN => {
                     // Need more input frames
                     break;
                 }
+                Err(ffmpeg::Error::Eof) => {
+                    // Fully drained after EOF
+                    break;
+                }
                 Err(e) => {
-                    return Err(NitrogenError::nvenc(format!(
-                        "Failed to receive packet: {}",
-                        e
-                    )));
+                    return Err(session_error("Failed to receive packet", e));
                 }
             }
         }
 
+        Ok(received)
+    }
+
+    /// Signal the end of input once per session
+    fn send_eof(&mut self) -> Result<()> {
+        if !self.eof_sent {
+            debug!("Flushing encoder ({} frames encoded)", self.frame_count);
+            self.encoder
+                .send_eof()
+                .map_err(|e| NitrogenError::nvenc(format!("Failed to send EOF: {}", e)))?;
+            self.eof_sent = true;
+        }
         Ok(())
     }
 
     /// Flush remaining packets from the encoder
     pub fn flush(&mut self) -> Result<()> {
-        debug!("Flushing encoder ({} frames encoded)", self.frame_count);
-        self.encoder
-            .send_eof()
-            .map_err(|e| NitrogenError::nvenc(format!("Failed to send EOF: {}", e)))?;
-        self.receive_packets()
+        self.send_eof()?;
+        self.receive_packets(usize::MAX).map(|_| ())
+    }
+
+    /// Release at most `max` of the packets held for lookahead and B-frames
+    pub fn drain(&mut self, max: usize) -> Result<usize> {
+        self.send_eof()?;
+        self.receive_packets(max)
+    }
+
+    /// Packets sent that some subscriber has not received yet
+    pub fn queued_packets(&self) -> usize {
+        self.output_tx.len()
+    }
+}
+
+impl Encoder for NvencEncoder {
+    fn encode(&mut self, frame: &Frame) -> Result<()> {
+        NvencEncoder::encode(self, frame)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        NvencEncoder::flush(self)
+    }
+
+    fn drain(&mut self, max: usize) -> Result<usize> {
+        NvencEncoder::drain(self, max)
+    }
+
+    fn queued_packets(&self) -> usize {
+        NvencEncoder::queued_packets(self)
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Arc<EncodedPacket>> {
+        NvencEncoder::subscribe(self)
+    }
+
+    fn output_size(&self) -> (u32, u32) {
+        NvencEncoder::output_size(self)
+    }
+
+    fn frame_count(&self) -> u64 {
+        NvencEncoder::frame_count(self)
+    }
+
+    fn request_keyframe(&self) {
+        NvencEncoder::request_keyframe(self)
+    }
+
+    fn keyframe_requester(&self) -> Arc<AtomicBool> {
+        NvencEncoder::keyframe_requester(self)
+    }
+
+    fn skip_frame(&mut self) {
+        NvencEncoder::skip_frame(self)
+    }
+
+    fn set_bitrate(&mut self, kbps: u32) -> Result<()> {
+        NvencEncoder::set_bitrate(self, kbps)
+    }
+
+    fn zero_copy_frames(&self) -> u64 {
+        NvencEncoder::zero_copy_frames(self)
+    }
+
+    fn last_frame_zero_copy(&self) -> bool {
+        NvencEncoder::last_frame_zero_copy(self)
+    }
+
+    fn reinit_count(&self) -> u64 {
+        NvencEncoder::reinit_count(self)
+    }
+}
+
+/// An encoder whose session can be torn down and opened again
+trait EncoderSession {
+    /// Encode one frame on the current session
+    fn encode_frame(&mut self, frame: &Frame) -> Result<()>;
+
+    /// Replace the session with a freshly opened one
+    fn reinit(&mut self) -> Result<()>;
+}
+
+impl EncoderSession for NvencEncoder {
+    fn encode_frame(&mut self, frame: &Frame) -> Result<()> {
+        self.encode_once(frame)
+    }
+
+    fn reinit(&mut self) -> Result<()> {
+        // Open a complete new session, then keep the output channel, keyframe
+        // flag and frame clock so subscribers and timestamps carry on
+        let fresh = Self::new(&self.config)?;
+        self.encoder = fresh.encoder;
+        self.dst_frame = fresh.dst_frame;
+        self.packet = fresh.packet;
+        self.output_format = fresh.output_format;
+        self.cuda = fresh.cuda;
+        self.scaler = None;
+        self.src_frame = None;
+        self.last_input_format = None;
+        self.eof_sent = false;
+        self.reinit_count += 1;
+        self.request_keyframe();
+        Ok(())
+    }
+}
+
+/// Encode `frame`, re-creating the session on recoverable errors
+///
+/// Returns the number of re-inits it took. Errors other than
+/// [`NitrogenError::NvencSession`] are returned straight away, and a frame
+/// that still fails after `max_reinits` fresh sessions becomes a fatal
+/// [`NitrogenError::Nvenc`].
+fn encode_with_recovery<S: EncoderSession>(
+    session: &mut S,
+    frame: &Frame,
+    max_reinits: u32,
+) -> Result<u32> {
+    let mut error = match session.encode_frame(frame) {
+        Ok(()) => return Ok(0),
+        Err(e) => e,
+    };
+
+    for attempt in 1..=max_reinits {
+        if !matches!(error, NitrogenError::NvencSession(_)) {
+            return Err(error);
+        }
+
+        warn!(
+            "{}, re-creating encoder session (attempt {}/{})",
+            error, attempt, max_reinits
+        );
+        session.reinit()?;
+
+        match session.encode_frame(frame) {
+            Ok(()) => {
+                info!("Encoder recovered after {} re-init(s)", attempt);
+                return Ok(attempt);
+            }
+            Err(e) => error = e,
+        }
+    }
+
+    match error {
+        NitrogenError::NvencSession(msg) => Err(NitrogenError::nvenc(format!(
+            "{} (still failing after {} re-inits)",
+            msg, max_reinits
+        ))),
+        other => Err(other),
+    }
+}
+
+/// Map an FFmpeg send/receive error, flagging the ones a new session may fix
+///
+/// Out-of-memory and invalid or unsupported parameters fail the same way on
+/// a fresh session, so those stay fatal.
+fn session_error(context: &str, e: ffmpeg::Error) -> NitrogenError {
+    let fatal = match e {
+        ffmpeg::Error::Other { errno } => matches!(
+            errno,
+            ffmpeg::error::ENOMEM | ffmpeg::error::EINVAL | ffmpeg::error::ENOSYS
+        ),
+        ffmpeg::Error::EncoderNotFound
+        | ffmpeg::Error::OptionNotFound
+        | ffmpeg::Error::PatchWelcome => true,
+        _ => false,
+    };
+
+    if fatal {
+        NitrogenError::nvenc(format!("{}: {}", context, e))
+    } else {
+        NitrogenError::nvenc_session(format!("{}: {}", context, e))
+    }
+}
+
+/// CUDA frames context feeding NVENC, plus the DMA-BUF importer
+struct CudaInput {
+    /// DMA-BUF importer sharing FFmpeg's primary context
+    importer: CudaImporter,
+    /// FFmpeg CUDA device context
+    device_ref: *mut ffmpeg::ffi::AVBufferRef,
+    /// FFmpeg CUDA frames context (BGRA surfaces at output size)
+    frames_ref: *mut ffmpeg::ffi::AVBufferRef,
+    /// Surface handed to the encoder for the current frame
+    hw_frame: Video,
+    /// Set after the first import failure so it is only warned about once
+    import_failed: bool,
+}
+
+// SAFETY: The FFmpeg buffer refs are reference counted and only used by the
+// encoder that owns this input.
+unsafe impl Send for CudaInput {}
+
+impl CudaInput {
+    /// Create a CUDA device and BGRA frames pool for the given GPU
+    fn new(gpu: u32, width: u32, height: u32) -> Result<Self> {
+        let importer = CudaImporter::new(gpu)?;
+        let device = std::ffi::CString::new(gpu.to_string())
+            .map_err(|e| NitrogenError::encoder(e.to_string()))?;
+
+        // SAFETY: All pointers come from FFmpeg allocators and are checked
+        // before use. Partially built contexts are unreferenced on failure.
+        unsafe {
+            let mut device_ref = std::ptr::null_mut();
+            let ret = ffmpeg::ffi::av_hwdevice_ctx_create(
+                &mut device_ref,
+                ffmpeg::ffi::AVHWDeviceType::AV_HWDEVICE_TYPE_CUDA,
+                device.as_ptr(),
+                std::ptr::null_mut(),
+                AV_CUDA_USE_PRIMARY_CONTEXT,
+            );
+            if ret < 0 {
+                return Err(NitrogenError::encoder(format!(
+                    "Failed to create CUDA device: {}",
+                    ffmpeg::Error::from(ret)
+                )));
+            }
+
+            let mut frames_ref = ffmpeg::ffi::av_hwframe_ctx_alloc(device_ref);
+            if frames_ref.is_null() {
+                ffmpeg::ffi::av_buffer_unref(&mut device_ref);
+                return Err(NitrogenError::encoder("Failed to allocate CUDA frames context"));
+            }
+
+            let frames = (*frames_ref).data as *mut ffmpeg::ffi::AVHWFramesContext;
+            (*frames).format = ffmpeg::ffi::AVPixelFormat::AV_PIX_FMT_CUDA;
+            (*frames).sw_format = ffmpeg::ffi::AVPixelFormat::AV_PIX_FMT_BGRA;
+            (*frames).width = width as i32;
+            (*frames).height = height as i32;
+
+            let ret = ffmpeg::ffi::av_hwframe_ctx_init(frames_ref);
+            if ret < 0 {
+                ffmpeg::ffi::av_buffer_unref(&mut frames_ref);
+                ffmpeg::ffi::av_buffer_unref(&mut device_ref);
+                return Err(NitrogenError::encoder(format!(
+                    "Failed to initialize CUDA frames: {}",
+                    ffmpeg::Error::from(ret)
+                )));
+            }
+
+            Ok(Self {
+                importer,
+                device_ref,
+                frames_ref,
+                hw_frame: Video::empty(),
+                import_failed: false,
+            })
+        }
+    }
+
+    /// Take a fresh surface from the pool into `hw_frame`
+    fn next_surface(&mut self) -> Result<()> {
+        // SAFETY: hw_frame is owned by us; unref drops the encoder's previous
+        // surface reference before a new one is attached.
+        unsafe {
+            let frame = self.hw_frame.as_mut_ptr();
+            ffmpeg::ffi::av_frame_unref(frame);
+            let ret = ffmpeg::ffi::av_hwframe_get_buffer(self.frames_ref, frame, 0);
+            if ret < 0 {
+                return Err(NitrogenError::encoder(format!(
+                    "Failed to get CUDA surface: {}",
+                    ffmpeg::Error::from(ret)
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Fill the next surface from a linear BGRA DMA-BUF on the GPU
+    fn upload_dmabuf(
+        &mut self,
+        fd: std::os::fd::RawFd,
+        offset: u32,
+        format: &FrameFormat,
+    ) -> Result<()> {
+        self.next_surface()?;
+        // SAFETY: next_surface attached a CUDA surface, whose data[0] is a
+        // CUdeviceptr with linesize[0] bytes per row.
+        let (dst, dst_pitch) = unsafe {
+            let frame = self.hw_frame.as_ptr();
+            ((*frame).data[0] as u64, (*frame).linesize[0] as usize)
+        };
+        self.importer.copy_to_device(
+            fd,
+            offset,
+            format.stride,
+            dst,
+            dst_pitch,
+            format.width as usize * 4,
+            format.height as usize,
+        )
+    }
+
+    /// Upload a system memory BGRA frame into the next surface
+    fn upload_frame(&mut self, src: &Video) -> Result<()> {
+        self.next_surface()?;
+        // SAFETY: Both frames are valid; src matches the pool's sw_format and size
+        let ret = unsafe {
+            ffmpeg::ffi::av_hwframe_transfer_data(self.hw_frame.as_mut_ptr(), src.as_ptr(), 0)
+        };
+        if ret < 0 {
+            return Err(NitrogenError::encoder(format!(
+                "Failed to upload frame to CUDA: {}",
+                ffmpeg::Error::from(ret)
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for CudaInput {
+    fn drop(&mut self) {
+        // SAFETY: Each ref is owned by us and unreferenced exactly once; the
+        // encoder holds its own reference to the frames context.
+        unsafe {
+            ffmpeg::ffi::av_frame_unref(self.hw_frame.as_mut_ptr());
+            ffmpeg::ffi::av_buffer_unref(&mut self.frames_ref);
+            ffmpeg::ffi::av_buffer_unref(&mut self.device_ref);
+        }
     }
 }
 
@@ -496,6 +1159,19 @@ fn pixel_format_from_fourcc(fourcc: u32) -> Pixel {
     }
 }
 
+/// Check if DMA-BUF frames can be fed to NVENC without a CPU copy
+///
+/// Requires `zero_copy` in the config, CUDA external memory import, and an
+/// 8-bit limited range output, since NVENC's own RGB to YUV conversion
+/// doesn't do 10-bit or full range.
+pub fn zero_copy_supported(config: &CaptureConfig) -> bool {
+    let ten_bit = config.codec == Codec::Av1 && config.av1.ten_bit;
+    config.zero_copy
+        && !ten_bit
+        && config.color_range == ColorRange::Limited
+        && cuda_import_available()
+}
+
 /// Check if NVENC is available
 pub fn check_nvenc_available() -> bool {
     ffmpeg::init().ok();
@@ -523,9 +1199,260 @@ pub fn list_available_encoders() -> Vec<(Codec, &'static str)> {
     available
 }
 
+/// Make the scaler produce YUV in the given range
+///
+/// swscale writes limited range by default, which would not match a
+/// full-range tag on the encoded stream.
+fn set_scaler_output_range(scaler: &mut scaling::Context, range: ColorRange) {
+    // SAFETY: rust-ffmpeg doesn't wrap the colorspace detail functions. The
+    // context pointer comes from a live scaler we exclusively borrow, and the
+    // tables returned by sws_getColorspaceDetails are owned by that context and
+    // passed straight back to sws_setColorspaceDetails.
+    unsafe {
+        let ctx = scaler.as_mut_ptr();
+        let mut inv_table: *mut std::os::raw::c_int = std::ptr::null_mut();
+        let mut table: *mut std::os::raw::c_int = std::ptr::null_mut();
+        let mut src_range = 0;
+        let mut dst_range = 0;
+        let mut brightness = 0;
+        let mut contrast = 0;
+        let mut saturation = 0;
+
+        if ffmpeg::ffi::sws_getColorspaceDetails(
+            ctx,
+            &mut inv_table,
+            &mut src_range,
+            &mut table,
+            &mut dst_range,
+            &mut brightness,
+            &mut contrast,
+            &mut saturation,
+        ) < 0
+        {
+            warn!("Scaler does not support colorspace details, output range unchanged");
+            return;
+        }
+
+        dst_range = i32::from(range == ColorRange::Full);
+        ffmpeg::ffi::sws_setColorspaceDetails(
+            ctx, inv_table, src_range, table, dst_range, brightness, contrast, saturation,
+        );
+    }
+}
+
+/// Build the `-tune` option and the rate control options that go with it
+///
+/// Low-latency tunes (`ll`, `ull`) encode CBR with zero delay and no
+/// B-frames. Otherwise AV1 uses its own tune (hq, uhq for RTX 50, lossless)
+/// and the others `hq`, both at VBR.
+fn tune_options(codec: Codec, tune: Tune, av1_tune: Av1Tune) -> Vec<(&'static str, String)> {
+    let mut opts = Vec::new();
+
+    if tune.is_low_latency() {
+        opts.push(("tune", tune.ffmpeg_value().unwrap_or("ll").to_string()));
+        opts.push(("zerolatency", "1".to_string()));
+        opts.push(("delay", "0".to_string()));
+        opts.push(("rc", "cbr".to_string())); // Constant bitrate for consistent latency
+        opts.push(("bf", "0".to_string())); // B-frames hold frames back
+    } else {
+        let value = match codec {
+            Codec::Av1 => av1_tune.ffmpeg_value(),
+            Codec::H264 | Codec::Hevc => tune.ffmpeg_value().unwrap_or("hq"),
+        };
+        opts.push(("tune", value.to_string()));
+        opts.push(("rc", "vbr".to_string())); // Variable bitrate for quality
+    }
+
+    opts
+}
+
+/// Build the lookahead and AQ encoder options
+///
+/// Lookahead buffers frames before encoding, so it is skipped (with a
+/// warning) in low-latency mode. AQ does not add latency and is always honored.
+fn quality_options(quality: &EncoderQuality, low_latency: bool) -> Vec<(&'static str, String)> {
+    let mut opts = Vec::new();
+
+    if quality.lookahead {
+        if low_latency {
+            warn!("Lookahead requested but disabled in low-latency mode");
+        } else {
+            // RTX 50 supports up to 250 frames lookahead
+            let depth = quality.effective_lookahead_depth();
+            opts.push(("rc-lookahead", depth.to_string()));
+        }
+    }
+
+    if quality.spatial_aq {
+        opts.push(("spatial-aq", "1".to_string()));
+    }
+
+    if quality.temporal_aq {
+        opts.push(("temporal-aq", "1".to_string()));
+    }
+
+    if let Some(multipass) = quality.multipass.ffmpeg_value() {
+        if low_latency {
+            warn!("Multipass requested but disabled in low-latency mode");
+        } else {
+            opts.push(("multipass", multipass.to_string()));
+        }
+    }
+
+    opts
+}
+
+/// Build the `-rc` override and quality target for an explicit rate control
+///
+/// Set after the tune options, so the mode replaces the tune's CBR/VBR.
+/// With constant quality NVENC aims for the `cq` level and only uses the
+/// bitrate as a ceiling; constant QP ignores the bitrate. H.264 and HEVC
+/// take quantizers up to 51, AV1 a 0-255 quantizer index and quality levels
+/// up to 63, so values are clamped per codec. Low-latency tunes are CBR, so
+/// other modes are dropped there (with a warning).
+fn rate_control_options(
+    rate_control: Option<RateControl>,
+    codec: Codec,
+    low_latency: bool,
+) -> Vec<(&'static str, String)> {
+    let Some(rate_control) = rate_control else {
+        return Vec::new();
+    };
+    if low_latency {
+        if rate_control != RateControl::Cbr {
+            warn!(
+                "{} rate control requested but disabled in low-latency mode",
+                rate_control.describe()
+            );
+        }
+        return Vec::new();
+    }
+
+    match rate_control {
+        RateControl::Cbr => vec![("rc", "cbr".to_string())],
+        RateControl::Vbr => vec![("rc", "vbr".to_string())],
+        RateControl::ConstQuality { cq } => {
+            let cq = cq.clamp(1, RateControl::max_constant_quality(codec));
+            vec![("rc", "vbr".to_string()), ("cq", cq.to_string())]
+        }
+        RateControl::Cqp { qp } => {
+            let qp = qp.min(RateControl::max_qp(codec));
+            vec![("rc", "constqp".to_string()), ("qp", qp.to_string())]
+        }
+    }
+}
+
+/// Build the peak rate and VBV buffer options (`-maxrate`, `-bufsize`)
+///
+/// Generic codec options, so they reach h264, hevc and av1_nvenc alike.
+/// NVENC caps VBR at `maxrate` over a window of `bufsize`; by default 1.5x
+/// the bitrate over one second. Constant QP has no rate to cap.
+fn vbv_options(config: &CaptureConfig) -> Vec<(&'static str, String)> {
+    if !config.effective_rate_control().uses_bitrate() {
+        return Vec::new();
+    }
+    let bits = |kbps: u32| (u64::from(kbps) * 1000).to_string();
+    vec![
+        ("maxrate", bits(config.effective_max_bitrate())),
+        ("bufsize", bits(config.effective_vbv_buffer_size())),
+    ]
+}
+
+/// Surfaces allocated when the async depth is left to FFmpeg
+const DEFAULT_SURFACES: u32 = 8;
+
+/// Build the surface and frame delay options for the async depth
+///
+/// FFmpeg's `delay` is the number of frames NVENC may hold before output,
+/// one less than the frames in flight. More frames in flight keep the GPU
+/// busy at high resolutions and frame rates, but every one of them is a
+/// frame of latency, so low-latency mode clamps the depth to 1 (with a
+/// warning). At least one surface more than the depth is allocated.
+fn async_depth_options(quality: &EncoderQuality, low_latency: bool) -> Vec<(&'static str, String)> {
+    let Some(depth) = quality.effective_async_depth(low_latency) else {
+        return vec![("surfaces", DEFAULT_SURFACES.to_string())];
+    };
+
+    if low_latency && quality.async_depth > 1 {
+        warn!(
+            "Async depth {} clamped to 1 in low-latency mode (each frame in flight adds latency)",
+            quality.async_depth
+        );
+    } else if quality.async_depth > MAX_ASYNC_DEPTH {
+        warn!(
+            "Async depth {} clamped to the maximum of {}",
+            quality.async_depth, MAX_ASYNC_DEPTH
+        );
+    }
+
+    vec![
+        ("surfaces", DEFAULT_SURFACES.max(depth + 1).to_string()),
+        ("delay", (depth - 1).to_string()),
+    ]
+}
+
+/// Build the temporal SVC options for `layers` temporal layers
+///
+/// The count is clamped to the codec's maximum and returned alongside the
+/// options. Temporal layers need a pure P-frame GOP: every frame above the
+/// base layer is a non-reference P-frame a receiver can drop under loss.
+/// Returns no options for a single layer or an unsupported codec.
+fn temporal_layer_options(codec: Codec, layers: u8) -> (u8, Vec<(&'static str, String)>) {
+    let max = codec.max_temporal_layers();
+    if layers > max && max > 0 {
+        warn!(
+            "{} temporal layers requested, {} supports {}; clamping",
+            layers, codec, max
+        );
+    }
+
+    let layers = layers.min(max);
+    if layers <= 1 {
+        return (layers.max(1), Vec::new());
+    }
+
+    let opts = vec![
+        ("bf", "0".to_string()),
+        ("nonref_p", "1".to_string()),
+        ("temporal_layers", layers.to_string()),
+    ];
+    (layers, opts)
+}
+
+/// Build the GOP options: AV1's keyframe interval, intra-refresh, or none
+///
+/// Static content forces an IDR on every refresh itself, so NVENC runs an
+/// infinite GOP. With intra-refresh NVENC takes the GOP length as the refresh
+/// period and stops inserting periodic IDRs, so the AV1 GOP length doesn't
+/// apply. Requested keyframes are still full IDRs (`forced-idr`) either way.
+fn gop_options(config: &CaptureConfig) -> Vec<(&'static str, String)> {
+    if config.content_mode == ContentMode::Static {
+        return vec![("g", "-1".to_string())]; // Infinite GOP
+    }
+
+    if let Some(period) = config.resolved_intra_refresh_period() {
+        // Intra-refresh is for low-delay streams, so no B-frames either
+        return vec![
+            ("intra-refresh", "1".to_string()),
+            ("g", period.to_string()),
+            ("bf", "0".to_string()),
+        ];
+    }
+
+    if let Some(interval) = config.keyframe_interval {
+        return vec![("g", interval.to_string())];
+    }
+
+    match config.codec {
+        Codec::Av1 => vec![("g", config.av1.resolved_gop(config.fps()).to_string())],
+        Codec::H264 | Codec::Hevc => Vec::new(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Preset;
 
     #[test]
     fn test_pixel_format_conversion() {
@@ -533,6 +1460,380 @@ mod tests {
         assert_eq!(pixel_format_from_fourcc(0x3231564E), Pixel::NV12);
     }
 
+    fn has_option(opts: &[(&'static str, String)], key: &str) -> bool {
+        opts.iter().any(|(k, _)| *k == key)
+    }
+
+    #[test]
+    fn test_temporal_layer_options() {
+        let (layers, opts) = temporal_layer_options(Codec::H264, 3);
+        assert_eq!(layers, 3);
+        assert!(opts.contains(&("temporal_layers", "3".to_string())));
+        assert!(opts.contains(&("bf", "0".to_string())));
+        assert!(has_option(&opts, "nonref_p"));
+
+        // Clamped to the codec's maximum
+        let (layers, opts) = temporal_layer_options(Codec::H264, 8);
+        assert_eq!(layers, Codec::H264.max_temporal_layers());
+        assert!(opts.contains(&("temporal_layers", "4".to_string())));
+
+        // Single layer and unsupported codecs leave the GOP alone
+        assert!(temporal_layer_options(Codec::H264, 1).1.is_empty());
+        let (layers, opts) = temporal_layer_options(Codec::Hevc, 3);
+        assert_eq!(layers, 1);
+        assert!(opts.is_empty());
+    }
+
+    fn option<'a>(opts: &'a [(&'static str, String)], key: &str) -> Option<&'a str> {
+        opts.iter().find(|(k, _)| *k == key).map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn test_gop_options_intra_refresh() {
+        for codec in [Codec::H264, Codec::Hevc, Codec::Av1] {
+            let config = CaptureConfig::monitor("test").with_codec(codec);
+            let regular = gop_options(&config);
+            assert!(!has_option(&regular, "intra-refresh"));
+
+            // The refresh period takes the place of the keyframe interval
+            let opts = gop_options(&config.with_intra_refresh(Some(30)));
+            assert_eq!(option(&opts, "intra-refresh"), Some("1"), "{}", codec);
+            assert_eq!(option(&opts, "g"), Some("30"), "{}", codec);
+            assert_eq!(option(&opts, "bf"), Some("0"), "{}", codec);
+        }
+
+        // AV1 normally gets its GOP length; one second of frames by default
+        let av1 = CaptureConfig::monitor("test").with_codec(Codec::Av1);
+        let gop = av1.av1.resolved_gop(av1.fps()).to_string();
+        assert_eq!(option(&gop_options(&av1), "g"), Some(gop.as_str()));
+        let refresh = gop_options(&av1.clone().with_intra_refresh(None));
+        let fps = av1.fps().to_string();
+        assert_eq!(option(&refresh, "g"), Some(fps.as_str()));
+
+        // Static content keeps its infinite GOP
+        let mut static_config = CaptureConfig::monitor("test").with_intra_refresh(None);
+        static_config.content_mode = ContentMode::Static;
+        let opts = gop_options(&static_config);
+        assert_eq!(option(&opts, "g"), Some("-1"));
+        assert!(!has_option(&opts, "intra-refresh"));
+    }
+
+    #[test]
+    fn test_recording_profile_options() {
+        let config = CaptureConfig::monitor("test").with_recording_profile();
+        let opts = gop_options(&config);
+        assert_eq!(option(&opts, "g"), Some("120"));
+
+        let tune = config.effective_tune();
+        assert!(!tune.is_low_latency());
+        let opts = rate_control_options(config.rate_control, config.codec, tune.is_low_latency());
+        assert_eq!(option(&opts, "cq"), Some("23"));
+
+        // CBR has no quality target
+        let cq = Some(RateControl::ConstQuality { cq: 23 });
+        assert!(rate_control_options(cq, Codec::H264, true).is_empty());
+    }
+
+    #[test]
+    fn test_vbv_options() {
+        // 6000 kbps 1080p60: peaks up to 9000 kbps over a one-second buffer
+        for codec in [Codec::H264, Codec::Hevc, Codec::Av1] {
+            let config = CaptureConfig::monitor("test")
+                .with_preset(Preset::P1080_60)
+                .with_codec(codec)
+                .with_bitrate(6000);
+            let opts = vbv_options(&config);
+            assert_eq!(option(&opts, "maxrate"), Some("9000000"), "{}", codec);
+            assert_eq!(option(&opts, "bufsize"), Some("6000000"), "{}", codec);
+
+            let capped = config.with_max_bitrate(6500).with_vbv_buffer_size(3000);
+            let opts = vbv_options(&capped);
+            assert_eq!(option(&opts, "maxrate"), Some("6500000"), "{}", codec);
+            assert_eq!(option(&opts, "bufsize"), Some("3000000"), "{}", codec);
+
+            // Low latency stays CBR; without it constant QP has no rate to cap
+            let mut cqp = capped.with_rate_control(RateControl::Cqp { qp: 20 });
+            assert!(!vbv_options(&cqp).is_empty());
+            cqp.low_latency = false;
+            assert!(vbv_options(&cqp).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_rate_control_options() {
+        // The tune decides when nothing is picked
+        assert!(rate_control_options(None, Codec::H264, false).is_empty());
+
+        let cqp = Some(RateControl::Cqp { qp: 80 });
+        for codec in [Codec::H264, Codec::Hevc] {
+            let opts = rate_control_options(cqp, codec, false);
+            assert_eq!(option(&opts, "rc"), Some("constqp"), "{}", codec);
+            assert_eq!(option(&opts, "qp"), Some("51"), "{}", codec);
+        }
+        // AV1 quantizers are qindex values
+        let opts = rate_control_options(cqp, Codec::Av1, false);
+        assert_eq!(option(&opts, "qp"), Some("80"));
+
+        let cq = Some(RateControl::ConstQuality { cq: 60 });
+        assert_eq!(option(&rate_control_options(cq, Codec::Hevc, false), "cq"), Some("51"));
+        let opts = rate_control_options(cq, Codec::Av1, false);
+        assert_eq!(option(&opts, "rc"), Some("vbr"));
+        assert_eq!(option(&opts, "cq"), Some("60"));
+
+        let vbr = Some(RateControl::Vbr);
+        assert_eq!(option(&rate_control_options(vbr, Codec::H264, false), "rc"), Some("vbr"));
+        assert!(rate_control_options(cqp, Codec::H264, true).is_empty());
+    }
+
+    #[test]
+    fn test_tune_options_ultra_low_latency() {
+        let quality = EncoderQuality {
+            lookahead: true,
+            multipass: MultipassMode::Full,
+            ..Default::default()
+        };
+
+        for codec in [Codec::H264, Codec::Hevc, Codec::Av1] {
+            let opts = tune_options(codec, Tune::Ull, Av1Tune::Uhq);
+            assert_eq!(option(&opts, "tune"), Some("ull"), "{}", codec);
+            assert_eq!(option(&opts, "bf"), Some("0"), "{}", codec);
+            assert_eq!(option(&opts, "delay"), Some("0"), "{}", codec);
+            assert_eq!(option(&opts, "rc"), Some("cbr"), "{}", codec);
+
+            let quality_opts = quality_options(&quality, Tune::Ull.is_low_latency());
+            assert!(!has_option(&quality_opts, "rc-lookahead"));
+            assert!(!has_option(&quality_opts, "multipass"));
+        }
+    }
+
+    #[test]
+    fn test_tune_options_auto() {
+        // low_latency = true maps to -tune ll on every codec
+        for codec in [Codec::H264, Codec::Hevc, Codec::Av1] {
+            let opts = tune_options(codec, Tune::Auto.resolve(true), Av1Tune::Hq);
+            assert_eq!(option(&opts, "tune"), Some("ll"), "{}", codec);
+            assert_eq!(option(&opts, "bf"), Some("0"), "{}", codec);
+        }
+
+        // Quality mode keeps B-frames; AV1 follows its own tune
+        let opts = tune_options(Codec::Hevc, Tune::Auto.resolve(false), Av1Tune::Uhq);
+        assert_eq!(option(&opts, "tune"), Some("hq"));
+        assert!(!has_option(&opts, "bf"));
+        let opts = tune_options(Codec::Av1, Tune::Auto.resolve(false), Av1Tune::Uhq);
+        assert_eq!(option(&opts, "tune"), Some("uhq"));
+        assert_eq!(option(&opts, "rc"), Some("vbr"));
+    }
+
+    #[test]
+    fn test_async_depth_options() {
+        let quality = EncoderQuality {
+            async_depth: 4,
+            ..Default::default()
+        };
+        let opts = async_depth_options(&quality, false);
+        assert_eq!(option(&opts, "delay"), Some("3"));
+        assert_eq!(option(&opts, "surfaces"), Some("8"));
+
+        // Low latency overrides the depth with one frame in flight
+        let opts = async_depth_options(&quality, true);
+        assert_eq!(option(&opts, "delay"), Some("0"));
+
+        // Deep queues get enough surfaces, up to the maximum depth
+        let deep = EncoderQuality {
+            async_depth: 100,
+            ..Default::default()
+        };
+        let opts = async_depth_options(&deep, false);
+        assert_eq!(option(&opts, "delay"), Some("31"));
+        assert_eq!(option(&opts, "surfaces"), Some("33"));
+
+        // Unset leaves the delay to FFmpeg
+        let opts = async_depth_options(&EncoderQuality::default(), false);
+        assert!(!has_option(&opts, "delay"));
+        assert_eq!(option(&opts, "surfaces"), Some("8"));
+    }
+
+    #[test]
+    fn test_quality_options_enabled() {
+        let quality = EncoderQuality {
+            lookahead: true,
+            lookahead_depth: 32,
+            spatial_aq: true,
+            temporal_aq: true,
+            ..Default::default()
+        };
+        let opts = quality_options(&quality, false);
+
+        assert!(opts.contains(&("rc-lookahead", "32".to_string())));
+        assert!(has_option(&opts, "spatial-aq"));
+        assert!(has_option(&opts, "temporal-aq"));
+    }
+
+    #[test]
+    fn test_quality_options_low_latency_omits_lookahead() {
+        let quality = EncoderQuality {
+            lookahead: true,
+            lookahead_depth: 32,
+            spatial_aq: true,
+            temporal_aq: true,
+            ..Default::default()
+        };
+        let opts = quality_options(&quality, true);
+
+        assert!(!has_option(&opts, "rc-lookahead"));
+        assert!(has_option(&opts, "spatial-aq"));
+    }
+
+    #[test]
+    fn test_quality_options_disabled() {
+        let quality = EncoderQuality {
+            lookahead: false,
+            lookahead_depth: 20,
+            spatial_aq: false,
+            temporal_aq: false,
+            ..Default::default()
+        };
+        assert!(quality_options(&quality, false).is_empty());
+    }
+
+    #[test]
+    fn test_quality_options_clamps_depth() {
+        let quality = EncoderQuality {
+            lookahead: true,
+            lookahead_depth: 1000,
+            ..Default::default()
+        };
+        let opts = quality_options(&quality, false);
+        assert!(opts.contains(&("rc-lookahead", "250".to_string())));
+    }
+
+    #[test]
+    fn test_quality_options_multipass_full() {
+        let quality = EncoderQuality {
+            multipass: MultipassMode::Full,
+            ..Default::default()
+        };
+        let opts = quality_options(&quality, false);
+        assert!(opts.contains(&("multipass", "fullres".to_string())));
+
+        let quality = EncoderQuality {
+            multipass: MultipassMode::Quarter,
+            ..Default::default()
+        };
+        let opts = quality_options(&quality, false);
+        assert!(opts.contains(&("multipass", "qres".to_string())));
+    }
+
+    #[test]
+    fn test_quality_options_low_latency_disables_multipass() {
+        let quality = EncoderQuality {
+            multipass: MultipassMode::Full,
+            ..Default::default()
+        };
+        assert!(!has_option(&quality_options(&quality, true), "multipass"));
+        assert!(!has_option(&quality_options(&EncoderQuality::default(), false), "multipass"));
+    }
+
+    /// Session that fails a set number of encodes before succeeding
+    struct MockSession {
+        failures: u32,
+        error: fn() -> NitrogenError,
+        encoded: u32,
+        reinits: u32,
+    }
+
+    impl MockSession {
+        fn new(failures: u32, error: fn() -> NitrogenError) -> Self {
+            Self {
+                failures,
+                error,
+                encoded: 0,
+                reinits: 0,
+            }
+        }
+    }
+
+    impl EncoderSession for MockSession {
+        fn encode_frame(&mut self, _frame: &Frame) -> Result<()> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err((self.error)());
+            }
+            self.encoded += 1;
+            Ok(())
+        }
+
+        fn reinit(&mut self) -> Result<()> {
+            self.reinits += 1;
+            Ok(())
+        }
+    }
+
+    fn test_frame() -> Frame {
+        Frame {
+            format: FrameFormat {
+                width: 2,
+                height: 2,
+                fourcc: 0x34325258,
+                stride: 8,
+            },
+            data: FrameData::Memory(vec![0; 16]),
+            pts: 0,
+            hdr_metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_recovery_after_transient_failure() {
+        let mut session = MockSession::new(1, || {
+            NitrogenError::nvenc_session("Failed to send frame")
+        });
+
+        assert_eq!(encode_with_recovery(&mut session, &test_frame(), 3).unwrap(), 1);
+        assert_eq!(session.reinits, 1);
+        assert_eq!(session.encoded, 1);
+
+        // Healthy frames afterwards don't re-init again
+        assert_eq!(encode_with_recovery(&mut session, &test_frame(), 3).unwrap(), 0);
+        assert_eq!(session.reinits, 1);
+    }
+
+    #[test]
+    fn test_recovery_gives_up_after_limit() {
+        let mut session = MockSession::new(10, || {
+            NitrogenError::nvenc_session("Failed to send frame")
+        });
+
+        let err = encode_with_recovery(&mut session, &test_frame(), 3).unwrap_err();
+        assert!(matches!(err, NitrogenError::Nvenc(_)));
+        assert_eq!(session.reinits, 3);
+        assert_eq!(session.encoded, 0);
+    }
+
+    #[test]
+    fn test_recovery_skips_fatal_errors() {
+        let mut session = MockSession::new(1, || NitrogenError::nvenc("Cannot allocate memory"));
+
+        assert!(encode_with_recovery(&mut session, &test_frame(), 3).is_err());
+        assert_eq!(session.reinits, 0);
+    }
+
+    #[test]
+    fn test_session_error_classification() {
+        let oom = ffmpeg::Error::Other {
+            errno: ffmpeg::error::ENOMEM,
+        };
+        assert!(matches!(session_error("send", oom), NitrogenError::Nvenc(_)));
+        assert!(matches!(
+            session_error("send", ffmpeg::Error::Unknown),
+            NitrogenError::NvencSession(_)
+        ));
+        assert!(matches!(
+            session_error("send", ffmpeg::Error::External),
+            NitrogenError::NvencSession(_)
+        ));
+    }
+
     #[test]
     fn test_nvenc_detection() {
         // This test just checks the function doesn't panic
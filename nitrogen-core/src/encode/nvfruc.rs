@@ -2,18 +2,34 @@
 //!
 //! Provides hardware-accelerated frame interpolation using NVIDIA's
 //! Optical Flow hardware on Turing+ GPUs.
+//!
+//! ## DMA-BUF Frames
+//!
+//! GPU-captured frames never reach the CPU on the way in: both DMA-BUFs are
+//! imported into CUDA (the same import as the encoder's zero-copy path, see
+//! `encode::cuda`) and handed to NvOFFRUC as device pointers. Only the
+//! interpolated frame is copied back, as a Memory frame. NvOFFRUC keeps the
+//! previous input itself, so at 2x every DMA-BUF is read once, when it
+//! arrives, and a buffer the capture pool has recycled since is never read
+//! again. NvOFFRUC produces one frame per input, so for the second and later
+//! in-between frames of a pair (3x, 4x) the earlier frame is submitted again
+//! before the later one; both are still held by the caller then.
 
 use std::ptr;
 use std::sync::Arc;
 use parking_lot::Mutex;
 use tracing::{debug, info, warn};
 
+use super::cuda::{cuda_import_available, modifier_supported, CudaImporter};
+use super::cuda_sys::CUdeviceptr;
 use super::nvfruc_sys::{
     NvOFFRUCLib, NvOFFRUC_CREATE_PARAM, NvOFFRUCCUDAResourceType, NvOFFRUCHandle,
     NvOFFRUC_PROCESS_IN_PARAMS, NvOFFRUC_PROCESS_OUT_PARAMS, NvOFFRUC_FRAMEDATA,
-    NvOFFRUCResourceType, NvOFFRUCSurfaceFormat,
+    NvOFFRUC_REGISTER_RESOURCE_PARAM, NvOFFRUC_UNREGISTER_RESOURCE_PARAM,
+    NvOFFRUCResourceType, NvOFFRUCSurfaceFormat, NVOFFRUC_MAX_RESOURCE,
 };
 use crate::error::{NitrogenError, Result};
+use crate::formats::fourcc;
 use crate::types::{Frame, FrameData, FrameFormat};
 
 /// Check if NvOFFRUC library is available on this system
@@ -32,6 +48,14 @@ pub struct NvFruc {
     height: u32,
     /// Lock for thread-safe processing
     process_lock: Mutex<()>,
+    /// DMA-BUF import into the context NvOFFRUC runs in (None without CUDA)
+    cuda: Option<CudaImporter>,
+    /// Device buffer interpolated DMA-BUF frames are written to
+    output: Option<CUdeviceptr>,
+    /// Device pointers registered with NvOFFRUC
+    registered: Vec<CUdeviceptr>,
+    /// Timestamps of the frames NvOFFRUC was given
+    timeline: FrucTimeline,
 }
 
 impl NvFruc {
@@ -62,10 +86,25 @@ impl NvFruc {
             uiReserved: [0; 32],
         };
 
-        // SAFETY: We pass properly initialized NvOFFRUC_CREATE_PARAM struct and a valid
-        // mutable pointer to receive the handle. The library is loaded and validated.
-        // All reserved fields are zeroed as required by the API.
-        let status = unsafe { (lib.create)(&create_param, &mut handle) };
+        // NvOFFRUC works in the CUDA context current when it is created, which
+        // has to be the one DMA-BUF frames get imported into
+        let mut cuda = if cuda_import_available() {
+            CudaImporter::new(0)
+                .inspect_err(|e| debug!("NvOFFRUC without DMA-BUF import: {}", e))
+                .ok()
+        } else {
+            None
+        };
+        let create = || {
+            // SAFETY: We pass properly initialized NvOFFRUC_CREATE_PARAM struct and a valid
+            // mutable pointer to receive the handle. The library is loaded and validated.
+            // All reserved fields are zeroed as required by the API.
+            unsafe { (lib.create)(&create_param, &mut handle) }
+        };
+        let status = match cuda.as_mut() {
+            Some(importer) => importer.with_context(|_| Ok(create()))?,
+            None => create(),
+        };
 
         if !status.is_success() {
             return Err(NitrogenError::encoder(format!(
@@ -85,6 +124,10 @@ impl NvFruc {
             width,
             height,
             process_lock: Mutex::new(()),
+            cuda,
+            output: None,
+            registered: Vec::new(),
+            timeline: FrucTimeline::default(),
         })
     }
 
@@ -99,8 +142,6 @@ impl NvFruc {
     /// * `Ok(Frame)` - The interpolated frame
     /// * `Err` - Interpolation failed
     pub fn interpolate(&mut self, prev: &Frame, curr: &Frame, t: f32) -> Result<Frame> {
-        let _lock = self.process_lock.lock();
-
         // Validate frame dimensions
         if prev.format.width != self.width || prev.format.height != self.height {
             return Err(NitrogenError::encoder(format!(
@@ -116,25 +157,20 @@ impl NvFruc {
             )));
         }
 
-        // For now, we only support Memory frames - DMA-BUF would need CUDA import
-        let prev_data = match &prev.data {
-            FrameData::Memory(data) => data,
-            FrameData::DmaBuf { .. } => {
-                return Err(NitrogenError::encoder(
-                    "NvOFFRUC currently requires Memory frames, not DMA-BUF".to_string(),
-                ));
+        let (prev_data, curr_data) = match (&prev.data, &curr.data) {
+            (FrameData::Memory(prev_data), FrameData::Memory(curr_data)) => (prev_data, curr_data),
+            (FrameData::DmaBuf { .. }, FrameData::DmaBuf { .. }) => {
+                return self.interpolate_dmabuf(prev, curr, t);
             }
-        };
-
-        let curr_data = match &curr.data {
-            FrameData::Memory(data) => data,
-            FrameData::DmaBuf { .. } => {
+            _ => {
                 return Err(NitrogenError::encoder(
-                    "NvOFFRUC currently requires Memory frames, not DMA-BUF".to_string(),
+                    "NvOFFRUC can't interpolate between a DMA-BUF and a Memory frame".to_string(),
                 ));
             }
         };
 
+        let _lock = self.process_lock.lock();
+
         // Allocate output buffer
         let output_size = prev_data.len();
         let mut output_data = vec![0u8; output_size];
@@ -208,6 +244,181 @@ impl NvFruc {
         })
     }
 
+    /// Interpolate two DMA-BUF frames on the GPU
+    ///
+    /// Errors (no CUDA, a tiled modifier, a non-RGB format, a failed import)
+    /// leave the caller to fall back. Any number of calls for the same pair
+    /// are fine; see [`FrucTimeline`] for what each one submits.
+    fn interpolate_dmabuf(&mut self, prev: &Frame, curr: &Frame, t: f32) -> Result<Frame> {
+        let Some(importer) = self.cuda.as_mut() else {
+            return Err(NitrogenError::encoder(
+                "CUDA DMA-BUF import is not available".to_string(),
+            ));
+        };
+
+        let height = self.height as usize;
+        let width_bytes = self.width as usize * 4;
+        let prev_ptr = map_dmabuf(importer, prev)?;
+        let curr_ptr = map_dmabuf(importer, curr)?;
+        let output = match self.output {
+            Some(output) => output,
+            None => *self.output.insert(importer.alloc(width_bytes * height)?),
+        };
+        self.register(&[prev_ptr, curr_ptr, output])?;
+
+        // NvOFFRUC interpolates between the frame it was given last and the
+        // new one; unless that was prev, it needs prev again first
+        let plan = self.timeline.plan(prev.pts, curr.pts, t);
+        let result = match plan.reference {
+            Some(reference) => self.submit(prev_ptr, prev.format.stride, reference, None),
+            None => Ok(false),
+        }
+        .and_then(|_| {
+            self.submit(curr_ptr, curr.format.stride, plan.input, Some((output, plan.output)))
+        });
+        match result {
+            Ok(_) => self.timeline.submitted(curr.pts, &plan),
+            Err(_) => self.timeline.failed(&plan),
+        }
+        let duration = curr.pts.saturating_sub(prev.pts);
+        let interpolated_pts = prev.pts + ((duration as f64 * t as f64) as u64);
+        if result? {
+            debug!("NvOFFRUC repeated a frame instead of interpolating");
+        }
+
+        let mut data = vec![0u8; width_bytes * height];
+        if let Some(ref mut importer) = self.cuda {
+            importer.copy_to_host(
+                output,
+                width_bytes,
+                &mut data,
+                width_bytes,
+                width_bytes,
+                height,
+            )?;
+        }
+
+        Ok(Frame {
+            format: FrameFormat {
+                stride: width_bytes as u32,
+                ..curr.format
+            },
+            data: FrameData::Memory(data),
+            pts: interpolated_pts,
+            hdr_metadata: curr.hdr_metadata,
+        })
+    }
+
+    /// Register device pointers NvOFFRUC hasn't seen yet
+    ///
+    /// When the table is full (the capture pool changed) everything is
+    /// unregistered first.
+    fn register(&mut self, ptrs: &[CUdeviceptr]) -> Result<()> {
+        let mut new: Vec<CUdeviceptr> = ptrs
+            .iter()
+            .copied()
+            .filter(|ptr| !self.registered.contains(ptr))
+            .collect();
+        new.dedup();
+        if new.is_empty() {
+            return Ok(());
+        }
+        let Some(ref mut importer) = self.cuda else {
+            return Err(NitrogenError::encoder("CUDA DMA-BUF import is not available".to_string()));
+        };
+        let (lib, handle, registered) = (&self.lib, self.handle, &mut self.registered);
+
+        importer.with_context(|_| {
+            if registered.len() + new.len() > NVOFFRUC_MAX_RESOURCE {
+                let mut param = NvOFFRUC_UNREGISTER_RESOURCE_PARAM::default();
+                for (slot, ptr) in param.pArrResource.iter_mut().zip(registered.iter()) {
+                    *slot = *ptr as *mut std::ffi::c_void;
+                }
+                param.uiCount = registered.len() as u32;
+                // SAFETY: Every pointer in the table was registered on this handle
+                let status = unsafe { (lib.unregister_resource)(handle, &param) };
+                if !status.is_success() {
+                    warn!("NvOFFRUC unregister failed: {}", status.to_error_string());
+                }
+                registered.clear();
+                new = ptrs.to_vec();
+                new.dedup();
+            }
+
+            let mut param = NvOFFRUC_REGISTER_RESOURCE_PARAM::default();
+            for (slot, ptr) in param.pArrResource.iter_mut().zip(new.iter()) {
+                *slot = *ptr as *mut std::ffi::c_void;
+            }
+            param.uiCount = new.len() as u32;
+            // SAFETY: The pointers are live device allocations in the current
+            // context, and there is room for them in the table
+            let status = unsafe { (lib.register_resource)(handle, &param) };
+            if !status.is_success() {
+                return Err(NitrogenError::encoder(format!(
+                    "NvOFFRUC register failed: {}",
+                    status.to_error_string()
+                )));
+            }
+            registered.extend(new);
+            Ok(())
+        })
+    }
+
+    /// Give NvOFFRUC its next input frame
+    ///
+    /// With an output buffer and timestamp, the frame at that time between
+    /// the previous input and this one is written there (ARGB, tightly
+    /// packed). Without, the input only becomes the reference for the next
+    /// call. Returns whether NvOFFRUC repeated a frame instead.
+    fn submit(
+        &mut self,
+        input: CUdeviceptr,
+        pitch: u32,
+        timestamp: f64,
+        output: Option<(CUdeviceptr, f64)>,
+    ) -> Result<bool> {
+        let Some(ref mut importer) = self.cuda else {
+            return Err(NitrogenError::encoder("CUDA DMA-BUF import is not available".to_string()));
+        };
+        let mut frame_repetition = false;
+        let in_params = NvOFFRUC_PROCESS_IN_PARAMS {
+            stFrameDataInput: NvOFFRUC_FRAMEDATA {
+                pFrame: input as *mut std::ffi::c_void,
+                nTimeStamp: timestamp,
+                nCuSurfacePitch: pitch as usize,
+                bHasFrameRepetitionOccurred: &mut frame_repetition,
+                uiReserved: [0; 32],
+            },
+            bSkipWarp: u32::from(output.is_none()),
+            ..Default::default()
+        };
+        let out_params = NvOFFRUC_PROCESS_OUT_PARAMS {
+            stFrameDataOutput: NvOFFRUC_FRAMEDATA {
+                pFrame: output.map_or(ptr::null_mut(), |(ptr, _)| ptr as *mut std::ffi::c_void),
+                nTimeStamp: output.map_or(timestamp, |(_, timestamp)| timestamp),
+                nCuSurfacePitch: (self.width * 4) as usize,
+                bHasFrameRepetitionOccurred: ptr::null_mut(),
+                uiReserved: [0; 32],
+            },
+            ..Default::default()
+        };
+
+        let (lib, handle) = (&self.lib, self.handle);
+        importer.with_context(|_| {
+            // SAFETY: The handle is live, the device pointers were registered
+            // on it, and the context they belong to is current.
+            let status = unsafe { (lib.process)(handle, &in_params, &out_params) };
+            if !status.is_success() {
+                return Err(NitrogenError::encoder(format!(
+                    "NvOFFRUC process failed: {}",
+                    status.to_error_string()
+                )));
+            }
+            Ok(())
+        })?;
+        Ok(frame_repetition)
+    }
+
     /// CPU fallback blend when GPU processing fails
     fn cpu_blend(
         &self,
@@ -255,9 +466,23 @@ impl NvFruc {
 impl Drop for NvFruc {
     fn drop(&mut self) {
         if !self.handle.is_null() {
-            // SAFETY: The handle is non-null and was successfully created.
-            // This is called only once during Drop, and the handle becomes invalid after.
-            let status = unsafe { (self.lib.destroy)(self.handle) };
+            let destroy = || {
+                // SAFETY: The handle is non-null and was successfully created.
+                // This is called only once during Drop, and the handle becomes invalid after.
+                unsafe { (self.lib.destroy)(self.handle) }
+            };
+            // Destroy in the context it was created in; the importer frees the
+            // output buffer and the imports after this
+            let status = match self.cuda {
+                Some(ref mut importer) => match importer.with_context(|_| Ok(destroy())) {
+                    Ok(status) => status,
+                    Err(e) => {
+                        warn!("Failed to destroy NvOFFRUC instance: {}", e);
+                        return;
+                    }
+                },
+                None => destroy(),
+            };
             if !status.is_success() {
                 warn!("Failed to destroy NvOFFRUC instance: {}", status.to_error_string());
             } else {
@@ -267,6 +492,96 @@ impl Drop for NvFruc {
     }
 }
 
+/// Timestamps handed to NvOFFRUC
+///
+/// NvOFFRUC places its output between the last two inputs by timestamp.
+/// Capture timestamps would go backwards when a pair's earlier frame is
+/// submitted again for another in-between frame, so each submission gets a
+/// timestamp of its own instead, always increasing and spaced like the
+/// frames they belong to.
+#[derive(Debug, Clone, Copy, Default)]
+struct FrucTimeline {
+    /// Capture pts of the frame NvOFFRUC holds as its reference
+    held: Option<u64>,
+    /// Latest timestamp NvOFFRUC may have been given
+    clock: f64,
+}
+
+/// Timestamps for interpolating one frame between a pair
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FrucPlan {
+    /// The earlier frame has to be submitted first, at this timestamp
+    reference: Option<f64>,
+    /// The later frame
+    input: f64,
+    /// The interpolated frame
+    output: f64,
+}
+
+impl FrucTimeline {
+    /// What to submit to interpolate at `t` between `prev_pts` and `curr_pts`
+    fn plan(&self, prev_pts: u64, curr_pts: u64, t: f32) -> FrucPlan {
+        let span = (curr_pts.saturating_sub(prev_pts) as f64).max(1.0);
+        // Unless NvOFFRUC already holds prev (the previous pair's later
+        // frame), prev goes in again: the first pair, a gap, or the next t
+        // of the same pair in 3x and 4x
+        let (reference, start) = if self.held == Some(prev_pts) {
+            (None, self.clock)
+        } else {
+            (Some(self.clock + span), self.clock + span)
+        };
+        FrucPlan {
+            reference,
+            input: start + span,
+            output: start + span * t as f64,
+        }
+    }
+
+    /// Record that the later frame of `plan` was submitted
+    fn submitted(&mut self, curr_pts: u64, plan: &FrucPlan) {
+        self.held = Some(curr_pts);
+        self.clock = plan.input;
+    }
+
+    /// Record that submitting `plan` failed part way; the next pair starts over
+    fn failed(&mut self, plan: &FrucPlan) {
+        self.held = None;
+        self.clock = plan.input;
+    }
+}
+
+/// Whether NvOFFRUC's ARGB surfaces can hold this format
+///
+/// NvOFFRUC only moves 4-byte pixels around, so any 8-bit RGB channel order
+/// comes out the way it went in.
+fn is_argb_layout(format: u32) -> bool {
+    use fourcc::*;
+    matches!(
+        format,
+        XRGB8888 | XBGR8888 | ARGB8888 | ABGR8888 | RGBA8888 | RGBX8888 | BGRX8888 | BGRA8888
+    )
+}
+
+/// Device address of a DMA-BUF frame's pixels
+fn map_dmabuf(importer: &mut CudaImporter, frame: &Frame) -> Result<CUdeviceptr> {
+    let FrameData::DmaBuf { fd, offset, modifier } = frame.data else {
+        return Err(NitrogenError::encoder("Not a DMA-BUF frame".to_string()));
+    };
+    if !is_argb_layout(frame.format.fourcc) {
+        return Err(NitrogenError::encoder(format!(
+            "NvOFFRUC needs 8-bit RGB frames, got fourcc {:#010x}",
+            frame.format.fourcc
+        )));
+    }
+    if !modifier_supported(modifier) {
+        return Err(NitrogenError::encoder(format!(
+            "DMA-BUF modifier {:#x} can't be mapped into CUDA",
+            modifier
+        )));
+    }
+    importer.map(fd, offset, frame.format.stride, frame.format.height as usize)
+}
+
 // SAFETY: NvFruc contains an opaque handle to the NVIDIA FRUC library.
 // The library itself is thread-safe according to NVIDIA documentation.
 // We additionally protect all process() calls with a Mutex<()> to ensure
@@ -307,6 +622,50 @@ mod tests {
         assert_eq!(NvOFFRUC_STATUS::ERR_NOT_SUPPORTED.to_error_string(), "Optical flow not supported on this hardware");
     }
 
+    #[test]
+    fn test_argb_layout() {
+        assert!(is_argb_layout(fourcc::BGRX8888));
+        assert!(is_argb_layout(fourcc::XRGB8888));
+        assert!(!is_argb_layout(fourcc::NV12));
+        assert!(!is_argb_layout(fourcc::RGB888));
+        assert!(!is_argb_layout(fourcc::P010));
+    }
+
+    #[test]
+    fn test_timeline_serves_every_t_of_a_pair() {
+        let mut timeline = FrucTimeline::default();
+        let mut stamps = Vec::new();
+        let mut run = |timeline: &mut FrucTimeline, prev: u64, curr: u64, t: f32| {
+            let plan = timeline.plan(prev, curr, t);
+            if let Some(reference) = plan.reference {
+                stamps.push(reference);
+            }
+            stamps.push(plan.input);
+            timeline.submitted(curr, &plan);
+            plan
+        };
+
+        // 4x: three in-between frames of one pair, each t of the way across
+        for t in [0.25, 0.5, 0.75] {
+            let plan = run(&mut timeline, 1000, 2000, t);
+            let reference = plan.reference.expect("prev is submitted for every t");
+            assert_eq!(plan.input - reference, 1000.0);
+            assert_eq!(plan.output, reference + 1000.0 * t as f64);
+        }
+
+        // The next pair continues from its earlier frame without resubmitting it
+        let plan = run(&mut timeline, 2000, 3000, 0.5);
+        assert_eq!(plan.reference, None);
+
+        // NvOFFRUC never sees time go backwards
+        assert!(stamps.windows(2).all(|pair| pair[1] > pair[0]), "{:?}", stamps);
+
+        // After a failure the next pair submits its earlier frame again, still later
+        timeline.failed(&plan);
+        let retry = timeline.plan(3000, 4000, 0.5);
+        assert!(retry.reference.is_some_and(|reference| reference > plan.input));
+    }
+
     #[test]
     fn test_status_is_success() {
         assert!(NvOFFRUC_STATUS::SUCCESS.is_success());
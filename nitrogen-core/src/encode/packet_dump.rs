@@ -0,0 +1,223 @@
+//! Encoded packet dump for debugging muxers and decoders
+//!
+//! With `NITROGEN_DUMP_PACKETS=<path>` set, the output of every encoder is
+//! also written to that file as JSON lines: timestamps, size, keyframe flag,
+//! the NAL unit types of H.264/HEVC packets and their first bytes. That is
+//! usually enough to explain "non-monotonic DTS" or missing-keyframe reports.
+//! Without the variable no receiver is subscribed, so the dump costs nothing.
+
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::sync::OnceLock;
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{info, warn};
+
+use super::{EncodedPacket, Encoder};
+use crate::config::{CaptureConfig, Codec};
+
+/// Environment variable naming the packet dump file
+pub const DUMP_PACKETS_ENV: &str = "NITROGEN_DUMP_PACKETS";
+
+/// Leading packet bytes included in each line
+const HEAD_BYTES: usize = 16;
+
+/// One line of the dump
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PacketRecord {
+    /// Encoder the packet came from, e.g. "H.264 1920x1080"
+    pub encoder: String,
+    /// Packet number within that encoder's output, from 0
+    pub index: u64,
+    /// Presentation timestamp
+    pub pts: i64,
+    /// Decode timestamp
+    pub dts: i64,
+    /// Size in bytes
+    pub size: usize,
+    /// Whether the encoder flagged the packet as a keyframe
+    pub keyframe: bool,
+    /// NAL unit types in the packet (H.264/HEVC only)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nal_types: Vec<u8>,
+    /// First bytes of the packet, as hex
+    pub head: String,
+}
+
+/// Turns one encoder's packets into dump lines
+#[derive(Debug)]
+pub struct PacketDump {
+    codec: Codec,
+    encoder: String,
+    count: u64,
+}
+
+impl PacketDump {
+    /// Dump packets of `codec` labelled with `encoder`
+    pub fn new(codec: Codec, encoder: impl Into<String>) -> Self {
+        Self {
+            codec,
+            encoder: encoder.into(),
+            count: 0,
+        }
+    }
+
+    /// Describe the next packet
+    pub fn record(&mut self, packet: &EncodedPacket) -> PacketRecord {
+        let head = packet.data.iter().take(HEAD_BYTES).map(|b| format!("{:02x}", b)).collect();
+        let record = PacketRecord {
+            encoder: self.encoder.clone(),
+            index: self.count,
+            pts: packet.pts,
+            dts: packet.dts,
+            size: packet.data.len(),
+            keyframe: packet.keyframe,
+            nal_types: nal_unit_types(self.codec, &packet.data),
+            head,
+        };
+        self.count += 1;
+        record
+    }
+
+    /// Write the next packet as a JSON line
+    pub fn write(&mut self, out: &mut impl Write, packet: &EncodedPacket) -> io::Result<()> {
+        let mut line = serde_json::to_vec(&self.record(packet))?;
+        line.push(b'\n');
+        out.write_all(&line)
+    }
+
+    /// Packets described so far
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+/// NAL unit types of an Annex-B H.264 or HEVC packet
+///
+/// AV1 packets are OBUs without start codes and yield no types.
+pub fn nal_unit_types(codec: Codec, data: &[u8]) -> Vec<u8> {
+    let nal_type: fn(u8) -> u8 = match codec {
+        Codec::H264 => |header| header & 0x1f,
+        Codec::Hevc => |header| (header >> 1) & 0x3f,
+        Codec::Av1 => return Vec::new(),
+    };
+
+    // Both 3- and 4-byte start codes end in 00 00 01
+    data.windows(4)
+        .filter(|w| w[..3] == [0, 0, 1])
+        .map(|w| nal_type(w[3]))
+        .collect()
+}
+
+/// The dump file, opened on first use (None = dumping is off)
+fn dump_writer() -> Option<&'static Mutex<LineWriter<File>>> {
+    static WRITER: OnceLock<Option<Mutex<LineWriter<File>>>> = OnceLock::new();
+    WRITER
+        .get_or_init(|| {
+            let path = std::env::var_os(DUMP_PACKETS_ENV)?;
+            match File::create(&path) {
+                Ok(file) => {
+                    info!("Dumping encoded packets to {:?}", path);
+                    Some(Mutex::new(LineWriter::new(file)))
+                }
+                Err(e) => {
+                    warn!("Failed to create packet dump {:?}: {}", path, e);
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+/// Follow an encoder's output into the dump file, if one is set
+///
+/// The tap runs on its own thread until the encoder is dropped.
+pub fn tap_packets(encoder: &dyn Encoder, config: &CaptureConfig) {
+    let Some(writer) = dump_writer() else {
+        return;
+    };
+
+    let (width, height) = encoder.output_size();
+    let mut dump = PacketDump::new(config.codec, format!("{} {}x{}", config.codec, width, height));
+    let mut rx = encoder.subscribe();
+    let spawned = std::thread::Builder::new()
+        .name("nitrogen-packet-dump".to_string())
+        .spawn(move || loop {
+            match rx.blocking_recv() {
+                Ok(packet) => {
+                    if let Err(e) = dump.write(&mut *writer.lock(), &packet) {
+                        warn!("Packet dump write failed, stopping it: {}", e);
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(n)) => warn!("Packet dump skipped {} packets", n),
+                Err(RecvError::Closed) => break,
+            }
+        });
+    if let Err(e) = spawned {
+        warn!("Failed to start packet dump thread: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nal_unit_types() {
+        // SPS, PPS and an IDR slice with mixed start code lengths
+        let h264 = [0, 0, 0, 1, 0x67, 0x42, 0, 0, 1, 0x68, 0xce, 0, 0, 0, 1, 0x65, 0x88];
+        assert_eq!(nal_unit_types(Codec::H264, &h264), vec![7, 8, 5]);
+
+        // VPS and an IDR_W_RADL slice
+        let hevc = [0, 0, 0, 1, 0x40, 0x01, 0, 0, 1, 0x26, 0x01];
+        assert_eq!(nal_unit_types(Codec::Hevc, &hevc), vec![32, 19]);
+
+        assert!(nal_unit_types(Codec::Av1, &h264).is_empty());
+    }
+
+    #[cfg(feature = "mock-encoder")]
+    #[test]
+    fn test_dump_mock_stream() {
+        use crate::encode::MockEncoder;
+        use crate::types::{Frame, FrameData, FrameFormat};
+
+        let config = CaptureConfig::monitor("test");
+        let mut encoder = MockEncoder::new(&config).with_gop(5);
+        let mut rx = encoder.subscribe();
+        let frame = Frame {
+            format: FrameFormat {
+                width: 2,
+                height: 2,
+                fourcc: 0x34325258,
+                stride: 8,
+            },
+            data: FrameData::Memory(vec![0; 16]),
+            pts: 0,
+            hdr_metadata: None,
+        };
+
+        let mut dump = PacketDump::new(config.codec, "mock");
+        let mut out = Vec::new();
+        for _ in 0..12 {
+            encoder.encode(&frame).unwrap();
+            dump.write(&mut out, &rx.try_recv().unwrap()).unwrap();
+        }
+        assert_eq!(dump.count(), 12);
+
+        let records: Vec<PacketRecord> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 12);
+        for (i, record) in records.iter().enumerate() {
+            assert_eq!(record.index, i as u64);
+            assert_eq!(record.keyframe, i % 5 == 0, "packet {}", i);
+            assert_eq!(record.nal_types[0], if record.keyframe { 5 } else { 1 });
+            assert!(record.head.starts_with("00000001"));
+        }
+    }
+}
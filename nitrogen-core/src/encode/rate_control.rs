@@ -0,0 +1,127 @@
+//! Rate control modes for the video encoder
+//!
+//! Without an explicit mode the tune decides: CBR for low-latency output,
+//! VBR otherwise. Recordings can aim for a quality instead of a bitrate,
+//! either with a constant quantizer (CQP), where the bitrate is ignored, or
+//! with NVENC's constant-quality VBR, where it only caps the peaks.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Codec, MAX_CONSTANT_QUALITY};
+
+/// Highest H.264/HEVC quantizer
+pub const MAX_QP: u8 = 51;
+
+/// Highest AV1 constant-quality level (AV1 NVENC goes past H.264's 51)
+pub const MAX_AV1_CONSTANT_QUALITY: u8 = 63;
+
+/// How the encoder spends bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateControl {
+    /// Constant bitrate, for streaming
+    Cbr,
+    /// Variable bitrate around the target
+    Vbr,
+    /// Constant quantizer; the bitrate is ignored
+    Cqp {
+        /// Quantizer (0-51; AV1 takes a 0-255 quantizer index)
+        qp: u8,
+    },
+    /// VBR towards a quality level, with the bitrate as the ceiling
+    ConstQuality {
+        /// Quality level (1-51, 1-63 for AV1; lower is better)
+        cq: u8,
+    },
+}
+
+impl RateControl {
+    /// Whether the mode looks at the bitrate at all
+    pub fn uses_bitrate(&self) -> bool {
+        !matches!(self, Self::Cqp { .. })
+    }
+
+    /// Highest quantizer `codec` accepts
+    pub fn max_qp(codec: Codec) -> u8 {
+        match codec {
+            Codec::Av1 => u8::MAX,
+            Codec::H264 | Codec::Hevc => MAX_QP,
+        }
+    }
+
+    /// Highest constant-quality level `codec` accepts
+    pub fn max_constant_quality(codec: Codec) -> u8 {
+        match codec {
+            Codec::Av1 => MAX_AV1_CONSTANT_QUALITY,
+            Codec::H264 | Codec::Hevc => MAX_CONSTANT_QUALITY,
+        }
+    }
+
+    /// Check the quality values against the codec's range
+    pub fn validate(&self, codec: Codec) -> Result<(), String> {
+        match *self {
+            Self::Cqp { qp } if qp > Self::max_qp(codec) => Err(format!(
+                "Constant QP {} is out of range for {} (0-{})",
+                qp,
+                codec,
+                Self::max_qp(codec)
+            )),
+            Self::ConstQuality { cq } if !(1..=Self::max_constant_quality(codec)).contains(&cq) => {
+                Err(format!(
+                    "Constant quality {} is out of range (1-{})",
+                    cq,
+                    Self::max_constant_quality(codec)
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Name for messages
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::Cbr => "CBR",
+            Self::Vbr => "VBR",
+            Self::Cqp { .. } => "Constant QP",
+            Self::ConstQuality { .. } => "Constant quality",
+        }
+    }
+}
+
+impl std::fmt::Display for RateControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cbr => write!(f, "cbr"),
+            Self::Vbr => write!(f, "vbr"),
+            Self::Cqp { qp } => write!(f, "cqp:{}", qp),
+            Self::ConstQuality { cq } => write!(f, "cq:{}", cq),
+        }
+    }
+}
+
+impl std::str::FromStr for RateControl {
+    type Err = String;
+
+    /// Parse `cbr`, `vbr`, `cqp:<qp>` or `cq:<level>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let (mode, value) = match s.split_once(':') {
+            Some((mode, value)) => (mode, Some(value)),
+            None => (s.as_str(), None),
+        };
+        let level = |name: &str| -> Result<u8, String> {
+            let value = value.ok_or_else(|| format!("{} needs a level, e.g. {}:23", mode, name))?;
+            value
+                .parse()
+                .map_err(|_| format!("invalid {} level '{}'", name, value))
+        };
+
+        match mode {
+            "cbr" if value.is_none() => Ok(Self::Cbr),
+            "vbr" if value.is_none() => Ok(Self::Vbr),
+            "cqp" | "qp" => Ok(Self::Cqp { qp: level("cqp")? }),
+            "cq" | "crf" => Ok(Self::ConstQuality { cq: level("cq")? }),
+            _ => Err(format!("Unknown rate control: {}", s)),
+        }
+    }
+}
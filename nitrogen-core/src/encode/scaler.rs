@@ -1,25 +1,247 @@
 //! Frame scaling and format conversion
 //!
 //! Handles resolution changes and pixel format conversion
-//! between capture and encoding.
+//! between capture and encoding. When the source aspect ratio differs from
+//! the output, the picture is stretched, fitted inside bars (letterbox /
+//! pillarbox) or cropped to fill, depending on the [`FitMode`].
+
+use std::fmt;
+use std::str::FromStr;
 
 use ffmpeg_next::format::Pixel;
 use ffmpeg_next::software::scaling::{self, Flags};
 use ffmpeg_next::util::frame::video::Video;
+use serde::{Deserialize, Serialize};
 use tracing::debug;
 
 use crate::error::{NitrogenError, Result};
 
+/// How a source is fitted into an output of a different aspect ratio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FitMode {
+    /// Scale both axes independently (distorts the picture)
+    Stretch,
+    /// Fit the whole picture, centered, with bars on the short sides
+    #[default]
+    Letterbox,
+    /// Fill the output, cutting the long sides off
+    Crop,
+}
+
+impl fmt::Display for FitMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stretch => write!(f, "stretch"),
+            Self::Letterbox => write!(f, "letterbox"),
+            Self::Crop => write!(f, "crop"),
+        }
+    }
+}
+
+impl FromStr for FitMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "stretch" | "fill" => Ok(Self::Stretch),
+            "letterbox" | "pillarbox" | "fit" => Ok(Self::Letterbox),
+            "crop" | "zoom" => Ok(Self::Crop),
+            _ => Err(format!("Unknown fit mode: {}", s)),
+        }
+    }
+}
+
+/// Color of the letterbox bars
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct BarColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl BarColor {
+    /// Black bars
+    pub const BLACK: Self = Self { r: 0, g: 0, b: 0 };
+
+    /// Bar pixel in the byte order of a packed format
+    fn pixel(self, format: Pixel) -> [u8; 4] {
+        let Self { r, g, b } = self;
+        match format {
+            Pixel::RGBA | Pixel::RGBZ | Pixel::RGB24 => [r, g, b, 0xff],
+            Pixel::ARGB | Pixel::ZRGB => [0xff, r, g, b],
+            Pixel::ABGR | Pixel::ZBGR => [0xff, b, g, r],
+            _ => [b, g, r, 0xff],
+        }
+    }
+}
+
+impl fmt::Display for BarColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+impl FromStr for BarColor {
+    type Err = String;
+
+    /// Parse "black", "white", "#rrggbb" or "rrggbb"
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        match s.to_lowercase().as_str() {
+            "black" => return Ok(Self::BLACK),
+            "white" => return Ok(Self { r: 0xff, g: 0xff, b: 0xff }),
+            _ => {}
+        }
+
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+        match (hex.len(), hex.is_ascii()) {
+            (6, true) => match (channel(0), channel(2), channel(4)) {
+                (Ok(r), Ok(g), Ok(b)) => Ok(Self { r, g, b }),
+                _ => Err(format!("Invalid bar color: {}", s)),
+            },
+            _ => Err(format!("Invalid bar color: {} (expected #rrggbb)", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for BarColor {
+    type Error = String;
+
+    fn try_from(s: String) -> std::result::Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<BarColor> for String {
+    fn from(color: BarColor) -> Self {
+        color.to_string()
+    }
+}
+
+/// A rectangle within a frame, in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Rect {
+    fn full(width: u32, height: u32) -> Self {
+        Self {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+}
+
+/// Source region to scale and where it lands in the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FitGeometry {
+    src: Rect,
+    dst: Rect,
+}
+
+/// Work out the fit of a source into an output size
+///
+/// Differences of a couple of pixels (1366x768 into 1080p) are stretched
+/// rather than framed with a sliver of bar.
+fn fit_geometry(
+    fit: FitMode,
+    src_width: u32,
+    src_height: u32,
+    width: u32,
+    height: u32,
+) -> FitGeometry {
+    let mut geometry = FitGeometry {
+        src: Rect::full(src_width, src_height),
+        dst: Rect::full(width, height),
+    };
+    let same_aspect = src_width as u64 * height as u64 == src_height as u64 * width as u64;
+    if same_aspect || src_width == 0 || src_height == 0 || width == 0 || height == 0 {
+        return geometry;
+    }
+
+    match fit {
+        FitMode::Stretch => {}
+        FitMode::Letterbox => {
+            let (w, h) = calculate_scaled_size(src_width, src_height, width, height);
+            let (w, h) = (w.clamp(1, width), h.clamp(1, height));
+            if width - w > 2 || height - h > 2 {
+                geometry.dst = Rect {
+                    x: (width - w) / 2,
+                    y: (height - h) / 2,
+                    width: w,
+                    height: h,
+                };
+            }
+        }
+        FitMode::Crop => {
+            let (x, y, w, h) = calculate_crop(src_width, src_height, width as f64 / height as f64);
+            let (w, h) = (w.clamp(1, src_width), h.clamp(1, src_height));
+            if src_width - w > 2 || src_height - h > 2 {
+                geometry.src = Rect {
+                    x: x.min(src_width - w),
+                    y: y.min(src_height - h),
+                    width: w,
+                    height: h,
+                };
+            }
+        }
+    }
+    geometry
+}
+
+/// Bytes per pixel of single-plane packed formats
+fn packed_bytes_per_pixel(format: Pixel) -> Option<usize> {
+    match format {
+        Pixel::BGRA
+        | Pixel::RGBA
+        | Pixel::ARGB
+        | Pixel::ABGR
+        | Pixel::BGRZ
+        | Pixel::RGBZ
+        | Pixel::ZRGB
+        | Pixel::ZBGR => Some(4),
+        Pixel::RGB24 | Pixel::BGR24 => Some(3),
+        _ => None,
+    }
+}
+
 /// Frame scaler for resolution and format conversion
 pub struct FrameScaler {
     /// FFmpeg scaling context
     context: scaling::Context,
+    /// Input frame buffer (used by [`FrameScaler::scale_packed`])
+    input: Video,
     /// Output frame buffer
     output: Video,
+    /// Input width
+    src_width: u32,
+    /// Input height
+    src_height: u32,
     /// Output width
     width: u32,
     /// Output height
     height: u32,
+    /// Input pixel format
+    src_format: Pixel,
+    /// Output pixel format
+    dst_format: Pixel,
+    /// Aspect ratio handling
+    fit: FitMode,
+    /// Letterbox bar color
+    color: BarColor,
+    /// Current source crop and output placement
+    geometry: FitGeometry,
+    /// Scaled picture before it is placed between the bars (fitted only)
+    placed: Option<Video>,
 }
 
 impl FrameScaler {
@@ -48,18 +270,142 @@ impl FrameScaler {
         )
         .map_err(|e| NitrogenError::encoder(format!("Failed to create scaler: {}", e)))?;
 
+        let input = Video::new(src_format, src_width, src_height);
         let output = Video::new(dst_format, dst_width, dst_height);
 
         Ok(Self {
             context,
+            input,
             output,
+            src_width,
+            src_height,
             width: dst_width,
             height: dst_height,
+            src_format,
+            dst_format,
+            fit: FitMode::Stretch,
+            color: BarColor::BLACK,
+            geometry: FitGeometry {
+                src: Rect::full(src_width, src_height),
+                dst: Rect::full(dst_width, dst_height),
+            },
+            placed: None,
         })
     }
 
+    /// Handle aspect mismatches with `fit` instead of stretching
+    ///
+    /// Letterbox and crop need packed single-plane formats (BGRA, RGB24...).
+    pub fn with_fit(mut self, fit: FitMode, color: BarColor) -> Result<Self> {
+        let packed = packed_bytes_per_pixel(self.src_format).is_some()
+            && packed_bytes_per_pixel(self.dst_format).is_some();
+        if fit != FitMode::Stretch && !packed {
+            return Err(NitrogenError::Unsupported(format!(
+                "{} fit needs packed formats, not {:?} -> {:?}",
+                fit, self.src_format, self.dst_format
+            )));
+        }
+
+        self.fit = fit;
+        self.color = color;
+        self.configure(self.src_width, self.src_height)?;
+        Ok(self)
+    }
+
+    /// Create a BGRA to BGRA scaler that keeps the alpha channel
+    ///
+    /// Used for the virtual camera when transparency is enabled. Formats
+    /// with padding instead of alpha (BGR0) would drop it.
+    pub fn bgra(src_width: u32, src_height: u32, dst_width: u32, dst_height: u32) -> Result<Self> {
+        Self::new(
+            src_width,
+            src_height,
+            Pixel::BGRA,
+            dst_width,
+            dst_height,
+            Pixel::BGRA,
+        )
+    }
+
+    /// Follow a new input size, keeping the output size and formats
+    ///
+    /// For sources that renegotiate mid-capture (fullscreen toggles). Does
+    /// nothing when the size is unchanged.
+    pub fn set_input_size(&mut self, src_width: u32, src_height: u32) -> Result<()> {
+        if (src_width, src_height) == (self.src_width, self.src_height) {
+            return Ok(());
+        }
+
+        debug!(
+            "Scaler input {}x{} -> {}x{} (output stays {}x{})",
+            self.src_width, self.src_height, src_width, src_height, self.width, self.height
+        );
+        self.configure(src_width, src_height)
+    }
+
+    /// Rebuild the context and buffers for a source size and the fit mode
+    fn configure(&mut self, src_width: u32, src_height: u32) -> Result<()> {
+        let geometry = fit_geometry(self.fit, src_width, src_height, self.width, self.height);
+        let FitGeometry { src, dst } = geometry;
+        if src != Rect::full(src_width, src_height) || dst != Rect::full(self.width, self.height) {
+            debug!(
+                "Fitting ({}) {}x{}+{}+{} -> {}x{}+{}+{}",
+                self.fit, src.width, src.height, src.x, src.y, dst.width, dst.height, dst.x, dst.y
+            );
+        }
+
+        self.context = scaling::Context::get(
+            self.src_format,
+            src.width,
+            src.height,
+            self.dst_format,
+            dst.width,
+            dst.height,
+            Flags::BILINEAR,
+        )
+        .map_err(|e| NitrogenError::encoder(format!("Failed to create scaler: {}", e)))?;
+        self.input = Video::new(self.src_format, src.width, src.height);
+
+        self.placed = if dst == Rect::full(self.width, self.height) {
+            None
+        } else {
+            self.fill_bars();
+            Some(Video::new(self.dst_format, dst.width, dst.height))
+        };
+        self.geometry = geometry;
+        self.src_width = src_width;
+        self.src_height = src_height;
+        Ok(())
+    }
+
+    /// Paint the whole output in the bar color
+    ///
+    /// The picture area is overwritten on every frame, so this only runs
+    /// when the geometry changes.
+    fn fill_bars(&mut self) {
+        let bytes_per_pixel = packed_bytes_per_pixel(self.dst_format).unwrap_or(4);
+        let pixel = self.color.pixel(self.dst_format);
+        let row: Vec<u8> = pixel[..bytes_per_pixel].repeat(self.width as usize);
+        let stride = self.output.stride(0);
+        let plane = self.output.data_mut(0);
+        for y in 0..self.height as usize {
+            plane[y * stride..y * stride + row.len()].copy_from_slice(&row);
+        }
+    }
+
+    /// Whether frames go through the crop/placement path
+    fn is_fitted(&self) -> bool {
+        self.placed.is_some() || self.geometry.src != Rect::full(self.src_width, self.src_height)
+    }
+
     /// Scale a frame
     pub fn scale(&mut self, input: &Video) -> Result<&Video> {
+        if self.is_fitted() {
+            let bytes_per_pixel = packed_bytes_per_pixel(self.src_format).unwrap_or(4);
+            self.scale_rows(input.data(0), input.stride(0), bytes_per_pixel)?;
+            return Ok(&self.output);
+        }
+
         self.context
             .run(input, &mut self.output)
             .map_err(|e| NitrogenError::encoder(format!("Scaling failed: {}", e)))?;
@@ -67,14 +413,116 @@ impl FrameScaler {
         Ok(&self.output)
     }
 
+    /// Scale the source region of a single-plane buffer into the output
+    fn scale_rows(&mut self, data: &[u8], stride: usize, bytes_per_pixel: usize) -> Result<()> {
+        let FitGeometry { src, dst } = self.geometry;
+        let src_row = src.width as usize * bytes_per_pixel;
+        let input_stride = self.input.stride(0);
+        let plane = self.input.data_mut(0);
+        for row in 0..src.height as usize {
+            let start = (src.y as usize + row) * stride + src.x as usize * bytes_per_pixel;
+            plane[row * input_stride..row * input_stride + src_row]
+                .copy_from_slice(&data[start..start + src_row]);
+        }
+
+        let Some(placed) = self.placed.as_mut() else {
+            return self
+                .context
+                .run(&self.input, &mut self.output)
+                .map_err(|e| NitrogenError::encoder(format!("Scaling failed: {}", e)));
+        };
+        self.context
+            .run(&self.input, placed)
+            .map_err(|e| NitrogenError::encoder(format!("Scaling failed: {}", e)))?;
+
+        // Copy the picture between the bars
+        let dst_bytes = packed_bytes_per_pixel(self.dst_format).unwrap_or(4);
+        let dst_row = dst.width as usize * dst_bytes;
+        let placed_stride = placed.stride(0);
+        let output_stride = self.output.stride(0);
+        let picture = placed.data(0);
+        let plane = self.output.data_mut(0);
+        for row in 0..dst.height as usize {
+            let start = (dst.y as usize + row) * output_stride + dst.x as usize * dst_bytes;
+            plane[start..start + dst_row]
+                .copy_from_slice(&picture[row * placed_stride..row * placed_stride + dst_row]);
+        }
+        Ok(())
+    }
+
+    /// Scale a tightly packed single-plane buffer (e.g. BGRA)
+    ///
+    /// Returns the scaled frame without row padding.
+    pub fn scale_packed(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let src_row = data.len() / self.src_height.max(1) as usize;
+        let bytes_per_pixel = src_row / self.src_width.max(1) as usize;
+        if bytes_per_pixel == 0 || src_row * self.src_height as usize != data.len() {
+            return Err(NitrogenError::encoder(format!(
+                "Packed buffer of {} bytes does not match {}x{}",
+                data.len(),
+                self.src_width,
+                self.src_height
+            )));
+        }
+
+        self.scale_rows(data, src_row, bytes_per_pixel)?;
+        Ok(self.packed_output(bytes_per_pixel))
+    }
+
+    /// Scale a single-plane buffer whose rows are `stride` bytes apart
+    ///
+    /// For packed formats with row padding (PipeWire buffers). Returns the
+    /// scaled frame without row padding.
+    pub fn scale_strided(&mut self, data: &[u8], stride: usize) -> Result<Vec<u8>> {
+        let bytes_per_pixel = packed_bytes_per_pixel(self.src_format).ok_or_else(|| {
+            NitrogenError::Unsupported(format!("{:?} is not a packed format", self.src_format))
+        })?;
+        let row = self.src_width as usize * bytes_per_pixel;
+        let needed = stride * (self.src_height.max(1) as usize - 1) + row;
+        if stride < row || data.len() < needed {
+            return Err(NitrogenError::encoder(format!(
+                "Buffer of {} bytes (stride {}) does not match {}x{}",
+                data.len(),
+                stride,
+                self.src_width,
+                self.src_height
+            )));
+        }
+
+        self.scale_rows(data, stride, bytes_per_pixel)?;
+        let dst_bytes = packed_bytes_per_pixel(self.dst_format).unwrap_or(bytes_per_pixel);
+        Ok(self.packed_output(dst_bytes))
+    }
+
+    /// Copy the output frame without row padding
+    fn packed_output(&self, bytes_per_pixel: usize) -> Vec<u8> {
+        let dst_row = self.width as usize * bytes_per_pixel;
+        let dst_stride = self.output.stride(0);
+        let plane = self.output.data(0);
+        let mut packed = Vec::with_capacity(dst_row * self.height as usize);
+        for row in 0..self.height as usize {
+            packed.extend_from_slice(&plane[row * dst_stride..row * dst_stride + dst_row]);
+        }
+        packed
+    }
+
+    /// Get input dimensions
+    pub fn input_size(&self) -> (u32, u32) {
+        (self.src_width, self.src_height)
+    }
+
     /// Get output dimensions
     pub fn output_size(&self) -> (u32, u32) {
         (self.width, self.height)
     }
+
+    /// Aspect ratio handling in use
+    pub fn fit(&self) -> FitMode {
+        self.fit
+    }
 }
 
 /// Calculate scaled dimensions maintaining aspect ratio
-#[allow(dead_code)]
 pub fn calculate_scaled_size(
     src_width: u32,
     src_height: u32,
@@ -96,19 +544,18 @@ pub fn calculate_scaled_size(
 }
 
 /// Calculate crop region for aspect ratio conversion
-#[allow(dead_code)]
 pub fn calculate_crop(src_width: u32, src_height: u32, dst_aspect: f64) -> (u32, u32, u32, u32) {
     let src_aspect = src_width as f64 / src_height as f64;
 
     if src_aspect > dst_aspect {
         // Source is wider, crop width
         let new_width = (src_height as f64 * dst_aspect) as u32;
-        let offset = (src_width - new_width) / 2;
+        let offset = src_width.saturating_sub(new_width) / 2;
         (offset, 0, new_width, src_height)
     } else {
         // Source is taller, crop height
         let new_height = (src_width as f64 / dst_aspect) as u32;
-        let offset = (src_height - new_height) / 2;
+        let offset = src_height.saturating_sub(new_height) / 2;
         (0, offset, src_width, new_height)
     }
 }
@@ -131,6 +578,35 @@ mod tests {
         assert!(h < 1080);
     }
 
+    #[test]
+    fn test_bgra_scaler_keeps_alpha() {
+        let mut scaler = FrameScaler::bgra(32, 32, 16, 16).expect("Should create scaler");
+        let input: Vec<u8> = [0x10, 0x20, 0x30, 0x40].repeat(32 * 32);
+
+        let output = scaler.scale_packed(&input).expect("Should scale");
+        assert_eq!(output.len(), 16 * 16 * 4);
+        assert!(output.chunks_exact(4).all(|px| px[3].abs_diff(0x40) <= 1));
+        assert_eq!(scaler.input_size(), (32, 32));
+        assert_eq!(scaler.output_size(), (16, 16));
+    }
+
+    #[test]
+    fn test_input_resize_keeps_output_size() {
+        // 1080p60 preset, source drops from 1920x1080 to 1280x720 mid-capture
+        let mut scaler = FrameScaler::bgra(1920, 1080, 1920, 1080).expect("Should create scaler");
+        let fullscreen: Vec<u8> = [0x10, 0x20, 0x30, 0xff].repeat(1920 * 1080);
+        assert_eq!(scaler.scale_packed(&fullscreen).unwrap().len(), 1920 * 1080 * 4);
+
+        scaler.set_input_size(1280, 720).expect("Should follow the new size");
+        assert_eq!(scaler.input_size(), (1280, 720));
+        assert_eq!(scaler.output_size(), (1920, 1080));
+
+        let windowed: Vec<u8> = [0x10, 0x20, 0x30, 0xff].repeat(1280 * 720);
+        let output = scaler.scale_packed(&windowed).expect("Should scale");
+        assert_eq!(output.len(), 1920 * 1080 * 4);
+        assert!(output.chunks_exact(4).all(|px| px[0].abs_diff(0x10) <= 1));
+    }
+
     #[test]
     fn test_crop() {
         // Ultrawide (21:9) to 16:9
@@ -140,4 +616,77 @@ mod tests {
         assert!(w < 3440);
         assert_eq!(h, 1440);
     }
+
+    #[test]
+    fn test_fit_output_matches_preset() {
+        // Ultrawide into 1080p60 and a portrait window into 720p
+        for (src_w, src_h, dst_w, dst_h) in [(3440, 1440, 1920, 1080), (720, 1280, 1280, 720)] {
+            let input: Vec<u8> = [0x80, 0x80, 0x80, 0xff].repeat((src_w * src_h) as usize);
+            for fit in [FitMode::Stretch, FitMode::Letterbox, FitMode::Crop] {
+                let mut scaler = FrameScaler::bgra(src_w, src_h, dst_w, dst_h)
+                    .and_then(|s| s.with_fit(fit, BarColor::BLACK))
+                    .expect("Should create scaler");
+                let output = scaler.scale_packed(&input).expect("Should scale");
+                assert_eq!(output.len(), (dst_w * dst_h * 4) as usize, "{} fit", fit);
+                assert_eq!(scaler.output_size(), (dst_w, dst_h));
+            }
+        }
+    }
+
+    #[test]
+    fn test_letterbox_bars() {
+        // 21:9 into 16:9: bars above and below, picture in the middle
+        let input: Vec<u8> = [0xc0, 0xc0, 0xc0, 0xff].repeat(3440 * 1440);
+        let mut scaler = FrameScaler::bgra(3440, 1440, 1920, 1080)
+            .and_then(|s| s.with_fit(FitMode::Letterbox, BarColor::BLACK))
+            .expect("Should create scaler");
+        let output = scaler.scale_packed(&input).expect("Should scale");
+
+        let row = |y: usize| &output[y * 1920 * 4..(y + 1) * 1920 * 4];
+        let black = |y: usize| row(y).chunks_exact(4).all(|px| px == [0, 0, 0, 0xff]);
+        assert!(black(0) && black(100) && black(1079));
+        assert!(row(540).chunks_exact(4).all(|px| px[0].abs_diff(0xc0) <= 1));
+
+        // The picture keeps the source aspect and is centered
+        let picture: Vec<usize> = (0..1080).filter(|&y| !black(y)).collect();
+        let (top, bottom) = (picture[0], 1079 - picture[picture.len() - 1]);
+        assert!(top.abs_diff(bottom) <= 1);
+        assert!(picture.len().abs_diff(1920 * 1440 / 3440) <= 2);
+
+        // Pillarbox with a custom color for a 4:3 source
+        let color: BarColor = "#102030".parse().unwrap();
+        let input: Vec<u8> = [0xc0, 0xc0, 0xc0, 0xff].repeat(640 * 480);
+        let mut scaler = FrameScaler::bgra(640, 480, 1280, 720)
+            .and_then(|s| s.with_fit(FitMode::Letterbox, color))
+            .expect("Should create scaler");
+        let output = scaler.scale_packed(&input).expect("Should scale");
+        assert_eq!(&output[..4], &[0x30, 0x20, 0x10, 0xff]);
+        let center = (360 * 1280 + 640) * 4;
+        assert!(output[center].abs_diff(0xc0) <= 1);
+    }
+
+    #[test]
+    fn test_fit_geometry() {
+        // Matching aspect ratios are a plain scale in every mode
+        for fit in [FitMode::Stretch, FitMode::Letterbox, FitMode::Crop] {
+            let geometry = fit_geometry(fit, 3840, 2160, 1920, 1080);
+            assert_eq!(geometry.src, Rect::full(3840, 2160));
+            assert_eq!(geometry.dst, Rect::full(1920, 1080));
+        }
+
+        // Crop keeps the output full and takes the center of the source
+        let geometry = fit_geometry(FitMode::Crop, 3440, 1440, 1920, 1080);
+        assert_eq!(geometry.dst, Rect::full(1920, 1080));
+        assert_eq!(geometry.src.height, 1440);
+        assert_eq!(geometry.src.x, (3440 - geometry.src.width) / 2);
+
+        // A near miss is stretched instead of leaving 1px bars
+        let geometry = fit_geometry(FitMode::Letterbox, 1366, 768, 1920, 1080);
+        assert_eq!(geometry.dst, Rect::full(1920, 1080));
+
+        assert_eq!("pillarbox".parse::<FitMode>(), Ok(FitMode::Letterbox));
+        assert_eq!(FitMode::default(), FitMode::Letterbox);
+        assert_eq!("000000".parse::<BarColor>(), Ok(BarColor::BLACK));
+        assert!("#12345".parse::<BarColor>().is_err());
+    }
 }
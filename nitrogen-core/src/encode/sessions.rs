@@ -0,0 +1,201 @@
+//! NVENC session accounting
+//!
+//! Consumer GeForce drivers cap how many encode sessions may be open at once
+//! across the whole system. Opening one more fails deep inside FFmpeg with an
+//! "out of memory" error, so every encoder takes a slot here first and a full
+//! pool returns [`NitrogenError::SessionLimit`] instead. The slot is released
+//! when the encoder is dropped.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use tracing::debug;
+
+use super::{get_gpu_info, mock_encoder_requested};
+use crate::error::{NitrogenError, Result};
+
+/// Stored limit meaning "no limit"
+const UNLIMITED: usize = 0;
+
+/// Counts open encoder sessions against an optional limit
+#[derive(Debug)]
+pub struct SessionPool {
+    active: AtomicUsize,
+    limit: AtomicUsize,
+}
+
+impl SessionPool {
+    /// Create a pool (None = unlimited)
+    pub fn new(limit: Option<usize>) -> Arc<Self> {
+        Arc::new(Self {
+            active: AtomicUsize::new(0),
+            limit: AtomicUsize::new(limit.unwrap_or(UNLIMITED)),
+        })
+    }
+
+    /// Maximum number of concurrent sessions (None = unlimited)
+    pub fn limit(&self) -> Option<usize> {
+        match self.limit.load(Ordering::SeqCst) {
+            UNLIMITED => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// Change the limit; sessions already open are kept
+    pub fn set_limit(&self, limit: Option<usize>) {
+        self.limit.store(limit.unwrap_or(UNLIMITED), Ordering::SeqCst);
+    }
+
+    /// Sessions currently open
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Take a slot for a new session
+    pub fn acquire(self: &Arc<Self>) -> Result<SessionSlot> {
+        let mut active = self.active.load(Ordering::SeqCst);
+        loop {
+            if let Some(limit) = self.limit() {
+                if active >= limit {
+                    return Err(NitrogenError::SessionLimit { limit, active });
+                }
+            }
+            match self.active.compare_exchange_weak(
+                active,
+                active + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => break,
+                Err(current) => active = current,
+            }
+        }
+
+        Ok(SessionSlot {
+            pool: Arc::clone(self),
+        })
+    }
+}
+
+/// A held session slot, released on drop
+#[derive(Debug)]
+pub struct SessionSlot {
+    pool: Arc<SessionPool>,
+}
+
+impl Drop for SessionSlot {
+    fn drop(&mut self) {
+        self.pool.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The process-wide pool used by [`super::create_encoder`]
+///
+/// Starts with the limit detected for the GPU (see [`detect_session_limit`]),
+/// or unlimited when the mock encoder stands in for NVENC.
+pub fn sessions() -> &'static Arc<SessionPool> {
+    static POOL: OnceLock<Arc<SessionPool>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        let limit = if mock_encoder_requested() {
+            None
+        } else {
+            detect_session_limit()
+        };
+        debug!(
+            "NVENC session limit: {}",
+            limit.map_or("none".to_string(), |l| l.to_string())
+        );
+        SessionPool::new(limit)
+    })
+}
+
+/// Number of encoder sessions currently open in this process
+pub fn active_sessions() -> usize {
+    sessions().active()
+}
+
+/// Detect the concurrent NVENC session limit of the installed GPU
+///
+/// Uses the GPU name and driver version from nvidia-smi. Returns None when
+/// there is no limit (professional cards) or it can't be determined.
+pub fn detect_session_limit() -> Option<usize> {
+    let gpu = get_gpu_info()?;
+    session_limit_for(&gpu.name, &gpu.driver_version)
+}
+
+/// Session limit for a GPU model and driver version
+///
+/// GeForce and TITAN cards allow 3 sessions before driver 530, 5 up to
+/// 550 and 8 since. Quadro, RTX A/PRO and data-center cards are unlimited.
+fn session_limit_for(name: &str, driver_version: &str) -> Option<usize> {
+    let name = name.to_lowercase();
+    if !(name.contains("geforce") || name.contains("titan")) {
+        return None;
+    }
+
+    let major: u32 = driver_version.split('.').next()?.trim().parse().ok()?;
+    Some(match major {
+        550.. => 8,
+        530.. => 5,
+        _ => 3,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_releases_slots() {
+        let pool = SessionPool::new(Some(2));
+        let first = pool.acquire().unwrap();
+        let _second = pool.acquire().unwrap();
+        assert_eq!(pool.active(), 2);
+
+        match pool.acquire() {
+            Err(NitrogenError::SessionLimit { limit, active }) => {
+                assert_eq!((limit, active), (2, 2));
+            }
+            other => panic!("expected the session limit, got {:?}", other),
+        }
+
+        drop(first);
+        assert_eq!(pool.active(), 1);
+        assert!(pool.acquire().is_ok());
+
+        pool.set_limit(None);
+        let unlimited: Vec<_> = (0..16).map(|_| pool.acquire().unwrap()).collect();
+        assert_eq!(pool.active(), 17);
+        drop(unlimited);
+    }
+
+    #[cfg(feature = "mock-encoder")]
+    #[test]
+    fn test_mock_encoders_past_limit() {
+        use crate::config::CaptureConfig;
+        use crate::encode::create_encoder_in;
+
+        let pool = SessionPool::new(Some(3));
+        let config = CaptureConfig::monitor("test");
+        let encoders: Vec<_> = (0..3)
+            .map(|_| create_encoder_in(&pool, &config).expect("Should fit in the limit"))
+            .collect();
+
+        let err = create_encoder_in(&pool, &config).err().expect("Fourth encoder should fail");
+        assert!(matches!(err, NitrogenError::SessionLimit { limit: 3, .. }));
+        assert!(err.to_string().contains('3'));
+
+        drop(encoders);
+        assert_eq!(pool.active(), 0);
+        assert!(create_encoder_in(&pool, &config).is_ok());
+    }
+
+    #[test]
+    fn test_session_limit_for_driver() {
+        assert_eq!(session_limit_for("NVIDIA GeForce RTX 3080", "525.147.05"), Some(3));
+        assert_eq!(session_limit_for("NVIDIA GeForce RTX 4090", "535.183.01"), Some(5));
+        assert_eq!(session_limit_for("NVIDIA GeForce RTX 5090", "570.86.16"), Some(8));
+        assert_eq!(session_limit_for("NVIDIA RTX A4000", "570.86.16"), None);
+        assert_eq!(session_limit_for("NVIDIA GeForce GTX 1080", "unknown"), None);
+    }
+}
@@ -0,0 +1,157 @@
+//! Frame skipping for mostly static content (slides, documents)
+//!
+//! In [`ContentMode::Static`](crate::config::ContentMode::Static) the
+//! encoder only sees frames that differ from the last encoded one, plus a
+//! periodic refresh. Skipped frames still advance the encoder clock, so the
+//! stream's timestamps stay continuous and players simply hold the last
+//! picture.
+
+use std::time::Duration;
+
+use tracing::trace;
+
+use super::frame_gen::frame_difference;
+use crate::types::{Frame, FrameData};
+
+/// Default luma histogram difference that counts as a change
+pub const DEFAULT_STATIC_THRESHOLD: f32 = 0.02;
+
+/// Default time between forced refreshes (each one is an IDR)
+pub const DEFAULT_STATIC_REFRESH: Duration = Duration::from_secs(5);
+
+/// What to do with a captured frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameDecision {
+    /// Content changed, encode normally
+    Encode,
+    /// Refresh interval elapsed, encode as an IDR
+    Refresh,
+    /// Near-identical to the last encoded frame, skip it
+    Skip,
+}
+
+/// Decides which frames to encode for static content
+#[derive(Debug)]
+pub struct StaticFrameFilter {
+    /// Minimum difference that counts as a change
+    threshold: f32,
+    /// Maximum time between encoded frames
+    refresh_interval: Duration,
+    /// Last encoded frame (system memory copy)
+    reference: Option<Frame>,
+    /// PTS of the last encoded frame in nanoseconds
+    last_emit_pts: u64,
+    /// Frames skipped so far
+    skipped: u64,
+}
+
+impl Default for StaticFrameFilter {
+    fn default() -> Self {
+        Self::new(DEFAULT_STATIC_THRESHOLD, DEFAULT_STATIC_REFRESH)
+    }
+}
+
+impl StaticFrameFilter {
+    /// Create a filter with a change threshold and refresh interval
+    pub fn new(threshold: f32, refresh_interval: Duration) -> Self {
+        Self {
+            threshold,
+            refresh_interval,
+            reference: None,
+            last_emit_pts: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Decide whether `frame` should be encoded
+    ///
+    /// Frames that can't be compared on the CPU (DMA-BUF) are always encoded.
+    pub fn check(&mut self, frame: &Frame) -> FrameDecision {
+        let Some(ref reference) = self.reference else {
+            self.emit(frame);
+            return FrameDecision::Encode;
+        };
+
+        let elapsed = Duration::from_nanos(frame.pts.saturating_sub(self.last_emit_pts));
+        if elapsed >= self.refresh_interval {
+            self.emit(frame);
+            return FrameDecision::Refresh;
+        }
+
+        match frame_difference(reference, frame) {
+            Some(diff) if diff < self.threshold => {
+                self.skipped += 1;
+                trace!("Static content: skipping frame (diff {:.4})", diff);
+                FrameDecision::Skip
+            }
+            _ => {
+                self.emit(frame);
+                FrameDecision::Encode
+            }
+        }
+    }
+
+    /// Number of frames skipped so far
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Remember `frame` as the new reference
+    fn emit(&mut self, frame: &Frame) {
+        self.last_emit_pts = frame.pts;
+        self.reference = match &frame.data {
+            FrameData::Memory(data) => Some(Frame {
+                format: frame.format.clone(),
+                data: FrameData::Memory(data.clone()),
+                pts: frame.pts,
+                hdr_metadata: frame.hdr_metadata,
+            }),
+            FrameData::DmaBuf { .. } => None,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FrameFormat;
+
+    fn frame(color: u8, pts_ms: u64) -> Frame {
+        Frame {
+            format: FrameFormat {
+                width: 64,
+                height: 64,
+                fourcc: 0x34325258, // XRGB8888
+                stride: 64 * 4,
+            },
+            data: FrameData::Memory(vec![color; 64 * 64 * 4]),
+            pts: pts_ms * 1_000_000,
+            hdr_metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_static_filter_skips_identical_frames() {
+        let mut filter = StaticFrameFilter::new(0.02, Duration::from_secs(5));
+
+        assert_eq!(filter.check(&frame(100, 0)), FrameDecision::Encode);
+        assert_eq!(filter.check(&frame(100, 16)), FrameDecision::Skip);
+        assert_eq!(filter.check(&frame(101, 33)), FrameDecision::Skip);
+        // A slide change is encoded and becomes the new reference
+        assert_eq!(filter.check(&frame(220, 50)), FrameDecision::Encode);
+        assert_eq!(filter.check(&frame(220, 66)), FrameDecision::Skip);
+        assert_eq!(filter.skipped(), 3);
+    }
+
+    #[test]
+    fn test_static_filter_periodic_refresh() {
+        let mut filter = StaticFrameFilter::new(0.02, Duration::from_secs(1));
+
+        assert_eq!(filter.check(&frame(100, 0)), FrameDecision::Encode);
+        assert_eq!(filter.check(&frame(100, 500)), FrameDecision::Skip);
+        assert_eq!(filter.check(&frame(100, 1000)), FrameDecision::Refresh);
+        // The refresh restarts the interval
+        assert_eq!(filter.check(&frame(100, 1500)), FrameDecision::Skip);
+        assert_eq!(filter.check(&frame(100, 2000)), FrameDecision::Refresh);
+    }
+}
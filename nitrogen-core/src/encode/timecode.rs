@@ -0,0 +1,397 @@
+//! SMPTE timecode for broadcast-style workflows
+//!
+//! [`Timecode`] is a non-drop-frame `HH:MM:SS:FF` value counted in frames
+//! from a configured start. The overlay burns it into the picture, and for
+//! codecs that define a timecode syntax it is also written into the
+//! bitstream: an HEVC `time_code` SEI message (payload type 136) or an AV1
+//! timecode metadata OBU. H.264 only carries timecode in `pic_timing` SEI,
+//! which needs HRD parameters NVENC doesn't write, so it gets the burn-in
+//! alone.
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::Codec;
+
+/// Seconds in a day, where a timecode wraps
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// HEVC prefix SEI NAL unit type
+const HEVC_PREFIX_SEI: u8 = 39;
+
+/// HEVC `time_code` SEI payload type
+const HEVC_TIME_CODE_PAYLOAD: u8 = 136;
+
+/// AV1 OBU types used while looking for the insertion point
+const AV1_OBU_METADATA: u8 = 5;
+const AV1_OBU_FRAME_HEADER: u8 = 3;
+const AV1_OBU_FRAME: u8 = 6;
+
+/// AV1 `METADATA_TYPE_TIMECODE`
+const AV1_METADATA_TIMECODE: u8 = 5;
+
+/// Non-drop-frame SMPTE timecode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+pub struct Timecode {
+    /// Hours (0-23)
+    pub hours: u8,
+    /// Minutes (0-59)
+    pub minutes: u8,
+    /// Seconds (0-59)
+    pub seconds: u8,
+    /// Frames within the second (below the frame rate)
+    pub frames: u8,
+}
+
+impl Timecode {
+    /// Create a timecode (fields are not range-checked, see [`Timecode::validate`])
+    pub fn new(hours: u8, minutes: u8, seconds: u8, frames: u8) -> Self {
+        Self {
+            hours,
+            minutes,
+            seconds,
+            frames,
+        }
+    }
+
+    /// Timecode `count` frames after midnight, wrapping at 24 hours
+    pub fn from_frames(count: u64, fps: u32) -> Self {
+        let fps = u64::from(fps.max(1));
+        let count = count % (SECONDS_PER_DAY * fps);
+        let total_seconds = count / fps;
+        Self {
+            hours: (total_seconds / 3600) as u8,
+            minutes: (total_seconds / 60 % 60) as u8,
+            seconds: (total_seconds % 60) as u8,
+            frames: (count % fps) as u8,
+        }
+    }
+
+    /// Frames since midnight at `fps`
+    pub fn to_frames(&self, fps: u32) -> u64 {
+        let seconds =
+            u64::from(self.hours) * 3600 + u64::from(self.minutes) * 60 + u64::from(self.seconds);
+        seconds * u64::from(fps) + u64::from(self.frames)
+    }
+
+    /// The timecode `frames` frames later
+    pub fn advanced(&self, frames: u64, fps: u32) -> Self {
+        Self::from_frames(self.to_frames(fps).wrapping_add(frames), fps)
+    }
+
+    /// Check the fields against the clock and the frame rate
+    pub fn validate(&self, fps: u32) -> Result<(), String> {
+        if self.hours > 23 || self.minutes > 59 || self.seconds > 59 {
+            return Err(format!("Timecode {} is not a time of day", self));
+        }
+        if u32::from(self.frames) >= fps {
+            return Err(format!(
+                "Timecode {} has frame {} but the frame rate is {} fps",
+                self, self.frames, fps
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Timecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}:{:02}:{:02}",
+            self.hours, self.minutes, self.seconds, self.frames
+        )
+    }
+}
+
+impl std::str::FromStr for Timecode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(';') {
+            return Err(format!(
+                "Drop-frame timecode is not supported: {} (use HH:MM:SS:FF)",
+                s
+            ));
+        }
+        let fields: Vec<&str> = s.trim().split(':').collect();
+        let [hours, minutes, seconds, frames] = fields.as_slice() else {
+            return Err(format!("Invalid timecode: {} (expected HH:MM:SS:FF)", s));
+        };
+        let parse = |field: &str| {
+            field
+                .parse::<u8>()
+                .map_err(|_| format!("Invalid timecode: {} (expected HH:MM:SS:FF)", s))
+        };
+        let timecode = Self::new(parse(hours)?, parse(minutes)?, parse(seconds)?, parse(frames)?);
+        if timecode.hours > 23 || timecode.minutes > 59 || timecode.seconds > 59 {
+            return Err(format!("Timecode {} is not a time of day", s));
+        }
+        Ok(timecode)
+    }
+}
+
+/// Whether `codec` has a bitstream timecode syntax we can write
+pub fn timecode_metadata_supported(codec: Codec) -> bool {
+    matches!(codec, Codec::Hevc | Codec::Av1)
+}
+
+/// Insert a timecode into an encoded access unit
+///
+/// Returns the new packet data, or None when the codec has no timecode
+/// syntax or the packet has no picture to attach it to.
+pub fn insert_timecode(codec: Codec, data: &[u8], timecode: Timecode, fps: u32) -> Option<Vec<u8>> {
+    match codec {
+        Codec::Hevc => {
+            let at = first_hevc_vcl_nal(data)?;
+            Some(splice(data, at, &hevc_time_code_sei(timecode, fps)))
+        }
+        Codec::Av1 => {
+            let at = first_av1_frame_obu(data)?;
+            Some(splice(data, at, &av1_timecode_obu(timecode, fps)))
+        }
+        Codec::H264 => None,
+    }
+}
+
+fn splice(data: &[u8], at: usize, insert: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + insert.len());
+    out.extend_from_slice(&data[..at]);
+    out.extend_from_slice(insert);
+    out.extend_from_slice(&data[at..]);
+    out
+}
+
+/// MSB-first bit writer for SEI/OBU payloads
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn put(&mut self, value: u32, count: u8) {
+        for bit in (0..count).rev() {
+            if self.bits == 0 {
+                self.bytes.push(0);
+            }
+            let last = self.bytes.len() - 1;
+            self.bytes[last] |= (((value >> bit) & 1) as u8) << (7 - self.bits);
+            self.bits = (self.bits + 1) % 8;
+        }
+    }
+
+    /// A one bit, then zeros up to the byte boundary
+    fn trailing_bits(&mut self) {
+        self.put(1, 1);
+        while self.bits != 0 {
+            self.put(0, 1);
+        }
+    }
+
+    fn is_aligned(&self) -> bool {
+        self.bits == 0
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Fields shared by the HEVC and AV1 clock timestamp syntax, from counting_type on
+///
+/// Non-drop counting (`counting_type` 0), full timestamp, no time offset.
+fn put_clock_timestamp(writer: &mut BitWriter, timecode: Timecode, fps: u32) {
+    // More frames than the field holds can't be signalled exactly
+    let frames = u32::from(timecode.frames).min(fps.saturating_sub(1)).min(511);
+    writer.put(0, 5); // counting_type
+    writer.put(1, 1); // full_timestamp_flag
+    writer.put(0, 1); // discontinuity_flag
+    writer.put(0, 1); // cnt_dropped_flag
+    writer.put(frames, 9); // n_frames
+    writer.put(u32::from(timecode.seconds), 6);
+    writer.put(u32::from(timecode.minutes), 6);
+    writer.put(u32::from(timecode.hours), 5);
+    writer.put(0, 5); // time_offset_length
+}
+
+/// HEVC prefix SEI NAL unit (with start code) carrying a `time_code` message
+fn hevc_time_code_sei(timecode: Timecode, fps: u32) -> Vec<u8> {
+    let mut payload = BitWriter::default();
+    payload.put(1, 2); // num_clock_ts
+    payload.put(1, 1); // clock_timestamp_flag
+    payload.put(0, 1); // units_field_based_flag
+    put_clock_timestamp(&mut payload, timecode, fps);
+    if !payload.is_aligned() {
+        payload.trailing_bits(); // payload_bit_equal_to_one + zeros
+    }
+    let payload = payload.into_bytes();
+
+    let mut rbsp = vec![HEVC_TIME_CODE_PAYLOAD, payload.len() as u8];
+    rbsp.extend_from_slice(&payload);
+    rbsp.push(0x80); // rbsp_trailing_bits
+
+    let mut nal = vec![0, 0, 0, 1, HEVC_PREFIX_SEI << 1, 1];
+    nal.extend_from_slice(&escape_emulation(&rbsp));
+    nal
+}
+
+/// AV1 metadata OBU (with size field) carrying a timecode
+fn av1_timecode_obu(timecode: Timecode, fps: u32) -> Vec<u8> {
+    let mut body = BitWriter::default();
+    put_clock_timestamp(&mut body, timecode, fps);
+    body.trailing_bits();
+
+    let mut payload = vec![AV1_METADATA_TIMECODE];
+    payload.extend_from_slice(&body.into_bytes());
+
+    // obu_type = METADATA, obu_has_size_field = 1
+    let mut obu = vec![(AV1_OBU_METADATA << 3) | 0b010];
+    write_leb128(&mut obu, payload.len() as u64);
+    obu.extend_from_slice(&payload);
+    obu
+}
+
+/// Insert emulation prevention bytes (00 00 0x -> 00 00 03 0x for x <= 3)
+fn escape_emulation(rbsp: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(rbsp.len() + 4);
+    let mut zeros = 0;
+    for &byte in rbsp {
+        if zeros >= 2 && byte <= 3 {
+            out.push(3);
+            zeros = 0;
+        }
+        out.push(byte);
+        zeros = if byte == 0 { zeros + 1 } else { 0 };
+    }
+    out
+}
+
+/// Offset of the start code before the first VCL NAL unit of an Annex B access unit
+fn first_hevc_vcl_nal(data: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i + 3 < data.len() {
+        if data[i] == 0 && data[i + 1] == 0 && data[i + 2] == 1 {
+            let nal_type = (data[i + 3] >> 1) & 0x3f;
+            if nal_type < 32 {
+                // Include the leading zero of a four-byte start code
+                return Some(if i > 0 && data[i - 1] == 0 { i - 1 } else { i });
+            }
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Offset of the first frame or frame header OBU of a temporal unit
+fn first_av1_frame_obu(data: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i < data.len() {
+        let header = data[i];
+        let obu_type = (header >> 3) & 0x0f;
+        if obu_type == AV1_OBU_FRAME || obu_type == AV1_OBU_FRAME_HEADER {
+            return Some(i);
+        }
+
+        let has_extension = header & 0b100 != 0;
+        let has_size = header & 0b010 != 0;
+        if !has_size {
+            // Without a size the OBU runs to the end; nothing follows it
+            return None;
+        }
+        let mut pos = i + 1 + usize::from(has_extension);
+        let size = read_leb128(data, &mut pos)?;
+        i = pos.checked_add(usize::try_from(size).ok()?)?;
+    }
+    None
+}
+
+fn write_leb128(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_leb128(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in 0..8 {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << (shift * 7);
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timecode_frames_roundtrip() {
+        let start: Timecode = "01:00:00:00".parse().unwrap();
+        assert_eq!(start.to_frames(60), 3600 * 60);
+        assert_eq!(start.advanced(59, 60).to_string(), "01:00:00:59");
+        assert_eq!(start.advanced(60, 60).to_string(), "01:00:01:00");
+        assert_eq!(
+            Timecode::new(23, 59, 59, 29).advanced(1, 30),
+            Timecode::default()
+        );
+
+        assert!(Timecode::new(0, 0, 0, 30).validate(30).is_err());
+        assert!(Timecode::new(0, 0, 0, 29).validate(30).is_ok());
+        assert!("00:00:00;00".parse::<Timecode>().is_err());
+        assert!("00:61:00:00".parse::<Timecode>().is_err());
+        assert!("00:00:00".parse::<Timecode>().is_err());
+    }
+
+    #[test]
+    fn test_hevc_time_code_sei_bytes() {
+        let sei = hevc_time_code_sei(Timecode::new(1, 2, 3, 4), 30);
+        // num_clock_ts=1, clock_timestamp_flag=1, units_field_based=0,
+        // counting_type=0, full=1, discont=0, dropped=0, n_frames=4,
+        // seconds=3, minutes=2, hours=1, time_offset_length=0, then 1 + 0000
+        assert_eq!(
+            sei,
+            vec![0, 0, 0, 1, 0x4e, 0x01, 0x88, 0x06, 0x60, 0x40, 0x20, 0x61, 0x04, 0x10, 0x80]
+        );
+        assert_eq!(escape_emulation(&[0, 0, 1, 0, 0, 0]), vec![0, 0, 3, 1, 0, 0, 3, 0]);
+    }
+
+    #[test]
+    fn test_av1_timecode_inserted_before_frame() {
+        // Temporal delimiter, sequence header (2-byte payload), frame (3-byte payload)
+        let temporal_unit = [0x12, 0x00, 0x0a, 0x02, 0xaa, 0xbb, 0x32, 0x03, 1, 2, 3];
+        let out = insert_timecode(Codec::Av1, &temporal_unit, Timecode::new(1, 2, 3, 4), 30)
+            .expect("AV1 supports timecode");
+
+        let obu = av1_timecode_obu(Timecode::new(1, 2, 3, 4), 30);
+        assert_eq!(obu, vec![0x2a, 0x06, 0x05, 0x04, 0x02, 0x06, 0x10, 0x41]);
+        assert_eq!(&out[..6], &temporal_unit[..6]);
+        assert_eq!(&out[6..6 + obu.len()], obu.as_slice());
+        assert_eq!(&out[6 + obu.len()..], &temporal_unit[6..]);
+    }
+
+    #[test]
+    fn test_hevc_sei_before_first_slice() {
+        // VPS, then an IDR slice behind a four-byte start code
+        let access_unit = [0, 0, 0, 1, 0x40, 0x01, 0xff, 0, 0, 0, 1, 0x26, 0x01, 0xaf];
+        let out = insert_timecode(Codec::Hevc, &access_unit, Timecode::default(), 60).unwrap();
+        let sei = hevc_time_code_sei(Timecode::default(), 60);
+        assert_eq!(&out[..7], &access_unit[..7]);
+        assert_eq!(&out[7..7 + sei.len()], sei.as_slice());
+        assert_eq!(&out[7 + sei.len()..], &access_unit[7..]);
+
+        assert!(insert_timecode(Codec::H264, &access_unit, Timecode::default(), 60).is_none());
+        assert!(!timecode_metadata_supported(Codec::H264));
+    }
+}
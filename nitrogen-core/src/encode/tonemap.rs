@@ -7,10 +7,43 @@
 //! - Reinhard (simple, preserves colors well)
 //! - ACES (filmic look, used in film production)
 //! - Hable (Uncharted 2 filmic curve)
+//! - BT.2390 (the ITU reference EETF for broadcast)
+//!
+//! By default the curve is scaled for the content's static peak luminance.
+//! In dynamic mode each frame's luminance is measured instead, smoothed over
+//! time, and used as the peak, so dark scenes aren't crushed and bright
+//! ones don't clip.
+
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use crate::types::{HdrMetadata, TransferFunction};
 use serde::{Deserialize, Serialize};
 
+/// Sample every Nth pixel in each dimension when measuring luminance
+const LUMINANCE_SAMPLE_STEP: usize = 4;
+
+/// Fraction of sampled pixels allowed above the measured peak
+///
+/// A few specular highlights shouldn't darken the whole frame.
+const PEAK_PERCENTILE: f32 = 0.99;
+
+/// Measured peak is kept at least this far above the frame average
+const MIN_PEAK_TO_AVERAGE: f32 = 2.0;
+
+/// Per-frame smoothing weight when the scene gets brighter
+///
+/// Adapting up quickly avoids clipping a sudden highlight for long.
+const BRIGHTEN_RATE: f32 = 0.2;
+
+/// Per-frame smoothing weight when the scene gets darker
+const DARKEN_RATE: f32 = 0.05;
+
+/// Peak changes smaller than this fraction are ignored, to avoid flicker
+const PEAK_DEADBAND: f32 = 0.02;
+
+/// PQ signal 1.0 in nits
+const PQ_MAX_NITS: f32 = 10000.0;
+
 /// Tonemapping algorithm selection
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -22,6 +55,8 @@ pub enum TonemapAlgorithm {
     Aces,
     /// Hable/Uncharted 2 filmic curve
     Hable,
+    /// ITU-R BT.2390 EETF - mid-tones untouched, highlights rolled off
+    Bt2390,
 }
 
 impl std::fmt::Display for TonemapAlgorithm {
@@ -30,6 +65,7 @@ impl std::fmt::Display for TonemapAlgorithm {
             TonemapAlgorithm::Reinhard => write!(f, "Reinhard"),
             TonemapAlgorithm::Aces => write!(f, "ACES"),
             TonemapAlgorithm::Hable => write!(f, "Hable"),
+            TonemapAlgorithm::Bt2390 => write!(f, "BT.2390"),
         }
     }
 }
@@ -42,6 +78,7 @@ impl std::str::FromStr for TonemapAlgorithm {
             "reinhard" => Ok(TonemapAlgorithm::Reinhard),
             "aces" => Ok(TonemapAlgorithm::Aces),
             "hable" | "uncharted2" | "filmic" => Ok(TonemapAlgorithm::Hable),
+            "bt2390" | "2390" => Ok(TonemapAlgorithm::Bt2390),
             _ => Err(format!("Unknown tonemap algorithm: {}", s)),
         }
     }
@@ -94,6 +131,8 @@ pub struct TonemapConfig {
     pub peak_luminance: u32,
     /// Target SDR white point (nits), typically 100-203
     pub sdr_white_point: u32,
+    /// Scale for each frame's measured luminance instead of the static peak
+    pub dynamic: bool,
 }
 
 impl Default for TonemapConfig {
@@ -103,10 +142,29 @@ impl Default for TonemapConfig {
             algorithm: TonemapAlgorithm::Reinhard,
             peak_luminance: 1000,
             sdr_white_point: 100,
+            dynamic: false,
         }
     }
 }
 
+/// Luminance measured from a sample of a frame's pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameLuminance {
+    /// Brightest sampled level, ignoring the top percent (nits)
+    pub peak_nits: f32,
+    /// Mean sampled level (nits)
+    pub average_nits: f32,
+}
+
+/// Luminance range for the BT.2390 curve
+#[derive(Debug, Clone, Copy)]
+struct Eetf {
+    /// Nits of linear light 1.0 from the transfer function
+    unit_nits: f32,
+    /// Source peak (nits), mapped to the SDR white point
+    source_peak: f32,
+}
+
 /// HDR tonemapper
 pub struct Tonemapper {
     config: TonemapConfig,
@@ -114,6 +172,8 @@ pub struct Tonemapper {
     pq_to_linear_lut: Vec<f32>,
     /// Precomputed linear -> SDR gamma lookup table (linear -> 8-bit)
     linear_to_sdr_lut: Vec<u8>,
+    /// Smoothed peak of recent frames in dynamic mode (f32 bits, 0 = none yet)
+    dynamic_peak: AtomicU32,
 }
 
 impl Tonemapper {
@@ -140,6 +200,7 @@ impl Tonemapper {
             config,
             pq_to_linear_lut,
             linear_to_sdr_lut,
+            dynamic_peak: AtomicU32::new(0),
         }
     }
 
@@ -154,7 +215,8 @@ impl Tonemapper {
 
     /// Tonemap an HDR frame to SDR
     ///
-    /// The frame data is expected to be in RGBA or BGRA format (4 bytes per pixel).
+    /// The frame data is expected to be in RGBA or BGRA format (4 bytes per pixel);
+    /// use [`Frame::to_bgra8`](crate::types::Frame::to_bgra8) to get it from other formats.
     /// For HDR10, the input should be 10-bit values packed into 16-bit or represented
     /// as normalized 8-bit for this simplified implementation.
     ///
@@ -176,10 +238,6 @@ impl Tonemapper {
 
         let default_metadata = HdrMetadata::default();
         let metadata = metadata.unwrap_or(&default_metadata);
-        let peak_nits = metadata.peak_luminance().max(self.config.peak_luminance);
-
-        // Scaling factor from peak luminance to SDR white point
-        let scale = self.config.sdr_white_point as f32 / peak_nits as f32;
 
         let pixel_count = (width * height) as usize;
         let expected_size = pixel_count * 4;
@@ -193,12 +251,29 @@ impl Tonemapper {
             return;
         }
 
+        if self.config.dynamic && metadata.transfer != TransferFunction::Sdr {
+            let luminance = self.measure_luminance(frame, width, height, metadata);
+            self.update_dynamic_peak(luminance, metadata);
+        }
+
+        // Scaling factor from peak luminance to SDR white point
+        let scale = self.effective_scale(Some(metadata));
+        let source_peak = self.bt2390_source_peak(metadata);
+
         match metadata.transfer {
             TransferFunction::Pq => {
-                self.tonemap_pq(frame, pixel_count, scale);
+                let eetf = Eetf {
+                    unit_nits: PQ_MAX_NITS,
+                    source_peak,
+                };
+                self.tonemap_pq(frame, pixel_count, scale, eetf);
             }
             TransferFunction::Hlg => {
-                self.tonemap_hlg(frame, pixel_count, scale);
+                let eetf = Eetf {
+                    unit_nits: metadata.peak_luminance() as f32,
+                    source_peak,
+                };
+                self.tonemap_hlg(frame, pixel_count, scale, eetf);
             }
             TransferFunction::Sdr => {
                 // Nothing to do for SDR
@@ -206,8 +281,137 @@ impl Tonemapper {
         }
     }
 
+    /// Scale applied to linear light before the tonemap curve
+    ///
+    /// In dynamic mode this follows the smoothed peak of the frames
+    /// tonemapped so far; before the first one, and in static mode, it's
+    /// derived from the metadata or configured peak luminance.
+    pub fn effective_scale(&self, metadata: Option<&HdrMetadata>) -> f32 {
+        let peak_nits = match self.dynamic_peak_nits() {
+            Some(peak) if self.config.dynamic => peak,
+            _ => self.static_peak_nits(metadata) as f32,
+        };
+        self.config.sdr_white_point as f32 / peak_nits
+    }
+
+    /// Smoothed peak luminance in dynamic mode (None before the first frame)
+    pub fn dynamic_peak_nits(&self) -> Option<f32> {
+        let bits = self.dynamic_peak.load(Ordering::Relaxed);
+        (bits != 0).then(|| f32::from_bits(bits))
+    }
+
+    /// Forget the smoothed peak, e.g. after switching sources
+    pub fn reset_dynamic_peak(&self) {
+        self.dynamic_peak.store(0, Ordering::Relaxed);
+    }
+
+    /// Source peak the BT.2390 curve rolls off to the SDR white point
+    ///
+    /// The content's own max_cll or mastering peak when it states one, the
+    /// configured peak otherwise; in dynamic mode, the smoothed measured peak.
+    fn bt2390_source_peak(&self, metadata: &HdrMetadata) -> f32 {
+        match self.dynamic_peak_nits() {
+            Some(peak) if self.config.dynamic => peak,
+            _ if metadata.max_cll.is_some() || metadata.mastering_max_luminance.is_some() => {
+                metadata.peak_luminance() as f32
+            }
+            _ => self.config.peak_luminance as f32,
+        }
+    }
+
+    /// Peak luminance from metadata or the configured override
+    fn static_peak_nits(&self, metadata: Option<&HdrMetadata>) -> u32 {
+        metadata
+            .map(|m| m.peak_luminance())
+            .unwrap_or_default()
+            .max(self.config.peak_luminance)
+    }
+
+    /// Estimate the peak and average luminance of an HDR frame
+    ///
+    /// Samples one pixel in every [`LUMINANCE_SAMPLE_STEP`] squared, like the
+    /// scene change histogram, using the brightest channel of each so
+    /// saturated colors count too.
+    pub fn measure_luminance(
+        &self,
+        frame: &[u8],
+        width: u32,
+        height: u32,
+        metadata: &HdrMetadata,
+    ) -> FrameLuminance {
+        let (width, height) = (width as usize, height as usize);
+        let mut histogram = [0u32; 256];
+        let mut samples = 0u32;
+        for y in (0..height).step_by(LUMINANCE_SAMPLE_STEP) {
+            for x in (0..width).step_by(LUMINANCE_SAMPLE_STEP) {
+                let offset = (y * width + x) * 4;
+                if let Some(px) = frame.get(offset..offset + 3) {
+                    histogram[px[0].max(px[1]).max(px[2]) as usize] += 1;
+                    samples += 1;
+                }
+            }
+        }
+        if samples == 0 {
+            return FrameLuminance {
+                peak_nits: 0.0,
+                average_nits: 0.0,
+            };
+        }
+
+        let to_nits = |level: usize| self.signal_to_nits(level as f32 / 255.0, metadata);
+        let peak_rank = (samples as f32 * PEAK_PERCENTILE) as u32;
+        let mut seen = 0u32;
+        let mut peak_level = 255;
+        let mut sum_nits = 0.0f32;
+        for (level, &count) in histogram.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            if seen <= peak_rank && seen + count > peak_rank {
+                peak_level = level;
+            }
+            seen += count;
+            sum_nits += to_nits(level) * count as f32;
+        }
+
+        FrameLuminance {
+            peak_nits: to_nits(peak_level),
+            average_nits: sum_nits / samples as f32,
+        }
+    }
+
+    /// Blend a frame's measured luminance into the smoothed peak
+    fn update_dynamic_peak(&self, luminance: FrameLuminance, metadata: &HdrMetadata) {
+        // Never brighter than SDR white maps to, never darker than the content can get
+        let floor = self.config.sdr_white_point as f32;
+        let ceiling = (self.static_peak_nits(Some(metadata)) as f32).max(floor);
+        let target = luminance
+            .peak_nits
+            .max(luminance.average_nits * MIN_PEAK_TO_AVERAGE)
+            .clamp(floor, ceiling);
+
+        let peak = match self.dynamic_peak_nits() {
+            None => target,
+            Some(current) if (target - current).abs() <= current * PEAK_DEADBAND => return,
+            Some(current) => {
+                let rate = if target > current { BRIGHTEN_RATE } else { DARKEN_RATE };
+                current + (target - current) * rate
+            }
+        };
+        self.dynamic_peak.store(peak.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Nits of a normalized PQ or HLG signal value
+    fn signal_to_nits(&self, signal: f32, metadata: &HdrMetadata) -> f32 {
+        match metadata.transfer {
+            TransferFunction::Pq => self.pq_lookup(signal) * PQ_MAX_NITS,
+            TransferFunction::Hlg => hlg_eotf(signal) * metadata.peak_luminance() as f32,
+            TransferFunction::Sdr => signal * self.config.sdr_white_point as f32,
+        }
+    }
+
     /// Tonemap PQ (HDR10) content
-    fn tonemap_pq(&self, frame: &mut [u8], pixel_count: usize, scale: f32) {
+    fn tonemap_pq(&self, frame: &mut [u8], pixel_count: usize, scale: f32, eetf: Eetf) {
         for i in 0..pixel_count {
             let offset = i * 4;
 
@@ -223,7 +427,8 @@ impl Tonemapper {
             let b_linear = self.pq_lookup(b);
 
             // Apply tonemapping in linear space
-            let (r_tm, g_tm, b_tm) = self.apply_tonemap(r_linear, g_linear, b_linear, scale);
+            let (r_tm, g_tm, b_tm) =
+                self.apply_tonemap(r_linear, g_linear, b_linear, scale, eetf);
 
             // Convert back to SDR gamma
             frame[offset] = self.linear_to_sdr(r_tm);
@@ -233,7 +438,7 @@ impl Tonemapper {
     }
 
     /// Tonemap HLG content
-    fn tonemap_hlg(&self, frame: &mut [u8], pixel_count: usize, scale: f32) {
+    fn tonemap_hlg(&self, frame: &mut [u8], pixel_count: usize, scale: f32, eetf: Eetf) {
         for i in 0..pixel_count {
             let offset = i * 4;
 
@@ -247,7 +452,8 @@ impl Tonemapper {
             let b_linear = hlg_eotf(b);
 
             // Apply tonemapping
-            let (r_tm, g_tm, b_tm) = self.apply_tonemap(r_linear, g_linear, b_linear, scale);
+            let (r_tm, g_tm, b_tm) =
+                self.apply_tonemap(r_linear, g_linear, b_linear, scale, eetf);
 
             // Convert to SDR gamma
             frame[offset] = self.linear_to_sdr(r_tm);
@@ -269,7 +475,20 @@ impl Tonemapper {
     }
 
     /// Apply the configured tonemapping algorithm
-    fn apply_tonemap(&self, r: f32, g: f32, b: f32, scale: f32) -> (f32, f32, f32) {
+    fn apply_tonemap(&self, r: f32, g: f32, b: f32, scale: f32, eetf: Eetf) -> (f32, f32, f32) {
+        if self.config.algorithm == TonemapAlgorithm::Bt2390 {
+            // Works in nits rather than the scaled range. Mapping the
+            // brightest channel and scaling the others with it keeps hues.
+            let target = self.config.sdr_white_point as f32;
+            let max = r.max(g).max(b) * eetf.unit_nits;
+            if max <= 0.0 {
+                return (0.0, 0.0, 0.0);
+            }
+            let ratio = bt2390_eetf(max, eetf.source_peak, target) / max;
+            let to_sdr = |c: f32| (c * eetf.unit_nits * ratio / target).min(1.0);
+            return (to_sdr(r), to_sdr(g), to_sdr(b));
+        }
+
         // Scale down from HDR luminance range
         let r = r * scale;
         let g = g * scale;
@@ -285,6 +504,7 @@ impl Tonemapper {
             TonemapAlgorithm::Hable => {
                 (hable(r), hable(g), hable(b))
             }
+            TonemapAlgorithm::Bt2390 => unreachable!("handled above"),
         }
     }
 
@@ -296,6 +516,7 @@ impl Tonemapper {
     /// Update the configuration
     pub fn set_config(&mut self, config: TonemapConfig) {
         self.config = config;
+        self.reset_dynamic_peak();
     }
 }
 
@@ -328,6 +549,13 @@ fn pq_eotf(pq: f32) -> f32 {
     (numerator / denominator).powf(1.0 / PQ_M1)
 }
 
+/// PQ inverse EOTF
+/// Converts linear light (normalized to 10000 nits) to a PQ signal
+fn pq_inverse_eotf(linear: f32) -> f32 {
+    let y = linear.clamp(0.0, 1.0).powf(PQ_M1);
+    ((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y)).powf(PQ_M2)
+}
+
 /// HLG EOTF constants
 const HLG_A: f32 = 0.17883277;
 const HLG_B: f32 = 0.28466892; // 1 - 4 * HLG_A
@@ -391,6 +619,36 @@ fn hable(x: f32) -> f32 {
     curr * white_scale
 }
 
+/// ITU-R BT.2390 EETF, mapping `nits` from a `source_peak` display to a
+/// `target_peak` one
+///
+/// Works in PQ space: signals below the knee at 1.5x the target's PQ level
+/// minus 0.5 pass through unchanged, and above it a Hermite spline rolls
+/// them off so the source peak lands exactly on the target peak. Black
+/// levels are taken as zero on both displays.
+fn bt2390_eetf(nits: f32, source_peak: f32, target_peak: f32) -> f32 {
+    if source_peak <= target_peak {
+        return nits.min(target_peak);
+    }
+
+    let source_pq = pq_inverse_eotf(source_peak / PQ_MAX_NITS);
+    let e1 = (pq_inverse_eotf(nits / PQ_MAX_NITS) / source_pq).min(1.0);
+    let max_lum = pq_inverse_eotf(target_peak / PQ_MAX_NITS) / source_pq;
+    let knee = (1.5 * max_lum - 0.5).max(0.0);
+
+    let e2 = if e1 < knee {
+        e1
+    } else {
+        let t = (e1 - knee) / (1.0 - knee);
+        let (t2, t3) = (t * t, t * t * t);
+        (2.0 * t3 - 3.0 * t2 + 1.0) * knee
+            + (t3 - 2.0 * t2 + t) * (1.0 - knee)
+            + (-2.0 * t3 + 3.0 * t2) * max_lum
+    };
+
+    pq_eotf(e2 * source_pq) * PQ_MAX_NITS
+}
+
 /// ACES filmic tonemapping (approximation)
 /// Based on the RRT+ODT fit by Krzysztof Narkowicz
 fn aces_tonemap(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
@@ -436,6 +694,8 @@ mod tests {
         assert_eq!("aces".parse::<TonemapAlgorithm>().unwrap(), TonemapAlgorithm::Aces);
         assert_eq!("hable".parse::<TonemapAlgorithm>().unwrap(), TonemapAlgorithm::Hable);
         assert_eq!("filmic".parse::<TonemapAlgorithm>().unwrap(), TonemapAlgorithm::Hable);
+        assert_eq!("bt2390".parse::<TonemapAlgorithm>().unwrap(), TonemapAlgorithm::Bt2390);
+        assert_eq!("2390".parse::<TonemapAlgorithm>().unwrap(), TonemapAlgorithm::Bt2390);
         assert!("invalid".parse::<TonemapAlgorithm>().is_err());
     }
 
@@ -496,6 +756,77 @@ mod tests {
         assert!(r <= 1.0 && g <= 1.0 && b <= 1.0);
     }
 
+    #[test]
+    fn test_bt2390_knee() {
+        // 1000-nit content on a 100-nit display: the knee sits near 27 nits
+        let eetf = |nits: f32| bt2390_eetf(nits, 1000.0, 100.0);
+        let rel = |a: f32, b: f32| (a - b).abs() / b;
+
+        // Shadows and mid-tones stay at their source level
+        for nits in [0.5, 5.0, 10.0, 20.0] {
+            assert!(rel(eetf(nits), nits) < 0.01, "{} -> {}", nits, eetf(nits));
+        }
+
+        // Just past the knee the curve still tracks the source
+        assert!(rel(eetf(30.0), 30.0) < 0.05, "30 -> {}", eetf(30.0));
+
+        // Highlights roll off smoothly: monotonic, compressed, never past the target
+        let mut last = eetf(27.0);
+        for nits in (30..=1000).step_by(10) {
+            let out = eetf(nits as f32);
+            assert!(out >= last && out < nits as f32 && out <= 100.01, "{} -> {}", nits, out);
+            last = out;
+        }
+        // The shoulder flattens out towards the peak
+        let (at_100, at_200, at_400) = (eetf(100.0), eetf(200.0), eetf(400.0));
+        assert!(at_100 < at_200 && at_200 < at_400);
+        assert!(at_400 - at_200 < at_200 - at_100);
+
+        // The source peak lands on the target peak, and anything brighter clips there
+        assert!(rel(eetf(1000.0), 100.0) < 0.001);
+        assert!(rel(eetf(4000.0), 100.0) < 0.001);
+
+        // Content already within the target passes through
+        assert_eq!(bt2390_eetf(80.0, 100.0, 100.0), 80.0);
+    }
+
+    #[test]
+    fn test_bt2390_follows_max_cll() {
+        let tonemapper = Tonemapper::new(TonemapConfig {
+            algorithm: TonemapAlgorithm::Bt2390,
+            ..Default::default()
+        });
+        let mut bright = HdrMetadata::hdr10();
+        bright.max_cll = Some(4000);
+        let mut dim = HdrMetadata::hdr10();
+        dim.max_cll = Some(400);
+
+        // max_cll beats the configured fallback, up or down
+        assert_eq!(tonemapper.bt2390_source_peak(&bright), 4000.0);
+        assert_eq!(tonemapper.bt2390_source_peak(&dim), 400.0);
+        assert_eq!(tonemapper.bt2390_source_peak(&HdrMetadata::hdr10()), 1000.0);
+
+        // Each peak maps to SDR white, and 400 nits is compressed harder for 4000-nit content
+        let map = |nits: f32, meta: &HdrMetadata| {
+            let eetf = Eetf {
+                unit_nits: PQ_MAX_NITS,
+                source_peak: tonemapper.bt2390_source_peak(meta),
+            };
+            tonemapper.apply_tonemap(nits / PQ_MAX_NITS, 0.0, 0.0, 0.1, eetf).0
+        };
+        assert!((map(4000.0, &bright) - 1.0).abs() < 0.001);
+        assert!((map(400.0, &dim) - 1.0).abs() < 0.001);
+        assert!(map(400.0, &bright) < map(400.0, &dim));
+
+        // The other channels keep their ratio to the brightest one
+        let eetf = Eetf {
+            unit_nits: PQ_MAX_NITS,
+            source_peak: 1000.0,
+        };
+        let (r, g, b) = tonemapper.apply_tonemap(0.05, 0.025, 0.0, 0.1, eetf);
+        assert!((g / r - 0.5).abs() < 0.001 && b == 0.0);
+    }
+
     #[test]
     fn test_should_tonemap() {
         let config = TonemapConfig::default();
@@ -531,6 +862,7 @@ mod tests {
             algorithm: TonemapAlgorithm::Reinhard,
             peak_luminance: 1000,
             sdr_white_point: 100,
+            dynamic: false,
         };
         let tonemapper = Tonemapper::new(config);
 
@@ -568,6 +900,47 @@ mod tests {
         assert_eq!(frame, original);
     }
 
+    #[test]
+    fn test_dynamic_scale_follows_scene() {
+        let dynamic = || {
+            Tonemapper::new(TonemapConfig {
+                mode: TonemapMode::On,
+                dynamic: true,
+                ..Default::default()
+            })
+        };
+        let meta = HdrMetadata::hdr10();
+        let frame = |level: u8| [level, level, level, 255].repeat(64);
+
+        // A dark scene gets a larger scale than a bright one
+        let (dark, bright) = (dynamic(), dynamic());
+        dark.tonemap(&mut frame(80), 8, 8, Some(&meta));
+        bright.tonemap(&mut frame(190), 8, 8, Some(&meta));
+        let (dark_scale, bright_scale) = (dark.effective_scale(None), bright.effective_scale(None));
+        assert!(dark_scale > bright_scale, "{} vs {}", dark_scale, bright_scale);
+
+        // Static mode ignores the content
+        let fixed = Tonemapper::new(TonemapConfig::default());
+        fixed.tonemap(&mut frame(80), 8, 8, Some(&meta));
+        assert_eq!(fixed.effective_scale(Some(&meta)), 0.1);
+
+        // A cut to a bright scene moves the peak only part of the way per frame
+        let dark_peak = dark.dynamic_peak_nits().unwrap();
+        let bright_peak = bright.dynamic_peak_nits().unwrap();
+        dark.tonemap(&mut frame(190), 8, 8, Some(&meta));
+        let stepped = dark.dynamic_peak_nits().unwrap();
+        assert!(stepped > dark_peak && stepped < bright_peak);
+
+        // Steady content converges, then stays put
+        for _ in 0..200 {
+            dark.tonemap(&mut frame(190), 8, 8, Some(&meta));
+        }
+        let settled = dark.dynamic_peak_nits().unwrap();
+        assert!((settled - bright_peak).abs() <= bright_peak * PEAK_DEADBAND);
+        dark.tonemap(&mut frame(190), 8, 8, Some(&meta));
+        assert_eq!(dark.dynamic_peak_nits(), Some(settled));
+    }
+
     #[test]
     fn test_hdr_metadata_peak_luminance() {
         let mut meta = HdrMetadata::hdr10();
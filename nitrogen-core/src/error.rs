@@ -12,6 +12,14 @@ pub enum NitrogenError {
     #[error("Portal error: {0}")]
     Portal(String),
 
+    /// Transient portal/D-Bus failure; repeating the request may fix it
+    #[error("Portal error: {0}")]
+    PortalTransient(String),
+
+    /// The user dismissed the portal's screen selection dialog
+    #[error("Capture cancelled")]
+    CaptureCancelled,
+
     /// PipeWire error
     #[error("PipeWire error: {0}")]
     PipeWire(String),
@@ -24,6 +32,14 @@ pub enum NitrogenError {
     #[error("NVENC error: {0}")]
     Nvenc(String),
 
+    /// Transient NVENC session failure; re-creating the session may fix it
+    #[error("NVENC session error: {0}")]
+    NvencSession(String),
+
+    /// Opening another encoder would exceed the GPU's concurrent session limit
+    #[error("NVENC session limit reached: {active} of {limit} sessions already open")]
+    SessionLimit { limit: usize, active: usize },
+
     /// Configuration error
     #[error("Configuration error: {0}")]
     Config(String),
@@ -45,9 +61,22 @@ pub enum NitrogenError {
     Unsupported(String),
 
     /// WebRTC error
+    #[cfg(feature = "webrtc")]
     #[error("WebRTC error: {0}")]
     WebRTC(String),
 
+    /// The daemon socket exists but nothing is accepting connections
+    #[error("Daemon unreachable: {0}")]
+    DaemonUnreachable(String),
+
+    /// The daemon accepted the connection but didn't answer in time
+    #[error("IPC timed out: {0}")]
+    IpcTimeout(String),
+
+    /// Client and daemon speak different IPC protocol versions
+    #[error("IPC protocol mismatch: client v{client}, daemon v{daemon}")]
+    IpcProtocolMismatch { client: u32, daemon: u32 },
+
     /// I/O error
     #[error("I/O error: {0}")]
     Io(#[from] std::io::Error),
@@ -67,6 +96,11 @@ impl NitrogenError {
         Self::Portal(msg.into())
     }
 
+    /// Create a transient portal error
+    pub fn portal_transient(msg: impl Into<String>) -> Self {
+        Self::PortalTransient(msg.into())
+    }
+
     /// Create a PipeWire error
     pub fn pipewire(msg: impl Into<String>) -> Self {
         Self::PipeWire(msg.into())
@@ -82,12 +116,18 @@ impl NitrogenError {
         Self::Nvenc(msg.into())
     }
 
+    /// Create a recoverable NVENC session error
+    pub fn nvenc_session(msg: impl Into<String>) -> Self {
+        Self::NvencSession(msg.into())
+    }
+
     /// Create a config error
     pub fn config(msg: impl Into<String>) -> Self {
         Self::Config(msg.into())
     }
 
     /// Create a WebRTC error
+    #[cfg(feature = "webrtc")]
     pub fn webrtc(msg: impl Into<String>) -> Self {
         Self::WebRTC(msg.into())
     }
@@ -103,10 +143,11 @@ impl NitrogenError {
     /// Get a user-friendly hint for how to resolve this error
     pub fn user_hint(&self) -> Option<&'static str> {
         match self {
-            Self::Portal(_) => Some(
+            Self::Portal(_) | Self::PortalTransient(_) => Some(
                 "Ensure xdg-desktop-portal is running and your compositor supports screen sharing.\n\
                  Try: systemctl --user restart xdg-desktop-portal"
             ),
+            Self::CaptureCancelled => None,
             Self::PipeWire(_) => Some(
                 "Ensure PipeWire is running: systemctl --user status pipewire\n\
                  Try: systemctl --user restart pipewire"
@@ -115,11 +156,16 @@ impl NitrogenError {
                 "Check that FFmpeg was compiled with NVENC support.\n\
                  Try: ffmpeg -encoders | grep nvenc"
             ),
-            Self::Nvenc(_) => Some(
+            Self::Nvenc(_) | Self::NvencSession(_) => Some(
                 "Ensure you have an NVIDIA GPU with NVENC support (GTX 600+ or Quadro K series+)\n\
                  and the proprietary NVIDIA drivers installed.\n\
                  Try: nvidia-smi"
             ),
+            Self::SessionLimit { .. } => Some(
+                "GeForce drivers cap how many encoders can run at once, system-wide.\n\
+                 Close other encoding apps (OBS, browsers), use fewer simulcast layers or a\n\
+                 separate recording preset, or set encoder.max_sessions if your driver allows more."
+            ),
             Self::Config(_) => Some(
                 "Check your configuration file at ~/.config/nitrogen/config.toml"
             ),
@@ -135,10 +181,23 @@ impl NitrogenError {
                  Use 'nitrogen stop' to stop it first, or 'nitrogen status' to check its state."
             ),
             Self::Unsupported(_) => None,
+            #[cfg(feature = "webrtc")]
             Self::WebRTC(_) => Some(
                 "Check your network configuration and ensure ICE servers are accessible.\n\
                  WebRTC requires proper network connectivity for peer-to-peer streaming."
             ),
+            Self::DaemonUnreachable(_) => Some(
+                "The socket file is stale, the daemon probably exited without cleaning up.\n\
+                 Start a new session with: nitrogen cast"
+            ),
+            Self::IpcTimeout(_) => Some(
+                "The daemon is running but not responding.\n\
+                 Try stopping it with Ctrl+C in its terminal, or: pkill -INT -x nitrogen"
+            ),
+            Self::IpcProtocolMismatch { .. } => Some(
+                "The running daemon was started by a different nitrogen version.\n\
+                 Stop it with Ctrl+C in its terminal, then start it again with this version."
+            ),
             Self::Io(_) => None,
             Self::WithContext { source, .. } => source.user_hint(),
         }
@@ -149,13 +208,37 @@ impl NitrogenError {
         matches!(
             self,
             Self::Portal(_)
+                | Self::PortalTransient(_)
+                | Self::CaptureCancelled
                 | Self::PipeWire(_)
+                | Self::SessionLimit { .. }
                 | Self::Config(_)
                 | Self::SourceNotFound(_)
                 | Self::NoActiveSession
                 | Self::SessionAlreadyRunning
+                | Self::DaemonUnreachable(_)
+                | Self::IpcTimeout(_)
+                | Self::IpcProtocolMismatch { .. }
         )
     }
+
+    /// Whether this is a transient portal failure worth retrying
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::PortalTransient(_) => true,
+            Self::WithContext { source, .. } => source.is_transient(),
+            _ => false,
+        }
+    }
+
+    /// Whether the user cancelled the capture
+    pub fn is_cancelled(&self) -> bool {
+        match self {
+            Self::CaptureCancelled => true,
+            Self::WithContext { source, .. } => source.is_cancelled(),
+            _ => false,
+        }
+    }
 }
 
 /// Extension trait for adding context to Results
@@ -174,13 +257,47 @@ impl<T> ResultExt<T> for Result<T> {
 
 impl From<ashpd::Error> for NitrogenError {
     fn from(err: ashpd::Error) -> Self {
-        Self::Portal(err.to_string())
+        use ashpd::desktop::ResponseError;
+
+        match err {
+            ashpd::Error::Response(ResponseError::Cancelled) => Self::CaptureCancelled,
+            // The portal backend failed without the user; GNOME does this on
+            // the first request after login
+            ashpd::Error::Response(ResponseError::Other) | ashpd::Error::NoResponse => {
+                Self::PortalTransient(err.to_string())
+            }
+            ashpd::Error::Zbus(err) => err.into(),
+            _ => Self::Portal(err.to_string()),
+        }
     }
 }
 
 impl From<zbus::Error> for NitrogenError {
     fn from(err: zbus::Error) -> Self {
-        Self::Portal(format!("D-Bus error: {}", err))
+        let message = format!("D-Bus error: {}", err);
+        if is_transient_dbus(&err) {
+            Self::PortalTransient(message)
+        } else {
+            Self::Portal(message)
+        }
+    }
+}
+
+/// D-Bus failures caused by a portal that is still starting or busy
+fn is_transient_dbus(err: &zbus::Error) -> bool {
+    use zbus::fdo;
+
+    match err {
+        zbus::Error::InputOutput(_) => true,
+        zbus::Error::FDO(err) => matches!(
+            **err,
+            fdo::Error::NoReply(_)
+                | fdo::Error::ServiceUnknown(_)
+                | fdo::Error::NameHasNoOwner(_)
+                | fdo::Error::Timeout(_)
+                | fdo::Error::TimedOut(_)
+        ),
+        _ => false,
     }
 }
 
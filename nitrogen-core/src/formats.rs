@@ -3,8 +3,14 @@
 //! Centralizes DRM fourcc format handling to avoid duplication across modules.
 //! All format constants and conversion functions should be defined here.
 
+use std::borrow::Cow;
+
+use ffmpeg_next::util::color::Range;
 use ghoststream::types::FrameFormat as GsFrameFormat;
 
+use crate::config::ColorRange;
+use crate::types::FrameFormat;
+
 /// DRM format fourcc constants
 ///
 /// These are the standard DRM/KMS fourcc codes for pixel formats.
@@ -32,6 +38,8 @@ pub mod fourcc {
     pub const BGR888: u32 = 0x20524742; // BGR
     /// NV12 - YUV 4:2:0 semi-planar
     pub const NV12: u32 = 0x3231564E; // NV12
+    /// I420/YU12 - YUV 4:2:0 planar (Y, then U, then V)
+    pub const I420: u32 = 0x32315559; // YU12
     /// YUY2/YUYV - YUV 4:2:2 packed
     pub const YUY2: u32 = 0x56595559; // YUYV
     /// P010 - 10-bit YUV 4:2:0 (HDR)
@@ -50,6 +58,7 @@ pub fn bytes_per_pixel(fourcc: u32) -> u32 {
         RGB888 | BGR888 => 3,
         // YUV formats (1.5 bytes per pixel average for NV12, 2 for YUY2)
         NV12 => 2, // Actually 1.5, but we use stride-based calculations
+        I420 => 2, // Same as NV12
         YUY2 => 2,
         P010 => 2, // 10-bit YUV
         // Default to 4 bytes (safe assumption for most desktop formats)
@@ -57,6 +66,124 @@ pub fn bytes_per_pixel(fourcc: u32) -> u32 {
     }
 }
 
+/// How a format lays out its pixels in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelLayout {
+    /// Whole pixels in one plane
+    Packed {
+        /// Bytes per pixel
+        bytes_per_pixel: u32,
+    },
+    /// 8-bit 4:2:0: a Y plane, then chroma at half resolution in each
+    /// direction
+    Yuv420 {
+        /// U and V share one plane, interleaved (NV12), rather than two (I420)
+        interleaved: bool,
+    },
+}
+
+/// Pixel layout of a fourcc format
+///
+/// Like [`bytes_per_pixel`], unknown formats count as packed 4-byte pixels.
+pub fn bytes_per_pixel_or_planar(fourcc: u32) -> PixelLayout {
+    match fourcc {
+        fourcc::NV12 => PixelLayout::Yuv420 { interleaved: true },
+        fourcc::I420 => PixelLayout::Yuv420 { interleaved: false },
+        _ => PixelLayout::Packed {
+            bytes_per_pixel: bytes_per_pixel(fourcc),
+        },
+    }
+}
+
+/// One plane of a frame buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Plane {
+    /// Byte offset of the plane in the buffer
+    pub offset: usize,
+    /// Bytes per row
+    pub stride: usize,
+    /// Width in samples (pixels, or chroma samples)
+    pub width: usize,
+    /// Rows
+    pub height: usize,
+    /// Bytes per sample (an interleaved UV pair counts as one sample)
+    pub sample_bytes: usize,
+    /// Pixels per sample in each direction (2 for 4:2:0 chroma)
+    pub subsampling: usize,
+}
+
+impl Plane {
+    /// Byte offset of the sample at `(x, y)`
+    pub fn at(&self, x: usize, y: usize) -> usize {
+        self.offset + y * self.stride + x * self.sample_bytes
+    }
+
+    /// Bytes the buffer needs to hold this plane
+    pub fn end(&self) -> usize {
+        if self.height == 0 {
+            self.offset
+        } else {
+            self.at(self.width, self.height - 1)
+        }
+    }
+}
+
+/// Planes of a frame
+///
+/// Packed formats have one plane, NV12 two (Y and UV) and I420 three (Y, U
+/// and V). I420 chroma rows are half the Y stride (rounded up). YUY2 and
+/// P010 count as a single packed plane.
+pub fn frame_planes(format: &FrameFormat) -> Vec<Plane> {
+    let width = format.width as usize;
+    let height = format.height as usize;
+    let stride = format.stride as usize;
+    let chroma = |offset: usize, stride: usize, sample_bytes: usize| Plane {
+        offset,
+        stride,
+        width: width.div_ceil(2),
+        height: height.div_ceil(2),
+        sample_bytes,
+        subsampling: 2,
+    };
+    let luma = Plane {
+        offset: 0,
+        stride,
+        width,
+        height,
+        sample_bytes: 1,
+        subsampling: 1,
+    };
+
+    match bytes_per_pixel_or_planar(format.fourcc) {
+        PixelLayout::Packed { bytes_per_pixel } => vec![Plane {
+            sample_bytes: bytes_per_pixel as usize,
+            ..luma
+        }],
+        PixelLayout::Yuv420 { interleaved: true } => vec![luma, chroma(stride * height, stride, 2)],
+        PixelLayout::Yuv420 { interleaved: false } => {
+            let chroma_stride = stride.div_ceil(2);
+            let u = chroma(stride * height, chroma_stride, 1);
+            let v = chroma(u.offset + chroma_stride * u.height, chroma_stride, 1);
+            vec![luma, u, v]
+        }
+    }
+}
+
+/// Check if a fourcc format carries a real alpha channel
+///
+/// X/padding formats (XRGB8888 etc.) leave the fourth byte undefined.
+pub fn has_alpha(fourcc: u32) -> bool {
+    use fourcc::*;
+    matches!(fourcc, ARGB8888 | ABGR8888 | RGBA8888 | BGRA8888)
+}
+
+/// Set every pixel of a BGRA buffer to fully opaque
+pub fn set_opaque_alpha(bgra: &mut [u8]) {
+    for pixel in bgra.chunks_exact_mut(4) {
+        pixel[3] = 0xFF;
+    }
+}
+
 /// Check if a fourcc format is HDR-capable (10-bit or higher)
 pub fn is_hdr_format(fourcc: u32) -> bool {
     matches!(fourcc, fourcc::P010)
@@ -81,6 +208,334 @@ pub fn fourcc_to_gs_format(fourcc: u32) -> GsFrameFormat {
     }
 }
 
+/// YUV to RGB conversion matrix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum YuvMatrix {
+    /// BT.709 (HD and most screen content)
+    #[default]
+    Bt709,
+    /// BT.2020 (HDR / wide gamut)
+    Bt2020,
+}
+
+/// Limited-range YUV to RGB coefficients in 10-bit fixed point
+struct YuvCoefficients {
+    y: i32,
+    rv: i32,
+    gu: i32,
+    gv: i32,
+    bu: i32,
+}
+
+impl YuvMatrix {
+    fn coefficients(self) -> YuvCoefficients {
+        match self {
+            YuvMatrix::Bt709 => YuvCoefficients {
+                y: 1192,
+                rv: 1836,
+                gu: 218,
+                gv: 546,
+                bu: 2163,
+            },
+            YuvMatrix::Bt2020 => YuvCoefficients {
+                y: 1192,
+                rv: 1719,
+                gu: 192,
+                gv: 666,
+                bu: 2193,
+            },
+        }
+    }
+}
+
+/// RGB to limited-range YUV coefficients in 8-bit fixed point
+struct RgbCoefficients {
+    y: [i32; 3],
+    u: [i32; 3],
+    v: [i32; 3],
+}
+
+impl YuvMatrix {
+    fn rgb_coefficients(self) -> RgbCoefficients {
+        match self {
+            YuvMatrix::Bt709 => RgbCoefficients {
+                y: [47, 157, 16],
+                u: [-26, -86, 112],
+                v: [112, -102, -10],
+            },
+            YuvMatrix::Bt2020 => RgbCoefficients {
+                y: [58, 149, 13],
+                u: [-31, -81, 112],
+                v: [112, -103, -9],
+            },
+        }
+    }
+}
+
+/// Row stride of the NV12 buffers [`bgra8_to_nv12`] produces
+///
+/// The width rounded up to even, so a UV row fits an odd-width frame.
+pub fn nv12_stride(width: u32) -> u32 {
+    width.div_ceil(2) * 2
+}
+
+/// Convert tightly packed BGRA8 to limited-range NV12
+///
+/// The result is a full-resolution Y plane followed by the interleaved UV
+/// plane at half resolution, both [`nv12_stride`] bytes per row. Each
+/// chroma sample averages a 2x2 block of pixels. Alpha is dropped.
+///
+/// Returns None when `bgra` is too small for the frame.
+pub fn bgra8_to_nv12(bgra: &[u8], width: u32, height: u32, matrix: YuvMatrix) -> Option<Vec<u8>> {
+    let stride = nv12_stride(width) as usize;
+    let (width, height) = (width as usize, height as usize);
+    if bgra.len() < width * height * 4 {
+        return None;
+    }
+
+    let c = matrix.rgb_coefficients();
+    let dot = |k: [i32; 3], [r, g, b]: [i32; 3]| k[0] * r + k[1] * g + k[2] * b;
+    let rgb = |x: usize, y: usize| {
+        let i = (y * width + x) * 4;
+        [bgra[i + 2] as i32, bgra[i + 1] as i32, bgra[i] as i32]
+    };
+
+    let chroma_rows = height.div_ceil(2);
+    let mut out = vec![0u8; stride * (height + chroma_rows)];
+    for y in 0..height {
+        for x in 0..width {
+            out[y * stride + x] = (16 + ((dot(c.y, rgb(x, y)) + 128) >> 8)).clamp(16, 235) as u8;
+        }
+    }
+
+    let uv_plane = stride * height;
+    for cy in 0..chroma_rows {
+        for cx in 0..width.div_ceil(2) {
+            // Sum of the 2x2 block, repeating the last column/row at odd edges
+            let mut sum = [0; 3];
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let px = rgb((cx * 2 + dx).min(width - 1), (cy * 2 + dy).min(height - 1));
+                for (total, value) in sum.iter_mut().zip(px) {
+                    *total += value;
+                }
+            }
+            let uv = uv_plane + cy * stride + cx * 2;
+            out[uv] = (128 + ((dot(c.u, sum) + 512) >> 10)).clamp(16, 240) as u8;
+            out[uv + 1] = (128 + ((dot(c.v, sum) + 512) >> 10)).clamp(16, 240) as u8;
+        }
+    }
+    Some(out)
+}
+
+/// Byte positions of R, G and B in a packed RGB pixel, by memory order
+#[derive(Clone, Copy)]
+enum PackedOrder {
+    /// B, G, R, A/X
+    Bgra,
+    /// R, G, B, A/X
+    Rgba,
+    /// R, G, B
+    Rgb,
+    /// B, G, R
+    Bgr,
+}
+
+/// Convert a CPU frame buffer to tightly packed BGRA8 (`width * 4` bytes per row)
+///
+/// Handles packed 32-bit RGB (XRGB, ARGB, BGRA, RGBA and their padded
+/// variants), 24-bit RGB, NV12, YUY2 and P010. YUV input is treated as
+/// limited range. Data that is already BGRA-ordered without row padding is
+/// returned borrowed; everything else is converted into a new buffer.
+///
+/// The fourth byte of padded formats (XRGB etc.) is passed through as-is,
+/// see [`has_alpha`]. YUV and 24-bit sources come out fully opaque. P010 is
+/// reduced to 8 bits without tonemapping.
+///
+/// Returns None for unsupported formats or buffers too small for the format.
+pub fn to_bgra8<'a>(data: &'a [u8], format: &FrameFormat, matrix: YuvMatrix) -> Option<Cow<'a, [u8]>> {
+    use fourcc::*;
+
+    let width = format.width as usize;
+    let height = format.height as usize;
+    let stride = format.stride as usize;
+
+    match format.fourcc {
+        XRGB8888 | ARGB8888 | ABGR8888 | BGRX8888 | BGRA8888 => {
+            if stride == width * 4 && data.len() >= width * height * 4 {
+                Some(Cow::Borrowed(&data[..width * height * 4]))
+            } else {
+                convert_packed(data, width, height, stride, PackedOrder::Bgra).map(Cow::Owned)
+            }
+        }
+        RGBA8888 | RGBX8888 | XBGR8888 => {
+            convert_packed(data, width, height, stride, PackedOrder::Rgba).map(Cow::Owned)
+        }
+        RGB888 => convert_packed(data, width, height, stride, PackedOrder::Rgb).map(Cow::Owned),
+        BGR888 => convert_packed(data, width, height, stride, PackedOrder::Bgr).map(Cow::Owned),
+        NV12 => convert_semi_planar(data, width, height, stride, 1, matrix).map(Cow::Owned),
+        I420 => convert_planar(data, format, matrix).map(Cow::Owned),
+        P010 => convert_semi_planar(data, width, height, stride, 2, matrix).map(Cow::Owned),
+        YUY2 => convert_yuy2(data, width, height, stride, matrix).map(Cow::Owned),
+        _ => None,
+    }
+}
+
+/// Repack packed RGB rows into BGRA8
+fn convert_packed(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    order: PackedOrder,
+) -> Option<Vec<u8>> {
+    let bpp = match order {
+        PackedOrder::Bgra | PackedOrder::Rgba => 4,
+        PackedOrder::Rgb | PackedOrder::Bgr => 3,
+    };
+    if height > 0 && (stride < width * bpp || data.len() < stride * (height - 1) + width * bpp) {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        let row = &data[y * stride..y * stride + width * bpp];
+        for px in row.chunks_exact(bpp) {
+            let bgra = match order {
+                PackedOrder::Bgra => [px[0], px[1], px[2], px[3]],
+                PackedOrder::Rgba => [px[2], px[1], px[0], px[3]],
+                PackedOrder::Rgb => [px[2], px[1], px[0], 0xFF],
+                PackedOrder::Bgr => [px[0], px[1], px[2], 0xFF],
+            };
+            out.extend_from_slice(&bgra);
+        }
+    }
+    Some(out)
+}
+
+/// Convert NV12 (`sample_bytes` = 1) or P010 (`sample_bytes` = 2) to BGRA8
+///
+/// Both have a full-resolution Y plane followed by an interleaved UV plane
+/// at half resolution, using the same stride. P010 samples are 16-bit
+/// little-endian with the value in the high bits.
+fn convert_semi_planar(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    sample_bytes: usize,
+    matrix: YuvMatrix,
+) -> Option<Vec<u8>> {
+    let chroma_rows = height.div_ceil(2);
+    let chroma_width = width.div_ceil(2) * 2;
+    if stride < chroma_width * sample_bytes || data.len() < stride * (height + chroma_rows) {
+        return None;
+    }
+
+    // 8-bit value of the sample at a byte offset
+    let sample = |offset: usize| -> u8 {
+        if sample_bytes == 1 {
+            data[offset]
+        } else {
+            data[offset + 1] // High byte of the little-endian 16-bit sample
+        }
+    };
+
+    let coefficients = matrix.coefficients();
+    let uv_plane = stride * height;
+    let mut out = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        let y_row = y * stride;
+        let uv_row = uv_plane + (y / 2) * stride;
+        for x in 0..width {
+            let uv = uv_row + (x / 2) * 2 * sample_bytes;
+            out.extend_from_slice(&yuv_to_bgra(
+                sample(y_row + x * sample_bytes),
+                sample(uv),
+                sample(uv + sample_bytes),
+                &coefficients,
+            ));
+        }
+    }
+    Some(out)
+}
+
+/// Convert planar I420 to BGRA8
+fn convert_planar(data: &[u8], format: &FrameFormat, matrix: YuvMatrix) -> Option<Vec<u8>> {
+    let planes = frame_planes(format);
+    let [y_plane, u_plane, v_plane] = planes[..] else {
+        return None;
+    };
+    if y_plane.stride < y_plane.width || data.len() < v_plane.end() {
+        return None;
+    }
+
+    let coefficients = matrix.coefficients();
+    let mut out = Vec::with_capacity(y_plane.width * y_plane.height * 4);
+    for y in 0..y_plane.height {
+        for x in 0..y_plane.width {
+            out.extend_from_slice(&yuv_to_bgra(
+                data[y_plane.at(x, y)],
+                data[u_plane.at(x / 2, y / 2)],
+                data[v_plane.at(x / 2, y / 2)],
+                &coefficients,
+            ));
+        }
+    }
+    Some(out)
+}
+
+/// Convert packed YUYV 4:2:2 to BGRA8
+fn convert_yuy2(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    stride: usize,
+    matrix: YuvMatrix,
+) -> Option<Vec<u8>> {
+    let row_bytes = width.div_ceil(2) * 4;
+    if height > 0 && (stride < row_bytes || data.len() < stride * (height - 1) + row_bytes) {
+        return None;
+    }
+
+    let coefficients = matrix.coefficients();
+    let mut out = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        let row = &data[y * stride..y * stride + row_bytes];
+        for x in 0..width {
+            let pair = &row[(x / 2) * 4..(x / 2) * 4 + 4];
+            let luma = if x % 2 == 0 { pair[0] } else { pair[2] };
+            out.extend_from_slice(&yuv_to_bgra(luma, pair[1], pair[3], &coefficients));
+        }
+    }
+    Some(out)
+}
+
+/// Convert one limited-range YUV sample to an opaque BGRA pixel
+fn yuv_to_bgra(y: u8, u: u8, v: u8, c: &YuvCoefficients) -> [u8; 4] {
+    let y = (y as i32 - 16) * c.y;
+    let u = u as i32 - 128;
+    let v = v as i32 - 128;
+
+    let r = (y + c.rv * v + 512) >> 10;
+    let g = (y - c.gu * u - c.gv * v + 512) >> 10;
+    let b = (y + c.bu * u + 512) >> 10;
+
+    [
+        b.clamp(0, 255) as u8,
+        g.clamp(0, 255) as u8,
+        r.clamp(0, 255) as u8,
+        0xFF,
+    ]
+}
+
+/// Convert a configured color range to the FFmpeg value
+pub fn ffmpeg_color_range(range: ColorRange) -> Range {
+    match range {
+        ColorRange::Limited => Range::MPEG,
+        ColorRange::Full => Range::JPEG,
+    }
+}
+
 /// Format information for debugging
 pub fn format_name(fourcc: u32) -> &'static str {
     use fourcc::*;
@@ -96,6 +551,7 @@ pub fn format_name(fourcc: u32) -> &'static str {
         RGB888 => "RGB888",
         BGR888 => "BGR888",
         NV12 => "NV12",
+        I420 => "I420",
         YUY2 => "YUY2",
         P010 => "P010",
         _ => "Unknown",
@@ -113,6 +569,35 @@ mod tests {
         assert_eq!(bytes_per_pixel(fourcc::NV12), 2);
     }
 
+    #[test]
+    fn test_frame_planes() {
+        let format = |fourcc| FrameFormat {
+            width: 6,
+            height: 3,
+            fourcc,
+            stride: 8,
+        };
+        assert_eq!(
+            bytes_per_pixel_or_planar(fourcc::XRGB8888),
+            PixelLayout::Packed { bytes_per_pixel: 4 }
+        );
+        assert_eq!(frame_planes(&format(fourcc::XRGB8888)).len(), 1);
+
+        // NV12: UV pairs after the Y plane, on the same stride
+        let nv12 = frame_planes(&format(fourcc::NV12));
+        assert_eq!(nv12.len(), 2);
+        assert_eq!((nv12[1].offset, nv12[1].stride), (24, 8));
+        assert_eq!((nv12[1].width, nv12[1].height, nv12[1].sample_bytes), (3, 2, 2));
+        assert_eq!(nv12[1].end(), 24 + 8 + 6);
+
+        // I420: U then V, each at half the Y stride
+        let i420 = frame_planes(&format(fourcc::I420));
+        assert_eq!(i420.len(), 3);
+        assert_eq!((i420[1].offset, i420[1].stride), (24, 4));
+        assert_eq!((i420[2].offset, i420[2].stride), (32, 4));
+        assert_eq!(i420[2].at(1, 1), 32 + 4 + 1);
+    }
+
     #[test]
     fn test_fourcc_to_gs_format() {
         assert_eq!(fourcc_to_gs_format(fourcc::XRGB8888), GsFrameFormat::Bgra);
@@ -125,6 +610,206 @@ mod tests {
         assert!(is_hdr_format(fourcc::P010));
     }
 
+    #[test]
+    fn test_has_alpha() {
+        assert!(has_alpha(fourcc::ARGB8888));
+        assert!(!has_alpha(fourcc::XRGB8888));
+
+        let mut pixels = vec![1, 2, 3, 0, 4, 5, 6, 7];
+        set_opaque_alpha(&mut pixels);
+        assert_eq!(pixels, vec![1, 2, 3, 0xFF, 4, 5, 6, 0xFF]);
+    }
+
+    #[test]
+    fn test_ffmpeg_color_range() {
+        assert_eq!(ffmpeg_color_range(ColorRange::Limited), Range::MPEG);
+        assert_eq!(ffmpeg_color_range(ColorRange::Full), Range::JPEG);
+    }
+
+    fn packed_format(fourcc: u32, width: u32, height: u32, stride: u32) -> FrameFormat {
+        FrameFormat {
+            width,
+            height,
+            fourcc,
+            stride,
+        }
+    }
+
+    /// Assert a BGRA pixel matches within a rounding tolerance
+    fn assert_pixel(actual: &[u8], expected: [u8; 4]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (*a as i32 - e as i32).abs() <= 2,
+                "expected {:?}, got {:?}",
+                expected,
+                &actual[..4]
+            );
+        }
+    }
+
+    #[test]
+    fn test_to_bgra8_bgra_ordered_is_borrowed() {
+        // B, G, R, X in memory for every BGRA-ordered fourcc
+        let data = [10u8, 20, 30, 40];
+        for fourcc in [
+            fourcc::XRGB8888,
+            fourcc::ARGB8888,
+            fourcc::ABGR8888,
+            fourcc::BGRX8888,
+            fourcc::BGRA8888,
+        ] {
+            let out = to_bgra8(&data, &packed_format(fourcc, 1, 1, 4), YuvMatrix::Bt709)
+                .expect("supported");
+            assert!(matches!(out, Cow::Borrowed(_)), "{}", format_name(fourcc));
+            assert_eq!(&*out, &[10, 20, 30, 40]);
+        }
+    }
+
+    #[test]
+    fn test_to_bgra8_strips_row_padding() {
+        // 1x2 XRGB with 8-byte rows
+        let data = [1u8, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8, 0, 0, 0, 0];
+        let out = to_bgra8(&data, &packed_format(fourcc::XRGB8888, 1, 2, 8), YuvMatrix::Bt709)
+            .expect("supported");
+        assert!(matches!(out, Cow::Owned(_)));
+        assert_eq!(&*out, &[1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_to_bgra8_rgb_ordered() {
+        let rgba = [30u8, 20, 10, 40];
+        for fourcc in [fourcc::RGBA8888, fourcc::RGBX8888, fourcc::XBGR8888] {
+            let out = to_bgra8(&rgba, &packed_format(fourcc, 1, 1, 4), YuvMatrix::Bt709)
+                .expect("supported");
+            assert_eq!(&*out, &[10, 20, 30, 40], "{}", format_name(fourcc));
+        }
+
+        let rgb = [30u8, 20, 10];
+        let out = to_bgra8(&rgb, &packed_format(fourcc::RGB888, 1, 1, 3), YuvMatrix::Bt709)
+            .expect("supported");
+        assert_eq!(&*out, &[10, 20, 30, 0xFF]);
+
+        let bgr = [10u8, 20, 30];
+        let out = to_bgra8(&bgr, &packed_format(fourcc::BGR888, 1, 1, 3), YuvMatrix::Bt709)
+            .expect("supported");
+        assert_eq!(&*out, &[10, 20, 30, 0xFF]);
+    }
+
+    #[test]
+    fn test_to_bgra8_nv12() {
+        // 2x2: Y plane, then one UV pair. BT.709 limited-range pure red.
+        let data = [63u8, 63, 63, 63, 102, 240];
+        let out = to_bgra8(&data, &packed_format(fourcc::NV12, 2, 2, 2), YuvMatrix::Bt709)
+            .expect("supported");
+        assert_eq!(out.len(), 16);
+        for pixel in out.chunks_exact(4) {
+            assert_pixel(pixel, [0, 0, 255, 0xFF]);
+        }
+
+        // Limited-range white and black don't depend on the matrix
+        let data = [235u8, 235, 16, 16, 128, 128];
+        let out = to_bgra8(&data, &packed_format(fourcc::NV12, 2, 2, 2), YuvMatrix::Bt2020)
+            .expect("supported");
+        assert_pixel(&out[0..4], [255, 255, 255, 0xFF]);
+        assert_pixel(&out[8..12], [0, 0, 0, 0xFF]);
+    }
+
+    #[test]
+    fn test_to_bgra8_i420() {
+        // 2x2: Y plane, then one U and one V sample. The NV12 red above.
+        let data = [63u8, 63, 63, 63, 102, 240];
+        let out = to_bgra8(&data, &packed_format(fourcc::I420, 2, 2, 2), YuvMatrix::Bt709)
+            .expect("supported");
+        for pixel in out.chunks_exact(4) {
+            assert_pixel(pixel, [0, 0, 255, 0xFF]);
+        }
+        assert!(to_bgra8(&data[..5], &packed_format(fourcc::I420, 2, 2, 2), YuvMatrix::Bt709)
+            .is_none());
+    }
+
+    #[test]
+    fn test_bgra8_to_nv12() {
+        // Left column red, right column white: 2x2 with one chroma sample
+        let bgra = [0, 0, 255, 255, 255, 255, 255, 255, 0, 0, 255, 255, 255, 255, 255, 255];
+        let nv12 = bgra8_to_nv12(&bgra, 2, 2, YuvMatrix::Bt709).expect("big enough");
+        assert_eq!(nv12.len(), 2 * 2 + 2);
+        assert_eq!(&nv12[..4], &[63, 235, 63, 235]);
+        // The chroma averages red with neutral white
+        assert_eq!(&nv12[4..], &[115, 184]);
+
+        // Pure red survives the round trip
+        let red: Vec<u8> = [0u8, 0, 255, 255].repeat(4);
+        let nv12 = bgra8_to_nv12(&red, 2, 2, YuvMatrix::Bt709).unwrap();
+        assert_eq!(nv12, [63, 63, 63, 63, 102, 240]);
+        let format = packed_format(fourcc::NV12, 2, 2, 2);
+        let back = to_bgra8(&nv12, &format, YuvMatrix::Bt709).unwrap();
+        assert_pixel(&back[..4], [0, 0, 255, 0xFF]);
+
+        // Odd sizes pad the stride and round the chroma up
+        let nv12 = bgra8_to_nv12(&[128; 3 * 3 * 4], 3, 3, YuvMatrix::Bt709).unwrap();
+        assert_eq!(nv12_stride(3), 4);
+        assert_eq!(nv12.len(), 4 * 3 + 4 * 2);
+        assert!(bgra8_to_nv12(&[0; 12], 2, 2, YuvMatrix::Bt709).is_none());
+    }
+
+    #[test]
+    fn test_to_bgra8_p010() {
+        // 2x1 with 16-bit samples (value in the high bits): white luma, neutral chroma
+        let white = 940u16 << 6;
+        let neutral = 512u16 << 6;
+        let mut data = Vec::new();
+        for sample in [white, white, neutral, neutral] {
+            data.extend_from_slice(&sample.to_le_bytes());
+        }
+        let out = to_bgra8(&data, &packed_format(fourcc::P010, 2, 1, 4), YuvMatrix::Bt2020)
+            .expect("supported");
+        assert_pixel(&out[0..4], [255, 255, 255, 0xFF]);
+        assert_pixel(&out[4..8], [255, 255, 255, 0xFF]);
+    }
+
+    #[test]
+    fn test_to_bgra8_yuy2() {
+        // Y0 U Y1 V pairs: two red pixels, then white and black
+        let data = [63u8, 102, 63, 240, 235, 128, 16, 128];
+        let out = to_bgra8(&data, &packed_format(fourcc::YUY2, 4, 1, 8), YuvMatrix::Bt709)
+            .expect("supported");
+        assert_pixel(&out[0..4], [0, 0, 255, 0xFF]);
+        assert_pixel(&out[4..8], [0, 0, 255, 0xFF]);
+        assert_pixel(&out[8..12], [255, 255, 255, 0xFF]);
+        assert_pixel(&out[12..16], [0, 0, 0, 0xFF]);
+    }
+
+    #[test]
+    fn test_frame_to_bgra8() {
+        use crate::types::{Frame, FrameData};
+
+        let frame = Frame {
+            format: packed_format(fourcc::XRGB8888, 1, 1, 4),
+            data: FrameData::Memory(vec![10, 20, 30, 40]),
+            pts: 0,
+            hdr_metadata: None,
+        };
+        assert_eq!(frame.to_bgra8().as_deref(), Some(&[10u8, 20, 30, 40][..]));
+
+        let dmabuf = Frame {
+            data: FrameData::DmaBuf {
+                fd: -1,
+                offset: 0,
+                modifier: 0,
+            },
+            ..frame
+        };
+        assert!(dmabuf.to_bgra8().is_none());
+    }
+
+    #[test]
+    fn test_to_bgra8_rejects_bad_input() {
+        let data = [0u8; 4];
+        assert!(to_bgra8(&data, &packed_format(0xDEADBEEF, 1, 1, 4), YuvMatrix::Bt709).is_none());
+        // NV12 buffer missing its chroma plane
+        assert!(to_bgra8(&data, &packed_format(fourcc::NV12, 2, 2, 2), YuvMatrix::Bt709).is_none());
+    }
+
     #[test]
     fn test_format_name() {
         assert_eq!(format_name(fourcc::XRGB8888), "XRGB8888");
@@ -4,12 +4,14 @@
 //! Works by reading keyboard events directly from /dev/input/event* devices.
 
 use evdev::{Device, InputEventKind, Key};
+use parking_lot::RwLock;
 use std::collections::HashSet;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, trace, warn};
 
+use crate::affinity::{self, ThreadRole};
 use crate::error::{NitrogenError, Result};
 
 /// A hotkey action
@@ -25,6 +27,10 @@ pub enum HotkeyAction {
     Pause,
     /// Toggle recording
     ToggleRecording,
+    /// Save a still of the capture (see [`Pipeline::snapshot`](crate::Pipeline::snapshot))
+    Screenshot,
+    /// Save the replay buffer (see [`Pipeline::save_replay`](crate::Pipeline::save_replay))
+    SaveReplay,
 }
 
 /// A hotkey binding (modifier keys + trigger key)
@@ -188,8 +194,8 @@ fn parse_key(name: &str) -> Result<Key> {
 
 /// Global hotkey listener
 pub struct HotkeyListener {
-    /// Registered hotkeys
-    hotkeys: Vec<Hotkey>,
+    /// Registered hotkeys, shared with the listener thread
+    hotkeys: Arc<RwLock<Vec<Hotkey>>>,
     /// Action sender
     action_tx: mpsc::Sender<HotkeyAction>,
     /// Running flag
@@ -207,7 +213,7 @@ impl HotkeyListener {
 
         Ok((
             Self {
-                hotkeys,
+                hotkeys: Arc::new(RwLock::new(hotkeys)),
                 action_tx,
                 running: Arc::new(AtomicBool::new(false)),
                 thread_handle: None,
@@ -237,6 +243,18 @@ impl HotkeyListener {
                 Key::KEY_F11,
                 HotkeyAction::ToggleRecording,
             ),
+            // Ctrl+Shift+Print to save a screenshot
+            Hotkey::new(
+                [Key::KEY_LEFTCTRL, Key::KEY_LEFTSHIFT],
+                Key::KEY_SYSRQ,
+                HotkeyAction::Screenshot,
+            ),
+            // Ctrl+Shift+F8 to save the replay buffer
+            Hotkey::new(
+                [Key::KEY_LEFTCTRL, Key::KEY_LEFTSHIFT],
+                Key::KEY_F8,
+                HotkeyAction::SaveReplay,
+            ),
         ];
 
         Self::new(hotkeys)
@@ -248,7 +266,7 @@ impl HotkeyListener {
             return Ok(());
         }
 
-        let hotkeys = self.hotkeys.clone();
+        let hotkeys = Arc::clone(&self.hotkeys);
         let action_tx = self.action_tx.clone();
         let running = self.running.clone();
 
@@ -257,6 +275,7 @@ impl HotkeyListener {
         let thread = std::thread::Builder::new()
             .name("nitrogen-hotkeys".to_string())
             .spawn(move || {
+                affinity::pin_current_thread(ThreadRole::Hotkeys);
                 if let Err(e) = run_hotkey_loop(hotkeys, action_tx, running.clone()) {
                     error!("Hotkey listener error: {}", e);
                 }
@@ -285,6 +304,20 @@ impl HotkeyListener {
     pub fn is_running(&self) -> bool {
         self.running.load(Ordering::SeqCst)
     }
+
+    /// Replace the active bindings
+    ///
+    /// Takes effect on the next key press, without restarting the listener
+    /// thread.
+    pub fn update_bindings(&self, hotkeys: Vec<Hotkey>) {
+        info!("Hotkey bindings updated ({} hotkeys)", hotkeys.len());
+        *self.hotkeys.write() = hotkeys;
+    }
+
+    /// The active bindings
+    pub fn bindings(&self) -> Vec<Hotkey> {
+        self.hotkeys.read().clone()
+    }
 }
 
 impl Drop for HotkeyListener {
@@ -293,6 +326,14 @@ impl Drop for HotkeyListener {
     }
 }
 
+/// Number of keyboard devices this user can read
+///
+/// Zero usually means the user isn't in the `input` group, so hotkeys
+/// won't work.
+pub fn readable_keyboard_count() -> usize {
+    find_keyboard_devices().len()
+}
+
 /// Find keyboard devices
 fn find_keyboard_devices() -> Vec<Device> {
     let mut devices = Vec::new();
@@ -329,7 +370,7 @@ fn find_keyboard_devices() -> Vec<Device> {
 
 /// Run the hotkey listening loop
 fn run_hotkey_loop(
-    hotkeys: Vec<Hotkey>,
+    hotkeys: Arc<RwLock<Vec<Hotkey>>>,
     action_tx: mpsc::Sender<HotkeyAction>,
     running: Arc<AtomicBool>,
 ) -> Result<()> {
@@ -363,13 +404,13 @@ fn run_hotkey_loop(
                                 pressed_keys.insert(key);
                                 trace!("Key pressed: {:?}", key);
 
-                                // Check if any hotkey matches
-                                for hotkey in &hotkeys {
-                                    if check_hotkey(&pressed_keys, hotkey) {
-                                        info!("Hotkey triggered: {:?}", hotkey.action);
-                                        if action_tx.blocking_send(hotkey.action).is_err() {
-                                            debug!("Action receiver dropped");
-                                        }
+                                // Check the current bindings; they may have been
+                                // swapped since the last key press
+                                let actions = triggered_actions(&pressed_keys, &hotkeys.read());
+                                for action in actions {
+                                    info!("Hotkey triggered: {:?}", action);
+                                    if action_tx.blocking_send(action).is_err() {
+                                        debug!("Action receiver dropped");
                                     }
                                 }
                             }
@@ -392,6 +433,15 @@ fn run_hotkey_loop(
     Ok(())
 }
 
+/// Actions of every hotkey the pressed keys match
+fn triggered_actions(pressed: &HashSet<Key>, hotkeys: &[Hotkey]) -> Vec<HotkeyAction> {
+    hotkeys
+        .iter()
+        .filter(|hotkey| check_hotkey(pressed, hotkey))
+        .map(|hotkey| hotkey.action)
+        .collect()
+}
+
 /// Check if a hotkey matches the currently pressed keys
 fn check_hotkey(pressed: &HashSet<Key>, hotkey: &Hotkey) -> bool {
     // The trigger key must be pressed
@@ -443,6 +493,10 @@ mod tests {
         let hotkey = Hotkey::parse("f5", HotkeyAction::Start).unwrap();
         assert_eq!(hotkey.key, Key::KEY_F5);
         assert!(hotkey.modifiers.is_empty());
+
+        let hotkey = Hotkey::parse("ctrl+shift+print", HotkeyAction::Screenshot).unwrap();
+        assert_eq!(hotkey.key, Key::KEY_SYSRQ);
+        assert_eq!(hotkey.action, HotkeyAction::Screenshot);
     }
 
     #[test]
@@ -477,4 +531,27 @@ mod tests {
             .collect();
         assert!(check_hotkey(&pressed, &hotkey));
     }
+
+    #[test]
+    fn test_update_bindings() {
+        let toggle = Hotkey::parse("ctrl+shift+f9", HotkeyAction::Toggle).unwrap();
+        let (listener, _rx) = HotkeyListener::new(vec![toggle]).unwrap();
+
+        let f9: HashSet<Key> = [Key::KEY_LEFTCTRL, Key::KEY_LEFTSHIFT, Key::KEY_F9]
+            .into_iter()
+            .collect();
+        let f10: HashSet<Key> = [Key::KEY_LEFTCTRL, Key::KEY_F10].into_iter().collect();
+        assert_eq!(triggered_actions(&f9, &listener.hotkeys.read()), [HotkeyAction::Toggle]);
+        assert!(triggered_actions(&f10, &listener.hotkeys.read()).is_empty());
+
+        // Rebound: the old combo no longer fires, the new one does
+        let record = Hotkey::parse("ctrl+f10", HotkeyAction::ToggleRecording).unwrap();
+        listener.update_bindings(vec![record.clone()]);
+        assert!(triggered_actions(&f9, &listener.hotkeys.read()).is_empty());
+        assert_eq!(
+            triggered_actions(&f10, &listener.hotkeys.read()),
+            [HotkeyAction::ToggleRecording]
+        );
+        assert_eq!(listener.bindings(), [record]);
+    }
 }
@@ -2,88 +2,152 @@
 //!
 //! Connects to the running daemon to send commands and receive responses.
 
+use std::io::ErrorKind;
+use std::path::Path;
 use std::time::Duration;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::UnixStream;
 use tracing::debug;
 
-use super::protocol::{IpcMessage, IpcResponse, PipelineStatistics, PipelineStatus};
+use super::protocol::{
+    IpcMessage, IpcResponse, PipelineStatistics, PipelineStatus, IPC_PROTOCOL_VERSION,
+};
 use super::socket_path;
 use crate::error::{NitrogenError, Result};
+use crate::output::ReplayInfo;
+use crate::preview::PreviewImage;
+use crate::snapshot::SnapshotInfo;
+use crate::types::CaptureSource;
 
-/// Default connection timeout
-const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Default timeout for connecting and for each request
+///
+/// The daemon answers from memory, so anything slower than this means it
+/// is wedged rather than busy.
+pub const DEFAULT_IPC_TIMEOUT: Duration = Duration::from_secs(1);
 
-/// Default read/write timeout
-const IO_TIMEOUT: Duration = Duration::from_secs(10);
+/// Timeout for a source switch, which waits on the user in the portal dialog
+pub const SOURCE_SELECTION_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Timeout for a snapshot, which encodes a full-resolution image
+pub const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Timeout for saving a replay, which muxes the whole buffer to disk
+pub const REPLAY_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// IPC client for communicating with the daemon
 pub struct IpcClient {
     stream: UnixStream,
+    /// Timeout applied to each request (write + response)
+    timeout: Duration,
 }
 
 impl IpcClient {
-    /// Connect to the daemon with default timeout
+    /// Connect to the daemon with the default timeout
     pub async fn connect() -> Result<Self> {
-        Self::connect_with_timeout(CONNECT_TIMEOUT).await
+        Self::connect_with_timeout(DEFAULT_IPC_TIMEOUT).await
     }
 
-    /// Connect to the daemon with custom timeout
+    /// Connect to the daemon with a custom timeout
+    ///
+    /// The same timeout is used for every request made through the client.
     pub async fn connect_with_timeout(timeout: Duration) -> Result<Self> {
-        let path = socket_path();
+        Self::connect_to(&socket_path(), timeout).await
+    }
 
+    /// Connect to a daemon listening on a specific socket
+    ///
+    /// Fails with [`NitrogenError::NoActiveSession`] if the socket doesn't
+    /// exist, [`NitrogenError::DaemonUnreachable`] if nothing is listening on
+    /// it, and [`NitrogenError::IpcTimeout`] if the connection isn't accepted
+    /// in time.
+    pub async fn connect_to(path: &Path, timeout: Duration) -> Result<Self> {
         if !path.exists() {
             return Err(NitrogenError::NoActiveSession);
         }
 
-        let stream = tokio::time::timeout(timeout, UnixStream::connect(&path))
+        let stream = tokio::time::timeout(timeout, UnixStream::connect(path))
             .await
-            .map_err(|_| NitrogenError::Config("Connection timed out".into()))?
-            .map_err(|e| NitrogenError::Config(format!("Failed to connect to daemon: {}", e)))?;
+            .map_err(|_| {
+                NitrogenError::IpcTimeout(format!("connecting to {:?} took over {:?}", path, timeout))
+            })?
+            .map_err(|e| match e.kind() {
+                ErrorKind::ConnectionRefused | ErrorKind::NotFound => {
+                    NitrogenError::DaemonUnreachable(format!("{:?}: {}", path, e))
+                }
+                _ => NitrogenError::Config(format!("Failed to connect to daemon: {}", e)),
+            })?;
 
         debug!("Connected to daemon at {:?}", path);
 
-        Ok(Self { stream })
+        Ok(Self { stream, timeout })
+    }
+
+    /// Set the timeout used for subsequent requests
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Timeout used for requests
+    pub fn timeout(&self) -> Duration {
+        self.timeout
     }
 
-    /// Send a message and receive a response with timeout
+    /// Send a message and receive a response within the request timeout
+    ///
+    /// A [`IpcResponse::VersionMismatch`] from the daemon is turned into
+    /// [`NitrogenError::IpcProtocolMismatch`] here, so callers never see it.
     async fn send(&mut self, msg: IpcMessage) -> Result<IpcResponse> {
-        self.send_with_timeout(msg, IO_TIMEOUT).await
+        let timeout = self.timeout;
+        let response = tokio::time::timeout(timeout, self.exchange(&msg))
+            .await
+            .map_err(|_| {
+                NitrogenError::IpcTimeout(format!("no response to {:?} within {:?}", msg, timeout))
+            })??;
+
+        match response {
+            IpcResponse::VersionMismatch { server_version, .. } => {
+                Err(NitrogenError::IpcProtocolMismatch {
+                    client: IPC_PROTOCOL_VERSION,
+                    daemon: server_version,
+                })
+            }
+            other => Ok(other),
+        }
     }
 
-    /// Send a message and receive a response with custom timeout
-    async fn send_with_timeout(
-        &mut self,
-        msg: IpcMessage,
-        timeout: Duration,
-    ) -> Result<IpcResponse> {
+    /// Write one message and read one response line
+    async fn exchange(&mut self, msg: &IpcMessage) -> Result<IpcResponse> {
         let (reader, mut writer) = self.stream.split();
 
-        // Send message with timeout
-        let msg_bytes = msg.to_bytes();
-        tokio::time::timeout(timeout, writer.write_all(&msg_bytes))
+        writer
+            .write_all(&msg.to_bytes())
             .await
-            .map_err(|_| NitrogenError::Config("Write timed out".into()))?
             .map_err(|e| NitrogenError::Config(format!("Failed to send message: {}", e)))?;
 
-        // Read response with timeout
         let mut reader = BufReader::new(reader);
         let mut line = String::new();
-        tokio::time::timeout(timeout, reader.read_line(&mut line))
+        let read = reader
+            .read_line(&mut line)
             .await
-            .map_err(|_| NitrogenError::Config("Read timed out".into()))?
             .map_err(|e| NitrogenError::Config(format!("Failed to read response: {}", e)))?;
+        if read == 0 {
+            return Err(NitrogenError::DaemonUnreachable(
+                "daemon closed the connection without responding".into(),
+            ));
+        }
 
         IpcResponse::from_bytes(line.trim().as_bytes())
             .map_err(|e| NitrogenError::Config(format!("Invalid response: {}", e)))
     }
 
     /// Ping the daemon to check if it's alive
+    ///
+    /// Returns Ok(false) if the daemon answered with something other than a
+    /// pong, and an error if it timed out or speaks another protocol version.
     pub async fn ping(&mut self) -> Result<bool> {
-        match self.send(IpcMessage::Ping).await {
-            Ok(IpcResponse::Pong) => Ok(true),
-            Ok(_) => Ok(false),
-            Err(_) => Ok(false),
+        match self.send(IpcMessage::Ping).await? {
+            IpcResponse::Pong => Ok(true),
+            _ => Ok(false),
         }
     }
 
@@ -122,4 +186,107 @@ impl IpcClient {
             _ => Err(NitrogenError::Config("Unexpected response".into())),
         }
     }
+
+    /// Request the encoder to emit a keyframe
+    pub async fn force_keyframe(&mut self) -> Result<()> {
+        match self.send(IpcMessage::ForceKeyframe).await? {
+            IpcResponse::Ok => Ok(()),
+            IpcResponse::Error { message } => Err(NitrogenError::Config(message)),
+            _ => Err(NitrogenError::Config("Unexpected response".into())),
+        }
+    }
+
+    /// Change the A/V sync offset in ms (positive delays audio, negative delays video)
+    pub async fn set_sync_offset(&mut self, offset_ms: i32) -> Result<()> {
+        match self.send(IpcMessage::SetSyncOffset { offset_ms }).await? {
+            IpcResponse::Ok => Ok(()),
+            IpcResponse::Error { message } => Err(NitrogenError::Config(message)),
+            _ => Err(NitrogenError::Config("Unexpected response".into())),
+        }
+    }
+
+    /// Pause the virtual camera behind its placeholder, or resume it
+    pub async fn set_camera_paused(&mut self, paused: bool) -> Result<()> {
+        match self.send(IpcMessage::SetCameraPaused { paused }).await? {
+            IpcResponse::Ok => Ok(()),
+            IpcResponse::Error { message } => Err(NitrogenError::Config(message)),
+            _ => Err(NitrogenError::Config("Unexpected response".into())),
+        }
+    }
+
+    /// Change the video bitrate in kbps, returning the old and new bitrate
+    pub async fn set_bitrate(&mut self, kbps: u32) -> Result<(u32, u32)> {
+        match self.send(IpcMessage::SetBitrate { kbps }).await? {
+            IpcResponse::BitrateChanged { old_kbps, new_kbps } => Ok((old_kbps, new_kbps)),
+            IpcResponse::Error { message } => Err(NitrogenError::Config(message)),
+            _ => Err(NitrogenError::Config("Unexpected response".into())),
+        }
+    }
+
+    /// Get a PNG thumbnail of the latest captured frame
+    ///
+    /// The daemon clamps `max_width` to its limits and renders at most twice
+    /// a second; faster requests get the previous thumbnail again.
+    pub async fn preview(&mut self, max_width: u32) -> Result<PreviewImage> {
+        match self.send(IpcMessage::GetPreview { max_width }).await? {
+            IpcResponse::Preview(image) => Ok(image),
+            IpcResponse::Error { message } => Err(NitrogenError::Config(message)),
+            _ => Err(NitrogenError::Config("Unexpected response".into())),
+        }
+    }
+
+    /// Save the latest captured frame to `path` (PNG or JPEG by extension)
+    ///
+    /// `path` is resolved by the daemon, so it should be absolute; recording
+    /// path placeholders like `{date}` and `{n}` are filled in. With `raw`
+    /// the frame is saved without tonemapping, watermark or overlay.
+    pub async fn snapshot(&mut self, path: &str, raw: bool) -> Result<SnapshotInfo> {
+        let timeout = self.timeout;
+        self.timeout = timeout.max(SNAPSHOT_TIMEOUT);
+        let path = path.to_string();
+        let response = self.send(IpcMessage::Snapshot { path, raw }).await;
+        self.timeout = timeout;
+
+        match response? {
+            IpcResponse::Snapshot(info) => Ok(info),
+            IpcResponse::Error { message } => Err(NitrogenError::Config(message)),
+            _ => Err(NitrogenError::Config("Unexpected response".into())),
+        }
+    }
+
+    /// Write the daemon's replay buffer to `path`
+    ///
+    /// `path` is resolved by the daemon, so it should be absolute; recording
+    /// path placeholders like `{date}` and `{n}` are filled in. Capture keeps
+    /// running while the replay is written.
+    pub async fn save_replay(&mut self, path: &str) -> Result<ReplayInfo> {
+        let timeout = self.timeout;
+        self.timeout = timeout.max(REPLAY_TIMEOUT);
+        let path = path.to_string();
+        let response = self.send(IpcMessage::SaveReplay { path }).await;
+        self.timeout = timeout;
+
+        match response? {
+            IpcResponse::Replay(info) => Ok(info),
+            IpcResponse::Error { message } => Err(NitrogenError::Config(message)),
+            _ => Err(NitrogenError::Config("Unexpected response".into())),
+        }
+    }
+
+    /// Switch the running capture to another source
+    ///
+    /// Waits up to [`SOURCE_SELECTION_TIMEOUT`] for the user to pick it in
+    /// the portal dialog. Returns the new source's size.
+    pub async fn set_source(&mut self, source: CaptureSource) -> Result<(u32, u32)> {
+        let timeout = self.timeout;
+        self.timeout = timeout.max(SOURCE_SELECTION_TIMEOUT);
+        let response = self.send(IpcMessage::SetSource { source }).await;
+        self.timeout = timeout;
+
+        match response? {
+            IpcResponse::SourceChanged { width, height } => Ok((width, height)),
+            IpcResponse::Error { message } => Err(NitrogenError::Config(message)),
+            _ => Err(NitrogenError::Config("Unexpected response".into())),
+        }
+    }
 }
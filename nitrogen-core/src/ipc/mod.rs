@@ -7,12 +7,18 @@ mod client;
 mod protocol;
 mod server;
 
-pub use client::IpcClient;
-pub use protocol::{IpcMessage, IpcResponse, PipelineStatistics, PipelineStatus};
+pub use client::{
+    IpcClient, DEFAULT_IPC_TIMEOUT, REPLAY_TIMEOUT, SNAPSHOT_TIMEOUT, SOURCE_SELECTION_TIMEOUT,
+};
+pub use protocol::{
+    IpcMessage, IpcResponse, PipelineStatistics, PipelineStatus, IPC_PROTOCOL_VERSION,
+};
 pub use server::IpcServer;
 
 use std::path::PathBuf;
 
+use crate::error::{NitrogenError, Result};
+
 /// Get the IPC socket path
 ///
 /// Uses XDG_RUNTIME_DIR if available, otherwise /tmp
@@ -28,18 +34,28 @@ pub fn socket_path() -> PathBuf {
     }
 }
 
-/// Check if the daemon is running by checking if the socket exists and is responsive
-pub async fn daemon_running() -> bool {
-    let path = socket_path();
-    if !path.exists() {
-        return false;
+/// Check that a responsive daemon is listening on the socket
+///
+/// Errors distinguish a missing socket ([`NitrogenError::NoActiveSession`]),
+/// a stale one ([`NitrogenError::DaemonUnreachable`]), a wedged daemon
+/// ([`NitrogenError::IpcTimeout`]) and a daemon from another release
+/// ([`NitrogenError::IpcProtocolMismatch`]). Gives up after
+/// [`DEFAULT_IPC_TIMEOUT`].
+pub async fn probe_daemon() -> Result<()> {
+    let mut client = IpcClient::connect().await?;
+    if client.ping().await? {
+        Ok(())
+    } else {
+        Err(NitrogenError::Config(
+            "Daemon answered ping with an unexpected response".into(),
+        ))
     }
+}
 
-    // Try to connect and ping
-    match IpcClient::connect().await {
-        Ok(mut client) => {
-            matches!(client.ping().await, Ok(true))
-        }
-        Err(_) => false,
-    }
+/// Check if the daemon is running by checking if the socket exists and is responsive
+///
+/// A daemon that doesn't answer within [`DEFAULT_IPC_TIMEOUT`] counts as not
+/// running; use [`probe_daemon`] to tell the cases apart.
+pub async fn daemon_running() -> bool {
+    probe_daemon().await.is_ok()
 }
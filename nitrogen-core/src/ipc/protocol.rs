@@ -4,6 +4,17 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::output::{ReplayInfo, StreamStats};
+use crate::preview::PreviewImage;
+use crate::snapshot::SnapshotInfo;
+use crate::types::CaptureSource;
+
+/// Version of the IPC wire protocol
+///
+/// Bump this whenever a message or response changes shape. Clients send it
+/// with every message and the daemon refuses to serve a different version.
+pub const IPC_PROTOCOL_VERSION: u32 = 1;
+
 /// Messages that can be sent to the daemon
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
@@ -18,6 +29,26 @@ pub enum IpcMessage {
     Stop,
     /// Force stop the daemon
     ForceStop,
+    /// Force the encoder to emit a keyframe on the next frame
+    ForceKeyframe,
+    /// Switch capture to another source (prompts via the portal)
+    SetSource { source: CaptureSource },
+    /// Change the A/V sync offset of recordings and streams (positive delays audio)
+    SetSyncOffset { offset_ms: i32 },
+    /// Fade the virtual camera to its privacy placeholder, or back to the capture
+    SetCameraPaused { paused: bool },
+    /// Change the video bitrate of the running encoder in kbps
+    SetBitrate { kbps: u32 },
+    /// Get a PNG thumbnail of the latest captured frame, at most `max_width` wide
+    GetPreview { max_width: u32 },
+    /// Save the latest captured frame to an image file (`raw` skips tonemap/overlay)
+    Snapshot {
+        path: String,
+        #[serde(default)]
+        raw: bool,
+    },
+    /// Write the replay buffer to a new file without stopping capture
+    SaveReplay { path: String },
 }
 
 /// Responses from the daemon
@@ -36,6 +67,21 @@ pub enum IpcResponse {
     Stats(PipelineStatistics),
     /// Shutdown acknowledgment
     Stopping,
+    /// Capture switched to a new source of this size
+    SourceChanged { width: u32, height: u32 },
+    /// Video bitrate changed, in kbps
+    BitrateChanged { old_kbps: u32, new_kbps: u32 },
+    /// Thumbnail of the capture
+    Preview(PreviewImage),
+    /// Still written to disk
+    Snapshot(SnapshotInfo),
+    /// Replay written to disk
+    Replay(ReplayInfo),
+    /// The client speaks a different protocol version than the daemon
+    VersionMismatch {
+        client_version: u32,
+        server_version: u32,
+    },
 }
 
 /// Current pipeline status
@@ -57,6 +103,15 @@ pub struct PipelineStatus {
     pub pid: u32,
     /// Uptime in seconds
     pub uptime_seconds: f64,
+    /// Seconds left before a `--duration` auto-stop (None = no limit)
+    #[serde(default)]
+    pub remaining_seconds: Option<f64>,
+    /// RTMP/SRT stream health (None = not streaming)
+    #[serde(default)]
+    pub stream: Option<StreamStats>,
+    /// Outputs the pipeline is feeding, e.g. "Camera: Nitrogen Camera"
+    #[serde(default)]
+    pub outputs: Vec<String>,
 }
 
 /// Pipeline statistics
@@ -80,20 +135,68 @@ pub struct PipelineStatistics {
     pub codec: String,
     /// Target bitrate in kbps
     pub bitrate: u32,
+    /// Average capture latency in milliseconds
+    #[serde(default)]
+    pub capture_latency_ms: f64,
+    /// Average encode latency in milliseconds
+    #[serde(default)]
+    pub encode_latency_ms: f64,
+    /// Average end-to-end latency in milliseconds
+    #[serde(default)]
+    pub total_latency_ms: f64,
+    /// GPU index being encoded on, if GPU monitoring is enabled
+    #[serde(default)]
+    pub gpu: Option<u32>,
+}
+
+/// A message as sent on the wire, tagged with the protocol version
+#[derive(Serialize)]
+struct VersionedMessage<'a> {
+    version: u32,
+    #[serde(flatten)]
+    message: &'a IpcMessage,
+}
+
+/// Just the version field of an incoming message
+#[derive(Deserialize)]
+struct VersionProbe {
+    /// Clients from before protocol versioning don't send one
+    #[serde(default)]
+    version: u32,
 }
 
 impl IpcMessage {
     /// Serialize message to JSON bytes with newline terminator
+    ///
+    /// The message carries a `version` field set to [`IPC_PROTOCOL_VERSION`].
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut bytes = serde_json::to_vec(self).unwrap_or_default();
+        let versioned = VersionedMessage {
+            version: IPC_PROTOCOL_VERSION,
+            message: self,
+        };
+        let mut bytes = serde_json::to_vec(&versioned).unwrap_or_default();
         bytes.push(b'\n');
         bytes
     }
 
     /// Deserialize message from JSON bytes
+    ///
+    /// The `version` field is ignored; use [`IpcMessage::peek_version`] to
+    /// check it first.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self, serde_json::Error> {
         serde_json::from_slice(bytes)
     }
+
+    /// Read the protocol version of a serialized message without parsing it
+    ///
+    /// Works even when the message type itself is unknown to this build.
+    /// Messages without a version field report 0. Returns None if the bytes
+    /// are not a JSON object.
+    pub fn peek_version(bytes: &[u8]) -> Option<u32> {
+        serde_json::from_slice::<VersionProbe>(bytes)
+            .ok()
+            .map(|probe| probe.version)
+    }
 }
 
 impl IpcResponse {
@@ -136,4 +239,119 @@ mod tests {
         let parsed = IpcResponse::from_bytes(&bytes[..bytes.len() - 1]).unwrap();
         assert!(matches!(parsed, IpcResponse::Pong));
     }
+
+    #[test]
+    fn test_message_carries_version() {
+        let bytes = IpcMessage::Stats.to_bytes();
+        let json = &bytes[..bytes.len() - 1];
+        assert_eq!(IpcMessage::peek_version(json), Some(IPC_PROTOCOL_VERSION));
+        assert!(matches!(
+            IpcMessage::from_bytes(json).unwrap(),
+            IpcMessage::Stats
+        ));
+    }
+
+    #[test]
+    fn test_preview_round_trip() {
+        let msg = IpcMessage::GetPreview { max_width: 320 };
+        let bytes = msg.to_bytes();
+        assert!(matches!(
+            IpcMessage::from_bytes(&bytes[..bytes.len() - 1]).unwrap(),
+            IpcMessage::GetPreview { max_width: 320 }
+        ));
+
+        let image = PreviewImage {
+            width: 2,
+            height: 1,
+            source_size: (4, 2),
+            pts: 7,
+            png: "iVBORw0KGgo=".to_string(),
+        };
+        let bytes = IpcResponse::Preview(image.clone()).to_bytes();
+        match IpcResponse::from_bytes(&bytes[..bytes.len() - 1]).unwrap() {
+            IpcResponse::Preview(parsed) => assert_eq!(parsed, image),
+            other => panic!("expected a preview, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        // Older clients don't send `raw`
+        let json = br#"{"version":1,"type":"Snapshot","path":"/tmp/still.png"}"#;
+        match IpcMessage::from_bytes(json).unwrap() {
+            IpcMessage::Snapshot { path, raw } => {
+                assert_eq!(path, "/tmp/still.png");
+                assert!(!raw);
+            }
+            other => panic!("expected a snapshot, got {:?}", other),
+        }
+
+        let info = SnapshotInfo {
+            path: "/tmp/still.png".into(),
+            width: 3840,
+            height: 2160,
+            format: crate::snapshot::SnapshotFormat::Png,
+            pts: 7,
+        };
+        let bytes = IpcResponse::Snapshot(info.clone()).to_bytes();
+        match IpcResponse::from_bytes(&bytes[..bytes.len() - 1]).unwrap() {
+            IpcResponse::Snapshot(parsed) => assert_eq!(parsed, info),
+            other => panic!("expected a snapshot, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_save_replay_round_trip() {
+        let json = br#"{"version":1,"type":"SaveReplay","path":"/tmp/clip-{n}.mp4"}"#;
+        match IpcMessage::from_bytes(json).unwrap() {
+            IpcMessage::SaveReplay { path } => assert_eq!(path, "/tmp/clip-{n}.mp4"),
+            other => panic!("expected a replay save, got {:?}", other),
+        }
+
+        let info = ReplayInfo {
+            path: "/tmp/clip-1.mp4".into(),
+            duration_ms: 30_500,
+            video_packets: 1830,
+            audio_packets: 1430,
+        };
+        let bytes = IpcResponse::Replay(info.clone()).to_bytes();
+        match IpcResponse::from_bytes(&bytes[..bytes.len() - 1]).unwrap() {
+            IpcResponse::Replay(parsed) => assert_eq!(parsed, info),
+            other => panic!("expected a replay, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_set_bitrate_round_trip() {
+        let bytes = IpcMessage::SetBitrate { kbps: 4000 }.to_bytes();
+        assert!(matches!(
+            IpcMessage::from_bytes(&bytes[..bytes.len() - 1]).unwrap(),
+            IpcMessage::SetBitrate { kbps: 4000 }
+        ));
+
+        let response = IpcResponse::BitrateChanged {
+            old_kbps: 6000,
+            new_kbps: 4000,
+        };
+        let bytes = response.to_bytes();
+        assert!(matches!(
+            IpcResponse::from_bytes(&bytes[..bytes.len() - 1]).unwrap(),
+            IpcResponse::BitrateChanged {
+                old_kbps: 6000,
+                new_kbps: 4000
+            }
+        ));
+    }
+
+    #[test]
+    fn test_peek_version() {
+        // Pre-versioning clients
+        assert_eq!(IpcMessage::peek_version(br#"{"type":"Ping"}"#), Some(0));
+        // Newer clients with message types this build doesn't know
+        assert_eq!(
+            IpcMessage::peek_version(br#"{"version":99,"type":"Teleport"}"#),
+            Some(99)
+        );
+        assert_eq!(IpcMessage::peek_version(b"not json"), None);
+    }
 }
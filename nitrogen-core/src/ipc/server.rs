@@ -11,7 +11,9 @@ use tokio::net::{UnixListener, UnixStream};
 use tokio::sync::{broadcast, RwLock};
 use tracing::{debug, error, info, warn};
 
-use super::protocol::{IpcMessage, IpcResponse, PipelineStatistics, PipelineStatus};
+use super::protocol::{
+    IpcMessage, IpcResponse, PipelineStatistics, PipelineStatus, IPC_PROTOCOL_VERSION,
+};
 use super::socket_path;
 use crate::error::{NitrogenError, Result};
 use crate::pipeline::Pipeline;
@@ -146,6 +148,24 @@ impl IpcServer {
                         continue;
                     }
 
+                    // Check the version before the message itself, so clients
+                    // from another release get a clear answer even when they
+                    // send message types this build doesn't know
+                    if let Some(version) = IpcMessage::peek_version(trimmed.as_bytes())
+                        .filter(|&v| v != IPC_PROTOCOL_VERSION)
+                    {
+                        warn!(
+                            "Rejecting IPC client with protocol v{} (daemon is v{})",
+                            version, IPC_PROTOCOL_VERSION
+                        );
+                        let response = IpcResponse::VersionMismatch {
+                            client_version: version,
+                            server_version: IPC_PROTOCOL_VERSION,
+                        };
+                        let _ = writer.write_all(&response.to_bytes()).await;
+                        return true;
+                    }
+
                     match IpcMessage::from_bytes(trimmed.as_bytes()) {
                         Ok(msg) => {
                             let (response, should_stop) = self.handle_message(msg).await;
@@ -203,6 +223,103 @@ impl IpcServer {
                 info!("Received force stop command via IPC");
                 (IpcResponse::Stopping, true)
             }
+            IpcMessage::ForceKeyframe => {
+                let pipeline_guard = self.pipeline.read().await;
+                match pipeline_guard.as_ref() {
+                    Some(pipeline) => match pipeline.request_keyframe() {
+                        Ok(()) => (IpcResponse::Ok, false),
+                        Err(e) => (IpcResponse::error(e.to_string()), false),
+                    },
+                    None => (IpcResponse::error("No active pipeline"), false),
+                }
+            }
+            IpcMessage::SetSyncOffset { offset_ms } => {
+                let mut pipeline_guard = self.pipeline.write().await;
+                match pipeline_guard.as_mut() {
+                    Some(pipeline) => match pipeline.set_sync_offset(offset_ms) {
+                        Ok(()) => (IpcResponse::Ok, false),
+                        Err(e) => (IpcResponse::error(e.to_string()), false),
+                    },
+                    None => (IpcResponse::error("No active pipeline"), false),
+                }
+            }
+            IpcMessage::SetCameraPaused { paused } => {
+                let mut pipeline_guard = self.pipeline.write().await;
+                match pipeline_guard.as_mut() {
+                    Some(pipeline) => {
+                        pipeline.set_camera_paused(paused);
+                        (IpcResponse::Ok, false)
+                    }
+                    None => (IpcResponse::error("No active pipeline"), false),
+                }
+            }
+            IpcMessage::SetBitrate { kbps } => {
+                let mut pipeline_guard = self.pipeline.write().await;
+                match pipeline_guard.as_mut() {
+                    Some(pipeline) => match pipeline.set_bitrate(kbps) {
+                        Ok((old_kbps, new_kbps)) => {
+                            (IpcResponse::BitrateChanged { old_kbps, new_kbps }, false)
+                        }
+                        Err(e) => (IpcResponse::error(e.to_string()), false),
+                    },
+                    None => (IpcResponse::error("No active pipeline"), false),
+                }
+            }
+            IpcMessage::GetPreview { max_width } => {
+                let mut pipeline_guard = self.pipeline.write().await;
+                match pipeline_guard.as_mut() {
+                    Some(pipeline) => match pipeline.preview(max_width) {
+                        Ok(image) => (IpcResponse::Preview(image), false),
+                        Err(e) => (IpcResponse::error(e.to_string()), false),
+                    },
+                    None => (IpcResponse::error("No active pipeline"), false),
+                }
+            }
+            IpcMessage::Snapshot { path, raw } => {
+                let pipeline_guard = self.pipeline.read().await;
+                match pipeline_guard.as_ref() {
+                    Some(pipeline) => match pipeline.snapshot(&path, raw) {
+                        Ok(info) => (IpcResponse::Snapshot(info), false),
+                        Err(e) => (IpcResponse::error(e.to_string()), false),
+                    },
+                    None => (IpcResponse::error("No active pipeline"), false),
+                }
+            }
+            IpcMessage::SaveReplay { path } => {
+                info!("Received replay save via IPC");
+                // The pipeline is only held while the buffer is copied
+                let saving = match self.pipeline.read().await.as_ref() {
+                    Some(pipeline) => pipeline.save_replay(&path),
+                    None => return (IpcResponse::error("No active pipeline"), false),
+                };
+                match saving {
+                    Ok(handle) => match handle.await {
+                        Ok(Ok(info)) => (IpcResponse::Replay(info), false),
+                        Ok(Err(e)) => (IpcResponse::error(e.to_string()), false),
+                        Err(e) => (IpcResponse::error(format!("Replay save failed: {}", e)), false),
+                    },
+                    Err(e) => (IpcResponse::error(e.to_string()), false),
+                }
+            }
+            IpcMessage::SetSource { source } => {
+                info!("Received source switch to {} via IPC", source);
+                // Holds the pipeline for the whole portal dialog; the switch
+                // keeps processing frames itself meanwhile
+                let mut pipeline_guard = self.pipeline.write().await;
+                match pipeline_guard.as_mut() {
+                    Some(pipeline) => match pipeline.restart_capture(source).await {
+                        Ok(session) => (
+                            IpcResponse::SourceChanged {
+                                width: session.width,
+                                height: session.height,
+                            },
+                            false,
+                        ),
+                        Err(e) => (IpcResponse::error(e.to_string()), false),
+                    },
+                    None => (IpcResponse::error("No active pipeline"), false),
+                }
+            }
         }
     }
 
@@ -223,6 +340,9 @@ impl IpcServer {
                     camera_name: None, // Could add to stats
                     pid: std::process::id(),
                     uptime_seconds: uptime,
+                    remaining_seconds: pipeline.remaining().map(|d| d.as_secs_f64()),
+                    stream: pipeline.stream_stats(),
+                    outputs: pipeline.active_outputs(),
                 }
             }
             None => PipelineStatus {
@@ -234,6 +354,9 @@ impl IpcServer {
                 camera_name: None,
                 pid: std::process::id(),
                 uptime_seconds: uptime,
+                remaining_seconds: None,
+                stream: None,
+                outputs: Vec::new(),
             },
         }
     }
@@ -244,6 +367,7 @@ impl IpcServer {
 
         pipeline_guard.as_ref().map(|pipeline| {
             let stats = pipeline.stats();
+            let latency = pipeline.metrics().get_stats();
             PipelineStatistics {
                 frames_processed: stats.frames_processed,
                 frames_dropped: stats.frames_dropped,
@@ -254,6 +378,10 @@ impl IpcServer {
                 resolution: stats.resolution,
                 codec: stats.codec,
                 bitrate: stats.bitrate,
+                capture_latency_ms: latency.capture_latency_ms,
+                encode_latency_ms: latency.encode_latency_ms,
+                total_latency_ms: latency.total_latency_ms,
+                gpu: pipeline.monitored_gpu(),
             }
         })
     }
@@ -17,7 +17,21 @@
 //! └─────────────────┘    └──────────────┘    │ + File Output   │
 //!                                            └─────────────────┘
 //! ```
+//!
+//! # Features
+//!
+//! The capture → encode → camera/recording path is always built. Optional
+//! parts with heavy dependencies can be left out with
+//! `default-features = false`:
+//!
+//! - `webrtc` - WebRTC output and its signaling server (webrtc crate)
+//! - `streaming` - RTMP/SRT/RIST output
+//! - `hotkeys` - global hotkeys read from evdev devices
+//!
+//! Settings for a disabled output still parse; asking the pipeline to start
+//! it returns [`NitrogenError::Unsupported`].
 
+pub mod affinity;
 pub mod capture;
 pub mod config;
 pub mod encode;
@@ -25,30 +39,51 @@ pub mod env;
 pub mod error;
 pub mod formats;
 pub mod gpu;
+#[cfg(feature = "hotkeys")]
 pub mod hotkeys;
 pub mod ipc;
 pub mod output;
 pub mod overlay;
 pub mod performance;
 pub mod pipeline;
+pub mod preview;
+pub mod shutdown;
+pub mod snapshot;
 pub mod types;
+pub mod watermark;
 
-pub use config::{AudioCodec, AudioSource, Av1Config, Av1Tier, Av1Tune, CaptureConfig, ChromaFormat, Codec, EncoderPreset, MultipassMode, Preset};
-pub use encode::{TonemapAlgorithm, TonemapConfig, TonemapMode, Tonemapper};
+pub use affinity::{CpuAffinity, CpuSet, ThreadRole};
+pub use config::{AudioCodec, AudioSource, Av1Config, Av1Tier, Av1Tune, CaptureConfig, ChromaFormat, Codec, ColorRange, EncoderPreset, EncoderQuality, MicChannel, MultipassMode, Preset, Tune};
+pub use encode::{RateControl, TonemapAlgorithm, TonemapConfig, TonemapMode, Tonemapper};
 pub use env::{detect_environment, is_steam_deck_hardware, RuntimeEnvironment, GamescopeInfo, WaylandInfo, EnvironmentOptimizations};
 pub use error::{NitrogenError, Result};
-pub use performance::{create_metrics, query_gpu_stats, GpuStats, LatencyStats, PerformanceMetrics};
+pub use performance::{
+    create_metrics, create_metrics_with_mode, query_gpu_stats, GpuStats, LatencyStats, MetricsMode,
+    PerformanceMetrics, StatsLogger,
+};
 pub use overlay::{LatencyOverlay, OverlayConfig, OverlayPosition};
 pub use gpu::{detect_rtx50_features, get_gpu_generation, GpuGeneration, RecommendedAv1Settings, Rtx50Features};
-pub use hotkeys::{Hotkey, HotkeyAction, HotkeyListener};
-pub use ipc::{daemon_running, socket_path, IpcClient, IpcServer};
+#[cfg(feature = "hotkeys")]
+pub use hotkeys::{readable_keyboard_count, Hotkey, HotkeyAction, HotkeyListener};
+pub use ipc::{daemon_running, probe_daemon, socket_path, IpcClient, IpcServer};
+pub use output::{
+    AvSyncOffset, ContainerFormat, DashConfig, DashOutput, FileRecorder, FrameSink, HlsConfig,
+    HlsOutput, ReplayBuffer, ReplayInfo, SegmentConfig, SimulcastLayer, StreamConfig,
+    StreamProtocol, StreamStats, WebRTCConfig,
+};
+#[cfg(feature = "streaming")]
 pub use output::{
-    FileRecorder, StreamConfig, StreamOutput, StreamProtocol,
-    WebRTCConfig, WebRTCOutput, start_signaling_server,
-    stream_av_from_channels, stream_from_channel,
+    multi_stream_av_from_channels, stream_av_from_channels, stream_from_channel, MultiStreamOutput,
+    StreamOutput,
 };
-pub use pipeline::{Pipeline, PipelineState, PipelineStats};
+#[cfg(feature = "webrtc")]
+pub use output::{start_signaling_server, WebRTCOutput};
+pub use pipeline::{Pipeline, PipelineEvent, PipelineState, PipelineStats};
+pub use preview::{PreviewCache, PreviewImage};
+pub use shutdown::{ShutdownCoordinator, ShutdownSignal};
+pub use snapshot::{SnapshotFormat, SnapshotInfo};
 pub use types::{
     AudioFormat, AudioFrame, AudioSampleFormat, CaptureSource, ColorPrimaries, Handle, HdrMetadata,
     SourceInfo, SourceKind, TransferFunction,
 };
+pub use watermark::{Watermark, WatermarkConfig};
@@ -0,0 +1,272 @@
+//! Privacy placeholder for a paused virtual camera
+//!
+//! While paused, the camera shows a placeholder image (solid black unless
+//! one is configured) instead of the capture. Pausing and resuming crossfade
+//! between the last live frame and the placeholder over a few output
+//! frames, using the same fixed-point blend as frame interpolation. A
+//! transition of 0 ms cuts straight over.
+
+use std::path::Path;
+
+use tracing::info;
+
+use crate::error::Result;
+use crate::watermark::{decode_png, scale_bilinear};
+
+/// Default crossfade length when pausing or resuming the camera
+pub const DEFAULT_CAMERA_TRANSITION_MS: u32 = 250;
+
+/// Swaps BGRA camera frames for a placeholder while paused
+#[derive(Debug)]
+pub struct CameraPause {
+    /// Output frames per crossfade (0 = hard cut)
+    transition_frames: u32,
+    paused: bool,
+    /// Frames into the crossfade towards the current state
+    step: u32,
+    /// Last live frame, faded out while pausing
+    frozen: Option<Vec<u8>>,
+    /// Placeholder as configured, BGRA
+    placeholder: Option<(Vec<u8>, u32, u32)>,
+    /// Placeholder at the output size, rebuilt when the size changes
+    scaled: Option<(Vec<u8>, u32, u32)>,
+}
+
+impl CameraPause {
+    /// Crossfade over `transition_ms` at `fps` output frames per second
+    pub fn new(transition_ms: u32, fps: u32) -> Self {
+        let transition_frames = (transition_ms as u64 * fps as u64 + 500) / 1000;
+        Self {
+            transition_frames: transition_frames as u32,
+            paused: false,
+            step: transition_frames as u32,
+            frozen: None,
+            placeholder: None,
+            scaled: None,
+        }
+    }
+
+    /// Show a BGRA image instead of solid black; it is scaled to the output
+    pub fn with_placeholder(mut self, bgra: Vec<u8>, width: u32, height: u32) -> Self {
+        self.placeholder = Some((bgra, width, height));
+        self.scaled = None;
+        self
+    }
+
+    /// Show a PNG file instead of solid black
+    pub fn load_placeholder(self, path: &Path) -> Result<Self> {
+        let (rgba, width, height) = decode_png(path)?;
+        info!(
+            "Loaded camera placeholder {} ({}x{})",
+            path.display(),
+            width,
+            height
+        );
+        let bgra = rgba
+            .chunks_exact(4)
+            .flat_map(|p| [p[2], p[1], p[0], p[3]])
+            .collect();
+        Ok(self.with_placeholder(bgra, width, height))
+    }
+
+    /// Whether the placeholder is (or is fading) in
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Length of a crossfade in output frames
+    pub fn transition_frames(&self) -> u32 {
+        self.transition_frames
+    }
+
+    /// Start fading to the placeholder, or back to the capture
+    ///
+    /// Reversing mid-fade continues from the current blend instead of
+    /// jumping back to either end.
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused == self.paused {
+            return;
+        }
+        self.paused = paused;
+        self.step = self.transition_frames - self.step;
+    }
+
+    /// Replace a live BGRA frame (width * height * 4 bytes) with what the
+    /// camera should show
+    pub fn apply(&mut self, frame: &mut [u8], width: u32, height: u32) {
+        let fading = self.step < self.transition_frames;
+        if !self.paused && !fading {
+            return;
+        }
+        if fading {
+            self.step += 1;
+        }
+        let t = if self.transition_frames == 0 {
+            1.0
+        } else {
+            self.step as f32 / self.transition_frames as f32
+        };
+
+        self.scale_placeholder(width, height);
+        let Some((placeholder, _, _)) = &self.scaled else {
+            return;
+        };
+        if placeholder.len() != frame.len() {
+            return;
+        }
+
+        if self.paused && !fading {
+            frame.copy_from_slice(placeholder);
+        } else if self.paused {
+            // Fade out the frame that was live when the pause started
+            let frozen = match self.frozen.take() {
+                Some(frozen) if frozen.len() == frame.len() => frozen,
+                _ => frame.to_vec(),
+            };
+            blend(frame, &frozen, placeholder, t);
+            if self.step < self.transition_frames {
+                self.frozen = Some(frozen);
+            }
+        } else {
+            self.frozen = None;
+            let live = frame.to_vec();
+            blend(frame, placeholder, &live, t);
+        }
+    }
+
+    /// Scale the placeholder to `width`x`height` unless it already is
+    fn scale_placeholder(&mut self, width: u32, height: u32) {
+        let stale = !matches!(&self.scaled, Some((_, w, h)) if (*w, *h) == (width, height));
+        if stale {
+            let image = match &self.placeholder {
+                Some((bgra, w, h)) if (*w, *h) == (width, height) => bgra.clone(),
+                Some((bgra, w, h)) => scale_bilinear(bgra, *w, *h, width, height),
+                None => [0, 0, 0, 255].repeat(width as usize * height as usize),
+            };
+            self.scaled = Some((image, width, height));
+        }
+    }
+}
+
+/// Write `from * (1 - t) + to * t` into `out`, in the 8-bit fixed point of
+/// the frame interpolation blend
+fn blend(out: &mut [u8], from: &[u8], to: &[u8], t: f32) {
+    let t_fixed = (t.clamp(0.0, 1.0) * 256.0).round() as u16;
+    let inv_t_fixed = 256 - t_fixed;
+    for ((o, &p), &c) in out.iter_mut().zip(from).zip(to) {
+        *o = (((p as u16 * inv_t_fixed) + (c as u16 * t_fixed)) >> 8) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid_frame(width: u32, height: u32, bgra: [u8; 4]) -> Vec<u8> {
+        bgra.repeat((width * height) as usize)
+    }
+
+    #[test]
+    fn test_transition_frames() {
+        assert_eq!(CameraPause::new(250, 60).transition_frames(), 15);
+        assert_eq!(CameraPause::new(250, 30).transition_frames(), 8);
+        assert_eq!(CameraPause::new(0, 60).transition_frames(), 0);
+    }
+
+    #[test]
+    fn test_live_passes_through() {
+        let mut pause = CameraPause::new(250, 60);
+        let mut frame = solid_frame(4, 4, [200, 100, 50, 255]);
+        pause.apply(&mut frame, 4, 4);
+        assert_eq!(frame, solid_frame(4, 4, [200, 100, 50, 255]));
+    }
+
+    #[test]
+    fn test_zero_transition_is_hard_cut() {
+        let mut pause = CameraPause::new(0, 60);
+        pause.set_paused(true);
+        let mut frame = solid_frame(4, 4, [200, 200, 200, 255]);
+        pause.apply(&mut frame, 4, 4);
+        assert_eq!(frame, solid_frame(4, 4, [0, 0, 0, 255]));
+
+        pause.set_paused(false);
+        let mut frame = solid_frame(4, 4, [200, 200, 200, 255]);
+        pause.apply(&mut frame, 4, 4);
+        assert_eq!(frame, solid_frame(4, 4, [200, 200, 200, 255]));
+    }
+
+    #[test]
+    fn test_pause_fades_last_live_frame() {
+        // 4 frames at 40 fps = 100 ms
+        let mut pause = CameraPause::new(100, 40);
+        pause.set_paused(true);
+
+        // The first frame after pausing is frozen and faded a quarter
+        let mut frame = solid_frame(2, 2, [200, 200, 200, 255]);
+        pause.apply(&mut frame, 2, 2);
+        assert_eq!(frame, solid_frame(2, 2, [150, 150, 150, 255]));
+
+        // Later capture doesn't show through, only the frozen frame
+        let mut frame = solid_frame(2, 2, [40, 40, 40, 255]);
+        pause.apply(&mut frame, 2, 2);
+        assert_eq!(frame, solid_frame(2, 2, [100, 100, 100, 255]));
+
+        for _ in 0..4 {
+            let mut frame = solid_frame(2, 2, [40, 40, 40, 255]);
+            pause.apply(&mut frame, 2, 2);
+        }
+        let mut frame = solid_frame(2, 2, [40, 40, 40, 255]);
+        pause.apply(&mut frame, 2, 2);
+        assert_eq!(frame, solid_frame(2, 2, [0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_resume_fades_in_live() {
+        let mut pause = CameraPause::new(100, 40);
+        pause.set_paused(true);
+        for _ in 0..4 {
+            let mut frame = solid_frame(2, 2, [200, 200, 200, 255]);
+            pause.apply(&mut frame, 2, 2);
+        }
+
+        pause.set_paused(false);
+        let mut frame = solid_frame(2, 2, [200, 200, 200, 255]);
+        pause.apply(&mut frame, 2, 2);
+        assert_eq!(frame, solid_frame(2, 2, [50, 50, 50, 255]));
+        for _ in 0..3 {
+            frame = solid_frame(2, 2, [200, 200, 200, 255]);
+            pause.apply(&mut frame, 2, 2);
+        }
+        assert_eq!(frame, solid_frame(2, 2, [200, 200, 200, 255]));
+    }
+
+    #[test]
+    fn test_reverse_mid_fade() {
+        let mut pause = CameraPause::new(100, 40);
+        pause.set_paused(true);
+        let mut frame = solid_frame(2, 2, [200, 200, 200, 255]);
+        pause.apply(&mut frame, 2, 2);
+
+        // A quarter of the way to the placeholder is three quarters back
+        pause.set_paused(false);
+        let mut frame = solid_frame(2, 2, [200, 200, 200, 255]);
+        pause.apply(&mut frame, 2, 2);
+        assert_eq!(frame, solid_frame(2, 2, [200, 200, 200, 255]));
+    }
+
+    #[test]
+    fn test_placeholder_scaled_to_output() {
+        let placeholder = solid_frame(8, 6, [10, 20, 30, 255]);
+        let mut pause = CameraPause::new(0, 60).with_placeholder(placeholder, 8, 6);
+        pause.set_paused(true);
+
+        let mut frame = solid_frame(4, 2, [200, 200, 200, 255]);
+        pause.apply(&mut frame, 4, 2);
+        assert_eq!(frame, solid_frame(4, 2, [10, 20, 30, 255]));
+
+        // A new output size rescales it
+        let mut frame = solid_frame(3, 3, [200, 200, 200, 255]);
+        pause.apply(&mut frame, 3, 3);
+        assert_eq!(frame, solid_frame(3, 3, [10, 20, 30, 255]));
+    }
+}
@@ -0,0 +1,238 @@
+//! Virtual camera consumer detection
+//!
+//! Discord and browsers close and reopen the camera when it's toggled, and
+//! the first frame they get can be a stale or half-written buffer. A watcher
+//! thread follows the PipeWire registry and counts the links leaving the
+//! camera's node, so the pipeline can push a complete frame as soon as a
+//! new consumer links instead of waiting for the next captured one.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use pipewire as pw;
+use tracing::{debug, error, info};
+
+use crate::error::{NitrogenError, Result};
+
+/// Links and nodes of the PipeWire graph that belong to the camera
+///
+/// Fed from registry events; kept apart from the watcher thread so the
+/// bookkeeping can be tested without PipeWire.
+#[derive(Debug)]
+pub struct ConsumerTracker {
+    camera_name: String,
+    /// Node IDs named like the camera
+    camera_nodes: HashSet<u32>,
+    /// Output node of every link seen, by link ID
+    links: HashMap<u32, u32>,
+}
+
+impl ConsumerTracker {
+    /// Track consumers of the camera node called `camera_name`
+    pub fn new(camera_name: impl Into<String>) -> Self {
+        Self {
+            camera_name: camera_name.into(),
+            camera_nodes: HashSet::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    /// A node appeared in the registry
+    pub fn node_added(&mut self, id: u32, node_name: Option<&str>) {
+        if node_name == Some(self.camera_name.as_str()) {
+            debug!("Virtual camera node is {}", id);
+            self.camera_nodes.insert(id);
+        }
+    }
+
+    /// A link appeared; returns true if it carries the camera to a consumer
+    pub fn link_added(&mut self, id: u32, output_node: Option<u32>) -> bool {
+        let Some(node) = output_node else {
+            return false;
+        };
+        self.links.insert(id, node);
+        self.camera_nodes.contains(&node)
+    }
+
+    /// A node or link went away
+    pub fn removed(&mut self, id: u32) {
+        self.links.remove(&id);
+        if self.camera_nodes.remove(&id) {
+            self.links.retain(|_, node| *node != id);
+        }
+    }
+
+    /// Links currently reading from the camera
+    pub fn consumers(&self) -> usize {
+        self.links
+            .values()
+            .filter(|node| self.camera_nodes.contains(node))
+            .count()
+    }
+}
+
+/// State shared with the watcher thread
+#[derive(Debug, Default)]
+struct WatchState {
+    /// Consumer links added since the last [`CameraConsumerWatcher::take_connected`]
+    connected: AtomicU64,
+    /// Consumers linked right now
+    consumers: AtomicUsize,
+}
+
+/// Background watcher for new virtual camera consumers
+pub struct CameraConsumerWatcher {
+    thread: Option<std::thread::JoinHandle<()>>,
+    shutdown_tx: Option<mpsc::Sender<()>>,
+    state: Arc<WatchState>,
+}
+
+impl CameraConsumerWatcher {
+    /// Start watching the camera node named `camera_name`
+    pub fn start(camera_name: &str) -> Result<Self> {
+        let (shutdown_tx, shutdown_rx) = mpsc::channel();
+        let state = Arc::new(WatchState::default());
+        let thread_state = state.clone();
+        let tracker = ConsumerTracker::new(camera_name);
+
+        let thread = std::thread::Builder::new()
+            .name("nitrogen-camera-watch".to_string())
+            .spawn(move || {
+                if let Err(e) = run_watch_loop(tracker, shutdown_rx, thread_state) {
+                    error!("Camera consumer watcher error: {}", e);
+                }
+            })
+            .map_err(|e| {
+                NitrogenError::pipewire(format!("Failed to spawn camera watcher thread: {}", e))
+            })?;
+
+        Ok(Self {
+            thread: Some(thread),
+            shutdown_tx: Some(shutdown_tx),
+            state,
+        })
+    }
+
+    /// Check whether a consumer linked since the last call
+    pub fn take_connected(&self) -> bool {
+        self.state.connected.swap(0, Ordering::SeqCst) > 0
+    }
+
+    /// Number of applications reading the camera right now
+    pub fn consumers(&self) -> usize {
+        self.state.consumers.load(Ordering::SeqCst)
+    }
+
+    /// Stop the watcher thread
+    pub fn stop(&mut self) {
+        if let Some(tx) = self.shutdown_tx.take() {
+            let _ = tx.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            if let Err(e) = thread.join() {
+                tracing::warn!("Camera watcher thread panicked: {:?}", e);
+            }
+        }
+    }
+}
+
+impl Drop for CameraConsumerWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Run a PipeWire main loop that feeds registry events to the tracker
+fn run_watch_loop(
+    tracker: ConsumerTracker,
+    shutdown_rx: mpsc::Receiver<()>,
+    state: Arc<WatchState>,
+) -> Result<()> {
+    pw::init();
+
+    let mainloop = pw::main_loop::MainLoop::new(None)
+        .map_err(|e| NitrogenError::pipewire(format!("Failed to create main loop: {}", e)))?;
+    let context = pw::context::Context::new(&mainloop)
+        .map_err(|e| NitrogenError::pipewire(format!("Failed to create context: {}", e)))?;
+    let core = context
+        .connect(None)
+        .map_err(|e| NitrogenError::pipewire(format!("Failed to connect to PipeWire: {}", e)))?;
+    let registry = core
+        .get_registry()
+        .map_err(|e| NitrogenError::pipewire(format!("Failed to get registry: {}", e)))?;
+
+    let tracker = Rc::new(RefCell::new(tracker));
+    let added_tracker = tracker.clone();
+    let added_state = state.clone();
+    let removed_tracker = tracker.clone();
+    let removed_state = state;
+    let _registry_listener = registry
+        .add_listener_local()
+        .global(move |global| {
+            let mut tracker = added_tracker.borrow_mut();
+            let props = global.props;
+            match global.type_ {
+                pw::types::ObjectType::Node => {
+                    tracker.node_added(global.id, props.and_then(|p| p.get("node.name")));
+                }
+                pw::types::ObjectType::Link => {
+                    let output = props
+                        .and_then(|p| p.get("link.output.node"))
+                        .and_then(|node| node.parse().ok());
+                    if tracker.link_added(global.id, output) {
+                        let consumers = tracker.consumers();
+                        info!("Virtual camera consumer connected ({} linked)", consumers);
+                        added_state.consumers.store(consumers, Ordering::SeqCst);
+                        added_state.connected.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+                _ => {}
+            }
+        })
+        .global_remove(move |id| {
+            let mut tracker = removed_tracker.borrow_mut();
+            tracker.removed(id);
+            removed_state
+                .consumers
+                .store(tracker.consumers(), Ordering::SeqCst);
+        })
+        .register();
+
+    while shutdown_rx.try_recv().is_err() {
+        mainloop.loop_().iterate(Duration::from_millis(50));
+    }
+    debug!("Camera consumer watcher stopped");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_counts_camera_links() {
+        let mut tracker = ConsumerTracker::new("Nitrogen Camera");
+        tracker.node_added(40, Some("alsa_output.pci"));
+        tracker.node_added(42, Some("Nitrogen Camera"));
+
+        // Links from other nodes are not camera consumers
+        assert!(!tracker.link_added(100, Some(40)));
+        assert!(!tracker.link_added(101, None));
+        assert!(tracker.link_added(102, Some(42)));
+        assert!(tracker.link_added(103, Some(42)));
+        assert_eq!(tracker.consumers(), 2);
+
+        tracker.removed(102);
+        assert_eq!(tracker.consumers(), 1);
+
+        // The camera node going away drops its links too
+        tracker.removed(42);
+        assert_eq!(tracker.consumers(), 0);
+        assert!(!tracker.link_added(104, Some(42)));
+    }
+}
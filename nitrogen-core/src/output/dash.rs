@@ -0,0 +1,299 @@
+//! DASH output for Nitrogen
+//!
+//! Muxes encoded packets into a live MPEG-DASH manifest (`manifest.mpd`)
+//! with CMAF (fragmented MP4) segments in a directory. In low-latency mode
+//! segments are written as chunked fMP4 so LL-DASH players can start decoding
+//! a segment before it is complete.
+//!
+//! Serve the directory with any HTTP server; unlike HLS there is no built-in
+//! server.
+
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use super::FileRecorder;
+use crate::config::{AudioCodec, Codec, ColorRange};
+use crate::encode::{EncodedAudioPacket, EncodedPacket};
+use crate::error::Result;
+
+/// Manifest file name inside the output directory
+pub const DASH_MANIFEST_NAME: &str = "manifest.mpd";
+
+/// Flags for the inner MP4 muxer: fragment on keyframes, write an empty
+/// `moov` (the init segment) and address samples relative to each `moof`
+const CMAF_MOVFLAGS: &str = "frag_keyframe+empty_moov+default_base_moof";
+
+/// DASH output configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DashConfig {
+    /// Directory the manifest and segments are written to
+    pub output_dir: PathBuf,
+    /// Target segment duration in seconds
+    pub segment_duration: u32,
+    /// Number of segments kept in the manifest window
+    pub window_size: u32,
+    /// Write chunked fMP4 for LL-DASH players
+    pub low_latency: bool,
+}
+
+impl Default for DashConfig {
+    fn default() -> Self {
+        Self {
+            output_dir: std::env::temp_dir().join("nitrogen-dash"),
+            segment_duration: 2,
+            window_size: 6,
+            low_latency: false,
+        }
+    }
+}
+
+impl DashConfig {
+    /// Path of the manifest file
+    pub fn manifest_path(&self) -> PathBuf {
+        self.output_dir.join(DASH_MANIFEST_NAME)
+    }
+
+    /// Private options for ffmpeg's DASH muxer
+    ///
+    /// Like HLS, the muxer only cuts segments on video keyframes, so every
+    /// segment starts with one. The muxer also delays the init segment until
+    /// the first packet arrives, which is where the codec headers come from.
+    fn muxer_options(&self) -> Vec<(&'static str, String)> {
+        let mut options = vec![
+            ("seg_duration", self.segment_duration.max(1).to_string()),
+            ("window_size", self.window_size.max(1).to_string()),
+            ("use_template", "1".to_string()),
+            ("use_timeline", "1".to_string()),
+            ("dash_segment_type", "mp4".to_string()),
+            ("init_seg_name", "init-$RepresentationID$.m4s".to_string()),
+            (
+                "media_seg_name",
+                "chunk-$RepresentationID$-$Number%05d$.m4s".to_string(),
+            ),
+            ("format_options", format!("movflags={}", CMAF_MOVFLAGS)),
+        ];
+
+        if self.low_latency {
+            // One CMAF chunk per frame, announced in the manifest as LL-DASH
+            options.push(("streaming", "1".to_string()));
+            options.push(("ldash", "1".to_string()));
+            options.push(("frag_type", "every_frame".to_string()));
+            options.push(("target_latency", self.segment_duration.max(1).to_string()));
+        }
+
+        options
+    }
+}
+
+/// DASH output sink
+///
+/// Writes a live manifest from the encoder's packet stream. Packets before
+/// the first keyframe are dropped so the init and first media segment are
+/// decodable.
+pub struct DashOutput {
+    /// Configuration
+    config: DashConfig,
+    /// Muxer writing the manifest and segments
+    recorder: FileRecorder,
+    /// Whether the first keyframe has been written
+    started: bool,
+    /// Video packets dropped while waiting for the first keyframe
+    skipped_packets: u64,
+}
+
+impl DashOutput {
+    /// Create a new DASH output
+    pub fn new(
+        config: DashConfig,
+        codec: Codec,
+        width: u32,
+        height: u32,
+        fps: u32,
+        bitrate: u32,
+    ) -> Result<Self> {
+        std::fs::create_dir_all(&config.output_dir)?;
+
+        let mut recorder =
+            FileRecorder::new(config.manifest_path(), codec, width, height, fps, bitrate)?;
+        for (key, value) in config.muxer_options() {
+            recorder.set_muxer_option(key, value)?;
+        }
+
+        info!(
+            "DASH output configured: {:?} ({}s segments, window {}{})",
+            config.output_dir,
+            config.segment_duration,
+            config.window_size,
+            if config.low_latency { ", low latency" } else { "" }
+        );
+
+        Ok(Self {
+            config,
+            recorder,
+            started: false,
+            skipped_packets: 0,
+        })
+    }
+
+    /// Add an audio stream to the segments
+    ///
+    /// Must be called before `start()`.
+    pub fn add_audio_stream(
+        &mut self,
+        audio_codec: AudioCodec,
+        sample_rate: u32,
+        channels: u32,
+        bitrate: u32,
+    ) -> Result<()> {
+        self.recorder
+            .add_audio_stream(audio_codec, sample_rate, channels, bitrate)
+    }
+
+    /// Tag the video stream with a color range
+    ///
+    /// Must be called before `start()`.
+    pub fn set_color_range(&mut self, range: ColorRange) -> Result<()> {
+        self.recorder.set_color_range(range)
+    }
+
+    /// Check if audio is enabled
+    pub fn has_audio(&self) -> bool {
+        self.recorder.has_audio()
+    }
+
+    /// Write the manifest header
+    pub fn start(&mut self) -> Result<()> {
+        self.recorder.write_header()
+    }
+
+    /// Write an encoded video packet
+    pub fn write_video_packet(&mut self, packet: &EncodedPacket) -> Result<()> {
+        if !self.started {
+            if !packet.keyframe {
+                self.skipped_packets += 1;
+                return Ok(());
+            }
+            self.started = true;
+            debug!(
+                "DASH output starting on keyframe ({} packets skipped)",
+                self.skipped_packets
+            );
+        }
+
+        self.recorder.write_video_packet(packet)
+    }
+
+    /// Write an encoded audio packet
+    ///
+    /// Audio before the first video keyframe is dropped with the video.
+    pub fn write_audio_packet(&mut self, packet: &EncodedAudioPacket) -> Result<()> {
+        if !self.started {
+            return Ok(());
+        }
+
+        self.recorder.write_audio_packet(packet)
+    }
+
+    /// Finish the manifest (switches it from dynamic to static)
+    pub fn finalize(&mut self) -> Result<()> {
+        self.recorder.finalize()
+    }
+
+    /// Path of the manifest file
+    pub fn manifest_path(&self) -> PathBuf {
+        self.config.manifest_path()
+    }
+
+    /// Number of packets written
+    pub fn packets_written(&self) -> u64 {
+        self.recorder.packets_written()
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> &DashConfig {
+        &self.config
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option<'a>(options: &'a [(&'static str, String)], key: &str) -> Option<&'a str> {
+        options
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn test_dash_config_default() {
+        let config = DashConfig::default();
+        assert_eq!(config.segment_duration, 2);
+        assert_eq!(config.window_size, 6);
+        assert!(!config.low_latency);
+        assert!(config.manifest_path().ends_with(DASH_MANIFEST_NAME));
+    }
+
+    #[test]
+    fn test_muxer_options() {
+        let options = DashConfig::default().muxer_options();
+
+        assert_eq!(option(&options, "seg_duration"), Some("2"));
+        assert_eq!(option(&options, "window_size"), Some("6"));
+        assert_eq!(option(&options, "dash_segment_type"), Some("mp4"));
+        assert_eq!(
+            option(&options, "format_options"),
+            Some("movflags=frag_keyframe+empty_moov+default_base_moof")
+        );
+        assert!(option(&options, "init_seg_name").unwrap().starts_with("init-"));
+        assert_eq!(option(&options, "streaming"), None);
+        assert_eq!(option(&options, "ldash"), None);
+    }
+
+    #[test]
+    fn test_low_latency_muxer_options() {
+        let config = DashConfig {
+            low_latency: true,
+            segment_duration: 0,
+            ..Default::default()
+        };
+        let options = config.muxer_options();
+
+        assert_eq!(option(&options, "streaming"), Some("1"));
+        assert_eq!(option(&options, "ldash"), Some("1"));
+        assert_eq!(option(&options, "frag_type"), Some("every_frame"));
+        // A zero duration is clamped rather than handed to ffmpeg
+        assert_eq!(option(&options, "seg_duration"), Some("1"));
+    }
+
+    #[test]
+    fn test_waits_for_keyframe() {
+        let dir = std::env::temp_dir().join(format!("nitrogen-dash-test-{}", std::process::id()));
+        let config = DashConfig {
+            output_dir: dir.clone(),
+            ..Default::default()
+        };
+        let mut output = DashOutput::new(config, Codec::H264, 320, 240, 30, 1000)
+            .expect("Should create DASH output");
+
+        let delta = EncodedPacket {
+            data: vec![0, 0, 0, 1, 0x41],
+            pts: 0,
+            dts: 0,
+            keyframe: false,
+        };
+        output
+            .write_video_packet(&delta)
+            .expect("Delta frame before keyframe should be skipped");
+        assert_eq!(output.packets_written(), 0);
+        assert_eq!(output.skipped_packets, 1);
+        assert!(!output.started);
+
+        drop(output);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,29 +1,438 @@
 //! File recording output
 //!
 //! Records encoded video and audio to MP4, MKV, or other container formats.
+//! A [`SegmentConfig`] splits a recording into numbered files, and a
+//! [`ReplayBuffer`] keeps the last few seconds in memory instead and writes
+//! them out on request.
 
-use std::path::PathBuf;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
 use tokio::sync::broadcast;
 use tracing::{debug, error, info, warn};
 
-use crate::config::{AudioCodec, Codec};
+use crate::capture::outputs::{is_picker_id, output_name};
+use crate::config::{AudioCodec, Codec, ColorRange, Preset};
 use crate::encode::{EncodedAudioPacket, EncodedPacket};
 use crate::error::{NitrogenError, Result};
+use crate::formats::ffmpeg_color_range;
+use crate::shutdown::ShutdownSignal;
+use crate::types::CaptureSource;
+
+use super::sync::{shift_ts, AvSyncOffset};
+use super::timestamps::MonotonicTimestamps;
 
 use ffmpeg::codec::Id;
-use ffmpeg::format::{context::Output, output};
-use ffmpeg::Rational;
+use ffmpeg::format::{context::Output, output, output_as};
+use ffmpeg::{Dictionary, Rational, Rescale};
 use ffmpeg_next as ffmpeg;
 
+/// Container metadata written to recordings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingMetadata {
+    /// Write tags at all (creation time, source, codec and Nitrogen version)
+    pub enabled: bool,
+    /// Title tag
+    pub title: Option<String>,
+}
+
+impl Default for RecordingMetadata {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            title: None,
+        }
+    }
+}
+
+/// What a recording was made from, for its tags
+#[derive(Debug, Clone)]
+pub struct RecordingInfo {
+    /// Capture source description (e.g. "Monitor(DP-1)")
+    pub source: String,
+    /// Video codec
+    pub codec: Codec,
+    /// Output preset (e.g. "1080p60")
+    pub preset: String,
+    /// When the recording started
+    pub created: SystemTime,
+}
+
+impl RecordingMetadata {
+    /// Container tags for a recording
+    ///
+    /// MP4 only keeps the keys it maps to `udta` atoms (title, comment and
+    /// `creation_time` in `mvhd`), so source, codec and preset are folded into
+    /// the comment. Matroska stores every key as a tag and also gets them
+    /// separately.
+    pub fn tags(&self, info: &RecordingInfo, matroska: bool) -> Vec<(&'static str, String)> {
+        if !self.enabled {
+            return Vec::new();
+        }
+
+        let recorded_with = format!("Nitrogen v{}", env!("CARGO_PKG_VERSION"));
+        let mut tags = Vec::new();
+        if let Some(ref title) = self.title {
+            tags.push(("title", title.clone()));
+        }
+        tags.push(("creation_time", format_utc(info.created)));
+        tags.push((
+            "comment",
+            format!(
+                "Recorded with {} from {} ({}, {})",
+                recorded_with, info.source, info.codec, info.preset
+            ),
+        ));
+        if matroska {
+            tags.push(("source", info.source.clone()));
+            tags.push(("codec", info.codec.to_string()));
+            tags.push(("preset", info.preset.clone()));
+            tags.push(("recorded_with", recorded_with));
+        }
+        tags
+    }
+}
+
+/// Seconds since the epoch (0 before it)
+fn epoch_secs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Split seconds since the epoch into (year, month, day, seconds of the day)
+fn civil_time(secs: i64) -> (i64, i64, i64, i64) {
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day, rem)
+}
+
+/// Format a time as ISO 8601 UTC (`2024-05-01T12:00:00Z`)
+fn format_utc(time: SystemTime) -> String {
+    let (year, month, day, rem) = civil_time(epoch_secs(time));
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+/// Offset of local time from UTC at `secs`, from the system time zone
+fn local_utc_offset(secs: i64) -> i64 {
+    let time = secs as libc::time_t;
+    // SAFETY: localtime_r only reads `time` and fills in `tm`
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        0
+    } else {
+        i64::from(tm.tm_gmtoff)
+    }
+}
+
+/// Largest `{n}` tried before giving up on finding a free file name
+const MAX_TEMPLATE_COUNTER: u32 = 9999;
+
+/// Values for the placeholders of a recording path template
+///
+/// `{date}` (YYYY-MM-DD), `{time}` (HH-MM-SS), `{source}`, `{preset}` and
+/// `{n}` (a counter that skips existing files) are replaced; anything else
+/// in braces is kept as written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplateVars {
+    /// `{date}`, local time
+    pub date: String,
+    /// `{time}`, local time
+    pub time: String,
+    /// `{source}`, safe to use in a file name
+    pub source: String,
+    /// `{preset}`
+    pub preset: String,
+}
+
+impl PathTemplateVars {
+    /// Placeholders for a recording of `source` at `preset` starting now
+    pub fn now(source: &CaptureSource, preset: &Preset) -> Self {
+        let secs = epoch_secs(SystemTime::now());
+        let (year, month, day, rem) = civil_time(secs + local_utc_offset(secs));
+
+        let source = match source {
+            CaptureSource::Monitor { id } if is_picker_id(id) => "monitor".to_string(),
+            CaptureSource::Monitor { id } => output_name(id).to_string(),
+            CaptureSource::Window { id } if is_picker_id(id) => "window".to_string(),
+            CaptureSource::Window { id } => format!("window-{}", id),
+        };
+
+        Self {
+            date: format!("{:04}-{:02}-{:02}", year, month, day),
+            time: format!("{:02}-{:02}-{:02}", rem / 3600, rem / 60 % 60, rem % 60),
+            source: source
+                .chars()
+                .map(|c| if c.is_alphanumeric() || "-_.".contains(c) { c } else { '_' })
+                .collect(),
+            preset: preset.to_string(),
+        }
+    }
+}
+
+/// Fill in a path template, using `n` for `{n}`
+pub fn expand_path_template(template: &str, vars: &PathTemplateVars, n: u32) -> String {
+    template
+        .replace("{date}", &vars.date)
+        .replace("{time}", &vars.time)
+        .replace("{source}", &vars.source)
+        .replace("{preset}", &vars.preset)
+        .replace("{n}", &n.to_string())
+}
+
+/// Resolve a recording path template to a file that can be written
+///
+/// `~/` is expanded to the home directory and the parent directory is
+/// created. With `{n}` in the template the counter counts up from 1 until
+/// the file doesn't exist yet; without it an existing file is an error
+/// unless `overwrite` is set.
+pub fn resolve_record_path(
+    template: &str,
+    vars: &PathTemplateVars,
+    overwrite: bool,
+) -> Result<PathBuf> {
+    let template = match (template.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().into_owned(),
+        _ => template.to_string(),
+    };
+    let path = pick_record_path(&template, vars, overwrite, |path| path.exists())?;
+
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            NitrogenError::config(format!(
+                "Failed to create recording directory {}: {}",
+                parent.display(),
+                e
+            ))
+        })?;
+    }
+    Ok(path)
+}
+
+/// Collision handling of [`resolve_record_path`], with `exists` standing
+/// in for the file system
+fn pick_record_path(
+    template: &str,
+    vars: &PathTemplateVars,
+    overwrite: bool,
+    exists: impl Fn(&Path) -> bool,
+) -> Result<PathBuf> {
+    if !template.contains("{n}") {
+        let path = PathBuf::from(expand_path_template(template, vars, 1));
+        if exists(&path) && !overwrite {
+            return Err(NitrogenError::config(format!(
+                "{} already exists; pass --overwrite to replace it or add {{n}} to the name",
+                path.display()
+            )));
+        }
+        return Ok(path);
+    }
+
+    (1..=MAX_TEMPLATE_COUNTER)
+        .map(|n| PathBuf::from(expand_path_template(template, vars, n)))
+        .find(|path| !exists(path))
+        .ok_or_else(|| {
+            NitrogenError::config(format!(
+                "No free file name for {} after {} tries",
+                template, MAX_TEMPLATE_COUNTER
+            ))
+        })
+}
+
+/// Container of a recording, when it shouldn't follow the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerFormat {
+    /// MP4 with the index written at the end
+    Mp4,
+    /// Matroska
+    Mkv,
+    /// MP4 written as self-contained fragments, playable while recording
+    /// and after a crash
+    #[serde(rename = "fmp4")]
+    FragmentedMp4,
+    /// WebM (Matroska subset: VP8/VP9/AV1 video, Opus/Vorbis audio)
+    #[serde(rename = "webm")]
+    WebM,
+}
+
+impl ContainerFormat {
+    /// FFmpeg muxer name
+    pub fn muxer(&self) -> &'static str {
+        match self {
+            Self::Mp4 | Self::FragmentedMp4 => "mp4",
+            Self::Mkv => "matroska",
+            Self::WebM => "webm",
+        }
+    }
+
+    /// Muxer options the format needs
+    fn muxer_options(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::FragmentedMp4 => &[("movflags", "frag_keyframe+empty_moov+default_base_moof")],
+            _ => &[],
+        }
+    }
+
+    /// Format FFmpeg picks for a file extension, if it's one of these
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "mp4" | "m4v" | "mov" => Some(Self::Mp4),
+            "mkv" => Some(Self::Mkv),
+            "webm" => Some(Self::WebM),
+            _ => None,
+        }
+    }
+
+    /// Why `video` and `audio` won't mux (cleanly) into this format, if at all
+    pub fn codec_warning(&self, video: Codec, audio: Option<AudioCodec>) -> Option<String> {
+        match (self, video, audio) {
+            (Self::WebM, Codec::H264 | Codec::Hevc, _) => Some(format!(
+                "WebM only holds VP8, VP9 and AV1 video, not {}; use mkv or mp4",
+                video
+            )),
+            (Self::WebM, _, Some(AudioCodec::Aac)) => {
+                Some("WebM only holds Opus or Vorbis audio, not AAC; use --audio-codec opus".into())
+            }
+            (Self::Mp4 | Self::FragmentedMp4, Codec::Av1, _) => Some(
+                "AV1 in MP4 needs FFmpeg 4.4 or newer; use mkv if the recording fails to start"
+                    .into(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ContainerFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mp4 => write!(f, "mp4"),
+            Self::Mkv => write!(f, "mkv"),
+            Self::FragmentedMp4 => write!(f, "fmp4"),
+            Self::WebM => write!(f, "webm"),
+        }
+    }
+}
+
+impl std::str::FromStr for ContainerFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mp4" => Ok(Self::Mp4),
+            "mkv" | "matroska" => Ok(Self::Mkv),
+            "fmp4" | "fragmented-mp4" | "fragmented_mp4" => Ok(Self::FragmentedMp4),
+            "webm" => Ok(Self::WebM),
+            _ => Err(format!("Unknown container: {} (mp4, mkv, fmp4, webm)", s)),
+        }
+    }
+}
+
+/// Limits after which a segmented recording moves on to the next file
+///
+/// A segment only ends on a keyframe, so it runs over a limit by up to one
+/// GOP. With both limits set, whichever is reached first ends it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SegmentConfig {
+    /// Length of each segment (None = no time limit)
+    pub duration: Option<Duration>,
+    /// Size of each segment in bytes (None = no size limit)
+    pub max_bytes: Option<u64>,
+}
+
+impl SegmentConfig {
+    /// Whether any limit is set
+    pub fn is_enabled(&self) -> bool {
+        self.duration.is_some() || self.max_bytes.is_some()
+    }
+
+    /// Whether a segment `elapsed` long holding `bytes` should end
+    pub fn is_due(&self, elapsed: Duration, bytes: u64) -> bool {
+        self.duration.is_some_and(|limit| elapsed >= limit)
+            || self.max_bytes.is_some_and(|limit| bytes >= limit)
+    }
+}
+
+/// File name of segment `index` of a recording at `base`
+///
+/// `clip.mp4` becomes `clip-0001.mp4`, `clip-0002.mp4` and so on.
+pub fn segment_path(base: &Path, index: u32) -> PathBuf {
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match base.extension() {
+        Some(extension) => format!("{}-{:04}.{}", stem, index, extension.to_string_lossy()),
+        None => format!("{}-{:04}", stem, index),
+    };
+    base.with_file_name(name)
+}
+
+/// Video stream parameters of a recording
+#[derive(Debug, Clone, Copy)]
+struct VideoParams {
+    codec: Codec,
+    width: u32,
+    height: u32,
+    fps: u32,
+    bitrate: u32,
+}
+
+/// Audio stream parameters of a recording
+#[derive(Debug, Clone, Copy)]
+struct AudioParams {
+    codec: AudioCodec,
+    sample_rate: u32,
+    channels: u32,
+    bitrate: u32,
+}
+
+/// Rotation state of a segmented recording
+#[derive(Debug, Clone)]
+struct Segments {
+    /// When to move on to the next file
+    config: SegmentConfig,
+    /// Recording path the segment names are derived from
+    base: PathBuf,
+    /// Number of the segment being written (from 1)
+    index: u32,
+    /// Video timestamp subtracted so the segment starts at zero
+    offset: i64,
+    /// Video timestamp of the first packet in the segment
+    start_pts: Option<i64>,
+    /// Payload bytes written to the segment
+    bytes: u64,
+}
+
 /// File recorder for saving encoded video and audio to disk
 pub struct FileRecorder {
     /// Output path
     path: PathBuf,
     /// FFmpeg output context
     output: Output,
-    /// Video stream index
-    video_stream_index: usize,
+    /// Video stream index (None for audio-only recordings)
+    video_stream_index: Option<usize>,
     /// Audio stream index (if audio enabled)
     audio_stream_index: Option<usize>,
     /// Video packets received
@@ -32,14 +441,36 @@ pub struct FileRecorder {
     audio_packets_written: u64,
     /// Whether the header has been written
     header_written: bool,
+    /// Whether the trailer has been written
+    finalized: bool,
     /// Video time base
     video_time_base: Rational,
     /// Audio time base (if audio enabled)
     audio_time_base: Option<Rational>,
+    /// Color range tagged on the video stream (None = unspecified)
+    color_range: Option<ColorRange>,
+    /// Private muxer options passed when writing the header
+    muxer_options: Vec<(String, String)>,
+    /// A/V sync offset applied before muxing
+    sync_offset: AvSyncOffset,
+    /// Monotonic fixup for video timestamps
+    video_timestamps: MonotonicTimestamps,
+    /// Monotonic fixup for audio timestamps
+    audio_timestamps: MonotonicTimestamps,
+    /// Container override the output was opened with
+    container: Option<ContainerFormat>,
+    /// Video stream, kept to open the next segment
+    video: Option<VideoParams>,
+    /// Audio stream, kept to open the next segment
+    audio: Option<AudioParams>,
+    /// Container tags, kept to open the next segment
+    metadata: Vec<(String, String)>,
+    /// Segment rotation (None = a single file)
+    segments: Option<Segments>,
 }
 
 impl FileRecorder {
-    /// Create a new file recorder
+    /// Create a new file recorder, with the container picked by extension
     pub fn new(
         path: impl Into<PathBuf>,
         codec: Codec,
@@ -48,20 +479,35 @@ impl FileRecorder {
         fps: u32,
         bitrate: u32,
     ) -> Result<Self> {
-        let path = path.into();
+        Self::new_with_container(path, None, codec, width, height, fps, bitrate)
+    }
 
-        info!("Creating file recorder: {:?}", path);
+    /// Create a new file recorder writing `container` whatever the extension
+    ///
+    /// With None the container follows the file extension, as in
+    /// [`new`](Self::new).
+    pub fn new_with_container(
+        path: impl Into<PathBuf>,
+        container: Option<ContainerFormat>,
+        codec: Codec,
+        width: u32,
+        height: u32,
+        fps: u32,
+        bitrate: u32,
+    ) -> Result<Self> {
+        let mut recorder = Self::open(path.into(), container)?;
+        let output = &mut recorder.output;
 
         // Determine container format from extension
-        let extension = path
-            .extension()
-            .and_then(|e| e.to_str())
-            .unwrap_or("mp4")
-            .to_lowercase();
-
-        // Create output context
-        let mut output = output(&path)
-            .map_err(|e| NitrogenError::encoder(format!("Failed to create output file: {}", e)))?;
+        let extension = match container {
+            Some(container) => container.to_string(),
+            None => recorder
+                .path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("mp4")
+                .to_lowercase(),
+        };
 
         // Add video stream
         let codec_id = match codec {
@@ -100,26 +546,167 @@ impl FileRecorder {
             stream.set_time_base(video_time_base);
         }
 
-        let video_stream_index = output.nb_streams() as usize - 1;
+        recorder.video_stream_index = Some(output.nb_streams() as usize - 1);
+        recorder.video_time_base = video_time_base;
+        recorder.video = Some(VideoParams {
+            codec,
+            width,
+            height,
+            fps,
+            bitrate,
+        });
 
         info!(
             "File recorder configured: {} ({}x{} @ {}fps, {} kbps)",
             extension, width, height, fps, bitrate
         );
 
+        Ok(recorder)
+    }
+
+    /// Create a recorder with a single audio stream and no video
+    ///
+    /// For `.m4a`, `.opus`, `.ogg` or `.mka` files; the container must be able
+    /// to hold `audio_codec` (Ogg only takes Opus, M4A only AAC).
+    pub fn audio_only(
+        path: impl Into<PathBuf>,
+        audio_codec: AudioCodec,
+        sample_rate: u32,
+        channels: u32,
+        bitrate: u32,
+    ) -> Result<Self> {
+        let mut recorder = Self::open(path.into(), None)?;
+        recorder.add_audio_stream(audio_codec, sample_rate, channels, bitrate)?;
+        info!("Audio-only recording: {:?}", recorder.path);
+        Ok(recorder)
+    }
+
+    /// Create the output context without any streams
+    ///
+    /// FFmpeg guesses the muxer from the extension unless `container` is set.
+    fn open(path: PathBuf, container: Option<ContainerFormat>) -> Result<Self> {
+        info!("Creating file recorder: {:?}", path);
+
+        let output = match container {
+            Some(container) => output_as(&path, container.muxer()),
+            None => output(&path),
+        }
+        .map_err(|e| NitrogenError::encoder(format!("Failed to create output file: {}", e)))?;
+        let muxer_options = container
+            .map(|c| c.muxer_options())
+            .unwrap_or_default()
+            .iter()
+            .map(|&(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
         Ok(Self {
             path,
             output,
-            video_stream_index,
+            video_stream_index: None,
             audio_stream_index: None,
             video_packets_written: 0,
             audio_packets_written: 0,
             header_written: false,
-            video_time_base,
+            finalized: false,
+            video_time_base: Rational::new(1, 90000),
             audio_time_base: None,
+            color_range: None,
+            muxer_options,
+            sync_offset: AvSyncOffset::default(),
+            video_timestamps: MonotonicTimestamps::default(),
+            audio_timestamps: MonotonicTimestamps::default(),
+            container,
+            video: None,
+            audio: None,
+            metadata: Vec::new(),
+            segments: None,
         })
     }
 
+    /// Tag the video stream with a color range
+    ///
+    /// Must be called before `write_header()`. Should match the encoder's
+    /// range so players expand levels correctly.
+    pub fn set_color_range(&mut self, range: ColorRange) -> Result<()> {
+        if self.header_written {
+            return Err(NitrogenError::config(
+                "Cannot set color range after header is written",
+            ));
+        }
+
+        let stream = self
+            .video_stream_index
+            .and_then(|index| self.output.stream(index))
+            .ok_or_else(|| NitrogenError::encoder("Video stream not found"))?;
+        let codec_par = stream.parameters();
+        // SAFETY: Same rationale as video parameters above - rust-ffmpeg lacks safe setters.
+        // The stream is owned by our output context and outlives this write.
+        unsafe {
+            let ptr = codec_par.as_ptr() as *mut ffmpeg::ffi::AVCodecParameters;
+            (*ptr).color_range = ffmpeg_color_range(range).into();
+        }
+
+        debug!("Video stream tagged with {} color range", range);
+        self.color_range = Some(range);
+        Ok(())
+    }
+
+    /// Get the color range tagged on the video stream
+    pub fn color_range(&self) -> Option<ColorRange> {
+        self.color_range
+    }
+
+    /// Check if the output is a Matroska/WebM file
+    pub fn is_matroska(&self) -> bool {
+        let name = self.output.format().name();
+        name.contains("matroska") || name.contains("webm")
+    }
+
+    /// Set the container metadata (see [`RecordingMetadata::tags`])
+    ///
+    /// Must be called before `write_header()`.
+    pub fn set_metadata(&mut self, tags: &[(&str, String)]) -> Result<()> {
+        if self.header_written {
+            return Err(NitrogenError::config(
+                "Cannot set metadata after header is written",
+            ));
+        }
+
+        let mut dict = Dictionary::new();
+        for (key, value) in tags {
+            dict.set(key, value);
+        }
+        self.output.set_metadata(dict);
+        self.metadata = tags
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect();
+        debug!("Recording tagged with {} metadata entries", tags.len());
+        Ok(())
+    }
+
+    /// Share an A/V sync offset with this recorder
+    ///
+    /// The offset is read for every packet, so changes made through another
+    /// handle apply to the rest of the recording.
+    pub fn set_sync_offset(&mut self, offset: AvSyncOffset) {
+        self.sync_offset = offset;
+    }
+
+    /// Set a private option for the container muxer (e.g. `hls_time`)
+    ///
+    /// Must be called before `write_header()`.
+    pub fn set_muxer_option(&mut self, key: impl Into<String>, value: impl Into<String>) -> Result<()> {
+        if self.header_written {
+            return Err(NitrogenError::config(
+                "Cannot set muxer options after header is written",
+            ));
+        }
+
+        self.muxer_options.push((key.into(), value.into()));
+        Ok(())
+    }
+
     /// Add an audio stream to the recording
     ///
     /// Must be called before `write_header()`.
@@ -176,6 +763,17 @@ impl FileRecorder {
                     AudioCodec::Opus => ffmpeg::ffi::AVSampleFormat::AV_SAMPLE_FMT_S16 as i32,
                     AudioCodec::Copy => ffmpeg::ffi::AVSampleFormat::AV_SAMPLE_FMT_FLT as i32,
                 };
+
+                // Decoder setup for the container (MP4 esds, Ogg/Matroska
+                // OpusHead); the Ogg muxer refuses to write a header without it
+                let extradata = audio_extradata(audio_codec, sample_rate, channels);
+                let padded = extradata.len() + ffmpeg::ffi::AV_INPUT_BUFFER_PADDING_SIZE as usize;
+                let buf = ffmpeg::ffi::av_mallocz(padded) as *mut u8;
+                if !buf.is_null() {
+                    std::ptr::copy_nonoverlapping(extradata.as_ptr(), buf, extradata.len());
+                    (*ptr).extradata = buf;
+                    (*ptr).extradata_size = extradata.len() as i32;
+                }
             }
 
             stream.set_time_base(audio_time_base);
@@ -183,6 +781,12 @@ impl FileRecorder {
 
         self.audio_stream_index = Some(self.output.nb_streams() as usize - 1);
         self.audio_time_base = Some(audio_time_base);
+        self.audio = Some(AudioParams {
+            codec: audio_codec,
+            sample_rate,
+            channels,
+            bitrate,
+        });
 
         info!(
             "Audio stream added: {:?} {}ch @ {}Hz, {}kbps",
@@ -192,20 +796,152 @@ impl FileRecorder {
         Ok(())
     }
 
+    /// Split the recording into numbered segments
+    ///
+    /// Must be called before `write_header()`, after the streams and tags
+    /// are set up. The recording is written to `<stem>-0001.<ext>` next to
+    /// its path instead, and moves on to the next number at the first
+    /// keyframe after a limit is reached. Each segment is a complete file
+    /// with timestamps starting at zero.
+    pub fn set_segments(&mut self, config: SegmentConfig) -> Result<()> {
+        if self.header_written {
+            return Err(NitrogenError::config(
+                "Cannot split the recording after header is written",
+            ));
+        }
+        if !self.has_video() {
+            return Err(NitrogenError::config(
+                "Audio-only recordings cannot be split into segments",
+            ));
+        }
+        if !config.is_enabled() {
+            self.segments = None;
+            return Ok(());
+        }
+
+        let base = self.path.clone();
+        self.reopen(segment_path(&base, 1))?;
+        // Opening the output created an empty file at the unsplit path
+        let _ = std::fs::remove_file(&base);
+        self.segments = Some(Segments {
+            config,
+            base,
+            index: 1,
+            offset: 0,
+            start_pts: None,
+            bytes: 0,
+        });
+        Ok(())
+    }
+
+    /// Number of the segment being written (None = not segmented)
+    pub fn segment_index(&self) -> Option<u32> {
+        self.segments.as_ref().map(|segments| segments.index)
+    }
+
+    /// Swap the output for a new file at `path` with the same streams
+    ///
+    /// Packet counts and segment state carry over; the old output is
+    /// dropped, so finalize it first if its header was written.
+    fn reopen(&mut self, path: PathBuf) -> Result<()> {
+        let mut next = match self.video {
+            Some(video) => Self::new_with_container(
+                path,
+                self.container,
+                video.codec,
+                video.width,
+                video.height,
+                video.fps,
+                video.bitrate,
+            )?,
+            None => Self::open(path, self.container)?,
+        };
+        if let Some(audio) = self.audio {
+            next.add_audio_stream(audio.codec, audio.sample_rate, audio.channels, audio.bitrate)?;
+        }
+        if let Some(range) = self.color_range {
+            next.set_color_range(range)?;
+        }
+        if !self.metadata.is_empty() {
+            let tags: Vec<(&str, String)> = self
+                .metadata
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.clone()))
+                .collect();
+            next.set_metadata(&tags)?;
+        }
+        next.muxer_options = self.muxer_options.clone();
+        next.sync_offset = self.sync_offset.clone();
+
+        std::mem::swap(self, &mut next);
+        // `next` now holds the old output
+        self.video_packets_written = next.video_packets_written;
+        self.audio_packets_written = next.audio_packets_written;
+        self.segments = next.segments.take();
+        Ok(())
+    }
+
+    /// Whether `packet` should open the next segment
+    fn segment_due(&self, packet: &EncodedPacket) -> bool {
+        let Some(segments) = &self.segments else {
+            return false;
+        };
+        let Some(start_pts) = segments.start_pts.filter(|_| packet.keyframe) else {
+            return false;
+        };
+        let elapsed = (packet.pts - start_pts)
+            .max(0)
+            .rescale(self.video_time_base, Rational::new(1, 1_000_000_000));
+        segments.config.is_due(Duration::from_nanos(elapsed as u64), segments.bytes)
+    }
+
+    /// Finish the current segment and open the next one at `pts`
+    fn next_segment(&mut self, pts: i64) -> Result<()> {
+        self.finalize()?;
+        let Some(segments) = self.segments.as_mut() else {
+            return Ok(());
+        };
+        segments.index += 1;
+        segments.offset = pts;
+        segments.start_pts = None;
+        segments.bytes = 0;
+        let path = segment_path(&segments.base, segments.index);
+        info!("Starting recording segment {:?}", path);
+
+        self.reopen(path)?;
+        self.write_header()
+    }
+
     /// Check if audio is enabled
     pub fn has_audio(&self) -> bool {
         self.audio_stream_index.is_some()
     }
 
+    /// Check if the recording has a video stream
+    pub fn has_video(&self) -> bool {
+        self.video_stream_index.is_some()
+    }
+
     /// Write header to file (must be called before writing packets)
     pub fn write_header(&mut self) -> Result<()> {
         if self.header_written {
             return Ok(());
         }
 
-        self.output
-            .write_header()
-            .map_err(|e| NitrogenError::encoder(format!("Failed to write file header: {}", e)))?;
+        let written = if self.muxer_options.is_empty() {
+            self.output.write_header()
+        } else {
+            let mut options = ffmpeg::Dictionary::new();
+            for (key, value) in &self.muxer_options {
+                options.set(key, value);
+            }
+            self.output.write_header_with(options).map(|unused| {
+                for (key, _) in unused.iter() {
+                    warn!("Muxer ignored option '{}'", key);
+                }
+            })
+        };
+        written.map_err(|e| NitrogenError::encoder(format!("Failed to write file header: {}", e)))?;
 
         self.header_written = true;
         debug!("File header written");
@@ -214,14 +950,39 @@ impl FileRecorder {
 
     /// Write an encoded video packet to the file
     pub fn write_video_packet(&mut self, packet: &EncodedPacket) -> Result<()> {
+        let video_stream_index = self.video_stream_index.ok_or_else(|| {
+            NitrogenError::config("Cannot write video packet - audio-only recording")
+        })?;
+        if self.finalized {
+            return Err(NitrogenError::encoder("Recording is already finalized"));
+        }
+
         if !self.header_written {
             self.write_header()?;
         }
 
+        if self.segment_due(packet) {
+            self.next_segment(packet.pts)?;
+        }
+        let offset = match self.segments.as_mut() {
+            Some(segments) => {
+                segments.start_pts.get_or_insert(packet.pts);
+                segments.bytes += packet.data.len() as u64;
+                segments.offset
+            }
+            None => 0,
+        };
+
         let mut pkt = ffmpeg::Packet::copy(&packet.data);
-        pkt.set_stream(self.video_stream_index);
-        pkt.set_pts(Some(packet.pts));
-        pkt.set_dts(Some(packet.dts));
+        pkt.set_stream(video_stream_index);
+        let delay = self.sync_offset.video_delay(self.video_time_base);
+        let (pts, dts) = self.video_timestamps.fix(
+            shift_ts(packet.pts - offset, delay),
+            shift_ts(packet.dts - offset, delay),
+            "video",
+        );
+        pkt.set_pts(Some(pts));
+        pkt.set_dts(Some(dts));
 
         if packet.keyframe {
             pkt.set_flags(ffmpeg::packet::Flags::KEY);
@@ -230,7 +991,7 @@ impl FileRecorder {
         // Rescale timestamps to output stream timebase
         let time_base = self
             .output
-            .stream(self.video_stream_index)
+            .stream(video_stream_index)
             .map(|s| s.time_base())
             .unwrap_or(Rational::new(1, 90000));
 
@@ -256,19 +1017,38 @@ impl FileRecorder {
         let audio_stream_index = self.audio_stream_index.ok_or_else(|| {
             NitrogenError::config("Cannot write audio packet - no audio stream configured")
         })?;
+        if self.finalized {
+            return Err(NitrogenError::encoder("Recording is already finalized"));
+        }
 
         if !self.header_written {
             self.write_header()?;
         }
 
+        let input_time_base = self.audio_time_base.unwrap_or(Rational::new(1, 48000));
+        // Audio captured just before a segment's first keyframe lands slightly
+        // below zero in the new segment rather than being dropped
+        let offset = match self.segments.as_mut() {
+            Some(segments) => {
+                segments.bytes += packet.data.len() as u64;
+                segments.offset.rescale(self.video_time_base, input_time_base)
+            }
+            None => 0,
+        };
+
         let mut pkt = ffmpeg::Packet::copy(&packet.data);
         pkt.set_stream(audio_stream_index);
-        pkt.set_pts(Some(packet.pts));
-        pkt.set_dts(Some(packet.dts));
+        let delay = self.sync_offset.audio_delay(input_time_base);
+        let (pts, dts) = self.audio_timestamps.fix(
+            shift_ts(packet.pts - offset, delay),
+            shift_ts(packet.dts - offset, delay),
+            "audio",
+        );
+        pkt.set_pts(Some(pts));
+        pkt.set_dts(Some(dts));
         pkt.set_duration(packet.duration);
 
         // Rescale timestamps to output stream timebase
-        let input_time_base = self.audio_time_base.unwrap_or(Rational::new(1, 48000));
         let output_time_base = self
             .output
             .stream(audio_stream_index)
@@ -298,7 +1078,15 @@ impl FileRecorder {
     }
 
     /// Finalize the file (write trailer)
+    ///
+    /// Only the first call writes the trailer; later calls (and `Drop`) are
+    /// no-ops.
     pub fn finalize(&mut self) -> Result<()> {
+        if self.finalized {
+            return Ok(());
+        }
+        self.finalized = true;
+
         if !self.header_written {
             warn!("Finalizing file without writing header");
             return Ok(());
@@ -313,6 +1101,12 @@ impl FileRecorder {
             "File recording complete: {:?} ({} video + {} audio = {} packets)",
             self.path, self.video_packets_written, self.audio_packets_written, total_packets
         );
+        if self.timestamps_corrected() > 0 {
+            warn!(
+                "{} packets had non-monotonic timestamps and were corrected",
+                self.timestamps_corrected()
+            );
+        }
 
         Ok(())
     }
@@ -322,6 +1116,11 @@ impl FileRecorder {
         &self.path
     }
 
+    /// Number of packets whose timestamps had to be made monotonic
+    pub fn timestamps_corrected(&self) -> u64 {
+        self.video_timestamps.corrected() + self.audio_timestamps.corrected()
+    }
+
     /// Get the number of video packets written
     pub fn video_packets_written(&self) -> u64 {
         self.video_packets_written
@@ -340,7 +1139,7 @@ impl FileRecorder {
 
 impl Drop for FileRecorder {
     fn drop(&mut self) {
-        if self.header_written {
+        if self.header_written && !self.finalized {
             if let Err(e) = self.output.write_trailer() {
                 error!("Failed to write file trailer on drop: {}", e);
             }
@@ -348,6 +1147,285 @@ impl Drop for FileRecorder {
     }
 }
 
+/// Where replays go when no path is given (a recording path template)
+pub const DEFAULT_REPLAY_PATH: &str = "~/Videos/nitrogen-replay-{date}_{time}-{n}.mp4";
+
+/// A replay written to disk by [`ReplayBuffer::save_replay`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReplayInfo {
+    /// File the replay was written to
+    pub path: PathBuf,
+    /// Length of the saved video in milliseconds
+    pub duration_ms: u64,
+    /// Video packets written
+    pub video_packets: u64,
+    /// Audio packets written
+    pub audio_packets: u64,
+}
+
+/// Rolling in-memory copy of the last few seconds of encoded output
+///
+/// Video is kept in whole GOPs: the oldest one is dropped only once the next
+/// keyframe is old enough, so the buffer always starts on an IDR and holds
+/// at least the configured window once it has filled. Audio older than the
+/// first buffered video frame is trimmed so a saved replay starts in sync.
+/// Packets are shared with the other outputs, so cloning the buffer to save
+/// it is cheap.
+#[derive(Clone)]
+pub struct ReplayBuffer {
+    /// How much video to keep
+    window: Duration,
+    /// Container of saved replays (None = by extension)
+    container: Option<ContainerFormat>,
+    /// Video codec
+    codec: Codec,
+    /// Video width
+    width: u32,
+    /// Video height
+    height: u32,
+    /// Frame rate, also the video time base (1/fps)
+    fps: u32,
+    /// Video bitrate in kbps
+    bitrate: u32,
+    /// Audio stream, if audio is buffered
+    audio: Option<AudioParams>,
+    /// Color range to tag saved replays with
+    color_range: Option<ColorRange>,
+    /// A/V sync offset applied when saving
+    sync_offset: AvSyncOffset,
+    /// Buffered video, starting on a keyframe
+    video_packets: VecDeque<Arc<EncodedPacket>>,
+    /// Buffered audio
+    audio_packets: VecDeque<Arc<EncodedAudioPacket>>,
+}
+
+impl ReplayBuffer {
+    /// Keep the last `window` of video encoded with these parameters
+    pub fn new(
+        window: Duration,
+        codec: Codec,
+        width: u32,
+        height: u32,
+        fps: u32,
+        bitrate: u32,
+    ) -> Self {
+        Self {
+            window,
+            container: None,
+            codec,
+            width,
+            height,
+            fps: fps.max(1),
+            bitrate,
+            audio: None,
+            color_range: None,
+            sync_offset: AvSyncOffset::default(),
+            video_packets: VecDeque::new(),
+            audio_packets: VecDeque::new(),
+        }
+    }
+
+    /// Buffer an audio stream too
+    pub fn with_audio(
+        mut self,
+        codec: AudioCodec,
+        sample_rate: u32,
+        channels: u32,
+        bitrate: u32,
+    ) -> Self {
+        self.audio = Some(AudioParams {
+            codec,
+            sample_rate: sample_rate.max(1),
+            channels,
+            bitrate,
+        });
+        self
+    }
+
+    /// Write replays as `container` whatever the file extension
+    pub fn with_container(mut self, container: Option<ContainerFormat>) -> Self {
+        self.container = container;
+        self
+    }
+
+    /// Tag saved replays with a color range
+    pub fn with_color_range(mut self, range: ColorRange) -> Self {
+        self.color_range = Some(range);
+        self
+    }
+
+    /// Share the A/V sync offset of the other outputs
+    pub fn with_sync_offset(mut self, offset: AvSyncOffset) -> Self {
+        self.sync_offset = offset;
+        self
+    }
+
+    /// Add an encoded video packet, dropping GOPs that fell out of the window
+    ///
+    /// Packets before the first keyframe are ignored.
+    pub fn push_video(&mut self, packet: Arc<EncodedPacket>) {
+        if self.video_packets.is_empty() && !packet.keyframe {
+            return;
+        }
+        // Earliest frame time inside the window
+        let cutoff = self.video_ns(packet.pts + 1) - self.window.as_nanos() as i64;
+        self.video_packets.push_back(packet);
+
+        // Drop the oldest GOP while the next one still covers the window
+        while let Some(next) = self.video_packets.iter().skip(1).position(|p| p.keyframe) {
+            let next = next + 1;
+            if self.video_ns(self.video_packets[next].pts) > cutoff {
+                break;
+            }
+            self.video_packets.drain(..next);
+        }
+        self.trim_audio();
+    }
+
+    /// Add an encoded audio packet
+    ///
+    /// Ignored unless the buffer was set up [`with_audio`](Self::with_audio).
+    pub fn push_audio(&mut self, packet: Arc<EncodedAudioPacket>) {
+        if self.audio.is_some() {
+            self.audio_packets.push_back(packet);
+            self.trim_audio();
+        }
+    }
+
+    /// Drop audio from before the first buffered video frame
+    ///
+    /// Until the first keyframe arrives, audio is kept for the window.
+    fn trim_audio(&mut self) {
+        let start = match (self.video_packets.front(), self.audio_packets.back()) {
+            (Some(first), _) => self.video_ns(first.pts),
+            (None, Some(last)) => self.audio_ns(last.pts) - self.window.as_nanos() as i64,
+            (None, None) => return,
+        };
+        while self
+            .audio_packets
+            .front()
+            .is_some_and(|packet| self.audio_ns(packet.pts) < start)
+        {
+            self.audio_packets.pop_front();
+        }
+    }
+
+    /// Video timestamp (1/fps) in nanoseconds
+    fn video_ns(&self, ts: i64) -> i64 {
+        ts.saturating_mul(1_000_000_000) / self.fps as i64
+    }
+
+    /// Audio timestamp (1/sample rate) in nanoseconds
+    fn audio_ns(&self, ts: i64) -> i64 {
+        let rate = self.audio.map_or(48000, |audio| audio.sample_rate);
+        ts.saturating_mul(1_000_000_000) / rate as i64
+    }
+
+    /// Length of the buffered video
+    pub fn duration(&self) -> Duration {
+        match (self.video_packets.front(), self.video_packets.back()) {
+            (Some(first), Some(last)) => {
+                let frames = (last.pts - first.pts).max(0) as u64 + 1;
+                Duration::from_nanos(frames * 1_000_000_000 / self.fps as u64)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Number of buffered video packets
+    pub fn video_packets(&self) -> usize {
+        self.video_packets.len()
+    }
+
+    /// Number of buffered audio packets
+    pub fn audio_packets(&self) -> usize {
+        self.audio_packets.len()
+    }
+
+    /// Timestamp of the first buffered video packet (always a keyframe)
+    pub fn start_pts(&self) -> Option<i64> {
+        self.video_packets.front().map(|packet| packet.pts)
+    }
+
+    /// Write the buffered packets to a new file at `path`
+    ///
+    /// The replay starts at the first buffered keyframe, with timestamps
+    /// rebased to zero and audio cut to the same start. Fails if no keyframe
+    /// has been buffered yet.
+    pub fn save_replay(&self, path: impl Into<PathBuf>) -> Result<ReplayInfo> {
+        let start_pts = self
+            .video_packets
+            .front()
+            .map(|packet| packet.pts)
+            .ok_or_else(|| NitrogenError::config("Replay buffer has no keyframe yet"))?;
+
+        let mut recorder = FileRecorder::new_with_container(
+            path,
+            self.container,
+            self.codec,
+            self.width,
+            self.height,
+            self.fps,
+            self.bitrate,
+        )?;
+        if let Some(range) = self.color_range {
+            recorder.set_color_range(range)?;
+        }
+        recorder.set_sync_offset(self.sync_offset.clone());
+        let audio_start = match self.audio {
+            Some(audio) => {
+                recorder.add_audio_stream(
+                    audio.codec,
+                    audio.sample_rate,
+                    audio.channels,
+                    audio.bitrate,
+                )?;
+                start_pts.saturating_mul(audio.sample_rate as i64) / self.fps as i64
+            }
+            None => 0,
+        };
+        recorder.write_header()?;
+
+        // Interleave by time so the muxer doesn't queue a whole stream
+        let mut audio = self.audio_packets.iter().peekable();
+        for packet in &self.video_packets {
+            let video_time = self.video_ns(packet.dts);
+            while let Some(next) = audio.next_if(|a| self.audio_ns(a.pts) <= video_time) {
+                recorder.write_audio_packet(&EncodedAudioPacket {
+                    pts: next.pts - audio_start,
+                    dts: next.dts - audio_start,
+                    ..(**next).clone()
+                })?;
+            }
+            recorder.write_video_packet(&EncodedPacket {
+                pts: packet.pts - start_pts,
+                dts: packet.dts - start_pts,
+                ..(**packet).clone()
+            })?;
+        }
+        for next in audio {
+            recorder.write_audio_packet(&EncodedAudioPacket {
+                pts: next.pts - audio_start,
+                dts: next.dts - audio_start,
+                ..(**next).clone()
+            })?;
+        }
+        recorder.finalize()?;
+
+        info!(
+            "Saved {:.1}s replay to {:?}",
+            self.duration().as_secs_f64(),
+            recorder.path()
+        );
+        Ok(ReplayInfo {
+            path: recorder.path().clone(),
+            duration_ms: self.duration().as_millis() as u64,
+            video_packets: recorder.video_packets_written(),
+            audio_packets: recorder.audio_packets_written(),
+        })
+    }
+}
+
 /// Async task to record video packets from a broadcast channel
 pub async fn record_from_channel(
     mut recorder: FileRecorder,
@@ -377,13 +1455,86 @@ pub async fn record_from_channel(
     Ok(recorder.packets_written())
 }
 
+/// Async task to record an audio-only file from a broadcast channel
+///
+/// Finalizes the file once the channel closes or `shutdown` fires.
+pub async fn record_audio_from_channel(
+    mut recorder: FileRecorder,
+    mut rx: broadcast::Receiver<Arc<EncodedAudioPacket>>,
+    mut shutdown: ShutdownSignal,
+) -> Result<u64> {
+    recorder.write_header()?;
+
+    loop {
+        tokio::select! {
+            biased;
+
+            result = rx.recv() => match result {
+                Ok(packet) => {
+                    if let Err(e) = recorder.write_audio_packet(&packet) {
+                        error!("Failed to write audio packet: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Closed) => {
+                    info!("Audio encoder channel closed, finalizing recording");
+                    break;
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Recording dropped {} audio frames due to lag", n);
+                }
+            },
+
+            _ = shutdown.wait() => {
+                info!("Shutdown requested, finalizing recording");
+                break;
+            }
+        }
+    }
+
+    recorder.finalize()?;
+    Ok(recorder.packets_written())
+}
+
+/// Codec-private data muxers expect for an audio stream
+///
+/// AAC gets a 2-byte AudioSpecificConfig (LC profile), Opus a 19-byte
+/// OpusHead with libopus' 312-sample pre-skip.
+fn audio_extradata(codec: AudioCodec, sample_rate: u32, channels: u32) -> Vec<u8> {
+    match codec {
+        AudioCodec::Aac => {
+            const RATES: [u32; 13] = [
+                96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025,
+                8000, 7350,
+            ];
+            let index = RATES.iter().position(|&r| r == sample_rate).unwrap_or(3) as u16;
+            let config = (2 << 11) | (index << 7) | ((channels as u16 & 0xf) << 3);
+            config.to_be_bytes().to_vec()
+        }
+        AudioCodec::Opus => {
+            let mut head = b"OpusHead".to_vec();
+            head.push(1); // version
+            head.push(channels as u8);
+            head.extend_from_slice(&312u16.to_le_bytes()); // pre-skip
+            head.extend_from_slice(&sample_rate.to_le_bytes());
+            head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+            head.push(0); // channel mapping family (mono/stereo)
+            head
+        }
+        AudioCodec::Copy => Vec::new(),
+    }
+}
+
 /// Async task to record both video and audio packets from broadcast channels
 ///
 /// This function uses tokio::select! to interleave video and audio packet writing.
+/// It finalizes the file once both channels close or `shutdown` fires. A
+/// segmented recorder switches files between two packets, so rotation
+/// needs nothing from this task.
 pub async fn record_av_from_channels(
     mut recorder: FileRecorder,
     mut video_rx: broadcast::Receiver<Arc<EncodedPacket>>,
     mut audio_rx: Option<broadcast::Receiver<Arc<EncodedAudioPacket>>>,
+    mut shutdown: ShutdownSignal,
 ) -> Result<u64> {
     use std::sync::atomic::{AtomicBool, Ordering};
 
@@ -441,6 +1592,12 @@ pub async fn record_av_from_channels(
                     }
                 }
             }
+
+            // Buffered packets win the biased select, so nothing is lost here
+            _ = shutdown.wait() => {
+                info!("Shutdown requested, finalizing recording");
+                break;
+            }
         }
     }
 
@@ -448,6 +1605,58 @@ pub async fn record_av_from_channels(
     Ok(recorder.packets_written())
 }
 
+/// Async task to keep a replay buffer filled from broadcast channels
+///
+/// Runs until the video channel closes or `shutdown` fires and returns the
+/// number of packets buffered. The buffer is shared so a replay can be saved
+/// at any time without stopping the task.
+pub async fn buffer_replay_from_channels(
+    buffer: Arc<parking_lot::Mutex<ReplayBuffer>>,
+    mut video_rx: broadcast::Receiver<Arc<EncodedPacket>>,
+    mut audio_rx: Option<broadcast::Receiver<Arc<EncodedAudioPacket>>>,
+    mut shutdown: ShutdownSignal,
+) -> Result<u64> {
+    let mut packets = 0u64;
+
+    loop {
+        tokio::select! {
+            biased;
+
+            video_result = video_rx.recv() => match video_result {
+                Ok(packet) => {
+                    buffer.lock().push_video(packet);
+                    packets += 1;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Replay buffer dropped {} video frames due to lag", n);
+                }
+            },
+
+            audio_result = async {
+                match audio_rx.as_mut() {
+                    Some(rx) => rx.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => match audio_result {
+                Ok(packet) => {
+                    buffer.lock().push_audio(packet);
+                    packets += 1;
+                }
+                Err(broadcast::error::RecvError::Closed) => audio_rx = None,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Replay buffer dropped {} audio frames due to lag", n);
+                }
+            },
+
+            _ = shutdown.wait() => break,
+        }
+    }
+
+    debug!("Replay buffer stopped after {} packets", packets);
+    Ok(packets)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -463,4 +1672,439 @@ mod tests {
         let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("mp4");
         assert_eq!(ext, "mkv");
     }
+
+    fn test_info() -> RecordingInfo {
+        RecordingInfo {
+            source: "Monitor(DP-1)".to_string(),
+            codec: Codec::Hevc,
+            preset: "1440p60".to_string(),
+            created: UNIX_EPOCH + std::time::Duration::from_secs(1_714_564_800),
+        }
+    }
+
+    fn template_vars() -> PathTemplateVars {
+        PathTemplateVars {
+            date: "2024-05-01".to_string(),
+            time: "12-30-00".to_string(),
+            source: "DP-1".to_string(),
+            preset: "1080p60".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_expand_path_template() {
+        let vars = template_vars();
+        assert_eq!(
+            expand_path_template("/videos/{source}-{date}_{time}-{preset}-{n}.mkv", &vars, 3),
+            "/videos/DP-1-2024-05-01_12-30-00-1080p60-3.mkv"
+        );
+        // Unknown placeholders are left alone
+        assert_eq!(expand_path_template("{title}.mp4", &vars, 1), "{title}.mp4");
+    }
+
+    #[test]
+    fn test_record_path_collisions() {
+        let vars = template_vars();
+        let taken = ["/v/rec-1.mp4", "/v/rec-2.mp4", "/v/fixed.mp4"];
+        let exists = |path: &Path| taken.iter().any(|t| Path::new(t) == path);
+
+        // {n} skips the files that are already there
+        let path = pick_record_path("/v/rec-{n}.mp4", &vars, false, exists).unwrap();
+        assert_eq!(path, PathBuf::from("/v/rec-3.mp4"));
+
+        // Without {n}, an existing file needs --overwrite
+        let err = pick_record_path("/v/fixed.mp4", &vars, false, exists).unwrap_err();
+        assert!(err.to_string().contains("--overwrite"), "{}", err);
+        let path = pick_record_path("/v/fixed.mp4", &vars, true, exists).unwrap();
+        assert_eq!(path, PathBuf::from("/v/fixed.mp4"));
+        assert!(pick_record_path("/v/new.mp4", &vars, false, exists).is_ok());
+    }
+
+    #[test]
+    fn test_template_vars_source_names() {
+        let source =
+            |source: CaptureSource| PathTemplateVars::now(&source, &Preset::P1080_60).source;
+        assert_eq!(source(CaptureSource::monitor("monitor:DP-2")), "DP-2");
+        assert_eq!(source(CaptureSource::monitor("portal")), "monitor");
+        assert_eq!(source(CaptureSource::window("a/b")), "window-a_b");
+
+        let vars = PathTemplateVars::now(&CaptureSource::monitor("DP-2"), &Preset::P1080_60);
+        assert_eq!(vars.preset, "1080p60");
+        assert_eq!(vars.date.len(), "2024-05-01".len());
+        assert_eq!(vars.time.len(), "12-30-00".len());
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_utc(UNIX_EPOCH + std::time::Duration::from_secs(1_709_210_096)),
+            "2024-02-29T12:34:56Z"
+        );
+    }
+
+    #[test]
+    fn test_metadata_tags() {
+        let metadata = RecordingMetadata {
+            enabled: true,
+            title: Some("Raid night".to_string()),
+        };
+
+        let tags = metadata.tags(&test_info(), false);
+        assert!(tags.contains(&("title", "Raid night".to_string())));
+        assert!(tags.contains(&("creation_time", "2024-05-01T12:00:00Z".to_string())));
+        let comment = &tags.iter().find(|(k, _)| *k == "comment").unwrap().1;
+        assert!(comment.contains("Monitor(DP-1)") && comment.contains("1440p60"));
+        assert!(!tags.iter().any(|(k, _)| *k == "source"));
+
+        let tags = metadata.tags(&test_info(), true);
+        assert!(tags.contains(&("source", "Monitor(DP-1)".to_string())));
+        assert!(tags.iter().any(|(k, v)| *k == "recorded_with" && v.starts_with("Nitrogen v")));
+
+        let disabled = RecordingMetadata {
+            enabled: false,
+            ..metadata
+        };
+        assert!(disabled.tags(&test_info(), true).is_empty());
+    }
+
+    #[test]
+    fn test_metadata_set_before_header() {
+        let path = std::env::temp_dir().join(format!(
+            "nitrogen-metadata-{}.mkv",
+            std::process::id()
+        ));
+        let mut recorder = FileRecorder::new(&path, Codec::H264, 320, 240, 30, 1000)
+            .expect("Should create recorder");
+        assert!(recorder.is_matroska());
+
+        let metadata = RecordingMetadata {
+            title: Some("Test capture".to_string()),
+            ..Default::default()
+        };
+        let tags = metadata.tags(&test_info(), recorder.is_matroska());
+        recorder.set_metadata(&tags).expect("Should set metadata");
+
+        let dict = recorder.output.metadata();
+        assert_eq!(dict.get("title"), Some("Test capture"));
+        assert_eq!(dict.get("creation_time"), Some("2024-05-01T12:00:00Z"));
+        assert_eq!(dict.get("source"), Some("Monitor(DP-1)"));
+
+        drop(recorder);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_color_range_tagged_in_parameters() {
+        let path = std::env::temp_dir().join(format!(
+            "nitrogen-color-range-{}.mkv",
+            std::process::id()
+        ));
+        let mut recorder = FileRecorder::new(&path, Codec::H264, 320, 240, 30, 1000)
+            .expect("Should create recorder");
+        assert_eq!(recorder.color_range(), None);
+
+        recorder
+            .set_color_range(ColorRange::Full)
+            .expect("Should tag color range");
+        assert_eq!(recorder.color_range(), Some(ColorRange::Full));
+
+        let stream = recorder
+            .output
+            .stream(recorder.video_stream_index.unwrap())
+            .expect("Video stream should exist");
+        // SAFETY: Reading a field of the live stream's codec parameters
+        let tagged = unsafe { (*stream.parameters().as_ptr()).color_range };
+        assert_eq!(tagged, ffmpeg::ffi::AVColorRange::AVCOL_RANGE_JPEG);
+
+        drop(recorder);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_audio_extradata() {
+        // AAC LC, 48 kHz, stereo
+        assert_eq!(audio_extradata(AudioCodec::Aac, 48000, 2), vec![0x11, 0x90]);
+
+        let head = audio_extradata(AudioCodec::Opus, 48000, 2);
+        assert_eq!(head.len(), 19);
+        assert_eq!(&head[..8], b"OpusHead");
+        assert_eq!(head[9], 2);
+        assert_eq!(u32::from_le_bytes([head[12], head[13], head[14], head[15]]), 48000);
+    }
+
+    #[test]
+    fn test_audio_only_recording() {
+        let cases = [
+            ("opus", AudioCodec::Opus),
+            ("mka", AudioCodec::Opus),
+            ("m4a", AudioCodec::Aac),
+        ];
+        for (extension, codec) in cases {
+            if !crate::encode::audio_codec_available(codec) {
+                continue;
+            }
+            let path = std::env::temp_dir().join(format!(
+                "nitrogen-audio-only-{}.{}",
+                std::process::id(),
+                extension
+            ));
+            let mut recorder = FileRecorder::audio_only(&path, codec, 48000, 2, 128)
+                .expect("Should create audio-only recorder");
+            assert!(recorder.has_audio());
+            assert!(!recorder.has_video());
+
+            let video = EncodedPacket {
+                data: vec![0, 0, 0, 1, 0x65],
+                pts: 0,
+                dts: 0,
+                keyframe: true,
+            };
+            assert!(recorder.write_video_packet(&video).is_err());
+
+            // Encode a short tone so the file has real packets
+            let mut encoder = crate::encode::AudioEncoder::new(codec, 48000, 2, 128)
+                .expect("Should create audio encoder");
+            let mut rx = encoder.subscribe();
+            let samples: Vec<f32> = (0..48000 * 2)
+                .map(|i| ((i / 2) as f32 * 440.0 * std::f32::consts::TAU / 48000.0).sin() * 0.2)
+                .collect();
+            let frame = crate::types::AudioFrame::new(Default::default(), samples, 0);
+            encoder.encode(&frame).expect("Should encode");
+            encoder.flush().expect("Should flush");
+            while let Ok(packet) = rx.try_recv() {
+                recorder.write_audio_packet(&packet).expect("Should write audio packet");
+            }
+            assert!(recorder.audio_packets_written() > 0, "{}", extension);
+            recorder.finalize().expect("Should write trailer");
+            drop(recorder);
+
+            // Reading it back finds one audio stream and no video
+            let input = ffmpeg::format::input(&path).expect("Should open the recording");
+            assert_eq!(input.streams().count(), 1, "{}", extension);
+            let stream = input.streams().best(ffmpeg::media::Type::Audio);
+            assert!(stream.is_some(), "{}", extension);
+            assert!(input.streams().best(ffmpeg::media::Type::Video).is_none());
+
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+
+    #[test]
+    fn test_container_overrides_extension() {
+        let path = std::env::temp_dir().join(format!(
+            "nitrogen-container-{}.capture",
+            std::process::id()
+        ));
+        let recorder = FileRecorder::new_with_container(
+            &path,
+            Some(ContainerFormat::Mkv),
+            Codec::H264,
+            320,
+            240,
+            30,
+            1000,
+        )
+        .expect("Should create recorder");
+        assert!(recorder.is_matroska());
+        assert!(recorder.muxer_options.is_empty());
+        drop(recorder);
+
+        // An .mkv name still written as fragmented MP4
+        let path = path.with_extension("mkv");
+        let recorder = FileRecorder::new_with_container(
+            &path,
+            Some(ContainerFormat::FragmentedMp4),
+            Codec::H264,
+            320,
+            240,
+            30,
+            1000,
+        )
+        .expect("Should create recorder");
+        assert!(!recorder.is_matroska());
+        assert_eq!(
+            recorder.muxer_options,
+            [("movflags".to_string(), "frag_keyframe+empty_moov+default_base_moof".to_string())]
+        );
+
+        drop(recorder);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(path.with_extension("capture"));
+    }
+
+    #[test]
+    fn test_container_codec_warnings() {
+        use ContainerFormat::*;
+
+        assert!(WebM.codec_warning(Codec::Av1, Some(AudioCodec::Opus)).is_none());
+        assert!(WebM.codec_warning(Codec::Hevc, None).unwrap().contains("HEVC"));
+        assert!(WebM.codec_warning(Codec::Av1, Some(AudioCodec::Aac)).unwrap().contains("AAC"));
+        assert!(Mkv.codec_warning(Codec::Hevc, Some(AudioCodec::Aac)).is_none());
+        assert!(Mp4.codec_warning(Codec::H264, Some(AudioCodec::Aac)).is_none());
+        assert!(FragmentedMp4.codec_warning(Codec::Av1, None).is_some());
+
+        assert_eq!(ContainerFormat::from_extension("MKV"), Some(Mkv));
+        assert_eq!(ContainerFormat::from_extension("mov"), Some(Mp4));
+        assert_eq!(ContainerFormat::from_extension("ts"), None);
+        assert_eq!("fmp4".parse::<ContainerFormat>(), Ok(FragmentedMp4));
+        assert_eq!("Matroska".parse::<ContainerFormat>(), Ok(Mkv));
+        assert!("avi".parse::<ContainerFormat>().is_err());
+        assert_eq!(FragmentedMp4.to_string(), "fmp4");
+    }
+
+    #[test]
+    fn test_segment_limits() {
+        let base = Path::new("/videos/clip.mp4");
+        assert_eq!(segment_path(base, 1), Path::new("/videos/clip-0001.mp4"));
+        assert_eq!(segment_path(base, 12), Path::new("/videos/clip-0012.mp4"));
+        assert_eq!(segment_path(Path::new("clip"), 2), Path::new("clip-0002"));
+
+        let by_time = SegmentConfig {
+            duration: Some(Duration::from_secs(60)),
+            max_bytes: None,
+        };
+        assert!(by_time.is_enabled());
+        assert!(!by_time.is_due(Duration::from_secs(59), u64::MAX));
+        assert!(by_time.is_due(Duration::from_secs(60), 0));
+
+        let by_size = SegmentConfig {
+            max_bytes: Some(1_000_000),
+            ..by_time
+        };
+        assert!(by_size.is_due(Duration::ZERO, 1_000_000));
+        assert!(!SegmentConfig::default().is_enabled());
+    }
+
+    #[test]
+    fn test_segments_rotate_on_keyframes() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("clip.mkv");
+        let mut recorder = FileRecorder::new(&base, Codec::H264, 320, 240, 30, 1000)
+            .expect("Should create recorder");
+        recorder.add_audio_stream(AudioCodec::Opus, 48000, 2, 128).unwrap();
+        let segments = SegmentConfig {
+            duration: Some(Duration::from_millis(1500)),
+            max_bytes: None,
+        };
+        recorder.set_segments(segments).expect("Should split the recording");
+        assert!(!base.exists());
+        assert_eq!(recorder.path(), &dir.path().join("clip-0001.mkv"));
+
+        // 100 frames with a keyframe every second: the 1.5s limit is reached
+        // mid-GOP, so the second segment starts on the keyframe at frame 60
+        for pts in 0..100 {
+            recorder
+                .write_audio_packet(&EncodedAudioPacket {
+                    data: vec![0xFC; 8],
+                    pts: pts * 1600,
+                    dts: pts * 1600,
+                    duration: 1600,
+                })
+                .unwrap();
+            recorder.write_video_packet(&replay_video(pts, pts % 30 == 0)).unwrap();
+        }
+        assert_eq!(recorder.segment_index(), Some(2));
+        // Nothing is lost at the switch
+        assert_eq!(recorder.video_packets_written(), 100);
+        assert_eq!(recorder.audio_packets_written(), 100);
+        recorder.finalize().unwrap();
+        drop(recorder);
+
+        for (index, packets) in [(1, 60), (2, 40)] {
+            let path = segment_path(&base, index);
+            let mut input = ffmpeg::format::input(&path).expect("Segment should be readable");
+            let video = input.streams().best(ffmpeg::media::Type::Video).unwrap().index();
+            let video_packets: Vec<_> = input
+                .packets()
+                .filter(|(stream, _)| stream.index() == video)
+                .map(|(_, packet)| packet)
+                .collect();
+            assert_eq!(video_packets.len(), packets, "segment {}", index);
+            assert!(video_packets[0].is_key());
+            assert_eq!(video_packets[0].pts(), Some(0));
+        }
+        assert!(!segment_path(&base, 3).exists());
+
+        // Audio-only recordings have no keyframes to split on
+        let audio_path = dir.path().join("voice.mka");
+        let mut audio = FileRecorder::audio_only(&audio_path, AudioCodec::Opus, 48000, 2, 128)
+            .expect("Should create audio-only recorder");
+        assert!(audio.set_segments(segments).is_err());
+    }
+
+    fn replay_video(pts: i64, keyframe: bool) -> Arc<EncodedPacket> {
+        Arc::new(EncodedPacket {
+            data: vec![0, 0, 0, 1, if keyframe { 0x65 } else { 0x41 }],
+            pts,
+            dts: pts,
+            keyframe,
+        })
+    }
+
+    /// 2s replay buffer at 30fps fed 160 frames with a keyframe every second
+    fn filled_replay() -> ReplayBuffer {
+        let window = Duration::from_secs(2);
+        let mut buffer = ReplayBuffer::new(window, Codec::H264, 320, 240, 30, 1000)
+            .with_audio(AudioCodec::Opus, 48000, 2, 128);
+
+        // Nothing is kept until the first keyframe
+        buffer.push_video(replay_video(0, false));
+        assert_eq!(buffer.video_packets(), 0);
+
+        for pts in 1..=160 {
+            // One audio packet per frame (1600 samples at 48 kHz)
+            buffer.push_audio(Arc::new(EncodedAudioPacket {
+                data: vec![0xFC; 8],
+                pts: pts * 1600,
+                dts: pts * 1600,
+                duration: 1600,
+            }));
+            buffer.push_video(replay_video(pts, (pts - 1) % 30 == 0));
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_replay_buffer_keeps_whole_gops() {
+        let buffer = filled_replay();
+
+        // The window starts at frame 101; the GOP holding it starts at 91
+        assert_eq!(buffer.start_pts(), Some(91));
+        assert_eq!(buffer.video_packets(), 70);
+        assert_eq!(buffer.duration().as_millis(), 2333);
+        // Audio from before the first video frame is gone
+        assert_eq!(buffer.audio_packets(), 70);
+    }
+
+    #[test]
+    fn test_save_replay_starts_on_keyframe() {
+        let buffer = filled_replay();
+        let path = std::env::temp_dir().join(format!("nitrogen-replay-{}.mkv", std::process::id()));
+        let info = buffer.save_replay(&path).expect("Should save the replay");
+        assert_eq!(info.path, path);
+        assert_eq!((info.video_packets, info.audio_packets), (70, 70));
+        assert_eq!(info.duration_ms, 2333);
+
+        let mut input = ffmpeg::format::input(&path).expect("Should open the replay");
+        let video = input
+            .streams()
+            .best(ffmpeg::media::Type::Video)
+            .expect("Replay should have video")
+            .index();
+        let (_, first) = input
+            .packets()
+            .find(|(stream, _)| stream.index() == video)
+            .expect("Replay should have video packets");
+        assert!(first.is_key());
+        assert_eq!(first.pts(), Some(0));
+
+        // The buffer is left as it was, so it can be saved again
+        assert_eq!(buffer.video_packets(), 70);
+        let _ = std::fs::remove_file(&path);
+
+        let window = Duration::from_secs(2);
+        let empty = ReplayBuffer::new(window, Codec::H264, 320, 240, 30, 1000);
+        assert!(empty.save_replay(&path).is_err());
+    }
 }
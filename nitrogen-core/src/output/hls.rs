@@ -0,0 +1,495 @@
+//! HLS output for Nitrogen
+//!
+//! Muxes encoded packets into a rolling HLS playlist (`index.m3u8` plus
+//! MPEG-TS or fMP4 segments) in a directory. Works on networks where WebRTC
+//! can't get through and plays natively on phones.
+//!
+//! ## HTTP Server
+//!
+//! Includes a small built-in HTTP server for the output directory:
+//! - `GET /` - Simple HTML player page
+//! - `GET /index.m3u8` - Live playlist
+//! - `GET /{segment}` - Media segments
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use super::FileRecorder;
+use crate::config::{AudioCodec, Codec, ColorRange};
+use crate::encode::{EncodedAudioPacket, EncodedPacket};
+use crate::error::{NitrogenError, Result};
+
+/// Playlist file name inside the output directory
+pub const HLS_PLAYLIST_NAME: &str = "index.m3u8";
+
+/// HLS segment container
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HlsSegmentFormat {
+    /// MPEG transport stream segments (`.ts`, widest player support)
+    #[default]
+    Mpegts,
+    /// Fragmented MP4 segments (`.m4s`, required for AV1)
+    Fmp4,
+}
+
+impl HlsSegmentFormat {
+    /// Returns the ffmpeg `hls_segment_type` value
+    pub fn ffmpeg_value(&self) -> &'static str {
+        match self {
+            Self::Mpegts => "mpegts",
+            Self::Fmp4 => "fmp4",
+        }
+    }
+
+    /// File extension used for segments
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Mpegts => "ts",
+            Self::Fmp4 => "m4s",
+        }
+    }
+}
+
+impl std::fmt::Display for HlsSegmentFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mpegts => write!(f, "mpegts"),
+            Self::Fmp4 => write!(f, "fmp4"),
+        }
+    }
+}
+
+impl std::str::FromStr for HlsSegmentFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mpegts" | "ts" => Ok(Self::Mpegts),
+            "fmp4" | "mp4" | "cmaf" => Ok(Self::Fmp4),
+            _ => Err(format!("Unknown HLS segment format: {}", s)),
+        }
+    }
+}
+
+/// HLS output configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HlsConfig {
+    /// Directory the playlist and segments are written to
+    pub output_dir: PathBuf,
+    /// Target segment duration in seconds
+    pub segment_duration: u32,
+    /// Number of segments kept in the sliding-window playlist
+    pub playlist_length: u32,
+    /// Segment container
+    pub segment_format: HlsSegmentFormat,
+    /// Built-in HTTP server port (0 = don't serve, just write files)
+    pub port: u16,
+}
+
+impl Default for HlsConfig {
+    fn default() -> Self {
+        Self {
+            output_dir: std::env::temp_dir().join("nitrogen-hls"),
+            segment_duration: 2,
+            playlist_length: 6,
+            segment_format: HlsSegmentFormat::default(),
+            port: 8088,
+        }
+    }
+}
+
+impl HlsConfig {
+    /// Path of the playlist file
+    pub fn playlist_path(&self) -> PathBuf {
+        self.output_dir.join(HLS_PLAYLIST_NAME)
+    }
+
+    /// Private options for ffmpeg's HLS muxer
+    ///
+    /// The muxer only cuts segments on keyframes, so each segment starts
+    /// with one. Old segments are deleted as they leave the playlist.
+    fn muxer_options(&self) -> Vec<(&'static str, String)> {
+        let segment_pattern = self
+            .output_dir
+            .join(format!("segment_%05d.{}", self.segment_format.extension()));
+
+        let mut options = vec![
+            ("hls_time", self.segment_duration.max(1).to_string()),
+            ("hls_list_size", self.playlist_length.max(1).to_string()),
+            (
+                "hls_flags",
+                "delete_segments+independent_segments+temp_file".to_string(),
+            ),
+            ("hls_segment_type", self.segment_format.ffmpeg_value().to_string()),
+            (
+                "hls_segment_filename",
+                segment_pattern.to_string_lossy().into_owned(),
+            ),
+        ];
+
+        if self.segment_format == HlsSegmentFormat::Fmp4 {
+            options.push(("hls_fmp4_init_filename", "init.mp4".to_string()));
+        }
+
+        options
+    }
+}
+
+/// HLS output sink
+///
+/// Writes a rolling playlist from the encoder's packet stream. Packets before
+/// the first keyframe are dropped so the first segment is decodable.
+pub struct HlsOutput {
+    /// Configuration
+    config: HlsConfig,
+    /// Muxer writing the playlist and segments
+    recorder: FileRecorder,
+    /// Whether the first keyframe has been written
+    started: bool,
+    /// Video packets dropped while waiting for the first keyframe
+    skipped_packets: u64,
+}
+
+impl HlsOutput {
+    /// Create a new HLS output
+    pub fn new(
+        config: HlsConfig,
+        codec: Codec,
+        width: u32,
+        height: u32,
+        fps: u32,
+        bitrate: u32,
+    ) -> Result<Self> {
+        std::fs::create_dir_all(&config.output_dir)?;
+
+        let mut recorder =
+            FileRecorder::new(config.playlist_path(), codec, width, height, fps, bitrate)?;
+        for (key, value) in config.muxer_options() {
+            recorder.set_muxer_option(key, value)?;
+        }
+
+        info!(
+            "HLS output configured: {:?} ({}s {} segments, window {})",
+            config.output_dir, config.segment_duration, config.segment_format, config.playlist_length
+        );
+
+        Ok(Self {
+            config,
+            recorder,
+            started: false,
+            skipped_packets: 0,
+        })
+    }
+
+    /// Add an audio stream to the segments
+    ///
+    /// Must be called before `start()`.
+    pub fn add_audio_stream(
+        &mut self,
+        audio_codec: AudioCodec,
+        sample_rate: u32,
+        channels: u32,
+        bitrate: u32,
+    ) -> Result<()> {
+        self.recorder
+            .add_audio_stream(audio_codec, sample_rate, channels, bitrate)
+    }
+
+    /// Tag the video stream with a color range
+    ///
+    /// Must be called before `start()`.
+    pub fn set_color_range(&mut self, range: ColorRange) -> Result<()> {
+        self.recorder.set_color_range(range)
+    }
+
+    /// Check if audio is enabled
+    pub fn has_audio(&self) -> bool {
+        self.recorder.has_audio()
+    }
+
+    /// Write the playlist header
+    pub fn start(&mut self) -> Result<()> {
+        self.recorder.write_header()
+    }
+
+    /// Write an encoded video packet
+    pub fn write_video_packet(&mut self, packet: &EncodedPacket) -> Result<()> {
+        if !self.started {
+            if !packet.keyframe {
+                self.skipped_packets += 1;
+                return Ok(());
+            }
+            self.started = true;
+            debug!(
+                "HLS output starting on keyframe ({} packets skipped)",
+                self.skipped_packets
+            );
+        }
+
+        self.recorder.write_video_packet(packet)
+    }
+
+    /// Write an encoded audio packet
+    ///
+    /// Audio before the first video keyframe is dropped with the video.
+    pub fn write_audio_packet(&mut self, packet: &EncodedAudioPacket) -> Result<()> {
+        if !self.started {
+            return Ok(());
+        }
+
+        self.recorder.write_audio_packet(packet)
+    }
+
+    /// Finish the playlist (writes `#EXT-X-ENDLIST`)
+    pub fn finalize(&mut self) -> Result<()> {
+        self.recorder.finalize()
+    }
+
+    /// Path of the playlist file
+    pub fn playlist_path(&self) -> PathBuf {
+        self.config.playlist_path()
+    }
+
+    /// Number of packets written
+    pub fn packets_written(&self) -> u64 {
+        self.recorder.packets_written()
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> &HlsConfig {
+        &self.config
+    }
+}
+
+// ============================================================================
+// HLS HTTP Server
+// ============================================================================
+
+/// Start the HLS HTTP server
+///
+/// Serves the playlist and segments from `dir`:
+/// - `GET /` returns an HTML player page
+/// - `GET /index.m3u8` returns the live playlist
+/// - `GET /{segment}` returns a media segment
+pub async fn start_hls_server(dir: PathBuf, port: u16) -> Result<()> {
+    let app = Router::new()
+        .route("/", get(player_page))
+        .route("/:file", get(serve_file))
+        .with_state(Arc::new(dir));
+
+    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port));
+    info!(
+        "HLS server starting on http://localhost:{}/{}",
+        port, HLS_PLAYLIST_NAME
+    );
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| NitrogenError::Io(e).with_context("Failed to bind HLS server"))?;
+
+    axum::serve(listener, app)
+        .await
+        .map_err(|e| NitrogenError::Io(e).with_context("HLS server error"))?;
+
+    Ok(())
+}
+
+/// HTML player page
+async fn player_page() -> Html<&'static str> {
+    Html(PLAYER_HTML)
+}
+
+/// Serve a playlist or segment file from the output directory
+async fn serve_file(
+    State(dir): State<Arc<PathBuf>>,
+    UrlPath(file): UrlPath<String>,
+) -> Response {
+    let Some(content_type) = hls_content_type(&file) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    match tokio::fs::read(dir.join(&file)).await {
+        Ok(data) => (
+            [
+                (header::CONTENT_TYPE, content_type),
+                (header::CACHE_CONTROL, "no-cache"),
+                (header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+            ],
+            data,
+        )
+            .into_response(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// MIME type for a servable HLS file, or None if the name isn't one
+///
+/// Only plain file names with HLS extensions are accepted, which keeps
+/// requests inside the output directory.
+fn hls_content_type(file: &str) -> Option<&'static str> {
+    let valid_name = !file.is_empty()
+        && !file.starts_with('.')
+        && file
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+    if !valid_name {
+        return None;
+    }
+
+    match Path::new(file).extension()?.to_str()? {
+        "m3u8" => Some("application/vnd.apple.mpegurl"),
+        "ts" => Some("video/mp2t"),
+        "m4s" => Some("video/iso.segment"),
+        "mp4" => Some("video/mp4"),
+        _ => None,
+    }
+}
+
+/// Embedded HTML player page
+///
+/// Uses native HLS playback where available (Safari, iOS, Android) and
+/// falls back to hls.js elsewhere.
+const PLAYER_HTML: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Nitrogen HLS Viewer</title>
+    <style>
+        body {
+            margin: 0;
+            background: #111;
+            color: #ddd;
+            font-family: sans-serif;
+            display: flex;
+            flex-direction: column;
+            align-items: center;
+            justify-content: center;
+            min-height: 100vh;
+        }
+        video {
+            width: 100%;
+            max-width: 1280px;
+            background: #000;
+        }
+        p {
+            font-size: 0.9em;
+            color: #888;
+        }
+    </style>
+</head>
+<body>
+    <video id="video" controls autoplay muted playsinline></video>
+    <p>Nitrogen HLS stream - <a href="index.m3u8" style="color:#8af">index.m3u8</a></p>
+    <script>
+        const video = document.getElementById('video');
+        const src = 'index.m3u8';
+        if (video.canPlayType('application/vnd.apple.mpegurl')) {
+            video.src = src;
+        } else {
+            const script = document.createElement('script');
+            script.src = 'https://cdn.jsdelivr.net/npm/hls.js@1';
+            script.onload = () => {
+                const hls = new Hls({ liveSyncDurationCount: 2 });
+                hls.loadSource(src);
+                hls.attachMedia(video);
+            };
+            document.head.appendChild(script);
+        }
+    </script>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hls_config_default() {
+        let config = HlsConfig::default();
+        assert_eq!(config.segment_duration, 2);
+        assert_eq!(config.playlist_length, 6);
+        assert_eq!(config.segment_format, HlsSegmentFormat::Mpegts);
+        assert!(config.playlist_path().ends_with(HLS_PLAYLIST_NAME));
+    }
+
+    #[test]
+    fn test_muxer_options() {
+        let config = HlsConfig {
+            segment_format: HlsSegmentFormat::Fmp4,
+            ..Default::default()
+        };
+        let options = config.muxer_options();
+        let get = |key: &str| {
+            options
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| v.as_str())
+        };
+
+        assert_eq!(get("hls_time"), Some("2"));
+        assert_eq!(get("hls_list_size"), Some("6"));
+        assert_eq!(get("hls_segment_type"), Some("fmp4"));
+        assert!(get("hls_flags").unwrap().contains("delete_segments"));
+        assert!(get("hls_segment_filename").unwrap().ends_with(".m4s"));
+        assert_eq!(get("hls_fmp4_init_filename"), Some("init.mp4"));
+    }
+
+    #[test]
+    fn test_hls_content_type() {
+        assert_eq!(
+            hls_content_type("index.m3u8"),
+            Some("application/vnd.apple.mpegurl")
+        );
+        assert_eq!(hls_content_type("segment_00001.ts"), Some("video/mp2t"));
+        assert_eq!(hls_content_type("segment_00001.m4s"), Some("video/iso.segment"));
+        assert_eq!(hls_content_type("init.mp4"), Some("video/mp4"));
+
+        assert_eq!(hls_content_type("..%2Fetc%2Fpasswd"), None);
+        assert_eq!(hls_content_type(".hidden.ts"), None);
+        assert_eq!(hls_content_type("notes.txt"), None);
+        assert_eq!(hls_content_type(""), None);
+    }
+
+    #[test]
+    fn test_waits_for_keyframe() {
+        let dir = std::env::temp_dir().join(format!("nitrogen-hls-test-{}", std::process::id()));
+        let config = HlsConfig {
+            output_dir: dir.clone(),
+            port: 0,
+            ..Default::default()
+        };
+        let mut output = HlsOutput::new(config, Codec::H264, 320, 240, 30, 1000)
+            .expect("Should create HLS output");
+
+        let delta = EncodedPacket {
+            data: vec![0, 0, 0, 1, 0x41],
+            pts: 0,
+            dts: 0,
+            keyframe: false,
+        };
+        output
+            .write_video_packet(&delta)
+            .expect("Delta frame before keyframe should be skipped");
+        assert_eq!(output.packets_written(), 0);
+        assert_eq!(output.skipped_packets, 1);
+        assert!(!output.started);
+
+        drop(output);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -3,23 +3,68 @@
 //! Supports multiple output targets:
 //! - Virtual camera (via PipeWire/ghoststream)
 //! - Virtual microphone (for Discord audio passthrough)
-//! - File recording (MP4, MKV)
-//! - WebRTC streaming (browser-based viewing)
-//! - RTMP/SRT streaming (Twitch, YouTube, etc.)
+//! - File recording (MP4, MKV), and a replay buffer of the last few seconds
+//! - WebRTC streaming (browser-based viewing, `webrtc` feature)
+//! - RTMP/SRT/RIST streaming (Twitch, YouTube, relays, `streaming` feature)
+//! - HLS playlists (rolling segments over HTTP)
+//! - DASH manifests (CMAF segments, optionally low-latency)
+//! - Custom sinks via the [`FrameSink`] trait
+//!
+//! A paused camera crossfades to a privacy placeholder, see [`CameraPause`].
+//!
+//! Recordings and RTMP/SRT streams share an [`AvSyncOffset`] for lining up
+//! delayed audio.
 
+mod camera_pause;
+mod camera_watch;
+mod dash;
 mod file;
+mod hls;
+mod sink;
+#[cfg(feature = "streaming")]
+mod srt_sys;
+#[cfg(feature = "streaming")]
 mod stream;
+mod stream_config;
+mod sync;
+mod timestamps;
 mod virtual_audio;
+#[cfg(feature = "webrtc")]
 mod webrtc;
+mod webrtc_config;
 
 // Re-export ghoststream's virtual camera and traits
-pub use file::{record_av_from_channels, record_from_channel, FileRecorder};
+pub use camera_pause::{CameraPause, DEFAULT_CAMERA_TRANSITION_MS};
+pub use camera_watch::{CameraConsumerWatcher, ConsumerTracker};
+pub use dash::{DashConfig, DashOutput, DASH_MANIFEST_NAME};
+pub use file::{
+    buffer_replay_from_channels, expand_path_template, record_audio_from_channel,
+    record_av_from_channels, record_from_channel, resolve_record_path, segment_path,
+    ContainerFormat, FileRecorder, PathTemplateVars, RecordingInfo, RecordingMetadata,
+    ReplayBuffer, ReplayInfo, SegmentConfig, DEFAULT_REPLAY_PATH,
+};
 pub use ghoststream::output::{RawOutputSink, VirtualCamera};
+pub use hls::{start_hls_server, HlsConfig, HlsOutput, HlsSegmentFormat, HLS_PLAYLIST_NAME};
+pub use sink::{run_sink, FrameSink};
+#[cfg(feature = "streaming")]
 pub use stream::{
-    stream_av_from_channels, stream_from_channel, StreamConfig, StreamOutput, StreamProtocol,
+    multi_stream_av_from_channels, stream_av_from_channels, stream_from_channel, MultiStreamOutput,
+    StreamOutput,
 };
+pub use stream_config::{
+    ReconnectPolicy, RistOptions, SrtLinkStats, SrtOptions, StreamConfig, StreamProtocol,
+    StreamStats, CONGESTION_LOSS_PERCENT, STREAM_STATS_INTERVAL,
+};
+pub use sync::{AvSyncOffset, MAX_AV_SYNC_OFFSET_MS};
 pub use virtual_audio::{VirtualMicrophone, DEFAULT_VIRTUAL_MIC_NAME};
-pub use webrtc::{start_signaling_server, WebRTCConfig, WebRTCOutput};
+#[cfg(feature = "webrtc")]
+pub use webrtc::{start_signaling_server, webrtc_from_channels, WebRTCOutput};
+#[cfg(feature = "webrtc")]
+pub use webrtc_config::check_webrtc_codec;
+pub use webrtc_config::{
+    default_ice_servers, default_simulcast_layers, select_layer, IceServer, SimulcastLayer,
+    WebRTCConfig, DEFAULT_STUN_SERVER, WEBRTC_VIDEO_CODECS,
+};
 
 /// Default camera name
 pub const DEFAULT_CAMERA_NAME: &str = "Nitrogen Camera";
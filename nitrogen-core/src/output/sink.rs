@@ -0,0 +1,286 @@
+//! Pluggable output sinks
+//!
+//! [`FrameSink`] is the extension point for feeding Nitrogen's encoded output
+//! into any transport (NDI, custom QUIC, etc.). The built-in outputs
+//! ([`FileRecorder`], [`StreamOutput`], [`WebRTCOutput`], [`HlsOutput`],
+//! [`DashOutput`]) implement it too, and custom sinks can be handed to
+//! [`Pipeline::new_with_sinks`](crate::Pipeline::new_with_sinks).
+//!
+//! # Example
+//!
+//! ```
+//! use async_trait::async_trait;
+//! use nitrogen_core::encode::{EncodedAudioPacket, EncodedPacket};
+//! use nitrogen_core::output::FrameSink;
+//! use nitrogen_core::Result;
+//!
+//! /// Sink that just counts packets
+//! #[derive(Default)]
+//! struct CountingSink {
+//!     video: u64,
+//!     audio: u64,
+//! }
+//!
+//! #[async_trait]
+//! impl FrameSink for CountingSink {
+//!     fn name(&self) -> &str {
+//!         "counter"
+//!     }
+//!
+//!     async fn write_video(&mut self, _packet: &EncodedPacket) -> Result<()> {
+//!         self.video += 1;
+//!         Ok(())
+//!     }
+//!
+//!     async fn write_audio(&mut self, _packet: &EncodedAudioPacket) -> Result<()> {
+//!         self.audio += 1;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let sinks: Vec<Box<dyn FrameSink>> = vec![Box::new(CountingSink::default())];
+//! assert_eq!(sinks[0].name(), "counter");
+//! ```
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::broadcast;
+use tracing::{error, info, warn};
+
+use super::{DashOutput, FileRecorder, HlsOutput};
+#[cfg(feature = "streaming")]
+use super::StreamOutput;
+#[cfg(feature = "webrtc")]
+use super::WebRTCOutput;
+use crate::encode::{EncodedAudioPacket, EncodedPacket};
+use crate::error::Result;
+use crate::shutdown::ShutdownSignal;
+
+/// Destination for encoded video and audio packets
+///
+/// Implementations must be object-safe so the pipeline can hold a
+/// `Vec<Box<dyn FrameSink>>`.
+#[async_trait]
+pub trait FrameSink: Send {
+    /// Short name used in log messages
+    fn name(&self) -> &str;
+
+    /// Prepare the sink (open files, connect, write headers)
+    async fn start(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Write an encoded video packet
+    async fn write_video(&mut self, packet: &EncodedPacket) -> Result<()>;
+
+    /// Write an encoded audio packet
+    ///
+    /// Sinks without audio support can ignore packets (the default).
+    async fn write_audio(&mut self, _packet: &EncodedAudioPacket) -> Result<()> {
+        Ok(())
+    }
+
+    /// Finish the sink (flush, write trailers, disconnect)
+    async fn stop(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl FrameSink for FileRecorder {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        self.write_header()
+    }
+
+    async fn write_video(&mut self, packet: &EncodedPacket) -> Result<()> {
+        self.write_video_packet(packet)
+    }
+
+    async fn write_audio(&mut self, packet: &EncodedAudioPacket) -> Result<()> {
+        if self.has_audio() {
+            self.write_audio_packet(packet)
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        self.finalize()
+    }
+}
+
+#[cfg(feature = "streaming")]
+#[async_trait]
+impl FrameSink for StreamOutput {
+    fn name(&self) -> &str {
+        self.protocol().protocol_name()
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        StreamOutput::start(self)
+    }
+
+    async fn write_video(&mut self, packet: &EncodedPacket) -> Result<()> {
+        self.write_video_packet(packet)
+    }
+
+    async fn write_audio(&mut self, packet: &EncodedAudioPacket) -> Result<()> {
+        if self.has_audio() {
+            self.write_audio_packet(packet)
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        StreamOutput::stop(self)
+    }
+}
+
+#[cfg(feature = "webrtc")]
+#[async_trait]
+impl FrameSink for WebRTCOutput {
+    fn name(&self) -> &str {
+        "webrtc"
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        if !self.is_running() {
+            self.init().await?;
+        }
+        Ok(())
+    }
+
+    async fn write_video(&mut self, packet: &EncodedPacket) -> Result<()> {
+        self.write_video_packet(packet).await
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        WebRTCOutput::stop(self).await
+    }
+}
+
+#[async_trait]
+impl FrameSink for HlsOutput {
+    fn name(&self) -> &str {
+        "hls"
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        HlsOutput::start(self)
+    }
+
+    async fn write_video(&mut self, packet: &EncodedPacket) -> Result<()> {
+        self.write_video_packet(packet)
+    }
+
+    async fn write_audio(&mut self, packet: &EncodedAudioPacket) -> Result<()> {
+        if self.has_audio() {
+            self.write_audio_packet(packet)
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        self.finalize()
+    }
+}
+
+#[async_trait]
+impl FrameSink for DashOutput {
+    fn name(&self) -> &str {
+        "dash"
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        DashOutput::start(self)
+    }
+
+    async fn write_video(&mut self, packet: &EncodedPacket) -> Result<()> {
+        self.write_video_packet(packet)
+    }
+
+    async fn write_audio(&mut self, packet: &EncodedAudioPacket) -> Result<()> {
+        if self.has_audio() {
+            self.write_audio_packet(packet)
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        self.finalize()
+    }
+}
+
+/// Drive a sink from the encoder broadcast channels
+///
+/// Runs until the video channel closes (and the audio channel, if any) or
+/// `shutdown` fires. Returns the number of packets written.
+pub async fn run_sink(
+    mut sink: Box<dyn FrameSink>,
+    mut video_rx: broadcast::Receiver<Arc<EncodedPacket>>,
+    mut audio_rx: Option<broadcast::Receiver<Arc<EncodedAudioPacket>>>,
+    mut shutdown: ShutdownSignal,
+) -> Result<u64> {
+    sink.start().await?;
+    info!("Output sink '{}' started", sink.name());
+
+    let mut packets = 0u64;
+    let mut video_done = false;
+    let mut audio_done = audio_rx.is_none();
+
+    while !(video_done && audio_done) {
+        tokio::select! {
+            biased;
+
+            video_result = video_rx.recv(), if !video_done => {
+                match video_result {
+                    Ok(packet) => match sink.write_video(&packet).await {
+                        Ok(()) => packets += 1,
+                        Err(e) => error!("Sink '{}' failed to write video packet: {}", sink.name(), e),
+                    },
+                    Err(broadcast::error::RecvError::Closed) => video_done = true,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Sink '{}' dropped {} video frames due to lag", sink.name(), n);
+                    }
+                }
+            }
+
+            audio_result = async {
+                match audio_rx.as_mut() {
+                    Some(rx) => rx.recv().await,
+                    None => std::future::pending().await,
+                }
+            }, if !audio_done => {
+                match audio_result {
+                    Ok(packet) => match sink.write_audio(&packet).await {
+                        Ok(()) => packets += 1,
+                        Err(e) => error!("Sink '{}' failed to write audio packet: {}", sink.name(), e),
+                    },
+                    Err(broadcast::error::RecvError::Closed) => audio_done = true,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("Sink '{}' dropped {} audio frames due to lag", sink.name(), n);
+                    }
+                }
+            }
+
+            // Buffered packets win the biased select, so nothing is lost here
+            _ = shutdown.wait() => {
+                info!("Shutdown requested, finishing sink '{}'", sink.name());
+                break;
+            }
+        }
+    }
+
+    sink.stop().await?;
+    info!("Output sink '{}' finished: {} packets", sink.name(), packets);
+
+    Ok(packets)
+}
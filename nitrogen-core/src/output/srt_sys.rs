@@ -0,0 +1,241 @@
+//! Raw FFI access to libsrt socket statistics
+//!
+//! FFmpeg's `libsrt` protocol doesn't expose link statistics, so they are
+//! read from libsrt directly: the SRT socket id is taken from the protocol's
+//! private context and passed to `srt_bstats`. libsrt is loaded at runtime;
+//! because FFmpeg already has it mapped, `dlopen` hands back the same
+//! instance and the socket id is valid in it. FFmpeg builds with a statically
+//! linked libsrt have no shared library to find, and get no link stats.
+
+#![allow(non_snake_case)]
+#![allow(dead_code)]
+
+use std::ffi::{c_char, c_int, c_void, CStr};
+
+/// `SRTSOCKET` from srt.h
+pub type SRTSOCKET = c_int;
+
+/// `SRT_ERROR` return value
+pub const SRT_ERROR: c_int = -1;
+
+/// FFmpeg `AVClass` name of the libsrt protocol context
+pub const LIBSRT_CLASS_NAME: &[u8] = b"libsrt";
+
+/// `SRT_TRACEBSTATS` (`struct CBytePerfMon`) from srt.h
+///
+/// Only the fields up to the send buffer are read; later libsrt versions
+/// append fields, which land in the reserved tail.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SRT_TRACEBSTATS {
+    // Global measurements
+    pub msTimeStamp: i64,
+    pub pktSentTotal: i64,
+    pub pktRecvTotal: i64,
+    pub pktSndLossTotal: c_int,
+    pub pktRcvLossTotal: c_int,
+    pub pktRetransTotal: c_int,
+    pub pktSentACKTotal: c_int,
+    pub pktRecvACKTotal: c_int,
+    pub pktSentNAKTotal: c_int,
+    pub pktRecvNAKTotal: c_int,
+    pub usSndDurationTotal: i64,
+    pub pktSndDropTotal: c_int,
+    pub pktRcvDropTotal: c_int,
+    pub pktRcvUndecryptTotal: c_int,
+    pub byteSentTotal: u64,
+    pub byteRecvTotal: u64,
+    pub byteRcvLossTotal: u64,
+    pub byteRetransTotal: u64,
+    pub byteSndDropTotal: u64,
+    pub byteRcvDropTotal: u64,
+    pub byteRcvUndecryptTotal: u64,
+
+    // Local measurements (since the last cleared read)
+    pub pktSent: i64,
+    pub pktRecv: i64,
+    pub pktSndLoss: c_int,
+    pub pktRcvLoss: c_int,
+    pub pktRetrans: c_int,
+    pub pktRcvRetrans: c_int,
+    pub pktSentACK: c_int,
+    pub pktRecvACK: c_int,
+    pub pktSentNAK: c_int,
+    pub pktRecvNAK: c_int,
+    pub mbpsSendRate: f64,
+    pub mbpsRecvRate: f64,
+    pub usSndDuration: i64,
+    pub pktReorderDistance: c_int,
+    pub pktRcvAvgBelatedTime: f64,
+    pub pktRcvBelated: i64,
+    pub pktSndDrop: c_int,
+    pub pktRcvDrop: c_int,
+    pub pktRcvUndecrypt: c_int,
+    pub byteSent: u64,
+    pub byteRecv: u64,
+    pub byteRcvLoss: u64,
+    pub byteRetrans: u64,
+    pub byteSndDrop: u64,
+    pub byteRcvDrop: u64,
+    pub byteRcvUndecrypt: u64,
+
+    // Instant measurements
+    pub usPktSndPeriod: f64,
+    pub pktFlowWindow: c_int,
+    pub pktCongestionWindow: c_int,
+    pub pktFlightSize: c_int,
+    pub msRTT: f64,
+    pub mbpsBandwidth: f64,
+    pub byteAvailSndBuf: c_int,
+    pub byteAvailRcvBuf: c_int,
+    pub mbpsMaxBW: f64,
+    pub byteMSS: c_int,
+    pub pktSndBuf: c_int,
+    pub byteSndBuf: c_int,
+    pub msSndBuf: c_int,
+    pub msSndTsbPdDelay: c_int,
+
+    /// Receive-side and newer fields we don't read
+    pub _reserved: [u8; 512],
+}
+
+impl Default for SRT_TRACEBSTATS {
+    fn default() -> Self {
+        // SAFETY: the struct is plain integers and floats, all valid as zero
+        unsafe { std::mem::zeroed() }
+    }
+}
+
+/// Leading fields of FFmpeg's `URLContext` (libavformat/url.h)
+///
+/// The layout has been unchanged since the protocol API was introduced.
+#[repr(C)]
+pub struct URLContextPrefix {
+    pub av_class: *const ffmpeg_next::ffi::AVClass,
+    pub prot: *const c_void,
+    pub priv_data: *mut c_void,
+}
+
+/// Leading fields of FFmpeg's `SRTContext` (libavformat/libsrt.c)
+#[repr(C)]
+pub struct SRTContextPrefix {
+    pub class: *const ffmpeg_next::ffi::AVClass,
+    pub fd: SRTSOCKET,
+}
+
+pub type FnSrtBstats =
+    unsafe extern "C" fn(u: SRTSOCKET, perf: *mut SRT_TRACEBSTATS, clear: c_int) -> c_int;
+
+pub type FnSrtGetLastErrorStr = unsafe extern "C" fn() -> *const c_char;
+
+/// Library names to search for libsrt (the one FFmpeg links against)
+pub const SRT_LIB_PATHS: &[&str] = &[
+    "libsrt.so.1.5",
+    "libsrt-gnutls.so.1.5",
+    "libsrt-openssl.so.1.5",
+    "libsrt.so.1.4",
+    "libsrt-gnutls.so.1.4",
+    "libsrt.so.1",
+    "libsrt.so",
+];
+
+/// Dynamically loaded libsrt
+pub struct SrtLib {
+    _lib: libloading::Library,
+    pub bstats: FnSrtBstats,
+    pub getlasterror_str: FnSrtGetLastErrorStr,
+}
+
+impl SrtLib {
+    /// Try to load libsrt from standard names
+    pub fn load() -> Result<Self, String> {
+        for path in SRT_LIB_PATHS {
+            if let Ok(lib) = Self::load_from_path(path) {
+                tracing::debug!("Loaded SRT library from: {}", path);
+                return Ok(lib);
+            }
+        }
+        Err("Failed to load libsrt from any known path".to_string())
+    }
+
+    /// Load the library from a specific path
+    pub fn load_from_path(path: &str) -> Result<Self, String> {
+        // SAFETY: libsrt is a system library already loaded by FFmpeg. The
+        // signatures match srt.h, and the pointers are copied out while the
+        // library handle is kept alive in the struct.
+        unsafe {
+            let lib = libloading::Library::new(path)
+                .map_err(|e| format!("Failed to load {}: {}", path, e))?;
+
+            let bstats = *lib
+                .get::<FnSrtBstats>(b"srt_bstats\0")
+                .map_err(|e| format!("Failed to get srt_bstats: {}", e))?;
+            let getlasterror_str = *lib
+                .get::<FnSrtGetLastErrorStr>(b"srt_getlasterror_str\0")
+                .map_err(|e| format!("Failed to get srt_getlasterror_str: {}", e))?;
+
+            Ok(Self {
+                _lib: lib,
+                bstats,
+                getlasterror_str,
+            })
+        }
+    }
+
+    /// Read statistics for a socket
+    ///
+    /// With `clear`, the local (interval) counters restart after the read.
+    pub fn stats(&self, socket: SRTSOCKET, clear: bool) -> Result<SRT_TRACEBSTATS, String> {
+        let mut perf = SRT_TRACEBSTATS::default();
+        // SAFETY: perf is a valid, writable SRT_TRACEBSTATS with extra room
+        // for fields appended by newer libsrt versions
+        let status = unsafe { (self.bstats)(socket, &mut perf, c_int::from(clear)) };
+        if status == SRT_ERROR {
+            // SAFETY: srt_getlasterror_str returns a thread-local NUL-terminated string
+            let msg = unsafe { CStr::from_ptr((self.getlasterror_str)()) };
+            return Err(msg.to_string_lossy().into_owned());
+        }
+        Ok(perf)
+    }
+}
+
+// SAFETY: SrtLib only holds function pointers into libsrt, whose API is
+// thread-safe. The library handle is kept alive for the lifetime of the struct.
+unsafe impl Send for SrtLib {}
+unsafe impl Sync for SrtLib {}
+
+/// Find the SRT socket behind an output's I/O context
+///
+/// Returns None unless the context is backed by FFmpeg's libsrt protocol.
+///
+/// # Safety
+/// `ctx` must be a valid output format context whose I/O was opened by
+/// FFmpeg (`avio_open`), and must stay open while the socket is used.
+pub unsafe fn srt_socket(ctx: *const ffmpeg_next::ffi::AVFormatContext) -> Option<SRTSOCKET> {
+    // SAFETY: the caller guarantees ctx is valid; every pointer is checked
+    // before it is followed, and the protocol class is verified before the
+    // private context is read as an SRTContext.
+    unsafe {
+        let pb = (*ctx).pb;
+        if pb.is_null() || (*pb).opaque.is_null() {
+            return None;
+        }
+
+        // avio_open wraps the URLContext directly as the AVIOContext opaque
+        let url = (*pb).opaque as *const URLContextPrefix;
+        let priv_data = (*url).priv_data as *const SRTContextPrefix;
+        if priv_data.is_null() {
+            return None;
+        }
+
+        let class = (*priv_data).class;
+        if class.is_null() || (*class).class_name.is_null() {
+            return None;
+        }
+        if CStr::from_ptr((*class).class_name).to_bytes() != LIBSRT_CLASS_NAME {
+            return None;
+        }
+
+        Some((*priv_data).fd)
+    }
+}
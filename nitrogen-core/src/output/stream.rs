@@ -1,106 +1,83 @@
-//! RTMP/SRT streaming output
+//! RTMP/SRT/RIST streaming output
 //!
-//! Streams encoded video and audio to RTMP or SRT servers.
+//! Streams encoded video and audio to RTMP, SRT or RIST servers.
 //! Supports streaming to services like Twitch, YouTube, or custom servers.
+//! SRT streams also report link statistics (RTT, loss, bandwidth estimate)
+//! read from libsrt; see [`StreamStats`], and take their latency and encryption
+//! from [`SrtOptions`](super::SrtOptions). RIST goes through FFmpeg's librist
+//! protocol, with [`RistOptions`](super::RistOptions) for the buffer and encryption.
+//!
+//! When the connection drops the streaming task reconnects with backoff, see
+//! [`ReconnectPolicy`]. Video that arrives meanwhile is dropped except for
+//! the latest keyframe, which is sent first once the stream is back; after
+//! it the stream resumes at the next keyframe.
 
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
 use tokio::sync::broadcast;
+use tokio::time::MissedTickBehavior;
 use tracing::{debug, error, info, warn};
 
+use super::srt_sys::{self, SrtLib, SRTSOCKET, SRT_TRACEBSTATS};
+use super::stream_config::{
+    ReconnectPolicy, SrtLinkStats, StreamConfig, StreamProtocol, StreamStats,
+    STREAM_STATS_INTERVAL,
+};
+use super::sync::{shift_ts, AvSyncOffset};
+use super::timestamps::MonotonicTimestamps;
+
 use crate::config::{AudioCodec, Codec};
 use crate::encode::{EncodedAudioPacket, EncodedPacket};
 use crate::error::{NitrogenError, Result};
+use crate::formats::ffmpeg_color_range;
+use crate::shutdown::ShutdownSignal;
 
 use ffmpeg::codec::Id;
-use ffmpeg::format::{context::Output, output_as};
-use ffmpeg::Rational;
+use ffmpeg::format::{context::Output, output_as_with};
+use ffmpeg::{Dictionary, Rational};
 use ffmpeg_next as ffmpeg;
 
-/// Streaming protocol
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum StreamProtocol {
-    /// RTMP (Real-Time Messaging Protocol) - Twitch, YouTube, etc.
-    Rtmp,
-    /// SRT (Secure Reliable Transport) - low latency streaming
-    Srt,
-}
-
-impl StreamProtocol {
-    /// Detect protocol from URL
-    pub fn from_url(url: &str) -> Option<Self> {
-        let lower = url.to_lowercase();
-        if lower.starts_with("rtmp://") || lower.starts_with("rtmps://") {
-            Some(Self::Rtmp)
-        } else if lower.starts_with("srt://") {
-            Some(Self::Srt)
-        } else {
-            None
-        }
-    }
-
-    /// Get the FFmpeg format name
-    pub fn format_name(&self) -> &'static str {
-        match self {
-            Self::Rtmp => "flv",
-            Self::Srt => "mpegts",
-        }
-    }
-}
-
-impl std::fmt::Display for StreamProtocol {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Rtmp => write!(f, "RTMP"),
-            Self::Srt => write!(f, "SRT"),
-        }
-    }
-}
-
-/// Stream output configuration
-#[derive(Debug, Clone)]
-pub struct StreamConfig {
-    /// Stream URL (rtmp://... or srt://...)
-    pub url: String,
-    /// Video codec
-    pub codec: Codec,
-    /// Video width
-    pub width: u32,
-    /// Video height
-    pub height: u32,
-    /// Framerate
-    pub fps: u32,
-    /// Video bitrate in kbps
-    pub bitrate: u32,
-    /// Audio codec (optional)
-    pub audio_codec: Option<AudioCodec>,
-    /// Audio sample rate
-    pub audio_sample_rate: u32,
-    /// Audio channels
-    pub audio_channels: u32,
-    /// Audio bitrate in kbps
-    pub audio_bitrate: u32,
-}
-
-impl Default for StreamConfig {
-    fn default() -> Self {
+/// URL query parameters whose values are redacted by [`StreamOutput::safe_url`]
+const SECRET_URL_PARAMS: &[&str] = &["secret", "passphrase", "key"];
+
+/// Shortest window [`StreamOutput::current_send_bitrate_kbps`] averages over
+const MIN_BITRATE_WINDOW: Duration = Duration::from_millis(500);
+
+/// Write errors that mean the connection to the server is gone
+const CONNECTION_ERRORS: &[&str] = &[
+    "Broken pipe",
+    "Connection reset",
+    "Connection refused",
+    "Connection timed out",
+    "Network is unreachable",
+];
+
+impl SrtLinkStats {
+    /// Convert a cleared `srt_bstats` read
+    pub(crate) fn from_perf(perf: &SRT_TRACEBSTATS) -> Self {
+        let count = |value: i32| value.max(0) as u64;
         Self {
-            url: String::new(),
-            codec: Codec::H264,
-            width: 1920,
-            height: 1080,
-            fps: 30,
-            bitrate: 6000,
-            audio_codec: Some(AudioCodec::Aac),
-            audio_sample_rate: 48000,
-            audio_channels: 2,
-            audio_bitrate: 128,
+            rtt_ms: perf.msRTT,
+            bandwidth_kbps: perf.mbpsBandwidth * 1000.0,
+            send_rate_kbps: perf.mbpsSendRate * 1000.0,
+            send_buffer_ms: perf.msSndBuf.max(0) as u32,
+            send_buffer_bytes: count(perf.byteSndBuf),
+            packets_sent: perf.pktSent.max(0) as u64,
+            packets_lost: count(perf.pktSndLoss),
+            packets_lost_total: count(perf.pktSndLossTotal),
+            packets_retransmitted_total: count(perf.pktRetransTotal),
+            packets_dropped_total: count(perf.pktSndDropTotal),
         }
     }
 }
 
-/// RTMP/SRT streaming output
+/// RTMP/SRT/RIST streaming output
 pub struct StreamOutput {
+    /// Configuration, kept to reopen the connection
+    config: StreamConfig,
     /// Stream URL
     url: String,
     /// Protocol detected from URL
@@ -125,6 +102,30 @@ pub struct StreamOutput {
     audio_time_base: Option<Rational>,
     /// Running flag
     running: AtomicBool,
+    /// Latest stats, shared with the pipeline
+    stats: Arc<Mutex<StreamStats>>,
+    /// libsrt and the stream's socket, once found
+    srt: Option<(SrtLib, SRTSOCKET)>,
+    /// Send bitrate window: start time and byte count, and the last rate
+    bitrate_window: Mutex<(Instant, u64, f64)>,
+    /// Called with the stats after every refresh
+    on_stats: Option<Box<dyn Fn(StreamStats) + Send>>,
+    /// A/V sync offset applied before muxing
+    sync_offset: AvSyncOffset,
+    /// Monotonic fixup for video timestamps
+    video_timestamps: MonotonicTimestamps,
+    /// Monotonic fixup for audio timestamps
+    audio_timestamps: MonotonicTimestamps,
+    /// Encoder keyframe request flag, set after reconnecting
+    keyframe_requester: Option<Arc<AtomicBool>>,
+}
+
+/// A freshly opened FFmpeg output with its streams
+struct OpenedOutput {
+    output: Output,
+    video_stream_index: usize,
+    audio_stream_index: Option<usize>,
+    audio_time_base: Option<Rational>,
 }
 
 impl StreamOutput {
@@ -132,8 +133,8 @@ impl StreamOutput {
     pub fn new(config: StreamConfig) -> Result<Self> {
         let protocol = StreamProtocol::from_url(&config.url).ok_or_else(|| {
             NitrogenError::config(format!(
-                "Invalid stream URL '{}'. Must start with rtmp://, rtmps://, or srt://",
-                config.url
+                "Invalid stream URL '{}'. Must start with rtmp://, rtmps://, srt://, or rist://",
+                Self::safe_url(&config.url)
             ))
         })?;
 
@@ -143,10 +144,81 @@ impl StreamOutput {
             Self::safe_url(&config.url)
         );
 
+        let opened = Self::open(&config, protocol)?;
+
+        info!(
+            "{} stream configured: {}x{} @ {}fps, {} kbps (max {} kbps, {} kbit VBV){}",
+            protocol,
+            config.width,
+            config.height,
+            config.fps,
+            config.bitrate,
+            config.max_bitrate,
+            config.vbv_buffer_size,
+            if opened.audio_stream_index.is_some() {
+                format!(
+                    " + audio {}ch @ {}Hz",
+                    config.audio_channels, config.audio_sample_rate
+                )
+            } else {
+                String::new()
+            }
+        );
+
+        Ok(Self {
+            url: config.url.clone(),
+            protocol,
+            output: opened.output,
+            video_stream_index: opened.video_stream_index,
+            audio_stream_index: opened.audio_stream_index,
+            video_packets_sent: AtomicU64::new(0),
+            audio_packets_sent: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            header_written: false,
+            video_time_base: Rational::new(1, config.fps as i32),
+            audio_time_base: opened.audio_time_base,
+            running: AtomicBool::new(false),
+            stats: Arc::new(Mutex::new(StreamStats::new(protocol))),
+            srt: None,
+            bitrate_window: Mutex::new((Instant::now(), 0, 0.0)),
+            on_stats: None,
+            sync_offset: AvSyncOffset::default(),
+            video_timestamps: MonotonicTimestamps::default(),
+            audio_timestamps: MonotonicTimestamps::default(),
+            keyframe_requester: None,
+            config,
+        })
+    }
+
+    /// Connect to the server and set up the video and audio streams
+    fn open(config: &StreamConfig, protocol: StreamProtocol) -> Result<OpenedOutput> {
+        let mut options = Dictionary::new();
+        let protocol_options = match protocol {
+            StreamProtocol::Rist => {
+                config.rist.validate().map_err(NitrogenError::config)?;
+                config.rist.ffmpeg_options()
+            }
+            StreamProtocol::Srt => {
+                config.srt.validate().map_err(NitrogenError::config)?;
+                config.srt.ffmpeg_options()
+            }
+            StreamProtocol::Rtmp => Vec::new(),
+        };
+        for (key, value) in protocol_options {
+            options.set(key, &value);
+        }
+
         // Create output context for the stream URL
-        let mut output = output_as(&config.url, protocol.format_name()).map_err(|e| {
-            NitrogenError::encoder(format!("Failed to create stream output: {}", e))
-        })?;
+        let mut output = output_as_with(&config.url, protocol.format_name(), options).map_err(
+            |e| match e {
+                ffmpeg::Error::ProtocolNotFound => NitrogenError::encoder(format!(
+                    "Failed to create stream output: FFmpeg has no '{}' protocol (built without lib{}?)",
+                    protocol.protocol_name(),
+                    protocol.protocol_name()
+                )),
+                e => NitrogenError::encoder(format!("Failed to create stream output: {}", e)),
+            },
+        )?;
 
         // Add video stream
         let codec_id = match config.codec {
@@ -175,6 +247,7 @@ impl StreamOutput {
                 (*ptr).height = config.height as i32;
                 (*ptr).bit_rate = (config.bitrate * 1000) as i64;
                 (*ptr).format = ffmpeg::ffi::AVPixelFormat::AV_PIX_FMT_NV12 as i32;
+                (*ptr).color_range = ffmpeg_color_range(config.color_range).into();
             }
 
             stream.set_time_base(video_time_base);
@@ -226,49 +299,50 @@ impl StreamOutput {
             (None, None)
         };
 
-        info!(
-            "{} stream configured: {}x{} @ {}fps, {} kbps{}",
-            protocol,
-            config.width,
-            config.height,
-            config.fps,
-            config.bitrate,
-            if audio_stream_index.is_some() {
-                format!(
-                    " + audio {}ch @ {}Hz",
-                    config.audio_channels, config.audio_sample_rate
-                )
-            } else {
-                String::new()
-            }
-        );
-
-        Ok(Self {
-            url: config.url,
-            protocol,
+        Ok(OpenedOutput {
             output,
             video_stream_index,
             audio_stream_index,
-            video_packets_sent: AtomicU64::new(0),
-            audio_packets_sent: AtomicU64::new(0),
-            bytes_sent: AtomicU64::new(0),
-            header_written: false,
-            video_time_base,
             audio_time_base,
-            running: AtomicBool::new(false),
         })
     }
 
-    /// Mask stream key in URL for safe logging
+    /// Mask stream keys and secrets in a URL for safe logging
     pub fn safe_url(url: &str) -> String {
+        let (path, query) = match url.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (url, None),
+        };
+
         // For RTMP URLs like rtmp://server/app/stream_key, mask the stream key
-        if let Some(idx) = url.rfind('/') {
-            let (base, key) = url.split_at(idx + 1);
-            if !key.is_empty() && !key.contains(':') {
-                return format!("{}****", base);
+        let host_start = path.find("://").map_or(0, |i| i + 3);
+        let mut safe = match path.rfind('/') {
+            Some(idx) if idx >= host_start => {
+                let (base, key) = path.split_at(idx + 1);
+                if !key.is_empty() && !key.contains(':') {
+                    format!("{}****", base)
+                } else {
+                    path.to_string()
+                }
             }
+            _ => path.to_string(),
+        };
+
+        // SRT passphrases and RIST secrets ride in the query string
+        if let Some(query) = query {
+            let params: Vec<String> = query
+                .split('&')
+                .map(|param| match param.split_once('=') {
+                    Some((name, _)) if SECRET_URL_PARAMS.contains(&name.to_lowercase().as_str()) => {
+                        format!("{}=****", name)
+                    }
+                    _ => param.to_string(),
+                })
+                .collect();
+            safe.push('?');
+            safe.push_str(&params.join("&"));
         }
-        url.to_string()
+        safe
     }
 
     /// Start streaming (write header)
@@ -289,11 +363,170 @@ impl StreamOutput {
 
         self.header_written = true;
         self.running.store(true, Ordering::SeqCst);
+        *self.bitrate_window.lock() = (Instant::now(), self.bytes_sent(), 0.0);
+
+        if self.protocol == StreamProtocol::Srt {
+            self.srt = self.find_srt_socket();
+        }
 
         info!("{} stream started successfully", self.protocol);
         Ok(())
     }
 
+    /// Locate libsrt and the socket FFmpeg opened for this stream
+    fn find_srt_socket(&self) -> Option<(SrtLib, SRTSOCKET)> {
+        let lib = match SrtLib::load() {
+            Ok(lib) => lib,
+            Err(e) => {
+                info!("SRT link stats unavailable: {}", e);
+                return None;
+            }
+        };
+
+        // SAFETY: the output context is open for as long as self exists
+        let socket = unsafe { srt_sys::srt_socket(self.output.as_ptr()) };
+        match socket {
+            Some(socket) => {
+                debug!("Reading SRT link stats from socket {}", socket);
+                Some((lib, socket))
+            }
+            None => {
+                info!("SRT link stats unavailable: stream socket not found");
+                None
+            }
+        }
+    }
+
+    /// Refresh the stream statistics
+    ///
+    /// Called periodically by the streaming task. Logs when an SRT link
+    /// becomes congested and when it recovers, and passes the stats to the
+    /// [`set_on_stats`](Self::set_on_stats) callback.
+    pub fn refresh_stats(&mut self) -> StreamStats {
+        let link = self
+            .srt
+            .as_ref()
+            .and_then(|(lib, socket)| match lib.stats(*socket, true) {
+                Ok(perf) => Some(SrtLinkStats::from_perf(&perf)),
+                Err(e) => {
+                    debug!("srt_bstats failed: {}", e);
+                    None
+                }
+            });
+
+        let stats = StreamStats {
+            protocol: self.protocol,
+            bytes_sent: self.bytes_sent(),
+            video_packets_sent: self.video_packets_sent(),
+            audio_packets_sent: self.audio_packets_sent(),
+            send_rate_kbps: self.current_send_bitrate_kbps(),
+            link,
+            timestamps_corrected: self.timestamps_corrected(),
+        };
+
+        let was_congested = self.stats.lock().congested();
+        if let Some(ref link) = stats.link {
+            if link.congested() && !was_congested {
+                warn!(
+                    "SRT link congested: {:.1}% loss, RTT {:.0} ms, {:.0} of {:.0} kbps",
+                    link.loss_percent(),
+                    link.rtt_ms,
+                    link.send_rate_kbps,
+                    link.bandwidth_kbps
+                );
+            } else if !link.congested() && was_congested {
+                info!("SRT link recovered (RTT {:.0} ms)", link.rtt_ms);
+            }
+        }
+
+        *self.stats.lock() = stats.clone();
+        if let Some(ref on_stats) = self.on_stats {
+            on_stats(stats.clone());
+        }
+        stats
+    }
+
+    /// Send bitrate over the last second in kbps
+    ///
+    /// Recalculated when the window is at least half a second old, so the
+    /// once-a-second stats refresh always gets a fresh rate; calls in
+    /// between return the previous one.
+    pub fn current_send_bitrate_kbps(&self) -> f64 {
+        let now = Instant::now();
+        let bytes = self.bytes_sent();
+        let mut window = self.bitrate_window.lock();
+        let (start, start_bytes, kbps) = *window;
+        let elapsed = now.duration_since(start);
+        if elapsed < MIN_BITRATE_WINDOW {
+            return kbps;
+        }
+
+        let kbps = (bytes - start_bytes) as f64 * 8.0 / 1000.0 / elapsed.as_secs_f64();
+        *window = (now, bytes, kbps);
+        kbps
+    }
+
+    /// Call `on_stats` with the stats every [`STREAM_STATS_INTERVAL`]
+    ///
+    /// Runs on the streaming task, so it should return quickly.
+    pub fn set_on_stats(&mut self, on_stats: impl Fn(StreamStats) + Send + 'static) {
+        self.on_stats = Some(Box::new(on_stats));
+    }
+
+    /// Latest stream statistics
+    pub fn stats(&self) -> StreamStats {
+        self.stats.lock().clone()
+    }
+
+    /// Shared handle to the statistics
+    ///
+    /// Stays readable after the output is moved into its streaming task.
+    pub fn stats_handle(&self) -> Arc<Mutex<StreamStats>> {
+        self.stats.clone()
+    }
+
+    /// Set the encoder keyframe request flag
+    ///
+    /// When set, a keyframe is requested after reconnecting so the stream
+    /// doesn't wait a whole GOP to resume.
+    pub fn set_keyframe_requester(&mut self, requester: Arc<AtomicBool>) {
+        self.keyframe_requester = Some(requester);
+    }
+
+    /// Forget a connection that is gone
+    ///
+    /// No trailer is written: there's nobody left to receive it.
+    fn disconnect(&mut self) {
+        self.header_written = false;
+        self.running.store(false, Ordering::SeqCst);
+        self.srt = None;
+    }
+
+    /// Open a new connection and write the header again
+    ///
+    /// Timestamps carry on from the old connection, so the server sees one
+    /// stream with a gap.
+    pub fn reconnect(&mut self) -> Result<()> {
+        self.disconnect();
+        let opened = Self::open(&self.config, self.protocol)?;
+        self.output = opened.output;
+        self.video_stream_index = opened.video_stream_index;
+        self.audio_stream_index = opened.audio_stream_index;
+        self.audio_time_base = opened.audio_time_base;
+        self.start()?;
+        if let Some(ref requester) = self.keyframe_requester {
+            requester.store(true, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    /// Share an A/V sync offset with this stream
+    ///
+    /// The offset is read for every packet, so it can be changed live.
+    pub fn set_sync_offset(&mut self, offset: AvSyncOffset) {
+        self.sync_offset = offset;
+    }
+
     /// Write a video packet to the stream
     pub fn write_video_packet(&mut self, packet: &EncodedPacket) -> Result<()> {
         if !self.header_written {
@@ -302,8 +535,14 @@ impl StreamOutput {
 
         let mut pkt = ffmpeg::Packet::copy(&packet.data);
         pkt.set_stream(self.video_stream_index);
-        pkt.set_pts(Some(packet.pts));
-        pkt.set_dts(Some(packet.dts));
+        let delay = self.sync_offset.video_delay(self.video_time_base);
+        let (pts, dts) = self.video_timestamps.fix(
+            shift_ts(packet.pts, delay),
+            shift_ts(packet.dts, delay),
+            "video",
+        );
+        pkt.set_pts(Some(pts));
+        pkt.set_dts(Some(dts));
 
         if packet.keyframe {
             pkt.set_flags(ffmpeg::packet::Flags::KEY);
@@ -350,12 +589,18 @@ impl StreamOutput {
 
         let mut pkt = ffmpeg::Packet::copy(&packet.data);
         pkt.set_stream(audio_stream_index);
-        pkt.set_pts(Some(packet.pts));
-        pkt.set_dts(Some(packet.dts));
+        let input_time_base = self.audio_time_base.unwrap_or(Rational::new(1, 48000));
+        let delay = self.sync_offset.audio_delay(input_time_base);
+        let (pts, dts) = self.audio_timestamps.fix(
+            shift_ts(packet.pts, delay),
+            shift_ts(packet.dts, delay),
+            "audio",
+        );
+        pkt.set_pts(Some(pts));
+        pkt.set_dts(Some(dts));
         pkt.set_duration(packet.duration);
 
         // Rescale timestamps
-        let input_time_base = self.audio_time_base.unwrap_or(Rational::new(1, 48000));
         let output_time_base = self
             .output
             .stream(audio_stream_index)
@@ -426,6 +671,11 @@ impl StreamOutput {
         self.audio_packets_sent.load(Ordering::Relaxed)
     }
 
+    /// Number of packets whose timestamps had to be made monotonic
+    pub fn timestamps_corrected(&self) -> u64 {
+        self.video_timestamps.corrected() + self.audio_timestamps.corrected()
+    }
+
     /// Get total bytes sent
     pub fn bytes_sent(&self) -> u64 {
         self.bytes_sent.load(Ordering::Relaxed)
@@ -447,46 +697,364 @@ impl Drop for StreamOutput {
     }
 }
 
+/// Whether a write error means the connection is gone
+fn is_connection_error(e: &NitrogenError) -> bool {
+    let message = e.to_string();
+    CONNECTION_ERRORS.iter().any(|error| message.contains(error))
+}
+
+/// Reconnect state of one stream destination
+struct Reconnect {
+    policy: ReconnectPolicy,
+    /// Failed attempts since the connection was lost
+    attempts: u32,
+    /// When the next attempt is due (None while connected)
+    next_attempt: Option<tokio::time::Instant>,
+    /// Latest keyframe received while disconnected
+    keyframe: Option<Arc<EncodedPacket>>,
+    /// Drop packets until the next keyframe
+    wait_keyframe: bool,
+}
+
+impl Reconnect {
+    fn new(policy: ReconnectPolicy) -> Self {
+        Self {
+            policy,
+            attempts: 0,
+            next_attempt: None,
+            keyframe: None,
+            wait_keyframe: false,
+        }
+    }
+
+    /// Whether packets can be sent right now
+    fn connected(&self) -> bool {
+        self.next_attempt.is_none()
+    }
+
+    /// Send a video packet, or keep it if it is a keyframe and the stream
+    /// is disconnected
+    ///
+    /// Fails only when the connection is lost and may not be retried.
+    fn write_video(
+        &mut self,
+        streamer: &mut StreamOutput,
+        packet: &Arc<EncodedPacket>,
+    ) -> Result<()> {
+        if !self.connected() {
+            if packet.keyframe {
+                self.keyframe = Some(packet.clone());
+            }
+            return Ok(());
+        }
+        if self.wait_keyframe && !packet.keyframe {
+            return Ok(());
+        }
+        self.wait_keyframe = false;
+        let result = streamer.write_video_packet(packet);
+        self.check(streamer, result, "video")
+    }
+
+    /// Send an audio packet unless the stream is waiting for video
+    fn write_audio(
+        &mut self,
+        streamer: &mut StreamOutput,
+        packet: &EncodedAudioPacket,
+    ) -> Result<()> {
+        if !self.connected() || self.wait_keyframe {
+            return Ok(());
+        }
+        let result = streamer.write_audio_packet(packet);
+        self.check(streamer, result, "audio")
+    }
+
+    /// Log a write error and start reconnecting if the connection is gone
+    fn check(&mut self, streamer: &mut StreamOutput, result: Result<()>, kind: &str) -> Result<()> {
+        let Err(e) = result else {
+            return Ok(());
+        };
+        error!("Failed to stream {} packet: {}", kind, e);
+        if !is_connection_error(&e) {
+            return Ok(());
+        }
+
+        warn!("{} stream connection lost", streamer.protocol());
+        streamer.disconnect();
+        if self.policy.max_retries == 0 {
+            return Err(NitrogenError::encoder(format!(
+                "{} stream connection lost: {}",
+                streamer.protocol(),
+                e
+            )));
+        }
+        self.attempts = 0;
+        self.keyframe = None;
+        self.schedule();
+        Ok(())
+    }
+
+    /// Plan the next attempt after the backoff delay
+    fn schedule(&mut self) {
+        let delay = self.policy.delay(self.attempts);
+        info!("Reconnecting in {:.1}s", delay.as_secs_f64());
+        self.next_attempt = Some(tokio::time::Instant::now() + delay);
+    }
+
+    /// Try to reconnect, failing once every attempt has been used
+    fn attempt(&mut self, streamer: &mut StreamOutput) -> Result<()> {
+        self.attempts += 1;
+        warn!(
+            "Reconnecting {} stream to {} (attempt {}/{})",
+            streamer.protocol(),
+            streamer.masked_url(),
+            self.attempts,
+            self.policy.max_retries
+        );
+        if let Err(e) = streamer.reconnect() {
+            if self.attempts >= self.policy.max_retries {
+                return Err(NitrogenError::encoder(format!(
+                    "{} stream connection lost, gave up after {} reconnect attempts: {}",
+                    streamer.protocol(),
+                    self.attempts,
+                    e
+                )));
+            }
+            warn!("Reconnect attempt {} failed: {}", self.attempts, e);
+            self.schedule();
+            return Ok(());
+        }
+
+        info!("{} stream reconnected", streamer.protocol());
+        self.next_attempt = None;
+        // Show the latest picture right away; the frames after it are gone,
+        // so the stream carries on at the next keyframe
+        if let Some(keyframe) = self.keyframe.take() {
+            let result = streamer.write_video_packet(&keyframe);
+            self.check(streamer, result, "video")?;
+        }
+        self.wait_keyframe = true;
+        Ok(())
+    }
+}
+
+/// One destination of a [`MultiStreamOutput`]
+struct Destination {
+    streamer: StreamOutput,
+    reconnect: Reconnect,
+}
+
+impl Destination {
+    fn packets_sent(&self) -> u64 {
+        self.streamer.video_packets_sent() + self.streamer.audio_packets_sent()
+    }
+}
+
+/// Several stream outputs sent the same packets
+///
+/// Streams to Twitch and YouTube at once, or to an RTMP and an SRT server.
+/// Every destination has its own connection and reconnects on its own; one
+/// that fails for good is logged and dropped while the others carry on.
+pub struct MultiStreamOutput {
+    destinations: Vec<Destination>,
+    /// Packets sent by destinations that have been dropped
+    dropped_packets: u64,
+}
+
+impl MultiStreamOutput {
+    /// Send to every one of `streamers`
+    pub fn new(streamers: Vec<StreamOutput>) -> Self {
+        let destinations = streamers
+            .into_iter()
+            .map(|streamer| Destination {
+                reconnect: Reconnect::new(streamer.config.reconnect),
+                streamer,
+            })
+            .collect();
+        Self {
+            destinations,
+            dropped_packets: 0,
+        }
+    }
+
+    /// Start every destination, dropping the ones that can't
+    ///
+    /// Fails only if none could be started.
+    pub fn start(&mut self) -> Result<()> {
+        self.for_each(|destination| destination.streamer.start())
+    }
+
+    /// Write a video packet to every destination
+    ///
+    /// Fails once the last destination has been dropped.
+    pub fn write_video_packet(&mut self, packet: &Arc<EncodedPacket>) -> Result<()> {
+        self.for_each(|destination| {
+            destination
+                .reconnect
+                .write_video(&mut destination.streamer, packet)
+        })
+    }
+
+    /// Write an audio packet to every destination
+    ///
+    /// Fails once the last destination has been dropped.
+    pub fn write_audio_packet(&mut self, packet: &EncodedAudioPacket) -> Result<()> {
+        self.for_each(|destination| {
+            destination
+                .reconnect
+                .write_audio(&mut destination.streamer, packet)
+        })
+    }
+
+    /// Wait until a disconnected destination is due to reconnect
+    ///
+    /// Never returns while every destination is connected.
+    async fn reconnect_due(&self) {
+        let next = self
+            .destinations
+            .iter()
+            .filter_map(|destination| destination.reconnect.next_attempt)
+            .min();
+        match next {
+            Some(at) => tokio::time::sleep_until(at).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    /// Try to reconnect the destinations that are due
+    fn reconnect(&mut self) -> Result<()> {
+        let now = tokio::time::Instant::now();
+        self.for_each(|destination| match destination.reconnect.next_attempt {
+            Some(at) if at <= now => destination.reconnect.attempt(&mut destination.streamer),
+            _ => Ok(()),
+        })
+    }
+
+    /// Refresh the statistics of the connected destinations
+    pub fn refresh_stats(&mut self) {
+        for destination in &mut self.destinations {
+            if destination.reconnect.connected() {
+                destination.streamer.refresh_stats();
+            }
+        }
+    }
+
+    /// Stop every destination
+    ///
+    /// All are stopped even if one fails; the first error is returned.
+    pub fn stop(&mut self) -> Result<()> {
+        let mut result = Ok(());
+        for destination in &mut self.destinations {
+            if let Err(e) = destination.streamer.stop() {
+                error!(
+                    "Failed to stop stream to {}: {}",
+                    destination.streamer.masked_url(),
+                    e
+                );
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Packets sent, summed over every destination including dropped ones
+    pub fn packets_sent(&self) -> u64 {
+        self.dropped_packets + self.destinations.iter().map(Destination::packets_sent).sum::<u64>()
+    }
+
+    /// Number of destinations still streaming or reconnecting
+    pub fn len(&self) -> usize {
+        self.destinations.len()
+    }
+
+    /// Whether every destination has been dropped
+    pub fn is_empty(&self) -> bool {
+        self.destinations.is_empty()
+    }
+
+    /// Run `f` on every destination, dropping the ones it fails for
+    ///
+    /// Fails with the last error once no destination is left.
+    fn for_each<F>(&mut self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&mut Destination) -> Result<()>,
+    {
+        let mut last_error = None;
+        let dropped_packets = &mut self.dropped_packets;
+        self.destinations.retain_mut(|destination| match f(destination) {
+            Ok(()) => true,
+            Err(e) => {
+                error!(
+                    "Dropping {} stream to {}: {}",
+                    destination.streamer.protocol(),
+                    destination.streamer.masked_url(),
+                    e
+                );
+                *dropped_packets += destination.packets_sent();
+                last_error = Some(e);
+                false
+            }
+        });
+        match last_error {
+            Some(e) if self.destinations.is_empty() => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Async task to stream video packets from a broadcast channel
 pub async fn stream_from_channel(
-    mut streamer: StreamOutput,
+    streamer: StreamOutput,
     mut rx: broadcast::Receiver<Arc<EncodedPacket>>,
 ) -> Result<u64> {
+    let mut streamer = MultiStreamOutput::new(vec![streamer]);
     streamer.start()?;
 
     loop {
-        match rx.recv().await {
-            Ok(packet) => {
-                if let Err(e) = streamer.write_video_packet(&packet) {
-                    error!("Failed to stream video packet: {}", e);
-                    // For streaming, connection errors are often fatal
-                    if e.to_string().contains("Broken pipe")
-                        || e.to_string().contains("Connection reset")
-                    {
-                        warn!("Stream connection lost");
-                        break;
-                    }
+        tokio::select! {
+            result = rx.recv() => match result {
+                Ok(packet) => streamer.write_video_packet(&packet)?,
+                Err(broadcast::error::RecvError::Closed) => {
+                    info!("Video encoder channel closed, stopping stream");
+                    break;
                 }
-            }
-            Err(broadcast::error::RecvError::Closed) => {
-                info!("Video encoder channel closed, stopping stream");
-                break;
-            }
-            Err(broadcast::error::RecvError::Lagged(n)) => {
-                warn!("Streaming dropped {} video frames due to lag", n);
-            }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Streaming dropped {} video frames due to lag", n);
+                }
+            },
+            _ = streamer.reconnect_due() => streamer.reconnect()?,
         }
     }
 
     streamer.stop()?;
-    Ok(streamer.video_packets_sent() + streamer.audio_packets_sent())
+    Ok(streamer.packets_sent())
 }
 
 /// Async task to stream both video and audio packets from broadcast channels
+///
+/// Reconnects after a lost connection as the stream's [`ReconnectPolicy`]
+/// allows, and fails once it runs out of attempts.
 pub async fn stream_av_from_channels(
-    mut streamer: StreamOutput,
+    streamer: StreamOutput,
+    video_rx: broadcast::Receiver<Arc<EncodedPacket>>,
+    audio_rx: Option<broadcast::Receiver<Arc<EncodedAudioPacket>>>,
+    shutdown: ShutdownSignal,
+) -> Result<u64> {
+    let streamer = MultiStreamOutput::new(vec![streamer]);
+    multi_stream_av_from_channels(streamer, video_rx, audio_rx, shutdown).await
+}
+
+/// Async task to stream video and audio to several destinations
+///
+/// Runs until the encoder channels close or shutdown, and fails only once
+/// every destination has been dropped. Returns the packets sent, summed over
+/// the destinations.
+pub async fn multi_stream_av_from_channels(
+    mut streamer: MultiStreamOutput,
     mut video_rx: broadcast::Receiver<Arc<EncodedPacket>>,
     mut audio_rx: Option<broadcast::Receiver<Arc<EncodedAudioPacket>>>,
+    mut shutdown: ShutdownSignal,
 ) -> Result<u64> {
     use std::sync::atomic::Ordering;
 
@@ -494,13 +1062,13 @@ pub async fn stream_av_from_channels(
 
     let video_done = AtomicBool::new(false);
     let audio_done = AtomicBool::new(audio_rx.is_none());
-    let connection_error = AtomicBool::new(false);
+
+    let mut stats_interval = tokio::time::interval(STREAM_STATS_INTERVAL);
+    stats_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
     loop {
-        // Exit when both streams are done or on connection error
-        if (video_done.load(Ordering::SeqCst) && audio_done.load(Ordering::SeqCst))
-            || connection_error.load(Ordering::SeqCst)
-        {
+        // Exit when both streams are done
+        if video_done.load(Ordering::SeqCst) && audio_done.load(Ordering::SeqCst) {
             break;
         }
 
@@ -510,15 +1078,7 @@ pub async fn stream_av_from_channels(
             // Video packets (priority)
             video_result = video_rx.recv(), if !video_done.load(Ordering::SeqCst) => {
                 match video_result {
-                    Ok(packet) => {
-                        if let Err(e) = streamer.write_video_packet(&packet) {
-                            error!("Failed to stream video packet: {}", e);
-                            let err_str = e.to_string();
-                            if err_str.contains("Broken pipe") || err_str.contains("Connection reset") {
-                                connection_error.store(true, Ordering::SeqCst);
-                            }
-                        }
-                    }
+                    Ok(packet) => streamer.write_video_packet(&packet)?,
                     Err(broadcast::error::RecvError::Closed) => {
                         info!("Video encoder channel closed");
                         video_done.store(true, Ordering::SeqCst);
@@ -537,11 +1097,7 @@ pub async fn stream_av_from_channels(
                 }
             }, if !audio_done.load(Ordering::SeqCst) => {
                 match audio_result {
-                    Ok(packet) => {
-                        if let Err(e) = streamer.write_audio_packet(&packet) {
-                            error!("Failed to stream audio packet: {}", e);
-                        }
-                    }
+                    Ok(packet) => streamer.write_audio_packet(&packet)?,
                     Err(broadcast::error::RecvError::Closed) => {
                         info!("Audio encoder channel closed");
                         audio_done.store(true, Ordering::SeqCst);
@@ -551,11 +1107,23 @@ pub async fn stream_av_from_channels(
                     }
                 }
             }
+
+            _ = streamer.reconnect_due() => streamer.reconnect()?,
+
+            _ = stats_interval.tick() => {
+                streamer.refresh_stats();
+            }
+
+            // Buffered packets win the biased select, so nothing is lost here
+            _ = shutdown.wait() => {
+                info!("Shutdown requested, closing stream");
+                break;
+            }
         }
     }
 
     streamer.stop()?;
-    Ok(streamer.video_packets_sent() + streamer.audio_packets_sent())
+    Ok(streamer.packets_sent())
 }
 
 #[cfg(test)]
@@ -563,46 +1131,66 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_protocol_detection() {
+    fn test_safe_url_masking() {
         assert_eq!(
-            StreamProtocol::from_url("rtmp://live.twitch.tv/app/key"),
-            Some(StreamProtocol::Rtmp)
+            StreamOutput::safe_url("rtmp://live.twitch.tv/app/secretkey123"),
+            "rtmp://live.twitch.tv/app/****"
         );
         assert_eq!(
-            StreamProtocol::from_url("rtmps://live.youtube.com/app/key"),
-            Some(StreamProtocol::Rtmp)
+            StreamOutput::safe_url("srt://localhost:9999"),
+            "srt://localhost:9999"
         );
         assert_eq!(
-            StreamProtocol::from_url("srt://localhost:9999"),
-            Some(StreamProtocol::Srt)
+            StreamOutput::safe_url("srt://host:9000?latency=200000&passphrase=hunter22"),
+            "srt://host:9000?latency=200000&passphrase=****"
         );
-        assert_eq!(StreamProtocol::from_url("http://example.com"), None);
-    }
-
-    #[test]
-    fn test_safe_url_masking() {
         assert_eq!(
-            StreamOutput::safe_url("rtmp://live.twitch.tv/app/secretkey123"),
-            "rtmp://live.twitch.tv/app/****"
+            StreamOutput::safe_url("rist://relay.example.com?buffer=1000&secret=topsecret&aes-type=256"),
+            "rist://relay.example.com?buffer=1000&secret=****&aes-type=256"
         );
         assert_eq!(
-            StreamOutput::safe_url("srt://localhost:9999"),
-            "srt://localhost:9999"
+            StreamOutput::safe_url("rtmp://live.twitch.tv/app/secretkey123?bandwidthtest=true"),
+            "rtmp://live.twitch.tv/app/****?bandwidthtest=true"
         );
     }
 
+    fn perf(sent: i64, lost: i32, send_mbps: f64, bandwidth_mbps: f64) -> SRT_TRACEBSTATS {
+        SRT_TRACEBSTATS {
+            pktSent: sent,
+            pktSndLoss: lost,
+            pktSndLossTotal: lost,
+            mbpsSendRate: send_mbps,
+            mbpsBandwidth: bandwidth_mbps,
+            msRTT: 42.0,
+            msSndBuf: 120,
+            ..Default::default()
+        }
+    }
+
     #[test]
-    fn test_stream_config_default() {
-        let config = StreamConfig::default();
-        assert_eq!(config.width, 1920);
-        assert_eq!(config.height, 1080);
-        assert_eq!(config.fps, 30);
-        assert!(config.audio_codec.is_some());
+    fn test_srt_link_stats_from_perf() {
+        let link = SrtLinkStats::from_perf(&perf(1000, 5, 6.0, 20.0));
+        assert_eq!(link.rtt_ms, 42.0);
+        assert_eq!(link.send_rate_kbps, 6000.0);
+        assert_eq!(link.bandwidth_kbps, 20000.0);
+        assert_eq!(link.send_buffer_ms, 120);
+        assert_eq!(link.packets_lost_total, 5);
+        assert!((link.loss_percent() - 0.5).abs() < f64::EPSILON);
+        assert!(!link.congested());
     }
 
     #[test]
-    fn test_protocol_format_name() {
-        assert_eq!(StreamProtocol::Rtmp.format_name(), "flv");
-        assert_eq!(StreamProtocol::Srt.format_name(), "mpegts");
+    fn test_srt_congestion() {
+        // Loss above the threshold
+        assert!(SrtLinkStats::from_perf(&perf(1000, 30, 6.0, 20.0)).congested());
+        // Sending faster than the link's estimated bandwidth
+        assert!(SrtLinkStats::from_perf(&perf(1000, 0, 6.0, 4.0)).congested());
+        // No packets, no loss
+        assert_eq!(SrtLinkStats::from_perf(&perf(0, 0, 0.0, 0.0)).loss_percent(), 0.0);
+
+        let mut stats = StreamStats::new(StreamProtocol::Srt);
+        assert!(!stats.congested());
+        stats.link = Some(SrtLinkStats::from_perf(&perf(100, 10, 6.0, 20.0)));
+        assert!(stats.congested());
     }
 }
@@ -0,0 +1,511 @@
+//! Streaming settings shared with the configuration
+//!
+//! Protocols, RIST options and stream statistics are plain data used by the
+//! config file and IPC in every build; the FFmpeg/libsrt output itself is in
+//! `stream.rs`, behind the `streaming` feature.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{AudioCodec, Codec, ColorRange};
+
+/// How often the streaming task refreshes [`StreamStats`]
+pub const STREAM_STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Packet loss over one stats interval at which an SRT link counts as congested
+pub const CONGESTION_LOSS_PERCENT: f64 = 2.0;
+
+/// Streaming protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamProtocol {
+    /// RTMP (Real-Time Messaging Protocol) - Twitch, YouTube, etc.
+    Rtmp,
+    /// SRT (Secure Reliable Transport) - low latency streaming
+    Srt,
+    /// RIST (Reliable Internet Stream Transport) - contribution over lossy links
+    Rist,
+}
+
+impl StreamProtocol {
+    /// Detect protocol from URL
+    pub fn from_url(url: &str) -> Option<Self> {
+        let lower = url.to_lowercase();
+        if lower.starts_with("rtmp://") || lower.starts_with("rtmps://") {
+            Some(Self::Rtmp)
+        } else if lower.starts_with("srt://") {
+            Some(Self::Srt)
+        } else if lower.starts_with("rist://") {
+            Some(Self::Rist)
+        } else {
+            None
+        }
+    }
+
+    /// Get the FFmpeg format name
+    pub fn format_name(&self) -> &'static str {
+        match self {
+            Self::Rtmp => "flv",
+            Self::Srt | Self::Rist => "mpegts",
+        }
+    }
+
+    /// Get the FFmpeg protocol name
+    pub fn protocol_name(&self) -> &'static str {
+        match self {
+            Self::Rtmp => "rtmp",
+            Self::Srt => "srt",
+            Self::Rist => "rist",
+        }
+    }
+}
+
+impl std::fmt::Display for StreamProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rtmp => write!(f, "RTMP"),
+            Self::Srt => write!(f, "SRT"),
+            Self::Rist => write!(f, "RIST"),
+        }
+    }
+}
+
+/// RIST-specific stream options, passed to FFmpeg's librist protocol
+///
+/// Unset fields keep librist's defaults. Options given in the URL itself
+/// (`rist://host:port?buffer=...`) are parsed by librist and still apply.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RistOptions {
+    /// Retransmission buffer in milliseconds
+    #[serde(default)]
+    pub buffer_ms: Option<u32>,
+    /// Pre-shared secret for encryption
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// AES key size in bits (128 or 256); 128 when only a secret is set
+    #[serde(default)]
+    pub encryption: Option<u32>,
+}
+
+impl RistOptions {
+    /// Whether no option is set
+    pub fn is_empty(&self) -> bool {
+        self.buffer_ms.is_none() && self.secret.is_none() && self.encryption.is_none()
+    }
+
+    /// Check the key size and that encryption has a secret to use
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if let Some(bits) = self.encryption {
+            if bits != 128 && bits != 256 {
+                return Err(format!("RIST encryption must be 128 or 256 bits, got {}", bits));
+            }
+            if self.secret.is_none() {
+                return Err("RIST encryption needs a secret".to_string());
+            }
+        }
+        if self.secret.as_deref().is_some_and(str::is_empty) {
+            return Err("RIST secret is empty".to_string());
+        }
+        Ok(())
+    }
+
+    /// FFmpeg librist option names and values
+    pub fn ffmpeg_options(&self) -> Vec<(&'static str, String)> {
+        let mut options = Vec::new();
+        if let Some(buffer) = self.buffer_ms {
+            options.push(("buffer_size", buffer.to_string()));
+        }
+        if let Some(ref secret) = self.secret {
+            options.push(("secret", secret.clone()));
+            options.push(("encryption", self.encryption.unwrap_or(128).to_string()));
+        }
+        options
+    }
+}
+
+/// SRT-specific stream options, passed to FFmpeg's libsrt protocol
+///
+/// Unset fields keep libsrt's defaults (120 ms latency, no encryption).
+/// Options in the URL itself (`srt://host:port?latency=...`) still apply
+/// when the matching field is unset.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SrtOptions {
+    /// Receiver buffer latency in milliseconds
+    #[serde(default)]
+    pub latency_ms: Option<u32>,
+    /// Passphrase for AES encryption (10 to 79 characters)
+    #[serde(default)]
+    pub passphrase: Option<String>,
+    /// AES key length in bytes (16, 24 or 32); 16 when only a passphrase is set
+    #[serde(default)]
+    pub pbkeylen: Option<u32>,
+}
+
+impl SrtOptions {
+    /// Set the latency in milliseconds
+    pub fn with_latency_ms(mut self, latency_ms: u32) -> Self {
+        self.latency_ms = Some(latency_ms);
+        self
+    }
+
+    /// Encrypt with a passphrase
+    pub fn with_passphrase(mut self, passphrase: impl Into<String>) -> Self {
+        self.passphrase = Some(passphrase.into());
+        self
+    }
+
+    /// Set the AES key length in bytes
+    pub fn with_pbkeylen(mut self, pbkeylen: u32) -> Self {
+        self.pbkeylen = Some(pbkeylen);
+        self
+    }
+
+    /// Whether no option is set
+    pub fn is_empty(&self) -> bool {
+        self.latency_ms.is_none() && self.passphrase.is_none() && self.pbkeylen.is_none()
+    }
+
+    /// Check the passphrase length and the key length
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if let Some(ref passphrase) = self.passphrase {
+            let len = passphrase.chars().count();
+            if !(10..=79).contains(&len) {
+                return Err(format!(
+                    "SRT passphrase must be 10 to 79 characters, got {}",
+                    len
+                ));
+            }
+        }
+        if let Some(len) = self.pbkeylen {
+            if ![16, 24, 32].contains(&len) {
+                return Err(format!("SRT key length must be 16, 24 or 32 bytes, got {}", len));
+            }
+            if self.passphrase.is_none() {
+                return Err("SRT key length needs a passphrase".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// FFmpeg libsrt option names and values
+    pub fn ffmpeg_options(&self) -> Vec<(&'static str, String)> {
+        let mut options = Vec::new();
+        if let Some(latency) = self.latency_ms {
+            // FFmpeg takes the latency in microseconds
+            options.push(("latency", (u64::from(latency) * 1000).to_string()));
+        }
+        if let Some(ref passphrase) = self.passphrase {
+            options.push(("passphrase", passphrase.clone()));
+            options.push(("pbkeylen", self.pbkeylen.unwrap_or(16).to_string()));
+        }
+        options
+    }
+}
+
+/// How a stream reconnects after losing its connection
+///
+/// The delay before each attempt doubles from `base_delay` up to
+/// `max_delay`; after `max_retries` failed attempts the stream gives up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// Reconnect attempts before giving up (0 = end the stream right away)
+    pub max_retries: u32,
+    /// Delay before the first attempt
+    pub base_delay: Duration,
+    /// Longest delay between two attempts
+    pub max_delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 10,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Never reconnect
+    pub fn disabled() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Delay before attempt `attempt` (0-based)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_delay)
+    }
+}
+
+/// Stream output configuration
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    /// Stream URL (rtmp://..., srt://... or rist://...)
+    pub url: String,
+    /// Video codec
+    pub codec: Codec,
+    /// Video width
+    pub width: u32,
+    /// Video height
+    pub height: u32,
+    /// Framerate
+    pub fps: u32,
+    /// Video bitrate in kbps
+    pub bitrate: u32,
+    /// Peak video bitrate in kbps the encoder is capped at
+    pub max_bitrate: u32,
+    /// Encoder VBV buffer size in kbits
+    pub vbv_buffer_size: u32,
+    /// Audio codec (optional)
+    pub audio_codec: Option<AudioCodec>,
+    /// Audio sample rate
+    pub audio_sample_rate: u32,
+    /// Audio channels
+    pub audio_channels: u32,
+    /// Audio bitrate in kbps
+    pub audio_bitrate: u32,
+    /// Color range tagged on the video stream
+    pub color_range: ColorRange,
+    /// RIST options (ignored for other protocols)
+    pub rist: RistOptions,
+    /// SRT options (ignored for other protocols)
+    pub srt: SrtOptions,
+    /// Reconnecting after the connection drops
+    pub reconnect: ReconnectPolicy,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            codec: Codec::H264,
+            width: 1920,
+            height: 1080,
+            fps: 30,
+            bitrate: 6000,
+            max_bitrate: 9000,
+            vbv_buffer_size: 6000,
+            audio_codec: Some(AudioCodec::Aac),
+            audio_sample_rate: 48000,
+            audio_channels: 2,
+            audio_bitrate: 128,
+            color_range: ColorRange::default(),
+            rist: RistOptions::default(),
+            srt: SrtOptions::default(),
+            reconnect: ReconnectPolicy::default(),
+        }
+    }
+}
+
+/// Health of a stream, refreshed every [`STREAM_STATS_INTERVAL`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StreamStats {
+    /// Protocol of the stream
+    pub protocol: StreamProtocol,
+    /// Total bytes handed to the muxer
+    pub bytes_sent: u64,
+    /// Video packets sent
+    pub video_packets_sent: u64,
+    /// Audio packets sent
+    pub audio_packets_sent: u64,
+    /// Payload rate over the last interval in kbps
+    pub send_rate_kbps: f64,
+    /// SRT socket statistics (None for RTMP, or when libsrt can't be reached)
+    pub link: Option<SrtLinkStats>,
+    /// Packets whose timestamps were bumped to keep them monotonic
+    #[serde(default)]
+    pub timestamps_corrected: u64,
+}
+
+impl StreamStats {
+    /// Stats for a stream that hasn't sent anything yet
+    pub fn new(protocol: StreamProtocol) -> Self {
+        Self {
+            protocol,
+            bytes_sent: 0,
+            video_packets_sent: 0,
+            audio_packets_sent: 0,
+            send_rate_kbps: 0.0,
+            link: None,
+            timestamps_corrected: 0,
+        }
+    }
+
+    /// Whether the link is congested (always false without link stats)
+    pub fn congested(&self) -> bool {
+        self.link.as_ref().is_some_and(SrtLinkStats::congested)
+    }
+}
+
+/// SRT link statistics from `srt_bstats`
+///
+/// Interval values cover the time since the previous refresh.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SrtLinkStats {
+    /// Smoothed round-trip time in milliseconds
+    pub rtt_ms: f64,
+    /// Estimated link capacity in kbps
+    pub bandwidth_kbps: f64,
+    /// Sending rate including retransmissions, in kbps
+    pub send_rate_kbps: f64,
+    /// Data waiting in the send buffer, in milliseconds
+    pub send_buffer_ms: u32,
+    /// Data waiting in the send buffer, in bytes
+    pub send_buffer_bytes: u64,
+    /// Packets sent during the interval
+    pub packets_sent: u64,
+    /// Packets reported lost by the receiver during the interval
+    pub packets_lost: u64,
+    /// Packets lost since the stream started
+    pub packets_lost_total: u64,
+    /// Packets retransmitted since the stream started
+    pub packets_retransmitted_total: u64,
+    /// Packets dropped as too late to send since the stream started
+    pub packets_dropped_total: u64,
+}
+
+impl SrtLinkStats {
+    /// Packet loss during the interval, in percent
+    pub fn loss_percent(&self) -> f64 {
+        if self.packets_sent == 0 {
+            return 0.0;
+        }
+        self.packets_lost as f64 * 100.0 / self.packets_sent as f64
+    }
+
+    /// Whether the link can't keep up with the stream
+    ///
+    /// True when interval loss reaches [`CONGESTION_LOSS_PERCENT`] or the
+    /// sending rate exceeds the estimated bandwidth. A caller can lower the
+    /// encoder bitrate in response.
+    pub fn congested(&self) -> bool {
+        self.loss_percent() >= CONGESTION_LOSS_PERCENT
+            || (self.bandwidth_kbps > 0.0 && self.send_rate_kbps > self.bandwidth_kbps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol_detection() {
+        assert_eq!(
+            StreamProtocol::from_url("rtmp://live.twitch.tv/app/key"),
+            Some(StreamProtocol::Rtmp)
+        );
+        assert_eq!(
+            StreamProtocol::from_url("rtmps://live.youtube.com/app/key"),
+            Some(StreamProtocol::Rtmp)
+        );
+        assert_eq!(
+            StreamProtocol::from_url("srt://localhost:9999"),
+            Some(StreamProtocol::Srt)
+        );
+        assert_eq!(
+            StreamProtocol::from_url("rist://relay.example.com:5000"),
+            Some(StreamProtocol::Rist)
+        );
+        assert_eq!(
+            StreamProtocol::from_url("RIST://@[::]:5000?buffer=1000"),
+            Some(StreamProtocol::Rist)
+        );
+        assert_eq!(StreamProtocol::from_url("http://example.com"), None);
+    }
+
+    #[test]
+    fn test_rist_options() {
+        assert!(RistOptions::default().is_empty());
+        assert!(RistOptions::default().ffmpeg_options().is_empty());
+
+        let options = RistOptions {
+            buffer_ms: Some(1000),
+            secret: Some("topsecret".to_string()),
+            encryption: None,
+        };
+        assert!(options.validate().is_ok());
+        // A secret alone encrypts with AES-128
+        assert_eq!(
+            options.ffmpeg_options(),
+            vec![
+                ("buffer_size", "1000".to_string()),
+                ("secret", "topsecret".to_string()),
+                ("encryption", "128".to_string()),
+            ]
+        );
+
+        let no_secret = RistOptions {
+            encryption: Some(256),
+            ..Default::default()
+        };
+        assert!(no_secret.validate().is_err());
+        let bad_size = RistOptions {
+            secret: Some("topsecret".to_string()),
+            encryption: Some(192),
+            ..Default::default()
+        };
+        assert!(bad_size.validate().is_err());
+    }
+
+    #[test]
+    fn test_stream_config_default() {
+        let config = StreamConfig::default();
+        assert_eq!(config.width, 1920);
+        assert_eq!(config.height, 1080);
+        assert_eq!(config.fps, 30);
+        assert!(config.audio_codec.is_some());
+        assert_eq!(config.color_range, ColorRange::Full);
+    }
+
+    #[test]
+    fn test_srt_options() {
+        assert!(SrtOptions::default().is_empty());
+        assert!(SrtOptions::default().ffmpeg_options().is_empty());
+
+        let options = SrtOptions::default()
+            .with_latency_ms(120)
+            .with_passphrase("correct horse battery");
+        assert!(options.validate().is_ok());
+        // Latency goes to FFmpeg in microseconds, and a passphrase alone
+        // encrypts with AES-128
+        assert_eq!(
+            options.ffmpeg_options(),
+            vec![
+                ("latency", "120000".to_string()),
+                ("passphrase", "correct horse battery".to_string()),
+                ("pbkeylen", "16".to_string()),
+            ]
+        );
+        assert!(options.clone().with_pbkeylen(32).validate().is_ok());
+        assert!(options.with_pbkeylen(20).validate().is_err());
+
+        assert!(SrtOptions::default().with_passphrase("secret").validate().is_err());
+        assert!(SrtOptions::default().with_passphrase("x".repeat(80)).validate().is_err());
+        assert!(SrtOptions::default().with_pbkeylen(24).validate().is_err());
+    }
+
+    #[test]
+    fn test_reconnect_backoff() {
+        let policy = ReconnectPolicy::default();
+        let delays: Vec<u64> = (0..7).map(|n| policy.delay(n).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 8, 16, 30, 30]);
+        assert_eq!(policy.delay(u32::MAX), policy.max_delay);
+        assert_eq!(ReconnectPolicy::disabled().max_retries, 0);
+    }
+
+    #[test]
+    fn test_protocol_format_name() {
+        assert_eq!(StreamProtocol::Rtmp.format_name(), "flv");
+        assert_eq!(StreamProtocol::Srt.format_name(), "mpegts");
+        assert_eq!(StreamProtocol::Rist.format_name(), "mpegts");
+        assert_eq!(StreamProtocol::Rist.protocol_name(), "rist");
+        assert_eq!(StreamProtocol::Rist.to_string(), "RIST");
+    }
+}
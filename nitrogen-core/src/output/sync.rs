@@ -0,0 +1,110 @@
+//! A/V sync offset for muxed outputs
+//!
+//! Audio and video are timestamped independently, so a capture chain with a
+//! fixed audio delay (Bluetooth headsets, some DACs) puts them out of sync by
+//! a constant amount. [`AvSyncOffset`] shifts one of the streams before muxing:
+//! a positive offset delays audio, a negative offset delays video. Only ever
+//! adding a delay means no timestamp is pushed below where the encoder put it.
+
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+use ffmpeg::Rational;
+use ffmpeg_next as ffmpeg;
+
+/// Largest accepted A/V sync offset in either direction (ms)
+pub const MAX_AV_SYNC_OFFSET_MS: i32 = 5000;
+
+/// Shared A/V sync offset, adjustable while outputs are running
+///
+/// Clones share the same value, so the pipeline keeps one handle and hands
+/// clones to each recorder and stream output.
+#[derive(Debug, Clone, Default)]
+pub struct AvSyncOffset(Arc<AtomicI32>);
+
+impl AvSyncOffset {
+    /// Create an offset (positive delays audio, negative delays video)
+    pub fn new(offset_ms: i32) -> Self {
+        Self(Arc::new(AtomicI32::new(offset_ms)))
+    }
+
+    /// Current offset in milliseconds
+    pub fn get_ms(&self) -> i32 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Change the offset; takes effect on the next packet
+    pub fn set_ms(&self, offset_ms: i32) {
+        self.0.store(offset_ms, Ordering::Relaxed);
+    }
+
+    /// Delay for audio timestamps, in ticks of `time_base`
+    pub fn audio_delay(&self, time_base: Rational) -> i64 {
+        ms_to_ticks(self.get_ms().max(0), time_base)
+    }
+
+    /// Delay for video timestamps, in ticks of `time_base`
+    pub fn video_delay(&self, time_base: Rational) -> i64 {
+        ms_to_ticks(self.get_ms().min(0).saturating_neg(), time_base)
+    }
+}
+
+/// Convert milliseconds to ticks of `time_base`, rounding to nearest
+fn ms_to_ticks(ms: i32, time_base: Rational) -> i64 {
+    let num = i64::from(time_base.numerator()).max(1);
+    let den = i64::from(time_base.denominator());
+    let scale = num * 1000;
+    (i64::from(ms) * den + scale / 2) / scale
+}
+
+/// Apply a delay to a packet timestamp (never below zero)
+pub(crate) fn shift_ts(ts: i64, delay: i64) -> i64 {
+    if delay == 0 {
+        ts
+    } else {
+        ts.saturating_add(delay).max(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positive_offset_delays_audio_by_samples() {
+        let offset = AvSyncOffset::new(80);
+        let audio_tb = Rational::new(1, 48000);
+
+        // 80 ms at 48 kHz is 3840 samples
+        assert_eq!(offset.audio_delay(audio_tb), 3840);
+        assert_eq!(shift_ts(960, offset.audio_delay(audio_tb)), 960 + 3840);
+        assert_eq!(offset.video_delay(Rational::new(1, 60)), 0);
+    }
+
+    #[test]
+    fn test_negative_offset_delays_video() {
+        let offset = AvSyncOffset::new(-50);
+
+        assert_eq!(offset.audio_delay(Rational::new(1, 48000)), 0);
+        // 50 ms at 60 fps is 3 frames
+        assert_eq!(offset.video_delay(Rational::new(1, 60)), 3);
+        assert_eq!(offset.video_delay(Rational::new(1, 90000)), 4500);
+    }
+
+    #[test]
+    fn test_offset_is_shared_between_clones() {
+        let offset = AvSyncOffset::new(0);
+        let output_handle = offset.clone();
+
+        offset.set_ms(120);
+        assert_eq!(output_handle.get_ms(), 120);
+        assert_eq!(output_handle.audio_delay(Rational::new(1, 48000)), 5760);
+    }
+
+    #[test]
+    fn test_shift_never_negative() {
+        assert_eq!(shift_ts(-1024, 0), -1024);
+        assert_eq!(shift_ts(-1024, 512), 0);
+        assert_eq!(shift_ts(i64::MAX, 10), i64::MAX);
+    }
+}
@@ -0,0 +1,110 @@
+//! Timestamp fixup before muxing
+//!
+//! Capture occasionally hands over frames with duplicate or backwards
+//! timestamps (clock glitches, compositor resets). FFmpeg muxers reject those
+//! with "non-monotonic DTS" errors and the file ends up corrupt, so each
+//! muxed stream runs its timestamps through [`MonotonicTimestamps`] first.
+
+use tracing::{debug, warn};
+
+/// Keeps one stream's DTS strictly increasing
+///
+/// A DTS that doesn't advance is bumped to one tick past the previous one,
+/// and the PTS is moved along with it so it never falls behind its DTS.
+/// Streams without B-frames (PTS = DTS) therefore come out with strictly
+/// increasing PTS too; reordered B-frame PTS are left alone otherwise.
+#[derive(Debug, Default)]
+pub(crate) struct MonotonicTimestamps {
+    /// DTS of the previous packet
+    last_dts: Option<i64>,
+    /// Packets whose timestamps were changed
+    corrected: u64,
+}
+
+impl MonotonicTimestamps {
+    /// Fix up a packet's `(pts, dts)`, returning the values to mux
+    pub fn fix(&mut self, pts: i64, dts: i64, stream: &str) -> (i64, i64) {
+        let (mut fixed_pts, mut fixed_dts) = (pts, dts);
+
+        if let Some(last) = self.last_dts {
+            if fixed_dts <= last {
+                fixed_dts = last.saturating_add(1);
+            }
+        }
+        if fixed_pts < fixed_dts {
+            fixed_pts = fixed_dts;
+        }
+        self.last_dts = Some(fixed_dts);
+
+        if (fixed_pts, fixed_dts) != (pts, dts) {
+            self.corrected += 1;
+            if self.corrected == 1 || self.corrected % 100 == 0 {
+                warn!(
+                    "Non-monotonic {} timestamp (pts {}, dts {}) bumped to dts {}; {} corrected so far",
+                    stream, pts, dts, fixed_dts, self.corrected
+                );
+            } else {
+                debug!(
+                    "Corrected {} timestamp: dts {} -> {}",
+                    stream, dts, fixed_dts
+                );
+            }
+        }
+
+        (fixed_pts, fixed_dts)
+    }
+
+    /// Number of packets whose timestamps were corrected
+    pub fn corrected(&self) -> u64 {
+        self.corrected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strictly_increasing(values: &[i64]) -> bool {
+        values.windows(2).all(|w| w[0] < w[1])
+    }
+
+    #[test]
+    fn test_duplicate_and_backwards_pts() {
+        let mut fixer = MonotonicTimestamps::default();
+        // Duplicate at 1, backwards jump from 3 to 2
+        let input = [0, 1, 1, 3, 2, 4];
+
+        let output: Vec<(i64, i64)> = input.iter().map(|&ts| fixer.fix(ts, ts, "video")).collect();
+        let pts: Vec<i64> = output.iter().map(|&(pts, _)| pts).collect();
+        let dts: Vec<i64> = output.iter().map(|&(_, dts)| dts).collect();
+
+        assert!(strictly_increasing(&pts), "{:?}", pts);
+        assert!(strictly_increasing(&dts), "{:?}", dts);
+        assert_eq!(dts, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(fixer.corrected(), 3);
+    }
+
+    #[test]
+    fn test_monotonic_input_is_untouched() {
+        let mut fixer = MonotonicTimestamps::default();
+        for ts in [0, 1024, 2048, 3072] {
+            assert_eq!(fixer.fix(ts, ts, "audio"), (ts, ts));
+        }
+        assert_eq!(fixer.corrected(), 0);
+    }
+
+    #[test]
+    fn test_reordered_pts_kept() {
+        let mut fixer = MonotonicTimestamps::default();
+        // I P B B in decode order: PTS jumps ahead of DTS and comes back
+        assert_eq!(fixer.fix(0, -2, "video"), (0, -2));
+        assert_eq!(fixer.fix(3, -1, "video"), (3, -1));
+        assert_eq!(fixer.fix(1, 0, "video"), (1, 0));
+        assert_eq!(fixer.fix(2, 1, "video"), (2, 1));
+        assert_eq!(fixer.corrected(), 0);
+
+        // A repeated DTS carries its PTS along when it would fall behind
+        assert_eq!(fixer.fix(1, 1, "video"), (2, 2));
+        assert_eq!(fixer.corrected(), 1);
+    }
+}
@@ -7,14 +7,44 @@
 //!
 //! Includes a built-in HTTP signaling server for easy browser-based viewing:
 //! - `GET /` - Simple HTML viewer page with WebRTC client
-//! - `GET /offer` - Returns SDP offer as JSON
-//! - `POST /answer` - Accepts SDP answer as JSON
-//! - `GET /status` - Connection status
-
-use std::sync::atomic::{AtomicBool, Ordering};
+//! - `GET /offer` - Creates a peer connection, returns its id and SDP offer as JSON
+//! - `POST /answer` - Accepts the SDP answer for a peer id as JSON
+//! - `GET /status` - Connection status and viewer count
+//!
+//! ## Audio
+//!
+//! With audio enabled every peer also gets an Opus track.
+//!
+//! ## Sample Timing
+//!
+//! Sample durations come from the spacing of the packet timestamps, so the
+//! RTP clocks follow the encoders' clocks at any frame rate and through
+//! gaps, instead of assuming 30 fps video and 20 ms audio frames.
+//!
+//! ## Viewers
+//!
+//! Every offer creates a peer connection of its own, with its own tracks, so
+//! any number of browsers can watch at once. Encoded samples are written to
+//! the tracks of every peer. Peers that fail or close are dropped on the next
+//! sample, and so are offers that never get an answer.
+//!
+//! ## Simulcast
+//!
+//! With simulcast layers configured, each layer has its own encoder and is
+//! published as an RTP encoding (`a=rid`) of one video track. Peers that
+//! negotiate simulcast (SFUs) receive every layer and forward the one that
+//! fits each viewer. Direct peers such as the built-in viewer get a single
+//! layer picked from their measured round-trip time, switched at keyframes.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use parking_lot::Mutex;
 use tokio::sync::{broadcast, RwLock};
+use tokio::task::JoinHandle;
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
 
 use axum::{
     extract::State,
@@ -25,85 +55,273 @@ use axum::{
 };
 
 use webrtc::api::interceptor_registry::register_default_interceptors;
-use webrtc::api::media_engine::{MediaEngine, MIME_TYPE_H264, MIME_TYPE_OPUS};
-use webrtc::api::APIBuilder;
+use webrtc::api::media_engine::{
+    MediaEngine, MIME_TYPE_AV1, MIME_TYPE_H264, MIME_TYPE_OPUS, MIME_TYPE_VP8, MIME_TYPE_VP9,
+};
+use webrtc::api::{APIBuilder, API};
 use webrtc::ice_transport::ice_server::RTCIceServer;
 use webrtc::interceptor::registry::Registry;
 use webrtc::peer_connection::configuration::RTCConfiguration;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
 use webrtc::peer_connection::RTCPeerConnection;
-use webrtc::rtp_transceiver::rtp_codec::RTCRtpCodecCapability;
+use webrtc::rtp_transceiver::rtp_codec::{
+    RTCRtpCodecCapability, RTCRtpCodecParameters, RTPCodecType,
+};
+use webrtc::rtp_transceiver::rtp_sender::RTCRtpSender;
+use webrtc::rtp_transceiver::RTCPFeedback;
+use webrtc::stats::StatsReportType;
 use webrtc::track::track_local::track_local_static_sample::TrackLocalStaticSample;
 use webrtc::track::track_local::TrackLocal;
 use webrtc::media::Sample;
 
-use crate::encode::EncodedPacket;
+use super::webrtc_config::{select_layer, IceServer, SimulcastLayer, WebRTCConfig};
+use crate::encode::{EncodedAudioPacket, EncodedPacket};
 use crate::error::{NitrogenError, Result};
 
-/// WebRTC output configuration
-#[derive(Debug, Clone)]
-pub struct WebRTCConfig {
-    /// ICE servers for NAT traversal
-    pub ice_servers: Vec<String>,
-    /// Video codec (h264 recommended for compatibility)
-    pub video_codec: String,
-    /// Video payload type
-    pub video_payload_type: u8,
-    /// Audio enabled
-    pub audio_enabled: bool,
+/// How often the RTT of a direct peer is sampled for layer selection
+const RTT_PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long an offer waits for its answer before the peer is dropped
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Clock rate of Opus RTP timestamps and of the audio encoder's time base
+const OPUS_CLOCK_RATE: u32 = 48_000;
+
+/// Ticks in one 20 ms Opus frame, for packets without a duration
+const OPUS_FRAME_TICKS: i64 = 960;
+
+impl From<&IceServer> for RTCIceServer {
+    fn from(server: &IceServer) -> Self {
+        RTCIceServer {
+            urls: server.urls.clone(),
+            username: server.username.clone().unwrap_or_default(),
+            credential: server.credential.clone().unwrap_or_default(),
+            ..Default::default()
+        }
+    }
 }
 
-impl Default for WebRTCConfig {
-    fn default() -> Self {
-        Self {
-            ice_servers: vec!["stun:stun.l.google.com:19302".to_string()],
-            video_codec: "h264".to_string(),
-            video_payload_type: 96,
-            audio_enabled: true,
+impl WebRTCConfig {
+    /// Peer connection configuration with the ICE servers
+    fn rtc_configuration(&self) -> RTCConfiguration {
+        RTCConfiguration {
+            ice_servers: self.ice_servers.iter().map(RTCIceServer::from).collect(),
+            ..Default::default()
         }
     }
+
+    /// RTP capability of the video codec, the only one offered
+    fn video_codec_capability(&self) -> Result<RTCRtpCodecCapability> {
+        let (mime_type, sdp_fmtp_line) = match self.video_codec.to_lowercase().as_str() {
+            "h264" => (
+                MIME_TYPE_H264,
+                "level-asymmetry-allowed=1;packetization-mode=1;profile-level-id=42e01f",
+            ),
+            "vp8" => (MIME_TYPE_VP8, ""),
+            "vp9" => (MIME_TYPE_VP9, "profile-id=0"),
+            "av1" => (MIME_TYPE_AV1, "profile-id=0"),
+            other => {
+                return Err(NitrogenError::webrtc(format!(
+                    "Unknown WebRTC video codec '{}'",
+                    other
+                )))
+            }
+        };
+        // Same feedback the default codecs ask for: loss recovery and REMB
+        let rtcp_feedback = [("goog-remb", ""), ("ccm", "fir"), ("nack", ""), ("nack", "pli")]
+            .into_iter()
+            .map(|(typ, parameter)| RTCPFeedback {
+                typ: typ.to_string(),
+                parameter: parameter.to_string(),
+            })
+            .collect();
+        Ok(RTCRtpCodecCapability {
+            mime_type: mime_type.to_string(),
+            clock_rate: 90_000,
+            channels: 0,
+            sdp_fmtp_line: sdp_fmtp_line.to_string(),
+            rtcp_feedback,
+        })
+    }
+}
+
+/// Layer selection state for peers that don't negotiate simulcast
+#[derive(Debug, Default)]
+struct LayerSwitch {
+    /// Set once the remote answer accepts simulcast
+    negotiated: AtomicBool,
+    /// Layer currently forwarded on the primary track
+    active: AtomicUsize,
+    /// Layer to switch to at its next keyframe
+    pending: AtomicUsize,
+}
+
+/// One viewer's peer connection and the tracks feeding it
+struct Peer {
+    /// Peer connection
+    connection: Arc<RTCPeerConnection>,
+    /// Video track, or one per simulcast layer (the first is the primary)
+    video_tracks: Vec<Arc<TrackLocalStaticSample>>,
+    /// Opus track (if audio is enabled)
+    audio_track: Option<Arc<TrackLocalStaticSample>>,
+    /// Which layer this peer receives when it didn't negotiate simulcast
+    layer_switch: Arc<LayerSwitch>,
+    /// RTT sampling task for layer selection
+    rtt_monitor: Option<JoinHandle<()>>,
+    /// When the offer was created
+    created: Instant,
+    /// Set once the answer arrived
+    answered: AtomicBool,
+}
+
+impl Peer {
+    /// Whether the peer is gone or never answered its offer
+    fn is_stale(&self) -> bool {
+        let state = self.connection.connection_state();
+        matches!(state, RTCPeerConnectionState::Failed | RTCPeerConnectionState::Closed)
+            || (!self.answered.load(Ordering::SeqCst) && self.created.elapsed() > ANSWER_TIMEOUT)
+    }
+
+    /// Whether media is flowing to the peer
+    fn is_connected(&self) -> bool {
+        self.connection.connection_state() == RTCPeerConnectionState::Connected
+    }
+
+    /// Track that carries a packet of simulcast `layer` to this peer, if any
+    ///
+    /// When the peer negotiated simulcast each layer goes to its own
+    /// encoding. Otherwise the layer picked by RTT is sent on the primary
+    /// track, and a new layer takes over at its first keyframe so the
+    /// decoder never sees a broken GOP.
+    fn layer_track(
+        &self,
+        id: Uuid,
+        layer: usize,
+        packet: &EncodedPacket,
+        layers: &[SimulcastLayer],
+    ) -> Option<&Arc<TrackLocalStaticSample>> {
+        let switch = &self.layer_switch;
+        if switch.negotiated.load(Ordering::SeqCst) {
+            return self.video_tracks.get(layer);
+        }
+
+        let pending = switch.pending.load(Ordering::SeqCst);
+        if layer == pending && packet.keyframe && switch.active.load(Ordering::SeqCst) != pending {
+            switch.active.store(pending, Ordering::SeqCst);
+            info!("WebRTC peer {} switched to layer {}", id, layers[pending].rid);
+        }
+        (layer == switch.active.load(Ordering::SeqCst)).then(|| &self.video_tracks[0])
+    }
+
+    /// Stop the RTT monitor and close the connection
+    async fn close(self) -> Result<()> {
+        if let Some(monitor) = self.rtt_monitor {
+            monitor.abort();
+        }
+        self.connection
+            .close()
+            .await
+            .map_err(|e| NitrogenError::webrtc(format!("Failed to close connection: {}", e)))
+    }
 }
 
 /// WebRTC output sink
 ///
-/// Streams encoded video/audio over WebRTC to connected peers.
+/// Streams encoded video/audio over WebRTC to every connected peer.
 pub struct WebRTCOutput {
     /// Configuration
     config: WebRTCConfig,
-    /// Peer connection
-    peer_connection: Option<Arc<RTCPeerConnection>>,
-    /// Video track
-    video_track: Option<Arc<TrackLocalStaticSample>>,
-    /// Audio track (if enabled)
-    audio_track: Option<Arc<TrackLocalStaticSample>>,
+    /// API that creates the peer connections (set by `init`)
+    api: Option<API>,
+    /// Peers by the id handed out with their offer
+    peers: RwLock<HashMap<Uuid, Peer>>,
     /// Running flag
     running: AtomicBool,
+    /// Encoder keyframe request flag, set when a peer connects
+    keyframe_requester: Option<Arc<AtomicBool>>,
+    /// Keyframe request flags of the layer encoders, in layer order
+    layer_keyframe_requesters: Vec<Arc<AtomicBool>>,
+    /// Sample clocks of the video layers (one without simulcast)
+    video_clocks: Mutex<Vec<SampleClock>>,
 }
 
 impl WebRTCOutput {
     /// Create a new WebRTC output with the given configuration
     pub async fn new(config: WebRTCConfig) -> Result<Self> {
         info!("Creating WebRTC output");
-        debug!("ICE servers: {:?}", config.ice_servers);
+        let urls: Vec<&str> = config
+            .ice_servers
+            .iter()
+            .flat_map(|server| server.urls.iter().map(String::as_str))
+            .collect();
+        debug!("ICE servers: {:?}", urls);
 
+        let video_clocks = vec![SampleClock::new(config.fps); config.simulcast.len().max(1)];
         Ok(Self {
             config,
-            peer_connection: None,
-            video_track: None,
-            audio_track: None,
+            api: None,
+            peers: RwLock::new(HashMap::new()),
             running: AtomicBool::new(false),
+            keyframe_requester: None,
+            layer_keyframe_requesters: Vec::new(),
+            video_clocks: Mutex::new(video_clocks),
         })
     }
 
-    /// Initialize the peer connection
+    /// Set the encoder keyframe request flag
+    ///
+    /// When set, a keyframe is requested every time a peer reaches the
+    /// `Connected` state so the viewer can start decoding immediately.
+    /// Must be called before [`init`](Self::init).
+    pub fn set_keyframe_requester(&mut self, requester: Arc<AtomicBool>) {
+        self.keyframe_requester = Some(requester);
+    }
+
+    /// Set the keyframe request flags of the simulcast layer encoders
+    ///
+    /// One flag per configured layer, in the same order. Every layer gets an
+    /// IDR when a peer connects, and a layer also gets one before a direct
+    /// peer is switched to it. Must be called before [`init`](Self::init).
+    pub fn set_layer_keyframe_requesters(&mut self, requesters: Vec<Arc<AtomicBool>>) {
+        self.layer_keyframe_requesters = requesters;
+    }
+
+    /// Set up the codecs and interceptors peer connections are created with
     pub async fn init(&mut self) -> Result<()> {
+        let video_codec = self.config.video_codec_capability()?;
+        let mime_type = video_codec.mime_type.clone();
+
         // Create media engine
         let mut media_engine = MediaEngine::default();
 
-        // Register H264 codec
+        // Offer the configured video codec only: the encoder produces nothing
+        // else, so a browser must not pick another one from the SDP
         media_engine
-            .register_default_codecs()
+            .register_codec(
+                RTCRtpCodecParameters {
+                    capability: video_codec,
+                    payload_type: self.config.video_payload_type,
+                    ..Default::default()
+                },
+                RTPCodecType::Video,
+            )
+            .map_err(|e| NitrogenError::webrtc(format!("Failed to register codecs: {}", e)))?;
+        media_engine
+            .register_codec(
+                RTCRtpCodecParameters {
+                    capability: RTCRtpCodecCapability {
+                        mime_type: MIME_TYPE_OPUS.to_string(),
+                        clock_rate: OPUS_CLOCK_RATE,
+                        channels: 2,
+                        sdp_fmtp_line: "minptime=10;useinbandfec=1".to_string(),
+                        rtcp_feedback: Vec::new(),
+                    },
+                    payload_type: 111,
+                    ..Default::default()
+                },
+                RTPCodecType::Audio,
+            )
             .map_err(|e| NitrogenError::webrtc(format!("Failed to register codecs: {}", e)))?;
 
         // Create interceptor registry
@@ -111,62 +329,59 @@ impl WebRTCOutput {
         registry = register_default_interceptors(registry, &mut media_engine)
             .map_err(|e| NitrogenError::webrtc(format!("Failed to register interceptors: {}", e)))?;
 
-        // Create API
-        let api = APIBuilder::new()
-            .with_media_engine(media_engine)
-            .with_interceptor_registry(registry)
-            .build();
+        // Create API (each peer connection gets its own copy of the media engine)
+        self.api = Some(
+            APIBuilder::new()
+                .with_media_engine(media_engine)
+                .with_interceptor_registry(registry)
+                .build(),
+        );
+        self.running.store(true, Ordering::SeqCst);
 
-        // Create peer connection configuration
-        let ice_servers: Vec<RTCIceServer> = self
-            .config
-            .ice_servers
-            .iter()
-            .map(|url| RTCIceServer {
-                urls: vec![url.clone()],
-                ..Default::default()
-            })
-            .collect();
+        if self.config.simulcast_enabled() {
+            let rids: Vec<&str> = self.config.simulcast.iter().map(|l| l.rid.as_str()).collect();
+            info!("WebRTC simulcast enabled with layers {:?}", rids);
+        }
+        info!("WebRTC output initialized ({})", mime_type);
+        Ok(())
+    }
 
-        let rtc_config = RTCConfiguration {
-            ice_servers,
-            ..Default::default()
-        };
+    /// Create a peer connection for a new viewer with its tracks
+    async fn new_peer(&self) -> Result<Peer> {
+        let api = self
+            .api
+            .as_ref()
+            .ok_or_else(|| NitrogenError::webrtc("WebRTC output not initialized".to_string()))?;
 
-        // Create peer connection
         let peer_connection = api
-            .new_peer_connection(rtc_config)
+            .new_peer_connection(self.config.rtc_configuration())
             .await
             .map_err(|e| NitrogenError::webrtc(format!("Failed to create peer connection: {}", e)))?;
-
         let peer_connection = Arc::new(peer_connection);
 
-        // Create video track
-        let video_track = Arc::new(TrackLocalStaticSample::new(
-            RTCRtpCodecCapability {
-                mime_type: MIME_TYPE_H264.to_string(),
-                ..Default::default()
-            },
-            "video".to_string(),
-            "nitrogen-video".to_string(),
-        ));
-
-        // Add video track to peer connection
-        let rtp_sender = peer_connection
-            .add_track(video_track.clone() as Arc<dyn TrackLocal + Send + Sync>)
-            .await
-            .map_err(|e| NitrogenError::webrtc(format!("Failed to add video track: {}", e)))?;
+        // Create video track(s)
+        let codec = self.config.video_codec_capability()?;
+        let video_tracks = if self.config.simulcast_enabled() {
+            add_simulcast_tracks(&peer_connection, codec, &self.config.simulcast).await?
+        } else {
+            let video_track = Arc::new(TrackLocalStaticSample::new(
+                codec,
+                "video".to_string(),
+                "nitrogen-video".to_string(),
+            ));
 
-        // Spawn RTCP reader
-        tokio::spawn(async move {
-            let mut rtcp_buf = vec![0u8; 1500];
-            while let Ok((_, _)) = rtp_sender.read(&mut rtcp_buf).await {}
-        });
+            // Add video track to peer connection
+            let rtp_sender = peer_connection
+                .add_track(video_track.clone() as Arc<dyn TrackLocal + Send + Sync>)
+                .await
+                .map_err(|e| NitrogenError::webrtc(format!("Failed to add video track: {}", e)))?;
+            spawn_rtcp_reader(rtp_sender);
 
-        self.video_track = Some(video_track);
+            vec![video_track]
+        };
 
-        // Create audio track if enabled
-        if self.config.audio_enabled {
+        // Create audio track if enabled; it is part of the offer
+        let audio_track = if self.config.audio_enabled {
             let audio_track = Arc::new(TrackLocalStaticSample::new(
                 RTCRtpCodecCapability {
                     mime_type: MIME_TYPE_OPUS.to_string(),
@@ -180,78 +395,120 @@ impl WebRTCOutput {
                 .add_track(audio_track.clone() as Arc<dyn TrackLocal + Send + Sync>)
                 .await
                 .map_err(|e| NitrogenError::webrtc(format!("Failed to add audio track: {}", e)))?;
+            spawn_rtcp_reader(audio_rtp_sender);
 
-            // Spawn RTCP reader for audio
-            tokio::spawn(async move {
-                let mut rtcp_buf = vec![0u8; 1500];
-                while let Ok((_, _)) = audio_rtp_sender.read(&mut rtcp_buf).await {}
-            });
+            Some(audio_track)
+        } else {
+            None
+        };
 
-            self.audio_track = Some(audio_track);
-        }
+        let layer_switch = Arc::new(LayerSwitch::default());
+        let rtt_monitor = self.config.simulcast_enabled().then(|| {
+            tokio::spawn(monitor_rtt(
+                peer_connection.clone(),
+                self.config.simulcast.clone(),
+                layer_switch.clone(),
+                self.layer_keyframe_requesters.clone(),
+            ))
+        });
+
+        Ok(Peer {
+            connection: peer_connection,
+            video_tracks,
+            audio_track,
+            layer_switch,
+            rtt_monitor,
+            created: Instant::now(),
+            answered: AtomicBool::new(false),
+        })
+    }
+
+    /// Create a peer connection and its SDP offer for a new viewer
+    ///
+    /// Returns the id the answer has to be sent back with, and the offer.
+    pub async fn create_offer(&self) -> Result<(Uuid, String)> {
+        let id = Uuid::new_v4();
+        let peer = self.new_peer().await?;
 
         // Set up connection state callback
-        peer_connection.on_peer_connection_state_change(Box::new(move |state: RTCPeerConnectionState| {
-            info!("WebRTC peer connection state: {:?}", state);
+        let keyframe_requester = self.keyframe_requester.clone();
+        let layer_requesters = self.layer_keyframe_requesters.clone();
+        peer.connection.on_peer_connection_state_change(Box::new(move |state: RTCPeerConnectionState| {
+            info!("WebRTC peer {} connection state: {:?}", id, state);
+
+            if state == RTCPeerConnectionState::Connected {
+                // New viewer needs an IDR to start decoding
+                if let Some(ref requester) = keyframe_requester {
+                    debug!("Requesting keyframe for newly connected peer");
+                    requester.store(true, Ordering::SeqCst);
+                }
+                for requester in &layer_requesters {
+                    requester.store(true, Ordering::SeqCst);
+                }
+            }
 
             if state == RTCPeerConnectionState::Failed {
-                error!("WebRTC peer connection failed");
+                error!("WebRTC peer {} connection failed", id);
             }
 
             Box::pin(async {})
         }));
 
-        self.peer_connection = Some(peer_connection);
-        self.running.store(true, Ordering::SeqCst);
-
-        info!("WebRTC output initialized");
-        Ok(())
-    }
-
-    /// Create an SDP offer for signaling
-    pub async fn create_offer(&self) -> Result<String> {
-        let pc = self.peer_connection.as_ref()
-            .ok_or_else(|| NitrogenError::webrtc("Peer connection not initialized".to_string()))?;
-
-        let offer = pc
-            .create_offer(None)
-            .await
-            .map_err(|e| NitrogenError::webrtc(format!("Failed to create offer: {}", e)))?;
-
-        pc.set_local_description(offer.clone())
-            .await
-            .map_err(|e| NitrogenError::webrtc(format!("Failed to set local description: {}", e)))?;
-
-        Ok(offer.sdp)
+        match local_offer(&peer.connection).await {
+            Ok(sdp) => {
+                self.peers.write().await.insert(id, peer);
+                debug!("Created WebRTC offer for peer {}", id);
+                Ok((id, sdp))
+            }
+            Err(e) => {
+                let _ = peer.close().await;
+                Err(e)
+            }
+        }
     }
 
-    /// Set the remote SDP answer from signaling
-    pub async fn set_answer(&self, sdp: &str) -> Result<()> {
-        let pc = self.peer_connection.as_ref()
-            .ok_or_else(|| NitrogenError::webrtc("Peer connection not initialized".to_string()))?;
+    /// Set the remote SDP answer of peer `id` from signaling
+    pub async fn set_answer(&self, id: Uuid, sdp: &str) -> Result<()> {
+        let peers = self.peers.read().await;
+        let peer = peers
+            .get(&id)
+            .ok_or_else(|| NitrogenError::webrtc(format!("Unknown WebRTC peer {}", id)))?;
 
         let answer = RTCSessionDescription::answer(sdp.to_string())
             .map_err(|e| NitrogenError::webrtc(format!("Invalid SDP answer: {}", e)))?;
 
-        pc.set_remote_description(answer)
+        peer.connection
+            .set_remote_description(answer)
             .await
             .map_err(|e| NitrogenError::webrtc(format!("Failed to set remote description: {}", e)))?;
+        peer.answered.store(true, Ordering::SeqCst);
+
+        if self.config.simulcast_enabled() {
+            let negotiated = sdp.contains("a=simulcast:");
+            peer.layer_switch.negotiated.store(negotiated, Ordering::SeqCst);
+            if negotiated {
+                info!("Peer {} accepted simulcast, sending all layers", id);
+            } else {
+                info!("Peer {} doesn't support simulcast, sending one layer chosen by RTT", id);
+            }
+        }
 
         Ok(())
     }
 
     /// Run the WebRTC output, consuming encoded packets from the channel
     pub async fn run(&self, mut video_rx: broadcast::Receiver<Arc<EncodedPacket>>) -> Result<()> {
-        let video_track = self.video_track.as_ref()
-            .ok_or_else(|| NitrogenError::webrtc("Video track not initialized".to_string()))?;
+        if self.api.is_none() {
+            return Err(NitrogenError::webrtc("WebRTC output not initialized".to_string()));
+        }
 
         info!("WebRTC output started");
 
         while self.running.load(Ordering::SeqCst) {
             match video_rx.recv().await {
                 Ok(packet) => {
-                    // Convert encoded packet to RTP and send
-                    if let Err(e) = self.send_video_packet(video_track, &packet).await {
+                    // Convert encoded packet to RTP and send to every peer
+                    if let Err(e) = self.write_video_packet(&packet).await {
                         warn!("Failed to send video packet: {}", e);
                     }
                 }
@@ -268,41 +525,213 @@ impl WebRTCOutput {
         Ok(())
     }
 
-    /// Send a video packet over RTP
-    async fn send_video_packet(
+    /// Run the WebRTC output with audio, consuming both channels
+    ///
+    /// Stops when the video channel closes or the output stops; a closed
+    /// audio channel only ends the audio.
+    pub async fn run_av(
         &self,
-        track: &Arc<TrackLocalStaticSample>,
-        packet: &EncodedPacket,
+        mut video_rx: broadcast::Receiver<Arc<EncodedPacket>>,
+        mut audio_rx: broadcast::Receiver<Arc<EncodedAudioPacket>>,
     ) -> Result<()> {
-        // TrackLocalStaticSample handles RTP packetization automatically
-        // We just need to send the encoded H.264 frame data
+        if self.api.is_none() {
+            return Err(NitrogenError::webrtc("WebRTC output not initialized".to_string()));
+        }
+
+        info!("WebRTC output started with audio");
+
+        let mut audio_clock = SampleClock::new(OPUS_CLOCK_RATE);
+        let mut audio_open = true;
+        while self.running.load(Ordering::SeqCst) {
+            tokio::select! {
+                video = video_rx.recv() => match video {
+                    Ok(packet) => {
+                        if let Err(e) = self.write_video_packet(&packet).await {
+                            warn!("Failed to send video packet: {}", e);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!("WebRTC output lagged by {} frames", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        info!("Video channel closed, stopping WebRTC output");
+                        break;
+                    }
+                },
+                audio = audio_rx.recv(), if audio_open => match audio {
+                    Ok(packet) => {
+                        let duration = audio_clock.opus_duration(&packet);
+                        if let Err(e) = self.write_audio_packet(&packet, duration).await {
+                            warn!("Failed to send audio packet: {}", e);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        // The gap shows up in the next packet's timestamp
+                        warn!("WebRTC audio lagged by {} packets", n);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => audio_open = false,
+                },
+            }
+        }
 
+        Ok(())
+    }
+
+    /// Write a single encoded video packet to the video track of every peer
+    pub async fn write_video_packet(&self, packet: &EncodedPacket) -> Result<()> {
+        let sample = self.video_sample(0, packet);
+        self.send_to_peers(&sample, |_, peer| Some(&peer.video_tracks[0])).await
+    }
+
+    /// Write an encoded Opus packet lasting `duration` to every peer
+    ///
+    /// Does nothing when audio is disabled.
+    pub async fn write_audio_packet(
+        &self,
+        packet: &EncodedAudioPacket,
+        duration: Duration,
+    ) -> Result<()> {
         let sample = Sample {
             data: bytes::Bytes::copy_from_slice(&packet.data),
-            duration: std::time::Duration::from_millis(33), // ~30fps default
+            duration,
             ..Default::default()
         };
+        self.send_to_peers(&sample, |_, peer| peer.audio_track.as_ref()).await
+    }
+
+    /// Write an encoded packet produced by the encoder of a simulcast layer
+    ///
+    /// Without simulcast only layer 0 is sent. With it, every peer gets the
+    /// layers it negotiated or the one its RTT allows, see
+    /// [`active_layer`](Self::active_layer).
+    pub async fn write_layer_packet(&self, layer: usize, packet: &EncodedPacket) -> Result<()> {
+        if !self.config.simulcast_enabled() {
+            return if layer == 0 {
+                self.write_video_packet(packet).await
+            } else {
+                Ok(())
+            };
+        }
 
-        track
-            .write_sample(&sample)
+        let layers = &self.config.simulcast;
+        if layer >= layers.len() {
+            return Err(NitrogenError::webrtc(format!("No simulcast layer {}", layer)));
+        }
+        let sample = self.video_sample(layer, packet);
+        self.send_to_peers(&sample, |id, peer| peer.layer_track(id, layer, packet, layers))
             .await
-            .map_err(|e| NitrogenError::webrtc(format!("Failed to write sample: {}", e)))?;
+    }
 
-        Ok(())
+    /// RTP sample of an encoded video packet from the encoder of `layer`
+    ///
+    /// Video timestamps count frames, so a sample lasts until the next
+    /// frame's timestamp and the first one a frame at the configured rate.
+    fn video_sample(&self, layer: usize, packet: &EncodedPacket) -> Sample {
+        // TrackLocalStaticSample handles RTP packetization automatically
+        // We just need to send the encoded frame data
+        let duration = self
+            .video_clocks
+            .lock()
+            .get_mut(layer)
+            .map_or(Duration::ZERO, |clock| clock.duration(packet.pts, 1));
+        Sample {
+            data: bytes::Bytes::copy_from_slice(&packet.data),
+            duration,
+            ..Default::default()
+        }
+    }
+
+    /// Send `sample` on the track `track` picks for each live peer
+    ///
+    /// Every peer is tried even if one fails; the first error is returned.
+    /// Stale peers are skipped and removed afterwards.
+    async fn send_to_peers<F>(&self, sample: &Sample, track: F) -> Result<()>
+    where
+        F: for<'p> Fn(Uuid, &'p Peer) -> Option<&'p Arc<TrackLocalStaticSample>>,
+    {
+        let mut result = Ok(());
+        let mut stale = false;
+        {
+            let peers = self.peers.read().await;
+            for (&id, peer) in peers.iter() {
+                if peer.is_stale() {
+                    stale = true;
+                    continue;
+                }
+                let Some(track) = track(id, peer) else {
+                    continue;
+                };
+                if let Err(e) = track.write_sample(sample).await {
+                    debug!("Failed to send sample to peer {}: {}", id, e);
+                    if result.is_ok() {
+                        result = Err(NitrogenError::webrtc(format!(
+                            "Failed to write sample: {}",
+                            e
+                        )));
+                    }
+                }
+            }
+        }
+        if stale {
+            self.prune_peers().await;
+        }
+        result
+    }
+
+    /// Close and forget peers that failed, closed or never answered
+    async fn prune_peers(&self) {
+        let stale: Vec<(Uuid, Peer)> = {
+            let mut peers = self.peers.write().await;
+            let ids: Vec<Uuid> = peers
+                .iter()
+                .filter(|(_, peer)| peer.is_stale())
+                .map(|(&id, _)| id)
+                .collect();
+            ids.into_iter()
+                .filter_map(|id| peers.remove(&id).map(|peer| (id, peer)))
+                .collect()
+        };
+        for (id, peer) in stale {
+            info!("Dropping WebRTC peer {}", id);
+            if let Err(e) = peer.close().await {
+                debug!("Closing peer {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Number of peers receiving media
+    pub async fn viewer_count(&self) -> usize {
+        self.peers.read().await.values().filter(|peer| peer.is_connected()).count()
+    }
+
+    /// Number of peers, including ones still connecting
+    pub async fn peer_count(&self) -> usize {
+        self.peers.read().await.len()
+    }
+
+    /// Layer currently sent to peer `id` if it didn't negotiate simulcast
+    pub async fn active_layer(&self, id: Uuid) -> Option<usize> {
+        let peers = self.peers.read().await;
+        peers
+            .get(&id)
+            .map(|peer| peer.layer_switch.active.load(Ordering::SeqCst))
     }
 
-    /// Stop the WebRTC output
+    /// Stop the WebRTC output and close every peer connection
     pub async fn stop(&mut self) -> Result<()> {
         info!("Stopping WebRTC output");
         self.running.store(false, Ordering::SeqCst);
 
-        if let Some(pc) = self.peer_connection.take() {
-            pc.close()
-                .await
-                .map_err(|e| NitrogenError::webrtc(format!("Failed to close connection: {}", e)))?;
+        let peers: Vec<Peer> = self.peers.write().await.drain().map(|(_, peer)| peer).collect();
+        let mut result = Ok(());
+        for peer in peers {
+            if let Err(e) = peer.close().await {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
         }
-
-        Ok(())
+        result
     }
 
     /// Check if the output is running
@@ -319,7 +748,262 @@ impl WebRTCOutput {
 impl Drop for WebRTCOutput {
     fn drop(&mut self) {
         self.running.store(false, Ordering::SeqCst);
+        for peer in self.peers.get_mut().values_mut() {
+            if let Some(monitor) = peer.rtt_monitor.take() {
+                monitor.abort();
+            }
+        }
+    }
+}
+
+/// Create the SDP offer of a new peer connection and apply it locally
+async fn local_offer(pc: &RTCPeerConnection) -> Result<String> {
+    let offer = pc
+        .create_offer(None)
+        .await
+        .map_err(|e| NitrogenError::webrtc(format!("Failed to create offer: {}", e)))?;
+
+    pc.set_local_description(offer.clone())
+        .await
+        .map_err(|e| NitrogenError::webrtc(format!("Failed to set local description: {}", e)))?;
+
+    Ok(offer.sdp)
+}
+
+/// Read RTCP from a sender until its connection goes away
+///
+/// The interceptors (NACK, receiver reports) only see RTCP that is read.
+fn spawn_rtcp_reader(sender: Arc<RTCRtpSender>) {
+    tokio::spawn(async move {
+        let mut rtcp_buf = vec![0u8; 1500];
+        while let Ok((_, _)) = sender.read(&mut rtcp_buf).await {}
+    });
+}
+
+/// Add one RID-tagged track per simulcast layer as encodings of a single sender
+///
+/// Returns the tracks in layer order.
+async fn add_simulcast_tracks(
+    peer_connection: &Arc<RTCPeerConnection>,
+    codec: RTCRtpCodecCapability,
+    layers: &[SimulcastLayer],
+) -> Result<Vec<Arc<TrackLocalStaticSample>>> {
+    let tracks: Vec<_> = layers
+        .iter()
+        .map(|layer| {
+            Arc::new(TrackLocalStaticSample::new_with_rid(
+                codec.clone(),
+                "video".to_string(),
+                layer.rid.clone(),
+                "nitrogen-video".to_string(),
+            ))
+        })
+        .collect();
+
+    let rtp_sender = peer_connection
+        .add_track(tracks[0].clone() as Arc<dyn TrackLocal + Send + Sync>)
+        .await
+        .map_err(|e| NitrogenError::webrtc(format!("Failed to add video track: {}", e)))?;
+    for (track, layer) in tracks.iter().zip(layers).skip(1) {
+        rtp_sender
+            .add_encoding(track.clone() as Arc<dyn TrackLocal + Send + Sync>)
+            .await
+            .map_err(|e| {
+                NitrogenError::webrtc(format!(
+                    "Failed to add simulcast layer {}: {}",
+                    layer.rid, e
+                ))
+            })?;
+    }
+    spawn_rtcp_reader(rtp_sender);
+
+    Ok(tracks)
+}
+
+
+/// Sample durations from the spacing of packet timestamps
+///
+/// RTP timestamps advance by each sample's duration. Giving a sample the
+/// time since the previous packet keeps them on the encoder's clock through
+/// gaps (lagged or dropped packets), one packet late at most. The first
+/// packet, and any out of order, use a fallback duration.
+#[derive(Debug, Clone)]
+struct SampleClock {
+    /// Timestamp ticks per second
+    rate: u32,
+    /// Timestamp of the newest packet so far
+    last_pts: Option<i64>,
+}
+
+impl SampleClock {
+    /// Clock for timestamps at `rate` ticks per second
+    fn new(rate: u32) -> Self {
+        Self {
+            rate,
+            last_pts: None,
+        }
+    }
+
+    /// Duration of the sample with timestamp `pts`
+    fn duration(&mut self, pts: i64, fallback_ticks: i64) -> Duration {
+        let ticks = match self.last_pts {
+            Some(last) if pts > last => pts - last,
+            Some(_) => return self.ticks(fallback_ticks),
+            None => fallback_ticks,
+        };
+        self.last_pts = Some(pts);
+        self.ticks(ticks)
+    }
+
+    /// Duration of an Opus packet (time base 1/48000)
+    fn opus_duration(&mut self, packet: &EncodedAudioPacket) -> Duration {
+        let fallback = if packet.duration > 0 {
+            packet.duration
+        } else {
+            OPUS_FRAME_TICKS
+        };
+        self.duration(packet.pts, fallback)
+    }
+
+    /// `ticks` as a duration
+    fn ticks(&self, ticks: i64) -> Duration {
+        Duration::from_nanos(ticks.max(0) as u64 * 1_000_000_000 / u64::from(self.rate.max(1)))
+    }
+}
+
+/// Sample the peer's RTT and steer direct peers to the layer that fits it
+async fn monitor_rtt(
+    pc: Arc<RTCPeerConnection>,
+    layers: Vec<SimulcastLayer>,
+    switch: Arc<LayerSwitch>,
+    requesters: Vec<Arc<AtomicBool>>,
+) {
+    let mut interval = tokio::time::interval(RTT_PROBE_INTERVAL);
+    loop {
+        interval.tick().await;
+        if switch.negotiated.load(Ordering::SeqCst) {
+            continue; // The SFU picks layers itself
+        }
+        let Some(rtt) = measure_rtt(&pc).await else {
+            continue;
+        };
+
+        let active = switch.active.load(Ordering::SeqCst);
+        let target = select_layer(&layers, rtt, active);
+        if target != switch.pending.swap(target, Ordering::SeqCst) {
+            debug!(
+                "RTT {:.0}ms, moving direct peer to layer {}",
+                rtt.as_secs_f64() * 1000.0,
+                layers[target].rid
+            );
+            if target != active {
+                // The switch happens at the new layer's next keyframe
+                if let Some(requester) = requesters.get(target) {
+                    requester.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+    }
+}
+
+/// Current RTT of the nominated ICE candidate pair
+async fn measure_rtt(pc: &RTCPeerConnection) -> Option<Duration> {
+    let report = pc.get_stats().await;
+    report.reports.values().find_map(|stats| match stats {
+        StatsReportType::CandidatePair(pair)
+            if pair.nominated && pair.current_round_trip_time > 0.0 =>
+        {
+            Some(Duration::from_secs_f64(pair.current_round_trip_time))
+        }
+        _ => None,
+    })
+}
+
+/// Forward encoded packets to a WebRTC output, one receiver per layer
+///
+/// Without simulcast pass a single receiver. Opus packets from `audio` go
+/// to the audio tracks. Returns the number of packets received once every
+/// channel has closed or the output stopped.
+pub async fn webrtc_from_channels(
+    output: Arc<RwLock<WebRTCOutput>>,
+    layers: Vec<broadcast::Receiver<Arc<EncodedPacket>>>,
+    audio: Option<broadcast::Receiver<Arc<EncodedAudioPacket>>>,
+) -> Result<u64> {
+    let mut tasks: Vec<_> = layers
+        .into_iter()
+        .enumerate()
+        .map(|(layer, rx)| tokio::spawn(forward_layer(output.clone(), layer, rx)))
+        .collect();
+    if let Some(rx) = audio {
+        tasks.push(tokio::spawn(forward_audio(output.clone(), rx)));
+    }
+
+    let mut packets = 0;
+    for task in tasks {
+        match task.await {
+            Ok(count) => packets += count,
+            Err(e) => warn!("WebRTC forwarding task panicked: {}", e),
+        }
+    }
+    Ok(packets)
+}
+
+/// Forward one layer's packets until its encoder goes away
+async fn forward_layer(
+    output: Arc<RwLock<WebRTCOutput>>,
+    layer: usize,
+    mut rx: broadcast::Receiver<Arc<EncodedPacket>>,
+) -> u64 {
+    let mut packets = 0;
+    loop {
+        match rx.recv().await {
+            Ok(packet) => {
+                let output = output.read().await;
+                if !output.is_running() {
+                    break;
+                }
+                if let Err(e) = output.write_layer_packet(layer, &packet).await {
+                    warn!("Failed to send video packet: {}", e);
+                }
+                packets += 1;
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                warn!("WebRTC output lagged by {} frames", n);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
     }
+    packets
+}
+
+/// Forward Opus packets until the audio encoder goes away
+async fn forward_audio(
+    output: Arc<RwLock<WebRTCOutput>>,
+    mut rx: broadcast::Receiver<Arc<EncodedAudioPacket>>,
+) -> u64 {
+    let mut clock = SampleClock::new(OPUS_CLOCK_RATE);
+    let mut packets = 0;
+    loop {
+        match rx.recv().await {
+            Ok(packet) => {
+                let output = output.read().await;
+                if !output.is_running() {
+                    break;
+                }
+                let duration = clock.opus_duration(&packet);
+                if let Err(e) = output.write_audio_packet(&packet, duration).await {
+                    warn!("Failed to send audio packet: {}", e);
+                }
+                packets += 1;
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                // The gap shows up in the next packet's timestamp
+                warn!("WebRTC audio lagged by {} packets", n);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+    packets
 }
 
 // ============================================================================
@@ -335,9 +1019,9 @@ struct SignalingState {
 ///
 /// This starts a simple HTTP server that handles WebRTC signaling:
 /// - `GET /` returns an HTML viewer page
-/// - `GET /offer` returns the SDP offer
-/// - `POST /answer` accepts the SDP answer
-/// - `GET /status` returns connection status
+/// - `GET /offer` creates a peer and returns its id and SDP offer
+/// - `POST /answer` accepts the SDP answer for a peer id
+/// - `GET /status` returns connection status and the viewer count
 pub async fn start_signaling_server(
     webrtc: Arc<RwLock<WebRTCOutput>>,
     port: u16,
@@ -376,8 +1060,16 @@ async fn get_offer(
 ) -> impl IntoResponse {
     let webrtc = state.webrtc.read().await;
 
+    // The viewer needs the same TURN relay when it's behind a strict NAT
     match webrtc.create_offer().await {
-        Ok(sdp) => (StatusCode::OK, Json(serde_json::json!({ "sdp": sdp }))),
+        Ok((id, sdp)) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "id": id,
+                "sdp": sdp,
+                "iceServers": webrtc.config.ice_servers,
+            })),
+        ),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({ "error": e.to_string() })),
@@ -388,6 +1080,8 @@ async fn get_offer(
 /// Answer request body
 #[derive(serde::Deserialize)]
 struct AnswerRequest {
+    /// Peer id from the offer
+    id: Uuid,
     sdp: String,
 }
 
@@ -398,7 +1092,7 @@ async fn set_answer(
 ) -> impl IntoResponse {
     let webrtc = state.webrtc.read().await;
 
-    match webrtc.set_answer(&body.sdp).await {
+    match webrtc.set_answer(body.id, &body.sdp).await {
         Ok(()) => (StatusCode::OK, Json(serde_json::json!({ "status": "ok" }))),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -416,8 +1110,11 @@ async fn get_status(
 
     Json(serde_json::json!({
         "running": running,
-        "video_enabled": webrtc.video_track.is_some(),
-        "audio_enabled": webrtc.audio_track.is_some(),
+        "video_enabled": running,
+        "audio_enabled": running && webrtc.config.audio_enabled,
+        "viewers": webrtc.viewer_count().await,
+        "peers": webrtc.peer_count().await,
+        "simulcast_layers": webrtc.config.simulcast.iter().map(|l| l.rid.as_str()).collect::<Vec<_>>(),
     }))
 }
 
@@ -476,7 +1173,7 @@ const VIEWER_HTML: &str = r#"<!DOCTYPE html>
 <body>
     <div class="container">
         <h1>Nitrogen WebRTC Viewer</h1>
-        <video id="video" autoplay playsinline muted></video>
+        <video id="video" autoplay playsinline muted controls></video>
         <div class="status">
             Status: <span id="status" class="disconnected">Disconnected</span>
         </div>
@@ -500,13 +1197,25 @@ const VIEWER_HTML: &str = r#"<!DOCTYPE html>
                 setStatus('Connecting...', 'connecting');
                 connectBtn.disabled = true;
 
+                // Get offer (and ICE servers) from server
+                const offerRes = await fetch('/offer');
+                const offerData = await offerRes.json();
+
+                if (offerData.error) {
+                    throw new Error(offerData.error);
+                }
+
                 // Create peer connection
                 pc = new RTCPeerConnection({
-                    iceServers: [{ urls: 'stun:stun.l.google.com:19302' }]
+                    iceServers: offerData.iceServers || [{ urls: 'stun:stun.l.google.com:19302' }]
                 });
 
+                // Video and audio arrive as separate tracks; play them together.
+                // Starts muted so autoplay is allowed, unmute from the controls.
+                const stream = new MediaStream();
+                video.srcObject = stream;
                 pc.ontrack = (event) => {
-                    video.srcObject = event.streams[0];
+                    stream.addTrack(event.track);
                 };
 
                 pc.oniceconnectionstatechange = () => {
@@ -520,14 +1229,6 @@ const VIEWER_HTML: &str = r#"<!DOCTYPE html>
                     }
                 };
 
-                // Get offer from server
-                const offerRes = await fetch('/offer');
-                const offerData = await offerRes.json();
-
-                if (offerData.error) {
-                    throw new Error(offerData.error);
-                }
-
                 // Set remote description (server's offer)
                 await pc.setRemoteDescription({
                     type: 'offer',
@@ -542,7 +1243,7 @@ const VIEWER_HTML: &str = r#"<!DOCTYPE html>
                 await fetch('/answer', {
                     method: 'POST',
                     headers: { 'Content-Type': 'application/json' },
-                    body: JSON.stringify({ sdp: answer.sdp })
+                    body: JSON.stringify({ id: offerData.id, sdp: answer.sdp })
                 });
 
             } catch (err) {
@@ -570,13 +1271,97 @@ const VIEWER_HTML: &str = r#"<!DOCTYPE html>
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::output::webrtc_config::DEFAULT_STUN_SERVER;
 
     #[test]
-    fn test_webrtc_config_default() {
-        let config = WebRTCConfig::default();
-        assert!(!config.ice_servers.is_empty());
-        assert_eq!(config.video_codec, "h264");
-        assert!(config.audio_enabled);
+    fn test_turn_credentials_reach_rtc_configuration() {
+        let config = WebRTCConfig {
+            ice_servers: vec![
+                IceServer::stun(DEFAULT_STUN_SERVER),
+                IceServer::turn("turn:turn.example.com:3478", "1760000000:viewer", "c2VjcmV0"),
+            ],
+            ..Default::default()
+        };
+        let rtc = config.rtc_configuration();
+
+        assert_eq!(rtc.ice_servers.len(), 2);
+        assert_eq!(rtc.ice_servers[0].urls, vec![DEFAULT_STUN_SERVER]);
+        assert!(rtc.ice_servers[0].username.is_empty());
+        let turn = &rtc.ice_servers[1];
+        assert_eq!(turn.urls, vec!["turn:turn.example.com:3478"]);
+        assert_eq!(turn.username, "1760000000:viewer");
+        assert_eq!(turn.credential, "c2VjcmV0");
+    }
+
+    #[test]
+    fn test_sample_clock_follows_pts() {
+        let packet = |pts, duration| EncodedAudioPacket {
+            data: Vec::new(),
+            pts,
+            dts: pts,
+            duration,
+        };
+        let mut clock = SampleClock::new(OPUS_CLOCK_RATE);
+
+        // The first packet has nothing to compare with
+        assert_eq!(clock.opus_duration(&packet(0, 0)), Duration::from_millis(20));
+        assert_eq!(clock.opus_duration(&packet(960, 960)), Duration::from_millis(20));
+        // Two lost packets stretch the next sample over the gap
+        assert_eq!(clock.opus_duration(&packet(3840, 960)), Duration::from_millis(60));
+        // A late packet doesn't wind the clock back
+        assert_eq!(clock.opus_duration(&packet(2880, 480)), Duration::from_millis(10));
+        assert_eq!(clock.opus_duration(&packet(4800, 960)), Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn test_video_sample_duration_from_pts() {
+        let config = WebRTCConfig {
+            ice_servers: Vec::new(),
+            fps: 120,
+            ..Default::default()
+        };
+        let output = WebRTCOutput::new(config).await.unwrap();
+        let duration = |pts| {
+            let packet = EncodedPacket {
+                data: Vec::new(),
+                pts,
+                dts: pts,
+                keyframe: false,
+            };
+            output.video_sample(0, &packet).duration
+        };
+        let frame = Duration::from_nanos(8_333_333);
+
+        // The first frame lasts 1/fps, the rest until the next timestamp
+        assert_eq!(duration(0), frame);
+        assert_eq!(duration(1), frame);
+        assert_eq!(duration(2), frame);
+        // A skipped frame doubles the next sample
+        assert_eq!(duration(4), frame * 2);
+        assert_eq!(duration(5), frame);
+    }
+
+    #[tokio::test]
+    async fn test_offer_uses_video_codec() {
+        let config = WebRTCConfig {
+            ice_servers: Vec::new(),
+            video_codec: "av1".to_string(),
+            audio_enabled: false,
+            ..Default::default()
+        };
+        let mut output = WebRTCOutput::new(config).await.unwrap();
+        output.init().await.unwrap();
+        let (_, sdp) = output.create_offer().await.unwrap();
+        assert!(sdp.contains("AV1/90000"));
+        assert!(!sdp.contains("H264/90000"));
+        output.stop().await.unwrap();
+
+        let config = WebRTCConfig {
+            video_codec: "hevc".to_string(),
+            ..Default::default()
+        };
+        let mut output = WebRTCOutput::new(config).await.unwrap();
+        assert!(output.init().await.is_err());
     }
 
     #[tokio::test]
@@ -585,4 +1370,50 @@ mod tests {
         let output = WebRTCOutput::new(config).await;
         assert!(output.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_offer_per_viewer() {
+        let config = WebRTCConfig {
+            ice_servers: Vec::new(),
+            ..Default::default()
+        };
+        let mut output = WebRTCOutput::new(config).await.unwrap();
+        assert!(output.create_offer().await.is_err());
+        output.init().await.unwrap();
+
+        let (first, first_sdp) = output.create_offer().await.unwrap();
+        let (second, _) = output.create_offer().await.unwrap();
+        assert_ne!(first, second);
+        assert!(first_sdp.contains("m=video"));
+        assert!(first_sdp.contains("m=audio"));
+        assert!(first_sdp.contains("opus/48000"));
+        assert_eq!(output.peer_count().await, 2);
+        assert_eq!(output.viewer_count().await, 0);
+
+        // Answers are routed by id
+        let unknown = output.set_answer(Uuid::new_v4(), "v=0").await.unwrap_err();
+        assert!(unknown.to_string().contains("Unknown WebRTC peer"));
+
+        // Peers without a viewer get nothing, but writing to them is fine
+        let packet = EncodedPacket {
+            data: vec![0, 0, 0, 1, 0x65],
+            pts: 0,
+            dts: 0,
+            keyframe: true,
+        };
+        output.write_video_packet(&packet).await.unwrap();
+        let audio = EncodedAudioPacket {
+            data: vec![0xfc],
+            pts: 0,
+            dts: 0,
+            duration: OPUS_FRAME_TICKS,
+        };
+        output
+            .write_audio_packet(&audio, Duration::from_millis(20))
+            .await
+            .unwrap();
+
+        output.stop().await.unwrap();
+        assert_eq!(output.peer_count().await, 0);
+    }
 }
@@ -0,0 +1,338 @@
+//! WebRTC settings shared with the configuration
+//!
+//! ICE servers, simulcast layers and the output configuration are plain
+//! data, so the config file and CLI can parse them in builds without the
+//! `webrtc` feature; the peer connection side lives in `webrtc.rs`.
+
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "webrtc")]
+use crate::config::Codec;
+#[cfg(feature = "webrtc")]
+use crate::error::{NitrogenError, Result};
+
+/// Fraction of a higher layer's RTT budget the RTT must drop below before
+/// switching up to it, so a peer near the limit doesn't flap between layers
+const UPGRADE_MARGIN: f64 = 0.8;
+
+/// Video codecs browsers can receive over WebRTC
+pub const WEBRTC_VIDEO_CODECS: &[&str] = &["h264", "vp8", "vp9", "av1"];
+
+/// Check that the encoder produces the WebRTC video codec
+///
+/// NVENC has no VP8 or VP9 encoder and browsers can't take HEVC, so only
+/// `h264` and `av1` with the matching encoder codec get past this.
+#[cfg(feature = "webrtc")]
+pub fn check_webrtc_codec(video_codec: &str, codec: Codec) -> Result<()> {
+    let video_codec = video_codec.to_lowercase();
+    if !WEBRTC_VIDEO_CODECS.contains(&video_codec.as_str()) {
+        return Err(NitrogenError::webrtc(format!(
+            "Unknown WebRTC video codec '{}' (expected one of: {})",
+            video_codec,
+            WEBRTC_VIDEO_CODECS.join(", ")
+        )));
+    }
+    let produced = match codec {
+        Codec::H264 => "h264",
+        Codec::Av1 => "av1",
+        Codec::Hevc => {
+            return Err(NitrogenError::webrtc(
+                "WebRTC can't carry HEVC, encode H.264 or AV1 for browser viewers",
+            ))
+        }
+    };
+    if video_codec != produced {
+        return Err(NitrogenError::webrtc(format!(
+            "WebRTC video codec {} doesn't match the {} encoder",
+            video_codec, codec
+        )));
+    }
+    Ok(())
+}
+
+/// One rendition of a simulcast bitrate ladder
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimulcastLayer {
+    /// RTP stream ID advertised in the SDP (`a=rid`)
+    pub rid: String,
+    /// Output height; the width follows the capture aspect ratio
+    pub height: u32,
+    /// Target bitrate in kbps
+    pub bitrate: u32,
+    /// Highest round-trip time in ms at which a direct peer gets this layer
+    /// (0 = no limit)
+    #[serde(default)]
+    pub max_rtt_ms: u32,
+}
+
+impl SimulcastLayer {
+    /// Create a layer
+    pub fn new(rid: impl Into<String>, height: u32, bitrate: u32, max_rtt_ms: u32) -> Self {
+        Self {
+            rid: rid.into(),
+            height,
+            bitrate,
+            max_rtt_ms,
+        }
+    }
+
+    /// Encoded size of this layer for a given full output size
+    ///
+    /// Keeps the output aspect ratio, never upscales, and rounds both
+    /// dimensions to even values as required for 4:2:0 encoding.
+    pub fn scaled_size(&self, width: u32, height: u32) -> (u32, u32) {
+        if height == 0 {
+            return (width & !1, 0);
+        }
+        let layer_height = self.height.min(height) & !1;
+        let layer_width = (width as u64 * layer_height as u64 + height as u64 / 2) / height as u64;
+        ((layer_width as u32) & !1, layer_height)
+    }
+}
+
+/// STUN server used when none is configured
+pub const DEFAULT_STUN_SERVER: &str = "stun:stun.l.google.com:19302";
+
+/// A STUN or TURN server for ICE
+///
+/// In the config file an entry is either a URL string (`"stun:host:3478"`)
+/// or a table with `urls` and, for TURN, `username` and `credential`.
+/// Time-limited TURN REST credentials go in the same two fields.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "IceServerEntry")]
+pub struct IceServer {
+    /// `stun:`, `stuns:`, `turn:` or `turns:` URLs of this server
+    pub urls: Vec<String>,
+    /// TURN username
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// TURN password or time-limited credential
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+}
+
+/// Config form of an [`IceServer`]: a bare URL or a full entry
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum IceServerEntry {
+    Url(String),
+    Server {
+        #[serde(alias = "url")]
+        urls: OneOrMany,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        credential: Option<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl From<IceServerEntry> for IceServer {
+    fn from(entry: IceServerEntry) -> Self {
+        match entry {
+            IceServerEntry::Url(url) => Self::stun(url),
+            IceServerEntry::Server {
+                urls,
+                username,
+                credential,
+            } => Self {
+                urls: match urls {
+                    OneOrMany::One(url) => vec![url],
+                    OneOrMany::Many(urls) => urls,
+                },
+                username,
+                credential,
+            },
+        }
+    }
+}
+
+impl IceServer {
+    /// Server without credentials
+    pub fn stun(url: impl Into<String>) -> Self {
+        Self {
+            urls: vec![url.into()],
+            username: None,
+            credential: None,
+        }
+    }
+
+    /// TURN server with credentials
+    pub fn turn(
+        url: impl Into<String>,
+        username: impl Into<String>,
+        credential: impl Into<String>,
+    ) -> Self {
+        Self {
+            urls: vec![url.into()],
+            username: Some(username.into()),
+            credential: Some(credential.into()),
+        }
+    }
+
+    /// Check if any URL is a TURN relay
+    pub fn is_turn(&self) -> bool {
+        self.urls
+            .iter()
+            .any(|url| url.starts_with("turn:") || url.starts_with("turns:"))
+    }
+
+    /// Check the URL schemes and that TURN servers have credentials
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.urls.is_empty() {
+            return Err("ICE server has no URLs".to_string());
+        }
+        for url in &self.urls {
+            let host = ["stun:", "stuns:", "turn:", "turns:"]
+                .iter()
+                .find_map(|scheme| url.strip_prefix(scheme));
+            if host.is_none_or(str::is_empty) {
+                return Err(format!(
+                    "Invalid ICE server URL '{}': expected stun:, stuns:, turn: or turns:",
+                    url
+                ));
+            }
+        }
+        if self.is_turn() && (self.username.is_none() || self.credential.is_none()) {
+            return Err(format!(
+                "TURN server '{}' needs a username and credential",
+                self.urls[0]
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// STUN-only list used when none is configured
+pub fn default_ice_servers() -> Vec<IceServer> {
+    vec![IceServer::stun(DEFAULT_STUN_SERVER)]
+}
+
+/// 1080p / 720p / 360p ladder used when `simulcast = true` lists no layers
+pub fn default_simulcast_layers() -> Vec<SimulcastLayer> {
+    vec![
+        SimulcastLayer::new("f", 1080, 6000, 60),
+        SimulcastLayer::new("h", 720, 2500, 150),
+        SimulcastLayer::new("q", 360, 600, 0),
+    ]
+}
+
+/// Pick the layer to send to a peer that doesn't negotiate simulcast
+///
+/// `layers` are ordered from highest to lowest quality and `current` is the
+/// layer being sent now. Returns the highest layer whose RTT budget fits,
+/// falling back to the lowest. Moving down is immediate; moving up requires
+/// some headroom under the higher layer's budget.
+pub fn select_layer(layers: &[SimulcastLayer], rtt: Duration, current: usize) -> usize {
+    let rtt_ms = rtt.as_secs_f64() * 1000.0;
+    for (index, layer) in layers.iter().enumerate() {
+        let margin = if index < current { UPGRADE_MARGIN } else { 1.0 };
+        if layer.max_rtt_ms == 0 || rtt_ms <= layer.max_rtt_ms as f64 * margin {
+            return index;
+        }
+    }
+    layers.len().saturating_sub(1)
+}
+
+/// WebRTC output configuration
+#[derive(Debug, Clone)]
+pub struct WebRTCConfig {
+    /// STUN/TURN servers for NAT traversal
+    pub ice_servers: Vec<IceServer>,
+    /// Video codec, one of [`WEBRTC_VIDEO_CODECS`] (h264 recommended for
+    /// compatibility)
+    pub video_codec: String,
+    /// Video payload type
+    pub video_payload_type: u8,
+    /// Video frame rate; video packet timestamps count frames at this rate
+    pub fps: u32,
+    /// Audio enabled
+    pub audio_enabled: bool,
+    /// Simulcast layers, highest first (fewer than two = single bitrate)
+    pub simulcast: Vec<SimulcastLayer>,
+}
+
+impl Default for WebRTCConfig {
+    fn default() -> Self {
+        Self {
+            ice_servers: default_ice_servers(),
+            video_codec: "h264".to_string(),
+            video_payload_type: 96,
+            fps: 60,
+            audio_enabled: true,
+            simulcast: Vec::new(),
+        }
+    }
+}
+
+impl WebRTCConfig {
+    /// Check if more than one layer is published
+    pub fn simulcast_enabled(&self) -> bool {
+        self.simulcast.len() > 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webrtc_config_default() {
+        let config = WebRTCConfig::default();
+        assert!(!config.ice_servers.is_empty());
+        assert_eq!(config.video_codec, "h264");
+        assert!(config.audio_enabled);
+    }
+
+    #[test]
+    #[cfg(feature = "webrtc")]
+    fn test_check_webrtc_codec() {
+        assert!(check_webrtc_codec("h264", Codec::H264).is_ok());
+        assert!(check_webrtc_codec("AV1", Codec::Av1).is_ok());
+        // The encoder has to produce the advertised codec
+        assert!(check_webrtc_codec("h264", Codec::Av1).is_err());
+        assert!(check_webrtc_codec("vp9", Codec::H264).is_err());
+        assert!(check_webrtc_codec("h265", Codec::Hevc).is_err());
+        let hevc = check_webrtc_codec("h264", Codec::Hevc).unwrap_err();
+        assert!(hevc.to_string().contains("HEVC"));
+    }
+
+    #[test]
+    fn test_ice_server_validate() {
+        assert!(IceServer::stun(DEFAULT_STUN_SERVER).validate().is_ok());
+        assert!(IceServer::turn("turns:relay.example.com:5349", "u", "p").validate().is_ok());
+        assert!(IceServer::stun("turn:relay.example.com:3478").validate().is_err());
+        assert!(IceServer::stun("http://example.com").validate().is_err());
+    }
+
+    #[test]
+    fn test_simulcast_layer_scaled_size() {
+        let layer = SimulcastLayer::new("h", 720, 2500, 150);
+        assert_eq!(layer.scaled_size(1920, 1080), (1280, 720));
+        // Ultrawide keeps its aspect ratio
+        assert_eq!(layer.scaled_size(2560, 1080), (1706, 720));
+        // Never upscales past the output
+        assert_eq!(layer.scaled_size(1280, 540), (1280, 540));
+    }
+
+    #[test]
+    fn test_select_layer_by_rtt() {
+        let layers = default_simulcast_layers();
+        let ms = Duration::from_millis;
+
+        assert_eq!(select_layer(&layers, ms(10), 0), 0);
+        assert_eq!(select_layer(&layers, ms(100), 0), 1);
+        assert_eq!(select_layer(&layers, ms(400), 0), 2);
+        // Upgrades need headroom under the higher layer's budget
+        assert_eq!(select_layer(&layers, ms(55), 1), 1);
+        assert_eq!(select_layer(&layers, ms(40), 1), 0);
+        assert_eq!(select_layer(&[], ms(10), 0), 0);
+    }
+}
@@ -2,7 +2,17 @@
 //!
 //! Renders performance statistics as text overlay on video frames.
 //! Uses simple bitmap font rendering for minimal dependencies.
+//!
+//! A custom template can replace the fixed stats line. Placeholders such as
+//! `{fps}` or `{title}` are filled in on every frame; unknown ones are drawn
+//! as written, and each `\n` starts a new line.
+//!
+//! A SMPTE timecode can be burned in as well, in its own box opposite the
+//! stats. It advances one frame per rendered frame from the configured start.
 
+use std::time::{Duration, Instant};
+
+use crate::encode::Timecode;
 use crate::performance::LatencyStats;
 use serde::{Deserialize, Serialize};
 
@@ -18,13 +28,22 @@ pub enum OverlayPosition {
 }
 
 impl OverlayPosition {
-    /// Parse from string
+    /// Parse from string, falling back to top-left for unknown values
     pub fn from_str(s: &str) -> Self {
+        s.parse().unwrap_or_default()
+    }
+}
+
+impl std::str::FromStr for OverlayPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.to_lowercase().as_str() {
-            "top-right" | "topright" | "tr" => Self::TopRight,
-            "bottom-left" | "bottomleft" | "bl" => Self::BottomLeft,
-            "bottom-right" | "bottomright" | "br" => Self::BottomRight,
-            _ => Self::TopLeft,
+            "top-left" | "topleft" | "tl" => Ok(Self::TopLeft),
+            "top-right" | "topright" | "tr" => Ok(Self::TopRight),
+            "bottom-left" | "bottomleft" | "bl" => Ok(Self::BottomLeft),
+            "bottom-right" | "bottomright" | "br" => Ok(Self::BottomRight),
+            _ => Err(format!("Unknown overlay position: {}", s)),
         }
     }
 }
@@ -50,6 +69,14 @@ pub struct OverlayConfig {
     pub font_scale: f32,
     /// Background opacity (0.0 - 1.0)
     pub background_opacity: f32,
+    /// Custom text with placeholders, replacing the `show_*` stats line
+    pub template: Option<String>,
+    /// Value of the `{title}` placeholder
+    pub title: String,
+    /// Burned-in timecode start (None = no timecode)
+    pub timecode: Option<Timecode>,
+    /// Frame rate the timecode counts frames at
+    pub timecode_fps: u32,
 }
 
 impl Default for OverlayConfig {
@@ -64,6 +91,10 @@ impl Default for OverlayConfig {
             show_drops: true,
             font_scale: 1.0,
             background_opacity: 0.7,
+            template: None,
+            title: String::new(),
+            timecode: None,
+            timecode_fps: 60,
         }
     }
 }
@@ -72,12 +103,20 @@ impl Default for OverlayConfig {
 #[derive(Debug)]
 pub struct LatencyOverlay {
     config: OverlayConfig,
+    /// Start of the `{time}` timer
+    started: Instant,
+    /// Frames of timecode rendered so far
+    timecode_frames: u64,
 }
 
 impl LatencyOverlay {
     /// Create a new overlay renderer with config
     pub fn new(config: OverlayConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            started: Instant::now(),
+            timecode_frames: 0,
+        }
     }
 
     /// Create with default config
@@ -110,8 +149,34 @@ impl LatencyOverlay {
         &self.config
     }
 
+    /// Set the text shown for `{title}`
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.config.title = title.into();
+    }
+
+    /// Restart the `{time}` timer from zero
+    pub fn reset_timer(&mut self) {
+        self.started = Instant::now();
+    }
+
+    /// Check if a timecode is burned in (independent of the stats overlay)
+    pub fn has_timecode(&self) -> bool {
+        self.config.timecode.is_some()
+    }
+
+    /// Timecode the next rendered frame will carry
+    pub fn next_timecode(&self) -> Option<Timecode> {
+        self.config
+            .timecode
+            .map(|start| start.advanced(self.timecode_frames, self.config.timecode_fps))
+    }
+
     /// Format stats into display text
     fn format_text(&self, stats: &LatencyStats) -> String {
+        if let Some(ref template) = self.config.template {
+            return render_template(template, stats, &self.config.title, self.started.elapsed());
+        }
+
         let mut parts = Vec::new();
 
         if self.config.show_capture {
@@ -149,17 +214,56 @@ impl LatencyOverlay {
         if text.is_empty() {
             return;
         }
+        self.draw_box(frame, width, height, self.config.position, &text);
+    }
+
+    /// Render the timecode onto a BGRA frame and advance it by one frame
+    ///
+    /// The box goes in the corner opposite the stats overlay. Returns the
+    /// timecode drawn, or None when no timecode is configured.
+    pub fn render_timecode(
+        &mut self,
+        frame: &mut [u8],
+        width: u32,
+        height: u32,
+    ) -> Option<Timecode> {
+        let timecode = self.next_timecode()?;
+        let position = match self.config.position {
+            OverlayPosition::TopLeft => OverlayPosition::BottomRight,
+            OverlayPosition::TopRight => OverlayPosition::BottomLeft,
+            OverlayPosition::BottomLeft => OverlayPosition::TopRight,
+            OverlayPosition::BottomRight => OverlayPosition::TopLeft,
+        };
+
+        self.draw_box(frame, width, height, position, &timecode.to_string());
+        self.timecode_frames += 1;
+        Some(timecode)
+    }
+
+    /// Draw text lines on a background box in one corner
+    fn draw_box(
+        &self,
+        frame: &mut [u8],
+        width: u32,
+        height: u32,
+        position: OverlayPosition,
+        text: &str,
+    ) {
+        let lines: Vec<&str> = text.lines().collect();
 
         // Calculate text dimensions
         let char_width = (6.0 * self.config.font_scale) as u32;
         let char_height = (8.0 * self.config.font_scale) as u32;
+        let line_height = char_height + (2.0 * self.config.font_scale) as u32;
         let padding = 4u32;
-        let text_width = text.len() as u32 * char_width;
+        let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let text_width = longest as u32 * char_width;
+        let text_height = line_height * (lines.len() as u32 - 1) + char_height;
         let box_width = text_width + padding * 2;
-        let box_height = char_height + padding * 2;
+        let box_height = text_height + padding * 2;
 
         // Calculate position
-        let (box_x, box_y) = match self.config.position {
+        let (box_x, box_y) = match position {
             OverlayPosition::TopLeft => (padding, padding),
             OverlayPosition::TopRight => (width.saturating_sub(box_width + padding), padding),
             OverlayPosition::BottomLeft => (padding, height.saturating_sub(box_height + padding)),
@@ -175,8 +279,10 @@ impl LatencyOverlay {
 
         // Draw text
         let text_x = box_x + padding;
-        let text_y = box_y + padding;
-        self.draw_text(frame, width, height, text_x, text_y, &text, [255, 255, 255, 255]);
+        for (i, line) in lines.iter().enumerate() {
+            let text_y = box_y + padding + i as u32 * line_height;
+            self.draw_text(frame, width, height, text_x, text_y, line, [255, 255, 255, 255]);
+        }
     }
 
     /// Draw a filled rectangle with alpha blending
@@ -198,12 +304,7 @@ impl LatencyOverlay {
             for px in x..x.saturating_add(w).min(width) {
                 let idx = py as usize * stride + px as usize * 4;
                 if idx + 3 < frame.len() {
-                    // Alpha blend
-                    for i in 0..3 {
-                        let src = color[i] as u32;
-                        let dst = frame[idx + i] as u32;
-                        frame[idx + i] = ((src * alpha + dst * (255 - alpha)) / 255) as u8;
-                    }
+                    blend_pixel(&mut frame[idx..idx + 3], &color, alpha);
                 }
             }
         }
@@ -273,6 +374,108 @@ impl LatencyOverlay {
     }
 }
 
+/// Alpha-blend one BGR pixel onto another (`alpha` 0-255)
+fn blend_pixel(dst: &mut [u8], src: &[u8], alpha: u32) {
+    for i in 0..3 {
+        let (src, old) = (src[i] as u32, dst[i] as u32);
+        dst[i] = ((src * alpha + old * (255 - alpha)) / 255) as u8;
+    }
+}
+
+/// Alpha-blend a BGRA image (straight alpha) onto a BGRA frame at `(x, y)`
+///
+/// Each pixel's alpha is multiplied by `opacity`. Parts of the image that
+/// fall outside the frame are clipped.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn blend_image(
+    frame: &mut [u8],
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    image: &[u8],
+    image_width: u32,
+    image_height: u32,
+    opacity: f32,
+) {
+    let stride = width as usize * 4;
+    let opacity = opacity.clamp(0.0, 1.0);
+
+    for iy in 0..image_height.min(height.saturating_sub(y)) {
+        for ix in 0..image_width.min(width.saturating_sub(x)) {
+            let src = (iy as usize * image_width as usize + ix as usize) * 4;
+            let Some(pixel) = image.get(src..src + 4) else {
+                return;
+            };
+            let alpha = (pixel[3] as f32 * opacity).round() as u32;
+            if alpha == 0 {
+                continue;
+            }
+
+            let idx = (y + iy) as usize * stride + (x + ix) as usize * 4;
+            if idx + 3 < frame.len() {
+                blend_pixel(&mut frame[idx..idx + 3], pixel, alpha);
+            }
+        }
+    }
+}
+
+/// Expand an overlay template
+///
+/// Known placeholders: `{fps}`, `{bitrate}` (kbps), `{drops}`, `{frames}`,
+/// `{capture}`, `{encode}`, `{output}`, `{latency}` (ms), `{time}` (elapsed
+/// as HH:MM:SS) and `{title}`. Anything else, including an unclosed `{`, is
+/// kept literally.
+fn render_template(
+    template: &str,
+    stats: &LatencyStats,
+    title: &str,
+    elapsed: Duration,
+) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('}') else {
+            out.push_str(&rest[open..]);
+            return out;
+        };
+
+        let name = &after[..close];
+        match name {
+            "fps" => out.push_str(&format!("{:.0}", stats.fps)),
+            "bitrate" => out.push_str(&stats.bitrate_kbps.to_string()),
+            "drops" => out.push_str(&stats.frames_dropped.to_string()),
+            "frames" => out.push_str(&stats.frames_processed.to_string()),
+            "capture" => out.push_str(&format!("{:.1}", stats.capture_latency_ms)),
+            "encode" => out.push_str(&format!("{:.1}", stats.encode_latency_ms)),
+            "output" => out.push_str(&format!("{:.1}", stats.output_latency_ms)),
+            "latency" => out.push_str(&format!("{:.1}", stats.total_latency_ms)),
+            "time" => {
+                let secs = elapsed.as_secs();
+                out.push_str(&format!(
+                    "{:02}:{:02}:{:02}",
+                    secs / 3600,
+                    (secs / 60) % 60,
+                    secs % 60
+                ));
+            }
+            "title" => out.push_str(title),
+            _ => {
+                out.push('{');
+                out.push_str(name);
+                out.push('}');
+            }
+        }
+        rest = &after[close + 1..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
 /// Get 5x7 bitmap for a character
 /// Each byte represents one row, with 5 bits used (high bits)
 fn get_char_bitmap(ch: char) -> [u8; 7] {
@@ -374,6 +577,56 @@ mod tests {
         assert!(text.contains("60fps"));
     }
 
+    #[test]
+    fn test_template_placeholders() {
+        let overlay = LatencyOverlay::new(OverlayConfig {
+            enabled: true,
+            template: Some("{fps}fps {foo} {drops}".to_string()),
+            ..Default::default()
+        });
+
+        let stats = LatencyStats {
+            fps: 59.9,
+            frames_dropped: 3,
+            ..Default::default()
+        };
+
+        assert_eq!(overlay.format_text(&stats), "60fps {foo} 3");
+    }
+
+    #[test]
+    fn test_template_title_time_and_lines() {
+        let stats = LatencyStats::default();
+
+        let text = render_template(
+            "{title}\nLive {time}",
+            &stats,
+            "My Stream",
+            Duration::from_secs(3725),
+        );
+        assert_eq!(text, "My Stream\nLive 01:02:05");
+
+        // Unclosed braces are kept as written
+        assert_eq!(render_template("{fps", &stats, "", Duration::ZERO), "{fps");
+    }
+
+    #[test]
+    fn test_render_template_multiline() {
+        let overlay = LatencyOverlay::new(OverlayConfig {
+            enabled: true,
+            template: Some("A\nB".to_string()),
+            ..Default::default()
+        });
+
+        let mut frame = vec![128u8; 100 * 100 * 4];
+        overlay.render(&mut frame, 100, 100, &LatencyStats::default());
+
+        // The second line is drawn below the first (row 4 + 10px line height)
+        let row = |y: usize| &frame[y * 100 * 4..(y + 1) * 100 * 4];
+        assert!(row(8).iter().any(|&b| b == 255));
+        assert!(row(18).iter().any(|&b| b == 255));
+    }
+
     #[test]
     fn test_render_disabled() {
         let overlay = LatencyOverlay::with_defaults();
@@ -405,6 +658,46 @@ mod tests {
         assert!(frame.iter().any(|&b| b != 128));
     }
 
+    #[test]
+    fn test_timecode_increases_every_frame() {
+        let mut overlay = LatencyOverlay::new(OverlayConfig {
+            timecode: Some("00:00:00:58".parse().unwrap()),
+            timecode_fps: 60,
+            ..Default::default()
+        });
+        assert!(overlay.has_timecode());
+        // The stats overlay stays off; only the timecode is drawn
+        assert!(!overlay.is_enabled());
+
+        let mut drawn = Vec::new();
+        let mut previous: Option<Vec<u8>> = None;
+        for _ in 0..4 {
+            let mut frame = vec![128u8; 200 * 100 * 4];
+            drawn.push(overlay.render_timecode(&mut frame, 200, 100).unwrap());
+
+            assert!(frame.iter().any(|&b| b != 128));
+            if let Some(prev) = previous {
+                assert_ne!(prev, frame, "timecode text should change every frame");
+            }
+            previous = Some(frame);
+        }
+
+        assert!(drawn.windows(2).all(|w| w[0] < w[1]), "{:?}", drawn);
+        assert_eq!(drawn[0].to_string(), "00:00:00:58");
+        // Frame 59 wraps into the next second
+        assert_eq!(drawn[2].to_string(), "00:00:01:00");
+        assert_eq!(overlay.next_timecode().unwrap().to_string(), "00:00:01:02");
+    }
+
+    #[test]
+    fn test_no_timecode_by_default() {
+        let mut overlay = LatencyOverlay::with_defaults();
+        let mut frame = vec![0u8; 100 * 100 * 4];
+
+        assert!(overlay.render_timecode(&mut frame, 100, 100).is_none());
+        assert!(frame.iter().all(|&b| b == 0));
+    }
+
     #[test]
     fn test_char_bitmap() {
         // Test that digits return non-zero bitmaps
@@ -2,19 +2,65 @@
 //!
 //! Provides:
 //! - Frame time tracking (capture, encode, output stages)
-//! - Rolling averages for latency statistics
+//! - Rolling or exponential moving averages for latency statistics
 //! - Dropped frame counting
 //! - GPU monitoring (temperature, power, utilization)
+//! - Periodic stats logging for unattended sessions
 
 use std::collections::VecDeque;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+use tracing::info;
+
+use crate::shutdown::ShutdownSignal;
 
 /// Maximum number of samples to keep for rolling averages
 const MAX_SAMPLES: usize = 120;
 
+/// Default EMA smoothing factor (weight of the newest sample)
+pub const DEFAULT_EMA_ALPHA: f64 = 0.2;
+
+/// How latency and frame-time samples are averaged
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricsMode {
+    /// Mean of the last `samples` values
+    Rolling {
+        /// Window size in samples
+        samples: usize,
+    },
+    /// Exponential moving average, reacts faster to spikes and uses O(1) memory
+    Ema {
+        /// Weight of the newest sample (0.0 - 1.0, higher = more responsive)
+        alpha: f64,
+    },
+}
+
+impl Default for MetricsMode {
+    fn default() -> Self {
+        Self::Rolling {
+            samples: MAX_SAMPLES,
+        }
+    }
+}
+
+impl MetricsMode {
+    /// Pick a mode from a sample count (0 = EMA with the default alpha)
+    pub fn from_sample_count(samples: usize) -> Self {
+        if samples == 0 {
+            Self::Ema {
+                alpha: DEFAULT_EMA_ALPHA,
+            }
+        } else {
+            Self::Rolling { samples }
+        }
+    }
+}
+
 /// Latency statistics snapshot
 #[derive(Debug, Clone)]
 pub struct LatencyStats {
@@ -34,6 +80,8 @@ pub struct LatencyStats {
     pub frames_processed: u64,
     /// Total frames dropped
     pub frames_dropped: u64,
+    /// Frames encoded from DMA-BUF without a CPU copy
+    pub zero_copy_frames: u64,
     /// Timestamp of this snapshot
     pub timestamp: Instant,
 }
@@ -49,6 +97,7 @@ impl Default for LatencyStats {
             bitrate_kbps: 0,
             frames_processed: 0,
             frames_dropped: 0,
+            zero_copy_frames: 0,
             timestamp: Instant::now(),
         }
     }
@@ -71,7 +120,7 @@ impl LatencyStats {
     pub fn format_detailed(&self) -> String {
         format!(
             "Latency: capture={:.2}ms encode={:.2}ms output={:.2}ms total={:.2}ms\n\
-             Performance: fps={:.1} bitrate={}kbps processed={} dropped={}",
+             Performance: fps={:.1} bitrate={}kbps processed={} dropped={} zero-copy={}",
             self.capture_latency_ms,
             self.encode_latency_ms,
             self.output_latency_ms,
@@ -79,7 +128,8 @@ impl LatencyStats {
             self.fps,
             self.bitrate_kbps,
             self.frames_processed,
-            self.frames_dropped
+            self.frames_dropped,
+            self.zero_copy_frames
         )
     }
 }
@@ -123,23 +173,109 @@ impl RollingAverage {
     }
 }
 
+/// Exponential moving average calculator for timing data
+#[derive(Debug)]
+struct EmaAverage {
+    /// Current average in seconds (None until the first sample)
+    value: Option<f64>,
+    alpha: f64,
+}
+
+impl EmaAverage {
+    fn new(alpha: f64) -> Self {
+        Self {
+            value: None,
+            alpha: alpha.clamp(f64::EPSILON, 1.0),
+        }
+    }
+
+    fn add(&mut self, duration: Duration) {
+        let sample = duration.as_secs_f64();
+        self.value = Some(match self.value {
+            // Seed with the first sample instead of ramping up from zero
+            None => sample,
+            Some(value) => value + self.alpha * (sample - value),
+        });
+    }
+
+    fn average(&self) -> Duration {
+        Duration::from_secs_f64(self.value.unwrap_or(0.0))
+    }
+
+    fn average_ms(&self) -> f64 {
+        self.value.unwrap_or(0.0) * 1000.0
+    }
+
+    fn clear(&mut self) {
+        self.value = None;
+    }
+}
+
+/// Averaging strategy selected by [`MetricsMode`]
+#[derive(Debug)]
+enum Average {
+    Rolling(RollingAverage),
+    Ema(EmaAverage),
+}
+
+impl Average {
+    fn new(mode: MetricsMode) -> Self {
+        match mode {
+            MetricsMode::Rolling { samples } => Self::Rolling(RollingAverage::new(samples.max(1))),
+            MetricsMode::Ema { alpha } => Self::Ema(EmaAverage::new(alpha)),
+        }
+    }
+
+    fn add(&mut self, duration: Duration) {
+        match self {
+            Self::Rolling(avg) => avg.add(duration),
+            Self::Ema(avg) => avg.add(duration),
+        }
+    }
+
+    fn average(&self) -> Duration {
+        match self {
+            Self::Rolling(avg) => avg.average(),
+            Self::Ema(avg) => avg.average(),
+        }
+    }
+
+    fn average_ms(&self) -> f64 {
+        match self {
+            Self::Rolling(avg) => avg.average_ms(),
+            Self::Ema(avg) => avg.average_ms(),
+        }
+    }
+
+    fn clear(&mut self) {
+        match self {
+            Self::Rolling(avg) => avg.clear(),
+            Self::Ema(avg) => avg.clear(),
+        }
+    }
+}
+
 /// Performance metrics collector
 ///
 /// Thread-safe metrics collection for all pipeline stages.
 #[derive(Debug)]
 pub struct PerformanceMetrics {
+    /// Averaging strategy for all stages
+    mode: MetricsMode,
     /// Capture stage latency samples
-    capture_latency: RwLock<RollingAverage>,
+    capture_latency: RwLock<Average>,
     /// Encode stage latency samples
-    encode_latency: RwLock<RollingAverage>,
+    encode_latency: RwLock<Average>,
     /// Output stage latency samples
-    output_latency: RwLock<RollingAverage>,
+    output_latency: RwLock<Average>,
     /// Frame time samples (for FPS calculation)
-    frame_times: RwLock<RollingAverage>,
+    frame_times: RwLock<Average>,
     /// Total frames processed
     frames_processed: AtomicU64,
     /// Total frames dropped
     frames_dropped: AtomicU64,
+    /// Frames encoded from DMA-BUF without a CPU copy
+    zero_copy_frames: AtomicU64,
     /// Total bytes encoded (for bitrate calculation)
     bytes_encoded: AtomicU64,
     /// Last bitrate calculation time
@@ -157,15 +293,22 @@ impl Default for PerformanceMetrics {
 }
 
 impl PerformanceMetrics {
-    /// Create a new metrics collector
+    /// Create a new metrics collector with rolling averages
     pub fn new() -> Self {
+        Self::with_mode(MetricsMode::default())
+    }
+
+    /// Create a metrics collector with the given averaging strategy
+    pub fn with_mode(mode: MetricsMode) -> Self {
         Self {
-            capture_latency: RwLock::new(RollingAverage::new(MAX_SAMPLES)),
-            encode_latency: RwLock::new(RollingAverage::new(MAX_SAMPLES)),
-            output_latency: RwLock::new(RollingAverage::new(MAX_SAMPLES)),
-            frame_times: RwLock::new(RollingAverage::new(MAX_SAMPLES)),
+            mode,
+            capture_latency: RwLock::new(Average::new(mode)),
+            encode_latency: RwLock::new(Average::new(mode)),
+            output_latency: RwLock::new(Average::new(mode)),
+            frame_times: RwLock::new(Average::new(mode)),
             frames_processed: AtomicU64::new(0),
             frames_dropped: AtomicU64::new(0),
+            zero_copy_frames: AtomicU64::new(0),
             bytes_encoded: AtomicU64::new(0),
             last_bitrate_time: RwLock::new(Instant::now()),
             last_bitrate_kbps: AtomicU64::new(0),
@@ -219,6 +362,11 @@ impl PerformanceMetrics {
         self.frames_dropped.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Record a frame encoded from DMA-BUF without a CPU copy
+    pub fn record_zero_copy_frame(&self) {
+        self.zero_copy_frames.fetch_add(1, Ordering::Relaxed);
+    }
+
     /// Record encoded bytes (for bitrate calculation)
     pub fn record_bytes_encoded(&self, bytes: u64) {
         self.bytes_encoded.fetch_add(bytes, Ordering::Relaxed);
@@ -250,6 +398,7 @@ impl PerformanceMetrics {
             bitrate_kbps,
             frames_processed: self.frames_processed.load(Ordering::Relaxed),
             frames_dropped: self.frames_dropped.load(Ordering::Relaxed),
+            zero_copy_frames: self.zero_copy_frames.load(Ordering::Relaxed),
             timestamp: Instant::now(),
         }
     }
@@ -272,6 +421,11 @@ impl PerformanceMetrics {
         self.last_bitrate_kbps.load(Ordering::Relaxed)
     }
 
+    /// Get the averaging strategy
+    pub fn mode(&self) -> MetricsMode {
+        self.mode
+    }
+
     /// Get total frames processed
     pub fn frames_processed(&self) -> u64 {
         self.frames_processed.load(Ordering::Relaxed)
@@ -282,6 +436,11 @@ impl PerformanceMetrics {
         self.frames_dropped.load(Ordering::Relaxed)
     }
 
+    /// Get total frames encoded without a CPU copy
+    pub fn zero_copy_frames(&self) -> u64 {
+        self.zero_copy_frames.load(Ordering::Relaxed)
+    }
+
     /// Get session duration
     pub fn session_duration(&self) -> Duration {
         Instant::now().duration_since(self.start_time)
@@ -295,6 +454,7 @@ impl PerformanceMetrics {
         self.frame_times.write().clear();
         self.frames_processed.store(0, Ordering::Relaxed);
         self.frames_dropped.store(0, Ordering::Relaxed);
+        self.zero_copy_frames.store(0, Ordering::Relaxed);
         self.bytes_encoded.store(0, Ordering::Relaxed);
         self.last_bitrate_kbps.store(0, Ordering::Relaxed);
         *self.last_bitrate_time.write() = Instant::now();
@@ -365,11 +525,94 @@ pub fn query_gpu_stats(gpu_index: u32) -> Option<GpuStats> {
     }
 }
 
+/// Logs [`LatencyStats`] and GPU stats on a fixed interval
+///
+/// For long unattended streams: the lines end up in the log or journal
+/// without an overlay. Intervals in which no frame was processed (capture
+/// paused, source hidden) are skipped instead of repeating stale averages.
+#[derive(Debug)]
+pub struct StatsLogger {
+    metrics: Arc<PerformanceMetrics>,
+    interval: Duration,
+    /// GPU to query with nvidia-smi (None = no GPU line)
+    gpu_index: Option<u32>,
+    /// Frames processed at the previous sample
+    last_frames: u64,
+}
+
+impl StatsLogger {
+    /// Log `metrics` every `interval`
+    pub fn new(metrics: Arc<PerformanceMetrics>, interval: Duration) -> Self {
+        Self {
+            metrics,
+            interval,
+            gpu_index: None,
+            last_frames: 0,
+        }
+    }
+
+    /// Add a [`GpuStats`] line for this GPU to every report
+    pub fn with_gpu(mut self, gpu_index: u32) -> Self {
+        self.gpu_index = Some(gpu_index);
+        self
+    }
+
+    /// Stats for the interval that just ended, or None if no frame was processed
+    pub fn sample(&mut self) -> Option<LatencyStats> {
+        let stats = self.metrics.get_stats();
+        if stats.frames_processed == self.last_frames {
+            return None;
+        }
+        self.last_frames = stats.frames_processed;
+        Some(stats)
+    }
+
+    /// Log at info level every interval until `shutdown` fires
+    pub async fn run(self, shutdown: ShutdownSignal) {
+        self.run_with(shutdown, |line| info!("{}", line)).await;
+    }
+
+    async fn run_with(mut self, mut shutdown: ShutdownSignal, mut emit: impl FnMut(&str)) {
+        let start = tokio::time::Instant::now() + self.interval;
+        let mut ticker = tokio::time::interval_at(start, self.interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+        loop {
+            tokio::select! {
+                _ = shutdown.wait() => break,
+                _ = ticker.tick() => {}
+            }
+
+            let Some(stats) = self.sample() else {
+                continue;
+            };
+            for line in stats.format_detailed().lines() {
+                emit(line);
+            }
+            if let Some(gpu) = self.gpu_index {
+                // nvidia-smi can take a while; keep it off the runtime threads
+                let gpu_stats = tokio::task::spawn_blocking(move || query_gpu_stats(gpu))
+                    .await
+                    .ok()
+                    .flatten();
+                if let Some(gpu_stats) = gpu_stats {
+                    emit(&gpu_stats.format_line());
+                }
+            }
+        }
+    }
+}
+
 /// Create a shared performance metrics instance
 pub fn create_metrics() -> Arc<PerformanceMetrics> {
     Arc::new(PerformanceMetrics::new())
 }
 
+/// Create a shared performance metrics instance with the given averaging strategy
+pub fn create_metrics_with_mode(mode: MetricsMode) -> Arc<PerformanceMetrics> {
+    Arc::new(PerformanceMetrics::with_mode(mode))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -390,6 +633,62 @@ mod tests {
         assert!((avg.average_ms() - 30.0).abs() < 0.1);
     }
 
+    #[test]
+    fn test_ema_step_convergence() {
+        let mut avg = EmaAverage::new(0.5);
+        avg.add(Duration::from_millis(10));
+        assert!((avg.average_ms() - 10.0).abs() < 1e-9);
+
+        // Step to 20ms: error halves each sample, 20 - 10 * 0.5^n
+        let expected = [15.0, 17.5, 18.75, 19.375];
+        for want in expected {
+            avg.add(Duration::from_millis(20));
+            assert!((avg.average_ms() - want).abs() < 1e-9);
+        }
+
+        avg.clear();
+        assert_eq!(avg.average(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_ema_more_responsive_than_rolling() {
+        let mode = MetricsMode::Ema { alpha: 0.2 };
+        let mut ema = Average::new(mode);
+        let mut rolling = Average::new(MetricsMode::default());
+
+        for _ in 0..MAX_SAMPLES {
+            ema.add(Duration::from_millis(10));
+            rolling.add(Duration::from_millis(10));
+        }
+        for _ in 0..10 {
+            ema.add(Duration::from_millis(20));
+            rolling.add(Duration::from_millis(20));
+        }
+
+        // EMA: 20 - 10 * 0.8^10 ~= 18.93ms; rolling: (110 * 10 + 10 * 20) / 120 ~= 10.83ms
+        let expected_ema = 20.0 - 10.0 * 0.8f64.powi(10);
+        assert!((ema.average_ms() - expected_ema).abs() < 1e-6);
+        assert!((rolling.average_ms() - 1300.0 / 120.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_metrics_mode_from_sample_count() {
+        assert_eq!(
+            MetricsMode::from_sample_count(0),
+            MetricsMode::Ema {
+                alpha: DEFAULT_EMA_ALPHA
+            }
+        );
+        assert_eq!(
+            MetricsMode::from_sample_count(60),
+            MetricsMode::Rolling { samples: 60 }
+        );
+
+        let metrics = PerformanceMetrics::with_mode(MetricsMode::from_sample_count(0));
+        metrics.record_capture(Duration::from_millis(4));
+        assert!((metrics.get_stats().capture_latency_ms - 4.0).abs() < 1e-6);
+    }
+
     #[test]
     fn test_performance_metrics() {
         let metrics = PerformanceMetrics::new();
@@ -418,6 +717,19 @@ mod tests {
         assert_eq!(metrics.frames_processed(), 1);
     }
 
+    #[test]
+    fn test_zero_copy_counter() {
+        let metrics = PerformanceMetrics::new();
+
+        metrics.record_zero_copy_frame();
+        metrics.record_zero_copy_frame();
+        assert_eq!(metrics.get_stats().zero_copy_frames, 2);
+        assert!(metrics.get_stats().format_detailed().contains("zero-copy=2"));
+
+        metrics.reset();
+        assert_eq!(metrics.zero_copy_frames(), 0);
+    }
+
     #[test]
     fn test_stats_formatting() {
         let stats = LatencyStats {
@@ -429,6 +741,7 @@ mod tests {
             bitrate_kbps: 6000,
             frames_processed: 1000,
             frames_dropped: 5,
+            zero_copy_frames: 990,
             timestamp: Instant::now(),
         };
 
@@ -449,4 +762,36 @@ mod tests {
         assert_eq!(metrics.frames_processed(), 0);
         assert_eq!(metrics.frames_dropped(), 0);
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stats_logger_cadence() {
+        let metrics = create_metrics();
+        let coordinator = crate::shutdown::ShutdownCoordinator::new();
+        let lines = Arc::new(parking_lot::Mutex::new(Vec::<String>::new()));
+
+        let logger = StatsLogger::new(metrics.clone(), Duration::from_secs(10));
+        let sink = lines.clone();
+        let task = tokio::spawn(logger.run_with(coordinator.subscribe(), move |line| {
+            sink.lock().push(line.to_string())
+        }));
+
+        // One report per interval while frames keep coming (checked halfway
+        // between ticks, at 15s, 25s and 35s)
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        for report in 1..=3 {
+            metrics.record_frame_processed();
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            let logged = lines.lock();
+            assert_eq!(logged.len(), report * 2, "{:?}", logged);
+            let expected = format!("processed={}", report);
+            assert!(logged.last().unwrap().contains(&expected), "{:?}", logged);
+        }
+
+        // A paused capture processes nothing, so nothing is logged
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(lines.lock().len(), 6);
+
+        coordinator.trigger();
+        task.await.unwrap();
+    }
 }
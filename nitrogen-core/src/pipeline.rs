@@ -9,31 +9,73 @@
 use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::broadcast;
 use tokio::task::JoinHandle;
 use tracing::{debug, error, info, trace, warn};
 
 use crate::capture::portal::{CaptureType, PortalCapture, SessionInfo};
-use crate::capture::{AudioCaptureStream, CaptureStream};
-use crate::config::{AudioSource, CaptureConfig};
-use crate::encode::{AudioEncoder, NvencEncoder, TonemapConfig, Tonemapper};
+use crate::capture::outputs::is_picker_id;
+use crate::capture::{
+    AudioCaptureStream, CaptureStream, CompositeCapture, FrameDecimator, FrameWatchdog,
+    StreamOptions, WatchdogStatus,
+};
+use crate::affinity::{self, ThreadRole};
+use crate::config::{
+    AudioSource, CameraFormat, CaptureConfig, ContentMode, CursorMode, LiveChange, OutputAudio,
+    ReloadPlan,
+};
+#[cfg(feature = "webrtc")]
+use crate::config::Preset;
+use crate::encode::{
+    create_encoder, frame_hash, zero_copy_supported, AudioEncoder, BarColor, EncodedAudioPacket,
+    Encoder, FitMode, FrameDecision, FrameScaler, StaticFrameFilter, TonemapConfig, Tonemapper,
+};
 use crate::error::{NitrogenError, Result};
 use crate::output::{
-    create_camera, record_av_from_channels, start_signaling_server, stream_av_from_channels,
-    FileRecorder, RawOutputSink, StreamConfig, StreamOutput, StreamProtocol, VirtualCamera,
-    VirtualMicrophone, WebRTCConfig, WebRTCOutput,
+    buffer_replay_from_channels, create_camera, record_audio_from_channel,
+    record_av_from_channels, resolve_record_path, run_sink, start_hls_server, AvSyncOffset,
+    CameraConsumerWatcher, CameraPause, DashOutput, FileRecorder, FrameSink, HlsOutput,
+    PathTemplateVars, RawOutputSink, RecordingInfo, ReplayBuffer, ReplayInfo, StreamStats,
+    VirtualCamera, VirtualMicrophone, MAX_AV_SYNC_OFFSET_MS,
+};
+#[cfg(feature = "streaming")]
+use crate::output::{
+    multi_stream_av_from_channels, MultiStreamOutput, ReconnectPolicy, StreamConfig,
+    StreamOutput, StreamProtocol,
+};
+#[cfg(feature = "webrtc")]
+use crate::output::{
+    check_webrtc_codec, start_signaling_server, webrtc_from_channels, SimulcastLayer,
+    WebRTCConfig, WebRTCOutput,
 };
+#[cfg(feature = "webrtc")]
 use tokio::sync::RwLock;
 use crate::overlay::{LatencyOverlay, OverlayConfig};
-use crate::performance::{create_metrics, PerformanceMetrics};
-use crate::formats::fourcc_to_gs_format;
-use crate::types::{AudioFrame, Frame, FrameData, Handle};
+use crate::watermark::Watermark;
+use crate::performance::{create_metrics_with_mode, PerformanceMetrics, StatsLogger};
+use crate::preview::{PreviewCache, PreviewImage};
+use crate::snapshot::{self, SnapshotFormat, SnapshotInfo};
+use crate::shutdown::ShutdownCoordinator;
+use crate::formats::{
+    bgra8_to_nv12, fourcc, fourcc_to_gs_format, has_alpha, nv12_stride, set_opaque_alpha,
+    YuvMatrix,
+};
+use crate::types::{
+    AudioFrame, AudioSampleFormat, CaptureSource, Frame, FrameData, FrameFormat, Handle,
+    SourceKind,
+};
 
 // Re-export ghoststream types for frame conversion and scaling
 use ghoststream::processing::{convert_colorspace, scale_frame};
 use ghoststream::types::{Frame as GsFrame, FrameFormat as GsFrameFormat, Resolution};
 
+/// Packets an encoder releases at a time while draining on stop
+const FLUSH_BATCH: usize = 8;
+
+/// How long draining waits for the outputs to take a batch before giving up
+const FLUSH_STALL_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// Main Nitrogen pipeline
 ///
 /// Manages the complete flow from screen capture to virtual camera output.
@@ -47,12 +89,20 @@ pub struct Pipeline {
     config: CaptureConfig,
     /// Portal capture
     portal: PortalCapture,
-    /// Capture stream (when active)
-    capture: Option<CaptureStream>,
+    /// Capture stream or layout composite (when active)
+    capture: Option<VideoCapture>,
     /// Frame receiver (reused across process calls)
     frame_rx: Option<broadcast::Receiver<Arc<Frame>>>,
+    /// Format renegotiations announced by the capture stream
+    format_rx: Option<broadcast::Receiver<FrameFormat>>,
+    /// Announced format still waiting for its first frame
+    pending_format: Option<FrameFormat>,
     /// Virtual camera (when active) - uses ghoststream's RawOutputSink
     camera: Option<VirtualCamera>,
+    /// Watches for applications opening the camera
+    camera_watcher: Option<CameraConsumerWatcher>,
+    /// Last frame written to the camera, re-sent to new consumers
+    last_camera_frame: Option<GsFrame>,
     /// Pipeline state
     state: PipelineState,
     /// Capture resolution (from portal)
@@ -68,9 +118,9 @@ pub struct Pipeline {
     /// Start time
     start_time: Option<Instant>,
     /// NVENC encoder for file recording
-    encoder: Option<NvencEncoder>,
-    /// Audio encoder for file recording
-    audio_encoder: Option<AudioEncoder>,
+    encoder: Option<Box<dyn Encoder>>,
+    /// Audio encoders, one per distinct output codec/bitrate
+    audio_encoders: AudioEncoderSet,
     /// Audio capture stream
     audio_capture: Option<AudioCaptureStream>,
     /// Audio frame receiver
@@ -79,12 +129,43 @@ pub struct Pipeline {
     virtual_mic: Option<VirtualMicrophone>,
     /// File recorder task handle
     recorder_handle: Option<JoinHandle<Result<u64>>>,
+    /// Last seconds of output, for saving replays
+    replay: Option<Arc<parking_lot::Mutex<ReplayBuffer>>>,
+    /// Task filling the replay buffer
+    replay_handle: Option<JoinHandle<Result<u64>>>,
     /// RTMP/SRT stream output task handle
     streamer_handle: Option<JoinHandle<Result<u64>>>,
+    /// Statistics of the RTMP/SRT stream, updated by its task
+    stream_stats: Option<Arc<parking_lot::Mutex<StreamStats>>>,
+    /// A/V sync offset shared with the recorder and stream output
+    sync_offset: AvSyncOffset,
     /// WebRTC output
+    #[cfg(feature = "webrtc")]
     webrtc_output: Option<Arc<RwLock<WebRTCOutput>>>,
     /// WebRTC signaling server task handle
     webrtc_server_handle: Option<JoinHandle<Result<()>>>,
+    /// Task forwarding encoded packets to WebRTC
+    webrtc_handle: Option<JoinHandle<Result<u64>>>,
+    /// Encoders for WebRTC simulcast layers that differ from the main output
+    simulcast_encoders: Vec<Box<dyn Encoder>>,
+    /// Recording encoder when `record_preset` differs from the main output
+    record_encoder: Option<Box<dyn Encoder>>,
+    /// Stream encoder when `stream_preset` differs from the main output
+    stream_encoder: Option<Box<dyn Encoder>>,
+    /// HLS output task handle
+    hls_handle: Option<JoinHandle<Result<u64>>>,
+    /// HLS HTTP server task handle
+    hls_server_handle: Option<JoinHandle<Result<()>>>,
+    /// DASH output task handle
+    dash_handle: Option<JoinHandle<Result<u64>>>,
+    /// User-provided output sinks (moved into tasks on start)
+    custom_sinks: Vec<Box<dyn FrameSink>>,
+    /// Custom sink task handles
+    sink_handles: Vec<JoinHandle<Result<u64>>>,
+    /// Periodic stats logging task handle
+    stats_handle: Option<JoinHandle<()>>,
+    /// Tells the output tasks to finalize on stop
+    shutdown: ShutdownCoordinator,
     /// Recording file path
     record_path: Option<PathBuf>,
     /// Audio samples processed
@@ -95,8 +176,180 @@ pub struct Pipeline {
     tonemapper: Tonemapper,
     /// Latency overlay renderer
     overlay: LatencyOverlay,
+    /// Logo blended into every frame
+    watermark: Option<Watermark>,
+    /// Whether the thread calling `process` has been pinned to the encode cores
+    encode_pinned: bool,
+    /// Scaler for the camera path when alpha or fitting is needed
+    camera_scaler: CameraScaler,
+    /// Privacy placeholder shown on the camera while paused
+    camera_pause: CameraPause,
+    /// Skips near-identical frames in static content mode
+    static_filter: Option<StaticFrameFilter>,
     /// Last frame time for FPS tracking
     last_frame_time: Option<Instant>,
+    /// Most recent captured frame (the picture held across a source switch)
+    last_frame: Option<Arc<Frame>>,
+    /// Frame repeated while a switched-to source starts up
+    held_frame: Option<Arc<Frame>>,
+    /// Last thumbnail served over IPC
+    preview_cache: PreviewCache,
+    /// Detects capture stalls and drives stream reconnects
+    watchdog: FrameWatchdog,
+    /// Thins a source faster than the output rate down to it
+    decimator: FrameDecimator,
+    /// Pipeline event broadcaster
+    events_tx: broadcast::Sender<PipelineEvent>,
+}
+
+/// Where video frames come from
+enum VideoCapture {
+    /// A single PipeWire stream
+    Single(CaptureStream),
+    /// Several sources composited by layout
+    Composite(CompositeCapture),
+}
+
+impl VideoCapture {
+    fn subscribe(&self) -> broadcast::Receiver<Arc<Frame>> {
+        match self {
+            Self::Single(capture) => capture.subscribe(),
+            Self::Composite(composite) => composite.subscribe(),
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        match self {
+            Self::Single(capture) => capture.is_running(),
+            Self::Composite(composite) => composite.is_running(),
+        }
+    }
+
+    async fn stop(&mut self) {
+        match self {
+            Self::Single(capture) => capture.stop(),
+            Self::Composite(composite) => composite.stop().await,
+        }
+    }
+}
+
+/// Audio encoders shared by outputs with the same codec and bitrate
+///
+/// Each output asks for its own [`OutputAudio`]; outputs that agree share an
+/// encoder, so the common case still encodes once.
+#[derive(Default)]
+struct AudioEncoderSet {
+    encoders: Vec<(OutputAudio, AudioEncoder)>,
+}
+
+impl AudioEncoderSet {
+    /// Subscribe to the encoder for `audio`, creating it on first use
+    fn subscribe(
+        &mut self,
+        audio: OutputAudio,
+    ) -> Option<broadcast::Receiver<Arc<EncodedAudioPacket>>> {
+        if let Some((_, encoder)) = self.encoders.iter().find(|(a, _)| *a == audio) {
+            return Some(encoder.subscribe());
+        }
+
+        info!(
+            "Initializing {} audio encoder ({} kbps)",
+            audio.codec,
+            audio.effective_bitrate()
+        );
+        match AudioEncoder::new(audio.codec, 48000, 2, audio.effective_bitrate()) {
+            Ok(encoder) => {
+                let rx = encoder.subscribe();
+                self.encoders.push((audio, encoder));
+                Some(rx)
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to create {} audio encoder: {}. Audio disabled for this output.",
+                    audio.codec, e
+                );
+                None
+            }
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.encoders.is_empty()
+    }
+
+    /// Feed a frame to every encoder, returning whether any accepted it
+    fn encode(&mut self, frame: &AudioFrame) -> bool {
+        let mut encoded = false;
+        for (audio, encoder) in &mut self.encoders {
+            match encoder.encode(frame) {
+                Ok(()) => encoded = true,
+                Err(e) => trace!("{} audio encoding failed: {}", audio.codec, e),
+            }
+        }
+        encoded
+    }
+
+    /// Flush and drop all encoders, closing their channels
+    fn flush(&mut self) {
+        for (audio, mut encoder) in self.encoders.drain(..) {
+            info!("Flushing {} audio encoder...", audio.codec);
+            if let Err(e) = encoder.flush() {
+                warn!("Audio encoder flush failed: {}", e);
+            }
+        }
+    }
+}
+
+/// Notable pipeline events for status reporting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PipelineEvent {
+    /// No capture frame arrived within the frame timeout
+    CaptureStalled {
+        /// Time since the last frame in milliseconds
+        elapsed_ms: u64,
+        /// Recovery attempt about to be made (starting at 1)
+        attempt: u32,
+    },
+    /// The PipeWire stream was reconnected after a stall
+    CaptureReconnected {
+        /// Recovery attempt that succeeded in reconnecting
+        attempt: u32,
+    },
+    /// Frames are flowing again after a stall
+    CaptureRecovered,
+    /// Recovery gave up after repeated stalls
+    CaptureFailed {
+        /// Number of recovery attempts made
+        attempts: u32,
+    },
+    /// The encoder session was re-created after a transient NVENC error
+    EncoderReconfigured {
+        /// Total re-inits since the encoder was created
+        reinits: u64,
+    },
+    /// The capture source changed size mid-stream (e.g. fullscreen toggle)
+    ///
+    /// Output stays at the preset resolution; the next frame is a keyframe.
+    CaptureResized {
+        /// New capture width
+        width: u32,
+        /// New capture height
+        height: u32,
+    },
+    /// Capture switched to another source via [`Pipeline::restart_capture`]
+    SourceChanged {
+        /// The new source
+        source: CaptureSource,
+        /// Width of the new source
+        width: u32,
+        /// Height of the new source
+        height: u32,
+    },
+    /// An application opened the virtual camera and was sent a full frame
+    CameraConsumerConnected {
+        /// Applications reading the camera now
+        consumers: usize,
+    },
 }
 
 /// Pipeline state
@@ -121,21 +374,47 @@ pub enum PipelineState {
 impl Pipeline {
     /// Create a new pipeline with the given configuration
     pub async fn new(config: CaptureConfig) -> Result<Self> {
+        Self::new_with_sinks(config, Vec::new()).await
+    }
+
+    /// Create a new pipeline that also feeds encoded output to custom sinks
+    ///
+    /// Each sink receives every encoded video packet (and audio packet when
+    /// audio encoding is active) from its own task once the pipeline starts.
+    pub async fn new_with_sinks(
+        config: CaptureConfig,
+        sinks: Vec<Box<dyn FrameSink>>,
+    ) -> Result<Self> {
         // Validate that at least one output is enabled
-        if !config.camera_enabled && config.record_path.is_none() && config.stream_url.is_none() {
+        if !config.camera_enabled
+            && config.record_path.is_none()
+            && config.stream_url.is_none()
+            && config.hls.is_none()
+            && config.dash.is_none()
+            && config.replay_buffer_secs.is_none()
+            && sinks.is_empty()
+        {
             return Err(NitrogenError::config(
-                "At least one output must be enabled (virtual camera, file recording, or streaming)".to_string(),
+                "At least one output must be enabled (virtual camera, file recording, streaming, HLS, DASH, or a custom sink)".to_string(),
             ));
         }
+        if config.audio_only {
+            config.validate_audio_only().map_err(NitrogenError::config)?;
+        }
+        check_output_features(&config)?;
 
-        let portal = PortalCapture::new().await?;
+        let portal = PortalCapture::new().await?.with_attempts(config.portal_attempts);
         let output_resolution = (config.width(), config.height());
         let record_path = config.record_path.clone();
 
-        // Create encoder if file recording is enabled
-        let encoder = if record_path.is_some() {
-            info!("File recording enabled, initializing NVENC encoder");
-            match NvencEncoder::new(&config) {
+        // Create encoder if file recording at the main resolution or the
+        // replay buffer is enabled
+        let encoder = if !config.audio_only
+            && ((record_path.is_some() && config.record_encoder_config().is_none())
+                || config.replay_buffer_secs.is_some())
+        {
+            info!("File recording or replay buffer enabled, initializing NVENC encoder");
+            match create_encoder(&config) {
                 Ok(enc) => Some(enc),
                 Err(e) => {
                     warn!(
@@ -149,36 +428,10 @@ impl Pipeline {
             None
         };
 
-        // Create audio encoder if audio recording is enabled
-        let audio_encoder = if record_path.is_some() && config.audio_source != AudioSource::None {
-            let audio_bitrate = if config.audio_bitrate == 0 {
-                config.audio_codec.default_bitrate()
-            } else {
-                config.audio_bitrate
-            };
-
-            info!(
-                "Audio recording enabled, initializing {:?} encoder",
-                config.audio_codec
-            );
-            match AudioEncoder::new(config.audio_codec, 48000, 2, audio_bitrate) {
-                Ok(enc) => Some(enc),
-                Err(e) => {
-                    warn!(
-                        "Failed to create audio encoder: {}. Audio recording disabled.",
-                        e
-                    );
-                    None
-                }
-            }
-        } else {
-            None
-        };
-
         let has_audio = config.audio_source != AudioSource::None;
 
         // Create performance metrics
-        let metrics = create_metrics();
+        let metrics = create_metrics_with_mode(config.metrics_mode);
 
         // Create tonemapper
         let tonemap_config = TonemapConfig {
@@ -186,6 +439,7 @@ impl Pipeline {
             algorithm: config.hdr_algorithm,
             peak_luminance: config.hdr_peak_luminance,
             sdr_white_point: 100,
+            dynamic: config.hdr_dynamic,
         };
         let tonemapper = Tonemapper::new(tonemap_config);
 
@@ -200,8 +454,18 @@ impl Pipeline {
             show_drops: true,
             font_scale: 1.0,
             background_opacity: 0.7,
+            template: config.overlay_template.clone(),
+            title: config.overlay_title.clone(),
+            timecode: config.overlay_timecode,
+            timecode_fps: config.fps(),
         };
         let overlay = LatencyOverlay::new(overlay_config);
+        let watermark = config.watermark.clone().map(Watermark::load).transpose()?;
+        // Capture and audio threads pin themselves when they're spawned
+        affinity::configure(config.cpu_affinity);
+
+        let watchdog = FrameWatchdog::from_millis(config.frame_timeout_ms);
+        let (events_tx, _) = broadcast::channel(16);
 
         info!(
             "Pipeline configured for {}x{} @ {}fps output{}{}{}",
@@ -221,13 +485,27 @@ impl Pipeline {
             }
         );
 
+        let static_filter =
+            (config.content_mode == ContentMode::Static).then(StaticFrameFilter::default);
+        let sync_offset = AvSyncOffset::new(config.av_sync_offset_ms);
+        let camera_scaler = CameraScaler::new(config.fit, config.fit_color);
+        let mut camera_pause = CameraPause::new(config.camera_transition_ms, config.fps());
+        if let Some(path) = &config.camera_placeholder {
+            camera_pause = camera_pause.load_placeholder(path)?;
+        }
+        let decimator = FrameDecimator::new(config.fps());
+
         Ok(Self {
             handle: Handle::new(),
             config,
             portal,
             capture: None,
             frame_rx: None,
+            format_rx: None,
+            pending_format: None,
             camera: None,
+            camera_watcher: None,
+            last_camera_frame: None,
             state: PipelineState::Idle,
             capture_resolution: None,
             output_resolution,
@@ -236,20 +514,47 @@ impl Pipeline {
             frames_failed: AtomicU64::new(0),
             start_time: None,
             encoder,
-            audio_encoder,
+            audio_encoders: AudioEncoderSet::default(),
             audio_capture: None,
             audio_frame_rx: None,
             virtual_mic: None,
             recorder_handle: None,
+            replay: None,
+            replay_handle: None,
             streamer_handle: None,
+            stream_stats: None,
+            sync_offset,
+            #[cfg(feature = "webrtc")]
             webrtc_output: None,
             webrtc_server_handle: None,
+            webrtc_handle: None,
+            simulcast_encoders: Vec::new(),
+            record_encoder: None,
+            stream_encoder: None,
+            hls_handle: None,
+            hls_server_handle: None,
+            dash_handle: None,
+            custom_sinks: sinks,
+            sink_handles: Vec::new(),
+            stats_handle: None,
+            shutdown: ShutdownCoordinator::new(),
             record_path,
             audio_samples_processed: AtomicU64::new(0),
             metrics,
             tonemapper,
             overlay,
+            watermark,
+            encode_pinned: false,
+            camera_scaler,
+            camera_pause,
+            static_filter,
             last_frame_time: None,
+            last_frame: None,
+            held_frame: None,
+            preview_cache: PreviewCache::default(),
+            watchdog,
+            decimator,
+            events_tx,
         })
     }
 
@@ -263,6 +568,16 @@ impl Pipeline {
         self.state
     }
 
+    /// Subscribe to pipeline events (capture stalls, recovery)
+    pub fn subscribe_events(&self) -> broadcast::Receiver<PipelineEvent> {
+        self.events_tx.subscribe()
+    }
+
+    /// Broadcast a pipeline event (no-op without subscribers)
+    fn emit(&self, event: PipelineEvent) {
+        let _ = self.events_tx.send(event);
+    }
+
     /// Start the pipeline
     ///
     /// This will:
@@ -274,6 +589,9 @@ impl Pipeline {
         if self.state != PipelineState::Idle {
             return Err(NitrogenError::SessionAlreadyRunning);
         }
+        if self.config.audio_only {
+            return self.start_audio_only();
+        }
 
         self.state = PipelineState::SelectingSource;
         info!(
@@ -281,139 +599,143 @@ impl Pipeline {
             self.handle, self.config
         );
 
-        // Determine capture type from config
-        let capture_type = match &self.config.source {
-            crate::types::CaptureSource::Monitor { .. } => CaptureType::Monitor,
-            crate::types::CaptureSource::Window { .. } => CaptureType::Window,
+        let session_info = if self.config.is_composited() {
+            self.start_composite().await?
+        } else {
+            self.start_capture().await?
         };
 
-        // Start portal session (will prompt user)
-        let session_info = self
-            .portal
-            .start_session(capture_type, self.config.cursor_mode, false)
-            .await?;
-
-        // Store capture resolution
-        self.capture_resolution = Some((session_info.width, session_info.height));
-
-        let needs_scaling = session_info.width != self.output_resolution.0
-            || session_info.height != self.output_resolution.1;
-
-        info!(
-            "Portal session started: {}x{} capture -> {}x{} output{}",
-            session_info.width,
-            session_info.height,
-            self.output_resolution.0,
-            self.output_resolution.1,
-            if needs_scaling {
-                " (scaling enabled)"
-            } else {
-                ""
-            }
-        );
-
-        // Get PipeWire fd and start capture stream
-        let fd = self.portal.take_pipewire_fd().await?;
-        let capture = CaptureStream::new(fd, session_info.node_id)?;
-
-        // Subscribe to frames
-        let frame_rx = capture.subscribe();
-        self.frame_rx = Some(frame_rx);
-        self.capture = Some(capture);
-
         // Create virtual camera using ghoststream at OUTPUT resolution (if enabled)
         if self.config.camera_enabled {
             let mut camera = create_camera(Some(&self.config.camera_name));
 
+            let format = match self.config.camera_format {
+                CameraFormat::Bgra => GsFrameFormat::Bgra,
+                CameraFormat::Nv12 => GsFrameFormat::Nv12,
+            };
             camera
                 .init_raw(
                     Resolution::new(self.output_resolution.0, self.output_resolution.1),
-                    GsFrameFormat::Bgra,
+                    format,
                 )
                 .await
                 .map_err(|e| NitrogenError::pipewire(format!("Camera init failed: {}", e)))?;
 
             self.camera = Some(camera);
-            info!("Virtual camera output enabled: {}", self.config.camera_name);
+            if self.config.camera_refresh_on_connect {
+                match CameraConsumerWatcher::start(&self.config.camera_name) {
+                    Ok(watcher) => self.camera_watcher = Some(watcher),
+                    Err(e) => warn!("Not watching for camera consumers: {}", e),
+                }
+            }
+            info!(
+                "Virtual camera output enabled: {} ({}{})",
+                self.config.camera_name,
+                self.config.camera_format,
+                if self.config.camera_alpha { " with alpha" } else { "" }
+            );
         } else {
             info!("Virtual camera output disabled");
         }
         self.state = PipelineState::WaitingForStream;
         self.start_time = Some(Instant::now());
+        self.overlay.reset_timer();
+
+        self.start_stats_logger();
 
         // Start audio capture if enabled
         if self.config.audio_source != AudioSource::None {
-            match AudioCaptureStream::new(self.config.audio_source) {
-                Ok(audio) => {
-                    let audio_rx = audio.subscribe();
-                    self.audio_frame_rx = Some(audio_rx);
-
-                    // Create virtual microphone for Discord audio passthrough
-                    let mic_rx = audio.subscribe();
-                    match VirtualMicrophone::new(
-                        Some("Nitrogen Audio"),
-                        mic_rx,
-                        48000, // sample rate
-                        2,     // stereo channels
-                    ) {
-                        Ok(mic) => {
-                            self.virtual_mic = Some(mic);
-                            info!("Virtual microphone created for Discord audio passthrough");
-                        }
-                        Err(e) => {
-                            warn!(
-                                "Failed to create virtual microphone: {}. Discord audio disabled.",
-                                e
-                            );
-                        }
-                    }
+            if let Err(e) = self.start_audio_capture(true) {
+                warn!("Failed to start audio capture: {}. Audio disabled.", e);
+            }
+        }
 
-                    self.audio_capture = Some(audio);
-                    info!("Audio capture started: {:?}", self.config.audio_source);
-                }
+        // A recording at its own resolution gets its own encoder
+        let record_config = self.config.record_encoder_config();
+        if let (Some(config), Some(_)) = (&record_config, &self.record_path) {
+            info!(
+                "Creating NVENC encoder for recording at {}x{}",
+                config.width(),
+                config.height()
+            );
+            match create_encoder(config) {
+                Ok(enc) => self.record_encoder = Some(enc),
                 Err(e) => {
-                    warn!("Failed to start audio capture: {}. Audio disabled.", e);
+                    warn!("Failed to create NVENC encoder for recording: {}. Recording disabled.", e);
                 }
             }
         }
+        let record_encoder = match record_config {
+            Some(_) => self.record_encoder.as_ref(),
+            None => self.encoder.as_ref(),
+        };
+        let record_output = record_config.as_ref().unwrap_or(&self.config);
 
         // Start file recorder if path specified and encoder is available
-        if let (Some(encoder), Some(path)) = (&self.encoder, &self.record_path) {
-            match FileRecorder::new(
+        if let (Some(encoder), Some(path)) = (record_encoder, &self.record_path) {
+            match FileRecorder::new_with_container(
                 path,
-                self.config.codec,
-                self.config.width(),
-                self.config.height(),
-                self.config.fps(),
-                self.config.effective_bitrate(),
+                self.config.record_container,
+                record_output.codec,
+                record_output.width(),
+                record_output.height(),
+                record_output.fps(),
+                record_output.effective_bitrate(),
             ) {
                 Ok(mut recorder) => {
-                    // Add audio stream if audio encoder is available
-                    let audio_rx = if let Some(ref audio_encoder) = self.audio_encoder {
-                        let audio_bitrate = if self.config.audio_bitrate == 0 {
-                            self.config.audio_codec.default_bitrate()
-                        } else {
-                            self.config.audio_bitrate
+                    if let Err(e) = recorder.set_color_range(self.config.color_range) {
+                        warn!("Failed to tag recording color range: {}", e);
+                    }
+                    recorder.set_sync_offset(self.sync_offset.clone());
+
+                    let metadata = &self.config.record_metadata;
+                    if metadata.enabled {
+                        let info = RecordingInfo {
+                            source: self.config.source.to_string(),
+                            codec: record_output.codec,
+                            preset: record_output.preset.to_string(),
+                            created: SystemTime::now(),
                         };
+                        let tags = metadata.tags(&info, recorder.is_matroska());
+                        if let Err(e) = recorder.set_metadata(&tags) {
+                            warn!("Failed to tag recording metadata: {}", e);
+                        }
+                    }
 
-                        if let Err(e) = recorder.add_audio_stream(
-                            self.config.audio_codec,
-                            48000,
-                            2,
-                            audio_bitrate,
-                        ) {
-                            warn!("Failed to add audio stream: {}", e);
-                            None
-                        } else {
-                            Some(audio_encoder.subscribe())
+                    // Add audio stream with the recording's own codec
+                    let audio_rx = if self.config.audio_source != AudioSource::None {
+                        let audio = self.config.effective_record_audio();
+                        match self.audio_encoders.subscribe(audio) {
+                            Some(rx) => {
+                                if let Err(e) = recorder.add_audio_stream(
+                                    audio.codec,
+                                    48000,
+                                    2,
+                                    audio.effective_bitrate(),
+                                ) {
+                                    warn!("Failed to add audio stream: {}", e);
+                                    None
+                                } else {
+                                    Some(rx)
+                                }
+                            }
+                            None => None,
                         }
                     } else {
                         None
                     };
 
+                    let segments = self.config.record_segments;
+                    if segments.is_enabled() {
+                        if let Err(e) = recorder.set_segments(segments) {
+                            warn!("Failed to split the recording: {}. Writing one file.", e);
+                        }
+                    }
+
                     let video_rx = encoder.subscribe();
+                    let shutdown = self.shutdown.subscribe();
                     let handle = tokio::spawn(async move {
-                        record_av_from_channels(recorder, video_rx, audio_rx).await
+                        record_av_from_channels(recorder, video_rx, audio_rx, shutdown).await
                     });
                     self.recorder_handle = Some(handle);
                     info!("File recording started: {:?}", path);
@@ -424,18 +746,39 @@ impl Pipeline {
             }
         }
 
-        // Start RTMP/SRT streaming if URL provided
+        self.start_replay_buffer();
+
+        // Start RTMP/SRT/RIST streaming if URL provided
+        #[cfg(feature = "streaming")]
         if let Some(ref stream_url) = self.config.stream_url {
-            // Validate and detect protocol
-            if let Some(protocol) = StreamProtocol::from_url(stream_url) {
+            // Validate the main destination; mirrors are checked as they are opened
+            if StreamProtocol::from_url(stream_url).is_some() {
+                let stream_override = self.config.stream_encoder_config();
                 // Streaming requires an encoder - create one if we don't have one for recording
-                let encoder_for_stream = if self.encoder.is_some() {
+                let encoder_for_stream = if let Some(ref config) = stream_override {
+                    // The stream is scaled and encoded separately from the main output
+                    info!(
+                        "Creating NVENC encoder for streaming at {}x{}",
+                        config.width(),
+                        config.height()
+                    );
+                    match create_encoder(config) {
+                        Ok(enc) => {
+                            self.stream_encoder = Some(enc);
+                            self.stream_encoder.as_ref()
+                        }
+                        Err(e) => {
+                            warn!("Failed to create encoder for streaming: {}. Streaming disabled.", e);
+                            None
+                        }
+                    }
+                } else if self.encoder.is_some() {
                     // Reuse existing encoder's broadcast channel
                     self.encoder.as_ref()
                 } else {
                     // Need to create an encoder just for streaming
                     info!("Creating NVENC encoder for streaming");
-                    match NvencEncoder::new(&self.config) {
+                    match create_encoder(&self.config) {
                         Ok(enc) => {
                             self.encoder = Some(enc);
                             self.encoder.as_ref()
@@ -448,56 +791,99 @@ impl Pipeline {
                 };
 
                 if let Some(encoder) = encoder_for_stream {
+                    let stream_output = stream_override.as_ref().unwrap_or(&self.config);
+                    let stream_audio = (self.config.audio_source != AudioSource::None)
+                        .then(|| self.config.effective_stream_audio());
                     let stream_config = StreamConfig {
-                        url: stream_url.clone(),
-                        codec: self.config.codec,
-                        width: self.config.width(),
-                        height: self.config.height(),
-                        fps: self.config.fps(),
-                        bitrate: self.config.effective_bitrate(),
-                        audio_codec: if self.config.audio_source != AudioSource::None {
-                            Some(self.config.audio_codec)
-                        } else {
-                            None
-                        },
+                        url: String::new(),
+                        codec: stream_output.codec,
+                        width: stream_output.width(),
+                        height: stream_output.height(),
+                        fps: stream_output.fps(),
+                        bitrate: stream_output.effective_bitrate(),
+                        max_bitrate: stream_output.effective_max_bitrate(),
+                        vbv_buffer_size: stream_output.effective_vbv_buffer_size(),
+                        audio_codec: stream_audio.map(|audio| audio.codec),
                         audio_sample_rate: 48000,
                         audio_channels: 2,
-                        audio_bitrate: self.config.effective_audio_bitrate(),
+                        audio_bitrate: stream_audio
+                            .map(|audio| audio.effective_bitrate())
+                            .unwrap_or(0),
+                        color_range: self.config.color_range,
+                        rist: self.config.stream_rist.clone(),
+                        srt: self.config.stream_srt.clone(),
+                        reconnect: ReconnectPolicy::default(),
                     };
 
-                    match StreamOutput::new(stream_config) {
-                        Ok(streamer) => {
-                            let video_rx = encoder.subscribe();
-                            let audio_rx = self.audio_encoder.as_ref().map(|e| e.subscribe());
-
-                            let handle = tokio::spawn(async move {
-                                stream_av_from_channels(streamer, video_rx, audio_rx).await
-                            });
-                            self.streamer_handle = Some(handle);
-
-                            // Mask stream key for logging
-                            let safe_url = StreamOutput::safe_url(stream_url);
-                            info!("{} stream started to {}", protocol, safe_url);
-                        }
-                        Err(e) => {
-                            warn!("Failed to create stream output: {}. Streaming disabled.", e);
+                    // Every destination gets its own connection to the same encoder
+                    let mut streamers = Vec::new();
+                    for url in self.config.stream_urls() {
+                        let config = StreamConfig {
+                            url: url.to_string(),
+                            ..stream_config.clone()
+                        };
+                        match StreamOutput::new(config) {
+                            Ok(mut streamer) => {
+                                streamer.set_sync_offset(self.sync_offset.clone());
+                                streamer.set_keyframe_requester(encoder.keyframe_requester());
+                                // Mask stream key for logging
+                                info!(
+                                    "{} stream started to {}",
+                                    streamer.protocol(),
+                                    streamer.masked_url()
+                                );
+                                streamers.push(streamer);
+                            }
+                            Err(e) => warn!(
+                                "Failed to create stream output to {}: {}",
+                                StreamOutput::safe_url(url),
+                                e
+                            ),
                         }
                     }
+
+                    if streamers.is_empty() {
+                        warn!("No stream output could be created. Streaming disabled.");
+                    } else {
+                        let video_rx = encoder.subscribe();
+                        let audio_rx =
+                            stream_audio.and_then(|audio| self.audio_encoders.subscribe(audio));
+
+                        // Status shows the main destination
+                        self.stream_stats = Some(streamers[0].stats_handle());
+                        let streamer = MultiStreamOutput::new(streamers);
+                        let shutdown = self.shutdown.subscribe();
+                        let handle = tokio::spawn(async move {
+                            multi_stream_av_from_channels(streamer, video_rx, audio_rx, shutdown)
+                                .await
+                        });
+                        self.streamer_handle = Some(handle);
+                    }
                 }
             } else {
                 warn!(
-                    "Invalid stream URL: {}. Must be rtmp://, rtmps://, or srt://",
-                    stream_url
+                    "Invalid stream URL: {}. Must be rtmp://, rtmps://, srt://, or rist://",
+                    StreamOutput::safe_url(stream_url)
                 );
             }
         }
 
         // Start WebRTC output if enabled
-        if self.config.webrtc_enabled {
+        #[cfg(feature = "webrtc")]
+        let webrtc_enabled = self.config.webrtc_enabled
+            && match check_webrtc_codec(&self.config.webrtc_video_codec, self.config.codec) {
+                Ok(()) => true,
+                Err(e) => {
+                    warn!("{}. WebRTC disabled.", e);
+                    false
+                }
+            };
+        #[cfg(feature = "webrtc")]
+        if webrtc_enabled {
             // WebRTC requires an encoder - create one if we don't have one
             if self.encoder.is_none() {
                 info!("Creating NVENC encoder for WebRTC");
-                match NvencEncoder::new(&self.config) {
+                match create_encoder(&self.config) {
                     Ok(enc) => {
                         self.encoder = Some(enc);
                     }
@@ -507,22 +893,68 @@ impl Pipeline {
                 }
             }
 
-            if self.encoder.is_some() {
+            if let Some(ref encoder) = self.encoder {
+                // Simulcast layers at the output size share the main encoder,
+                // the others get their own
+                let layers = usable_simulcast_layers(&self.config);
+                let mut layer_rx = Vec::new();
+                let mut layer_requesters = Vec::new();
+                let mut published = Vec::new();
+                for layer in layers {
+                    if layer.height == self.config.height() {
+                        layer_rx.push(encoder.subscribe());
+                        layer_requesters.push(encoder.keyframe_requester());
+                        published.push(layer);
+                        continue;
+                    }
+                    match create_encoder(&simulcast_layer_config(&self.config, &layer)) {
+                        Ok(layer_encoder) => {
+                            layer_rx.push(layer_encoder.subscribe());
+                            layer_requesters.push(layer_encoder.keyframe_requester());
+                            self.simulcast_encoders.push(layer_encoder);
+                            published.push(layer);
+                        }
+                        Err(e) => warn!("Skipping simulcast layer {}: {}", layer.rid, e),
+                    }
+                }
+                if published.len() < 2 {
+                    // Not enough layers for simulcast, send the main encoder only
+                    published.clear();
+                    layer_requesters.clear();
+                    self.simulcast_encoders.clear();
+                    layer_rx = vec![encoder.subscribe()];
+                }
+                let audio_rx = if self.config.audio_source != AudioSource::None {
+                    self.audio_encoders.subscribe(self.config.effective_webrtc_audio())
+                } else {
+                    None
+                };
+
                 let webrtc_config = WebRTCConfig {
-                    ice_servers: vec!["stun:stun.l.google.com:19302".to_string()],
-                    video_codec: "h264".to_string(),
+                    ice_servers: self.config.webrtc_ice_servers.clone(),
+                    video_codec: self.config.webrtc_video_codec.clone(),
                     video_payload_type: 96,
-                    audio_enabled: self.config.audio_source != AudioSource::None,
+                    fps: self.config.fps(),
+                    audio_enabled: audio_rx.is_some(),
+                    simulcast: published,
                 };
 
                 match WebRTCOutput::new(webrtc_config).await {
                     Ok(mut output) => {
+                        output.set_keyframe_requester(encoder.keyframe_requester());
+                        output.set_layer_keyframe_requesters(layer_requesters);
                         if let Err(e) = output.init().await {
                             warn!("Failed to initialize WebRTC: {}", e);
+                            self.simulcast_encoders.clear();
                         } else {
                             let output = Arc::new(RwLock::new(output));
                             self.webrtc_output = Some(output.clone());
 
+                            let forward_output = output.clone();
+                            self.webrtc_handle = Some(tokio::spawn(async move {
+                                webrtc_from_channels(forward_output, layer_rx, audio_rx).await
+                            }));
+
                             // Start the signaling server
                             let port = self.config.webrtc_port;
                             let server_handle = tokio::spawn(async move {
@@ -535,7 +967,181 @@ impl Pipeline {
                     }
                     Err(e) => {
                         warn!("Failed to create WebRTC output: {}", e);
+                        self.simulcast_encoders.clear();
+                    }
+                }
+            }
+        }
+
+        // Start HLS output if configured
+        if let Some(ref hls_config) = self.config.hls {
+            if self.encoder.is_none() {
+                info!("Creating NVENC encoder for HLS");
+                match create_encoder(&self.config) {
+                    Ok(enc) => {
+                        self.encoder = Some(enc);
+                    }
+                    Err(e) => {
+                        warn!("Failed to create encoder for HLS: {}. HLS disabled.", e);
+                    }
+                }
+            }
+
+            if let Some(ref encoder) = self.encoder {
+                match HlsOutput::new(
+                    hls_config.clone(),
+                    self.config.codec,
+                    self.config.width(),
+                    self.config.height(),
+                    self.config.fps(),
+                    self.config.effective_bitrate(),
+                ) {
+                    Ok(mut hls) => {
+                        if let Err(e) = hls.set_color_range(self.config.color_range) {
+                            warn!("Failed to tag HLS color range: {}", e);
+                        }
+
+                        let audio_rx = if self.config.audio_source != AudioSource::None {
+                            let audio = self.config.shared_audio();
+                            match self.audio_encoders.subscribe(audio) {
+                                Some(rx) => {
+                                    if let Err(e) = hls.add_audio_stream(
+                                        audio.codec,
+                                        48000,
+                                        2,
+                                        audio.effective_bitrate(),
+                                    ) {
+                                        warn!("Failed to add HLS audio stream: {}", e);
+                                        None
+                                    } else {
+                                        Some(rx)
+                                    }
+                                }
+                                None => None,
+                            }
+                        } else {
+                            None
+                        };
+
+                        let video_rx = encoder.subscribe();
+                        let shutdown = self.shutdown.subscribe();
+                        let handle = tokio::spawn(async move {
+                            run_sink(Box::new(hls), video_rx, audio_rx, shutdown).await
+                        });
+                        self.hls_handle = Some(handle);
+
+                        if hls_config.port != 0 {
+                            let dir = hls_config.output_dir.clone();
+                            let port = hls_config.port;
+                            let server_handle =
+                                tokio::spawn(async move { start_hls_server(dir, port).await });
+                            self.hls_server_handle = Some(server_handle);
+                            info!("HLS output enabled - view at http://localhost:{}", port);
+                        } else {
+                            info!("HLS output enabled: {:?}", hls_config.playlist_path());
+                        }
+                    }
+                    Err(e) => {
+                        warn!("Failed to create HLS output: {}. HLS disabled.", e);
+                    }
+                }
+            }
+        }
+
+        // Start DASH output if configured
+        if let Some(ref dash_config) = self.config.dash {
+            if self.encoder.is_none() {
+                info!("Creating NVENC encoder for DASH");
+                match create_encoder(&self.config) {
+                    Ok(enc) => {
+                        self.encoder = Some(enc);
+                    }
+                    Err(e) => {
+                        warn!("Failed to create encoder for DASH: {}. DASH disabled.", e);
+                    }
+                }
+            }
+
+            if let Some(ref encoder) = self.encoder {
+                match DashOutput::new(
+                    dash_config.clone(),
+                    self.config.codec,
+                    self.config.width(),
+                    self.config.height(),
+                    self.config.fps(),
+                    self.config.effective_bitrate(),
+                ) {
+                    Ok(mut dash) => {
+                        if let Err(e) = dash.set_color_range(self.config.color_range) {
+                            warn!("Failed to tag DASH color range: {}", e);
+                        }
+
+                        let audio_rx = if self.config.audio_source != AudioSource::None {
+                            let audio = self.config.shared_audio();
+                            match self.audio_encoders.subscribe(audio) {
+                                Some(rx) => {
+                                    if let Err(e) = dash.add_audio_stream(
+                                        audio.codec,
+                                        48000,
+                                        2,
+                                        audio.effective_bitrate(),
+                                    ) {
+                                        warn!("Failed to add DASH audio stream: {}", e);
+                                        None
+                                    } else {
+                                        Some(rx)
+                                    }
+                                }
+                                None => None,
+                            }
+                        } else {
+                            None
+                        };
+
+                        let video_rx = encoder.subscribe();
+                        let shutdown = self.shutdown.subscribe();
+                        let handle = tokio::spawn(async move {
+                            run_sink(Box::new(dash), video_rx, audio_rx, shutdown).await
+                        });
+                        self.dash_handle = Some(handle);
+                        info!("DASH output enabled: {:?}", dash_config.manifest_path());
+                    }
+                    Err(e) => {
+                        warn!("Failed to create DASH output: {}. DASH disabled.", e);
+                    }
+                }
+            }
+        }
+
+        // Start custom sinks
+        if !self.custom_sinks.is_empty() {
+            if self.encoder.is_none() {
+                info!("Creating NVENC encoder for custom sinks");
+                match create_encoder(&self.config) {
+                    Ok(enc) => {
+                        self.encoder = Some(enc);
                     }
+                    Err(e) => {
+                        warn!("Failed to create encoder for custom sinks: {}. Sinks disabled.", e);
+                    }
+                }
+            }
+
+            if let Some(ref encoder) = self.encoder {
+                for sink in self.custom_sinks.drain(..) {
+                    let name = sink.name().to_string();
+                    let video_rx = encoder.subscribe();
+                    let audio_rx = if self.config.audio_source != AudioSource::None {
+                        self.audio_encoders.subscribe(self.config.shared_audio())
+                    } else {
+                        None
+                    };
+                    let shutdown = self.shutdown.subscribe();
+                    let handle = tokio::spawn(async move {
+                        run_sink(sink, video_rx, audio_rx, shutdown).await
+                    });
+                    self.sink_handles.push(handle);
+                    info!("Custom output sink '{}' started", name);
                 }
             }
         }
@@ -551,11 +1157,330 @@ impl Pipeline {
         Ok(session_info)
     }
 
+    /// Start capturing the configured audio source
+    ///
+    /// With `virtual_mic` the audio is also played into a virtual microphone
+    /// for Discord passthrough.
+    fn start_audio_capture(&mut self, virtual_mic: bool) -> Result<()> {
+        let audio = AudioCaptureStream::with_latency_quantum(
+            self.config.audio_source.clone(),
+            self.config.mic_channel,
+            AudioSampleFormat::default(),
+            self.config.audio_latency_quantum,
+        )?;
+        self.audio_frame_rx = Some(audio.subscribe());
+
+        if virtual_mic {
+            let mic_rx = audio.subscribe();
+            match VirtualMicrophone::new(
+                Some("Nitrogen Audio"),
+                mic_rx,
+                48000, // sample rate
+                2,     // stereo channels
+            ) {
+                Ok(mic) => {
+                    self.virtual_mic = Some(mic);
+                    info!("Virtual microphone created for Discord audio passthrough");
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to create virtual microphone: {}. Discord audio disabled.",
+                        e
+                    );
+                }
+            }
+        }
+
+        self.audio_capture = Some(audio);
+        info!("Audio capture started: {:?}", self.config.audio_source);
+        Ok(())
+    }
+
+    /// Start filling the replay buffer from the main encoder, if it is on
+    ///
+    /// Replays use the main output's codec and resolution with the
+    /// recording's container and audio settings.
+    fn start_replay_buffer(&mut self) {
+        let Some(secs) = self.config.replay_buffer_secs else {
+            return;
+        };
+        let Some(encoder) = &self.encoder else {
+            warn!("No video encoder is available. Replay buffer disabled.");
+            return;
+        };
+
+        let mut buffer = ReplayBuffer::new(
+            Duration::from_secs(secs as u64),
+            self.config.codec,
+            self.config.width(),
+            self.config.height(),
+            self.config.fps(),
+            self.config.effective_bitrate(),
+        )
+        .with_container(self.config.record_container)
+        .with_color_range(self.config.color_range)
+        .with_sync_offset(self.sync_offset.clone());
+
+        let audio_rx = if self.config.audio_source != AudioSource::None {
+            let audio = self.config.effective_record_audio();
+            let rx = self.audio_encoders.subscribe(audio);
+            if rx.is_some() {
+                buffer = buffer.with_audio(audio.codec, 48000, 2, audio.effective_bitrate());
+            }
+            rx
+        } else {
+            None
+        };
+
+        let buffer = Arc::new(parking_lot::Mutex::new(buffer));
+        self.replay_handle = Some(tokio::spawn(buffer_replay_from_channels(
+            buffer.clone(),
+            encoder.subscribe(),
+            audio_rx,
+            self.shutdown.subscribe(),
+        )));
+        self.replay = Some(buffer);
+        info!("Replay buffer enabled: last {}s", secs);
+    }
+
+    /// Start an audio-only recording: no portal, video capture or encoder
+    fn start_audio_only(&mut self) -> Result<SessionInfo> {
+        let path = self
+            .record_path
+            .clone()
+            .ok_or_else(|| NitrogenError::config("Audio-only mode needs a recording file"))?;
+
+        self.start_audio_capture(false)?;
+
+        let audio = self.config.effective_record_audio();
+        let recorder = FileRecorder::audio_only(
+            &path,
+            audio.codec,
+            48000,
+            2,
+            audio.effective_bitrate(),
+        )?;
+        let audio_rx = self.audio_encoders.subscribe(audio).ok_or_else(|| {
+            NitrogenError::encoder(format!("Failed to create {} audio encoder", audio.codec))
+        })?;
+
+        let shutdown = self.shutdown.subscribe();
+        self.recorder_handle = Some(tokio::spawn(record_audio_from_channel(
+            recorder, audio_rx, shutdown,
+        )));
+        info!("Audio-only recording started: {:?}", path);
+
+        self.state = PipelineState::Running;
+        self.start_time = Some(Instant::now());
+
+        Ok(SessionInfo {
+            node_id: 0,
+            width: 0,
+            height: 0,
+            source_type: SourceKind::Virtual,
+            cursor_mode: CursorMode::Hidden,
+        })
+    }
+
+    /// Start the portal session and PipeWire stream for `config.source`
+    async fn start_capture(&mut self) -> Result<SessionInfo> {
+        let session_info =
+            start_source_session(&self.portal, &self.config.source, self.config.cursor_mode)
+                .await?;
+        self.connect_capture(&session_info).await?;
+        Ok(session_info)
+    }
+
+    /// Connect a capture stream to the portal's current session
+    async fn connect_capture(&mut self, session_info: &SessionInfo) -> Result<()> {
+        // Store capture resolution
+        self.capture_resolution = Some((session_info.width, session_info.height));
+
+        let needs_scaling = session_info.width != self.output_resolution.0
+            || session_info.height != self.output_resolution.1;
+
+        info!(
+            "Portal session started: {}x{} capture -> {}x{} output{}",
+            session_info.width,
+            session_info.height,
+            self.output_resolution.0,
+            self.output_resolution.1,
+            if needs_scaling {
+                " (scaling enabled)"
+            } else {
+                ""
+            }
+        );
+
+        // Get PipeWire fd and start capture stream
+        let fd = self.portal.take_pipewire_fd().await?;
+        // DMA-BUF frames only pay off when NVENC can import them; the camera
+        // path would just map them back to system memory, and static content
+        // mode has to compare pixels on the CPU. A cursor drawn from metadata,
+        // a burned-in timecode, or a watermark also needs the pixels in system
+        // memory.
+        let cursor_scale = (session_info.cursor_mode == CursorMode::Metadata)
+            .then_some(self.config.cursor_scale);
+        let options = StreamOptions {
+            prefer_alpha: self.config.camera_alpha && self.config.camera_enabled,
+            dmabuf: self.config.has_encoded_output()
+                && self.config.content_mode == ContentMode::Motion
+                && cursor_scale.is_none()
+                && self.config.overlay_timecode.is_none()
+                && self.config.watermark.is_none()
+                && zero_copy_supported(&self.config),
+            cursor_scale,
+            hdr_input: self.config.hdr_input,
+            latency_quantum: self.config.capture_latency_quantum,
+        };
+        if let Some(scale) = cursor_scale {
+            info!("Drawing the cursor from portal metadata at {}x scale", scale);
+        }
+        if options.dmabuf {
+            info!("Requesting DMA-BUF frames for zero-copy encoding");
+        }
+        let capture = CaptureStream::with_options(fd, session_info.node_id, options)?;
+
+        // Subscribe to frames
+        let frame_rx = capture.subscribe();
+        self.frame_rx = Some(frame_rx);
+        self.format_rx = Some(capture.subscribe_format_changes());
+        self.pending_format = None;
+        self.capture = Some(VideoCapture::Single(capture));
+
+        Ok(())
+    }
+
+    /// Switch capture to another source without stopping the outputs
+    ///
+    /// The new source is picked on a second portal session while the old
+    /// stream keeps feeding the encoders and camera. Once it is selected the
+    /// old stream is torn down and the last captured picture is repeated
+    /// until the new stream delivers, so the camera and recordings see no
+    /// gap. The encoders rescale whatever size the new source has to the
+    /// output resolution, and a keyframe is requested at the switch. If the
+    /// selection fails or is cancelled, the old source keeps running.
+    ///
+    /// Not supported with a layout, since each layer has its own source.
+    pub async fn restart_capture(&mut self, new_source: CaptureSource) -> Result<SessionInfo> {
+        if !matches!(
+            self.state,
+            PipelineState::Running | PipelineState::WaitingForStream
+        ) {
+            return Err(NitrogenError::NoActiveSession);
+        }
+        if !matches!(self.capture, Some(VideoCapture::Single(_))) {
+            return Err(NitrogenError::Unsupported(
+                "Switching sources is not supported with a layout".to_string(),
+            ));
+        }
+
+        info!(
+            "Pipeline {} switching source: {:?} -> {:?}",
+            self.handle, self.config.source, new_source
+        );
+
+        // Keep the current source flowing while the user picks the new one
+        let portal = PortalCapture::new().await?.with_attempts(self.config.portal_attempts);
+        let session = start_source_session(&portal, &new_source, self.config.cursor_mode);
+        tokio::pin!(session);
+        let mut old_rx = self.frame_rx.take();
+        let result = loop {
+            tokio::select! {
+                result = &mut session => break result,
+                frame = next_frame(&mut old_rx) => {
+                    self.last_frame = Some(frame.clone());
+                    self.process_frame(&frame).await?;
+                }
+            }
+        };
+
+        let session_info = match result {
+            Ok(info) => info,
+            Err(e) => {
+                warn!("Source switch failed, keeping the current source: {}", e);
+                self.frame_rx = self.capture.as_ref().map(|c| c.subscribe());
+                return Err(e);
+            }
+        };
+
+        // DMA-BUF frames belong to the old stream's buffer pool, so the held
+        // picture has to be copied out before the stream goes away
+        self.held_frame = self.last_frame.take().and_then(|frame| held_frame(&frame));
+        if self.held_frame.is_none() {
+            debug!("No frame to hold during the source switch");
+        }
+
+        if let Some(mut capture) = self.capture.take() {
+            capture.stop().await;
+        }
+        if let Err(e) = self.portal.stop_session().await {
+            debug!("Failed to close the previous portal session: {}", e);
+        }
+        self.portal = portal;
+
+        self.connect_capture(&session_info).await?;
+        self.config.source = new_source.clone();
+        self.state = PipelineState::WaitingForStream;
+        self.watchdog.disarm();
+        self.decimator.reset();
+        self.tonemapper.reset_dynamic_peak();
+        if let Err(e) = self.request_keyframe() {
+            trace!("No keyframe after source switch: {}", e);
+        }
+
+        self.emit(PipelineEvent::SourceChanged {
+            source: new_source,
+            width: session_info.width,
+            height: session_info.height,
+        });
+
+        Ok(session_info)
+    }
+
+    /// Start one capture per layout layer and composite them at output size
+    async fn start_composite(&mut self) -> Result<SessionInfo> {
+        let (width, height) = self.output_resolution;
+        let composite = CompositeCapture::start(
+            self.config.layout.clone(),
+            width,
+            height,
+            self.config.fps(),
+            self.config.cursor_mode,
+            self.config.cursor_scale,
+        )
+        .await?;
+
+        // Composited frames are already at output size
+        self.capture_resolution = Some((width, height));
+        let first = &composite.sessions()[0];
+        let session_info = SessionInfo {
+            node_id: first.node_id,
+            width,
+            height,
+            source_type: first.source_type,
+            cursor_mode: first.cursor_mode,
+        };
+
+        self.frame_rx = Some(composite.subscribe());
+        self.format_rx = None;
+        self.capture = Some(VideoCapture::Composite(composite));
+        Ok(session_info)
+    }
+
     /// Process frames in the pipeline
     ///
     /// This should be called in a loop while the pipeline is running.
     /// Returns false when the pipeline should stop.
+    ///
+    /// The first call pins the calling thread to the `encode` cores of
+    /// `cpu_affinity`, since frames are converted and encoded here.
     pub async fn process(&mut self) -> Result<bool> {
+        if !self.encode_pinned {
+            affinity::pin_current_thread(ThreadRole::Encode);
+            self.encode_pinned = true;
+        }
+
         match self.state {
             PipelineState::Stopped | PipelineState::Error | PipelineState::Idle => {
                 return Ok(false);
@@ -573,21 +1498,46 @@ impl Pipeline {
                 if let Some(ref capture) = self.capture {
                     if capture.is_running() {
                         self.state = PipelineState::Running;
+                        self.held_frame = None;
+                        self.watchdog.arm();
                         info!("Pipeline {} now streaming", self.handle);
                     } else {
-                        // Wait a bit for stream to start
-                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        // A reconnecting stream that never comes back still counts as a stall
+                        self.check_frame_watchdog().await?;
+
+                        // Repeat the held picture at the output rate across a
+                        // source switch; otherwise wait a bit for the stream
+                        if let Some(frame) = self.held_frame.clone() {
+                            self.process_frame(&frame).await?;
+                            let fps = self.config.fps().max(1);
+                            tokio::time::sleep(Duration::from_secs(1) / fps).await;
+                        } else {
+                            tokio::time::sleep(Duration::from_millis(50)).await;
+                        }
                         return Ok(true);
                     }
                 } else {
                     return Ok(false);
                 }
             }
+            PipelineState::Running if self.config.audio_only => {
+                // No video frames; keep the audio encoders fed
+                if self.audio_capture.as_ref().is_some_and(|a| !a.is_running()) {
+                    warn!("Audio capture stopped unexpectedly");
+                    self.state = PipelineState::Error;
+                    return Ok(false);
+                }
+                self.process_audio_frames();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                return Ok(true);
+            }
             PipelineState::Running => {
                 // Continue to process frames below
             }
         }
 
+        self.refresh_camera_consumers().await;
+
         // Get frame receiver (create new one if needed due to lag)
         if self.frame_rx.is_none() {
             if let Some(ref capture) = self.capture {
@@ -615,8 +1565,18 @@ impl Pipeline {
 
         match recv_result {
             Ok(Ok(frame)) => {
+                if self.watchdog.frame_received() {
+                    info!("Capture recovered, frames are flowing again");
+                    self.emit(PipelineEvent::CaptureRecovered);
+                }
+                // Drop evenly down to the output rate instead of lagging
+                if !self.decimator.keep(frame.pts) {
+                    self.metrics.record_frame_dropped();
+                    return Ok(true);
+                }
                 // Process the frame
                 self.process_frame(&frame).await?;
+                self.last_frame = Some(frame);
                 Ok(true)
             }
             Ok(Err(broadcast::error::RecvError::Lagged(n))) => {
@@ -636,11 +1596,174 @@ impl Pipeline {
             Err(_) => {
                 // Timeout - no frame available, but keep running
                 trace!("No frame available (timeout)");
+                self.check_frame_watchdog().await?;
                 Ok(true)
             }
         }
     }
 
+    /// Re-send the last camera frame when an application opens the camera
+    ///
+    /// A fresh consumer otherwise waits for the next captured frame (long
+    /// with static content) and may start on a stale buffer. The camera
+    /// carries raw frames, so there is no keyframe to request for it.
+    async fn refresh_camera_consumers(&mut self) {
+        let Some(watcher) = &self.camera_watcher else {
+            return;
+        };
+        if !watcher.take_connected() {
+            return;
+        }
+        let consumers = watcher.consumers();
+
+        if let (Some(camera), Some(frame)) = (&mut self.camera, &self.last_camera_frame) {
+            match camera.write_frame(frame).await {
+                Ok(()) => debug!("Sent a full frame to the new camera consumer"),
+                Err(e) => warn!("Failed to refresh the camera for a new consumer: {}", e),
+            }
+        }
+        self.emit(PipelineEvent::CameraConsumerConnected { consumers });
+    }
+
+    /// Check the frame watchdog and reconnect the capture stream on a stall
+    ///
+    /// Returns an error once every recovery attempt has failed.
+    async fn check_frame_watchdog(&mut self) -> Result<()> {
+        match self.watchdog.check() {
+            WatchdogStatus::Healthy => Ok(()),
+            WatchdogStatus::Stalled { elapsed, attempt } => {
+                warn!(
+                    "No capture frame for {}ms, reconnecting PipeWire stream (attempt {})",
+                    elapsed.as_millis(),
+                    attempt
+                );
+                self.emit(PipelineEvent::CaptureStalled {
+                    elapsed_ms: elapsed.as_millis() as u64,
+                    attempt,
+                });
+
+                // Layout layers each hold their last frame; there's no single
+                // stream to reconnect
+                if !matches!(self.capture, Some(VideoCapture::Single(_))) {
+                    return Ok(());
+                }
+
+                let fd = match self.portal.reopen_pipewire_fd().await {
+                    Ok(fd) => fd,
+                    Err(e) => {
+                        warn!("Failed to reopen PipeWire remote: {}", e);
+                        return Ok(());
+                    }
+                };
+                if let Some(VideoCapture::Single(ref mut capture)) = self.capture {
+                    match capture.reconnect(fd) {
+                        Ok(()) => {
+                            self.frame_rx = Some(capture.subscribe());
+                            self.state = PipelineState::WaitingForStream;
+                            self.emit(PipelineEvent::CaptureReconnected { attempt });
+                        }
+                        Err(e) => warn!("Failed to reconnect capture stream: {}", e),
+                    }
+                }
+                Ok(())
+            }
+            WatchdogStatus::Exhausted { attempts } => {
+                error!(
+                    "Capture stream stalled and did not recover after {} attempts",
+                    attempts
+                );
+                self.emit(PipelineEvent::CaptureFailed { attempts });
+                self.state = PipelineState::Error;
+                Err(NitrogenError::pipewire(format!(
+                    "Capture stalled: no frames after {} reconnect attempts",
+                    attempts
+                )))
+            }
+        }
+    }
+
+    /// Follow the capture size when the source renegotiates mid-stream
+    ///
+    /// A change announced by the stream is applied on the first frame in the
+    /// new format, and a frame that doesn't match the known capture size
+    /// (reconnects, portal sizes off by a few pixels) counts as one too. The
+    /// output stays at the preset: the encoders and the camera scaler adapt
+    /// their input per frame, so the encode sessions carry on and only need
+    /// a keyframe to start the new picture cleanly.
+    fn track_capture_format(&mut self, format: &FrameFormat) {
+        if let Some(rx) = self.format_rx.as_mut() {
+            while let Ok(announced) = rx.try_recv() {
+                self.pending_format = Some(announced);
+            }
+        }
+
+        let size = (format.width, format.height);
+        let announced = self.pending_format.is_some_and(|pending| {
+            (pending.width, pending.height, pending.fourcc) == (size.0, size.1, format.fourcc)
+        });
+        if !announced && self.capture_resolution.is_none_or(|known| known == size) {
+            return;
+        }
+
+        self.pending_format = None;
+        let previous = self.capture_resolution.replace(size);
+        info!(
+            "Capture resized {} -> {}x{}, output stays {}x{}",
+            previous
+                .map(|(w, h)| format!("{}x{}", w, h))
+                .unwrap_or_else(|| "unknown".to_string()),
+            size.0,
+            size.1,
+            self.output_resolution.0,
+            self.output_resolution.1
+        );
+        if let Err(e) = self.request_keyframe() {
+            trace!("No keyframe after capture resize: {}", e);
+        }
+        self.emit(PipelineEvent::CaptureResized {
+            width: size.0,
+            height: size.1,
+        });
+    }
+
+    /// Copy of the frame with the watermark and timecode burned in, for
+    /// every output
+    ///
+    /// Returns None when neither is configured or the frame isn't in system
+    /// memory; the timecode only advances for frames that get one.
+    fn burn_in(&mut self, frame: &Frame) -> Option<Frame> {
+        if !self.overlay.has_timecode() && self.watermark.is_none() {
+            return None;
+        }
+        let Some(pixels) = frame.to_bgra8() else {
+            trace!("No burn-in on frame in {:#x}: not in system memory", frame.format.fourcc);
+            return None;
+        };
+
+        let (width, height) = (frame.format.width, frame.format.height);
+        let mut pixels = pixels.into_owned();
+        if let Some(watermark) = &self.watermark {
+            watermark.render(&mut pixels, width, height);
+        }
+        self.overlay.render_timecode(&mut pixels, width, height);
+
+        Some(Frame {
+            format: FrameFormat {
+                width,
+                height,
+                fourcc: if has_alpha(frame.format.fourcc) {
+                    fourcc::ARGB8888
+                } else {
+                    fourcc::XRGB8888
+                },
+                stride: width * 4,
+            },
+            data: FrameData::Memory(pixels),
+            pts: frame.pts,
+            hdr_metadata: frame.hdr_metadata,
+        })
+    }
+
     /// Process a single frame
     async fn process_frame(&mut self, frame: &Frame) -> Result<()> {
         let frame_start = Instant::now();
@@ -651,20 +1774,92 @@ impl Pipeline {
         }
         self.last_frame_time = Some(frame_start);
 
+        self.track_capture_format(&frame.format);
+
+        // Opt-in frame tracing: grep "frame-hash" and match PTS/hash pairs
+        // across stages to find drops and duplicates
+        let hash = if self.config.frame_hashing {
+            frame_hash(frame)
+        } else {
+            None
+        };
+        if let Some(hash) = hash {
+            trace!("frame-hash capture pts={} hash={:08x}", frame.pts, hash);
+        }
+
+        // The watermark and timecode go into the pixels before any encoder or
+        // the camera sees them; static detection still compares the captured
+        // frames
+        let captured = frame;
+        let burned_in = self.burn_in(frame);
+        let frame = burned_in.as_ref().unwrap_or(frame);
+
         // Encode video frame for file recording if encoder is active
         let encode_start = Instant::now();
+        let encoding = self.encoder.is_some()
+            || self.record_encoder.is_some()
+            || self.stream_encoder.is_some();
+        let decision = match self.static_filter {
+            Some(ref mut filter) if encoding => filter.check(captured),
+            _ => FrameDecision::Encode,
+        };
+        let mut reinits = None;
         if let Some(ref mut encoder) = self.encoder {
-            if let Err(e) = encoder.encode(frame) {
+            let reinits_before = encoder.reinit_count();
+            if decision == FrameDecision::Refresh {
+                // Periodic IDR so viewers joining a static stream can sync
+                encoder.request_keyframe();
+            }
+
+            for layer_encoder in &mut self.simulcast_encoders {
+                if let Err(e) = encode_secondary(layer_encoder, frame, decision) {
+                    trace!("Simulcast layer encoding failed: {}", e);
+                }
+            }
+
+            if decision == FrameDecision::Skip {
+                encoder.skip_frame();
+                if let Some(hash) = hash {
+                    trace!("frame-hash skip pts={} hash={:08x}", frame.pts, hash);
+                }
+            } else if let Err(e) = encoder.encode(frame) {
                 // Log but don't fail - camera output can still work
                 trace!("Video encoding failed: {}", e);
             } else {
+                if let Some(hash) = hash {
+                    trace!("frame-hash encode pts={} hash={:08x}", frame.pts, hash);
+                }
                 self.metrics.record_encode_timing(encode_start, Instant::now());
+                if encoder.last_frame_zero_copy() {
+                    self.metrics.record_zero_copy_frame();
+                }
+            }
+
+            if encoder.reinit_count() != reinits_before {
+                reinits = Some(encoder.reinit_count());
+            }
+        }
+        if let Some(reinits) = reinits {
+            self.emit(PipelineEvent::EncoderReconfigured { reinits });
+        }
+
+        // Outputs at their own resolution share the captured frame and only
+        // duplicate the scale + encode
+        let output_encoders = self.record_encoder.iter_mut().chain(self.stream_encoder.iter_mut());
+        for output_encoder in output_encoders {
+            if let Err(e) = encode_secondary(output_encoder, frame, decision) {
+                trace!("Per-output encoding failed: {}", e);
             }
         }
 
         // Process any available audio frames
         self.process_audio_frames();
 
+        // Without a camera, the converted frame would be thrown away
+        if self.camera.is_none() {
+            return Ok(());
+        }
+
         let capture_start = Instant::now();
 
         let gs_frame = match &frame.data {
@@ -675,9 +1870,27 @@ impl Pipeline {
                 let (dst_width, dst_height) = self.output_resolution;
 
                 // Process frame: convert to BGRA if needed, then scale if needed
-                let mut processed_data = process_frame_data(
-                    data, src_width, src_height, src_format, dst_width, dst_height,
-                )?;
+                let mut processed_data = if self.config.camera_alpha {
+                    process_frame_data_alpha(
+                        data,
+                        src_width,
+                        src_height,
+                        frame.format.fourcc,
+                        dst_width,
+                        dst_height,
+                        &mut self.camera_scaler,
+                    )?
+                } else {
+                    process_frame_data(
+                        data,
+                        src_width,
+                        src_height,
+                        src_format,
+                        dst_width,
+                        dst_height,
+                        &mut self.camera_scaler,
+                    )?
+                };
 
                 // Apply HDR tonemapping if needed
                 self.tonemapper.tonemap(
@@ -716,9 +1929,29 @@ impl Pipeline {
                         let src_format = fourcc_to_gs_format(frame.format.fourcc);
                         let (dst_width, dst_height) = self.output_resolution;
 
-                        match process_frame_data(
-                            &data, src_width, src_height, src_format, dst_width, dst_height,
-                        ) {
+                        let processed = if self.config.camera_alpha {
+                            process_frame_data_alpha(
+                                &data,
+                                src_width,
+                                src_height,
+                                frame.format.fourcc,
+                                dst_width,
+                                dst_height,
+                                &mut self.camera_scaler,
+                            )
+                        } else {
+                            process_frame_data(
+                                &data,
+                                src_width,
+                                src_height,
+                                src_format,
+                                dst_width,
+                                dst_height,
+                                &mut self.camera_scaler,
+                            )
+                        };
+
+                        match processed {
                             Ok(mut processed_data) => {
                                 // Apply HDR tonemapping if needed
                                 self.tonemapper.tonemap(
@@ -762,12 +1995,25 @@ impl Pipeline {
             }
         };
 
+        // While paused, the camera fades to the placeholder instead
+        let gs_frame = gs_frame.map(|mut f| {
+            self.camera_pause.apply(&mut f.data, f.width, f.height);
+            f
+        });
+
+        // The frames above are BGRA; NV12 cameras get them converted last
+        let gs_frame = gs_frame.and_then(|f| camera_frame(f, self.config.camera_format));
+
         self.metrics.record_capture_timing(capture_start, Instant::now());
 
         // Send to camera
         let output_start = Instant::now();
         if let (Some(camera), Some(gs_frame)) = (&mut self.camera, gs_frame) {
-            if let Err(e) = camera.write_frame(&gs_frame).await {
+            let written = camera.write_frame(&gs_frame).await;
+            if self.camera_watcher.is_some() {
+                self.last_camera_frame = Some(gs_frame);
+            }
+            if let Err(e) = written {
                 let failed = self.frames_failed.fetch_add(1, Ordering::Relaxed) + 1;
                 error!(
                     "Failed to write frame to camera: {} (total failures: {})",
@@ -775,6 +2021,9 @@ impl Pipeline {
                 );
             } else {
                 self.metrics.record_output_timing(output_start, Instant::now());
+                if let Some(hash) = hash {
+                    trace!("frame-hash camera pts={} hash={:08x}", frame.pts, hash);
+                }
                 let count = self.frames_processed.fetch_add(1, Ordering::Relaxed) + 1;
                 if count % 300 == 0 {
                     // Log stats every ~5 seconds at 60fps
@@ -787,9 +2036,10 @@ impl Pipeline {
                     let failed = self.frames_failed.load(Ordering::Relaxed);
                     let stats = self.metrics.get_stats();
                     debug!(
-                        "Pipeline {}: {} frames ({:.1} fps), {} dropped, {} failed | Latency: cap={:.1}ms enc={:.1}ms out={:.1}ms",
+                        "Pipeline {}: {} frames ({:.1} fps), {} dropped, {} failed | Latency: cap={:.1}ms enc={:.1}ms out={:.1}ms | zero-copy={}",
                         self.handle, count, fps, dropped, failed,
-                        stats.capture_latency_ms, stats.encode_latency_ms, stats.output_latency_ms
+                        stats.capture_latency_ms, stats.encode_latency_ms, stats.output_latency_ms,
+                        stats.zero_copy_frames
                     );
                 }
             }
@@ -798,6 +2048,33 @@ impl Pipeline {
         Ok(())
     }
 
+    /// Drain the packets the video encoders still hold
+    ///
+    /// NVENC keeps frames back for lookahead and B-frames. They come out in
+    /// small batches, each one waiting for the outputs to catch up, so a deep
+    /// lookahead can't overrun the packet channel and lose the end of a
+    /// recording. [`stop`](Self::stop) does this before finalizing outputs;
+    /// the encoders take no more frames afterwards.
+    pub async fn flush(&mut self) -> Result<()> {
+        let encoders = self
+            .encoder
+            .iter_mut()
+            .chain(self.simulcast_encoders.iter_mut())
+            .chain(self.record_encoder.iter_mut())
+            .chain(self.stream_encoder.iter_mut());
+
+        let mut first_error = None;
+        for encoder in encoders {
+            match drain_encoder(encoder.as_mut()).await {
+                Ok(packets) => debug!("Drained {} delayed packets", packets),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
     /// Stop the pipeline
     pub async fn stop(&mut self) -> Result<()> {
         if self.state == PipelineState::Stopped {
@@ -807,13 +2084,19 @@ impl Pipeline {
         self.state = PipelineState::Stopping;
         info!("Stopping pipeline {}", self.handle);
 
+        // Averages stop meaning anything once capture stops
+        if let Some(handle) = self.stats_handle.take() {
+            handle.abort();
+        }
+
         // Drop frame receivers first
         self.frame_rx = None;
+        self.format_rx = None;
         self.audio_frame_rx = None;
 
         // Stop video capture
         if let Some(mut capture) = self.capture.take() {
-            capture.stop();
+            capture.stop().await;
         }
 
         // Stop audio capture
@@ -827,22 +2110,23 @@ impl Pipeline {
             info!("Virtual microphone stopped");
         }
 
-        // Flush video encoder and drop it (closes the broadcast channel)
-        if let Some(mut encoder) = self.encoder.take() {
-            info!("Flushing video encoder...");
-            if let Err(e) = encoder.flush() {
-                warn!("Video encoder flush failed: {}", e);
-            }
-            // Encoder dropped here, closing the broadcast channel
+        // Drain the video encoders while the outputs still read, then drop
+        // them (closes the broadcast channels)
+        info!("Flushing video encoder...");
+        if let Err(e) = self.flush().await {
+            warn!("Video encoder flush failed: {}", e);
         }
+        self.encoder = None;
+        self.simulcast_encoders.clear();
+        self.record_encoder = None;
+        self.stream_encoder = None;
 
-        // Flush audio encoder and drop it
-        if let Some(mut audio_encoder) = self.audio_encoder.take() {
-            info!("Flushing audio encoder...");
-            if let Err(e) = audio_encoder.flush() {
-                warn!("Audio encoder flush failed: {}", e);
-            }
-        }
+        // Flush audio encoders and drop them
+        self.audio_encoders.flush();
+
+        // Everything is flushed into the channels; the output tasks write what
+        // is buffered, then finalize even if a channel is still held open
+        self.shutdown.trigger();
 
         // Wait for recorder to finish
         if let Some(handle) = self.recorder_handle.take() {
@@ -854,6 +2138,15 @@ impl Pipeline {
             }
         }
 
+        if let Some(handle) = self.replay_handle.take() {
+            match handle.await {
+                Ok(Ok(packets)) => debug!("Replay buffer stopped after {} packets", packets),
+                Ok(Err(e)) => warn!("Replay buffer finished with error: {}", e),
+                Err(e) => warn!("Replay buffer task panicked: {}", e),
+            }
+        }
+        self.replay = None;
+
         // Wait for streaming to finish
         if let Some(handle) = self.streamer_handle.take() {
             info!("Waiting for streaming to complete...");
@@ -864,13 +2157,58 @@ impl Pipeline {
             }
         }
 
+        // Wait for HLS output to finish
+        if let Some(handle) = self.hls_handle.take() {
+            info!("Waiting for HLS output to complete...");
+            match handle.await {
+                Ok(Ok(packets)) => info!("HLS output complete: {} packets written", packets),
+                Ok(Err(e)) => warn!("HLS output finished with error: {}", e),
+                Err(e) => warn!("HLS task panicked: {}", e),
+            }
+        }
+
+        // Wait for DASH output to finish
+        if let Some(handle) = self.dash_handle.take() {
+            info!("Waiting for DASH output to complete...");
+            match handle.await {
+                Ok(Ok(packets)) => info!("DASH output complete: {} packets written", packets),
+                Ok(Err(e)) => warn!("DASH output finished with error: {}", e),
+                Err(e) => warn!("DASH task panicked: {}", e),
+            }
+        }
+
+        // Stop HLS HTTP server
+        if let Some(handle) = self.hls_server_handle.take() {
+            info!("Stopping HLS server...");
+            handle.abort();
+        }
+
+        // Wait for custom sinks to finish
+        for handle in self.sink_handles.drain(..) {
+            match handle.await {
+                Ok(Ok(packets)) => info!("Custom sink complete: {} packets written", packets),
+                Ok(Err(e)) => warn!("Custom sink finished with error: {}", e),
+                Err(e) => warn!("Custom sink task panicked: {}", e),
+            }
+        }
+
         // Stop WebRTC signaling server
         if let Some(handle) = self.webrtc_server_handle.take() {
             info!("Stopping WebRTC signaling server...");
             handle.abort(); // Signaling server runs forever, so abort it
         }
 
+        // Forwarding ends once the encoders above are dropped
+        if let Some(handle) = self.webrtc_handle.take() {
+            match handle.await {
+                Ok(Ok(packets)) => debug!("WebRTC forwarding complete: {} packets", packets),
+                Ok(Err(e)) => warn!("WebRTC forwarding finished with error: {}", e),
+                Err(e) => warn!("WebRTC forwarding task panicked: {}", e),
+            }
+        }
+
         // Stop WebRTC output
+        #[cfg(feature = "webrtc")]
         if let Some(webrtc) = self.webrtc_output.take() {
             let mut output = webrtc.write().await;
             if let Err(e) = output.stop().await {
@@ -879,15 +2217,19 @@ impl Pipeline {
         }
 
         // Stop camera (using RawOutputSink::finish)
+        self.camera_watcher = None;
+        self.last_camera_frame = None;
         if let Some(mut camera) = self.camera.take() {
             if let Err(e) = camera.finish().await {
                 warn!("Failed to cleanly stop virtual camera: {}", e);
             }
         }
 
-        // Stop portal session
-        if let Err(e) = self.portal.stop_session().await {
-            warn!("Failed to cleanly stop portal session: {}", e);
+        // Stop portal session (layout layers close their own)
+        if self.portal.is_active().await {
+            if let Err(e) = self.portal.stop_session().await {
+                warn!("Failed to cleanly stop portal session: {}", e);
+            }
         }
 
         self.state = PipelineState::Stopped;
@@ -930,10 +2272,10 @@ impl Pipeline {
 
     /// Process available audio frames
     fn process_audio_frames(&mut self) {
-        // Skip if no audio encoder
-        let Some(ref mut audio_encoder) = self.audio_encoder else {
+        // Skip if no output wants encoded audio
+        if self.audio_encoders.is_empty() {
             return;
-        };
+        }
 
         // Get audio receiver, re-subscribing if needed
         if self.audio_frame_rx.is_none() {
@@ -951,9 +2293,7 @@ impl Pipeline {
             loop {
                 match audio_rx.try_recv() {
                     Ok(audio_frame) => {
-                        if let Err(e) = audio_encoder.encode(&audio_frame) {
-                            trace!("Audio encoding failed: {}", e);
-                        } else {
+                        if self.audio_encoders.encode(&audio_frame) {
                             self.audio_samples_processed
                                 .fetch_add(audio_frame.sample_count as u64, Ordering::Relaxed);
                         }
@@ -991,6 +2331,17 @@ impl Pipeline {
         )
     }
 
+    /// When the session should stop, if `max_duration` is set and capture has started
+    pub fn deadline(&self) -> Option<Instant> {
+        Some(self.start_time? + self.config.max_duration?)
+    }
+
+    /// Time left before the `max_duration` auto-stop
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline()
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
     /// Get the number of frames processed
     pub fn frames_processed(&self) -> u64 {
         self.frames_processed.load(Ordering::Relaxed)
@@ -1011,17 +2362,294 @@ impl Pipeline {
         self.metrics.clone()
     }
 
+    /// GPU the stats logger samples, if GPU monitoring is enabled
+    pub fn monitored_gpu(&self) -> Option<u32> {
+        self.config.gpu_monitoring.then_some(self.config.gpu)
+    }
+
+    /// Short descriptions of the outputs being fed, for status displays
+    pub fn active_outputs(&self) -> Vec<String> {
+        let mut outputs = Vec::new();
+        if self.camera.is_some() {
+            outputs.push(format!("Camera: {}", self.config.camera_name));
+        }
+        if self.recorder_handle.is_some() {
+            if let Some(ref path) = self.config.record_path {
+                outputs.push(format!("Recording: {}", path.display()));
+            }
+        }
+        if let Some(secs) = self.config.replay_buffer_secs.filter(|_| self.replay.is_some()) {
+            outputs.push(format!("Replay buffer: {}s", secs));
+        }
+        if let Some(ref stats) = self.stream_stats {
+            outputs.push(format!("Stream: {}", stats.lock().protocol));
+        }
+        if self.webrtc_server_handle.is_some() {
+            outputs.push(format!("WebRTC: port {}", self.config.webrtc_port));
+        }
+        if self.hls_handle.is_some() {
+            outputs.push("HLS".to_string());
+        }
+        if self.dash_handle.is_some() {
+            outputs.push("DASH".to_string());
+        }
+        if !self.sink_handles.is_empty() {
+            outputs.push(format!("Custom sinks: {}", self.sink_handles.len()));
+        }
+        outputs
+    }
+
+    /// Force the next encoded frame to be a keyframe
+    ///
+    /// Returns an error if no encoder is active (camera-only sessions send
+    /// raw frames and have no GOP to reset).
+    pub fn request_keyframe(&self) -> Result<()> {
+        let mut encoders = self
+            .encoder
+            .iter()
+            .chain(&self.simulcast_encoders)
+            .chain(&self.record_encoder)
+            .chain(&self.stream_encoder)
+            .peekable();
+        if encoders.peek().is_none() {
+            return Err(NitrogenError::encoder("No active encoder"));
+        }
+        for encoder in encoders {
+            encoder.request_keyframe();
+        }
+        info!("Keyframe requested for pipeline {}", self.handle);
+        Ok(())
+    }
+
+    /// Change the video bitrate of the main encoder while running
+    ///
+    /// Does what `--bitrate` does at start: simulcast layers at the output
+    /// size follow, while recordings and streams at their own preset keep
+    /// their encoder's bitrate. The new rate starts on the next frame, which
+    /// is a keyframe. Returns the old and new bitrate in kbps.
+    pub fn set_bitrate(&mut self, kbps: u32) -> Result<(u32, u32)> {
+        if kbps == 0 {
+            return Err(NitrogenError::config("Bitrate must be above 0 kbps"));
+        }
+        let rate_control = self.config.effective_rate_control();
+        if !rate_control.uses_bitrate() {
+            return Err(NitrogenError::config(format!(
+                "{} rate control ignores the bitrate",
+                rate_control.describe()
+            )));
+        }
+        let encoder = self
+            .encoder
+            .as_mut()
+            .ok_or_else(|| NitrogenError::encoder("No active encoder"))?;
+        encoder.set_bitrate(kbps)?;
+
+        let old = self.config.effective_bitrate();
+        self.config.bitrate = kbps;
+        info!("Bitrate changed from {} to {} kbps", old, kbps);
+        Ok((old, kbps))
+    }
+
+    /// Log performance stats every `stats_interval_secs`, if set
+    fn start_stats_logger(&mut self) {
+        if self.config.stats_interval_secs > 0 {
+            let interval = Duration::from_secs(self.config.stats_interval_secs.into());
+            let mut logger = StatsLogger::new(self.metrics.clone(), interval);
+            if self.config.gpu_monitoring {
+                logger = logger.with_gpu(self.config.gpu);
+            }
+            let shutdown = self.shutdown.subscribe();
+            self.stats_handle = Some(tokio::spawn(logger.run(shutdown)));
+            info!("Logging performance stats every {}s", self.config.stats_interval_secs);
+        }
+    }
+
+    /// The configuration the pipeline is running with
+    pub fn config(&self) -> &CaptureConfig {
+        &self.config
+    }
+
+    /// Apply the live part of a config reload (see [`ReloadPlan`])
+    ///
+    /// Each change is applied on its own; the first one that fails is
+    /// returned after the rest have been tried.
+    pub fn apply_reload(&mut self, plan: &ReloadPlan) -> Result<()> {
+        let mut result = Ok(());
+        for change in &plan.live {
+            let applied = match change {
+                LiveChange::Overlay {
+                    enabled,
+                    position,
+                    template,
+                    title,
+                } => {
+                    let mut overlay = self.overlay.config().clone();
+                    overlay.enabled = *enabled;
+                    overlay.position = *position;
+                    overlay.template = template.clone();
+                    overlay.title = title.clone();
+                    self.overlay.set_config(overlay);
+                    self.config.overlay_enabled = *enabled;
+                    self.config.overlay_position = *position;
+                    self.config.overlay_template = template.clone();
+                    self.config.overlay_title = title.clone();
+                    info!("Latency overlay settings reloaded");
+                    Ok(())
+                }
+                LiveChange::SyncOffset(offset_ms) => self.set_sync_offset(*offset_ms),
+                LiveChange::StatsInterval(secs) => {
+                    if let Some(handle) = self.stats_handle.take() {
+                        handle.abort();
+                    }
+                    self.config.stats_interval_secs = *secs;
+                    if self.is_running() {
+                        self.start_stats_logger();
+                    }
+                    Ok(())
+                }
+            };
+            if let Err(e) = applied {
+                warn!("Failed to apply reloaded setting: {}", e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Pause the virtual camera behind its placeholder, or resume it
+    ///
+    /// Recording and streams keep the capture; only the camera fades over
+    /// `camera_transition_ms`.
+    pub fn set_camera_paused(&mut self, paused: bool) {
+        self.camera_pause.set_paused(paused);
+        info!("Virtual camera {}", if paused { "paused" } else { "resumed" });
+    }
+
+    /// Whether the virtual camera shows its placeholder
+    pub fn camera_paused(&self) -> bool {
+        self.camera_pause.is_paused()
+    }
+
     /// Toggle the latency overlay on/off
     pub fn toggle_overlay(&mut self) {
         self.overlay.toggle();
         info!("Latency overlay: {}", if self.overlay.is_enabled() { "enabled" } else { "disabled" });
     }
 
+    /// Change the overlay `{title}` text while running
+    pub fn set_overlay_title(&mut self, title: impl Into<String>) {
+        self.overlay.set_title(title);
+    }
+
     /// Check if overlay is enabled
     pub fn overlay_enabled(&self) -> bool {
         self.overlay.is_enabled()
     }
 
+    /// Current A/V sync offset in ms (positive delays audio)
+    pub fn sync_offset_ms(&self) -> i32 {
+        self.sync_offset.get_ms()
+    }
+
+    /// Change the A/V sync offset of the recording and stream while running
+    ///
+    /// Shrinking the offset mid-session moves the delayed stream's
+    /// timestamps back; they are held one tick apart until they catch up.
+    pub fn set_sync_offset(&mut self, offset_ms: i32) -> Result<()> {
+        if offset_ms.unsigned_abs() > MAX_AV_SYNC_OFFSET_MS as u32 {
+            return Err(NitrogenError::config(format!(
+                "A/V sync offset {} ms is out of range (at most {} ms either way)",
+                offset_ms, MAX_AV_SYNC_OFFSET_MS
+            )));
+        }
+        self.sync_offset.set_ms(offset_ms);
+        self.config.av_sync_offset_ms = offset_ms;
+        info!("A/V sync offset set to {} ms", offset_ms);
+        Ok(())
+    }
+
+    /// Latest RTMP/SRT stream statistics (None when not streaming)
+    ///
+    /// Refreshed by the streaming task every
+    /// [`STREAM_STATS_INTERVAL`](crate::output::STREAM_STATS_INTERVAL).
+    pub fn stream_stats(&self) -> Option<StreamStats> {
+        self.stream_stats.as_ref().map(|stats| stats.lock().clone())
+    }
+
+    /// PNG thumbnail of the most recent captured frame
+    ///
+    /// Rate-limited, see [`PreviewCache`]. Fails before the first frame.
+    pub fn preview(&mut self, max_width: u32) -> Result<PreviewImage> {
+        let frame = self.last_frame.as_deref().or(self.held_frame.as_deref());
+        self.preview_cache.get(frame, max_width)
+    }
+
+    /// Write the most recent captured frame to an image file
+    ///
+    /// `path` is a template like the recording path (`~/`, `{date}`, `{n}`...)
+    /// and its extension picks PNG or JPEG. The still is full resolution;
+    /// tonemapping, the watermark and the latency overlay are applied as
+    /// configured unless `raw` is set. Fails before the first frame.
+    pub fn snapshot(&self, path: &str, raw: bool) -> Result<SnapshotInfo> {
+        let frame = self
+            .last_frame
+            .as_deref()
+            .or(self.held_frame.as_deref())
+            .ok_or_else(|| NitrogenError::config("No frame has been captured yet"))?;
+        let vars = PathTemplateVars::now(&self.config.source, &self.config.preset);
+        let path = resolve_record_path(path, &vars, true)?;
+        let format = SnapshotFormat::from_path(&path)?;
+
+        let (width, height) = (frame.format.width, frame.format.height);
+        let mut bgra = snapshot::frame_bgra(frame)?;
+        if !raw {
+            self.tonemapper.tonemap(&mut bgra, width, height, frame.hdr_metadata.as_ref());
+            if let Some(watermark) = &self.watermark {
+                watermark.render(&mut bgra, width, height);
+            }
+            if self.overlay.is_enabled() {
+                let stats = self.metrics.get_stats();
+                self.overlay.render(&mut bgra, width, height, &stats);
+            }
+        }
+        snapshot::write_snapshot(&bgra, width, height, &path, format)?;
+
+        info!("Saved {}x{} snapshot to {}", width, height, path.display());
+        Ok(SnapshotInfo {
+            path,
+            width,
+            height,
+            format,
+            pts: frame.pts,
+        })
+    }
+
+    /// Write the replay buffer to a new file while capture carries on
+    ///
+    /// `path` is a template like the recording path (`~/`, `{date}`,
+    /// `{n}`...); an existing file is never replaced. The buffer is copied
+    /// and muxed on a blocking thread, so the pipeline doesn't need to stay
+    /// locked; await the handle for the result. The replay starts on the
+    /// oldest buffered keyframe, and saving fails if the replay buffer is
+    /// off or hasn't seen a keyframe yet.
+    pub fn save_replay(&self, path: &str) -> Result<JoinHandle<Result<ReplayInfo>>> {
+        let buffer = self
+            .replay
+            .as_ref()
+            .ok_or_else(|| {
+                NitrogenError::config("Replay buffer is off; start with --replay-buffer <SECS>")
+            })?
+            .lock()
+            .clone();
+        let vars = PathTemplateVars::now(&self.config.source, &self.config.preset);
+        let path = resolve_record_path(path, &vars, false)?;
+
+        // Muxing seconds of video is too slow for the async workers
+        Ok(tokio::task::spawn_blocking(move || buffer.save_replay(path)))
+    }
+
     /// Get pipeline statistics
     pub fn stats(&self) -> PipelineStats {
         let elapsed = self
@@ -1052,6 +2680,251 @@ impl Pipeline {
     }
 }
 
+/// Start a portal session for `source`
+///
+/// Prompts the user. A monitor given by connector name (e.g. DP-2) is
+/// matched against the selected streams.
+async fn start_source_session(
+    portal: &PortalCapture,
+    source: &CaptureSource,
+    cursor_mode: CursorMode,
+) -> Result<SessionInfo> {
+    match source {
+        CaptureSource::Monitor { id } if !is_picker_id(id) => {
+            portal.start_output_session(cursor_mode, id).await
+        }
+        CaptureSource::Monitor { .. } => {
+            portal
+                .start_session(CaptureType::Monitor, cursor_mode, false)
+                .await
+        }
+        CaptureSource::Window { .. } => {
+            portal
+                .start_session(CaptureType::Window, cursor_mode, false)
+                .await
+        }
+    }
+}
+
+/// Drain one encoder in batches its subscribers can keep up with
+///
+/// Waits for every batch to be received before releasing the next. If the
+/// outputs stop reading for [`FLUSH_STALL_TIMEOUT`], the rest is released
+/// without waiting. Returns the number of packets drained.
+async fn drain_encoder(encoder: &mut dyn Encoder) -> Result<usize> {
+    let mut drained = 0;
+    let mut stalled = false;
+    loop {
+        let released = encoder.drain(FLUSH_BATCH)?;
+        if released == 0 {
+            return Ok(drained);
+        }
+        drained += released;
+
+        let deadline = Instant::now() + FLUSH_STALL_TIMEOUT;
+        while !stalled && encoder.queued_packets() > 0 {
+            if Instant::now() >= deadline {
+                warn!(
+                    "Outputs stopped reading while flushing; {} packets may be lost",
+                    encoder.queued_packets()
+                );
+                stalled = true;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+    }
+}
+
+/// Next frame from a capture receiver, skipping lag
+///
+/// Never resolves once the receiver is gone or closed.
+async fn next_frame(rx: &mut Option<broadcast::Receiver<Arc<Frame>>>) -> Arc<Frame> {
+    if let Some(receiver) = rx.as_mut() {
+        loop {
+            match receiver.recv().await {
+                Ok(frame) => return frame,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+    *rx = None;
+    std::future::pending().await
+}
+
+/// Copy of `frame` that stays valid after its stream stops
+///
+/// Shared-memory frames are reused as is; DMA-BUF frames are mapped and
+/// copied. None if the buffer can't be mapped.
+fn held_frame(frame: &Arc<Frame>) -> Option<Arc<Frame>> {
+    match frame.data {
+        FrameData::Memory(_) => Some(frame.clone()),
+        FrameData::DmaBuf { .. } => {
+            let size = (frame.format.stride * frame.format.height) as usize;
+            match frame.data.try_map_dmabuf(size) {
+                Ok(data) => Some(Arc::new(Frame {
+                    format: frame.format,
+                    data: FrameData::Memory(data),
+                    pts: frame.pts,
+                    hdr_metadata: frame.hdr_metadata,
+                })),
+                Err(e) => {
+                    debug!("Could not copy the last frame to hold: {}", e);
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Refuse outputs this build was compiled without
+///
+/// Their settings still parse, so without this check a config asking for
+/// WebRTC or a stream would start with the output silently missing.
+fn check_output_features(config: &CaptureConfig) -> Result<()> {
+    if config.webrtc_enabled && !cfg!(feature = "webrtc") {
+        return Err(NitrogenError::Unsupported(
+            "WebRTC output: nitrogen was built without the webrtc feature".to_string(),
+        ));
+    }
+    if config.stream_url.is_some() && !cfg!(feature = "streaming") {
+        return Err(NitrogenError::Unsupported(
+            "RTMP/SRT/RIST streaming: nitrogen was built without the streaming feature".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Configured simulcast layers that fit the output, highest first
+///
+/// Layers taller than the output would be upscaled, so they're dropped.
+#[cfg(feature = "webrtc")]
+fn usable_simulcast_layers(config: &CaptureConfig) -> Vec<SimulcastLayer> {
+    let mut layers: Vec<SimulcastLayer> = config
+        .webrtc_simulcast
+        .iter()
+        .filter(|layer| {
+            let fits = layer.height <= config.height();
+            if !fits {
+                info!(
+                    "Skipping simulcast layer {} ({}p): taller than the {}p output",
+                    layer.rid,
+                    layer.height,
+                    config.height()
+                );
+            }
+            fits
+        })
+        .cloned()
+        .collect();
+    layers.sort_by(|a, b| b.height.cmp(&a.height));
+    layers.dedup_by_key(|layer| layer.height);
+    layers
+}
+
+/// Feed a frame to an encoder beside the main one, following the static
+/// content decision made for the main encoder
+fn encode_secondary(
+    encoder: &mut dyn Encoder,
+    frame: &Frame,
+    decision: FrameDecision,
+) -> Result<()> {
+    if decision == FrameDecision::Refresh {
+        encoder.request_keyframe();
+    }
+    if decision == FrameDecision::Skip {
+        encoder.skip_frame();
+        Ok(())
+    } else {
+        encoder.encode(frame)
+    }
+}
+
+/// Encoder configuration for one simulcast layer
+#[cfg(feature = "webrtc")]
+fn simulcast_layer_config(config: &CaptureConfig, layer: &SimulcastLayer) -> CaptureConfig {
+    let (width, height) = layer.scaled_size(config.width(), config.height());
+    let mut layer_config = config.clone();
+    layer_config.preset = Preset::Custom {
+        width,
+        height,
+        fps: config.fps(),
+    };
+    layer_config.bitrate = layer.bitrate;
+    // Zero-copy input only helps at the capture size
+    layer_config.zero_copy = false;
+    layer_config
+}
+
+/// Camera path scaler that follows the capture size
+///
+/// Created on the first frame that needs it: always for alpha, otherwise
+/// only when the fit mode has to letterbox or crop.
+struct CameraScaler {
+    scaler: Option<FrameScaler>,
+    fit: FitMode,
+    color: BarColor,
+}
+
+impl CameraScaler {
+    fn new(fit: FitMode, color: BarColor) -> Self {
+        Self {
+            scaler: None,
+            fit,
+            color,
+        }
+    }
+
+    /// Scale packed BGRA to the output size, keeping alpha
+    fn scale(
+        &mut self,
+        data: &[u8],
+        src_width: u32,
+        src_height: u32,
+        dst_width: u32,
+        dst_height: u32,
+    ) -> Result<Vec<u8>> {
+        // Follow the capture size; the output stays at the preset
+        match &mut self.scaler {
+            Some(scaler) if scaler.output_size() == (dst_width, dst_height) => {
+                scaler.set_input_size(src_width, src_height)?;
+            }
+            slot => {
+                let scaler = FrameScaler::bgra(src_width, src_height, dst_width, dst_height)?
+                    .with_fit(self.fit, self.color)?;
+                *slot = Some(scaler);
+            }
+        }
+
+        self.scaler
+            .as_mut()
+            .expect("scaler was just created")
+            .scale_packed(data)
+    }
+}
+
+/// Convert a BGRA camera frame to the format the camera was set up with
+///
+/// None if the frame is too small for its size, which would be a bug.
+fn camera_frame(frame: GsFrame, format: CameraFormat) -> Option<GsFrame> {
+    match format {
+        CameraFormat::Bgra => Some(frame),
+        CameraFormat::Nv12 => {
+            let Some(data) = bgra8_to_nv12(&frame.data, frame.width, frame.height, YuvMatrix::Bt709)
+            else {
+                error!("Camera frame is too small for {}x{}", frame.width, frame.height);
+                return None;
+            };
+            Some(GsFrame {
+                data,
+                stride: nv12_stride(frame.width),
+                format: GsFrameFormat::Nv12,
+                ..frame
+            })
+        }
+    }
+}
+
 /// Process frame data: convert colorspace and scale as needed
 fn process_frame_data(
     data: &[u8],
@@ -1060,6 +2933,7 @@ fn process_frame_data(
     src_format: GsFrameFormat,
     dst_width: u32,
     dst_height: u32,
+    scaler: &mut CameraScaler,
 ) -> Result<Vec<u8>> {
     // Step 1: Convert to BGRA if not already
     let bgra_data = if src_format != GsFrameFormat::Bgra {
@@ -1069,13 +2943,48 @@ fn process_frame_data(
         data.to_vec()
     };
 
-    // Step 2: Scale if dimensions differ
-    if src_width != dst_width || src_height != dst_height {
+    // Step 2: Scale if dimensions differ, framing the picture unless stretching
+    if src_width == dst_width && src_height == dst_height {
+        Ok(bgra_data)
+    } else if scaler.fit != FitMode::Stretch {
+        scaler.scale(&bgra_data, src_width, src_height, dst_width, dst_height)
+    } else {
         scale_frame(&bgra_data, src_width, src_height, dst_width, dst_height)
             .map_err(|e| NitrogenError::encoder(format!("Scaling failed: {}", e)))
+    }
+}
+
+/// Process frame data for a camera that keeps transparency
+///
+/// Like [`process_frame_data`], but always scales with the alpha-preserving
+/// [`FrameScaler`] and marks frames from padded formats (XRGB etc.) opaque,
+/// since their fourth byte is undefined.
+fn process_frame_data_alpha(
+    data: &[u8],
+    src_width: u32,
+    src_height: u32,
+    src_fourcc: u32,
+    dst_width: u32,
+    dst_height: u32,
+    scaler: &mut CameraScaler,
+) -> Result<Vec<u8>> {
+    let src_format = fourcc_to_gs_format(src_fourcc);
+    let mut bgra_data = if src_format != GsFrameFormat::Bgra {
+        convert_colorspace(data, src_format, GsFrameFormat::Bgra, src_width, src_height)
+            .map_err(|e| NitrogenError::encoder(format!("Colorspace conversion failed: {}", e)))?
     } else {
-        Ok(bgra_data)
+        data.to_vec()
+    };
+
+    if !has_alpha(src_fourcc) {
+        set_opaque_alpha(&mut bgra_data);
+    }
+
+    if src_width == dst_width && src_height == dst_height {
+        return Ok(bgra_data);
     }
+
+    scaler.scale(&bgra_data, src_width, src_height, dst_width, dst_height)
 }
 
 /// Pipeline statistics
@@ -1134,4 +3043,135 @@ mod tests {
         assert_eq!(PipelineState::Idle, PipelineState::Idle);
         assert_ne!(PipelineState::Idle, PipelineState::Running);
     }
+
+    #[test]
+    fn test_outputs_need_their_feature() {
+        let mut config = CaptureConfig::monitor("test");
+        assert!(check_output_features(&config).is_ok());
+
+        config.webrtc_enabled = true;
+        assert_eq!(check_output_features(&config).is_ok(), cfg!(feature = "webrtc"));
+
+        config.webrtc_enabled = false;
+        config.stream_url = Some("srt://localhost:9999".to_string());
+        assert_eq!(check_output_features(&config).is_ok(), cfg!(feature = "streaming"));
+    }
+
+    #[tokio::test]
+    async fn test_next_frame_skips_lag() {
+        let (tx, rx) = broadcast::channel(2);
+        let frame = |pts| {
+            Arc::new(Frame {
+                format: crate::types::FrameFormat {
+                    width: 1,
+                    height: 1,
+                    fourcc: 0x34325258,
+                    stride: 4,
+                },
+                data: FrameData::Memory(vec![0; 4]),
+                pts,
+                hdr_metadata: None,
+            })
+        };
+        for pts in 0..4 {
+            tx.send(frame(pts)).unwrap();
+        }
+
+        let mut rx = Some(rx);
+        assert_eq!(next_frame(&mut rx).await.pts, 2);
+        assert_eq!(next_frame(&mut rx).await.pts, 3);
+
+        // Shared-memory frames can be held without copying
+        let last = frame(3);
+        assert!(Arc::ptr_eq(&held_frame(&last).unwrap(), &last));
+
+        drop(tx);
+        let pending = tokio::time::timeout(Duration::from_millis(10), next_frame(&mut rx)).await;
+        assert!(pending.is_err());
+        assert!(rx.is_none());
+    }
+
+    #[test]
+    fn test_camera_frame_nv12() {
+        let (width, height) = (4, 2);
+        let red = [0u8, 0, 255, 255].repeat(width * height);
+        let bgra = || GsFrame {
+            data: red.clone(),
+            width: width as u32,
+            height: height as u32,
+            stride: width as u32 * 4,
+            format: GsFrameFormat::Bgra,
+            pts: 42,
+            duration: 0,
+            is_keyframe: true,
+            dmabuf_fd: None,
+        };
+        assert_eq!(camera_frame(bgra(), CameraFormat::Bgra).unwrap().data, red);
+
+        let nv12 = camera_frame(bgra(), CameraFormat::Nv12).unwrap();
+        assert!(matches!(nv12.format, GsFrameFormat::Nv12));
+        assert_eq!((nv12.width, nv12.height, nv12.stride, nv12.pts), (4, 2, 4, 42));
+        // Full-size Y plane, then one row of interleaved UV at half resolution
+        assert_eq!(nv12.data.len(), width * height + width);
+        assert!(nv12.data[..width * height].iter().all(|&y| y == 63));
+        assert_eq!(&nv12.data[width * height..], &[102, 240, 102, 240]);
+    }
+
+    /// Sink that logs what it was given, in order
+    #[cfg(feature = "mock-encoder")]
+    struct EventSink(Arc<parking_lot::Mutex<Vec<String>>>);
+
+    #[cfg(feature = "mock-encoder")]
+    #[async_trait::async_trait]
+    impl FrameSink for EventSink {
+        fn name(&self) -> &str {
+            "events"
+        }
+
+        async fn write_video(&mut self, packet: &crate::encode::EncodedPacket) -> Result<()> {
+            self.0.lock().push(format!("video {}", packet.pts));
+            Ok(())
+        }
+
+        async fn stop(&mut self) -> Result<()> {
+            self.0.lock().push("stop".to_string());
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "mock-encoder")]
+    #[tokio::test]
+    async fn test_flush_drains_delayed_packets() {
+        // Lookahead deeper than the 64-packet channel: one burst would lag
+        let mut encoder = crate::encode::MockEncoder::new(&CaptureConfig::monitor("test"))
+            .with_delay(100);
+        let events = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let shutdown = ShutdownCoordinator::new();
+        let sink = Box::new(EventSink(events.clone()));
+        let output = tokio::spawn(run_sink(sink, encoder.subscribe(), None, shutdown.subscribe()));
+
+        let frame = Frame {
+            format: crate::types::FrameFormat {
+                width: 1,
+                height: 1,
+                fourcc: 0x34325258,
+                stride: 4,
+            },
+            data: FrameData::Memory(vec![0; 4]),
+            pts: 0,
+            hdr_metadata: None,
+        };
+        for _ in 0..120 {
+            encoder.encode(&frame).unwrap();
+        }
+
+        assert_eq!(drain_encoder(&mut encoder).await.unwrap(), 100);
+        drop(encoder);
+        assert_eq!(output.await.unwrap().unwrap(), 120);
+
+        // Every packet reached the output, and the trailer came after the last
+        let mut expected: Vec<String> = (0..120).map(|pts| format!("video {}", pts)).collect();
+        expected.push("stop".to_string());
+        assert_eq!(*events.lock(), expected);
+    }
 }
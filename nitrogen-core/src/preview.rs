@@ -0,0 +1,188 @@
+//! Thumbnails of the capture for control UIs
+//!
+//! The daemon keeps the most recent captured frame; on request it is scaled
+//! down with the frame scaler and PNG-encoded, so a GUI can confirm what is
+//! being captured without subscribing to the frame stream. Previews are
+//! small (at most [`MAX_PREVIEW_WIDTH`] wide) and rendered at most once per
+//! [`MIN_PREVIEW_INTERVAL`]; requests in between get the last one again.
+
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ffmpeg_next::format::Pixel;
+use serde::{Deserialize, Serialize};
+
+use crate::encode::FrameScaler;
+use crate::error::{NitrogenError, Result};
+use crate::types::Frame;
+
+/// Widest preview the daemon renders
+pub const MAX_PREVIEW_WIDTH: u32 = 640;
+
+/// Narrowest preview the daemon renders
+pub const MIN_PREVIEW_WIDTH: u32 = 16;
+
+/// Shortest time between two rendered previews
+pub const MIN_PREVIEW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A PNG still of the capture
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreviewImage {
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// Size of the captured frame it was scaled from
+    pub source_size: (u32, u32),
+    /// Capture timestamp of the frame in nanoseconds
+    pub pts: u64,
+    /// Base64-encoded PNG
+    pub png: String,
+}
+
+impl PreviewImage {
+    /// Decode the PNG bytes
+    pub fn png_bytes(&self) -> Result<Vec<u8>> {
+        BASE64
+            .decode(&self.png)
+            .map_err(|e| NitrogenError::config(format!("Invalid preview data: {}", e)))
+    }
+}
+
+/// Preview size fitting `max_width` while keeping the aspect ratio
+///
+/// Never upscales; `max_width` is clamped to
+/// [`MIN_PREVIEW_WIDTH`]..=[`MAX_PREVIEW_WIDTH`].
+pub fn preview_size(width: u32, height: u32, max_width: u32) -> (u32, u32) {
+    let target = max_width.clamp(MIN_PREVIEW_WIDTH, MAX_PREVIEW_WIDTH).min(width);
+    if width == 0 {
+        return (0, 0);
+    }
+    let scaled = (height as u64 * target as u64 + width as u64 / 2) / width as u64;
+    (target, (scaled as u32).max(1))
+}
+
+/// Scale a captured frame down and encode it as PNG
+pub fn render_preview(frame: &Frame, max_width: u32) -> Result<PreviewImage> {
+    let (src_w, src_h) = (frame.format.width, frame.format.height);
+    let bgra = frame.to_bgra8().ok_or_else(|| {
+        NitrogenError::Unsupported("Preview is not available for GPU (DMA-BUF) frames".into())
+    })?;
+
+    let (width, height) = preview_size(src_w, src_h, max_width);
+    if width == 0 {
+        return Err(NitrogenError::config("Captured frame is empty"));
+    }
+    let mut scaler = FrameScaler::new(src_w, src_h, Pixel::BGRA, width, height, Pixel::RGB24)?;
+    let rgb = scaler.scale_strided(&bgra, src_w as usize * 4)?;
+
+    Ok(PreviewImage {
+        width,
+        height,
+        source_size: (src_w, src_h),
+        pts: frame.pts,
+        png: BASE64.encode(encode_png(&rgb, width, height)?),
+    })
+}
+
+/// Encode packed RGB24 pixels as PNG
+fn encode_png(rgb: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    let png_error = |e: png::EncodingError| {
+        NitrogenError::encoder(format!("Failed to encode preview: {}", e))
+    };
+
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    // Speed over size, it's a thumbnail
+    encoder.set_compression(png::Compression::Fast);
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    writer.write_image_data(rgb).map_err(png_error)?;
+    writer.finish().map_err(png_error)?;
+    Ok(out)
+}
+
+/// The last rendered preview, reused for requests that come too soon
+#[derive(Debug, Default)]
+pub struct PreviewCache {
+    last: Option<(Instant, PreviewImage)>,
+}
+
+impl PreviewCache {
+    /// Preview of `frame`, or the previous one if it's younger than
+    /// [`MIN_PREVIEW_INTERVAL`]
+    ///
+    /// Fails when no frame has been captured yet.
+    pub fn get(&mut self, frame: Option<&Frame>, max_width: u32) -> Result<PreviewImage> {
+        if let Some((rendered, image)) = &self.last {
+            if rendered.elapsed() < MIN_PREVIEW_INTERVAL {
+                return Ok(image.clone());
+            }
+        }
+
+        let frame =
+            frame.ok_or_else(|| NitrogenError::config("No frame has been captured yet"))?;
+        let image = render_preview(frame, max_width)?;
+        self.last = Some((Instant::now(), image.clone()));
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{FrameData, FrameFormat};
+
+    fn solid_frame(width: u32, height: u32, bgra: [u8; 4]) -> Frame {
+        Frame {
+            format: FrameFormat {
+                width,
+                height,
+                fourcc: crate::formats::fourcc::ARGB8888,
+                stride: width * 4,
+            },
+            data: FrameData::Memory(bgra.repeat((width * height) as usize)),
+            pts: 42,
+            hdr_metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_preview_size() {
+        assert_eq!(preview_size(1920, 1080, 320), (320, 180));
+        // Clamped to the maximum, and never upscaled
+        assert_eq!(preview_size(3840, 2160, 4000), (640, 360));
+        assert_eq!(preview_size(200, 100, 320), (200, 100));
+        assert_eq!(preview_size(1920, 1080, 0), (16, 9));
+    }
+
+    #[test]
+    fn test_render_preview_png() {
+        let frame = solid_frame(64, 32, [255, 0, 0, 255]);
+        let image = render_preview(&frame, 32).expect("Should render");
+        assert_eq!((image.width, image.height), (32, 16));
+        assert_eq!(image.source_size, (64, 32));
+        assert_eq!(image.pts, 42);
+
+        let png = image.png_bytes().unwrap();
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (32, 16));
+        // BGRA blue comes out as RGB blue
+        assert_eq!(&buf[..3], &[0, 0, 255]);
+    }
+
+    #[test]
+    fn test_cache_without_frame() {
+        let mut cache = PreviewCache::default();
+        assert!(cache.get(None, 320).is_err());
+
+        let frame = solid_frame(64, 32, [0, 0, 0, 255]);
+        let first = cache.get(Some(&frame), 320).unwrap();
+        // Within the interval, even without a frame, the last preview is reused
+        assert_eq!(cache.get(None, 320).unwrap(), first);
+    }
+}
@@ -0,0 +1,119 @@
+//! Shutdown coordination for pipeline tasks
+//!
+//! Output tasks normally end when the encoder channels close. That isn't
+//! enough on shutdown: a task parked on a channel that never closes would
+//! keep the recording from being finalized. The pipeline triggers a
+//! [`ShutdownCoordinator`] once the encoders are flushed, and every output
+//! task awaits its [`ShutdownSignal`], drains whatever is still buffered and
+//! finalizes its output.
+
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+/// Broadcasts a one-shot shutdown to all pipeline tasks
+#[derive(Debug, Clone)]
+pub struct ShutdownCoordinator {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl Default for ShutdownCoordinator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownCoordinator {
+    /// Create a coordinator that hasn't been triggered yet
+    pub fn new() -> Self {
+        let (tx, _) = watch::channel(false);
+        Self { tx: Arc::new(tx) }
+    }
+
+    /// Get a signal for a task to await
+    pub fn subscribe(&self) -> ShutdownSignal {
+        ShutdownSignal {
+            rx: self.tx.subscribe(),
+        }
+    }
+
+    /// Tell every task to finish up
+    ///
+    /// Returns `true` only for the call that actually started the shutdown,
+    /// so callers racing each other (signal handler, IPC, `--duration`) run
+    /// their cleanup once.
+    pub fn trigger(&self) -> bool {
+        self.tx.send_if_modified(|triggered| !std::mem::replace(triggered, true))
+    }
+
+    /// Whether shutdown has been triggered
+    pub fn is_triggered(&self) -> bool {
+        *self.tx.borrow()
+    }
+}
+
+/// Resolves once the coordinator is triggered
+#[derive(Debug, Clone)]
+pub struct ShutdownSignal {
+    rx: watch::Receiver<bool>,
+}
+
+impl ShutdownSignal {
+    /// Wait until shutdown is triggered
+    ///
+    /// Returns immediately if it already was. Dropping the coordinator also
+    /// counts as a shutdown, so tasks never wait on a pipeline that is gone.
+    pub async fn wait(&mut self) {
+        let _ = self.rx.wait_for(|triggered| *triggered).await;
+    }
+
+    /// Whether shutdown has been triggered
+    pub fn is_triggered(&self) -> bool {
+        *self.rx.borrow()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn test_finalize_runs_once_on_shutdown() {
+        let coordinator = ShutdownCoordinator::new();
+        let finalized = Arc::new(AtomicU32::new(0));
+
+        let mut signal = coordinator.subscribe();
+        let counter = finalized.clone();
+        let task = tokio::spawn(async move {
+            signal.wait().await;
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        // Signal handler and IPC stop racing each other
+        assert!(coordinator.trigger());
+        assert!(!coordinator.clone().trigger());
+        task.await.unwrap();
+
+        assert!(coordinator.is_triggered());
+        assert_eq!(finalized.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_late_subscriber_sees_shutdown() {
+        let coordinator = ShutdownCoordinator::new();
+        coordinator.trigger();
+
+        let mut signal = coordinator.subscribe();
+        assert!(signal.is_triggered());
+        signal.wait().await;
+    }
+
+    #[tokio::test]
+    async fn test_dropped_coordinator_releases_waiters() {
+        let coordinator = ShutdownCoordinator::new();
+        let mut signal = coordinator.subscribe();
+        drop(coordinator);
+        signal.wait().await;
+    }
+}
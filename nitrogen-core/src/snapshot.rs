@@ -0,0 +1,292 @@
+//! Full-resolution stills of the capture
+//!
+//! Unlike [`preview`](crate::preview) thumbnails, snapshots are written to a
+//! file at the captured resolution. The format follows the file extension:
+//! PNG is lossless, JPEG goes through FFmpeg's MJPEG encoder. By default the
+//! pipeline applies HDR tonemapping, the watermark and the latency overlay
+//! first, so the still looks like the outputs; a raw snapshot skips them.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use ffmpeg_next as ffmpeg;
+use ffmpeg_next::codec::{self, encoder};
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::software::scaling::{self, Flags};
+use ffmpeg_next::util::frame::video::Video;
+use ffmpeg_next::Rational;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{NitrogenError, Result};
+use crate::types::{Frame, FrameData};
+
+/// Where snapshots go when no path is given (a recording path template)
+pub const DEFAULT_SNAPSHOT_PATH: &str = "~/Pictures/nitrogen-{date}_{time}-{n}.png";
+
+/// MJPEG quantizer scale for JPEG snapshots (2 = best, 31 = worst)
+const JPEG_QSCALE: i32 = 2;
+
+/// Image format of a snapshot file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SnapshotFormat {
+    /// Lossless PNG
+    Png,
+    /// Baseline JPEG
+    Jpeg,
+}
+
+impl SnapshotFormat {
+    /// Format for a file name, from its extension
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("png") => Ok(Self::Png),
+            Some("jpg" | "jpeg") => Ok(Self::Jpeg),
+            _ => Err(NitrogenError::config(format!(
+                "Unsupported snapshot file {}: use a .png, .jpg or .jpeg name",
+                path.display()
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for SnapshotFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Png => write!(f, "png"),
+            Self::Jpeg => write!(f, "jpeg"),
+        }
+    }
+}
+
+/// A snapshot the daemon wrote
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    /// File the image was written to
+    pub path: PathBuf,
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// Image format
+    pub format: SnapshotFormat,
+    /// Capture timestamp of the frame in nanoseconds
+    pub pts: u64,
+}
+
+/// Pixels of a captured frame as tightly packed BGRA
+///
+/// DMA-BUF frames are mapped into system memory first.
+pub fn frame_bgra(frame: &Frame) -> Result<Vec<u8>> {
+    let unsupported = || {
+        NitrogenError::Unsupported(format!(
+            "Snapshots of {:#x} frames are not supported",
+            frame.format.fourcc
+        ))
+    };
+
+    match frame.data {
+        FrameData::Memory(_) => frame.to_bgra8().map(Cow::into_owned).ok_or_else(unsupported),
+        FrameData::DmaBuf { .. } => {
+            let size = (frame.format.stride * frame.format.height) as usize;
+            let data = frame.data.try_map_dmabuf(size).map_err(|e| {
+                NitrogenError::Unsupported(format!("Failed to map DMA-BUF frame: {}", e))
+            })?;
+            let mapped = Frame {
+                format: frame.format,
+                data: FrameData::Memory(data),
+                pts: frame.pts,
+                hdr_metadata: frame.hdr_metadata,
+            };
+            mapped.to_bgra8().map(Cow::into_owned).ok_or_else(unsupported)
+        }
+    }
+}
+
+/// Encode BGRA pixels in `format` and write them to `path`
+pub fn write_snapshot(
+    bgra: &[u8],
+    width: u32,
+    height: u32,
+    path: &Path,
+    format: SnapshotFormat,
+) -> Result<()> {
+    if width == 0 || height == 0 {
+        return Err(NitrogenError::config("Captured frame is empty"));
+    }
+    if bgra.len() < width as usize * height as usize * 4 {
+        return Err(NitrogenError::encoder(format!(
+            "Buffer of {} bytes does not match {}x{} BGRA",
+            bgra.len(),
+            width,
+            height
+        )));
+    }
+
+    let image = match format {
+        SnapshotFormat::Png => encode_png(&bgra_to_rgb(bgra, width, height), width, height)?,
+        SnapshotFormat::Jpeg => encode_jpeg(bgra, width, height)?,
+    };
+    std::fs::write(path, image).map_err(|e| {
+        NitrogenError::config(format!("Failed to write snapshot {}: {}", path.display(), e))
+    })
+}
+
+/// Drop the alpha channel and swap BGR to RGB
+fn bgra_to_rgb(bgra: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let pixels = width as usize * height as usize;
+    bgra.chunks_exact(4)
+        .take(pixels)
+        .flat_map(|px| [px[2], px[1], px[0]])
+        .collect()
+}
+
+/// Encode packed RGB24 pixels as PNG
+fn encode_png(rgb: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    let png_error = |e: png::EncodingError| {
+        NitrogenError::encoder(format!("Failed to encode snapshot: {}", e))
+    };
+
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    writer.write_image_data(rgb).map_err(png_error)?;
+    writer.finish().map_err(png_error)?;
+    Ok(out)
+}
+
+/// Encode packed BGRA pixels as JPEG with FFmpeg's MJPEG encoder
+fn encode_jpeg(bgra: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    let jpeg_error = |e: ffmpeg::Error| {
+        NitrogenError::encoder(format!("Failed to encode snapshot: {}", e))
+    };
+
+    let codec = encoder::find(codec::Id::MJPEG).ok_or_else(|| {
+        NitrogenError::Unsupported("FFmpeg was built without the MJPEG encoder".into())
+    })?;
+    let mut context = codec::context::Context::new_with_codec(codec)
+        .encoder()
+        .video()
+        .map_err(jpeg_error)?;
+    context.set_width(width);
+    context.set_height(height);
+    // Full-range 4:2:0, what JPEG decoders expect
+    context.set_format(Pixel::YUVJ420P);
+    context.set_time_base(Rational::new(1, 1));
+    context.set_flags(codec::Flags::QSCALE);
+    context.set_global_quality(JPEG_QSCALE * ffmpeg::ffi::FF_QP2LAMBDA as i32);
+    let mut encoder = context.open().map_err(jpeg_error)?;
+
+    let mut src = Video::new(Pixel::BGRA, width, height);
+    let row = width as usize * 4;
+    let stride = src.stride(0);
+    let plane = src.data_mut(0);
+    for (y, line) in bgra.chunks_exact(row).take(height as usize).enumerate() {
+        plane[y * stride..y * stride + row].copy_from_slice(line);
+    }
+
+    let mut yuv = Video::new(Pixel::YUVJ420P, width, height);
+    scaling::Context::get(
+        Pixel::BGRA,
+        width,
+        height,
+        Pixel::YUVJ420P,
+        width,
+        height,
+        Flags::BILINEAR,
+    )
+    .and_then(|mut scaler| scaler.run(&src, &mut yuv))
+    .map_err(jpeg_error)?;
+    yuv.set_pts(Some(0));
+
+    encoder.send_frame(&yuv).map_err(jpeg_error)?;
+    encoder.send_eof().map_err(jpeg_error)?;
+    let mut packet = ffmpeg::Packet::empty();
+    let mut out = Vec::new();
+    while encoder.receive_packet(&mut packet).is_ok() {
+        out.extend_from_slice(packet.data().unwrap_or_default());
+    }
+    if out.is_empty() {
+        return Err(NitrogenError::encoder("MJPEG encoder produced no image"));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FrameFormat;
+
+    /// Frame whose left half is red and right half blue, with row padding
+    fn mock_frame(width: u32, height: u32) -> Frame {
+        let stride = width * 4 + 16;
+        let mut data = vec![0; (stride * height) as usize];
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let bgra = if x < width as usize / 2 {
+                    [0, 0, 255, 255]
+                } else {
+                    [255, 0, 0, 255]
+                };
+                let start = y * stride as usize + x * 4;
+                data[start..start + 4].copy_from_slice(&bgra);
+            }
+        }
+        Frame {
+            format: FrameFormat {
+                width,
+                height,
+                fourcc: crate::formats::fourcc::ARGB8888,
+                stride,
+            },
+            data: FrameData::Memory(data),
+            pts: 7,
+            hdr_metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_png_dimensions() {
+        let frame = mock_frame(96, 54);
+        let bgra = frame_bgra(&frame).unwrap();
+        assert_eq!(bgra.len(), 96 * 54 * 4);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("still.png");
+        let format = SnapshotFormat::from_path(&path).unwrap();
+        write_snapshot(&bgra, 96, 54, &path, format).unwrap();
+
+        let file = std::fs::File::open(&path).unwrap();
+        let mut reader = png::Decoder::new(file).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (96, 54));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        // Full resolution: both halves survive unscaled
+        assert_eq!(&buf[..3], &[255, 0, 0]);
+        assert_eq!(&buf[95 * 3..96 * 3], &[0, 0, 255]);
+    }
+
+    #[test]
+    fn test_snapshot_format_from_path() {
+        assert_eq!(SnapshotFormat::from_path(Path::new("a.PNG")).unwrap(), SnapshotFormat::Png);
+        assert_eq!(SnapshotFormat::from_path(Path::new("a.jpg")).unwrap(), SnapshotFormat::Jpeg);
+        assert_eq!(SnapshotFormat::from_path(Path::new("a.jpeg")).unwrap(), SnapshotFormat::Jpeg);
+        assert!(SnapshotFormat::from_path(Path::new("a.webp")).is_err());
+        assert!(SnapshotFormat::from_path(Path::new("snapshot")).is_err());
+
+        // Short buffers and empty frames are refused before anything is written
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.png");
+        let bgra = frame_bgra(&mock_frame(8, 8)).unwrap();
+        assert!(write_snapshot(&bgra[..16], 8, 8, &path, SnapshotFormat::Png).is_err());
+        assert!(write_snapshot(&bgra, 0, 8, &path, SnapshotFormat::Png).is_err());
+        assert!(!path.exists());
+    }
+}
@@ -4,7 +4,11 @@
 //! the capture and streaming pipeline.
 
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::formats::YuvMatrix;
 
 /// HDR transfer function (EOTF)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
@@ -67,7 +71,7 @@ impl std::fmt::Display for ColorPrimaries {
 }
 
 /// HDR metadata for a video frame
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct HdrMetadata {
     /// Transfer function (EOTF)
     pub transfer: TransferFunction,
@@ -136,6 +140,43 @@ impl HdrMetadata {
     }
 }
 
+impl std::str::FromStr for HdrMetadata {
+    type Err = String;
+
+    /// Parse `pq`, `hlg` or `sdr`, optionally with the mastering peak in
+    /// nits (`pq:1000`)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (transfer, peak) = match s.split_once(':') {
+            Some((transfer, peak)) => (transfer, Some(peak)),
+            None => (s, None),
+        };
+        let metadata = match transfer.trim().to_lowercase().as_str() {
+            "pq" | "hdr10" => Self::hdr10(),
+            "hlg" => Self::hlg(),
+            "sdr" => Self::sdr(),
+            other => return Err(format!("Unknown HDR transfer: {}", other)),
+        };
+
+        let Some(peak) = peak else {
+            return Ok(metadata);
+        };
+        if !metadata.is_hdr() {
+            return Err("SDR input takes no peak luminance".to_string());
+        }
+        let nits: u32 = peak
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid peak luminance: {}", peak))?;
+        if !(100..=10_000).contains(&nits) {
+            return Err(format!("Peak luminance {} nits is out of range (100-10000)", nits));
+        }
+        Ok(Self {
+            mastering_max_luminance: Some(nits),
+            ..metadata
+        })
+    }
+}
+
 /// Global handle counter for unique session IDs
 static HANDLE_COUNTER: AtomicU64 = AtomicU64::new(1);
 
@@ -230,6 +271,67 @@ impl CaptureSource {
             Self::Monitor { id } | Self::Window { id } => id,
         }
     }
+
+    /// Whether `other` is the same source, comparing stable identifiers only
+    ///
+    /// Monitors compare by connector name, ignoring case and a `monitor:`
+    /// prefix (`DP-2` is `monitor:dp-2`); windows compare their compositor ID
+    /// exactly. Picker requests (`portal`, `portal:screen`) only match other
+    /// picker requests. Portal fallback IDs (`node:N`) never match anything:
+    /// PipeWire hands out node IDs per session, so they can't confirm that
+    /// two sources are the same.
+    pub fn is_same(&self, other: &CaptureSource) -> bool {
+        match (self, other) {
+            (Self::Monitor { id: a }, Self::Monitor { id: b }) => {
+                same_source_id(a, b, SourceKind::Monitor)
+            }
+            (Self::Window { id: a }, Self::Window { id: b }) => {
+                same_source_id(a, b, SourceKind::Window)
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a granted source (e.g. a portal stream) is this source
+    ///
+    /// Same rules as [`is_same`](Self::is_same); size, name and refresh
+    /// rate are ignored.
+    pub fn matches(&self, info: &SourceInfo) -> bool {
+        self.kind() == info.kind && same_source_id(self.id(), &info.id, info.kind)
+    }
+}
+
+/// Compare two source IDs of the same kind by their stable part
+fn same_source_id(a: &str, b: &str, kind: SourceKind) -> bool {
+    let normalize = |id: &str| match kind {
+        SourceKind::Monitor => id.strip_prefix("monitor:").unwrap_or(id).to_ascii_lowercase(),
+        _ => id.to_string(),
+    };
+    let (a, b) = (normalize(a), normalize(b));
+    let picker = |id: &str| id.is_empty() || id == "portal" || id.starts_with("portal:");
+
+    if picker(&a) || picker(&b) {
+        return picker(&a) && picker(&b);
+    }
+    !a.starts_with("node:") && a == b
+}
+
+impl std::str::FromStr for CaptureSource {
+    type Err = String;
+
+    /// Parse `monitor[:ID]` or `window[:ID]`; without an ID the portal picker is shown
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, id) = s.split_once(':').unwrap_or((s, ""));
+        let id = if id.is_empty() { "portal" } else { id };
+        match kind.to_lowercase().as_str() {
+            "monitor" => Ok(Self::monitor(id)),
+            "window" => Ok(Self::window(id)),
+            _ => Err(format!(
+                "Unknown source '{}' (expected monitor[:ID] or window[:ID])",
+                s
+            )),
+        }
+    }
 }
 
 impl std::fmt::Display for CaptureSource {
@@ -242,7 +344,7 @@ impl std::fmt::Display for CaptureSource {
 }
 
 /// Information about an available capture source
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SourceInfo {
     /// Unique identifier for this source
     pub id: String,
@@ -287,6 +389,15 @@ impl SourceInfo {
         self.hw_accelerated = hw;
         self
     }
+
+    /// Whether `other` describes the same source
+    ///
+    /// Compares kind and ID like [`CaptureSource::is_same`]. Name, size,
+    /// refresh rate and hardware support can change between sessions (a
+    /// mode switch, a renamed window) and are ignored.
+    pub fn is_same(&self, other: &SourceInfo) -> bool {
+        self.kind == other.kind && same_source_id(&self.id, &other.id, self.kind)
+    }
 }
 
 impl std::fmt::Display for SourceInfo {
@@ -329,6 +440,25 @@ pub struct Frame {
     pub hdr_metadata: Option<HdrMetadata>,
 }
 
+impl Frame {
+    /// Get the pixels as tightly packed BGRA8
+    ///
+    /// Borrows the frame data when it is already BGRA8 without row padding,
+    /// otherwise converts it (see [`crate::formats::to_bgra8`]). YUV frames
+    /// use the BT.2020 matrix when the HDR metadata says BT.2020 primaries,
+    /// BT.709 otherwise.
+    ///
+    /// Returns None for DMA-BUF frames and unsupported formats.
+    pub fn to_bgra8(&self) -> Option<Cow<'_, [u8]>> {
+        let data = self.data.as_memory()?;
+        let matrix = match self.hdr_metadata {
+            Some(metadata) if metadata.primaries == ColorPrimaries::Bt2020 => YuvMatrix::Bt2020,
+            _ => YuvMatrix::Bt709,
+        };
+        crate::formats::to_bgra8(data, &self.format, matrix)
+    }
+}
+
 /// Frame data storage
 #[derive(Debug)]
 pub enum FrameData {
@@ -416,8 +546,40 @@ impl AudioFrame {
 
     /// Duration of this frame in nanoseconds
     pub fn duration_ns(&self) -> u64 {
+        if self.format.sample_rate == 0 {
+            return 0;
+        }
         (self.sample_count as u64 * 1_000_000_000) / self.format.sample_rate as u64
     }
+
+    /// Duration of this frame
+    pub fn duration(&self) -> Duration {
+        Duration::from_nanos(self.duration_ns())
+    }
+
+    /// Timestamp just past the last sample, in nanoseconds
+    pub fn end_pts(&self) -> u64 {
+        self.pts + self.duration_ns()
+    }
+
+    /// RMS amplitude over all channels
+    pub fn rms(&self) -> f32 {
+        rms_amplitude(&self.samples)
+    }
+
+    /// Check whether the RMS amplitude is at or below `threshold`
+    pub fn is_silent(&self, threshold: f32) -> bool {
+        self.rms() <= threshold
+    }
+}
+
+/// RMS amplitude of interleaved samples (0.0 for an empty buffer)
+pub fn rms_amplitude(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let sum: f32 = samples.iter().map(|s| s * s).sum();
+    (sum / samples.len() as f32).sqrt()
 }
 
 impl FrameData {
@@ -496,3 +658,31 @@ impl FrameData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_frame_duration() {
+        let frame = AudioFrame::new(AudioFormat::default(), vec![0.0; 2048], 1_000_000);
+        assert_eq!(frame.sample_count, 1024);
+        // 1024 / 48000 s = 21.333 ms
+        assert_eq!(frame.duration_ns(), 21_333_333);
+        assert_eq!(frame.duration(), Duration::from_nanos(21_333_333));
+        assert_eq!(frame.end_pts(), 22_333_333);
+    }
+
+    #[test]
+    fn test_audio_frame_silence() {
+        let silent = AudioFrame::new(AudioFormat::default(), vec![0.0; 2048], 0);
+        assert_eq!(silent.rms(), 0.0);
+        assert!(silent.is_silent(0.0));
+
+        let tone: Vec<f32> = (0..2048).map(|i| if i % 2 == 0 { 0.5 } else { -0.5 }).collect();
+        let loud = AudioFrame::new(AudioFormat::default(), tone, 0);
+        assert!((loud.rms() - 0.5).abs() < 1e-6);
+        assert!(!loud.is_silent(0.1));
+        assert!(loud.is_silent(0.5));
+    }
+}
@@ -0,0 +1,326 @@
+//! Logo watermark compositing
+//!
+//! A PNG (with or without alpha) is loaded once, scaled, and alpha-blended
+//! into a corner of every frame before encoding, using the same blend as
+//! the latency overlay. A logo larger than the frame is clipped at the edges.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::error::{NitrogenError, Result};
+use crate::overlay::{blend_image, OverlayPosition};
+
+/// Largest accepted logo scale factor
+pub const MAX_WATERMARK_SCALE: f32 = 8.0;
+
+/// Where and how to draw the watermark
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WatermarkConfig {
+    /// PNG file to draw
+    pub path: PathBuf,
+    /// Corner of the frame
+    #[serde(default = "default_position")]
+    pub position: OverlayPosition,
+    /// Distance from the frame edges in pixels
+    #[serde(default = "default_margin")]
+    pub margin: u32,
+    /// Opacity multiplied into the image's own alpha (0.0 - 1.0)
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    /// Size relative to the PNG's own size (1.0 = as is)
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+}
+
+fn default_position() -> OverlayPosition {
+    OverlayPosition::BottomRight
+}
+
+fn default_margin() -> u32 {
+    16
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+impl WatermarkConfig {
+    /// Watermark with default placement (bottom-right, 16 px margin, opaque)
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            position: default_position(),
+            margin: default_margin(),
+            opacity: default_opacity(),
+            scale: default_scale(),
+        }
+    }
+
+    /// Check opacity and scale
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return Err(format!(
+                "Watermark opacity {} is out of range (0.0 - 1.0)",
+                self.opacity
+            ));
+        }
+        if !(self.scale > 0.0 && self.scale <= MAX_WATERMARK_SCALE) {
+            return Err(format!(
+                "Watermark scale {} is out of range (above 0, at most {})",
+                self.scale, MAX_WATERMARK_SCALE
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A loaded logo, ready to blend onto BGRA frames
+#[derive(Debug)]
+pub struct Watermark {
+    config: WatermarkConfig,
+    /// Scaled logo, BGRA with straight alpha
+    image: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+impl Watermark {
+    /// Load and scale the PNG named in `config`
+    pub fn load(config: WatermarkConfig) -> Result<Self> {
+        config.validate().map_err(NitrogenError::config)?;
+        let (rgba, width, height) = decode_png(&config.path)?;
+        info!(
+            "Loaded watermark {} ({}x{}, {:?})",
+            config.path.display(),
+            width,
+            height,
+            config.position
+        );
+        Ok(Self::from_rgba(config, &rgba, width, height))
+    }
+
+    /// Build from RGBA pixels (straight alpha), scaling by `config.scale`
+    pub fn from_rgba(config: WatermarkConfig, rgba: &[u8], width: u32, height: u32) -> Self {
+        let mut bgra: Vec<u8> = rgba
+            .chunks_exact(4)
+            .flat_map(|p| [p[2], p[1], p[0], p[3]])
+            .collect();
+        let (mut w, mut h) = (width, height);
+
+        if (config.scale - 1.0).abs() > f32::EPSILON {
+            let scaled_w = ((width as f32 * config.scale).round() as u32).max(1);
+            let scaled_h = ((height as f32 * config.scale).round() as u32).max(1);
+            bgra = scale_bilinear(&bgra, width, height, scaled_w, scaled_h);
+            (w, h) = (scaled_w, scaled_h);
+        }
+
+        Self {
+            config,
+            image: bgra,
+            width: w,
+            height: h,
+        }
+    }
+
+    /// Logo size after scaling
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Blend the logo onto a BGRA frame (width * height * 4 bytes)
+    pub fn render(&self, frame: &mut [u8], width: u32, height: u32) {
+        let margin = self.config.margin;
+        let right = width.saturating_sub(self.width.saturating_add(margin));
+        let bottom = height.saturating_sub(self.height.saturating_add(margin));
+        let (x, y) = match self.config.position {
+            OverlayPosition::TopLeft => (margin, margin),
+            OverlayPosition::TopRight => (right, margin),
+            OverlayPosition::BottomLeft => (margin, bottom),
+            OverlayPosition::BottomRight => (right, bottom),
+        };
+
+        blend_image(
+            frame,
+            width,
+            height,
+            x,
+            y,
+            &self.image,
+            self.width,
+            self.height,
+            self.config.opacity,
+        );
+    }
+}
+
+/// Decode a PNG into 8-bit RGBA
+pub(crate) fn decode_png(path: &Path) -> Result<(Vec<u8>, u32, u32)> {
+    let load_error = |e: &dyn std::fmt::Display| {
+        NitrogenError::config(format!("Failed to load image {}: {}", path.display(), e))
+    };
+
+    let file = File::open(path).map_err(|e| load_error(&e))?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    // Palette and 16-bit images come out as 8-bit RGB(A) or gray
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| load_error(&e))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|e| load_error(&e))?;
+    let data = &buf[..info.buffer_size()];
+
+    let rgba = match info.color_type {
+        png::ColorType::Rgba => data.to_vec(),
+        png::ColorType::Rgb => data.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => {
+            data.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect()
+        }
+        png::ColorType::Grayscale => data.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        other => return Err(load_error(&format!("unsupported color type {:?}", other))),
+    };
+    Ok((rgba, info.width, info.height))
+}
+
+/// Resize a BGRA image with bilinear filtering
+///
+/// Colors are weighted by alpha so transparent pixels don't darken the
+/// edges of the logo.
+pub(crate) fn scale_bilinear(
+    src: &[u8],
+    width: u32,
+    height: u32,
+    new_width: u32,
+    new_height: u32,
+) -> Vec<u8> {
+    let mut out = vec![0u8; new_width as usize * new_height as usize * 4];
+    let pixel = |x: u32, y: u32| {
+        let i = (y as usize * width as usize + x as usize) * 4;
+        &src[i..i + 4]
+    };
+
+    for y in 0..new_height {
+        let sy = ((y as f32 + 0.5) * height as f32 / new_height as f32 - 0.5).max(0.0);
+        let y0 = (sy as u32).min(height - 1);
+        let y1 = (y0 + 1).min(height - 1);
+        let fy = sy - y0 as f32;
+
+        for x in 0..new_width {
+            let sx = ((x as f32 + 0.5) * width as f32 / new_width as f32 - 0.5).max(0.0);
+            let x0 = (sx as u32).min(width - 1);
+            let x1 = (x0 + 1).min(width - 1);
+            let fx = sx - x0 as f32;
+
+            let taps = [
+                (pixel(x0, y0), (1.0 - fx) * (1.0 - fy)),
+                (pixel(x1, y0), fx * (1.0 - fy)),
+                (pixel(x0, y1), (1.0 - fx) * fy),
+                (pixel(x1, y1), fx * fy),
+            ];
+            let alpha: f32 = taps.iter().map(|(p, w)| p[3] as f32 * w).sum();
+
+            let o = (y as usize * new_width as usize + x as usize) * 4;
+            if alpha > 0.0 {
+                for c in 0..3 {
+                    let color: f32 = taps.iter().map(|(p, w)| p[c] as f32 * p[3] as f32 * w).sum();
+                    out[o + c] = (color / alpha).round().min(255.0) as u8;
+                }
+            }
+            out[o + 3] = alpha.round().min(255.0) as u8;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid_frame(width: u32, height: u32, bgra: [u8; 4]) -> Vec<u8> {
+        bgra.repeat((width * height) as usize)
+    }
+
+    fn pixel(frame: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * width + x) * 4) as usize;
+        [frame[i], frame[i + 1], frame[i + 2], frame[i + 3]]
+    }
+
+    fn config(position: OverlayPosition, margin: u32, opacity: f32) -> WatermarkConfig {
+        WatermarkConfig {
+            position,
+            margin,
+            opacity,
+            ..WatermarkConfig::new("logo.png")
+        }
+    }
+
+    #[test]
+    fn test_semi_transparent_logo_blend() {
+        // 2x2 logo: red, green, blue, white, all at half alpha
+        let logo = [
+            255, 0, 0, 128, 0, 255, 0, 128, //
+            0, 0, 255, 128, 255, 255, 255, 128,
+        ];
+        let watermark = Watermark::from_rgba(config(OverlayPosition::TopLeft, 1, 1.0), &logo, 2, 2);
+        let mut frame = solid_frame(4, 4, [0, 0, 0, 255]);
+        watermark.render(&mut frame, 4, 4);
+
+        // BGRA output, each channel 255 * 128 / 255 over black
+        assert_eq!(pixel(&frame, 4, 1, 1), [0, 0, 128, 255]);
+        assert_eq!(pixel(&frame, 4, 2, 1), [0, 128, 0, 255]);
+        assert_eq!(pixel(&frame, 4, 1, 2), [128, 0, 0, 255]);
+        assert_eq!(pixel(&frame, 4, 2, 2), [128, 128, 128, 255]);
+        // The margin is untouched
+        assert_eq!(pixel(&frame, 4, 0, 0), [0, 0, 0, 255]);
+        assert_eq!(pixel(&frame, 4, 3, 3), [0, 0, 0, 255]);
+
+        // Opacity scales the image alpha on top
+        let faded = Watermark::from_rgba(config(OverlayPosition::BottomRight, 0, 0.5), &logo, 2, 2);
+        let mut frame = solid_frame(4, 4, [0, 0, 0, 255]);
+        faded.render(&mut frame, 4, 4);
+        assert_eq!(pixel(&frame, 4, 3, 3), [64, 64, 64, 255]);
+        assert_eq!(pixel(&frame, 4, 1, 1), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_logo_clipped_to_frame() {
+        let logo = [255u8; 6 * 6 * 4];
+        let watermark =
+            Watermark::from_rgba(config(OverlayPosition::BottomRight, 2, 1.0), &logo, 6, 6);
+        let mut frame = solid_frame(4, 4, [0, 0, 0, 255]);
+        watermark.render(&mut frame, 4, 4);
+
+        assert!(frame.chunks_exact(4).all(|p| p == [255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_scaled_logo_size() {
+        let logo = [255u8; 4 * 2 * 4];
+        let scale = |scale| WatermarkConfig {
+            scale,
+            ..WatermarkConfig::new("logo.png")
+        };
+
+        let watermark = Watermark::from_rgba(scale(2.0), &logo, 4, 2);
+        assert_eq!(watermark.size(), (8, 4));
+        assert!(watermark.image.chunks_exact(4).all(|p| p == [255, 255, 255, 255]));
+
+        assert_eq!(Watermark::from_rgba(scale(0.1), &logo, 4, 2).size(), (1, 1));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(WatermarkConfig::new("logo.png").validate().is_ok());
+        assert!(config(OverlayPosition::TopLeft, 0, 1.5).validate().is_err());
+
+        let mut big = WatermarkConfig::new("logo.png");
+        big.scale = 0.0;
+        assert!(big.validate().is_err());
+    }
+}
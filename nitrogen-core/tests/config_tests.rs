@@ -1,7 +1,21 @@
 //! Integration tests for configuration system
 
+use std::path::PathBuf;
+use std::time::Duration;
+
+use nitrogen_core::affinity::{CpuSet, ThreadRole};
+use nitrogen_core::capture::{DuckingMode, LimiterMode};
 use nitrogen_core::config::{
-    sample_config, CaptureConfig, Codec, ConfigFile, EncoderPreset, Preset,
+    parse_bitrate, parse_duration, recording, sample_config, setting_line, AudioCodec,
+    AudioSource, Av1Tune, CameraFormat, CaptureConfig, Codec, ConfigFile, ContentMode, CursorMode,
+    EncoderPreset, LayerSettings, MicChannel, MultipassMode, OutputAudioSettings, Preset, Tune,
+};
+use nitrogen_core::output::{ContainerFormat, IceServer, RistOptions, SrtOptions, StreamProtocol};
+use nitrogen_core::types::{
+    CaptureSource, HdrMetadata, SourceInfo, SourceKind, TransferFunction,
+};
+use nitrogen_core::{
+    MetricsMode, OverlayPosition, RateControl, SimulcastLayer, TonemapAlgorithm, TonemapMode,
 };
 use tempfile::TempDir;
 
@@ -45,6 +59,89 @@ fn test_custom_preset() {
     assert_eq!(custom.fps(), 100);
 }
 
+#[test]
+fn test_custom_preset_from_string() {
+    assert_eq!(
+        "1728x1080@90".parse::<Preset>(),
+        Ok(Preset::Custom {
+            width: 1728,
+            height: 1080,
+            fps: 90
+        })
+    );
+    assert_eq!(
+        "2560x1080@60".parse::<Preset>(),
+        Ok(Preset::Custom {
+            width: 2560,
+            height: 1080,
+            fps: 60
+        })
+    );
+    // Case-insensitive separator and the Display "fps" suffix
+    assert_eq!(
+        "2560X1080@60fps".parse::<Preset>(),
+        Ok(Preset::Custom {
+            width: 2560,
+            height: 1080,
+            fps: 60
+        })
+    );
+}
+
+#[test]
+fn test_custom_preset_rejects_malformed() {
+    let err = "foo x bar".parse::<Preset>().unwrap_err();
+    assert!(err.contains("foo x bar"), "Error should echo input: {}", err);
+
+    assert!("1920x1080".parse::<Preset>().is_err());
+    assert!("1920x@60".parse::<Preset>().is_err());
+    assert!("0x1080@60".parse::<Preset>().is_err());
+    assert!("1920x1080@0".parse::<Preset>().is_err());
+    assert!("8000x1080@60".parse::<Preset>().is_err());
+    assert!("1920x1080@500".parse::<Preset>().is_err());
+}
+
+#[test]
+fn test_preset_all_named() {
+    let named = Preset::all_named();
+    // Every preset the CLI has always accepted is still listed
+    for preset in [
+        Preset::P720_30,
+        Preset::P720_60,
+        Preset::P1080_30,
+        Preset::P1080_60,
+        Preset::P1440_30,
+        Preset::P1440_60,
+        Preset::P1440_120,
+        Preset::P4k30,
+        Preset::P4k60,
+        Preset::P4k120,
+    ] {
+        assert!(named.contains(&preset), "{} missing from all_named", preset);
+    }
+    assert!(named.contains(&Preset::default()));
+    assert!(named.iter().all(|p| !matches!(p, Preset::Custom { .. })));
+
+    let unique: std::collections::HashSet<_> = named.iter().collect();
+    assert_eq!(unique.len(), named.len());
+}
+
+#[test]
+fn test_preset_display_round_trip() {
+    for &preset in Preset::all_named() {
+        assert_eq!(preset.to_string().parse::<Preset>(), Ok(preset));
+    }
+
+    for width in (16..=7680).step_by(487) {
+        for height in (16..=4320).step_by(331) {
+            for fps in [1, 24, 30, 59, 90, 144, 240] {
+                let preset = Preset::Custom { width, height, fps };
+                assert_eq!(preset.to_string().parse::<Preset>(), Ok(preset));
+            }
+        }
+    }
+}
+
 #[test]
 fn test_codec_nvenc_encoder() {
     assert_eq!(Codec::H264.nvenc_encoder(), "h264_nvenc");
@@ -146,6 +243,166 @@ fn test_capture_config_warnings() {
     assert!(warnings.iter().any(|w| w.contains("Discord")));
 }
 
+#[test]
+fn test_camera_alpha_warnings() {
+    // Camera-only alpha is the supported path
+    let config = CaptureConfig::monitor("test")
+        .with_preset(Preset::P1080_60)
+        .with_camera_alpha(true);
+    assert!(config.validate().is_empty());
+
+    // NVENC outputs can't carry alpha
+    let config = CaptureConfig::monitor("test")
+        .with_preset(Preset::P1080_60)
+        .with_camera_alpha(true)
+        .with_record_path("/tmp/out.mkv");
+    assert!(config.validate().iter().any(|w| w.contains("opaque")));
+
+    // NV12 cameras have no alpha to keep
+    let config = CaptureConfig::monitor("test")
+        .with_preset(Preset::P1080_60)
+        .with_camera_alpha(true)
+        .with_camera_format(CameraFormat::Nv12);
+    assert!(config.validate().iter().any(|w| w.contains("NV12")));
+    assert_eq!("yuv420".parse::<CameraFormat>(), Ok(CameraFormat::Nv12));
+}
+
+#[test]
+fn test_record_container_warnings() {
+    // AV1 in WebM is fine
+    let config = CaptureConfig::monitor("test")
+        .with_preset(Preset::P1080_60)
+        .with_codec(Codec::Av1)
+        .with_record_path("/tmp/out.webm");
+    assert_eq!(config.effective_record_container(), Some(ContainerFormat::WebM));
+    assert!(config.validate().is_empty());
+
+    // HEVC can't go in WebM, whether by extension or explicitly
+    let config = config.with_codec(Codec::Hevc);
+    assert!(config.validate().iter().any(|w| w.contains("WebM")));
+    let config = CaptureConfig::monitor("test")
+        .with_preset(Preset::P1080_60)
+        .with_codec(Codec::Hevc)
+        .with_record_path("/tmp/out.mkv")
+        .with_record_container(ContainerFormat::WebM);
+    assert!(config.validate().iter().any(|w| w.contains("WebM")));
+
+    // The explicit container wins over the extension
+    let config = config.with_record_container(ContainerFormat::Mkv);
+    assert!(config.validate().is_empty());
+
+    let file: ConfigFile = toml::from_str("[recording]\ncontainer = \"fmp4\"\n").unwrap();
+    let config = file.to_capture_config(CaptureSource::monitor("DP-1")).unwrap();
+    assert_eq!(config.record_container, Some(ContainerFormat::FragmentedMp4));
+    let file: ConfigFile = toml::from_str("[recording]\ncontainer = \"avi\"\n").unwrap();
+    assert!(file.to_capture_config(CaptureSource::monitor("DP-1")).is_err());
+}
+
+#[test]
+fn test_replay_buffer_settings() {
+    let config = CaptureConfig::monitor("test").with_preset(Preset::P1080_60);
+    assert_eq!(config.replay_buffer_secs, None);
+    assert_eq!(config.clone().with_replay_buffer(0).replay_buffer_secs, None);
+
+    // 30s at the preset bitrate is nothing to warn about; ten minutes at 50 Mbps is
+    let config = config.with_replay_buffer(30);
+    assert_eq!(config.replay_buffer_secs, Some(30));
+    assert!(config.validate().is_empty());
+    let config = config.with_bitrate(50_000).with_replay_buffer(600);
+    assert!(config.validate().iter().any(|w| w.contains("replay buffer")));
+
+    let file: ConfigFile = toml::from_str("[recording]\nreplay_buffer = 45\n").unwrap();
+    let config = file.to_capture_config(CaptureSource::monitor("DP-1")).unwrap();
+    assert_eq!(config.replay_buffer_secs, Some(45));
+    let file: ConfigFile = toml::from_str("[recording]\n").unwrap();
+    let config = file.to_capture_config(CaptureSource::monitor("DP-1")).unwrap();
+    assert_eq!(config.replay_buffer_secs, None);
+}
+
+#[test]
+fn test_segment_settings() {
+    let config = CaptureConfig::monitor("test").with_record_path("/tmp/clip.mp4");
+    assert!(!config.record_segments.is_enabled());
+    let config = config
+        .with_segment_duration(Duration::from_secs(600))
+        .with_segment_size(2000);
+    assert_eq!(config.record_segments.duration, Some(Duration::from_secs(600)));
+    assert_eq!(config.record_segments.max_bytes, Some(2_000_000_000));
+    assert!(config.validate().is_empty());
+    assert_eq!(config.with_segment_size(0).record_segments.max_bytes, None);
+
+    // Limits without a recording do nothing
+    let config = CaptureConfig::monitor("test").with_segment_duration(Duration::from_secs(60));
+    assert!(config.validate().iter().any(|w| w.contains("segment")));
+
+    let file: ConfigFile =
+        toml::from_str("[recording]\nsegment_duration = \"15m\"\nsegment_size = 500\n").unwrap();
+    let config = file.to_capture_config(CaptureSource::monitor("DP-1")).unwrap();
+    assert_eq!(config.record_segments.duration, Some(Duration::from_secs(900)));
+    assert_eq!(config.record_segments.max_bytes, Some(500_000_000));
+    let file: ConfigFile = toml::from_str("[recording]\nsegment_duration = \"soon\"\n").unwrap();
+    assert!(file.to_capture_config(CaptureSource::monitor("DP-1")).is_err());
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+    assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+    assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+    assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+    assert_eq!(parse_duration("00:05:00"), Ok(Duration::from_secs(300)));
+    assert_eq!(parse_duration("1:02:03"), Ok(Duration::from_secs(3723)));
+    assert_eq!(parse_duration("02:30"), Ok(Duration::from_secs(150)));
+
+    assert!(parse_duration("").is_err());
+    assert!(parse_duration("0s").is_err());
+    assert!(parse_duration("5x").is_err());
+    assert!(parse_duration("m").is_err());
+    assert!(parse_duration("00:75:00").is_err());
+}
+
+#[test]
+fn test_parse_bitrate() {
+    assert_eq!(parse_bitrate("6000").unwrap(), 6000);
+    assert_eq!(parse_bitrate("6000k").unwrap(), 6000);
+    assert_eq!(parse_bitrate("6000kbps").unwrap(), 6000);
+    assert_eq!(parse_bitrate("6M").unwrap(), 6000);
+    assert_eq!(parse_bitrate("6mbps").unwrap(), 6000);
+    assert_eq!(parse_bitrate("6 Mbps").unwrap(), 6000);
+    assert_eq!(parse_bitrate("2.5M").unwrap(), 2500);
+    assert_eq!(parse_bitrate("1.1M").unwrap(), 1100);
+    assert_eq!(parse_bitrate("0").unwrap(), 0);
+
+    assert!(parse_bitrate("").is_err());
+    assert!(parse_bitrate("6x").is_err());
+    assert!(parse_bitrate("M").is_err());
+    assert!(parse_bitrate("1.5k").is_err());
+    assert!(parse_bitrate("-6M").is_err());
+    assert!(parse_bitrate("99999999M").is_err());
+}
+
+#[test]
+fn test_config_file_bitrate_units() {
+    let config: ConfigFile = toml::from_str(
+        r#"
+        [defaults]
+        bitrate = "6M"
+
+        [audio]
+        bitrate = "160k"
+
+        [audio.record]
+        bitrate = 192
+        "#,
+    )
+    .expect("Bitrates with units should parse");
+    assert_eq!(config.defaults.bitrate, 6000);
+    assert_eq!(config.audio.bitrate, 160);
+    assert_eq!(config.audio.record.unwrap().bitrate, 192);
+
+    assert!(toml::from_str::<ConfigFile>("[defaults]\nbitrate = \"6x\"").is_err());
+}
+
 #[test]
 fn test_config_file_default() {
     let config = ConfigFile::default();
@@ -162,6 +419,496 @@ fn test_config_file_sample_parses() {
     assert_eq!(config.defaults.preset, "1080p60");
 }
 
+#[test]
+fn test_config_file_to_capture_config() {
+    let toml = r#"
+        [defaults]
+        preset = "1440p60"
+        codec = "hevc"
+        bitrate = 12000
+
+        [encoder]
+        quality = "slow"
+        lookahead = true
+
+        [av1]
+        tune = "uhq"
+
+        [audio]
+        source = "both"
+        codec = "opus"
+        limiter = "truepeak"
+        limiter_threshold_db = -2.0
+        sync_offset_ms = -80
+        latency_quantum = 256
+
+        [audio.mic]
+        channel = "left"
+
+        [capture]
+        cursor = "metadata"
+        cursor_scale = 2.0
+        latency_quantum = 1024
+        portal_attempts = 5
+
+        [hdr]
+        tonemap = "on"
+        algorithm = "aces"
+        dynamic = true
+    "#;
+    let file: ConfigFile = toml::from_str(toml).expect("Config should parse");
+    let config = file
+        .to_capture_config(CaptureSource::monitor("DP-1"))
+        .expect("Config should convert");
+
+    assert_eq!(config.source, CaptureSource::monitor("DP-1"));
+    assert_eq!(config.preset, Preset::P1440_60);
+    assert_eq!(config.codec, Codec::Hevc);
+    assert_eq!(config.bitrate, 12000);
+    assert_eq!(config.encoder_preset, EncoderPreset::Slow);
+    assert!(config.quality.lookahead);
+    assert_eq!(config.av1.tune, Av1Tune::Uhq);
+    assert_eq!(config.audio_source, AudioSource::Both);
+    assert_eq!(config.audio_codec, AudioCodec::Opus);
+    assert_eq!(config.mic_channel, MicChannel::Left);
+    assert_eq!(config.audio_limiter.mode, LimiterMode::TruePeak);
+    assert_eq!(config.audio_limiter.threshold_db, -2.0);
+    assert_eq!(config.audio_limiter.release_ms, 50);
+    assert_eq!(config.av_sync_offset_ms, -80);
+    assert_eq!(config.cursor_mode, CursorMode::Metadata);
+    assert_eq!(config.cursor_scale, 2.0);
+    assert_eq!(config.capture_latency_quantum, Some(1024));
+    assert_eq!(config.portal_attempts, 5);
+    assert_eq!(config.audio_latency_quantum, Some(256));
+    assert_eq!(config.hdr_tonemap, TonemapMode::On);
+    assert_eq!(config.hdr_algorithm, TonemapAlgorithm::Aces);
+    assert!(config.hdr_dynamic);
+}
+
+#[test]
+fn test_config_file_deprecated_av1_quality_keys() {
+    let toml = r#"
+        [encoder]
+        lookahead_depth = 16
+
+        [av1]
+        lookahead = true
+        lookahead_depth = 32
+        spatial_aq = true
+    "#;
+    let file: ConfigFile = toml::from_str(toml).expect("Config should parse");
+    let config = file
+        .to_capture_config(CaptureSource::monitor("DP-1"))
+        .expect("Config should convert");
+
+    // The old [av1] keys still take effect, for every codec
+    assert!(config.quality.lookahead);
+    assert_eq!(config.quality.lookahead_depth, 32);
+    assert!(config.quality.spatial_aq);
+    assert!(!config.quality.temporal_aq);
+}
+
+#[test]
+fn test_config_file_camera_transition() {
+    let config = ConfigFile::default()
+        .to_capture_config(CaptureSource::monitor("DP-1"))
+        .expect("Default config should convert");
+    assert_eq!(config.camera_transition_ms, 250);
+    assert_eq!(config.camera_placeholder, None);
+
+    let toml = r#"
+        [camera]
+        transition_ms = 0
+        placeholder = "/nonexistent/away.png"
+    "#;
+    let file: ConfigFile = toml::from_str(toml).expect("Config should parse");
+    let config = file
+        .to_capture_config(CaptureSource::monitor("DP-1"))
+        .expect("Config should convert");
+    assert_eq!(config.camera_transition_ms, 0);
+    assert_eq!(config.camera_placeholder, Some(PathBuf::from("/nonexistent/away.png")));
+    assert!(config.validate().iter().any(|w| w.contains("placeholder")));
+}
+
+#[test]
+fn test_config_file_to_capture_config_defaults() {
+    let config = ConfigFile::default()
+        .to_capture_config(CaptureSource::window("42"))
+        .expect("Default config should convert");
+    let expected = CaptureConfig::window("42");
+
+    assert_eq!(config.preset, expected.preset);
+    assert_eq!(config.codec, expected.codec);
+    assert_eq!(config.encoder_preset, expected.encoder_preset);
+    assert_eq!(config.quality, expected.quality);
+    // Adaptive quantization stays opt-in
+    assert!(!config.quality.spatial_aq);
+    assert!(!config.quality.temporal_aq);
+    assert_eq!(config.av1, expected.av1);
+    assert_eq!(config.camera_name, expected.camera_name);
+    assert_eq!(config.audio_limiter, expected.audio_limiter);
+    assert!(config.hls.is_none());
+    assert!(config.dash.is_none());
+
+    let sample: ConfigFile = toml::from_str(&sample_config()).expect("Sample config should parse");
+    assert!(sample.to_capture_config(CaptureSource::monitor("portal")).is_ok());
+}
+
+#[test]
+fn test_config_file_error_points_at_line() {
+    let content = "[defaults]\npreset = \"1080p60\"\n\n[encoder]\ntune = \"fast\"\n";
+    let file = ConfigFile::parse(content).expect("Valid TOML should parse");
+    let err = file
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect_err("Unknown tune should fail")
+        .to_string();
+    assert!(err.contains("encoder.tune"), "{}", err);
+    assert_eq!(setting_line(content, "encoder.tune"), Some(5));
+
+    let err = ConfigFile::parse("[encoder]\ntune = fast\n").expect_err("Bare word is not TOML");
+    assert!(err.to_string().contains("line 2"), "{}", err);
+}
+
+#[test]
+fn test_config_file_to_capture_config_rejects_typos() {
+    let mut file = ConfigFile::default();
+    file.defaults.codec = "h265x".to_string();
+    let err = file
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect_err("Unknown codec should fail")
+        .to_string();
+    assert!(err.contains("defaults.codec"), "{}", err);
+    assert!(err.contains("h265x"), "{}", err);
+    assert!(err.contains("h264, hevc, av1"), "{}", err);
+
+    let mut file = ConfigFile::default();
+    file.audio.mic.channel = "center".to_string();
+    assert!(file.to_capture_config(CaptureSource::monitor("portal")).is_err());
+
+    let mut file = ConfigFile::default();
+    file.capture.cursor = "sideways".to_string();
+    assert!(file.to_capture_config(CaptureSource::monitor("portal")).is_err());
+
+    let mut file = ConfigFile::default();
+    file.audio.limiter = "brickwall".to_string();
+    assert!(file.to_capture_config(CaptureSource::monitor("portal")).is_err());
+
+    let mut file = ConfigFile::default();
+    file.overlay.position = "middle".to_string();
+    assert!(file.to_capture_config(CaptureSource::monitor("portal")).is_err());
+}
+
+#[test]
+fn test_config_file_metrics_mode() {
+    let config = ConfigFile::default()
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Default config should convert");
+    assert_eq!(config.metrics_mode, MetricsMode::Rolling { samples: 120 });
+
+    // A sample count of 0 switches to an EMA
+    let mut file = ConfigFile::default();
+    file.performance.metrics_sample_count = 0;
+    file.performance.ema_alpha = 0.5;
+    let config = file
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("EMA config should convert");
+    assert_eq!(config.metrics_mode, MetricsMode::Ema { alpha: 0.5 });
+
+    file.performance.ema_alpha = 1.5;
+    assert!(file.to_capture_config(CaptureSource::monitor("portal")).is_err());
+}
+
+#[test]
+fn test_config_zero_copy() {
+    // On by default; the encoder still checks CUDA support at runtime
+    assert!(CaptureConfig::monitor("portal").zero_copy);
+    assert!(!CaptureConfig::monitor("portal").has_encoded_output());
+
+    let mut file = ConfigFile::default();
+    file.encoder.zero_copy = false;
+    let config = file
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Config should convert");
+    assert!(!config.zero_copy);
+
+    let toml_str = "[encoder]\nquality = \"fast\"\n";
+    let parsed: ConfigFile = toml::from_str(toml_str).expect("Should parse");
+    assert!(parsed.encoder.zero_copy);
+}
+
+#[test]
+fn test_config_file_content_mode() {
+    let config = ConfigFile::default()
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Default config should convert");
+    assert_eq!(config.content_mode, ContentMode::Motion);
+
+    let mut file = ConfigFile::default();
+    file.encoder.content_mode = "static".to_string();
+    let config = file
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Static config should convert");
+    assert_eq!(config.content_mode, ContentMode::Static);
+
+    file.encoder.content_mode = "timelapse".to_string();
+    assert!(file.to_capture_config(CaptureSource::monitor("portal")).is_err());
+}
+
+#[test]
+fn test_config_file_multipass() {
+    let config = ConfigFile::default()
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Default config should convert");
+    assert_eq!(config.multipass(), MultipassMode::Disabled);
+
+    let mut file = ConfigFile::default();
+    file.encoder.multipass = "full".to_string();
+    file.defaults.low_latency = false;
+    let config = file
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Multipass config should convert");
+    assert_eq!(config.multipass(), MultipassMode::Full);
+    assert!(!config.validate().iter().any(|w| w.contains("Multipass")));
+
+    file.encoder.multipass = "half".to_string();
+    assert!(file.to_capture_config(CaptureSource::monitor("portal")).is_err());
+}
+
+#[test]
+fn test_multipass_low_latency_warning() {
+    let config = CaptureConfig::monitor("portal").with_multipass(MultipassMode::Full);
+    assert!(config.low_latency);
+    assert!(config.validate().iter().any(|w| w.contains("Multipass")));
+
+    let mut config = config;
+    config.low_latency = false;
+    assert!(!config.validate().iter().any(|w| w.contains("Multipass")));
+}
+
+#[test]
+fn test_async_depth_low_latency_override() {
+    let config = CaptureConfig::monitor("portal").with_async_depth(4);
+    assert!(config.low_latency);
+    assert_eq!(config.quality.effective_async_depth(true), Some(1));
+    assert!(config.validate().iter().any(|w| w.contains("Async depth")));
+
+    let mut config = config;
+    config.low_latency = false;
+    assert_eq!(config.quality.effective_async_depth(false), Some(4));
+    assert!(!config.validate().iter().any(|w| w.contains("Async depth")));
+
+    let mut file = ConfigFile::default();
+    file.encoder.async_depth = 3;
+    let config = file
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Async depth config should convert");
+    assert_eq!(config.quality.async_depth, 3);
+    assert_eq!(ConfigFile::default().encoder.async_depth, 0);
+}
+
+#[test]
+fn test_av1_multipass_fallback() {
+    let mut config = CaptureConfig::monitor("portal").with_codec(Codec::Av1);
+    config.av1.multipass = MultipassMode::Quarter;
+    assert_eq!(config.multipass(), MultipassMode::Quarter);
+
+    // The shared setting takes precedence, and other codecs ignore av1.multipass
+    let config = config.with_multipass(MultipassMode::Full);
+    assert_eq!(config.multipass(), MultipassMode::Full);
+    let mut config = CaptureConfig::monitor("portal");
+    config.av1.multipass = MultipassMode::Quarter;
+    assert_eq!(config.multipass(), MultipassMode::Disabled);
+}
+
+#[test]
+fn test_config_file_layout() {
+    let config = ConfigFile::default()
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Default config should convert");
+    assert!(!config.is_composited());
+
+    // Grid of sources across the 1080p output
+    let mut file = ConfigFile::default();
+    file.layout.sources = vec!["window".to_string(), "monitor:DP-2".to_string()];
+    let config = file
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Grid layout should convert");
+    assert_eq!(config.layout.len(), 2);
+    assert_eq!(config.layout[0].source, CaptureSource::window("portal"));
+    assert_eq!(config.layout[1].source, CaptureSource::monitor("DP-2"));
+    assert_eq!((config.layout[1].x, config.layout[1].width), (960, 960));
+    assert!(config.validate_strict().is_ok());
+
+    // Explicit layers
+    let mut file = ConfigFile::default();
+    file.layout.layers = vec![LayerSettings {
+        source: "window:42".to_string(),
+        x: 1440,
+        y: 810,
+        width: 480,
+        height: 270,
+        z: 1,
+    }];
+    let config = file
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Layer layout should convert");
+    assert_eq!(config.layout[0].source, CaptureSource::window("42"));
+    assert_eq!(config.layout[0].z, 1);
+
+    file.layout.layers[0].width = 0;
+    assert!(file.to_capture_config(CaptureSource::monitor("portal")).is_err());
+
+    file.layout.layers[0].width = 480;
+    file.layout.sources = vec!["window".to_string()];
+    assert!(file.to_capture_config(CaptureSource::monitor("portal")).is_err());
+
+    let mut file = ConfigFile::default();
+    file.layout.sources = vec!["screen".to_string()];
+    assert!(file.to_capture_config(CaptureSource::monitor("portal")).is_err());
+}
+
+#[test]
+fn test_config_file_layout_toml() {
+    let toml_str = r#"
+[[layout.layers]]
+source = "monitor:DP-2"
+width = 1920
+height = 1080
+
+[[layout.layers]]
+source = "window"
+x = 1440
+y = 810
+width = 480
+height = 270
+z = 1
+"#;
+    let parsed: ConfigFile = toml::from_str(toml_str).expect("Should parse");
+    assert_eq!(parsed.layout.layers.len(), 2);
+    assert_eq!(parsed.layout.layers[0].x, 0);
+    assert_eq!(parsed.layout.layers[1].z, 1);
+}
+
+#[test]
+fn test_capture_source_parse() {
+    assert_eq!("monitor:DP-2".parse(), Ok(CaptureSource::monitor("DP-2")));
+    assert_eq!("window".parse(), Ok(CaptureSource::window("portal")));
+    assert_eq!("Monitor:".parse(), Ok(CaptureSource::monitor("portal")));
+    assert!("camera:0".parse::<CaptureSource>().is_err());
+}
+
+#[test]
+fn test_capture_source_is_same() {
+    // Monitors match on the connector, however it is written
+    let dp2 = CaptureSource::monitor("DP-2");
+    assert!(dp2.is_same(&CaptureSource::monitor("monitor:dp-2")));
+    assert!(!dp2.is_same(&CaptureSource::monitor("HDMI-A-1")));
+    assert!(!dp2.is_same(&CaptureSource::window("DP-2")));
+
+    // Window IDs are compositor-specific and compared exactly
+    let window = CaptureSource::window("0x3a00007");
+    assert!(window.is_same(&CaptureSource::window("0x3a00007")));
+    assert!(!window.is_same(&CaptureSource::window("0x3A00008")));
+
+    // Picker requests only match each other
+    assert!(CaptureSource::monitor("portal").is_same(&CaptureSource::monitor("")));
+    assert!(!CaptureSource::monitor("portal").is_same(&dp2));
+
+    // Granted streams: size, name and node ID don't matter, the stream ID does
+    let granted = SourceInfo::new("DP-2", "DP-2", SourceKind::Monitor, (2560, 1440));
+    let after_mode_switch = SourceInfo::new("dp-2", "Main", SourceKind::Monitor, (1920, 1080))
+        .with_refresh_rate(60.0);
+    assert!(dp2.matches(&granted));
+    assert!(granted.is_same(&after_mode_switch));
+    assert!(!CaptureSource::monitor("DP-1").matches(&granted));
+
+    // Without a stream ID only the node ID is known, which confirms nothing
+    let node = |id: u32| {
+        SourceInfo::new(format!("node:{}", id), "Monitor 1", SourceKind::Monitor, (1920, 1080))
+    };
+    assert!(!node(57).is_same(&node(63)));
+    assert!(!node(57).is_same(&node(57)));
+    assert!(!CaptureSource::monitor("node:57").matches(&node(57)));
+}
+
+#[test]
+fn test_layout_layer_outside_output_warns() {
+    use nitrogen_core::capture::LayoutLayer;
+
+    let config = CaptureConfig::monitor("portal").with_layout(vec![LayoutLayer::new(
+        CaptureSource::window("portal"),
+        4000,
+        0,
+        640,
+        360,
+    )]);
+    assert!(config.validate().iter().any(|w| w.contains("outside")));
+}
+
+#[test]
+fn test_config_file_webrtc_simulcast() {
+    let config = ConfigFile::default()
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Default config should convert");
+    assert!(config.webrtc_simulcast.is_empty());
+
+    let mut file = ConfigFile::default();
+    file.webrtc.simulcast = true;
+    let config = file
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Default ladder should convert");
+    let heights: Vec<u32> = config.webrtc_simulcast.iter().map(|l| l.height).collect();
+    assert_eq!(heights, vec![1080, 720, 360]);
+
+    // Layers are sorted highest first
+    file.webrtc.layers = vec![
+        SimulcastLayer::new("low", 360, 500, 0),
+        SimulcastLayer::new("high", 720, 2000, 80),
+    ];
+    let config = file
+        .to_capture_config(CaptureSource::monitor("portal"))
+        .expect("Custom ladder should convert");
+    assert_eq!(config.webrtc_simulcast[0].rid, "high");
+
+    file.webrtc.layers = vec![
+        SimulcastLayer::new("a", 720, 2000, 0),
+        SimulcastLayer::new("a", 360, 500, 0),
+    ];
+    assert!(file.to_capture_config(CaptureSource::monitor("portal")).is_err());
+
+    file.webrtc.layers = vec![SimulcastLayer::new("a", 720, 2000, 0)];
+    assert!(file.to_capture_config(CaptureSource::monitor("portal")).is_err());
+}
+
+#[test]
+fn test_config_file_webrtc_ice_servers() {
+    let toml_str = r#"
+[webrtc]
+ice_servers = [
+    "stun:stun.l.google.com:19302",
+    { urls = ["turn:turn.example.com:3478", "turns:turn.example.com:5349"], username = "user", credential = "secret" },
+]
+"#;
+    let parsed: ConfigFile = toml::from_str(toml_str).expect("Should parse");
+    let config = parsed.to_capture_config(CaptureSource::monitor("test")).expect("Should convert");
+
+    let servers = &config.webrtc_ice_servers;
+    assert_eq!(servers.len(), 2);
+    assert_eq!(servers[0], IceServer::stun("stun:stun.l.google.com:19302"));
+    assert!(servers[1].is_turn());
+    assert_eq!(servers[1].urls.len(), 2);
+    assert_eq!(servers[1].username.as_deref(), Some("user"));
+    assert_eq!(servers[1].credential.as_deref(), Some("secret"));
+
+    // TURN without credentials is rejected
+    let toml_str = r#"
+[webrtc]
+ice_servers = [{ urls = "turn:turn.example.com:3478" }]
+"#;
+    let parsed: ConfigFile = toml::from_str(toml_str).expect("Should parse");
+    assert!(parsed.to_capture_config(CaptureSource::monitor("test")).is_err());
+}
+
 #[test]
 fn test_config_file_save_load() {
     let temp_dir = TempDir::new().expect("Failed to create temp dir");
@@ -212,3 +959,594 @@ fn test_codec_display() {
     assert_eq!(format!("{}", Codec::Hevc), "HEVC");
     assert_eq!(format!("{}", Codec::Av1), "AV1");
 }
+
+#[test]
+fn test_config_file_per_output_audio() {
+    let toml_str = r#"
+[audio]
+source = "desktop"
+codec = "aac"
+
+[audio.record]
+codec = "opus"
+bitrate = 160
+"#;
+    let parsed: ConfigFile = toml::from_str(toml_str).expect("Should parse");
+    let config = parsed.to_capture_config(CaptureSource::monitor("test")).expect("Should convert");
+
+    let record = config.effective_record_audio();
+    assert_eq!(record.codec, AudioCodec::Opus);
+    assert_eq!(record.effective_bitrate(), 160);
+
+    // The stream falls back to the shared codec and its default bitrate
+    let stream = config.effective_stream_audio();
+    assert_eq!(stream.codec, AudioCodec::Aac);
+    assert_eq!(stream.effective_bitrate(), 192);
+}
+
+#[test]
+fn test_audio_only_validation() {
+    let config = CaptureConfig::monitor("test")
+        .with_audio_only()
+        .with_audio_source(AudioSource::Both)
+        .with_audio_codec(AudioCodec::Aac)
+        .with_record_path("/tmp/out.opus");
+    assert!(config.validate_audio_only().is_ok());
+    // The container picks the codec
+    assert_eq!(config.effective_record_audio().codec, AudioCodec::Opus);
+    assert_eq!(
+        config.clone().with_record_path("/tmp/out.m4a").effective_record_audio().codec,
+        AudioCodec::Aac
+    );
+
+    // Needs an audio source, an audio container and no other outputs
+    let mut silent = config.clone();
+    silent.audio_source = AudioSource::None;
+    assert!(silent.validate_audio_only().is_err());
+    assert!(config.clone().with_record_path("/tmp/out.mp4").validate_audio_only().is_err());
+    let mut no_file = config.clone();
+    no_file.record_path = None;
+    assert!(no_file.validate_audio_only().is_err());
+    let mut camera = config.clone();
+    camera.camera_enabled = true;
+    assert!(camera.validate_audio_only().is_err());
+    let mut streaming = config;
+    streaming.stream_url = Some("rtmp://localhost/live".to_string());
+    assert!(streaming.validate_audio_only().is_err());
+}
+
+#[test]
+fn test_config_file_rejects_output_audio_copy() {
+    let mut file = ConfigFile::default();
+    file.audio.stream = Some(OutputAudioSettings {
+        codec: "copy".to_string(),
+        bitrate: 0,
+    });
+    assert!(file.to_capture_config(CaptureSource::monitor("test")).is_err());
+}
+
+#[test]
+fn test_output_audio_copy_fails_strict_validation() {
+    let config = CaptureConfig::monitor("test")
+        .with_audio_source(AudioSource::Desktop)
+        .with_record_audio(AudioCodec::Copy, 0);
+    assert!(config.validate_strict().is_err());
+
+    // Without audio capture there is nothing to encode
+    let config = CaptureConfig::monitor("test").with_record_audio(AudioCodec::Copy, 0);
+    assert!(config.validate_strict().is_ok());
+}
+
+#[test]
+fn test_config_file_overlay_template() {
+    let toml_str = r#"
+[overlay]
+enabled = true
+template = "{title}\n{time} | {fps}fps"
+title = "Speedrun"
+"#;
+    let parsed: ConfigFile = toml::from_str(toml_str).expect("Should parse");
+    let config = parsed
+        .to_capture_config(CaptureSource::monitor("test"))
+        .expect("Should convert");
+    assert_eq!(
+        config.overlay_template.as_deref(),
+        Some("{title}\n{time} | {fps}fps")
+    );
+    assert_eq!(config.overlay_title, "Speedrun");
+
+    // An empty template keeps the stats line
+    let config = ConfigFile::default()
+        .to_capture_config(CaptureSource::monitor("test"))
+        .unwrap();
+    assert!(config.overlay_template.is_none());
+}
+
+#[test]
+fn test_config_file_dash_section() {
+    let toml_str = r#"
+[dash]
+enabled = true
+dir = "/tmp/nitrogen-dash-test"
+segment_duration = 4
+low_latency = true
+"#;
+    let parsed: ConfigFile = toml::from_str(toml_str).expect("Should parse");
+    let config = parsed
+        .to_capture_config(CaptureSource::monitor("test"))
+        .expect("Should convert");
+    let dash = config.dash.expect("DASH should be enabled");
+    assert_eq!(dash.output_dir, std::path::Path::new("/tmp/nitrogen-dash-test"));
+    assert_eq!(dash.segment_duration, 4);
+    assert_eq!(dash.window_size, 6);
+    assert!(dash.low_latency);
+    assert!(config.has_encoded_output());
+}
+
+#[test]
+fn test_audio_source_application() {
+    assert_eq!(
+        "app:firefox".parse::<AudioSource>(),
+        Ok(AudioSource::Application("firefox".to_string()))
+    );
+    // The application name keeps its case, the prefix doesn't matter
+    assert_eq!(
+        "Application:Discord".parse::<AudioSource>(),
+        Ok(AudioSource::Application("Discord".to_string()))
+    );
+    assert!("app:".parse::<AudioSource>().is_err());
+    assert_eq!(AudioSource::Application("87".to_string()).to_string(), "app:87");
+    assert_eq!("desktop".parse::<AudioSource>(), Ok(AudioSource::Desktop));
+
+    let toml_str = r#"
+[audio]
+source = "app:firefox"
+"#;
+    let parsed: ConfigFile = toml::from_str(toml_str).expect("Should parse");
+    let config = parsed
+        .to_capture_config(CaptureSource::monitor("test"))
+        .expect("Should convert");
+    assert_eq!(
+        config.audio_source,
+        AudioSource::Application("firefox".to_string())
+    );
+    assert!(config.has_audio());
+}
+
+#[test]
+fn test_config_file_recording_metadata() {
+    let toml_str = r#"
+[recording.metadata]
+title = "Friday raid"
+"#;
+    let parsed: ConfigFile = toml::from_str(toml_str).expect("Should parse");
+    let config = parsed
+        .to_capture_config(CaptureSource::monitor("test"))
+        .expect("Should convert");
+    assert!(config.record_metadata.enabled);
+    assert_eq!(config.record_metadata.title.as_deref(), Some("Friday raid"));
+
+    // Tagging is on by default
+    let config = ConfigFile::default()
+        .to_capture_config(CaptureSource::monitor("test"))
+        .unwrap();
+    assert!(config.record_metadata.enabled);
+    assert!(config.record_metadata.title.is_none());
+}
+
+#[test]
+fn test_config_file_overlay_timecode() {
+    let toml_str = r#"
+[defaults]
+preset = "1080p30"
+codec = "hevc"
+
+[overlay]
+timecode = true
+timecode_start = "10:00:00:00"
+timecode_metadata = true
+"#;
+    let parsed: ConfigFile = toml::from_str(toml_str).expect("Should parse");
+    let config = parsed
+        .to_capture_config(CaptureSource::monitor("test"))
+        .expect("Should convert");
+    let start = config.overlay_timecode.expect("timecode enabled");
+    assert_eq!(start.to_string(), "10:00:00:00");
+    assert!(config.timecode_metadata);
+    assert!(config.validate_strict().is_ok());
+    assert!(!config.validate().iter().any(|w| w.contains("timecode")));
+
+    // Frame 30 doesn't exist at 30 fps
+    let late = config.clone().with_timecode("10:00:00:30".parse().unwrap());
+    assert!(late.validate_strict().is_err());
+
+    // H.264 keeps the burn-in but can't carry the metadata
+    let mut h264 = config;
+    h264.codec = Codec::H264;
+    assert!(h264.validate().iter().any(|w| w.contains("only burned in")));
+
+    let bad: ConfigFile = toml::from_str("[overlay]\ntimecode = true\ntimecode_start = \"1:2\"\n").unwrap();
+    let err = bad.to_capture_config(CaptureSource::monitor("test")).unwrap_err();
+    assert!(err.to_string().contains("overlay.timecode_start"));
+
+    // Off unless asked for
+    let config = ConfigFile::default()
+        .to_capture_config(CaptureSource::monitor("test"))
+        .unwrap();
+    assert!(config.overlay_timecode.is_none());
+}
+
+#[test]
+fn test_config_file_ducking() {
+    let config = ConfigFile::default()
+        .to_capture_config(CaptureSource::monitor("test"))
+        .expect("Should convert");
+    assert!(!config.audio_ducking);
+    assert_eq!(config.audio_ducking_mode, DuckingMode::Rms);
+
+    let parsed: ConfigFile =
+        toml::from_str("[audio.ducking]\nenabled = true\nmode = \"vad\"\n").unwrap();
+    let config = parsed
+        .to_capture_config(CaptureSource::monitor("test"))
+        .expect("Should convert");
+    assert!(config.audio_ducking);
+    assert_eq!(config.audio_ducking_mode, DuckingMode::Vad);
+
+    let bad: ConfigFile = toml::from_str("[audio.ducking]\nmode = \"gate\"\n").unwrap();
+    let err = bad.to_capture_config(CaptureSource::monitor("test")).unwrap_err();
+    assert!(err.to_string().contains("audio.ducking.mode"), "{}", err);
+}
+
+#[test]
+fn test_config_file_hdr_input() {
+    let parsed: ConfigFile = toml::from_str("[hdr]\ninput = \"pq:1000\"\n").unwrap();
+    let config = parsed
+        .to_capture_config(CaptureSource::monitor("test"))
+        .expect("Should convert");
+    let input = config.hdr_input.expect("input set");
+    assert_eq!(input.transfer, TransferFunction::Pq);
+    assert_eq!(input.peak_luminance(), 1000);
+
+    assert_eq!("hlg".parse::<HdrMetadata>().unwrap(), HdrMetadata::hlg());
+    assert!("sdr:400".parse::<HdrMetadata>().is_err());
+    assert!("pq:20".parse::<HdrMetadata>().is_err());
+
+    let bad: ConfigFile = toml::from_str("[hdr]\ninput = \"dolby\"\n").unwrap();
+    let err = bad.to_capture_config(CaptureSource::monitor("test")).unwrap_err();
+    assert!(err.to_string().contains("hdr.input"), "{}", err);
+}
+
+#[test]
+fn test_config_file_cpu_affinity() {
+    let toml_str = r#"
+[performance.cpu_affinity]
+capture = "2"
+encode = "3-4"
+"#;
+    let parsed: ConfigFile = toml::from_str(toml_str).expect("Should parse");
+    let config = parsed
+        .to_capture_config(CaptureSource::monitor("test"))
+        .expect("Should convert");
+    assert_eq!(config.cpu_affinity.capture, Some(CpuSet::from_cores([2])));
+    assert_eq!(config.cpu_affinity.encode, Some(CpuSet::from_cores([3, 4])));
+    assert_eq!(config.cpu_affinity.get(ThreadRole::Audio), None);
+
+    let bad: ConfigFile =
+        toml::from_str("[performance.cpu_affinity]\naudio = \"4-2\"\n").unwrap();
+    let err = bad.to_capture_config(CaptureSource::monitor("test")).unwrap_err();
+    assert!(err.to_string().contains("performance.cpu_affinity.audio"), "{}", err);
+}
+
+#[test]
+fn test_config_file_watermark() {
+    let toml_str = r#"
+[watermark]
+path = "/nonexistent/logo.png"
+position = "top-right"
+opacity = 0.5
+"#;
+    let parsed: ConfigFile = toml::from_str(toml_str).expect("Should parse");
+    let config = parsed
+        .to_capture_config(CaptureSource::monitor("test"))
+        .expect("Should convert");
+    let watermark = config.watermark.clone().expect("watermark set");
+    assert_eq!(watermark.position, OverlayPosition::TopRight);
+    assert_eq!(watermark.margin, 16);
+    assert_eq!(watermark.scale, 1.0);
+    assert!(config.validate().iter().any(|w| w.contains("does not exist")));
+
+    let bad: ConfigFile =
+        toml::from_str("[watermark]\npath = \"logo.png\"\nopacity = 2.0\n").unwrap();
+    assert!(bad.to_capture_config(CaptureSource::monitor("test")).is_err());
+
+    let config = ConfigFile::default()
+        .to_capture_config(CaptureSource::monitor("test"))
+        .unwrap();
+    assert!(config.watermark.is_none());
+}
+
+#[test]
+fn test_rist_stream_options() {
+    let mut config = CaptureConfig::monitor("test");
+    config.stream_url = Some("rist://relay.example.com:5000".to_string());
+    assert_eq!(config.stream_protocol(), Some(StreamProtocol::Rist));
+    assert!(config.validate_strict().is_ok());
+
+    // Encryption without a secret can't be negotiated
+    let config = config.with_stream_rist(RistOptions {
+        encryption: Some(256),
+        ..Default::default()
+    });
+    assert!(config.validate_strict().is_err());
+
+    // Options are ignored, with a warning, on other protocols
+    let mut config = config.with_stream_rist(RistOptions {
+        buffer_ms: Some(1000),
+        ..Default::default()
+    });
+    config.stream_url = Some("srt://localhost:9999".to_string());
+    assert!(config.validate_strict().is_ok());
+    assert!(config.validate().iter().any(|w| w.contains("RIST options")));
+}
+
+#[test]
+fn test_srt_stream_options() {
+    let mut config = CaptureConfig::monitor("test")
+        .with_stream_srt(SrtOptions::default().with_latency_ms(120).with_passphrase("secret"));
+    config.stream_url = Some("srt://host:9000".to_string());
+    // Too short for libsrt
+    assert!(config.validate_strict().is_err());
+
+    let config = config.with_stream_srt(
+        SrtOptions::default()
+            .with_latency_ms(120)
+            .with_passphrase("a longer secret"),
+    );
+    assert!(config.validate_strict().is_ok());
+    assert!(!config.validate().iter().any(|w| w.contains("SRT options")));
+
+    // Options are ignored, with a warning, on other protocols
+    let mut config = config;
+    config.stream_url = Some("rtmp://live.twitch.tv/app/key".to_string());
+    assert!(config.validate().iter().any(|w| w.contains("SRT options")));
+}
+
+#[test]
+fn test_stream_mirrors() {
+    let config = CaptureConfig::monitor("test").with_stream_mirror("srt://relay.example.com:9000");
+    // Mirrors only copy a main stream
+    assert!(config.stream_urls().is_empty());
+
+    let mut config = config.with_stream_mirror("rist://relay.example.com:5000");
+    config.stream_url = Some("rtmp://live.twitch.tv/app/key".to_string());
+    assert_eq!(
+        config.stream_urls(),
+        [
+            "rtmp://live.twitch.tv/app/key",
+            "srt://relay.example.com:9000",
+            "rist://relay.example.com:5000",
+        ]
+    );
+    assert!(config.streams_to(StreamProtocol::Rist));
+    assert_eq!(config.stream_protocol(), Some(StreamProtocol::Rtmp));
+
+    // RIST options apply to the rist:// mirror
+    let config = config.with_stream_rist(RistOptions {
+        buffer_ms: Some(1000),
+        ..Default::default()
+    });
+    assert!(!config.validate().iter().any(|w| w.contains("RIST options")));
+}
+
+#[test]
+fn test_per_output_presets() {
+    let config = CaptureConfig::monitor("test")
+        .with_preset(Preset::P1440_60)
+        .with_bitrate(12_000)
+        .with_record_path("/tmp/game.mkv")
+        .with_record_preset(Preset::P1440_60)
+        .with_stream_preset(Preset::P1080_60);
+
+    // Same resolution as the main output shares the main encoder
+    assert!(config.record_encoder_config().is_none());
+
+    let stream = config
+        .stream_encoder_config()
+        .expect("Stream should get its own encoder");
+    assert_eq!((stream.width(), stream.height()), (1920, 1080));
+    assert_eq!(stream.effective_bitrate(), Preset::P1080_60.suggested_bitrate());
+    assert!(config.validate_strict().is_ok());
+
+    // Only the resolution may differ
+    let config = config.with_stream_preset(Preset::P1080_30);
+    assert!(config.validate_strict().is_err());
+}
+
+#[test]
+fn test_per_output_encode_load_warning() {
+    let base = CaptureConfig::monitor("test").with_preset(Preset::P4k60);
+    // One encoder at 4K60 is at the budget, not over it
+    assert!(!base
+        .validate()
+        .iter()
+        .any(|w| w.contains("separate resolutions")));
+
+    let mut config = base
+        .with_record_preset(Preset::P1440_60)
+        .with_record_path("/tmp/game.mkv");
+    config.webrtc_enabled = true;
+    assert!(config.encode_pixel_rate() > 500_000_000);
+    assert!(config
+        .validate()
+        .iter()
+        .any(|w| w.contains("separate resolutions")));
+}
+
+#[test]
+fn test_cursor_scale_validation() {
+    let config = CaptureConfig::monitor("test").with_cursor_mode(CursorMode::Metadata);
+    assert!(config.validate_strict().is_ok());
+    assert!(config.clone().with_cursor_scale(2.5).validate_strict().is_ok());
+    assert!(config.clone().with_cursor_scale(0.0).validate_strict().is_err());
+    assert!(config.with_cursor_scale(8.0).validate_strict().is_err());
+
+    // The scale is ignored unless Nitrogen draws the cursor
+    let embedded = CaptureConfig::monitor("test").with_cursor_scale(2.0);
+    assert!(embedded.validate().iter().any(|w| w.contains("Cursor scale")));
+}
+
+#[test]
+fn test_tune_resolution() {
+    let config = CaptureConfig::monitor("test");
+    assert_eq!(config.effective_tune(), Tune::Ll);
+
+    let mut quality = config.clone();
+    quality.low_latency = false;
+    assert_eq!(quality.effective_tune(), Tune::Hq);
+
+    // An explicit tune wins over the low-latency flag
+    let ull = quality.with_tune(Tune::Ull).with_lookahead(true);
+    assert_eq!(ull.effective_tune(), Tune::Ull);
+    let warnings = ull.validate();
+    assert!(warnings.iter().any(|w| w.contains("Ultra-low-latency")));
+    assert!(warnings.iter().any(|w| w.contains("Lookahead is ignored")));
+}
+
+#[test]
+fn test_av_sync_offset_validation() {
+    let config = CaptureConfig::monitor("test");
+    assert!(config.clone().with_av_sync_offset(-80).validate_strict().is_ok());
+    assert!(config.clone().with_av_sync_offset(5000).validate_strict().is_ok());
+    assert!(config.with_av_sync_offset(-6000).validate_strict().is_err());
+}
+
+#[test]
+fn test_temporal_layers_validation() {
+    let config = CaptureConfig::monitor("test")
+        .with_codec(Codec::H264)
+        .with_temporal_layers(3);
+    assert!(config.validate_strict().is_ok());
+
+    // Only H.264 supports temporal SVC
+    let hevc = config.clone().with_codec(Codec::Hevc);
+    assert!(hevc.validate_strict().is_err());
+
+    // Over the limit is clamped with a warning, recording gets no benefit
+    let config = config.with_temporal_layers(8).with_record_path("/tmp/out.mkv");
+    let warnings = config.validate();
+    assert!(warnings.iter().any(|w| w.contains("clamped")));
+    assert!(warnings.iter().any(|w| w.contains("recording")));
+}
+
+#[test]
+fn test_latency_quantum_validation() {
+    let config = CaptureConfig::monitor("test");
+    assert_eq!(config.capture_latency_quantum, None);
+    assert!(config.clone().with_latency_quantum(Some(512), Some(256)).validate_strict().is_ok());
+    assert!(config.clone().with_latency_quantum(Some(0), None).validate_strict().is_err());
+    let err = config.with_latency_quantum(None, Some(16384)).validate_strict().unwrap_err();
+    assert!(err.contains("Audio"));
+}
+
+#[test]
+fn test_recording_profile() {
+    let config = CaptureConfig::monitor("test").with_recording_profile();
+    assert!(!config.low_latency);
+    assert!(!config.camera_enabled);
+    assert_eq!(config.constant_quality(), Some(recording::CONSTANT_QUALITY));
+    assert_eq!(config.keyframe_interval, Some(config.fps() * recording::KEYFRAME_SECONDS));
+    assert!(config.validate_strict().is_ok());
+
+    // Explicit settings survive the profile
+    let custom = CaptureConfig::monitor("test")
+        .with_constant_quality(18)
+        .with_keyframe_interval(30)
+        .with_recording_profile();
+    assert_eq!((custom.constant_quality(), custom.keyframe_interval), (Some(18), Some(30)));
+
+    assert!(custom.clone().with_constant_quality(0).validate_strict().is_err());
+    assert!(custom.clone().with_constant_quality(52).validate_strict().is_err());
+    assert!(custom.with_keyframe_interval(0).validate_strict().is_err());
+
+    // Low latency is CBR, the quality target can't apply
+    let streaming = CaptureConfig::monitor("test").with_constant_quality(23);
+    assert!(streaming.validate().iter().any(|w| w.contains("Constant quality")));
+}
+
+#[test]
+fn test_rate_control() {
+    assert_eq!("cbr".parse::<RateControl>(), Ok(RateControl::Cbr));
+    assert_eq!("CQP:20".parse::<RateControl>(), Ok(RateControl::Cqp { qp: 20 }));
+    assert_eq!("crf:23".parse::<RateControl>(), Ok(RateControl::ConstQuality { cq: 23 }));
+    assert!("cq".parse::<RateControl>().is_err());
+    assert!("abr".parse::<RateControl>().is_err());
+    assert_eq!(RateControl::Cqp { qp: 20 }.to_string(), "cqp:20");
+
+    // The tune decides until a mode is picked; low latency stays CBR
+    let config = CaptureConfig::monitor("test");
+    assert_eq!(config.effective_rate_control(), RateControl::Cbr);
+    let mut config = config.with_rate_control(RateControl::Cqp { qp: 20 });
+    assert_eq!(config.effective_rate_control(), RateControl::Cbr);
+    assert!(config.validate().iter().any(|w| w.contains("low-latency")));
+    config.low_latency = false;
+    assert_eq!(config.effective_rate_control(), RateControl::Cqp { qp: 20 });
+    assert!(config.validate().is_empty());
+
+    // CQP has no use for a bitrate
+    let with_bitrate = config.clone().with_bitrate(8000);
+    assert!(with_bitrate.validate().iter().any(|w| w.contains("ignores the 8000 kbps")));
+
+    // AV1 has wider ranges
+    let cq = |cq| config.clone().with_rate_control(RateControl::ConstQuality { cq });
+    assert!(cq(60).validate_strict().is_err());
+    assert!(cq(60).with_codec(Codec::Av1).validate_strict().is_ok());
+    let qp = |qp| config.clone().with_rate_control(RateControl::Cqp { qp });
+    assert!(qp(120).validate_strict().is_err());
+    assert!(qp(120).with_codec(Codec::Av1).validate_strict().is_ok());
+
+    let file: ConfigFile = toml::from_str("[encoder]\nrate_control = \"cq:19\"\n").unwrap();
+    let config = file.to_capture_config(CaptureSource::monitor("DP-1")).unwrap();
+    assert_eq!(config.constant_quality(), Some(19));
+    let file: ConfigFile = toml::from_str("[encoder]\n").unwrap();
+    let config = file.to_capture_config(CaptureSource::monitor("DP-1")).unwrap();
+    assert_eq!(config.rate_control, None);
+    let file: ConfigFile = toml::from_str("[encoder]\nrate_control = \"abr\"\n").unwrap();
+    assert!(file.to_capture_config(CaptureSource::monitor("DP-1")).is_err());
+}
+
+#[test]
+fn test_bitrate_cap() {
+    let config = CaptureConfig::monitor("test")
+        .with_preset(Preset::P1080_60)
+        .with_bitrate(6000);
+    assert_eq!(config.effective_max_bitrate(), 9000);
+    assert_eq!(config.effective_vbv_buffer_size(), 6000);
+
+    let capped = config.clone().with_max_bitrate(6500).with_vbv_buffer_size(3000);
+    assert_eq!((capped.effective_max_bitrate(), capped.effective_vbv_buffer_size()), (6500, 3000));
+    assert!(capped.validate().is_empty());
+    assert_eq!(capped.with_max_bitrate(0).effective_max_bitrate(), 9000);
+
+    // A cap below the target can't be met
+    let low = config.with_max_bitrate(4000);
+    assert!(low.validate().iter().any(|w| w.contains("Max bitrate 4000 kbps")));
+
+    let file: ConfigFile =
+        toml::from_str("[encoder]\nmax_bitrate = \"6M\"\nvbv_buffer_size = 3000\n").unwrap();
+    let config = file.to_capture_config(CaptureSource::monitor("DP-1")).unwrap();
+    assert_eq!((config.max_bitrate, config.vbv_buffer_size), (Some(6000), Some(3000)));
+}
+
+#[test]
+fn test_intra_refresh_validation() {
+    let config = CaptureConfig::monitor("test").with_intra_refresh(None);
+    assert_eq!(config.resolved_intra_refresh_period(), Some(config.fps()));
+    assert!(config.validate().iter().all(|w| !w.contains("Intra-refresh")));
+    assert_eq!(CaptureConfig::monitor("test").resolved_intra_refresh_period(), None);
+
+    // Fine for streams, but recordings lose their seek points
+    let recording = config.clone().with_record_path("/tmp/out.mkv");
+    assert!(recording.validate().iter().any(|w| w.contains("streaming")));
+
+    assert!(config.with_intra_refresh(Some(0)).validate_strict().is_err());
+}
@@ -133,6 +133,34 @@ fn test_av1_encoder_available() {
     }
 }
 
+#[test]
+#[ignore = "Requires NVIDIA GPU with NVENC"]
+fn test_forced_keyframe_flag() {
+    use nitrogen_core::config::{CaptureConfig, Preset};
+    use nitrogen_core::encode::NvencEncoder;
+
+    let config = CaptureConfig::monitor("test")
+        .with_codec(Codec::H264)
+        .with_preset(Preset::P720_30);
+    let mut encoder = NvencEncoder::new(&config).expect("Should create encoder");
+    let mut rx = encoder.subscribe();
+
+    // Encode enough frames to get past the initial IDR
+    for _ in 0..10 {
+        let frame = mocks::create_gradient_frame(1280, 720);
+        encoder.encode(&frame).expect("Should encode");
+    }
+    while rx.try_recv().is_ok() {}
+
+    encoder.request_keyframe();
+    let frame = mocks::create_gradient_frame(1280, 720);
+    encoder.encode(&frame).expect("Should encode");
+    encoder.flush().expect("Should flush");
+
+    let first = rx.try_recv().expect("Should produce a packet");
+    assert!(first.keyframe, "Forced frame should be a keyframe");
+}
+
 #[test]
 fn test_test_frame_sizes() {
     // Test common Discord streaming resolutions
@@ -177,3 +205,25 @@ fn test_gradient_frame_variety() {
         assert_ne!(top_left, bottom_left, "Gradient should vary vertically");
     }
 }
+
+#[test]
+#[cfg(feature = "mock-encoder")]
+fn test_mock_encoder_selected() {
+    use nitrogen_core::config::{CaptureConfig, Preset};
+
+    let config = CaptureConfig::monitor("test")
+        .with_codec(Codec::H264)
+        .with_preset(Preset::P720_30);
+    assert!(encode::mock_encoder_requested());
+
+    let mut encoder = encode::create_encoder(&config).expect("Should create mock encoder");
+    let mut rx = encoder.subscribe();
+    assert_eq!(encoder.output_size(), (1280, 720));
+
+    let frame = mocks::create_gradient_frame(1280, 720);
+    encoder.encode(&frame).expect("Should encode");
+    encoder.flush().expect("Should flush");
+
+    let first = rx.try_recv().expect("Should produce a packet");
+    assert!(first.keyframe, "First packet should be a keyframe");
+}
@@ -1,6 +1,14 @@
 //! Integration tests for IPC protocol
 
-use nitrogen_core::ipc::{IpcMessage, IpcResponse, PipelineStatistics, PipelineStatus};
+use std::time::{Duration, Instant};
+
+use nitrogen_core::error::NitrogenError;
+use nitrogen_core::ipc::{
+    IpcClient, IpcMessage, IpcResponse, PipelineStatistics, PipelineStatus, IPC_PROTOCOL_VERSION,
+};
+use nitrogen_core::{CaptureSource, StreamProtocol, StreamStats};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixListener;
 
 #[test]
 fn test_message_ping_serialization() {
@@ -42,6 +50,60 @@ fn test_message_force_stop_serialization() {
     assert!(matches!(parsed, IpcMessage::ForceStop));
 }
 
+#[test]
+fn test_message_force_keyframe_serialization() {
+    let msg = IpcMessage::ForceKeyframe;
+    let bytes = msg.to_bytes();
+    let parsed = IpcMessage::from_bytes(&bytes[..bytes.len() - 1]).expect("Should parse");
+    assert!(matches!(parsed, IpcMessage::ForceKeyframe));
+}
+
+#[test]
+fn test_message_set_source_serialization() {
+    let msg = IpcMessage::SetSource {
+        source: CaptureSource::monitor("DP-1"),
+    };
+    let bytes = msg.to_bytes();
+    let parsed = IpcMessage::from_bytes(&bytes[..bytes.len() - 1]).expect("Should parse");
+    match parsed {
+        IpcMessage::SetSource { source } => assert_eq!(source, CaptureSource::monitor("DP-1")),
+        other => panic!("Unexpected message: {:?}", other),
+    }
+}
+
+#[test]
+fn test_message_set_sync_offset_serialization() {
+    let msg = IpcMessage::SetSyncOffset { offset_ms: -80 };
+    let bytes = msg.to_bytes();
+    let parsed = IpcMessage::from_bytes(&bytes[..bytes.len() - 1]).expect("Should parse");
+    assert!(matches!(parsed, IpcMessage::SetSyncOffset { offset_ms: -80 }));
+}
+
+#[test]
+fn test_message_set_camera_paused_serialization() {
+    let msg = IpcMessage::SetCameraPaused { paused: true };
+    let bytes = msg.to_bytes();
+    let parsed = IpcMessage::from_bytes(&bytes[..bytes.len() - 1]).expect("Should parse");
+    assert!(matches!(parsed, IpcMessage::SetCameraPaused { paused: true }));
+}
+
+#[test]
+fn test_response_source_changed_serialization() {
+    let resp = IpcResponse::SourceChanged {
+        width: 2560,
+        height: 1440,
+    };
+    let bytes = resp.to_bytes();
+    let parsed = IpcResponse::from_bytes(&bytes[..bytes.len() - 1]).expect("Should parse");
+    assert!(matches!(
+        parsed,
+        IpcResponse::SourceChanged {
+            width: 2560,
+            height: 1440
+        }
+    ));
+}
+
 #[test]
 fn test_response_ok_serialization() {
     let resp = IpcResponse::Ok;
@@ -88,6 +150,9 @@ fn test_response_status_serialization() {
         camera_name: Some("Test Camera".to_string()),
         pid: 12345,
         uptime_seconds: 123.45,
+        remaining_seconds: Some(30.0),
+        stream: None,
+        outputs: vec!["Camera: Test Camera".to_string()],
     };
     let resp = IpcResponse::Status(status);
     let bytes = resp.to_bytes();
@@ -100,7 +165,9 @@ fn test_response_status_serialization() {
             assert_eq!(s.resolution, Some((1920, 1080)));
             assert_eq!(s.fps, Some(60));
             assert_eq!(s.camera_name, Some("Test Camera".to_string()));
+            assert_eq!(s.remaining_seconds, Some(30.0));
             assert_eq!(s.pid, 12345);
+            assert_eq!(s.outputs, vec!["Camera: Test Camera".to_string()]);
             assert!((s.uptime_seconds - 123.45).abs() < 0.001);
         }
         _ => panic!("Expected Status response"),
@@ -119,6 +186,10 @@ fn test_response_stats_serialization() {
         resolution: (1920, 1080),
         codec: "H.264".to_string(),
         bitrate: 6000,
+        capture_latency_ms: 1.5,
+        encode_latency_ms: 3.25,
+        total_latency_ms: 6.0,
+        gpu: Some(0),
     };
     let resp = IpcResponse::Stats(stats);
     let bytes = resp.to_bytes();
@@ -134,6 +205,8 @@ fn test_response_stats_serialization() {
             assert_eq!(s.resolution, (1920, 1080));
             assert_eq!(s.codec, "H.264");
             assert_eq!(s.bitrate, 6000);
+            assert!((s.encode_latency_ms - 3.25).abs() < 0.001);
+            assert_eq!(s.gpu, Some(0));
         }
         _ => panic!("Expected Stats response"),
     }
@@ -178,3 +251,158 @@ fn test_bytes_have_newline_terminator() {
     let bytes = resp.to_bytes();
     assert_eq!(bytes.last(), Some(&b'\n'));
 }
+
+#[test]
+fn test_status_without_remaining_seconds() {
+    // Daemons without --duration support omit the field
+    let json = r#"{"type":"Status","running":true,"state":"Running","source":null,"resolution":null,"fps":null,"camera_name":null,"pid":1,"uptime_seconds":1.0}"#;
+    match IpcResponse::from_bytes(json.as_bytes()).expect("Should parse") {
+        IpcResponse::Status(s) => {
+            assert_eq!(s.remaining_seconds, None);
+            assert!(s.outputs.is_empty());
+        }
+        _ => panic!("Expected Status response"),
+    }
+}
+
+#[test]
+fn test_status_with_stream_stats() {
+    let mut stream = StreamStats::new(StreamProtocol::Srt);
+    stream.bytes_sent = 1_000_000;
+    stream.send_rate_kbps = 6000.0;
+    let status = PipelineStatus {
+        running: true,
+        state: "Running".to_string(),
+        source: None,
+        resolution: None,
+        fps: None,
+        camera_name: None,
+        pid: 1,
+        uptime_seconds: 1.0,
+        remaining_seconds: None,
+        stream: Some(stream.clone()),
+        outputs: Vec::new(),
+    };
+
+    let bytes = IpcResponse::Status(status).to_bytes();
+    match IpcResponse::from_bytes(&bytes[..bytes.len() - 1]).expect("Should parse") {
+        IpcResponse::Status(s) => assert_eq!(s.stream, Some(stream)),
+        _ => panic!("Expected Status response"),
+    }
+}
+
+#[test]
+fn test_message_includes_protocol_version() {
+    let bytes = IpcMessage::Ping.to_bytes();
+    let json_str = std::str::from_utf8(&bytes[..bytes.len() - 1]).expect("Should be valid UTF-8");
+    assert!(json_str.contains(&format!("\"version\":{}", IPC_PROTOCOL_VERSION)));
+}
+
+#[test]
+fn test_version_mismatch_serialization() {
+    let resp = IpcResponse::VersionMismatch {
+        client_version: 0,
+        server_version: IPC_PROTOCOL_VERSION,
+    };
+    let bytes = resp.to_bytes();
+    let parsed = IpcResponse::from_bytes(&bytes[..bytes.len() - 1]).expect("Should parse");
+    match parsed {
+        IpcResponse::VersionMismatch {
+            client_version,
+            server_version,
+        } => {
+            assert_eq!(client_version, 0);
+            assert_eq!(server_version, IPC_PROTOCOL_VERSION);
+        }
+        _ => panic!("Expected VersionMismatch response"),
+    }
+}
+
+#[tokio::test]
+async fn test_client_missing_socket() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("nitrogen.sock");
+
+    let result = IpcClient::connect_to(&path, Duration::from_millis(200)).await;
+    assert!(matches!(result, Err(NitrogenError::NoActiveSession)));
+}
+
+#[tokio::test]
+async fn test_client_stale_socket_refused() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("nitrogen.sock");
+
+    // Binding leaves the socket file behind after the listener is dropped
+    drop(UnixListener::bind(&path).expect("bind"));
+    assert!(path.exists());
+
+    let result = IpcClient::connect_to(&path, Duration::from_millis(200)).await;
+    assert!(matches!(result, Err(NitrogenError::DaemonUnreachable(_))));
+}
+
+#[tokio::test]
+async fn test_client_times_out_on_unresponsive_daemon() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("nitrogen.sock");
+    let listener = UnixListener::bind(&path).expect("bind");
+
+    // Accept the connection and read from it, but never answer
+    let wedged = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.expect("accept");
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        let _ = reader.read_line(&mut line).await;
+        tokio::time::sleep(Duration::from_secs(30)).await;
+    });
+
+    let timeout = Duration::from_millis(200);
+    let mut client = IpcClient::connect_to(&path, timeout)
+        .await
+        .expect("connect should succeed");
+    assert_eq!(client.timeout(), timeout);
+
+    let started = Instant::now();
+    let result = client.ping().await;
+    let elapsed = started.elapsed();
+
+    assert!(matches!(result, Err(NitrogenError::IpcTimeout(_))));
+    assert!(elapsed >= timeout);
+    assert!(elapsed < Duration::from_secs(5), "ping hung for {:?}", elapsed);
+
+    wedged.abort();
+}
+
+#[tokio::test]
+async fn test_client_reports_protocol_mismatch() {
+    let dir = tempfile::tempdir().expect("tempdir");
+    let path = dir.path().join("nitrogen.sock");
+    let listener = UnixListener::bind(&path).expect("bind");
+
+    // A daemon from a future release rejecting this client
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.expect("accept");
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        let mut line = String::new();
+        reader.read_line(&mut line).await.expect("read");
+        let version = IpcMessage::peek_version(line.trim().as_bytes()).expect("versioned");
+        let resp = IpcResponse::VersionMismatch {
+            client_version: version,
+            server_version: IPC_PROTOCOL_VERSION + 1,
+        };
+        writer.write_all(&resp.to_bytes()).await.expect("write");
+    });
+
+    let mut client = IpcClient::connect_to(&path, Duration::from_secs(2))
+        .await
+        .expect("connect should succeed");
+    match client.status().await {
+        Err(NitrogenError::IpcProtocolMismatch { client, daemon }) => {
+            assert_eq!(client, IPC_PROTOCOL_VERSION);
+            assert_eq!(daemon, IPC_PROTOCOL_VERSION + 1);
+        }
+        other => panic!("Expected protocol mismatch, got {:?}", other.map(|_| ())),
+    }
+
+    server.await.expect("server task");
+}
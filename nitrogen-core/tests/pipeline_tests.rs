@@ -252,3 +252,59 @@ fn test_portal_source_listing() {
     // On Wayland, we get placeholder sources
     assert!(!sources.is_empty(), "Should return some sources");
 }
+
+#[tokio::test]
+async fn test_frame_watchdog_fires_when_source_stops() {
+    use nitrogen_core::capture::{FrameWatchdog, WatchdogStatus};
+    use std::time::Duration;
+
+    let source = mocks::MockFrameSource::new();
+    let mut rx = source.subscribe();
+    let mut watchdog = FrameWatchdog::new(Duration::from_millis(50), 2);
+    watchdog.arm();
+
+    // While frames keep arriving the watchdog stays quiet
+    for _ in 0..3 {
+        source
+            .send_frame(mocks::create_test_frame(16, 16, [0, 0, 0, 255]))
+            .expect("Should send frame");
+        rx.recv().await.expect("Should receive frame");
+        assert!(!watchdog.frame_received());
+        assert_eq!(watchdog.check(), WatchdogStatus::Healthy);
+    }
+
+    // Source stops sending: the receive times out and the watchdog fires
+    let recv = tokio::time::timeout(Duration::from_millis(80), rx.recv()).await;
+    assert!(recv.is_err(), "Mock source should be silent");
+    match watchdog.check() {
+        WatchdogStatus::Stalled { elapsed, attempt } => {
+            assert!(elapsed >= Duration::from_millis(50));
+            assert_eq!(attempt, 1);
+        }
+        other => panic!("Expected stall, got {:?}", other),
+    }
+
+    // Each attempt gets a fresh timeout before firing again
+    assert_eq!(watchdog.check(), WatchdogStatus::Healthy);
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(matches!(
+        watchdog.check(),
+        WatchdogStatus::Stalled { attempt: 2, .. }
+    ));
+
+    // Out of attempts
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(
+        watchdog.check(),
+        WatchdogStatus::Exhausted { attempts: 2 }
+    );
+
+    // A frame arriving clears the stall
+    source
+        .send_frame(mocks::create_test_frame(16, 16, [0, 0, 0, 255]))
+        .expect("Should send frame");
+    rx.recv().await.expect("Should receive frame");
+    assert!(watchdog.frame_received());
+    assert_eq!(watchdog.attempts(), 0);
+    assert_eq!(watchdog.check(), WatchdogStatus::Healthy);
+}
@@ -0,0 +1,113 @@
+//! Integration tests for the pluggable output sink API
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use nitrogen_core::encode::{EncodedAudioPacket, EncodedPacket};
+use nitrogen_core::output::{run_sink, FrameSink};
+use nitrogen_core::Result;
+use tokio::sync::broadcast;
+
+/// Custom sink that just counts packets
+#[derive(Default, Clone)]
+struct CountingSink {
+    video: Arc<AtomicU64>,
+    audio: Arc<AtomicU64>,
+    started: Arc<AtomicBool>,
+    stopped: Arc<AtomicBool>,
+}
+
+#[async_trait]
+impl FrameSink for CountingSink {
+    fn name(&self) -> &str {
+        "counter"
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        self.started.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn write_video(&mut self, _packet: &EncodedPacket) -> Result<()> {
+        self.video.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn write_audio(&mut self, _packet: &EncodedAudioPacket) -> Result<()> {
+        self.audio.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        self.stopped.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+fn video_packet(pts: i64) -> Arc<EncodedPacket> {
+    Arc::new(EncodedPacket {
+        data: vec![0, 0, 0, 1],
+        pts,
+        dts: pts,
+        keyframe: pts == 0,
+    })
+}
+
+fn audio_packet(pts: i64) -> Arc<EncodedAudioPacket> {
+    Arc::new(EncodedAudioPacket {
+        data: vec![0xFF; 8],
+        pts,
+        dts: pts,
+        duration: 960,
+    })
+}
+
+#[test]
+fn test_frame_sink_is_object_safe() {
+    let sinks: Vec<Box<dyn FrameSink>> = vec![Box::new(CountingSink::default())];
+    assert_eq!(sinks.len(), 1);
+    assert_eq!(sinks[0].name(), "counter");
+}
+
+#[tokio::test]
+async fn test_run_sink_counts_packets() {
+    let sink = CountingSink::default();
+    let (video_tx, video_rx) = broadcast::channel(16);
+    let (audio_tx, audio_rx) = broadcast::channel(16);
+
+    let handle = tokio::spawn(run_sink(Box::new(sink.clone()), video_rx, Some(audio_rx)));
+
+    for i in 0..5 {
+        video_tx.send(video_packet(i)).expect("Should send video");
+    }
+    for i in 0..3 {
+        audio_tx.send(audio_packet(i * 960)).expect("Should send audio");
+    }
+
+    // Closing both channels ends the sink task
+    drop(video_tx);
+    drop(audio_tx);
+
+    let written = handle.await.expect("Task should not panic").expect("Sink should succeed");
+    assert_eq!(written, 8);
+    assert_eq!(sink.video.load(Ordering::SeqCst), 5);
+    assert_eq!(sink.audio.load(Ordering::SeqCst), 3);
+    assert!(sink.started.load(Ordering::SeqCst));
+    assert!(sink.stopped.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn test_run_sink_video_only() {
+    let sink = CountingSink::default();
+    let (video_tx, video_rx) = broadcast::channel(16);
+
+    let handle = tokio::spawn(run_sink(Box::new(sink.clone()), video_rx, None));
+
+    video_tx.send(video_packet(0)).expect("Should send video");
+    drop(video_tx);
+
+    let written = handle.await.expect("Task should not panic").expect("Sink should succeed");
+    assert_eq!(written, 1);
+    assert_eq!(sink.audio.load(Ordering::SeqCst), 0);
+}